@@ -0,0 +1,111 @@
+//! Compares loading lots of keys and points into tries and quadtrees one by one against loading
+//! them through a TrieWriter and a QuadtreeWriter.
+//!
+//! Run with `cargo bench --bench bulk_ingest`.
+
+use std::time::{Duration, Instant};
+
+use rs_arboretum::spatial::quadtree::point_quadtree::*;
+use rs_arboretum::spatial::quadtree::prelude::*;
+use rs_arboretum::spatial::quadtree::writer::*;
+use rs_arboretum::trie::grammar::*;
+use rs_arboretum::trie::trie::*;
+use rs_arboretum::trie::writer::*;
+
+const KEYS: usize = 200_000;
+const POINTS: usize = 500_000;
+
+/// Returns a generator of random numbers below 'n', from a fixed seed.
+fn rng(seed: u32) -> impl FnMut(usize) -> usize {
+    let mut state = seed;
+    move |n| {
+        state = state.wrapping_mul(1103515245).wrapping_add(12345);
+        (state >> 8) as usize % n
+    }
+}
+
+fn report(name: &str, one_by_one: Duration, batched: Duration) {
+    println!(
+        "{:<12} {:>14.1} {:>14.1} {:>10.1}x",
+        name,
+        one_by_one.as_secs_f64() * 1000.0,
+        batched.as_secs_f64() * 1000.0,
+        one_by_one.as_secs_f64() / batched.as_secs_f64()
+    );
+}
+
+/// Loads the keys into a trie one by one and through a TrieWriter, reporting both.
+fn ingest_keys(name: &str, keys: &[String]) {
+    let start = Instant::now();
+    let mut trie = Trie::<usize>::new(Grammar::default());
+    for (i, key) in keys.iter().enumerate() {
+        let _ = trie.insert_or_update(key, i);
+    }
+    let one_by_one = start.elapsed();
+
+    let start = Instant::now();
+    let mut batched = Trie::<usize>::new(Grammar::default());
+    let mut writer = TrieWriter::new(&mut batched);
+    for (i, key) in keys.iter().enumerate() {
+        let _ = writer.insert(key, i);
+    }
+    drop(writer);
+    let elapsed = start.elapsed();
+
+    assert_eq!(trie.len(), batched.len());
+    report(name, one_by_one, elapsed);
+}
+
+fn tries() {
+    let mut next = rng(7);
+    let keys: Vec<String> = (0..KEYS)
+        .map(|_| (0..6 + next(6)).map(|_| (b'a' + next(26) as u8) as char).collect())
+        .collect();
+    ingest_keys("trie", &keys);
+
+    // Keys which mostly share long prefixes, like paths or urls, where most of the nodes of a key
+    // already exist and the writer only looks them up once per batch.
+    let prefixes: Vec<String> = (0..100)
+        .map(|_| (0..24).map(|_| (b'a' + next(26) as u8) as char).collect())
+        .collect();
+    let keys: Vec<String> = (0..KEYS)
+        .map(|_| {
+            let suffix: String = (0..3).map(|_| (b'a' + next(26) as u8) as char).collect();
+            format!("{}{}", prefixes[next(prefixes.len())], suffix)
+        })
+        .collect();
+    ingest_keys("trie prefix", &keys);
+}
+
+fn quadtrees() {
+    let bbox = BBox2D { min: Vec2::from([0.0, 0.0]), max: Vec2::from([1000.0, 1000.0]) };
+    let mut next = rng(11);
+    let points: Vec<Vec2> = (0..POINTS)
+        .map(|_| Vec2::from([next(1_000_000) as f32 / 1000.0, next(1_000_000) as f32 / 1000.0]))
+        .collect();
+
+    let start = Instant::now();
+    let mut tree = PointQuadtree::<usize>::with_config(&bbox, QuadtreeConfig::default());
+    for (i, p) in points.iter().enumerate() {
+        let _ = tree.insert(p, i);
+    }
+    let one_by_one = start.elapsed();
+
+    let start = Instant::now();
+    let mut batched = PointQuadtree::<usize>::with_config(&bbox, QuadtreeConfig::default());
+    let mut writer = QuadtreeWriter::new(&mut batched);
+    for (i, p) in points.iter().enumerate() {
+        let _ = writer.insert(p, i);
+    }
+    drop(writer);
+    let elapsed = start.elapsed();
+
+    assert_eq!(tree.len(), batched.len());
+    report("quadtree", one_by_one, elapsed);
+}
+
+fn main() {
+    println!("{:<12} {:>14} {:>14} {:>11}", "structure", "one by one ms", "batched ms", "speedup");
+    tries();
+    quadtrees();
+}
@@ -0,0 +1,67 @@
+//! Compares the memory and lookup time of tries over grammars of very different sizes, which is
+//! what the adaptive layout of a node's children trades off against each other.
+//!
+//! Run with `cargo bench --bench trie_children`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use rs_arboretum::trie::grammar::*;
+use rs_arboretum::trie::trie::*;
+
+const KEYS: usize = 20_000;
+const LOOKUPS: usize = 200_000;
+
+/// Returns 'n' random keys made of the given chars, from a fixed seed.
+fn keys(chars: &[char], n: usize) -> Vec<String> {
+    let mut state: u32 = 7;
+    let mut next = move || {
+        state = state.wrapping_mul(1103515245).wrapping_add(12345);
+        (state >> 16) as usize
+    };
+
+    (0..n)
+        .map(|_| (0..4 + next() % 8).map(|_| chars[next() % chars.len()]).collect())
+        .collect()
+}
+
+fn run(name: &str, grammar: Grammar, chars: &[char]) {
+    let keys = keys(chars, KEYS);
+
+    let mut trie = Trie::<usize>::new(grammar);
+    for (i, key) in keys.iter().enumerate() {
+        let _ = trie.insert(key, i);
+    }
+
+    let stats = trie.memory_stats();
+
+    let start = Instant::now();
+    for i in 0..LOOKUPS {
+        black_box(trie.find(&keys[i % keys.len()]));
+    }
+    let lookup_ns = start.elapsed().as_nanos() as f64 / LOOKUPS as f64;
+
+    println!(
+        "{:<28} {:>10} {:>12} {:>14.1} {:>12.1}",
+        name, stats.nodes, stats.approx_bytes, stats.approx_bytes as f64 / trie.len() as f64, lookup_ns
+    );
+}
+
+fn main() {
+    println!("{:<28} {:>10} {:>12} {:>14} {:>12}", "grammar", "nodes", "bytes", "bytes/key", "ns/lookup");
+
+    // Few chars, so nodes near the root fill up and switch to a slot per char.
+    let binary = ['0', '1'];
+    run("2 chars", Grammar::from("01", Case::Sensitive), &binary);
+
+    let letters: Vec<char> = ('a'..='z').collect();
+    run("26 chars", Grammar::default(), &letters);
+
+    // The same keys, but in a grammar of every Unicode letter, where a slot per char would take
+    // megabytes per node.
+    run("unicode letters, 26 used", Grammar::builder(Case::Sensitive).unicode_letters().build(), &letters);
+
+    // Keys spread over thousands of chars, so nodes near the root end up with maps.
+    let cjk: Vec<char> = ('\u{4E00}'..='\u{5DFF}').collect();
+    run("unicode letters, 4096 used", Grammar::builder(Case::Sensitive).unicode_letters().build(), &cjk);
+}
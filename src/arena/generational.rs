@@ -0,0 +1,552 @@
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::arena::persistent::PersistentVec;
+use crate::arena::prelude::*;
+use crate::sync::RwLock;
+
+/// An Id which is only valid for as long as the node it was issued for is alive.
+///
+/// The index refers to a slot in the arena, and the generation records how many times that slot
+/// had been recycled when the Id was issued. Once the node is deleted and the slot is reused, the
+/// old Id no longer matches the slot's generation and is considered stale.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct GenerationalId {
+    pub index: usize,
+    pub generation: usize
+}
+
+impl HasId for GenerationalId {
+    type Id = GenerationalId;
+    fn get_id(&self) -> GenerationalId { *self }
+}
+
+/// Copies a node which is shared with a snapshot, before it is changed.
+type CopyNode<T> = fn(&T) -> T;
+
+#[derive(Debug)]
+struct Slot<T> {
+    generation: usize,
+    value: Option<SharedRef<T>>,
+
+    /// True if the slot has been handed out by 'get_new_id' and not deleted yet.
+    reserved: bool,
+
+    /// The epoch of the arena when the node was stored. If a snapshot has been taken since, the
+    /// node is shared with it, and gets copied before it is changed so that the change doesn't
+    /// leak into the snapshot.
+    epoch: usize,
+
+    /// The highest generation which may have been handed out for the slot. This is only ahead of
+    /// 'generation' once 'restore' brings back an older node, whose slot then has to skip past it
+    /// when the node is deleted.
+    max_issued: usize
+}
+
+impl<T> Clone for Slot<T> {
+    fn clone(&self) -> Self {
+        Self {
+            generation: self.generation,
+            value: self.value.clone(),
+            reserved: self.reserved,
+            epoch: self.epoch,
+            max_issued: self.max_issued
+        }
+    }
+}
+
+impl<T> Slot<T> {
+    /// Empties the slot and moves it to a generation which no Id has been handed out for yet.
+    fn release(&mut self) {
+        self.value = None;
+        self.reserved = false;
+        self.generation = self.generation.max(self.max_issued) + 1;
+    }
+}
+
+#[derive(Debug)]
+struct Storage<T> {
+    /// The slots are shared with the snapshots taken of the arena, and only copied where they
+    /// change afterwards.
+    slots: PersistentVec<Slot<T>>,
+    free: Vec<usize>,
+
+    /// The generation of newly allocated slots. Once slots at the end have been released by
+    /// 'shrink_to_fit', slots allocated in their place must start past their generations so that
+    /// stale Ids for the released slots stay stale.
+    min_generation: usize
+}
+
+impl<T> Storage<T> {
+    fn new() -> Self {
+        Self { slots: PersistentVec::new(), free: vec![], min_generation: 0 }
+    }
+
+    /// Returns a copy of the storage which shares its slots, leaving out the free slots which
+    /// 'restore' finds again.
+    fn share(&self) -> Self {
+        Self { slots: self.slots.clone(), free: vec![], min_generation: self.min_generation }
+    }
+}
+
+/// A memory arena which recycles the slots of deleted nodes, using generations to guarantee that
+/// stale Ids never alias the nodes that replace them.
+///
+/// The slots can be spread over several shards, each behind its own lock, so that threads looking
+/// up different nodes don't all contend for a single lock. The shard of a node is encoded in its
+/// index, which with a single shard is just the position of its slot.
+pub struct GenerationalArena<T> {
+    shards: Vec<RwLock<Storage<T>>>,
+
+    /// The shard which the next new Id is taken from.
+    next_shard: usize,
+
+    len: usize,
+    high_water_mark: usize,
+
+    /// The number of snapshots taken so far, see 'Slot::epoch'.
+    epoch: AtomicUsize,
+
+    /// Copies a node which is shared with a snapshot. This is only set once a snapshot has been
+    /// taken, which requires the nodes to be Clone.
+    copy_node: RwLock<Option<CopyNode<T>>>
+}
+
+/// The state of a GenerationalArena at some point in time, which it can be restored to.
+///
+/// The snapshot shares its slots and nodes with the arena rather than copying them. The arena only
+/// copies a node once it is looked up to be changed, along with the few slots on the way to it, so
+/// taking a snapshot is O(number of shards) and every following change only pays for the nodes it
+/// touches.
+pub struct ArenaSnapshot<T> {
+    shards: Vec<Storage<T>>,
+    next_shard: usize,
+    len: usize,
+    copy_node: CopyNode<T>
+}
+
+impl<T> Clone for ArenaSnapshot<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shards: self.shards.iter().map(Storage::share).collect(),
+            next_shard: self.next_shard,
+            len: self.len,
+            copy_node: self.copy_node
+        }
+    }
+}
+
+impl<T> ArenaSnapshot<T> {
+    /// Looks up the node for the given Id as it was when the snapshot was taken. The arena copies a
+    /// node before changing it, so the nodes of a snapshot never change and their locks are only
+    /// ever shared with other readers.
+    pub fn get_node(&self, id: &GenerationalId) -> Option<SharedRef<T>> {
+        let n = self.shards.len();
+        self.shards[id.index % n].slots.get(id.index / n)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.value.clone())
+    }
+}
+
+impl<T: HasId<Id = GenerationalId>> GenerationalArena<T> {
+    pub fn new() -> Self {
+        Self::with_shards(1)
+    }
+
+    /// Returns a new arena whose slots are spread over the given number of shards.
+    pub fn with_shards(shards: usize) -> Self {
+        assert!(shards > 0, "arena must have at least 1 shard");
+
+        Self {
+            shards: (0..shards).map(|_| RwLock::new(Storage::new())).collect(),
+            next_shard: 0,
+            len: 0,
+            high_water_mark: 0,
+            epoch: AtomicUsize::new(0),
+            copy_node: RwLock::new(None)
+        }
+    }
+
+    /// Returns the number of shards the slots are spread over.
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Splits an index into the shard holding the slot and the position of the slot in the shard.
+    fn locate(&self, index: usize) -> (&RwLock<Storage<T>>, usize) {
+        let n = self.shards.len();
+        (&self.shards[index % n], index / n)
+    }
+
+    fn index_of(&self, shard: usize, slot: usize) -> usize {
+        slot * self.shards.len() + shard
+    }
+
+    /// Stores the node in the slot which was reserved for it, returning a reference to it.
+    fn fill_slot(storage: &mut Storage<T>, slot: usize, node: T, epoch: usize) -> Result<SharedRef<T>, ArenaError> {
+        let generation = node.get_id().generation;
+
+        match storage.slots.get_mut(slot) {
+            Some(slot) if slot.generation == generation && slot.reserved => {
+                if slot.value.is_some() {
+                    return Err(ArenaError::NodeExists);
+                }
+
+                let node = SharedRef::new(RwLock::new(node));
+                slot.value = Some(Arc::clone(&node));
+                slot.epoch = epoch;
+                Ok(node)
+            }
+            _ => Err(ArenaError::InvalidId)
+        }
+    }
+
+    fn _get_node(&self, id: &GenerationalId) -> Option<SharedRef<T>> {
+        let (shard, slot) = self.locate(id.index);
+
+        let storage = shard.read().unwrap();
+        storage.slots.get(slot)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.value.clone())
+    }
+
+    /// Looks up the node for the given Id in order to change it. A node which is shared with a
+    /// snapshot is copied first, so that the change doesn't leak into the snapshot, which is why
+    /// nodes which are only read should be looked up with 'get_node' instead.
+    ///
+    /// The node must be done changing before the next snapshot is taken.
+    pub fn get_node_mut(&self, id: &GenerationalId) -> Option<SharedRef<T>> {
+        let (shard, slot) = self.locate(id.index);
+
+        // --
+        // Most nodes aren't shared, so the write lock is only taken to copy a node which is. The
+        // epoch is read under the lock, since snapshots hold every lock while moving it on.
+        {
+            let storage = shard.read().unwrap();
+            let slot = storage.slots.get(slot).filter(|slot| slot.generation == id.generation)?;
+            if slot.epoch == self.epoch.load(Ordering::SeqCst) {
+                return slot.value.clone();
+            }
+        }
+
+        let mut storage = shard.write().unwrap();
+        let epoch = self.epoch.load(Ordering::SeqCst);
+        let slot = storage.slots.get_mut(slot).filter(|slot| slot.generation == id.generation)?;
+
+        if slot.epoch != epoch {
+            if let Some(node) = &slot.value {
+                let copy_node = self.copy_node.read().unwrap().expect("nodes are only shared once a snapshot was taken");
+                let copy = copy_node(&node.read().unwrap());
+                slot.value = Some(SharedRef::new(RwLock::new(copy)));
+            }
+            slot.epoch = epoch;
+        }
+
+        slot.value.clone()
+    }
+
+    /// Restores the arena to the given snapshot, which must have been taken from this arena. The
+    /// snapshot stays valid, so the arena can be restored to it again later.
+    ///
+    /// Unlike taking the snapshot, this visits every slot.
+    pub fn restore(&mut self, snapshot: &ArenaSnapshot<T>) {
+        assert_eq!(self.shards.len(), snapshot.shards.len(), "snapshot was taken from a different arena");
+
+        for (shard, saved) in self.shards.iter_mut().zip(&snapshot.shards) {
+            let current = shard.get_mut().unwrap();
+            let mut storage = saved.share();
+
+            // --
+            // Ids issued since the snapshot must stay stale, so every slot skips past the
+            // generations handed out for it in the meantime: free slots right away, and slots of
+            // restored nodes once those are deleted. Slots which were released by 'compact' or
+            // 'shrink_to_fit' since only handed out generations below the current minimum.
+            for idx in 0..storage.slots.len() {
+                let issued = current.slots.get(idx)
+                    .map_or(current.min_generation, |current| current.generation.max(current.max_issued));
+
+                let slot = storage.slots.get_mut(idx).unwrap();
+                slot.max_issued = slot.max_issued.max(issued);
+                if slot.value.is_none() {
+                    slot.generation = slot.generation.max(issued + 1);
+                    if !slot.reserved {
+                        storage.free.push(idx);
+                    }
+                }
+            }
+
+            storage.min_generation = current.slots.iter()
+                .skip(storage.slots.len())
+                .map(|slot| slot.generation.max(slot.max_issued) + 1)
+                .fold(storage.min_generation.max(current.min_generation), usize::max);
+
+            *current = storage;
+        }
+
+        self.next_shard = snapshot.next_shard;
+        self.len = snapshot.len;
+        self.high_water_mark = self.high_water_mark.max(self.len);
+        self.copy_node.get_mut().unwrap().get_or_insert(snapshot.copy_node);
+    }
+
+    /// Moves every node into the lowest free slot of its shard and releases the slots left over,
+    /// calling 'remap' with the old and the new Id of every node. Unlike 'shrink_to_fit', this also
+    /// reclaims the slots of deleted nodes in the middle of the arena.
+    ///
+    /// The arena can't update the Ids stored within the nodes, so the owner of the arena has to do
+    /// that from the Ids passed to 'remap'. All old Ids become stale, including Ids handed out by
+    /// 'get_new_id' whose nodes haven't been added yet.
+    pub fn compact<F: FnMut(GenerationalId, GenerationalId)>(&mut self, mut remap: F) {
+        for (shard_idx, shard) in self.shards.iter().enumerate() {
+            let mut storage = shard.write().unwrap();
+
+            // Every Id issued for the shard so far has a lower generation than the moved nodes, so
+            // none of them can alias a moved node.
+            let generation = storage.slots.iter()
+                .map(|slot| slot.generation.max(slot.max_issued) + 1)
+                .fold(storage.min_generation, usize::max);
+
+            let mut slots = PersistentVec::new();
+            for (slot_idx, slot) in storage.slots.iter().enumerate() {
+                if slot.value.is_none() {
+                    continue;
+                }
+
+                let old = GenerationalId { index: self.index_of(shard_idx, slot_idx), generation: slot.generation };
+                let new = GenerationalId { index: self.index_of(shard_idx, slots.len()), generation };
+                slots.push(Slot { generation, max_issued: generation, ..slot.clone() });
+                remap(old, new);
+            }
+
+            slots.shrink_to_fit();
+            *storage = Storage { slots, free: vec![], min_generation: generation };
+        }
+    }
+}
+
+impl<T: HasId<Id = GenerationalId> + Clone> GenerationalArena<T> {
+
+    /// Takes a snapshot of the arena which it can later be restored to, see 'ArenaSnapshot'.
+    ///
+    /// Nodes looked up with 'get_node_mut' must be done changing by the time the snapshot is taken.
+    pub fn snapshot(&self) -> ArenaSnapshot<T> {
+        self.copy_node.write().unwrap().get_or_insert(T::clone);
+
+        // Every shard is locked while the epoch moves on, which marks every node stored so far as
+        // shared with the snapshot.
+        let shards: Vec<_> = self.shards.iter().map(|shard| shard.write().unwrap()).collect();
+        self.epoch.fetch_add(1, Ordering::SeqCst);
+
+        ArenaSnapshot {
+            shards: shards.iter().map(|storage| storage.share()).collect(),
+            next_shard: self.next_shard,
+            len: self.len,
+            copy_node: T::clone
+        }
+    }
+}
+
+impl<T: HasId<Id = GenerationalId>> Default for GenerationalArena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: HasId<Id = GenerationalId>> IsMemoryArena for GenerationalArena<T> {
+    type Id = GenerationalId;
+    type Node = T;
+
+    fn get_node(&self, id: &Self::Id) -> Option<SharedRef<Self::Node>> {
+        self._get_node(id)
+    }
+
+    fn get_node_weak(&self, id: &Self::Id) -> Option<WeakRef<Self::Node>> {
+        self._get_node(id).as_ref().map(Arc::downgrade)
+    }
+
+    fn add_node(&mut self, node: Self::Node) -> Result<(), ArenaError> {
+        self.add_nodes(core::iter::once(node))
+    }
+
+    fn add_nodes<I: IntoIterator<Item = Self::Node>>(&mut self, nodes: I) -> Result<(), ArenaError> {
+        // The arena is borrowed mutably, so the shards can be accessed without locking them at all.
+        let n = self.shards.len();
+        let epoch = *self.epoch.get_mut();
+        let mut shards: Vec<&mut Storage<T>> = self.shards.iter_mut().map(|shard| shard.get_mut().unwrap()).collect();
+        let mut added = 0;
+
+        let result = nodes.into_iter().try_for_each(|node| {
+            let index = node.get_id().index;
+            Self::fill_slot(shards[index % n], index / n, node, epoch)?;
+            added += 1;
+            Ok(())
+        });
+
+        self.len += added;
+        self.high_water_mark = self.high_water_mark.max(self.len);
+        result
+    }
+
+    fn get_many(&self, ids: &[Self::Id]) -> Vec<Option<SharedRef<Self::Node>>> {
+        // Look up every node under a single read lock per shard.
+        let shards: Vec<_> = self.shards.iter().map(|shard| shard.read().unwrap()).collect();
+        let n = shards.len();
+
+        ids.iter()
+            .map(|id| {
+                shards[id.index % n].slots.get(id.index / n)
+                    .filter(|slot| slot.generation == id.generation)
+                    .and_then(|slot| slot.value.clone())
+            })
+            .collect()
+    }
+
+    /// The node is looked up with 'get_node_mut', since it is usually filled in right after.
+    fn get_or_insert_with<F: FnOnce() -> Self::Node>(
+        &mut self,
+        id: &Self::Id,
+        f: F
+    ) -> Result<SharedRef<Self::Node>, ArenaError> {
+        if let Some(node) = self.get_node_mut(id) {
+            return Ok(node);
+        }
+
+        let node = f();
+        if node.get_id() != *id {
+            return Err(ArenaError::InvalidId);
+        }
+
+        let n = self.shards.len();
+        let epoch = *self.epoch.get_mut();
+        let node = Self::fill_slot(self.shards[id.index % n].get_mut().unwrap(), id.index / n, node, epoch)?;
+
+        self.len += 1;
+        self.high_water_mark = self.high_water_mark.max(self.len);
+        Ok(node)
+    }
+
+    fn delete_node(&mut self, id: &Self::Id) -> Result<(), ArenaError> {
+        let (shard, slot_idx) = self.locate(id.index);
+        let mut storage = shard.write().unwrap();
+
+        match storage.slots.get_mut(slot_idx) {
+            Some(slot) if slot.generation == id.generation && slot.value.is_some() => slot.release(),
+            _ => return Err(ArenaError::NodeNotFound)
+        }
+
+        storage.free.push(slot_idx);
+        drop(storage);
+
+        self.len -= 1;
+
+        Ok(())
+    }
+
+    fn get_new_id(&mut self) -> Self::Id {
+        let shard_idx = self.next_shard;
+        self.next_shard = (self.next_shard + 1) % self.shards.len();
+
+        let epoch = *self.epoch.get_mut();
+        let storage = self.shards[shard_idx].get_mut().unwrap();
+
+        let (slot_idx, generation) = match storage.free.pop() {
+            Some(slot_idx) => {
+                let slot = storage.slots.get_mut(slot_idx).unwrap();
+                slot.reserved = true;
+                (slot_idx, slot.generation)
+            }
+            None => {
+                let generation = storage.min_generation;
+                storage.slots.push(Slot { generation, value: None, reserved: true, epoch, max_issued: generation });
+                (storage.slots.len() - 1, generation)
+            }
+        };
+
+        GenerationalId { index: self.index_of(shard_idx, slot_idx), generation }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        self.shards.iter().map(|shard| shard.read().unwrap().slots.capacity()).sum()
+    }
+
+    /// Nodes which are going to be changed have to be looked up again with 'get_node_mut', in case
+    /// they are shared with a snapshot.
+    fn iter(&self) -> impl Iterator<Item = (Self::Id, SharedRef<Self::Node>)> {
+        let mut nodes = vec![];
+
+        for (shard_idx, shard) in self.shards.iter().enumerate() {
+            let storage = shard.read().unwrap();
+            for (slot_idx, slot) in storage.slots.iter().enumerate() {
+                if let Some(node) = &slot.value {
+                    let id = GenerationalId { index: self.index_of(shard_idx, slot_idx), generation: slot.generation };
+                    nodes.push((id, Arc::clone(node)));
+                }
+            }
+        }
+
+        nodes.into_iter()
+    }
+
+    fn retain<F: FnMut(&Self::Node) -> bool>(&mut self, mut f: F) {
+        for shard in &mut self.shards {
+            let storage = shard.get_mut().unwrap();
+
+            for index in 0..storage.slots.len() {
+                let keep = match &storage.slots.get(index).unwrap().value {
+                    Some(node) => f(&node.read().unwrap()),
+                    None => true
+                };
+
+                if !keep {
+                    storage.slots.get_mut(index).unwrap().release();
+                    storage.free.push(index);
+                    self.len -= 1;
+                }
+            }
+        }
+    }
+
+    fn stats(&self) -> ArenaStats {
+        let mut approx_bytes = self.len * shared_node_bytes::<T>();
+
+        for shard in &self.shards {
+            let storage = shard.read().unwrap();
+            approx_bytes += storage.slots.capacity() * core::mem::size_of::<Slot<T>>();
+            approx_bytes += storage.free.capacity() * core::mem::size_of::<usize>();
+        }
+
+        ArenaStats {
+            nodes: self.len,
+            approx_bytes,
+            high_water_mark: self.high_water_mark
+        }
+    }
+
+    fn shrink_to_fit(&mut self) {
+        for shard in &mut self.shards {
+            let storage = shard.get_mut().unwrap();
+
+            // Slots in the middle are referred to by their position, so only the unused slots at
+            // the end can be released.
+            while let Some(slot) = storage.slots.last() {
+                if slot.value.is_some() || slot.reserved {
+                    break;
+                }
+
+                storage.min_generation = storage.min_generation.max(slot.generation);
+                storage.slots.pop();
+            }
+
+            let len = storage.slots.len();
+            storage.free.retain(|slot_idx| *slot_idx < len);
+            storage.slots.shrink_to_fit();
+            storage.free.shrink_to_fit();
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::marker::PhantomData;
+use core::num::NonZeroU32;
+
+/// The types an 'Arena' can use for its Ids, which are issued from a counter.
+///
+/// Narrower types make every link between nodes smaller, at the cost of limiting how many Ids the
+/// arena can issue: an Arena<T, u32> holds up to 2^32 nodes over its lifetime, and panics after
+/// that. 'NonZeroU32' leaves a niche, so an Option of it is as small as the Id itself.
+pub trait ArenaIndex: Copy + Eq + Hash + Send + Sync {
+    /// Returns the Id for the given value of the counter, or None if it doesn't fit.
+    fn from_counter(counter: usize) -> Option<Self>;
+
+    /// Returns the value of the counter the Id was issued for.
+    fn to_counter(self) -> usize;
+}
+
+macro_rules! impl_arena_index {
+    ($($t:ty),*) => {
+        $(
+            impl ArenaIndex for $t {
+                fn from_counter(counter: usize) -> Option<Self> {
+                    Self::try_from(counter).ok()
+                }
+
+                fn to_counter(self) -> usize {
+                    self as usize
+                }
+            }
+        )*
+    };
+}
+
+impl_arena_index!(usize, u64, u32, u16);
+
+impl ArenaIndex for NonZeroU32 {
+    fn from_counter(counter: usize) -> Option<Self> {
+        let value = u32::try_from(counter.checked_add(1)?).ok()?;
+        NonZeroU32::new(value)
+    }
+
+    fn to_counter(self) -> usize {
+        self.get() as usize - 1
+    }
+}
+
+/// An Id which is tagged with the type it belongs to, so the Ids of different trees can't be
+/// mixed up even if they share the same representation.
+///
+/// The tag only exists at compile time; a TypedId is exactly as large as its index.
+pub struct TypedId<Tag, I: ArenaIndex = u32> {
+    index: I,
+    _tag: PhantomData<fn() -> Tag>
+}
+
+impl<Tag, I: ArenaIndex> TypedId<Tag, I> {
+    pub fn new(index: I) -> Self {
+        Self { index, _tag: PhantomData }
+    }
+
+    pub fn index(&self) -> I {
+        self.index
+    }
+}
+
+// --
+// These are implemented by hand, since deriving them would require the tag to implement them too.
+
+impl<Tag, I: ArenaIndex> Clone for TypedId<Tag, I> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Tag, I: ArenaIndex> Copy for TypedId<Tag, I> {}
+
+impl<Tag, I: ArenaIndex> PartialEq for TypedId<Tag, I> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<Tag, I: ArenaIndex> Eq for TypedId<Tag, I> {}
+
+impl<Tag, I: ArenaIndex> Hash for TypedId<Tag, I> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+
+impl<Tag, I: ArenaIndex + fmt::Debug> fmt::Debug for TypedId<Tag, I> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TypedId({:?})", self.index)
+    }
+}
+
+impl<Tag, I: ArenaIndex> ArenaIndex for TypedId<Tag, I> {
+    fn from_counter(counter: usize) -> Option<Self> {
+        I::from_counter(counter).map(Self::new)
+    }
+
+    fn to_counter(self) -> usize {
+        self.index.to_counter()
+    }
+}
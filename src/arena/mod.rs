@@ -1,10 +1,50 @@
-use std::collections::HashMap;
-use std::fmt::Debug;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Arc, RwLock};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::collections::HashMap;
+use crate::sync::RwLock;
+
+pub mod generational;
+pub mod id;
+
+mod persistent;
+
+pub use generational::{ArenaSnapshot, GenerationalArena, GenerationalId};
+pub use id::{ArenaIndex, TypedId};
 
 pub mod prelude {
-    use std::sync::{Arc, RwLock, Weak};
+    use alloc::sync::{Arc, Weak};
+    use alloc::vec::Vec;
+    use core::error::Error;
+    use core::fmt;
+
+    use crate::sync::RwLock;
+
+    /// The ways in which an operation on a memory arena can fail.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum ArenaError {
+        /// A node with the same id is already stored in the arena.
+        NodeExists,
+
+        /// No node with the given id is stored in the arena.
+        NodeNotFound,
+
+        /// The id was not issued by the arena, or it refers to a node which has been deleted.
+        InvalidId
+    }
+
+    impl fmt::Display for ArenaError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                ArenaError::NodeExists => write!(f, "node already exists"),
+                ArenaError::NodeNotFound => write!(f, "node doesn't exist"),
+                ArenaError::InvalidId => write!(f, "id was not issued by this arena or is stale")
+            }
+        }
+    }
+
+    impl Error for ArenaError {}
 
     pub trait HasId: Sync + Send  {
         type Id;
@@ -19,6 +59,26 @@ pub mod prelude {
     pub type SharedRef<T> = Arc<RwLock<T>>;
     pub type WeakRef<T> = Weak<RwLock<T>>;
 
+    /// A snapshot of the memory used by an arena.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub struct ArenaStats {
+        /// The number of nodes stored in the arena.
+        pub nodes: usize,
+
+        /// The approximate number of bytes used by the arena's storage and nodes. Memory which the
+        /// nodes themselves allocate (e.g. the buffer of a Vec) isn't included.
+        pub approx_bytes: usize,
+
+        /// The largest number of nodes the arena has stored at once.
+        pub high_water_mark: usize
+    }
+
+    /// Returns the approximate number of bytes used by a node behind a SharedRef, including the
+    /// reference counts and the lock.
+    pub(crate) fn shared_node_bytes<T>() -> usize {
+        2 * core::mem::size_of::<usize>() + core::mem::size_of::<RwLock<T>>()
+    }
+
     pub trait IsMemoryArena {
         type Id;
         type Node;
@@ -27,13 +87,67 @@ pub mod prelude {
         fn get_node_weak(&self, id: &Self::Id) -> Option<WeakRef<Self::Node>>;
 
         /// Adds a node to the tree.
-        fn add_node(&mut self, node: Self::Node) -> Result<(), String>;
+        fn add_node(&mut self, node: Self::Node) -> Result<(), ArenaError>;
+
+        /// Adds every node to the tree, stopping at the first one which can't be added. The nodes
+        /// before it stay added.
+        ///
+        /// Arenas take their locks once for the whole batch, rather than once per node.
+        fn add_nodes<I: IntoIterator<Item = Self::Node>>(&mut self, nodes: I) -> Result<(), ArenaError> {
+            nodes.into_iter().try_for_each(|node| self.add_node(node))
+        }
+
+        /// Looks up the node for every Id, in the same order. Arenas take their locks once for the
+        /// whole batch, rather than once per node.
+        fn get_many(&self, ids: &[Self::Id]) -> Vec<Option<SharedRef<Self::Node>>> {
+            ids.iter().map(|id| self.get_node(id)).collect()
+        }
+
+        /// Returns the node for the given Id, first adding the node returned by 'f' if there is
+        /// none, all while holding the lock once. 'f' must return a node with the given Id.
+        fn get_or_insert_with<F: FnOnce() -> Self::Node>(
+            &mut self,
+            id: &Self::Id,
+            f: F
+        ) -> Result<SharedRef<Self::Node>, ArenaError> {
+            if let Some(node) = self.get_node(id) {
+                return Ok(node);
+            }
+
+            self.add_node(f())?;
+            self.get_node(id).ok_or(ArenaError::InvalidId)
+        }
 
         /// Removes the node from the tree.
-        fn delete_node(&mut self, id: &Self::Id) -> Result<(), String>;
+        fn delete_node(&mut self, id: &Self::Id) -> Result<(), ArenaError>;
 
         /// Returns a new unique Id.
         fn get_new_id(&mut self) -> Self::Id;
+
+        /// Returns the number of nodes stored in the arena.
+        fn len(&self) -> usize;
+
+        /// Returns true if the arena stores no nodes.
+        fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        /// Returns the number of nodes the arena can hold without allocating more memory.
+        fn capacity(&self) -> usize;
+
+        /// Returns every node in the arena along with its Id, in no particular order.
+        ///
+        /// The nodes are collected up front, so the arena isn't locked while iterating.
+        fn iter(&self) -> impl Iterator<Item = (Self::Id, SharedRef<Self::Node>)>;
+
+        /// Removes every node for which 'f' returns false.
+        fn retain<F: FnMut(&Self::Node) -> bool>(&mut self, f: F);
+
+        /// Returns the number of nodes stored in the arena along with its memory usage.
+        fn stats(&self) -> ArenaStats;
+
+        /// Releases as much of the memory left behind by deleted nodes as possible.
+        fn shrink_to_fit(&mut self);
     }
 }
 
@@ -41,24 +155,36 @@ use prelude::*;
 
 pub type Id = usize;
 
-pub struct Arena<T> {
-    storage: Arc<RwLock<HashMap<Id, SharedRef<T>>>>,
-    id_counter: AtomicUsize
+/// A memory arena which issues its Ids from a counter, and never reuses them.
+///
+/// The Ids are usize by default, but any 'ArenaIndex' can be used instead, e.g. u32 to halve the
+/// size of every link on 64-bit targets, or a 'TypedId' to keep the Ids of different trees apart.
+pub struct Arena<T, I: ArenaIndex = Id> {
+    storage: Arc<RwLock<HashMap<I, SharedRef<T>>>>,
+    id_counter: AtomicUsize,
+    high_water_mark: usize
 }
 
-impl<T: HasId + Debug + Clone + Send + Sync> Arena<T> {
+impl<T: HasId, I: ArenaIndex> Arena<T, I> {
     pub fn new() -> Self {
         Self {
-            storage: Arc::new(RwLock::new(HashMap::<usize, SharedRef<T>>::new())),
-            id_counter: AtomicUsize::default()
+            storage: Arc::new(RwLock::new(HashMap::<I, SharedRef<T>>::new())),
+            id_counter: AtomicUsize::default(),
+            high_water_mark: 0
         }
     }
 }
 
-impl<T: HasId + Debug + Clone + Send + Sync> IsMemoryArena for Arena<T>
-    where usize: From<T::Id>
+impl<T: HasId, I: ArenaIndex> Default for Arena<T, I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: HasId, I: ArenaIndex> IsMemoryArena for Arena<T, I>
+    where I: From<T::Id>
 {
-    type Id = usize;
+    type Id = I;
     type Node = T;
 
     fn get_node(&self, id: &Self::Id) -> Option<SharedRef<Self::Node>> {
@@ -69,19 +195,58 @@ impl<T: HasId + Debug + Clone + Send + Sync> IsMemoryArena for Arena<T>
         self.storage.read().unwrap().get(id).map(Arc::downgrade)
     }
 
-    fn add_node(&mut self, node: Self::Node) -> Result<(), String> {
-        if self.storage.read().unwrap().contains_key(&node.get_id().into()) {
-            return Err(String::from("node already exists!"));
+    fn add_node(&mut self, node: Self::Node) -> Result<(), ArenaError> {
+        self.add_nodes(core::iter::once(node))
+    }
+
+    fn add_nodes<N: IntoIterator<Item = Self::Node>>(&mut self, nodes: N) -> Result<(), ArenaError> {
+        let mut storage = self.storage.write().unwrap();
+        let mut result = Ok(());
+
+        for node in nodes {
+            let id: I = node.get_id().into();
+            if storage.contains_key(&id) {
+                result = Err(ArenaError::NodeExists);
+                break;
+            }
+
+            storage.insert(id, SharedRef::new(RwLock::new(node)));
         }
 
-        self.storage.write().unwrap().insert(node.get_id().into(), SharedRef::new(RwLock::new(node.clone())));
+        self.high_water_mark = self.high_water_mark.max(storage.len());
+        result
+    }
 
-        Ok(())
+    fn get_many(&self, ids: &[Self::Id]) -> Vec<Option<SharedRef<Self::Node>>> {
+        let storage = self.storage.read().unwrap();
+        ids.iter().map(|id| storage.get(id).map(Arc::clone)).collect()
     }
 
-    fn delete_node(&mut self, id: &Self::Id) -> Result<(), String> {
+    fn get_or_insert_with<F: FnOnce() -> Self::Node>(
+        &mut self,
+        id: &Self::Id,
+        f: F
+    ) -> Result<SharedRef<Self::Node>, ArenaError> {
+        let mut storage = self.storage.write().unwrap();
+        if let Some(node) = storage.get(id) {
+            return Ok(Arc::clone(node));
+        }
+
+        let node = f();
+        if I::from(node.get_id()) != *id {
+            return Err(ArenaError::InvalidId);
+        }
+
+        let node = SharedRef::new(RwLock::new(node));
+        storage.insert(*id, Arc::clone(&node));
+        self.high_water_mark = self.high_water_mark.max(storage.len());
+
+        Ok(node)
+    }
+
+    fn delete_node(&mut self, id: &Self::Id) -> Result<(), ArenaError> {
         if !self.storage.read().unwrap().contains_key(id) {
-            return Err(String::from("node doesn't exist!"));
+            return Err(ArenaError::NodeNotFound);
         }
 
         self.storage.write().unwrap().remove( id);
@@ -90,6 +255,511 @@ impl<T: HasId + Debug + Clone + Send + Sync> IsMemoryArena for Arena<T>
     }
 
     fn get_new_id(&mut self) -> Self::Id {
-        self.id_counter.fetch_add(1, Ordering::SeqCst)
+        let counter = self.id_counter.fetch_add(1, Ordering::SeqCst);
+        I::from_counter(counter).expect("arena ran out of ids!")
+    }
+
+    fn len(&self) -> usize {
+        self.storage.read().unwrap().len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.storage.read().unwrap().capacity()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (Self::Id, SharedRef<Self::Node>)> {
+        let storage = self.storage.read().unwrap();
+        let nodes: Vec<_> = storage.iter().map(|(id, node)| (*id, Arc::clone(node))).collect();
+        nodes.into_iter()
+    }
+
+    fn retain<F: FnMut(&Self::Node) -> bool>(&mut self, mut f: F) {
+        self.storage.write().unwrap().retain(|_, node| f(&node.read().unwrap()));
+    }
+
+    fn stats(&self) -> ArenaStats {
+        let storage = self.storage.read().unwrap();
+
+        // Every bucket of the map holds an entry plus a byte of control data.
+        let entry_bytes = core::mem::size_of::<(I, SharedRef<T>)>() + 1;
+
+        ArenaStats {
+            nodes: storage.len(),
+            approx_bytes: storage.capacity() * entry_bytes + storage.len() * shared_node_bytes::<T>(),
+            high_water_mark: self.high_water_mark
+        }
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.storage.write().unwrap().shrink_to_fit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::num::NonZeroU32;
+
+    use crate::arena::*;
+
+    #[derive(Debug, Clone)]
+    struct Node {
+        id: GenerationalId,
+        value: i32
+    }
+
+    impl HasId for Node {
+        type Id = GenerationalId;
+        fn get_id(&self) -> GenerationalId { self.id }
+    }
+
+    #[test]
+    fn test_generational_arena() {
+        let mut arena = GenerationalArena::<Node>::new();
+
+        let a = arena.get_new_id();
+        let b = arena.get_new_id();
+        assert_ne!(a, b);
+
+        assert!(arena.add_node(Node { id: a, value: 1 }).is_ok());
+        assert_eq!(arena.add_node(Node { id: a, value: 1 }), Err(ArenaError::NodeExists));
+        assert!(arena.add_node(Node { id: b, value: 2 }).is_ok());
+        assert_eq!(arena.get_node(&a).unwrap().read().unwrap().value, 1);
+
+        assert!(arena.delete_node(&a).is_ok());
+        assert!(arena.get_node(&a).is_none());
+        assert_eq!(arena.delete_node(&a), Err(ArenaError::NodeNotFound));
+
+        // The slot of 'a' gets recycled, but the stale id must not alias the new node.
+        let c = arena.get_new_id();
+        assert_eq!(c.index, a.index);
+        assert_ne!(c, a);
+        assert_eq!(arena.add_node(Node { id: a, value: 3 }), Err(ArenaError::InvalidId));
+        assert!(arena.add_node(Node { id: c, value: 3 }).is_ok());
+        assert!(arena.get_node(&a).is_none());
+        assert!(arena.get_node_weak(&a).is_none());
+        assert_eq!(arena.get_node(&c).unwrap().read().unwrap().value, 3);
+        assert_eq!(arena.get_node(&b).unwrap().read().unwrap().value, 2);
+    }
+
+    #[test]
+    fn test_arena_iteration() {
+        let mut arena = GenerationalArena::<Node>::new();
+        assert!(arena.is_empty());
+
+        let ids: Vec<_> = (0..10).map(|value| {
+            let id = arena.get_new_id();
+            arena.add_node(Node { id, value }).unwrap();
+            id
+        }).collect();
+
+        // Reserved ids aren't counted until their node is added.
+        let reserved = arena.get_new_id();
+        assert_eq!(arena.len(), 10);
+        assert!(arena.capacity() >= 11);
+
+        let mut values: Vec<i32> = arena.iter().map(|(id, node)| {
+            let node = node.read().unwrap();
+            assert_eq!(node.id, id);
+            node.value
+        }).collect();
+        values.sort();
+        assert_eq!(values, (0..10).collect::<Vec<_>>());
+
+        arena.retain(|node| node.value % 2 == 0);
+        assert_eq!(arena.len(), 5);
+        assert!(arena.get_node(&ids[1]).is_none());
+        assert!(arena.get_node(&ids[2]).is_some());
+        assert!(arena.add_node(Node { id: reserved, value: 10 }).is_ok());
+
+        // The slots of removed nodes are recycled.
+        let id = arena.get_new_id();
+        assert!(ids.iter().any(|old| old.index == id.index));
+        assert!(!ids.contains(&id));
+
+        let mut arena = Arena::<usize>::new();
+        for _ in 0..4 {
+            let id = arena.get_new_id();
+            arena.add_node(id).unwrap();
+        }
+        arena.retain(|id| *id != 2);
+        assert_eq!(arena.len(), 3);
+        assert!(arena.get_node(&2).is_none());
+        assert_eq!(arena.iter().map(|(id, _)| id).max(), Some(3));
+    }
+
+    #[test]
+    fn test_arena_shards() {
+        let mut arena = GenerationalArena::<Node>::with_shards(4);
+        assert_eq!(arena.shards(), 4);
+
+        let ids: Vec<_> = (0..100).map(|value| {
+            let id = arena.get_new_id();
+            arena.add_node(Node { id, value }).unwrap();
+            id
+        }).collect();
+
+        // New ids are handed out round-robin, so every shard gets its share of the nodes.
+        for shard in 0..4 {
+            assert_eq!(ids.iter().filter(|id| id.index % 4 == shard).count(), 25);
+        }
+
+        for (value, id) in ids.iter().enumerate() {
+            assert_eq!(arena.get_node(id).unwrap().read().unwrap().value, value as i32);
+        }
+        assert_eq!(arena.len(), 100);
+        assert_eq!(arena.iter().count(), 100);
+
+        assert!(arena.delete_node(&ids[7]).is_ok());
+        assert!(arena.get_node(&ids[7]).is_none());
+        assert_eq!(arena.delete_node(&ids[7]), Err(ArenaError::NodeNotFound));
+
+        arena.retain(|node| node.value % 2 == 0);
+        assert_eq!(arena.len(), 50);
+        assert!(ids.iter().step_by(2).all(|id| arena.get_node(id).is_some()));
+
+        // Recycled slots never alias the ids of deleted nodes.
+        let id = arena.get_new_id();
+        assert!(!ids.contains(&id));
+    }
+
+    #[test]
+    fn test_arena_stats() {
+        let mut arena = GenerationalArena::<Node>::with_shards(2);
+        assert_eq!(arena.stats(), ArenaStats { nodes: 0, approx_bytes: 0, high_water_mark: 0 });
+
+        let ids: Vec<_> = (0..100).map(|value| {
+            let id = arena.get_new_id();
+            arena.add_node(Node { id, value }).unwrap();
+            id
+        }).collect();
+
+        let full = arena.stats();
+        assert_eq!(full.nodes, 100);
+        assert_eq!(full.high_water_mark, 100);
+
+        // Delete the newer half of the nodes, and one older node.
+        for id in ids[50..].iter().chain(&ids[10..11]) {
+            arena.delete_node(id).unwrap();
+        }
+        assert_eq!(arena.stats().nodes, 49);
+        assert_eq!(arena.stats().high_water_mark, 100);
+
+        arena.shrink_to_fit();
+        let shrunk = arena.stats();
+        assert_eq!(shrunk.nodes, 49);
+        assert!(shrunk.approx_bytes < full.approx_bytes);
+
+        // Released slots are allocated again without reviving the Ids of their old nodes.
+        for id in &ids[..50] {
+            assert_eq!(arena.get_node(id).is_some(), *id != ids[10]);
+        }
+        let new_ids: Vec<_> = (0..60).map(|value| {
+            let id = arena.get_new_id();
+            arena.add_node(Node { id, value }).unwrap();
+            id
+        }).collect();
+        assert!(ids.iter().all(|id| !new_ids.contains(id)));
+        assert!(ids[50..].iter().chain(&ids[10..11]).all(|id| arena.get_node(id).is_none()));
+        assert_eq!(arena.stats().high_water_mark, 109);
+
+        let mut arena = Arena::<usize>::new();
+        for _ in 0..1000 {
+            let id = arena.get_new_id();
+            arena.add_node(id).unwrap();
+        }
+        arena.retain(|id| *id < 10);
+
+        let before = arena.stats();
+        assert_eq!(before.nodes, 10);
+        assert_eq!(before.high_water_mark, 1000);
+
+        arena.shrink_to_fit();
+        assert!(arena.stats().approx_bytes < before.approx_bytes);
+        assert_eq!(arena.iter().count(), 10);
+    }
+
+    #[test]
+    fn test_arena_compact() {
+        let mut arena = GenerationalArena::<Node>::with_shards(2);
+
+        let ids: Vec<_> = (0..100).map(|value| {
+            let id = arena.get_new_id();
+            arena.add_node(Node { id, value }).unwrap();
+            id
+        }).collect();
+        let snapshot = arena.snapshot();
+
+        // Deleting every node but the last few leaves slots in the middle which 'shrink_to_fit'
+        // can't release.
+        arena.retain(|node| node.value % 10 == 0 || node.value >= 95);
+        arena.shrink_to_fit();
+        let before = arena.stats();
+
+        let mut remapped = HashMap::new();
+        arena.compact(|old, new| {
+            remapped.insert(old, new);
+        });
+
+        assert_eq!(remapped.len(), 15);
+        assert!(arena.stats().approx_bytes < before.approx_bytes);
+        assert_eq!(arena.capacity(), 15);
+
+        // Every node is found under its new Id, and none under its old one.
+        for (old, new) in &remapped {
+            let value = arena.get_node(new).unwrap().read().unwrap().value;
+            assert_eq!(ids[value as usize], *old);
+            assert!(arena.get_node(old).is_none());
+        }
+
+        let id = arena.get_new_id();
+        assert!(arena.add_node(Node { id, value: -1 }).is_ok());
+        assert!(!ids.contains(&id) && !remapped.values().any(|new| *new == id));
+
+        // Snapshots taken before compacting still restore the old layout.
+        arena.restore(&snapshot);
+        assert_eq!(arena.len(), 100);
+        assert_eq!(arena.get_node(&ids[42]).unwrap().read().unwrap().value, 42);
+        assert!(remapped.values().all(|new| arena.get_node(new).is_none()));
+    }
+
+    #[test]
+    fn test_arena_snapshot() {
+        let mut arena = GenerationalArena::<Node>::with_shards(2);
+
+        let ids: Vec<_> = (0..10).map(|value| {
+            let id = arena.get_new_id();
+            arena.add_node(Node { id, value }).unwrap();
+            id
+        }).collect();
+
+        let snapshot = arena.snapshot();
+
+        // --
+        // Change the arena in every way possible, without the snapshot noticing.
+        arena.get_node_mut(&ids[0]).unwrap().write().unwrap().value = 100;
+        assert!(arena.delete_node(&ids[1]).is_ok());
+        let recycled = arena.get_new_id();
+        arena.add_node(Node { id: recycled, value: 101 }).unwrap();
+        arena.retain(|node| node.value != 2);
+        let added: Vec<_> = (0..10).map(|value| {
+            let id = arena.get_new_id();
+            arena.add_node(Node { id, value }).unwrap();
+            id
+        }).collect();
+        assert_eq!(arena.len(), 19);
+
+        for _ in 0..2 {
+            arena.restore(&snapshot);
+            assert_eq!(arena.len(), 10);
+
+            for (value, id) in ids.iter().enumerate() {
+                assert_eq!(arena.get_node(id).unwrap().read().unwrap().value, value as i32);
+            }
+
+            // Ids handed out since the snapshot stay stale, even once their slots are reused.
+            assert!(arena.get_node(&recycled).is_none());
+            let new_ids: Vec<_> = (0..20).map(|_| arena.get_new_id()).collect();
+            assert!(new_ids.iter().all(|id| *id != recycled && !added.contains(id)));
+
+            arena.get_node_mut(&ids[3]).unwrap().write().unwrap().value = -3;
+        }
+
+        assert_eq!(arena.iter().count(), 10);
+        assert_eq!(arena.get_node(&ids[3]).unwrap().read().unwrap().value, -3);
+    }
+
+    #[test]
+    fn test_arena_restore_after_shrink() {
+        let mut arena = GenerationalArena::<Node>::new();
+        let add = |arena: &mut GenerationalArena<Node>, value| {
+            let id = arena.get_new_id();
+            arena.add_node(Node { id, value }).unwrap();
+            id
+        };
+
+        let a = add(&mut arena, 0);
+        let b = add(&mut arena, 1);
+        let c = add(&mut arena, 2);
+        arena.delete_node(&c).unwrap();
+        let snapshot = arena.snapshot();
+
+        // --
+        // Reuse the free slot of 'c' and the slot of 'b', then release both past the end.
+        let stale_c = add(&mut arena, 3);
+        arena.delete_node(&b).unwrap();
+        let stale_b = add(&mut arena, 4);
+        arena.delete_node(&stale_c).unwrap();
+        arena.delete_node(&stale_b).unwrap();
+        arena.shrink_to_fit();
+        assert_eq!(arena.capacity(), 1);
+
+        arena.restore(&snapshot);
+        assert_eq!(arena.len(), 2);
+        assert_eq!(arena.get_node(&b).unwrap().read().unwrap().value, 1);
+        assert!(arena.get_node(&stale_b).is_none());
+        assert!(arena.get_node(&stale_c).is_none());
+
+        // The slots past the end of the shrunk arena must not hand out their stale Ids again, not
+        // even once the restored node in the slot of 'b' is deleted.
+        let recycled = add(&mut arena, 5);
+        assert_eq!(recycled.index, c.index);
+        assert!(recycled != c && recycled != stale_c);
+
+        arena.delete_node(&b).unwrap();
+        let recycled = add(&mut arena, 6);
+        assert_eq!(recycled.index, b.index);
+        assert_ne!(recycled, stale_b);
+        assert!(arena.get_node(&stale_b).is_none());
+        assert_eq!(arena.get_node(&a).unwrap().read().unwrap().value, 0);
+
+        // --
+        // The same goes for the slots which 'compact' releases.
+        let snapshot = arena.snapshot();
+        let stale: Vec<_> = (0..5).map(|value| add(&mut arena, value)).collect();
+        stale.iter().for_each(|id| arena.delete_node(id).unwrap());
+        arena.compact(|_, _| {});
+
+        arena.restore(&snapshot);
+        let new_ids: Vec<_> = (0..10).map(|value| add(&mut arena, value)).collect();
+        assert!(new_ids.iter().all(|id| !stale.contains(id)));
+        assert!(stale.iter().all(|id| arena.get_node(id).is_none()));
+    }
+
+    #[test]
+    fn test_persistent_vec() {
+        use crate::arena::persistent::PersistentVec;
+
+        // Enough items for a tree with two levels of branches.
+        let mut vec: PersistentVec<usize> = (0..2000).collect();
+        assert_eq!(vec.len(), 2000);
+        assert!(vec.iter().copied().eq(0..2000));
+
+        // --
+        // Clones keep their items while the original changes, and the other way around.
+        let clone = vec.clone();
+        *vec.get_mut(1500).unwrap() = 0;
+        (0..1000).for_each(|_| { vec.pop(); });
+        vec.push(7);
+
+        assert_eq!(vec.len(), 1001);
+        assert_eq!(vec.last(), Some(&7));
+        assert_eq!(vec.get(1001), None);
+        assert_eq!(clone.get(1500), Some(&1500));
+        assert!(clone.iter().copied().eq(0..2000));
+
+        while vec.pop().is_some() {}
+        assert_eq!(vec.len(), 0);
+        assert_eq!(vec.iter().count(), 0);
+        assert_eq!(clone.len(), 2000);
+    }
+
+    #[test]
+    fn test_arena_batch_operations() {
+        let mut arena = GenerationalArena::<Node>::with_shards(3);
+
+        let ids: Vec<_> = (0..10).map(|_| arena.get_new_id()).collect();
+        assert!(arena.add_nodes(ids.iter().enumerate().map(|(value, id)| Node { id: *id, value: value as i32 })).is_ok());
+        assert_eq!(arena.len(), 10);
+        assert_eq!(arena.stats().high_water_mark, 10);
+
+        // The batch stops at the first node which can't be added, keeping the ones before it.
+        let more: Vec<_> = (0..2).map(|_| arena.get_new_id()).collect();
+        let batch = vec![Node { id: more[0], value: 10 }, Node { id: ids[0], value: 0 }, Node { id: more[1], value: 11 }];
+        assert_eq!(arena.add_nodes(batch), Err(ArenaError::NodeExists));
+        assert_eq!(arena.len(), 11);
+        assert!(arena.get_node(&more[1]).is_none());
+
+        // Lookups keep their order, and stale or unknown Ids come back empty, even across snapshots.
+        let snapshot = arena.snapshot();
+        arena.delete_node(&ids[4]).unwrap();
+        let values: Vec<_> = arena.get_many(&[ids[9], ids[4], more[0], ids[0], more[1]]).into_iter()
+            .map(|node| node.map(|node| node.read().unwrap().value))
+            .collect();
+        assert_eq!(values, vec![Some(9), None, Some(10), Some(0), None]);
+        arena.get_node_mut(&ids[1]).unwrap().write().unwrap().value = -1;
+
+        arena.restore(&snapshot);
+        assert_eq!(arena.get_node(&ids[1]).unwrap().read().unwrap().value, 1);
+        assert_eq!(arena.get_many(&ids[4..5])[0].as_ref().unwrap().read().unwrap().value, 4);
+
+        // --
+        // Nodes are only created if they don't exist yet.
+        let existing = arena.get_or_insert_with(&ids[2], || unreachable!()).unwrap();
+        assert_eq!(existing.read().unwrap().value, 2);
+
+        let id = arena.get_new_id();
+        let node = arena.get_or_insert_with(&id, || Node { id, value: 20 }).unwrap();
+        assert_eq!(node.read().unwrap().value, 20);
+        assert_eq!(arena.get_node(&id).unwrap().read().unwrap().value, 20);
+        assert_eq!(arena.len(), 12);
+
+        let other = arena.get_new_id();
+        assert_eq!(arena.get_or_insert_with(&other, || Node { id, value: 0 }).err(), Some(ArenaError::InvalidId));
+        assert_eq!(arena.get_or_insert_with(&ids[4], || Node { id: ids[4], value: 0 }).unwrap().read().unwrap().value, 4);
+
+        let mut arena = Arena::<usize>::new();
+        assert!(arena.add_nodes(0..5).is_ok());
+        assert_eq!(arena.add_nodes(4..6), Err(ArenaError::NodeExists));
+        assert_eq!(arena.get_many(&[3, 7]).iter().map(Option::is_some).collect::<Vec<_>>(), vec![true, false]);
+        assert_eq!(*arena.get_or_insert_with(&7, || 7).unwrap().read().unwrap(), 7);
+        assert_eq!(arena.get_or_insert_with(&8, || 9).err(), Some(ArenaError::InvalidId));
+        assert_eq!(arena.len(), 6);
+    }
+
+    struct Leaf {
+        id: TypedId<Leaf>,
+        children: Vec<Option<TypedId<Leaf, NonZeroU32>>>
+    }
+
+    impl HasId for Leaf {
+        type Id = TypedId<Leaf>;
+        fn get_id(&self) -> TypedId<Leaf> { self.id }
+    }
+
+    #[test]
+    fn test_arena_typed_ids() {
+        // Narrow Ids halve the size of every link, and NonZeroU32 gets Option for free.
+        assert_eq!(std::mem::size_of::<TypedId<Leaf>>(), 4);
+        assert_eq!(std::mem::size_of::<Option<TypedId<Leaf, NonZeroU32>>>(), 4);
+        assert_eq!(NonZeroU32::from_counter(0).map(ArenaIndex::to_counter), Some(0));
+        assert_eq!(NonZeroU32::from_counter(u32::MAX as usize), None);
+        assert_eq!(u16::from_counter(1 << 16), None);
+
+        let mut arena = Arena::<Leaf, TypedId<Leaf>>::new();
+        let ids: Vec<_> = (0..10).map(|_| {
+            let id = arena.get_new_id();
+            arena.add_node(Leaf { id, children: vec![] }).unwrap();
+            id
+        }).collect();
+        assert_eq!(ids.iter().map(|id| id.index()).collect::<Vec<_>>(), (0..10).collect::<Vec<u32>>());
+
+        let child = NonZeroU32::from_counter(ids[1].to_counter()).map(TypedId::new);
+        arena.get_node(&ids[0]).unwrap().write().unwrap().children.push(child);
+        assert_eq!(arena.get_node(&ids[0]).unwrap().read().unwrap().children[0].unwrap().to_counter(), 1);
+
+        assert!(arena.delete_node(&ids[3]).is_ok());
+        assert!(arena.get_node(&ids[3]).is_none());
+        assert_eq!(arena.add_node(Leaf { id: ids[4], children: vec![] }), Err(ArenaError::NodeExists));
+        assert_eq!(arena.len(), 9);
+    }
+
+    struct Callback {
+        id: GenerationalId,
+        f: Box<dyn Fn() -> i32 + Send + Sync>
+    }
+
+    impl HasId for Callback {
+        type Id = GenerationalId;
+        fn get_id(&self) -> GenerationalId { self.id }
+    }
+
+    #[test]
+    fn test_arena_non_clone_nodes() {
+        let mut arena = GenerationalArena::<Callback>::new();
+
+        let id = arena.get_new_id();
+        assert!(arena.add_node(Callback { id, f: Box::new(|| 42) }).is_ok());
+        assert_eq!((arena.get_node(&id).unwrap().read().unwrap().f)(), 42);
     }
 }
@@ -0,0 +1,210 @@
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// The number of bits of an index which pick the child at every level of the tree.
+const BITS: usize = 5;
+const WIDTH: usize = 1 << BITS;
+const MASK: usize = WIDTH - 1;
+
+enum Node<T> {
+    Leaf(Vec<T>),
+    Branch(Vec<Arc<Node<T>>>)
+}
+
+impl<T: Clone> Clone for Node<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Node::Leaf(items) => Node::Leaf(items.clone()),
+            Node::Branch(children) => Node::Branch(children.clone())
+        }
+    }
+}
+
+impl<T> Node<T> {
+    fn is_empty(&self) -> bool {
+        match self {
+            Node::Leaf(items) => items.is_empty(),
+            Node::Branch(children) => children.is_empty()
+        }
+    }
+}
+
+/// A vector whose clones share their items, which are only copied once one of the clones changes.
+///
+/// The items are stored in the leaves of a tree of 'WIDTH' wide nodes, so cloning the vector is
+/// O(1), and changing an item only copies the nodes on the path down to it which are still shared.
+pub(crate) struct PersistentVec<T> {
+    root: Arc<Node<T>>,
+
+    /// The number of levels of branches above the leaves.
+    height: usize,
+    len: usize
+}
+
+impl<T> Clone for PersistentVec<T> {
+    fn clone(&self) -> Self {
+        Self { root: Arc::clone(&self.root), height: self.height, len: self.len }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for PersistentVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T> PersistentVec<T> {
+    pub fn new() -> Self {
+        Self { root: Arc::new(Node::Leaf(vec![])), height: 0, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index >= self.len {
+            return None;
+        }
+
+        let mut node = &*self.root;
+        for level in (1..=self.height).rev() {
+            let Node::Branch(children) = node else { unreachable!() };
+            node = &children[(index >> (level * BITS)) & MASK];
+        }
+
+        let Node::Leaf(items) = node else { unreachable!() };
+        items.get(index & MASK)
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.len.checked_sub(1).and_then(|index| self.get(index))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.leaves().into_iter().flatten()
+    }
+
+    /// Returns the number of items the leaves can hold without allocating more memory.
+    pub fn capacity(&self) -> usize {
+        self.leaves().iter().map(|items| items.capacity()).sum()
+    }
+
+    fn leaves(&self) -> Vec<&Vec<T>> {
+        let mut leaves = vec![];
+        let mut stack = vec![&*self.root];
+
+        while let Some(node) = stack.pop() {
+            match node {
+                Node::Leaf(items) => leaves.push(items),
+                Node::Branch(children) => stack.extend(children.iter().rev().map(|child| &**child))
+            }
+        }
+
+        leaves
+    }
+}
+
+impl<T: Clone> PersistentVec<T> {
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index >= self.len {
+            return None;
+        }
+
+        let mut node = Arc::make_mut(&mut self.root);
+        for level in (1..=self.height).rev() {
+            let Node::Branch(children) = node else { unreachable!() };
+            node = Arc::make_mut(&mut children[(index >> (level * BITS)) & MASK]);
+        }
+
+        let Node::Leaf(items) = node else { unreachable!() };
+        items.get_mut(index & MASK)
+    }
+
+    pub fn push(&mut self, item: T) {
+        // The tree grows a level once every leaf below the root is full.
+        if self.len == WIDTH << (self.height * BITS) {
+            let root = Arc::clone(&self.root);
+            self.root = Arc::new(Node::Branch(vec![root]));
+            self.height += 1;
+        }
+
+        let index = self.len;
+        let mut node = Arc::make_mut(&mut self.root);
+        for level in (1..=self.height).rev() {
+            let Node::Branch(children) = node else { unreachable!() };
+
+            let idx = (index >> (level * BITS)) & MASK;
+            if idx == children.len() {
+                children.push(Arc::new(if level == 1 { Node::Leaf(vec![]) } else { Node::Branch(vec![]) }));
+            }
+            node = Arc::make_mut(&mut children[idx]);
+        }
+
+        let Node::Leaf(items) = node else { unreachable!() };
+        items.push(item);
+        self.len += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let item = Self::_pop(Arc::make_mut(&mut self.root));
+        self.len -= 1;
+
+        if self.len == 0 {
+            *self = Self::new();
+        }
+
+        // The root is dropped once its first child holds every item.
+        while self.height > 0 && self.len <= WIDTH << ((self.height - 1) * BITS) {
+            let Node::Branch(children) = &*self.root else { unreachable!() };
+            self.root = Arc::clone(&children[0]);
+            self.height -= 1;
+        }
+
+        Some(item)
+    }
+
+    fn _pop(node: &mut Node<T>) -> T {
+        match node {
+            Node::Leaf(items) => items.pop().expect("leaf is empty"),
+            Node::Branch(children) => {
+                let last = children.last_mut().expect("branch is empty");
+                let item = Self::_pop(Arc::make_mut(last));
+
+                if last.is_empty() {
+                    children.pop();
+                }
+                item
+            }
+        }
+    }
+
+    /// Releases the memory the last leaf holds beyond its items.
+    pub fn shrink_to_fit(&mut self) {
+        let mut node = Arc::make_mut(&mut self.root);
+        while let Node::Branch(children) = node {
+            match children.last_mut() {
+                Some(last) => node = Arc::make_mut(last),
+                None => return
+            }
+        }
+
+        let Node::Leaf(items) = node else { unreachable!() };
+        items.shrink_to_fit();
+    }
+}
+
+impl<T: Clone> FromIterator<T> for PersistentVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut vec = Self::new();
+        iter.into_iter().for_each(|item| vec.push(item));
+        vec
+    }
+}
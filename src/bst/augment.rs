@@ -0,0 +1,37 @@
+/// Metadata which a balanced binary search tree keeps for every node, computed from the node's own
+/// entry and the metadata of its children. The tree recomputes it bottom-up for every node whose
+/// subtree changes, including the nodes involved in rotations.
+///
+/// Subtree sizes, the largest value in a subtree or the sum of its values can all be kept this way,
+/// since they only depend on what is below the node and not on how the subtree is shaped.
+pub trait Augmentation<K, V>: Clone + Send + Sync {
+    fn compute(key: &K, value: &V, left: Option<&Self>, right: Option<&Self>) -> Self;
+}
+
+/// Keeps no metadata at all, which is what trees use unless told otherwise.
+impl<K, V> Augmentation<K, V> for () {
+    fn compute(_: &K, _: &V, _: Option<&Self>, _: Option<&Self>) -> Self {}
+}
+
+/// Augmentations which count the nodes of their subtree, which is what order statistics such as
+/// 'RbTree::rank' and 'RbTree::select' are built on. Custom augmentations can implement this
+/// alongside their own metadata to support them as well.
+pub trait CountsNodes {
+    fn subtree_size(&self) -> usize;
+}
+
+/// Counts the nodes in every subtree, including the node itself.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct SubtreeSize(pub usize);
+
+impl<K, V> Augmentation<K, V> for SubtreeSize {
+    fn compute(_: &K, _: &V, left: Option<&Self>, right: Option<&Self>) -> Self {
+        Self(1 + left.map_or(0, |s| s.0) + right.map_or(0, |s| s.0))
+    }
+}
+
+impl CountsNodes for SubtreeSize {
+    fn subtree_size(&self) -> usize {
+        self.0
+    }
+}
@@ -0,0 +1,443 @@
+use alloc::{format, vec};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt::Debug;
+use core::ops::RangeBounds;
+
+use crate::arena::*;
+use crate::arena::prelude::*;
+use crate::bst::bounds::*;
+use crate::visualize::{DotWriter, ToDot};
+
+type Id = GenerationalId;
+
+#[derive(Debug, Clone)]
+struct AvlNode<K: Send + Sync, V: Send + Sync> {
+    pub id: Id,
+
+    pub key: K,
+    pub value: V,
+
+    /// The height and number of nodes of the subtree rooted at this node, a leaf has height 1.
+    pub height: usize,
+    pub size: usize,
+
+    pub left: Option<Id>,
+    pub right: Option<Id>
+}
+
+impl<K: Send + Sync, V: Send + Sync> HasId for AvlNode<K, V> {
+    type Id = Id;
+
+    fn get_id(&self) -> Self::Id {
+        self.id
+    }
+}
+
+impl<K: Send + Sync, V: Send + Sync> AvlNode<K, V> {
+    /// Constructs a new leaf from the given arguments
+    pub fn new(id: Id, key: K, value: V) -> Self {
+        Self {
+            id,
+            key,
+            value,
+            height: 1,
+            size: 1,
+            left: None,
+            right: None
+        }
+    }
+}
+
+/// This class represents a thread-safe AVL tree, a binary search tree which keeps the heights of
+/// the 2 subtrees of every node within 1 of each other.
+///
+/// Every node also tracks the size of its subtree, which allows for rank queries in O(log n).
+pub struct AvlTree<K: Ord + Send + Sync, V: Send + Sync> {
+    arena: GenerationalArena<AvlNode<K, V>>,
+    root: Option<Id>
+}
+
+impl<K: Ord + Send + Sync, V: Send + Sync> Default for AvlTree<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Send + Sync, V: Send + Sync> AvlTree<K, V> {
+
+    /// Constructs a new empty AvlTree
+    pub fn new() -> Self {
+        Self {
+            arena: GenerationalArena::new(),
+            root: None
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.size_of(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Returns the height of the tree, which is 0 for an empty tree.
+    pub fn height(&self) -> usize {
+        self.height_of(&self.root)
+    }
+
+    /// Inserts the key, returning the previous value if it already exists.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let root = self.root;
+        let (root, prev) = self._insert(root, key, value);
+        self.root = Some(root);
+        prev
+    }
+
+    /// Removes the key, returning its value if it existed.
+    pub fn delete(&mut self, key: &K) -> Option<V> {
+        let root = self.root;
+        let (root, removed) = self._delete(root, key);
+        self.root = root;
+        removed
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self._find_node(key).is_some()
+    }
+
+    /// Returns the number of keys in the tree which are smaller than 'key'.
+    pub fn rank(&self, key: &K) -> usize {
+        let mut rank = 0;
+        let mut node_id = self.root;
+
+        while let Some(id) = node_id {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+
+            if *key <= node.key {
+                node_id = node.left;
+            } else {
+                rank += self.size_of(&node.left) + 1;
+                node_id = node.right;
+            }
+        }
+
+        rank
+    }
+
+    fn _insert(&mut self, node_id: Option<Id>, key: K, value: V) -> (Id, Option<V>) {
+        let id = match node_id {
+            None => {
+                let id = self.arena.get_new_id();
+                self.arena.add_node(AvlNode::new(id, key, value)).expect("could not add node!");
+                return (id, None);
+            }
+            Some(id) => id
+        };
+
+        let node_ref = self.node(&id);
+        let (ordering, left, right) = {
+            let node = node_ref.read().unwrap();
+            (key.cmp(&node.key), node.left, node.right)
+        };
+
+        let prev = match ordering {
+            Ordering::Equal => {
+                let prev = core::mem::replace(&mut node_ref.write().unwrap().value, value);
+                return (id, Some(prev));
+            }
+            Ordering::Less => {
+                let (child, prev) = self._insert(left, key, value);
+                node_ref.write().unwrap().left = Some(child);
+                prev
+            }
+            Ordering::Greater => {
+                let (child, prev) = self._insert(right, key, value);
+                node_ref.write().unwrap().right = Some(child);
+                prev
+            }
+        };
+
+        (self.rebalance(id), prev)
+    }
+
+    fn _delete(&mut self, node_id: Option<Id>, key: &K) -> (Option<Id>, Option<V>) {
+        let id = match node_id {
+            None => return (None, None),
+            Some(id) => id
+        };
+
+        let node_ref = self.node(&id);
+        let (ordering, left, right) = {
+            let node = node_ref.read().unwrap();
+            (key.cmp(&node.key), node.left, node.right)
+        };
+
+        let removed = match ordering {
+            Ordering::Less => {
+                let (child, removed) = self._delete(left, key);
+                node_ref.write().unwrap().left = child;
+                removed
+            }
+            Ordering::Greater => {
+                let (child, removed) = self._delete(right, key);
+                node_ref.write().unwrap().right = child;
+                removed
+            }
+            Ordering::Equal => {
+                // --
+                // A node with 2 children is replaced by the smallest node of its right subtree.
+                let replacement = match (left, right) {
+                    (None, None) => None,
+                    (Some(child), None) | (None, Some(child)) => Some(child),
+                    (Some(left), Some(right)) => {
+                        let (rest, min_id) = self._take_min(right);
+
+                        let min_ref = self.node(&min_id);
+                        let mut min = min_ref.write().unwrap();
+                        min.left = Some(left);
+                        min.right = rest;
+                        drop(min);
+
+                        Some(self.rebalance(min_id))
+                    }
+                };
+
+                drop(node_ref);
+                let node = self.take_node(&id);
+                return (replacement, Some(node.value));
+            }
+        };
+
+        (Some(self.rebalance(id)), removed)
+    }
+
+    /// Detaches the smallest node of the given subtree, returning the new root of the subtree and
+    /// the id of the detached node.
+    fn _take_min(&mut self, node_id: Id) -> (Option<Id>, Id) {
+        let node_ref = self.node(&node_id);
+        let (left, right) = {
+            let node = node_ref.read().unwrap();
+            (node.left, node.right)
+        };
+
+        match left {
+            None => (right, node_id),
+            Some(left) => {
+                let (rest, min_id) = self._take_min(left);
+                node_ref.write().unwrap().left = rest;
+                (Some(self.rebalance(node_id)), min_id)
+            }
+        }
+    }
+
+    /// Restores the AVL invariant at the given node, returning the new root of its subtree.
+    fn rebalance(&self, node_id: Id) -> Id {
+        self.update(&node_id);
+
+        let (left, right) = self.children(&node_id);
+        let balance = self.height_of(&left) as isize - self.height_of(&right) as isize;
+
+        if balance > 1 {
+            let left = left.unwrap();
+            if self.balance_of(&left) < 0 {
+                let left = self.rotate_left(left);
+                self.node(&node_id).write().unwrap().left = Some(left);
+            }
+            self.rotate_right(node_id)
+        } else if balance < -1 {
+            let right = right.unwrap();
+            if self.balance_of(&right) > 0 {
+                let right = self.rotate_right(right);
+                self.node(&node_id).write().unwrap().right = Some(right);
+            }
+            self.rotate_left(node_id)
+        } else {
+            node_id
+        }
+    }
+
+    fn rotate_left(&self, node_id: Id) -> Id {
+        let node_ref = self.node(&node_id);
+        let right_id = node_ref.read().unwrap().right.expect("rotation requires a right child");
+        let right_ref = self.node(&right_id);
+
+        let moved = right_ref.read().unwrap().left;
+        node_ref.write().unwrap().right = moved;
+        right_ref.write().unwrap().left = Some(node_id);
+
+        self.update(&node_id);
+        self.update(&right_id);
+        right_id
+    }
+
+    fn rotate_right(&self, node_id: Id) -> Id {
+        let node_ref = self.node(&node_id);
+        let left_id = node_ref.read().unwrap().left.expect("rotation requires a left child");
+        let left_ref = self.node(&left_id);
+
+        let moved = left_ref.read().unwrap().right;
+        node_ref.write().unwrap().left = moved;
+        left_ref.write().unwrap().right = Some(node_id);
+
+        self.update(&node_id);
+        self.update(&left_id);
+        left_id
+    }
+
+    /// Recomputes the height and size of the given node from its children.
+    fn update(&self, node_id: &Id) {
+        let (left, right) = self.children(node_id);
+        let height = 1 + self.height_of(&left).max(self.height_of(&right));
+        let size = 1 + self.size_of(&left) + self.size_of(&right);
+
+        let node_ref = self.node(node_id);
+        let mut node = node_ref.write().unwrap();
+        node.height = height;
+        node.size = size;
+    }
+
+    fn balance_of(&self, node_id: &Id) -> isize {
+        let (left, right) = self.children(node_id);
+        self.height_of(&left) as isize - self.height_of(&right) as isize
+    }
+
+    fn children(&self, node_id: &Id) -> (Option<Id>, Option<Id>) {
+        let node_ref = self.node(node_id);
+        let node = node_ref.read().unwrap();
+        (node.left, node.right)
+    }
+
+    fn height_of(&self, node_id: &Option<Id>) -> usize {
+        node_id.map_or(0, |id| self.node(&id).read().unwrap().height)
+    }
+
+    fn size_of(&self, node_id: &Option<Id>) -> usize {
+        node_id.map_or(0, |id| self.node(&id).read().unwrap().size)
+    }
+
+    fn node(&self, node_id: &Id) -> SharedRef<AvlNode<K, V>> {
+        self.arena.get_node(node_id).expect("could not find node")
+    }
+
+    /// Removes the node from the arena, handing back ownership of it.
+    fn take_node(&mut self, node_id: &Id) -> AvlNode<K, V> {
+        let node_ref = self.node(node_id);
+        self.arena.delete_node(node_id).expect("could not delete node");
+
+        match Arc::try_unwrap(node_ref) {
+            Ok(node) => node.into_inner().unwrap(),
+            Err(_) => panic!("node is still referenced")
+        }
+    }
+
+    fn _find_node(&self, key: &K) -> Option<Id> {
+        let mut node_id = self.root;
+
+        while let Some(id) = node_id {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+
+            node_id = match key.cmp(&node.key) {
+                Ordering::Equal => return Some(id),
+                Ordering::Less => node.left,
+                Ordering::Greater => node.right
+            };
+        }
+
+        None
+    }
+}
+
+impl<K: Ord + Clone + Send + Sync, V: Clone + Send + Sync> AvlTree<K, V> {
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let id = self._find_node(key)?;
+        let value = self.node(&id).read().unwrap().value.clone();
+        Some(value)
+    }
+
+    /// Returns the key at the given rank (i.e. the 'rank'-th smallest key) along with its value.
+    pub fn select(&self, rank: usize) -> Option<(K, V)> {
+        let mut rank = rank;
+        let mut node_id = self.root;
+
+        while let Some(id) = node_id {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+            let left_size = self.size_of(&node.left);
+
+            match rank.cmp(&left_size) {
+                Ordering::Equal => return Some((node.key.clone(), node.value.clone())),
+                Ordering::Less => node_id = node.left,
+                Ordering::Greater => {
+                    rank -= left_size + 1;
+                    node_id = node.right;
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns all keys along with their values, in ascending order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (K, V)> {
+        self.range(..)
+    }
+
+    /// Returns all keys within the given range along with their values, in ascending order.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> impl DoubleEndedIterator<Item = (K, V)> {
+        let mut result = vec![];
+        self._range(&self.root, &range, &mut result);
+        result.into_iter()
+    }
+
+    fn _range<R: RangeBounds<K>>(&self, node_id: &Option<Id>, range: &R, out: &mut Vec<(K, V)>) {
+        let node_ref = match node_id {
+            None => return,
+            Some(id) => self.node(id)
+        };
+        let node = node_ref.read().unwrap();
+
+        let after_start = after_start(&node.key, range);
+        let before_end = before_end(&node.key, range);
+
+        // Subtrees which lie entirely outside of the range are skipped.
+        if after_start {
+            self._range(&node.left, range, out);
+        }
+        if after_start && before_end {
+            out.push((node.key.clone(), node.value.clone()));
+        }
+        if before_end {
+            self._range(&node.right, range, out);
+        }
+    }
+}
+
+impl<K: Ord + Debug + Send + Sync, V: Debug + Send + Sync> ToDot for AvlTree<K, V> {
+    fn to_dot(&self) -> String {
+        let mut dot = DotWriter::new("AvlTree");
+        let mut stack: Vec<Id> = self.root.into_iter().collect();
+
+        while let Some(id) = stack.pop() {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+
+            dot.node(&id, &format!("{:?}: {:?}\nheight {}", node.key, node.value, node.height), "");
+
+            for (child, name) in [(node.left, "L"), (node.right, "R")] {
+                if let Some(child_id) = child {
+                    dot.edge(&id, &child_id, name, "");
+                    stack.push(child_id);
+                }
+            }
+        }
+
+        dot.finish()
+    }
+}
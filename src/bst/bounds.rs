@@ -0,0 +1,19 @@
+use core::ops::{Bound, RangeBounds};
+
+/// Returns true if 'key' is not below the start of the range.
+pub(crate) fn after_start<K: Ord, R: RangeBounds<K>>(key: &K, range: &R) -> bool {
+    match range.start_bound() {
+        Bound::Included(start) => key >= start,
+        Bound::Excluded(start) => key > start,
+        Bound::Unbounded => true
+    }
+}
+
+/// Returns true if 'key' is not above the end of the range.
+pub(crate) fn before_end<K: Ord, R: RangeBounds<K>>(key: &K, range: &R) -> bool {
+    match range.end_bound() {
+        Bound::Included(end) => key <= end,
+        Bound::Excluded(end) => key < end,
+        Bound::Unbounded => true
+    }
+}
@@ -0,0 +1,288 @@
+use alloc::{format, vec};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::ops::Range;
+
+use crate::arena::*;
+use crate::arena::prelude::*;
+use crate::visualize::{DotWriter, ToDot};
+
+type Id = GenerationalId;
+
+#[derive(Debug, Clone)]
+struct IntervalNode<K: Send + Sync, V: Send + Sync> {
+    pub id: Id,
+
+    pub interval: Range<K>,
+    pub value: V,
+
+    /// The largest end of any interval in the subtree rooted at this node.
+    pub max_end: K,
+
+    pub height: usize,
+
+    pub left: Option<Id>,
+    pub right: Option<Id>
+}
+
+impl<K: Send + Sync, V: Send + Sync> HasId for IntervalNode<K, V> {
+    type Id = Id;
+
+    fn get_id(&self) -> Self::Id {
+        self.id
+    }
+}
+
+impl<K: Clone + Send + Sync, V: Send + Sync> IntervalNode<K, V> {
+    /// Constructs a new leaf from the given arguments
+    pub fn new(id: Id, interval: Range<K>, value: V) -> Self {
+        Self {
+            id,
+            max_end: interval.end.clone(),
+            interval,
+            value,
+            height: 1,
+            left: None,
+            right: None
+        }
+    }
+}
+
+/// This class represents a thread-safe interval tree, which finds all of the stored intervals
+/// containing a point or overlapping another interval.
+///
+/// Intervals are half-open, i.e. 'start..end' contains 'start' but not 'end'. The tree is an AVL
+/// tree ordered by the start of each interval, where every node also records the largest end in
+/// its subtree so that subtrees which end before the query can be skipped.
+pub struct IntervalTree<K: Ord + Clone + Send + Sync, V: Send + Sync> {
+    arena: GenerationalArena<IntervalNode<K, V>>,
+    root: Option<Id>,
+    size: usize
+}
+
+impl<K: Ord + Clone + Send + Sync, V: Send + Sync> Default for IntervalTree<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Clone + Send + Sync, V: Send + Sync> IntervalTree<K, V> {
+
+    /// Constructs a new empty IntervalTree
+    pub fn new() -> Self {
+        Self {
+            arena: GenerationalArena::new(),
+            root: None,
+            size: 0
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Inserts the interval, which may be equal to or overlap intervals already in the tree.
+    pub fn insert(&mut self, interval: Range<K>, value: V) {
+        let root = self.root;
+        self.root = Some(self._insert(root, interval, value));
+        self.size += 1;
+    }
+
+    fn _insert(&mut self, node_id: Option<Id>, interval: Range<K>, value: V) -> Id {
+        let id = match node_id {
+            None => {
+                let id = self.arena.get_new_id();
+                self.arena.add_node(IntervalNode::new(id, interval, value)).expect("could not add node!");
+                return id;
+            }
+            Some(id) => id
+        };
+
+        let node_ref = self.node(&id);
+        let (go_left, left, right) = {
+            let node = node_ref.read().unwrap();
+            (interval.start < node.interval.start, node.left, node.right)
+        };
+
+        // Intervals with equal starts go to the right, so they are visited in insertion order.
+        if go_left {
+            let child = self._insert(left, interval, value);
+            node_ref.write().unwrap().left = Some(child);
+        } else {
+            let child = self._insert(right, interval, value);
+            node_ref.write().unwrap().right = Some(child);
+        }
+
+        self.rebalance(id)
+    }
+
+    /// Restores the AVL invariant at the given node, returning the new root of its subtree.
+    fn rebalance(&self, node_id: Id) -> Id {
+        self.update(&node_id);
+
+        let (left, right) = self.children(&node_id);
+        let balance = self.height_of(&left) as isize - self.height_of(&right) as isize;
+
+        if balance > 1 {
+            let left = left.unwrap();
+            if self.balance_of(&left) < 0 {
+                let left = self.rotate_left(left);
+                self.node(&node_id).write().unwrap().left = Some(left);
+            }
+            self.rotate_right(node_id)
+        } else if balance < -1 {
+            let right = right.unwrap();
+            if self.balance_of(&right) > 0 {
+                let right = self.rotate_right(right);
+                self.node(&node_id).write().unwrap().right = Some(right);
+            }
+            self.rotate_left(node_id)
+        } else {
+            node_id
+        }
+    }
+
+    fn rotate_left(&self, node_id: Id) -> Id {
+        let node_ref = self.node(&node_id);
+        let right_id = node_ref.read().unwrap().right.expect("rotation requires a right child");
+        let right_ref = self.node(&right_id);
+
+        let moved = right_ref.read().unwrap().left;
+        node_ref.write().unwrap().right = moved;
+        right_ref.write().unwrap().left = Some(node_id);
+
+        self.update(&node_id);
+        self.update(&right_id);
+        right_id
+    }
+
+    fn rotate_right(&self, node_id: Id) -> Id {
+        let node_ref = self.node(&node_id);
+        let left_id = node_ref.read().unwrap().left.expect("rotation requires a left child");
+        let left_ref = self.node(&left_id);
+
+        let moved = left_ref.read().unwrap().right;
+        node_ref.write().unwrap().left = moved;
+        left_ref.write().unwrap().right = Some(node_id);
+
+        self.update(&node_id);
+        self.update(&left_id);
+        left_id
+    }
+
+    /// Recomputes the height and largest end of the given node from its children.
+    fn update(&self, node_id: &Id) {
+        let (left, right) = self.children(node_id);
+        let height = 1 + self.height_of(&left).max(self.height_of(&right));
+
+        let node_ref = self.node(node_id);
+        let mut max_end = node_ref.read().unwrap().interval.end.clone();
+        for child in [left, right].iter().flatten() {
+            let child_end = self.node(child).read().unwrap().max_end.clone();
+            if child_end > max_end {
+                max_end = child_end;
+            }
+        }
+
+        let mut node = node_ref.write().unwrap();
+        node.height = height;
+        node.max_end = max_end;
+    }
+
+    fn balance_of(&self, node_id: &Id) -> isize {
+        let (left, right) = self.children(node_id);
+        self.height_of(&left) as isize - self.height_of(&right) as isize
+    }
+
+    fn children(&self, node_id: &Id) -> (Option<Id>, Option<Id>) {
+        let node_ref = self.node(node_id);
+        let node = node_ref.read().unwrap();
+        (node.left, node.right)
+    }
+
+    fn height_of(&self, node_id: &Option<Id>) -> usize {
+        node_id.map_or(0, |id| self.node(&id).read().unwrap().height)
+    }
+
+    fn node(&self, node_id: &Id) -> SharedRef<IntervalNode<K, V>> {
+        self.arena.get_node(node_id).expect("could not find node")
+    }
+}
+
+impl<K: Ord + Clone + Send + Sync, V: Clone + Send + Sync> IntervalTree<K, V> {
+
+    /// Returns all intervals containing 'k' along with their values, ordered by their starts.
+    pub fn query_point(&self, k: &K) -> impl Iterator<Item = (Range<K>, V)> {
+        let mut result = vec![];
+        self._query(&self.root, k, &|start| start <= k, &mut result);
+        result.into_iter()
+    }
+
+    /// Returns all intervals overlapping 'range' along with their values, ordered by their starts.
+    pub fn query_overlapping(&self, range: &Range<K>) -> impl Iterator<Item = (Range<K>, V)> {
+        let mut result = vec![];
+        if range.start < range.end {
+            self._query(&self.root, &range.start, &|start| *start < range.end, &mut result);
+        }
+        result.into_iter()
+    }
+
+    /// Visits every interval which ends after 'lo' and whose start satisfies 'starts_in'. Since
+    /// 'starts_in' only ever rejects large starts, the right subtree is skipped once it fails.
+    fn _query(
+        &self,
+        node_id: &Option<Id>,
+        lo: &K,
+        starts_in: &dyn Fn(&K) -> bool,
+        out: &mut Vec<(Range<K>, V)>
+    ) {
+        let node_ref = match node_id {
+            None => return,
+            Some(id) => self.node(id)
+        };
+        let node = node_ref.read().unwrap();
+
+        // If every interval in this subtree ends before 'lo', none of them can match.
+        if node.max_end <= *lo {
+            return;
+        }
+
+        self._query(&node.left, lo, starts_in, out);
+
+        if starts_in(&node.interval.start) {
+            if *lo < node.interval.end {
+                out.push((node.interval.clone(), node.value.clone()));
+            }
+            self._query(&node.right, lo, starts_in, out);
+        }
+    }
+}
+
+impl<K: Ord + Clone + Debug + Send + Sync, V: Debug + Send + Sync> ToDot for IntervalTree<K, V> {
+    fn to_dot(&self) -> String {
+        let mut dot = DotWriter::new("IntervalTree");
+        let mut stack: Vec<Id> = self.root.into_iter().collect();
+
+        while let Some(id) = stack.pop() {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+
+            let label = format!("{:?}: {:?}\nmax end {:?}", node.interval, node.value, node.max_end);
+            dot.node(&id, &label, "");
+
+            for (child, name) in [(node.left, "L"), (node.right, "R")] {
+                if let Some(child_id) = child {
+                    dot.edge(&id, &child_id, name, "");
+                    stack.push(child_id);
+                }
+            }
+        }
+
+        dot.finish()
+    }
+}
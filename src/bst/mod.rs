@@ -0,0 +1,441 @@
+pub mod augment;
+pub mod avl;
+pub mod interval;
+pub mod rbtree;
+pub mod splay;
+pub mod treap;
+
+pub(crate) mod bounds;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::bst::augment::*;
+    use crate::bst::avl::*;
+    use crate::bst::interval::*;
+    use crate::bst::rbtree::*;
+    use crate::bst::splay::*;
+    use crate::bst::treap::*;
+
+    #[test]
+    fn test_avl_tree() {
+        let mut tree = AvlTree::<i32, String>::new();
+        assert!(tree.is_empty());
+        assert!(tree.get(&1).is_none());
+        assert!(tree.delete(&1).is_none());
+
+        assert!(tree.insert(5, String::from("five")).is_none());
+        assert!(tree.insert(2, String::from("two")).is_none());
+        assert!(tree.insert(8, String::from("eight")).is_none());
+        assert_eq!(tree.insert(2, String::from("deux")), Some(String::from("two")));
+        assert_eq!(tree.len(), 3);
+
+        assert_eq!(tree.get(&2), Some(String::from("deux")));
+        assert!(tree.contains_key(&8));
+        assert!(!tree.contains_key(&3));
+
+        assert_eq!(tree.delete(&5), Some(String::from("five")));
+        assert!(tree.delete(&5).is_none());
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree.iter().map(|(k, _)| k).collect::<Vec<_>>(), vec![2, 8]);
+    }
+
+    #[test]
+    fn test_avl_tree_against_btreemap() {
+        let mut tree = AvlTree::<u32, u32>::new();
+        let mut expected = BTreeMap::new();
+
+        // A simple LCG, so the test is deterministic without pulling in a dependency.
+        let mut state = 12345u32;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) % 500
+        };
+
+        for i in 0..2000 {
+            let key = next();
+            if i % 3 == 0 {
+                assert_eq!(tree.delete(&key), expected.remove(&key));
+            } else {
+                assert_eq!(tree.insert(key, i), expected.insert(key, i));
+            }
+        }
+
+        assert_eq!(tree.len(), expected.len());
+        assert_eq!(tree.iter().collect::<Vec<_>>(), expected.clone().into_iter().collect::<Vec<_>>());
+
+        // An AVL tree is never more than ~1.44 times taller than a perfectly balanced tree.
+        let bound = 1.44 * ((tree.len() + 2) as f64).log2();
+        assert!((tree.height() as f64) <= bound);
+
+        for (rank, (key, value)) in expected.iter().enumerate() {
+            assert_eq!(tree.rank(key), rank);
+            assert_eq!(tree.select(rank), Some((*key, *value)));
+        }
+        assert!(tree.select(expected.len()).is_none());
+
+        assert_eq!(
+            tree.range(100..200).collect::<Vec<_>>(),
+            expected.range(100..200).map(|(k, v)| (*k, *v)).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            tree.range(..=50).collect::<Vec<_>>(),
+            expected.range(..=50).map(|(k, v)| (*k, *v)).collect::<Vec<_>>()
+        );
+        assert_eq!(tree.range(600..).count(), 0);
+    }
+
+    #[test]
+    fn test_rb_tree() {
+        let mut tree = RbTree::<i32, &str>::new();
+        assert!(tree.is_empty());
+        assert!(tree.first().is_none());
+        assert!(tree.remove(&1).is_none());
+
+        assert!(tree.insert(5, "five").is_none());
+        assert!(tree.insert(2, "two").is_none());
+        assert!(tree.insert(8, "eight").is_none());
+        assert_eq!(tree.insert(2, "deux"), Some("two"));
+        assert_eq!(tree.len(), 3);
+
+        assert_eq!(tree.get(&2), Some("deux"));
+        assert_eq!(tree.first(), Some((2, "deux")));
+        assert_eq!(tree.last(), Some((8, "eight")));
+
+        assert_eq!(tree.remove(&5), Some("five"));
+        assert!(tree.remove(&5).is_none());
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree.range(3..).collect::<Vec<_>>(), vec![(8, "eight")]);
+        assert_eq!(tree.iter().rev().map(|(k, _)| k).collect::<Vec<_>>(), vec![8, 2]);
+
+        assert_eq!(tree.remove(&2), Some("deux"));
+        assert_eq!(tree.remove(&8), Some("eight"));
+        assert!(tree.is_empty());
+        assert!(tree.last().is_none());
+    }
+
+    #[test]
+    fn test_rb_tree_against_btreemap() {
+        let mut tree = RbTree::<u32, u32>::new();
+        let mut expected = BTreeMap::new();
+
+        let mut state = 54321u32;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) % 500
+        };
+
+        for i in 0..4000 {
+            let key = next();
+            if i % 3 == 0 {
+                assert_eq!(tree.remove(&key), expected.remove(&key));
+            } else {
+                assert_eq!(tree.insert(key, i), expected.insert(key, i));
+            }
+            assert_eq!(tree.len(), expected.len());
+        }
+
+        assert_eq!(tree.iter().collect::<Vec<_>>(), expected.clone().into_iter().collect::<Vec<_>>());
+        assert_eq!(tree.first(), expected.first_key_value().map(|(k, v)| (*k, *v)));
+        assert_eq!(tree.last(), expected.last_key_value().map(|(k, v)| (*k, *v)));
+        assert_eq!(
+            tree.range(100..200).collect::<Vec<_>>(),
+            expected.range(100..200).map(|(k, v)| (*k, *v)).collect::<Vec<_>>()
+        );
+
+        // Draining the tree exercises every deletion case.
+        for key in expected.keys() {
+            assert!(tree.remove(key).is_some());
+        }
+        assert!(tree.is_empty());
+    }
+
+    /// Keeps the size of every subtree along with the largest value in it.
+    #[derive(Clone)]
+    struct SizeAndMax {
+        size: usize,
+        max: u32
+    }
+
+    impl Augmentation<u32, u32> for SizeAndMax {
+        fn compute(_: &u32, value: &u32, left: Option<&Self>, right: Option<&Self>) -> Self {
+            let children = [left, right];
+            let children = children.iter().flatten();
+
+            Self {
+                size: 1 + children.clone().map(|c| c.size).sum::<usize>(),
+                max: children.map(|c| c.max).fold(*value, u32::max)
+            }
+        }
+    }
+
+    impl CountsNodes for SizeAndMax {
+        fn subtree_size(&self) -> usize {
+            self.size
+        }
+    }
+
+    #[test]
+    fn test_rb_tree_order_statistics() {
+        let mut tree = RbTree::<u32, u32, SubtreeSize>::new();
+        assert_eq!(tree.rank(&10), 0);
+        assert!(tree.select(0).is_none());
+        assert!(tree.augmentation().is_none());
+
+        let mut augmented = RbTree::<u32, u32, SizeAndMax>::new();
+        let mut expected = BTreeMap::new();
+
+        let mut state = 777u32;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) % 300
+        };
+
+        for i in 0..3000 {
+            let key = next();
+            let value = next();
+            if i % 3 == 0 {
+                assert_eq!(tree.remove(&key), expected.remove(&key));
+                augmented.remove(&key);
+            } else {
+                tree.insert(key, value);
+                augmented.insert(key, value);
+                expected.insert(key, value);
+            }
+
+            assert_eq!(tree.augmentation().map_or(0, |size| size.0), expected.len());
+            let summary = augmented.augmentation();
+            assert_eq!(summary.as_ref().map_or(0, |s| s.size), expected.len());
+            assert_eq!(summary.map(|s| s.max), expected.values().max().copied());
+        }
+
+        let entries: Vec<(u32, u32)> = expected.into_iter().collect();
+        for (rank, (key, value)) in entries.iter().enumerate() {
+            assert_eq!(tree.select(rank), Some((*key, *value)));
+            assert_eq!(augmented.select(rank), Some((*key, *value)));
+            assert_eq!(tree.rank(key), rank);
+            assert_eq!(tree.rank(&(key + 1)), entries.iter().filter(|(k, _)| *k <= *key).count());
+        }
+        assert!(tree.select(entries.len()).is_none());
+        assert_eq!(tree.rank(&1000), entries.len());
+    }
+
+    #[test]
+    fn test_interval_tree() {
+        let mut tree = IntervalTree::<u32, &str>::new();
+        assert!(tree.query_point(&5).next().is_none());
+
+        tree.insert(10..20, "a");
+        tree.insert(15..25, "b");
+        tree.insert(0..5, "c");
+        tree.insert(10..20, "d");
+        tree.insert(30..40, "e");
+        assert_eq!(tree.len(), 5);
+
+        assert_eq!(tree.query_point(&12).map(|(_, v)| v).collect::<Vec<_>>(), vec!["a", "d"]);
+        assert_eq!(tree.query_point(&20).map(|(_, v)| v).collect::<Vec<_>>(), vec!["b"]);
+        assert_eq!(tree.query_point(&0).map(|(_, v)| v).collect::<Vec<_>>(), vec!["c"]);
+        assert!(tree.query_point(&5).next().is_none());
+        assert!(tree.query_point(&40).next().is_none());
+
+        assert_eq!(tree.query_overlapping(&(4..11)).map(|(_, v)| v).collect::<Vec<_>>(), vec!["c", "a", "d"]);
+        assert_eq!(tree.query_overlapping(&(20..31)).map(|(_, v)| v).collect::<Vec<_>>(), vec!["b", "e"]);
+        assert!(tree.query_overlapping(&(25..30)).next().is_none());
+        assert!(tree.query_overlapping(&(12..12)).next().is_none());
+    }
+
+    #[test]
+    fn test_interval_tree_against_brute_force() {
+        let mut tree = IntervalTree::<u32, usize>::new();
+        let mut intervals = vec![];
+
+        let mut state = 99u32;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) % 1000
+        };
+
+        for i in 0..500 {
+            let start = next();
+            let interval = start..start + 1 + next() % 50;
+            tree.insert(interval.clone(), i);
+            intervals.push((interval, i));
+        }
+
+        for _ in 0..200 {
+            let k = next();
+            let mut expected: Vec<usize> = intervals.iter()
+                .filter(|(r, _)| r.contains(&k))
+                .map(|(_, i)| *i)
+                .collect();
+            let mut found: Vec<usize> = tree.query_point(&k).map(|(_, i)| i).collect();
+            expected.sort();
+            found.sort();
+            assert_eq!(found, expected);
+
+            let query = k..k + 1 + next() % 30;
+            let mut expected: Vec<usize> = intervals.iter()
+                .filter(|(r, _)| r.start < query.end && query.start < r.end)
+                .map(|(_, i)| *i)
+                .collect();
+            let found: Vec<(std::ops::Range<u32>, usize)> = tree.query_overlapping(&query).collect();
+            assert!(found.windows(2).all(|w| w[0].0.start <= w[1].0.start));
+
+            let mut found: Vec<usize> = found.into_iter().map(|(_, i)| i).collect();
+            expected.sort();
+            found.sort();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn test_treap() {
+        let mut treap = Treap::<i32, String>::new();
+        assert!(treap.is_empty());
+        assert!(treap.remove(&1).is_none());
+
+        for key in [5, 2, 8, 1, 9, 3] {
+            assert!(treap.insert(key, key.to_string()).is_none());
+        }
+        assert_eq!(treap.insert(3, "three".to_string()), Some("3".to_string()));
+        assert_eq!(treap.len(), 6);
+        assert_eq!(treap.get(&3), Some("three".to_string()));
+        assert_eq!(treap.first(), Some((1, "1".to_string())));
+        assert_eq!(treap.last(), Some((9, "9".to_string())));
+        assert_eq!(treap.rank(&5), 3);
+        assert_eq!(treap.select(3), Some((5, "5".to_string())));
+
+        let (left, right) = treap.split(&5);
+        assert!(treap.is_empty());
+        assert_eq!(left.iter().map(|(k, _)| k).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(right.iter().map(|(k, _)| k).collect::<Vec<_>>(), vec![5, 8, 9]);
+
+        let mut merged = Treap::merge(left, right);
+        assert_eq!(merged.len(), 6);
+        assert_eq!(merged.iter().map(|(k, _)| k).collect::<Vec<_>>(), vec![1, 2, 3, 5, 8, 9]);
+        assert_eq!(merged.remove(&5), Some("5".to_string()));
+        assert_eq!(merged.range(2..9).map(|(k, _)| k).collect::<Vec<_>>(), vec![2, 3, 8]);
+
+        // Splitting outside of the keys leaves one side empty.
+        let (left, right) = merged.split(&0);
+        assert!(left.is_empty());
+        assert_eq!(right.len(), 5);
+        let merged = Treap::merge(right, left);
+        assert_eq!(merged.len(), 5);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_treap_merge_overlapping() {
+        let mut a = Treap::<i32, ()>::new();
+        let mut b = Treap::<i32, ()>::new();
+        a.insert(5, ());
+        b.insert(3, ());
+        Treap::merge(a, b);
+    }
+
+    #[test]
+    fn test_treap_against_btreemap() {
+        let mut treap = Treap::<u32, u32>::with_seed(42);
+        let mut map = BTreeMap::new();
+
+        let mut state: u32 = 11;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) % 500
+        };
+
+        for i in 0..2000 {
+            let key = next();
+            match next() % 4 {
+                0 => assert_eq!(treap.remove(&key), map.remove(&key)),
+                1 => {
+                    // Split off and merge back in, which must not change the contents.
+                    let (left, right) = treap.split(&key);
+                    assert_eq!(left.len(), map.range(..key).count());
+                    assert_eq!(right.len(), map.range(key..).count());
+                    treap = Treap::merge(left, right);
+                }
+                _ => assert_eq!(treap.insert(key, i), map.insert(key, i))
+            }
+            assert_eq!(treap.len(), map.len());
+        }
+
+        assert!(treap.iter().eq(map.iter().map(|(k, v)| (*k, *v))));
+        for key in 0..500 {
+            assert_eq!(treap.get(&key), map.get(&key).copied());
+            assert_eq!(treap.rank(&key), map.range(..key).count());
+        }
+    }
+
+    #[test]
+    fn test_splay_tree() {
+        let mut tree = SplayTree::<i32, String>::new();
+        assert!(tree.is_empty());
+        assert!(tree.get(&1).is_none());
+        assert!(tree.remove(&1).is_none());
+        assert!(!tree.splay(&1));
+        assert!(tree.first().is_none());
+
+        for key in [5, 2, 8, 1, 9, 3] {
+            assert!(tree.insert(key, key.to_string()).is_none());
+            assert_eq!(tree.root().map(|(k, _)| k), Some(key));
+        }
+        assert_eq!(tree.insert(2, String::from("deux")), Some(String::from("2")));
+        assert_eq!(tree.len(), 6);
+
+        // Every access brings the key to the root.
+        assert_eq!(tree.get(&8), Some(String::from("8")));
+        assert_eq!(tree.root(), Some((8, String::from("8"))));
+        assert!(tree.contains_key(&2));
+        assert_eq!(tree.root().unwrap().0, 2);
+
+        // Splaying for a missing key brings up one of its neighbours instead.
+        assert!(!tree.splay(&4));
+        assert!([3, 5].contains(&tree.root().unwrap().0));
+
+        assert_eq!(tree.first(), Some((1, String::from("1"))));
+        assert_eq!(tree.last(), Some((9, String::from("9"))));
+        assert_eq!(tree.root().unwrap().0, 9);
+
+        assert_eq!(tree.remove(&5), Some(String::from("5")));
+        assert_eq!(tree.remove(&5), None);
+        assert_eq!(tree.iter().map(|(k, _)| k).collect::<Vec<_>>(), vec![1, 2, 3, 8, 9]);
+
+        // Sorted inserts make a path as long as the tree, which splaying has to cope with.
+        let mut tree = SplayTree::<u32, ()>::new();
+        for key in 0..100_000 {
+            tree.insert(key, ());
+        }
+        assert!(tree.contains_key(&0));
+        assert_eq!(tree.iter().count(), 100_000);
+        assert_eq!(tree.first(), Some((0, ())));
+    }
+
+    #[test]
+    fn test_splay_tree_against_btreemap() {
+        let mut tree = SplayTree::<u32, u32>::new();
+        let mut map = BTreeMap::new();
+
+        let mut state: u32 = 5;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) % 300
+        };
+
+        for i in 0..3000 {
+            let key = next();
+            match next() % 4 {
+                0 => assert_eq!(tree.remove(&key), map.remove(&key)),
+                1 => assert_eq!(tree.get(&key), map.get(&key).copied()),
+                _ => assert_eq!(tree.insert(key, i), map.insert(key, i))
+            }
+            assert_eq!(tree.len(), map.len());
+        }
+
+        assert!(tree.iter().eq(map.iter().map(|(k, v)| (*k, *v))));
+        assert_eq!(tree.first(), map.first_key_value().map(|(k, v)| (*k, *v)));
+        assert_eq!(tree.last(), map.last_key_value().map(|(k, v)| (*k, *v)));
+    }
+}
@@ -0,0 +1,576 @@
+use alloc::{format, vec};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt::Debug;
+use core::ops::RangeBounds;
+use core::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+use crate::arena::*;
+use crate::arena::prelude::*;
+use crate::bst::augment::*;
+use crate::bst::bounds::*;
+use crate::visualize::{DotWriter, ToDot};
+
+type Id = GenerationalId;
+
+#[derive(Debug, Clone)]
+struct RbNode<K: Send + Sync, V: Send + Sync, A> {
+    pub id: Id,
+
+    pub key: K,
+    pub value: V,
+
+    /// The color of the link from the parent to this node.
+    pub red: bool,
+
+    /// The Augmentation of the subtree rooted at this node.
+    pub augmentation: A,
+
+    pub left: Option<Id>,
+    pub right: Option<Id>
+}
+
+impl<K: Send + Sync, V: Send + Sync, A: Send + Sync> HasId for RbNode<K, V, A> {
+    type Id = Id;
+
+    fn get_id(&self) -> Self::Id {
+        self.id
+    }
+}
+
+impl<K: Send + Sync, V: Send + Sync, A: Augmentation<K, V>> RbNode<K, V, A> {
+    /// Constructs a new red leaf from the given arguments
+    pub fn new(id: Id, key: K, value: V) -> Self {
+        Self {
+            id,
+            augmentation: A::compute(&key, &value, None, None),
+            key,
+            value,
+            red: true,
+            left: None,
+            right: None
+        }
+    }
+}
+
+/// This class represents a thread-safe ordered map, implemented as a left-leaning red-black tree.
+///
+/// Every 3-node of the equivalent 2-3 tree is represented by a red link, which always leans left.
+/// This keeps the tree balanced with far fewer cases to handle than a classic red-black tree.
+///
+/// Every node can carry an Augmentation of its subtree, which is kept up to date as the tree
+/// changes. With 'SubtreeSize' (or any Augmentation which 'CountsNodes') the tree supports order
+/// statistics through 'rank' and 'select' in O(log n).
+pub struct RbTree<K: Ord + Send + Sync, V: Send + Sync, A: Augmentation<K, V> = ()> {
+    arena: GenerationalArena<RbNode<K, V, A>>,
+    root: Option<Id>,
+    size: AtomicUsize
+}
+
+impl<K: Ord + Send + Sync, V: Send + Sync, A: Augmentation<K, V>> Default for RbTree<K, V, A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Send + Sync, V: Send + Sync, A: Augmentation<K, V>> RbTree<K, V, A> {
+
+    /// Constructs a new empty RbTree
+    pub fn new() -> Self {
+        Self {
+            arena: GenerationalArena::new(),
+            root: None,
+            size: AtomicUsize::new(0)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.size.load(AtomicOrdering::SeqCst)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Inserts the key, returning the previous value if it already exists.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let root = self.root;
+        let (root, prev) = self._insert(root, key, value);
+
+        self.node(&root).write().unwrap().red = false;
+        self.root = Some(root);
+
+        if prev.is_none() {
+            self.size.fetch_add(1, AtomicOrdering::SeqCst);
+        }
+        prev
+    }
+
+    /// Removes the key, returning its value if it existed.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        if !self.contains_key(key) {
+            return None;
+        }
+
+        let root = self.root.unwrap();
+        let (left, right) = self.children(&root);
+        if !self.is_red(&left) && !self.is_red(&right) {
+            self.node(&root).write().unwrap().red = true;
+        }
+
+        let (root, removed) = self._remove(root, key);
+        if let Some(root) = root {
+            self.node(&root).write().unwrap().red = false;
+        }
+        self.root = root;
+
+        self.size.fetch_sub(1, AtomicOrdering::SeqCst);
+        Some(removed)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self._find_node(key).is_some()
+    }
+
+    /// Returns the Augmentation of the whole tree, i.e. that of its root.
+    pub fn augmentation(&self) -> Option<A> {
+        self.root.map(|id| self.node(&id).read().unwrap().augmentation.clone())
+    }
+
+    /// Returns the number of keys in the tree which are smaller than 'key'.
+    pub fn rank(&self, key: &K) -> usize
+        where A: CountsNodes
+    {
+        let mut rank = 0;
+        let mut node_id = self.root;
+
+        while let Some(id) = node_id {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+
+            if *key <= node.key {
+                node_id = node.left;
+            } else {
+                rank += self.size_of(&node.left) + 1;
+                node_id = node.right;
+            }
+        }
+
+        rank
+    }
+
+    fn _insert(&mut self, node_id: Option<Id>, key: K, value: V) -> (Id, Option<V>) {
+        let id = match node_id {
+            None => {
+                let id = self.arena.get_new_id();
+                self.arena.add_node(RbNode::new(id, key, value)).expect("could not add node!");
+                return (id, None);
+            }
+            Some(id) => id
+        };
+
+        let node_ref = self.node(&id);
+        let (ordering, left, right) = {
+            let node = node_ref.read().unwrap();
+            (key.cmp(&node.key), node.left, node.right)
+        };
+
+        let prev = match ordering {
+            Ordering::Equal => {
+                let prev = core::mem::replace(&mut node_ref.write().unwrap().value, value);
+                self.update(&id);
+                return (id, Some(prev));
+            }
+            Ordering::Less => {
+                let (child, prev) = self._insert(left, key, value);
+                node_ref.write().unwrap().left = Some(child);
+                prev
+            }
+            Ordering::Greater => {
+                let (child, prev) = self._insert(right, key, value);
+                node_ref.write().unwrap().right = Some(child);
+                prev
+            }
+        };
+
+        (self.balance(id), prev)
+    }
+
+    /// Removes the key from the given subtree, which must contain it, returning the new root of
+    /// the subtree and the removed value.
+    fn _remove(&mut self, node_id: Id, key: &K) -> (Option<Id>, V) {
+        let mut id = node_id;
+
+        let removed = if *key < self.node(&id).read().unwrap().key {
+            // --
+            // Make sure the left child isn't a 2-node before descending into it.
+            let (left, _) = self.children(&id);
+            if !self.is_red(&left) && !self.is_red(&self.children(&left.unwrap()).0) {
+                id = self.move_red_left(id);
+            }
+
+            let (left, _) = self.children(&id);
+            let (child, removed) = self._remove(left.unwrap(), key);
+            self.node(&id).write().unwrap().left = child;
+            removed
+        } else {
+            if self.is_red(&self.children(&id).0) {
+                id = self.rotate_right(id);
+            }
+
+            let (_, right) = self.children(&id);
+            if *key == self.node(&id).read().unwrap().key && right.is_none() {
+                return (None, self.take_node(&id).value);
+            }
+
+            // --
+            // Make sure the right child isn't a 2-node before descending into it.
+            let right = right.unwrap();
+            if !self.is_red(&Some(right)) && !self.is_red(&self.children(&right).0) {
+                id = self.move_red_right(id);
+            }
+
+            let (left, right) = self.children(&id);
+            if *key == self.node(&id).read().unwrap().key {
+                // --
+                // Put the smallest node of the right subtree in place of this one.
+                let (rest, min_id) = self._remove_min(right.unwrap());
+                let red = self.node(&id).read().unwrap().red;
+
+                let min_ref = self.node(&min_id);
+                let mut min = min_ref.write().unwrap();
+                min.left = left;
+                min.right = rest;
+                min.red = red;
+                drop(min);
+
+                let removed = self.take_node(&id).value;
+                id = min_id;
+                removed
+            } else {
+                let (child, removed) = self._remove(right.unwrap(), key);
+                self.node(&id).write().unwrap().right = child;
+                removed
+            }
+        };
+
+        (Some(self.balance(id)), removed)
+    }
+
+    /// Detaches the smallest node of the given subtree, returning the new root of the subtree and
+    /// the id of the detached node.
+    fn _remove_min(&mut self, node_id: Id) -> (Option<Id>, Id) {
+        let mut id = node_id;
+
+        let (left, _) = self.children(&id);
+        let left = match left {
+            // In a left-leaning tree a node without a left child has no right child either.
+            None => return (None, id),
+            Some(left) => left
+        };
+
+        if !self.is_red(&Some(left)) && !self.is_red(&self.children(&left).0) {
+            id = self.move_red_left(id);
+        }
+
+        let (left, _) = self.children(&id);
+        let (rest, min_id) = self._remove_min(left.unwrap());
+        self.node(&id).write().unwrap().left = rest;
+
+        (Some(self.balance(id)), min_id)
+    }
+
+    /// Restores the left-leaning invariants at the given node, returning the new root of its
+    /// subtree.
+    fn balance(&self, node_id: Id) -> Id {
+        let mut id = node_id;
+
+        let (left, right) = self.children(&id);
+        if self.is_red(&right) && !self.is_red(&left) {
+            id = self.rotate_left(id);
+        }
+
+        let (left, _) = self.children(&id);
+        if self.is_red(&left) && self.is_red(&self.children(&left.unwrap()).0) {
+            id = self.rotate_right(id);
+        }
+
+        let (left, right) = self.children(&id);
+        if self.is_red(&left) && self.is_red(&right) {
+            self.flip_colors(&id);
+        }
+
+        self.update(&id);
+        id
+    }
+
+    /// Assuming the node is red and both its children are 2-nodes, makes its left child or one of
+    /// the left child's children red.
+    fn move_red_left(&self, node_id: Id) -> Id {
+        let mut id = node_id;
+        self.flip_colors(&id);
+
+        let right = self.children(&id).1.unwrap();
+        if self.is_red(&self.children(&right).0) {
+            let right = self.rotate_right(right);
+            self.node(&id).write().unwrap().right = Some(right);
+            id = self.rotate_left(id);
+            self.flip_colors(&id);
+        }
+
+        id
+    }
+
+    /// Assuming the node is red and both its children are 2-nodes, makes its right child or one
+    /// of the right child's children red.
+    fn move_red_right(&self, node_id: Id) -> Id {
+        let mut id = node_id;
+        self.flip_colors(&id);
+
+        let left = self.children(&id).0.unwrap();
+        if self.is_red(&self.children(&left).0) {
+            id = self.rotate_right(id);
+            self.flip_colors(&id);
+        }
+
+        id
+    }
+
+    fn rotate_left(&self, node_id: Id) -> Id {
+        let node_ref = self.node(&node_id);
+        let right_id = node_ref.read().unwrap().right.expect("rotation requires a right child");
+        let right_ref = self.node(&right_id);
+
+        let mut node = node_ref.write().unwrap();
+        let mut right = right_ref.write().unwrap();
+
+        node.right = right.left;
+        right.left = Some(node_id);
+        right.red = node.red;
+        node.red = true;
+
+        drop((node, right));
+        self.update(&node_id);
+        self.update(&right_id);
+        right_id
+    }
+
+    fn rotate_right(&self, node_id: Id) -> Id {
+        let node_ref = self.node(&node_id);
+        let left_id = node_ref.read().unwrap().left.expect("rotation requires a left child");
+        let left_ref = self.node(&left_id);
+
+        let mut node = node_ref.write().unwrap();
+        let mut left = left_ref.write().unwrap();
+
+        node.left = left.right;
+        left.right = Some(node_id);
+        left.red = node.red;
+        node.red = true;
+
+        drop((node, left));
+        self.update(&node_id);
+        self.update(&left_id);
+        left_id
+    }
+
+    /// Flips the colors of the node and both of its children.
+    fn flip_colors(&self, node_id: &Id) {
+        let (left, right) = {
+            let node_ref = self.node(node_id);
+            let mut node = node_ref.write().unwrap();
+            node.red = !node.red;
+            (node.left, node.right)
+        };
+
+        for child in [left, right].iter().flatten() {
+            let child_ref = self.node(child);
+            let mut child = child_ref.write().unwrap();
+            child.red = !child.red;
+        }
+    }
+
+    /// Recomputes the Augmentation of the given node from its entry and its children.
+    fn update(&self, node_id: &Id) {
+        let (left, right) = self.children(node_id);
+        let left = left.map(|id| self.node(&id).read().unwrap().augmentation.clone());
+        let right = right.map(|id| self.node(&id).read().unwrap().augmentation.clone());
+
+        let node_ref = self.node(node_id);
+        let mut node = node_ref.write().unwrap();
+        node.augmentation = A::compute(&node.key, &node.value, left.as_ref(), right.as_ref());
+    }
+
+    fn size_of(&self, node_id: &Option<Id>) -> usize
+        where A: CountsNodes
+    {
+        node_id.map_or(0, |id| self.node(&id).read().unwrap().augmentation.subtree_size())
+    }
+
+    fn is_red(&self, node_id: &Option<Id>) -> bool {
+        node_id.is_some_and(|id| self.node(&id).read().unwrap().red)
+    }
+
+    fn children(&self, node_id: &Id) -> (Option<Id>, Option<Id>) {
+        let node_ref = self.node(node_id);
+        let node = node_ref.read().unwrap();
+        (node.left, node.right)
+    }
+
+    fn node(&self, node_id: &Id) -> SharedRef<RbNode<K, V, A>> {
+        self.arena.get_node(node_id).expect("could not find node")
+    }
+
+    /// Removes the node from the arena, handing back ownership of it.
+    fn take_node(&mut self, node_id: &Id) -> RbNode<K, V, A> {
+        let node_ref = self.node(node_id);
+        self.arena.delete_node(node_id).expect("could not delete node");
+
+        match Arc::try_unwrap(node_ref) {
+            Ok(node) => node.into_inner().unwrap(),
+            Err(_) => panic!("node is still referenced")
+        }
+    }
+
+    fn _find_node(&self, key: &K) -> Option<Id> {
+        let mut node_id = self.root;
+
+        while let Some(id) = node_id {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+
+            node_id = match key.cmp(&node.key) {
+                Ordering::Equal => return Some(id),
+                Ordering::Less => node.left,
+                Ordering::Greater => node.right
+            };
+        }
+
+        None
+    }
+
+    /// Returns the id of the node found by always following the given side from the root.
+    fn _find_extreme(&self, leftmost: bool) -> Option<Id> {
+        let mut node_id = self.root?;
+
+        loop {
+            let (left, right) = self.children(&node_id);
+            match if leftmost { left } else { right } {
+                None => return Some(node_id),
+                Some(id) => node_id = id
+            }
+        }
+    }
+}
+
+impl<K: Ord + Clone + Send + Sync, V: Clone + Send + Sync, A: Augmentation<K, V>> RbTree<K, V, A> {
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let id = self._find_node(key)?;
+        let value = self.node(&id).read().unwrap().value.clone();
+        Some(value)
+    }
+
+    /// Returns the smallest key along with its value.
+    pub fn first(&self) -> Option<(K, V)> {
+        self._find_extreme(true).map(|id| self.entry(&id))
+    }
+
+    /// Returns the largest key along with its value.
+    pub fn last(&self) -> Option<(K, V)> {
+        self._find_extreme(false).map(|id| self.entry(&id))
+    }
+
+    /// Returns the key at the given rank (i.e. the 'rank'-th smallest key) along with its value.
+    pub fn select(&self, rank: usize) -> Option<(K, V)>
+        where A: CountsNodes
+    {
+        let mut rank = rank;
+        let mut node_id = self.root;
+
+        while let Some(id) = node_id {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+            let left_size = self.size_of(&node.left);
+
+            match rank.cmp(&left_size) {
+                Ordering::Equal => return Some((node.key.clone(), node.value.clone())),
+                Ordering::Less => node_id = node.left,
+                Ordering::Greater => {
+                    rank -= left_size + 1;
+                    node_id = node.right;
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns all keys along with their values, in ascending order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (K, V)> {
+        self.range(..)
+    }
+
+    /// Returns all keys within the given range along with their values, in ascending order.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> impl DoubleEndedIterator<Item = (K, V)> {
+        let mut result = vec![];
+        self._range(&self.root, &range, &mut result);
+        result.into_iter()
+    }
+
+    fn entry(&self, node_id: &Id) -> (K, V) {
+        let node_ref = self.node(node_id);
+        let node = node_ref.read().unwrap();
+        (node.key.clone(), node.value.clone())
+    }
+
+    fn _range<R: RangeBounds<K>>(&self, node_id: &Option<Id>, range: &R, out: &mut Vec<(K, V)>) {
+        let node_ref = match node_id {
+            None => return,
+            Some(id) => self.node(id)
+        };
+        let node = node_ref.read().unwrap();
+
+        let after_start = after_start(&node.key, range);
+        let before_end = before_end(&node.key, range);
+
+        // Subtrees which lie entirely outside of the range are skipped.
+        if after_start {
+            self._range(&node.left, range, out);
+        }
+        if after_start && before_end {
+            out.push((node.key.clone(), node.value.clone()));
+        }
+        if before_end {
+            self._range(&node.right, range, out);
+        }
+    }
+}
+
+impl<K: Ord + Debug + Send + Sync, V: Debug + Send + Sync, A: Augmentation<K, V>> ToDot for RbTree<K, V, A> {
+    fn to_dot(&self) -> String {
+        let mut dot = DotWriter::new("RbTree");
+        let mut stack: Vec<Id> = self.root.into_iter().collect();
+
+        while let Some(id) = stack.pop() {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+
+            let attrs = if node.red { "color=red" } else { "" };
+            dot.node(&id, &format!("{:?}: {:?}", node.key, node.value), attrs);
+
+            // Links are colored like the nodes they lead to.
+            for (child, name) in [(node.left, "L"), (node.right, "R")] {
+                if let Some(child_id) = child {
+                    let attrs = if self.is_red(&child) { "color=red" } else { "" };
+                    dot.edge(&id, &child_id, name, attrs);
+                    stack.push(child_id);
+                }
+            }
+        }
+
+        dot.finish()
+    }
+}
@@ -0,0 +1,386 @@
+use alloc::{format, vec};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt::Debug;
+
+use crate::arena::*;
+use crate::arena::prelude::*;
+use crate::visualize::{DotWriter, ToDot};
+
+type Id = GenerationalId;
+
+#[derive(Debug, Clone)]
+struct SplayNode<K: Send + Sync, V: Send + Sync> {
+    pub id: Id,
+
+    pub key: K,
+    pub value: V,
+
+    pub left: Option<Id>,
+    pub right: Option<Id>
+}
+
+impl<K: Send + Sync, V: Send + Sync> HasId for SplayNode<K, V> {
+    type Id = Id;
+
+    fn get_id(&self) -> Self::Id {
+        self.id
+    }
+}
+
+impl<K: Send + Sync, V: Send + Sync> SplayNode<K, V> {
+    /// Constructs a new leaf from the given arguments
+    pub fn new(id: Id, key: K, value: V) -> Self {
+        Self {
+            id,
+            key,
+            value,
+            left: None,
+            right: None
+        }
+    }
+}
+
+/// This class represents a thread-safe ordered map, implemented as a splay tree.
+///
+/// Every access moves the accessed key to the root by a series of rotations (splaying), which also
+/// roughly halves the depth of the nodes along the way. The tree isn't balanced, but any sequence
+/// of operations takes O(log n) amortized time each, and keys which are accessed often stay close
+/// to the root. This makes the tree a good fit for skewed access patterns. Since lookups reshape
+/// the tree, they take '&mut self'.
+pub struct SplayTree<K: Ord + Send + Sync, V: Send + Sync> {
+    arena: GenerationalArena<SplayNode<K, V>>,
+    root: Option<Id>,
+    size: usize
+}
+
+impl<K: Ord + Send + Sync, V: Send + Sync> Default for SplayTree<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Send + Sync, V: Send + Sync> SplayTree<K, V> {
+
+    /// Constructs a new empty SplayTree
+    pub fn new() -> Self {
+        Self {
+            arena: GenerationalArena::new(),
+            root: None,
+            size: 0
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Inserts the key, returning the previous value if it already exists. The key ends up at the
+    /// root of the tree.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let (left, right) = match self.root {
+            None => (None, None),
+            Some(root) => {
+                let root = self._splay(root, &key);
+                self.root = Some(root);
+
+                // --
+                // The splayed root is either the key itself, or its predecessor or successor, in
+                // which case the new node takes its place with the root hanging off of one side.
+                let root_ref = self.node(&root);
+                let mut root_node = root_ref.write().unwrap();
+
+                match key.cmp(&root_node.key) {
+                    Ordering::Equal => return Some(core::mem::replace(&mut root_node.value, value)),
+                    Ordering::Less => (root_node.left.take(), Some(root)),
+                    Ordering::Greater => (Some(root), root_node.right.take())
+                }
+            }
+        };
+
+        let id = self.arena.get_new_id();
+        let mut node = SplayNode::new(id, key, value);
+        node.left = left;
+        node.right = right;
+
+        self.arena.add_node(node).expect("could not add node!");
+        self.root = Some(id);
+        self.size += 1;
+        None
+    }
+
+    /// Removes the key, returning its value if it existed.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        if !self.splay(key) {
+            return None;
+        }
+
+        let root = self.root.expect("tree is not empty");
+        let node = self.take_node(&root);
+
+        // Every key on the left is smaller than 'key', so splaying it for 'key' brings up its
+        // largest key, which is left without a right child.
+        self.root = match (node.left, node.right) {
+            (None, right) => right,
+            (Some(left), right) => {
+                let left = self._splay(left, key);
+                self.node(&left).write().unwrap().right = right;
+                Some(left)
+            }
+        };
+
+        self.size -= 1;
+        Some(node.value)
+    }
+
+    pub fn contains_key(&mut self, key: &K) -> bool {
+        self.splay(key)
+    }
+
+    /// Splays the tree for the given key, returning true if the key exists. Afterwards the key (or
+    /// if it doesn't exist, its predecessor or successor) is at the root.
+    pub fn splay(&mut self, key: &K) -> bool {
+        let root = match self.root {
+            None => return false,
+            Some(root) => self._splay(root, key)
+        };
+
+        self.root = Some(root);
+        let found = self.node(&root).read().unwrap().key == *key;
+        found
+    }
+
+    /// Splays the subtree rooted at the given node for 'key', returning the new root of the subtree.
+    ///
+    /// This is the top-down variant, which takes apart the path to 'key' on the way down: nodes
+    /// larger than 'key' are gathered into a right tree and nodes smaller into a left tree, which
+    /// are then hung off of the node the search stopped at. It doesn't need parent links, and
+    /// doesn't recurse, so long paths in degenerate trees are no problem.
+    fn _splay(&mut self, root: Id, key: &K) -> Id {
+        // The roots of the left and right trees, and the nodes they are assembled at.
+        let (mut left_root, mut left_max): (Option<Id>, Option<Id>) = (None, None);
+        let (mut right_root, mut right_min): (Option<Id>, Option<Id>) = (None, None);
+
+        let mut t = root;
+
+        loop {
+            let (ordering, left, right) = self.probe(&t, key);
+
+            match ordering {
+                Ordering::Equal => break,
+                Ordering::Less => {
+                    let mut child = match left {
+                        None => break,
+                        Some(id) => id
+                    };
+
+                    // --
+                    // Zig-zig: rotate right before going down another level on the left.
+                    let (child_ordering, child_left, _) = self.probe(&child, key);
+                    if child_ordering == Ordering::Less {
+                        self.rotate_right(&t, &child);
+                        t = child;
+                        child = match child_left {
+                            None => break,
+                            Some(id) => id
+                        };
+                    }
+
+                    // Link t into the right tree, as the new smallest node.
+                    match right_min {
+                        None => right_root = Some(t),
+                        Some(min) => self.node(&min).write().unwrap().left = Some(t)
+                    }
+                    right_min = Some(t);
+                    t = child;
+                }
+                Ordering::Greater => {
+                    let mut child = match right {
+                        None => break,
+                        Some(id) => id
+                    };
+
+                    // --
+                    // Zag-zag: rotate left before going down another level on the right.
+                    let (child_ordering, _, child_right) = self.probe(&child, key);
+                    if child_ordering == Ordering::Greater {
+                        self.rotate_left(&t, &child);
+                        t = child;
+                        child = match child_right {
+                            None => break,
+                            Some(id) => id
+                        };
+                    }
+
+                    // Link t into the left tree, as the new largest node.
+                    match left_max {
+                        None => left_root = Some(t),
+                        Some(max) => self.node(&max).write().unwrap().right = Some(t)
+                    }
+                    left_max = Some(t);
+                    t = child;
+                }
+            }
+        }
+
+        // --
+        // Reassemble: the children of t go to the inner edges of the left and right trees, which
+        // then become the children of t.
+        let t_ref = self.node(&t);
+        let mut t_node = t_ref.write().unwrap();
+
+        if let Some(max) = left_max {
+            self.node(&max).write().unwrap().right = t_node.left;
+            t_node.left = left_root;
+        }
+        if let Some(min) = right_min {
+            self.node(&min).write().unwrap().left = t_node.right;
+            t_node.right = right_root;
+        }
+
+        t
+    }
+
+    /// Compares 'key' against the key of the given node, also returning the node's children.
+    fn probe(&self, node_id: &Id, key: &K) -> (Ordering, Option<Id>, Option<Id>) {
+        let node_ref = self.node(node_id);
+        let node = node_ref.read().unwrap();
+        (key.cmp(&node.key), node.left, node.right)
+    }
+
+    /// Rotates 'child' (the left child of 'node') up into the place of 'node'.
+    fn rotate_right(&self, node_id: &Id, child_id: &Id) {
+        let child_ref = self.node(child_id);
+        let mut child = child_ref.write().unwrap();
+        self.node(node_id).write().unwrap().left = child.right;
+        child.right = Some(*node_id);
+    }
+
+    /// Rotates 'child' (the right child of 'node') up into the place of 'node'.
+    fn rotate_left(&self, node_id: &Id, child_id: &Id) {
+        let child_ref = self.node(child_id);
+        let mut child = child_ref.write().unwrap();
+        self.node(node_id).write().unwrap().right = child.left;
+        child.left = Some(*node_id);
+    }
+
+    fn node(&self, node_id: &Id) -> SharedRef<SplayNode<K, V>> {
+        self.arena.get_node(node_id).expect("could not find node")
+    }
+
+    /// Removes the node from the arena, handing back ownership of it.
+    fn take_node(&mut self, node_id: &Id) -> SplayNode<K, V> {
+        let node_ref = self.node(node_id);
+        self.arena.delete_node(node_id).expect("could not delete node");
+
+        match Arc::try_unwrap(node_ref) {
+            Ok(node) => node.into_inner().unwrap(),
+            Err(_) => panic!("node is still referenced")
+        }
+    }
+}
+
+impl<K: Ord + Clone + Send + Sync, V: Clone + Send + Sync> SplayTree<K, V> {
+
+    /// Returns the value for the key, splaying it to the root.
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        if !self.splay(key) {
+            return None;
+        }
+
+        let root = self.root.expect("tree is not empty");
+        let value = self.node(&root).read().unwrap().value.clone();
+        Some(value)
+    }
+
+    /// Returns the key at the root along with its value, i.e. the most recently accessed key.
+    pub fn root(&self) -> Option<(K, V)> {
+        self.root.map(|id| self.entry(&id))
+    }
+
+    /// Returns the smallest key along with its value, splaying it to the root.
+    pub fn first(&mut self) -> Option<(K, V)> {
+        let key = self.extreme(|node| node.left)?;
+        self.splay(&key);
+        self.root()
+    }
+
+    /// Returns the largest key along with its value, splaying it to the root.
+    pub fn last(&mut self) -> Option<(K, V)> {
+        let key = self.extreme(|node| node.right)?;
+        self.splay(&key);
+        self.root()
+    }
+
+    /// Returns all keys along with their values, in ascending order. This doesn't reshape the tree.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (K, V)> {
+        let mut result = Vec::with_capacity(self.size);
+        let mut stack = vec![];
+        let mut node_id = self.root;
+
+        // An explicit stack, since the tree can be as deep as it is large.
+        while node_id.is_some() || !stack.is_empty() {
+            while let Some(id) = node_id {
+                stack.push(id);
+                node_id = self.node(&id).read().unwrap().left;
+            }
+
+            let id = stack.pop().unwrap();
+            result.push(self.entry(&id));
+            node_id = self.node(&id).read().unwrap().right;
+        }
+
+        result.into_iter()
+    }
+
+    /// Returns the key found by following the given child link from the root for as long as
+    /// possible.
+    fn extreme<F: Fn(&SplayNode<K, V>) -> Option<Id>>(&self, next: F) -> Option<K> {
+        let mut node_id = self.root?;
+
+        loop {
+            let node_ref = self.node(&node_id);
+            let node = node_ref.read().unwrap();
+
+            match next(&node) {
+                None => return Some(node.key.clone()),
+                Some(id) => node_id = id
+            }
+        }
+    }
+
+    fn entry(&self, node_id: &Id) -> (K, V) {
+        let node_ref = self.node(node_id);
+        let node = node_ref.read().unwrap();
+        (node.key.clone(), node.value.clone())
+    }
+}
+
+impl<K: Ord + Debug + Send + Sync, V: Debug + Send + Sync> ToDot for SplayTree<K, V> {
+    fn to_dot(&self) -> String {
+        let mut dot = DotWriter::new("SplayTree");
+        let mut stack: Vec<Id> = self.root.into_iter().collect();
+
+        while let Some(id) = stack.pop() {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+
+            dot.node(&id, &format!("{:?}: {:?}", node.key, node.value), "");
+
+            for (child, name) in [(node.left, "L"), (node.right, "R")] {
+                if let Some(child_id) = child {
+                    dot.edge(&id, &child_id, name, "");
+                    stack.push(child_id);
+                }
+            }
+        }
+
+        dot.finish()
+    }
+}
@@ -0,0 +1,485 @@
+use alloc::{format, vec};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt::Debug;
+use core::ops::RangeBounds;
+
+use crate::arena::*;
+use crate::arena::prelude::*;
+use crate::bst::bounds::*;
+use crate::random::{Rng, XorShift};
+use crate::visualize::{DotWriter, ToDot};
+
+type Id = GenerationalId;
+
+#[derive(Debug, Clone)]
+struct TreapNode<K: Send + Sync, V: Send + Sync> {
+    pub id: Id,
+
+    pub key: K,
+    pub value: V,
+
+    /// Nodes are heap-ordered by priority, i.e. no node has a higher priority than its parent.
+    pub priority: u64,
+
+    /// The number of nodes in the subtree rooted at this node.
+    pub size: usize,
+
+    pub left: Option<Id>,
+    pub right: Option<Id>
+}
+
+impl<K: Send + Sync, V: Send + Sync> HasId for TreapNode<K, V> {
+    type Id = Id;
+
+    fn get_id(&self) -> Self::Id {
+        self.id
+    }
+}
+
+impl<K: Send + Sync, V: Send + Sync> TreapNode<K, V> {
+    /// Constructs a new leaf from the given arguments
+    pub fn new(id: Id, key: K, value: V, priority: u64) -> Self {
+        Self {
+            id,
+            key,
+            value,
+            priority,
+            size: 1,
+            left: None,
+            right: None
+        }
+    }
+}
+
+/// This class represents a thread-safe ordered map, implemented as a treap.
+///
+/// Every node is given a random priority, and the tree is kept in the shape it would have if the
+/// keys had been inserted in order of decreasing priority. This makes the tree balanced in
+/// expectation, and lets whole trees be split apart and merged back together in logarithmic time.
+pub struct Treap<K: Ord + Send + Sync, V: Send + Sync> {
+    arena: GenerationalArena<TreapNode<K, V>>,
+    root: Option<Id>,
+
+    /// The generator for the priorities of new nodes.
+    rng: XorShift
+}
+
+impl<K: Ord + Send + Sync, V: Send + Sync> Default for Treap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Send + Sync, V: Send + Sync> Treap<K, V> {
+
+    /// Constructs a new empty Treap
+    pub fn new() -> Self {
+        Self::with_rng(XorShift::new())
+    }
+
+    /// Constructs a new empty Treap whose priorities are generated from the given seed, which makes
+    /// the shape of the tree reproducible.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(XorShift::with_seed(seed))
+    }
+
+    fn with_rng(rng: XorShift) -> Self {
+        Self {
+            arena: GenerationalArena::new(),
+            root: None,
+            rng
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.size_of(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Inserts the key, returning the previous value if it already exists.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(id) = self._find_node(&key) {
+            return Some(core::mem::replace(&mut self.node(&id).write().unwrap().value, value));
+        }
+
+        let id = self.arena.get_new_id();
+        let priority = self.next_priority();
+        self.arena.add_node(TreapNode::new(id, key, value, priority)).expect("could not add node!");
+
+        // --
+        // Put the new node in between the keys smaller and larger than it.
+        let root = self.root;
+        let (left, right) = {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+            self._split(root, &node.key)
+        };
+
+        let left = self._merge(left, Some(id));
+        self.root = self._merge(left, right);
+        None
+    }
+
+    /// Removes the key, returning its value if it existed.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let root = self.root;
+        let (root, removed) = self._remove(root, key);
+        self.root = root;
+        removed
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self._find_node(key).is_some()
+    }
+
+    /// Returns the number of keys in the tree which are smaller than 'key'.
+    pub fn rank(&self, key: &K) -> usize {
+        let mut rank = 0;
+        let mut node_id = self.root;
+
+        while let Some(id) = node_id {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+
+            if *key <= node.key {
+                node_id = node.left;
+            } else {
+                rank += self.size_of(&node.left) + 1;
+                node_id = node.right;
+            }
+        }
+
+        rank
+    }
+
+    /// Moves every entry out of this tree, returning a tree holding the keys smaller than 'key' and
+    /// a tree holding the rest. This tree is left empty.
+    pub fn split(&mut self, key: &K) -> (Self, Self) {
+        let root = self.root.take();
+        let (left, right) = self._split(root, key);
+
+        // --
+        // Only the smaller side has to be moved to an arena of its own, the larger one keeps this
+        // tree's arena.
+        let left_larger = self.size_of(&left) >= self.size_of(&right);
+        let (kept_root, moved_root) = if left_larger { (left, right) } else { (right, left) };
+
+        let mut moved = Self::with_seed(self.next_priority());
+        moved.root = moved_root.map(|id| self._move_into(id, &mut moved.arena));
+
+        let mut kept = Self::with_seed(self.next_priority());
+        kept.root = kept_root;
+        kept.arena = core::mem::take(&mut self.arena);
+
+        if left_larger { (kept, moved) } else { (moved, kept) }
+    }
+
+    /// Joins 2 trees into one, where every key in 'a' must be smaller than every key in 'b'.
+    pub fn merge(a: Self, b: Self) -> Self {
+        if let (Some(a_max), Some(b_min)) = (a._find_extreme(false), b._find_extreme(true)) {
+            let a_ref = a.node(&a_max);
+            let b_ref = b.node(&b_min);
+            assert!(
+                a_ref.read().unwrap().key < b_ref.read().unwrap().key,
+                "keys of 'a' must be smaller than keys of 'b'"
+            );
+        }
+
+        // --
+        // Move the nodes of the smaller tree over to the arena of the larger one.
+        let (mut a, mut b) = (a, b);
+        if a.len() >= b.len() {
+            let b_root = b.root.take();
+            let moved = b_root.map(|id| b._move_into(id, &mut a.arena));
+            let a_root = a.root;
+            a.root = a._merge(a_root, moved);
+            a
+        } else {
+            let a_root = a.root.take();
+            let moved = a_root.map(|id| a._move_into(id, &mut b.arena));
+            let b_root = b.root;
+            b.root = b._merge(moved, b_root);
+            b
+        }
+    }
+
+    /// Splits the given subtree into the keys smaller than 'key' and the rest, returning the roots
+    /// of both parts.
+    fn _split(&self, node_id: Option<Id>, key: &K) -> (Option<Id>, Option<Id>) {
+        let id = match node_id {
+            None => return (None, None),
+            Some(id) => id
+        };
+
+        let node_ref = self.node(&id);
+        let (smaller, left, right) = {
+            let node = node_ref.read().unwrap();
+            (node.key < *key, node.left, node.right)
+        };
+
+        if smaller {
+            let (rest, larger) = self._split(right, key);
+            node_ref.write().unwrap().right = rest;
+            self.update(&id);
+            (Some(id), larger)
+        } else {
+            let (smaller, rest) = self._split(left, key);
+            node_ref.write().unwrap().left = rest;
+            self.update(&id);
+            (smaller, Some(id))
+        }
+    }
+
+    /// Joins 2 subtrees, where every key in 'a' is smaller than every key in 'b', returning the
+    /// root of the joined tree.
+    fn _merge(&self, a: Option<Id>, b: Option<Id>) -> Option<Id> {
+        let (a_id, b_id) = match (a, b) {
+            (None, b) => return b,
+            (a, None) => return a,
+            (Some(a), Some(b)) => (a, b)
+        };
+
+        let a_ref = self.node(&a_id);
+        let b_ref = self.node(&b_id);
+        let a_priority = a_ref.read().unwrap().priority;
+        let b_priority = b_ref.read().unwrap().priority;
+
+        if a_priority >= b_priority {
+            let right = a_ref.read().unwrap().right;
+            let right = self._merge(right, b);
+            a_ref.write().unwrap().right = right;
+            self.update(&a_id);
+            a
+        } else {
+            let left = b_ref.read().unwrap().left;
+            let left = self._merge(a, left);
+            b_ref.write().unwrap().left = left;
+            self.update(&b_id);
+            b
+        }
+    }
+
+    fn _remove(&mut self, node_id: Option<Id>, key: &K) -> (Option<Id>, Option<V>) {
+        let id = match node_id {
+            None => return (None, None),
+            Some(id) => id
+        };
+
+        let (ordering, left, right) = {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+            (key.cmp(&node.key), node.left, node.right)
+        };
+
+        match ordering {
+            Ordering::Equal => {
+                // The children are already heap-ordered, so joining them is all it takes.
+                let root = self._merge(left, right);
+                (root, Some(self.take_node(&id).value))
+            }
+            Ordering::Less => {
+                let (child, removed) = self._remove(left, key);
+                self.node(&id).write().unwrap().left = child;
+                self.update(&id);
+                (Some(id), removed)
+            }
+            Ordering::Greater => {
+                let (child, removed) = self._remove(right, key);
+                self.node(&id).write().unwrap().right = child;
+                self.update(&id);
+                (Some(id), removed)
+            }
+        }
+    }
+
+    /// Moves the subtree rooted at the given node over to another arena, returning the new id of
+    /// its root.
+    fn _move_into(&mut self, node_id: Id, arena: &mut GenerationalArena<TreapNode<K, V>>) -> Id {
+        let mut node = self.take_node(&node_id);
+
+        node.left = node.left.map(|id| self._move_into(id, arena));
+        node.right = node.right.map(|id| self._move_into(id, arena));
+        node.id = arena.get_new_id();
+
+        let id = node.id;
+        arena.add_node(node).expect("could not add node!");
+        id
+    }
+
+    fn next_priority(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    /// Recomputes the size of the node from its children.
+    fn update(&self, node_id: &Id) {
+        let (left, right) = self.children(node_id);
+        let size = 1 + self.size_of(&left) + self.size_of(&right);
+        self.node(node_id).write().unwrap().size = size;
+    }
+
+    fn size_of(&self, node_id: &Option<Id>) -> usize {
+        node_id.map_or(0, |id| self.node(&id).read().unwrap().size)
+    }
+
+    fn children(&self, node_id: &Id) -> (Option<Id>, Option<Id>) {
+        let node_ref = self.node(node_id);
+        let node = node_ref.read().unwrap();
+        (node.left, node.right)
+    }
+
+    fn node(&self, node_id: &Id) -> SharedRef<TreapNode<K, V>> {
+        self.arena.get_node(node_id).expect("could not find node")
+    }
+
+    /// Removes the node from the arena, handing back ownership of it.
+    fn take_node(&mut self, node_id: &Id) -> TreapNode<K, V> {
+        let node_ref = self.node(node_id);
+        self.arena.delete_node(node_id).expect("could not delete node");
+
+        match Arc::try_unwrap(node_ref) {
+            Ok(node) => node.into_inner().unwrap(),
+            Err(_) => panic!("node is still referenced")
+        }
+    }
+
+    fn _find_node(&self, key: &K) -> Option<Id> {
+        let mut node_id = self.root;
+
+        while let Some(id) = node_id {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+
+            node_id = match key.cmp(&node.key) {
+                Ordering::Equal => return Some(id),
+                Ordering::Less => node.left,
+                Ordering::Greater => node.right
+            };
+        }
+
+        None
+    }
+
+    /// Returns the id of the node found by always following the given side from the root.
+    fn _find_extreme(&self, leftmost: bool) -> Option<Id> {
+        let mut node_id = self.root?;
+
+        loop {
+            let (left, right) = self.children(&node_id);
+            match if leftmost { left } else { right } {
+                None => return Some(node_id),
+                Some(id) => node_id = id
+            }
+        }
+    }
+}
+
+impl<K: Ord + Clone + Send + Sync, V: Clone + Send + Sync> Treap<K, V> {
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let id = self._find_node(key)?;
+        let value = self.node(&id).read().unwrap().value.clone();
+        Some(value)
+    }
+
+    /// Returns the smallest key along with its value.
+    pub fn first(&self) -> Option<(K, V)> {
+        self._find_extreme(true).map(|id| self.entry(&id))
+    }
+
+    /// Returns the largest key along with its value.
+    pub fn last(&self) -> Option<(K, V)> {
+        self._find_extreme(false).map(|id| self.entry(&id))
+    }
+
+    /// Returns the key at the given rank (i.e. the 'rank'-th smallest key) along with its value.
+    pub fn select(&self, rank: usize) -> Option<(K, V)> {
+        let mut rank = rank;
+        let mut node_id = self.root;
+
+        while let Some(id) = node_id {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+            let left_size = self.size_of(&node.left);
+
+            match rank.cmp(&left_size) {
+                Ordering::Equal => return Some((node.key.clone(), node.value.clone())),
+                Ordering::Less => node_id = node.left,
+                Ordering::Greater => {
+                    rank -= left_size + 1;
+                    node_id = node.right;
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns all keys along with their values, in ascending order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (K, V)> {
+        self.range(..)
+    }
+
+    /// Returns all keys within the given range along with their values, in ascending order.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> impl DoubleEndedIterator<Item = (K, V)> {
+        let mut result = vec![];
+        self._range(&self.root, &range, &mut result);
+        result.into_iter()
+    }
+
+    fn entry(&self, node_id: &Id) -> (K, V) {
+        let node_ref = self.node(node_id);
+        let node = node_ref.read().unwrap();
+        (node.key.clone(), node.value.clone())
+    }
+
+    fn _range<R: RangeBounds<K>>(&self, node_id: &Option<Id>, range: &R, out: &mut Vec<(K, V)>) {
+        let node_ref = match node_id {
+            None => return,
+            Some(id) => self.node(id)
+        };
+        let node = node_ref.read().unwrap();
+
+        let after_start = after_start(&node.key, range);
+        let before_end = before_end(&node.key, range);
+
+        // Subtrees which lie entirely outside of the range are skipped.
+        if after_start {
+            self._range(&node.left, range, out);
+        }
+        if after_start && before_end {
+            out.push((node.key.clone(), node.value.clone()));
+        }
+        if before_end {
+            self._range(&node.right, range, out);
+        }
+    }
+}
+
+impl<K: Ord + Debug + Send + Sync, V: Debug + Send + Sync> ToDot for Treap<K, V> {
+    fn to_dot(&self) -> String {
+        let mut dot = DotWriter::new("Treap");
+        let mut stack: Vec<Id> = self.root.into_iter().collect();
+
+        while let Some(id) = stack.pop() {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+
+            dot.node(&id, &format!("{:?}: {:?}", node.key, node.value), "");
+
+            for (child, name) in [(node.left, "L"), (node.right, "R")] {
+                if let Some(child_id) = child {
+                    dot.edge(&id, &child_id, name, "");
+                    stack.push(child_id);
+                }
+            }
+        }
+
+        dot.finish()
+    }
+}
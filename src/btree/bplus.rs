@@ -0,0 +1,570 @@
+use alloc::collections::VecDeque;
+use alloc::{format, vec};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::ops::{Bound, RangeBounds};
+
+use crate::arena::*;
+use crate::arena::prelude::*;
+use crate::bst::bounds::*;
+use crate::visualize::{DotWriter, ToDot};
+
+type Id = GenerationalId;
+
+#[derive(Debug, Clone)]
+enum BNode<K: Send + Sync, V: Send + Sync> {
+    /// 'children[i]' holds the keys in [keys[i - 1], keys[i]).
+    Internal {
+        id: Id,
+        keys: Vec<K>,
+        children: Vec<Id>
+    },
+
+    /// Leaves are linked together in key order, so scans never have to go back up the tree.
+    Leaf {
+        id: Id,
+        keys: Vec<K>,
+        values: Vec<V>,
+        next: Option<Id>
+    }
+}
+
+impl<K: Send + Sync, V: Send + Sync> HasId for BNode<K, V> {
+    type Id = Id;
+
+    fn get_id(&self) -> Self::Id {
+        match self {
+            BNode::Internal { id, .. } => *id,
+            BNode::Leaf { id, .. } => *id
+        }
+    }
+}
+
+/// This class represents a thread-safe B+ tree, where every node holds up to 'B' keys.
+///
+/// All values are stored in the leaves, which form a linked list in key order, while internal
+/// nodes only hold the separator keys used to route searches. Varying 'B' trades tree height
+/// against the size of each node.
+pub struct BPlusTree<K: Ord + Clone + Send + Sync, V: Send + Sync, const B: usize> {
+    arena: GenerationalArena<BNode<K, V>>,
+    root: Id,
+    size: usize
+}
+
+impl<K: Ord + Clone + Send + Sync, V: Send + Sync, const B: usize> Default for BPlusTree<K, V, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Clone + Send + Sync, V: Send + Sync, const B: usize> BPlusTree<K, V, B> {
+
+    /// Constructs a new empty BPlusTree
+    pub fn new() -> Self {
+        assert!(B >= 3, "nodes must hold at least 3 keys");
+
+        let mut arena = GenerationalArena::new();
+        let root = arena.get_new_id();
+        arena.add_node(BNode::Leaf { id: root, keys: vec![], values: vec![], next: None })
+            .expect("failed to add root to tree!");
+
+        Self {
+            arena,
+            root,
+            size: 0
+        }
+    }
+
+    /// Constructs a BPlusTree from entries whose keys are strictly ascending, building it bottom
+    /// up one level at a time instead of inserting the entries one by one.
+    pub fn from_sorted(entries: impl IntoIterator<Item = (K, V)>) -> Self {
+        assert!(B >= 3, "nodes must hold at least 3 keys");
+
+        let (keys, values): (Vec<K>, Vec<V>) = entries.into_iter().unzip();
+        assert!(keys.windows(2).all(|w| w[0] < w[1]), "keys must be strictly ascending");
+
+        if keys.is_empty() {
+            return Self::new();
+        }
+
+        let mut arena = GenerationalArena::new();
+        let size = keys.len();
+
+        // --
+        // Fill the leaves, each level is made of (smallest key, node) pairs.
+        let sizes = group_sizes(size, B);
+        let ids: Vec<Id> = sizes.iter().map(|_| arena.get_new_id()).collect();
+
+        let mut level = vec![];
+        let mut keys = keys.into_iter();
+        let mut values = values.into_iter();
+
+        for (i, n) in sizes.into_iter().enumerate() {
+            let leaf_keys: Vec<K> = keys.by_ref().take(n).collect();
+            level.push((leaf_keys[0].clone(), ids[i]));
+
+            let node = BNode::Leaf {
+                id: ids[i],
+                keys: leaf_keys,
+                values: values.by_ref().take(n).collect(),
+                next: ids.get(i + 1).copied()
+            };
+            arena.add_node(node).expect("could not add node!");
+        }
+
+        // --
+        // Then stack levels of internal nodes on top until a single root remains.
+        while level.len() > 1 {
+            let mut nodes = level.into_iter();
+            level = vec![];
+
+            for n in group_sizes(nodes.len(), B + 1) {
+                let group: Vec<(K, Id)> = nodes.by_ref().take(n).collect();
+                let id = arena.get_new_id();
+
+                level.push((group[0].0.clone(), id));
+
+                let node = BNode::Internal {
+                    id,
+                    keys: group.iter().skip(1).map(|(key, _)| key.clone()).collect(),
+                    children: group.iter().map(|(_, id)| *id).collect()
+                };
+                arena.add_node(node).expect("could not add node!");
+            }
+        }
+
+        Self {
+            arena,
+            root: level[0].1,
+            size
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of levels in the tree, counting the leaves.
+    pub fn depth(&self) -> usize {
+        let mut depth = 1;
+        let mut node_id = self.root;
+
+        while let BNode::Internal { children, .. } = &*self.node(&node_id).read().unwrap() {
+            node_id = children[0];
+            depth += 1;
+        }
+
+        depth
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        let leaf_ref = self.node(&self.find_leaf(Bound::Included(key)));
+        let leaf = leaf_ref.read().unwrap();
+
+        match &*leaf {
+            BNode::Leaf { keys, .. } => keys.binary_search(key).is_ok(),
+            BNode::Internal { .. } => unreachable!()
+        }
+    }
+
+    /// Inserts the key, returning the previous value if it already exists.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let root = self.root;
+        let (prev, split) = self._insert(&root, key, value);
+
+        // --
+        // If the root was split, the tree grows by a level.
+        if let Some((separator, sibling)) = split {
+            let id = self.arena.get_new_id();
+            let node = BNode::Internal { id, keys: vec![separator], children: vec![root, sibling] };
+            self.arena.add_node(node).expect("could not add node!");
+            self.root = id;
+        }
+
+        if prev.is_none() {
+            self.size += 1;
+        }
+        prev
+    }
+
+    /// Inserts into the subtree rooted at the given node. If the node overflows, it is split and
+    /// the separator key along with the id of the new right sibling is returned.
+    fn _insert(&mut self, node_id: &Id, key: K, value: V) -> (Option<V>, Option<(K, Id)>) {
+        let node_ref = self.node(node_id);
+
+        let child = match &*node_ref.read().unwrap() {
+            BNode::Internal { keys, children, .. } => {
+                let idx = child_idx(keys, &key);
+                Some((idx, children[idx]))
+            }
+            BNode::Leaf { .. } => None
+        };
+
+        match child {
+            None => {
+                let mut node = node_ref.write().unwrap();
+                let (keys, values, next) = match &mut *node {
+                    BNode::Leaf { keys, values, next, .. } => (keys, values, next),
+                    BNode::Internal { .. } => unreachable!()
+                };
+
+                let idx = match keys.binary_search(&key) {
+                    Ok(idx) => return (Some(core::mem::replace(&mut values[idx], value)), None),
+                    Err(idx) => idx
+                };
+
+                keys.insert(idx, key);
+                values.insert(idx, value);
+
+                if keys.len() <= B {
+                    return (None, None);
+                }
+
+                // --
+                // Move the upper half of the entries into a new leaf, right after this one.
+                let sibling = self.arena.get_new_id();
+                let mid = keys.len() / 2;
+
+                let right_keys = keys.split_off(mid);
+                let separator = right_keys[0].clone();
+                let leaf = BNode::Leaf {
+                    id: sibling,
+                    keys: right_keys,
+                    values: values.split_off(mid),
+                    next: next.replace(sibling)
+                };
+                self.arena.add_node(leaf).expect("could not add node!");
+
+                (None, Some((separator, sibling)))
+            }
+
+            Some((idx, child_id)) => {
+                let (prev, split) = self._insert(&child_id, key, value);
+
+                let (separator, new_child) = match split {
+                    None => return (prev, None),
+                    Some(split) => split
+                };
+
+                let mut node = node_ref.write().unwrap();
+                let (keys, children) = match &mut *node {
+                    BNode::Internal { keys, children, .. } => (keys, children),
+                    BNode::Leaf { .. } => unreachable!()
+                };
+
+                keys.insert(idx, separator);
+                children.insert(idx + 1, new_child);
+
+                if keys.len() <= B {
+                    return (prev, None);
+                }
+
+                // --
+                // Move the upper half of the keys into a new node, the middle key moves up.
+                let sibling = self.arena.get_new_id();
+                let mid = keys.len() / 2;
+
+                let right_keys = keys.split_off(mid + 1);
+                let separator = keys.pop().unwrap();
+                let internal = BNode::Internal {
+                    id: sibling,
+                    keys: right_keys,
+                    children: children.split_off(mid + 1)
+                };
+                self.arena.add_node(internal).expect("could not add node!");
+
+                (prev, Some((separator, sibling)))
+            }
+        }
+    }
+
+    /// Returns the id of the leaf which would hold the first key within the given start bound.
+    fn find_leaf(&self, start: Bound<&K>) -> Id {
+        let mut node_id = self.root;
+
+        loop {
+            let node_ref = self.node(&node_id);
+            let node = node_ref.read().unwrap();
+
+            match &*node {
+                BNode::Leaf { .. } => return node_id,
+                BNode::Internal { keys, children, .. } => {
+                    node_id = match start {
+                        Bound::Unbounded => children[0],
+                        Bound::Included(key) | Bound::Excluded(key) => children[child_idx(keys, key)]
+                    };
+                }
+            }
+        }
+    }
+
+    /// Returns the path to the leaf which would hold the last key within the given end bound, as
+    /// the ids of the nodes on the way along with the index of the child taken below each of them.
+    fn find_last_leaf(&self, end: Bound<&K>) -> Vec<(Id, usize)> {
+        let mut path = vec![];
+        let mut node_id = self.root;
+
+        loop {
+            let node_ref = self.node(&node_id);
+            let node = node_ref.read().unwrap();
+
+            match &*node {
+                BNode::Leaf { .. } => {
+                    path.push((node_id, 0));
+                    return path;
+                }
+                BNode::Internal { keys, children, .. } => {
+                    let idx = match end {
+                        Bound::Unbounded => children.len() - 1,
+                        Bound::Included(key) | Bound::Excluded(key) => child_idx(keys, key)
+                    };
+                    path.push((node_id, idx));
+                    node_id = children[idx];
+                }
+            }
+        }
+    }
+
+    /// Moves the path returned by 'find_last_leaf' to the previous leaf, returning false if there
+    /// is none.
+    fn prev_leaf(&self, path: &mut Vec<(Id, usize)>) -> bool {
+        path.pop();
+
+        // Go up until there is a child to the left, then down the rightmost children below it.
+        while let Some((_, idx)) = path.last() {
+            if *idx > 0 {
+                break;
+            }
+            path.pop();
+        }
+
+        let (parent_id, idx) = match path.last_mut() {
+            None => return false,
+            Some(last) => {
+                last.1 -= 1;
+                *last
+            }
+        };
+
+        let mut node_id = match &*self.node(&parent_id).read().unwrap() {
+            BNode::Internal { children, .. } => children[idx],
+            BNode::Leaf { .. } => unreachable!()
+        };
+
+        loop {
+            let node_ref = self.node(&node_id);
+            let node = node_ref.read().unwrap();
+
+            match &*node {
+                BNode::Leaf { .. } => {
+                    path.push((node_id, 0));
+                    return true;
+                }
+                BNode::Internal { children, .. } => {
+                    path.push((node_id, children.len() - 1));
+                    node_id = children[children.len() - 1];
+                }
+            }
+        }
+    }
+
+    fn node(&self, node_id: &Id) -> SharedRef<BNode<K, V>> {
+        self.arena.get_node(node_id).expect("could not find node")
+    }
+}
+
+impl<K: Ord + Clone + Send + Sync, V: Clone + Send + Sync, const B: usize> BPlusTree<K, V, B> {
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let leaf_ref = self.node(&self.find_leaf(Bound::Included(key)));
+        let leaf = leaf_ref.read().unwrap();
+
+        match &*leaf {
+            BNode::Leaf { keys, values, .. } => keys.binary_search(key).ok().map(|idx| values[idx].clone()),
+            BNode::Internal { .. } => unreachable!()
+        }
+    }
+
+    /// Returns all keys along with their values, in ascending order.
+    pub fn iter(&self) -> Range<'_, K, V, B> {
+        self.range(..)
+    }
+
+    /// Returns all keys within the given range along with their values, in ascending order.
+    ///
+    /// Only the path to the first leaf is searched, after that the scan follows the links between
+    /// the leaves.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Range<'_, K, V, B> {
+        Range {
+            tree: self,
+            leaf: Some(self.find_leaf(range.start_bound())),
+            buffer: VecDeque::new(),
+            back_path: self.find_last_leaf(range.end_bound()),
+            back_buffer: VecDeque::new(),
+            bounds: (range.start_bound().cloned(), range.end_bound().cloned())
+        }
+    }
+}
+
+/// An iterator over a range of the keys and values of a BPlusTree, in ascending order, or in
+/// descending order when iterated from the back.
+///
+/// The leaves only link to the next leaf, so the back of the iterator keeps the path from the root
+/// to its leaf instead. Both ends narrow the bounds of the range as they go, which is how they
+/// notice having met in the middle.
+pub struct Range<'a, K: Ord + Clone + Send + Sync, V: Send + Sync, const B: usize> {
+    tree: &'a BPlusTree<K, V, B>,
+
+    /// The next leaf to be copied into the buffer.
+    leaf: Option<Id>,
+
+    buffer: VecDeque<(K, V)>,
+
+    /// The path to the next leaf to be copied into the back buffer, see 'find_last_leaf'.
+    back_path: Vec<(Id, usize)>,
+
+    back_buffer: VecDeque<(K, V)>,
+    bounds: (Bound<K>, Bound<K>)
+}
+
+impl<K: Ord + Clone + Send + Sync, V: Clone + Send + Sync, const B: usize> Iterator for Range<'_, K, V, B> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // --
+        // Copy whole leaves at a time, so each leaf is only locked once.
+        while self.buffer.is_empty() {
+            let leaf_ref = self.tree.node(&self.leaf?);
+            let leaf = leaf_ref.read().unwrap();
+
+            let (keys, values, next) = match &*leaf {
+                BNode::Leaf { keys, values, next, .. } => (keys, values, next),
+                BNode::Internal { .. } => unreachable!()
+            };
+
+            for (key, value) in keys.iter().zip(values) {
+                if !before_end(key, &self.bounds) {
+                    self.leaf = None;
+                    break;
+                }
+                if after_start(key, &self.bounds) {
+                    self.buffer.push_back((key.clone(), value.clone()));
+                }
+            }
+
+            if self.leaf.is_some() {
+                self.leaf = *next;
+            }
+        }
+
+        // The back may have passed this key already.
+        let (key, value) = self.buffer.pop_front()?;
+        if !before_end(&key, &self.bounds) {
+            self.leaf = None;
+            self.buffer.clear();
+            return None;
+        }
+
+        self.bounds.0 = Bound::Excluded(key.clone());
+        Some((key, value))
+    }
+}
+
+impl<K: Ord + Clone + Send + Sync, V: Clone + Send + Sync, const B: usize> DoubleEndedIterator for Range<'_, K, V, B> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while self.back_buffer.is_empty() {
+            let &(leaf_id, _) = self.back_path.last()?;
+            let leaf_ref = self.tree.node(&leaf_id);
+            let leaf = leaf_ref.read().unwrap();
+
+            let (keys, values) = match &*leaf {
+                BNode::Leaf { keys, values, .. } => (keys, values),
+                BNode::Internal { .. } => unreachable!()
+            };
+
+            let mut done = false;
+            for (key, value) in keys.iter().zip(values).rev() {
+                if !after_start(key, &self.bounds) {
+                    done = true;
+                    break;
+                }
+                if before_end(key, &self.bounds) {
+                    self.back_buffer.push_back((key.clone(), value.clone()));
+                }
+            }
+
+            if done || !self.tree.prev_leaf(&mut self.back_path) {
+                self.back_path.clear();
+            }
+        }
+
+        // The front may have passed this key already.
+        let (key, value) = self.back_buffer.pop_front()?;
+        if !after_start(&key, &self.bounds) {
+            self.back_path.clear();
+            self.back_buffer.clear();
+            return None;
+        }
+
+        self.bounds.1 = Bound::Excluded(key.clone());
+        Some((key, value))
+    }
+}
+
+/// Returns the index of the child of an internal node whose keys are 'keys' which holds 'key'.
+fn child_idx<K: Ord>(keys: &[K], key: &K) -> usize {
+    match keys.binary_search(key) {
+        Ok(idx) => idx + 1,
+        Err(idx) => idx
+    }
+}
+
+/// Splits 'n' items into as few groups of at most 'max' items as possible, with sizes as even as
+/// possible so that no group ends up underfull.
+fn group_sizes(n: usize, max: usize) -> Vec<usize> {
+    let groups = n.div_ceil(max);
+    (0..groups)
+        .map(|i| n / groups + usize::from(i < n % groups))
+        .collect()
+}
+
+impl<K: Ord + Clone + Debug + Send + Sync, V: Debug + Send + Sync, const B: usize> ToDot for BPlusTree<K, V, B> {
+    fn to_dot(&self) -> String {
+        let mut dot = DotWriter::new("BPlusTree");
+        let mut stack = vec![self.root];
+
+        while let Some(id) = stack.pop() {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+
+            match &*node {
+                BNode::Internal { keys, children, .. } => {
+                    dot.node(&id, &format!("{:?}", keys), "shape=box");
+                    for child_id in children {
+                        dot.edge(&id, child_id, "", "");
+                        stack.push(*child_id);
+                    }
+                }
+                BNode::Leaf { keys, values, next, .. } => {
+                    let entries: Vec<String> = keys.iter()
+                        .zip(values)
+                        .map(|(key, value)| format!("{:?}: {:?}", key, value))
+                        .collect();
+                    dot.node(&id, &entries.join("\n"), "shape=box, style=rounded");
+
+                    // The links between the leaves are drawn dashed, so they stand out.
+                    if let Some(next_id) = next {
+                        dot.edge(&id, next_id, "", "style=dashed, constraint=false");
+                    }
+                }
+            }
+        }
+
+        dot.finish()
+    }
+}
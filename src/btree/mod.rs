@@ -0,0 +1,134 @@
+pub mod bplus;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::ops::Bound;
+
+    use crate::btree::bplus::*;
+
+    #[test]
+    fn test_bplus_tree() {
+        let mut tree = BPlusTree::<i32, &str, 3>::new();
+        assert!(tree.is_empty());
+        assert!(tree.get(&1).is_none());
+        assert!(tree.iter().next().is_none());
+
+        for (i, word) in ["zero", "one", "two", "three", "four", "five", "six"].iter().enumerate() {
+            assert!(tree.insert(i as i32, word).is_none());
+        }
+        assert_eq!(tree.insert(3, "drei"), Some("three"));
+        assert_eq!(tree.len(), 7);
+        assert!(tree.depth() > 1);
+
+        assert_eq!(tree.get(&3), Some("drei"));
+        assert!(tree.contains_key(&6));
+        assert!(!tree.contains_key(&7));
+
+        assert_eq!(tree.iter().map(|(k, _)| k).collect::<Vec<_>>(), (0..7).collect::<Vec<_>>());
+        assert_eq!(tree.range(2..5).map(|(_, v)| v).collect::<Vec<_>>(), vec!["two", "drei", "four"]);
+        assert_eq!(tree.range((Bound::Excluded(4), Bound::Unbounded)).count(), 2);
+        assert_eq!(tree.range(10..).count(), 0);
+
+        assert_eq!(tree.iter().rev().map(|(k, _)| k).collect::<Vec<_>>(), (0..7).rev().collect::<Vec<_>>());
+        assert_eq!(tree.range(..=3).next_back(), Some((3, "drei")));
+        assert_eq!(tree.range(10..).next_back(), None);
+    }
+
+    #[test]
+    fn test_bplus_tree_double_ended() {
+        let mut tree = BPlusTree::<u32, u32, 3>::new();
+        let mut expected = BTreeMap::new();
+
+        let mut state = 77u32;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) % 500
+        };
+
+        for i in 0..300 {
+            let key = next();
+            tree.insert(key, i);
+            expected.insert(key, i);
+        }
+
+        for _ in 0..200 {
+            let (a, b) = (next(), next());
+            let (lo, hi) = (a.min(b), a.max(b));
+            let mut range = tree.range(lo..hi);
+            let mut expected_range = expected.range(lo..hi).map(|(k, v)| (*k, *v));
+
+            // Taking from both ends at random must meet in the middle without repeating any key.
+            loop {
+                let (item, expected_item) = match next() % 2 {
+                    0 => (range.next(), expected_range.next()),
+                    _ => (range.next_back(), expected_range.next_back())
+                };
+                assert_eq!(item, expected_item);
+
+                if item.is_none() {
+                    assert_eq!(range.next(), None);
+                    assert_eq!(range.next_back(), None);
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_bplus_tree_against_btreemap() {
+        let mut tree = BPlusTree::<u32, u32, 4>::new();
+        let mut expected = BTreeMap::new();
+
+        let mut state = 2024u32;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) % 1000
+        };
+
+        for i in 0..3000 {
+            let key = next();
+            assert_eq!(tree.insert(key, i), expected.insert(key, i));
+        }
+
+        assert_eq!(tree.len(), expected.len());
+        assert_eq!(tree.iter().collect::<Vec<_>>(), expected.clone().into_iter().collect::<Vec<_>>());
+
+        for _ in 0..100 {
+            let (a, b) = (next(), next());
+            let (lo, hi) = (a.min(b), a.max(b));
+            assert_eq!(
+                tree.range(lo..=hi).collect::<Vec<_>>(),
+                expected.range(lo..=hi).map(|(k, v)| (*k, *v)).collect::<Vec<_>>()
+            );
+            assert_eq!(tree.get(&a), expected.get(&a).copied());
+        }
+    }
+
+    #[test]
+    fn test_bplus_tree_from_sorted() {
+        let tree = BPlusTree::<u32, u32, 8>::from_sorted(vec![]);
+        assert!(tree.is_empty());
+        assert_eq!(tree.depth(), 1);
+
+        let mut tree = BPlusTree::<u32, u32, 8>::from_sorted((0..1000).map(|i| (2 * i, i)));
+        assert_eq!(tree.len(), 1000);
+        assert_eq!(tree.depth(), 4);
+        assert_eq!(tree.get(&500), Some(250));
+        assert!(tree.get(&501).is_none());
+        assert_eq!(tree.range(100..110).map(|(k, _)| k).collect::<Vec<_>>(), vec![100, 102, 104, 106, 108]);
+
+        // The tree should remain fully mutable.
+        for i in 0..1000 {
+            assert!(tree.insert(2 * i + 1, i).is_none());
+        }
+        assert_eq!(tree.len(), 2000);
+        assert_eq!(tree.iter().map(|(k, _)| k).collect::<Vec<_>>(), (0..2000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_bplus_tree_from_unsorted() {
+        BPlusTree::<u32, u32, 8>::from_sorted(vec![(2, 0), (1, 0)]);
+    }
+}
@@ -0,0 +1,10 @@
+//! The hash maps and sets used throughout the crate.
+//!
+//! With the 'std' feature these are std's. Without it there's no source of randomness to seed std's
+//! hasher with, so they are hashbrown's instead, which std's are built on anyway.
+
+#[cfg(feature = "std")]
+pub use std::collections::{HashMap, HashSet};
+
+#[cfg(not(feature = "std"))]
+pub use hashbrown::{HashMap, HashSet};
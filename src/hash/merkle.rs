@@ -0,0 +1,137 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use digest::{Digest, Output};
+
+/// Leaves and inner nodes are hashed with different prefixes, so that an inner node can never be
+/// passed off as a leaf (or vice versa) in a proof.
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// The side of the path being proven that a sibling hash sits on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right
+}
+
+/// A Merkle tree is a binary tree in which every leaf holds the hash of a block of data, and every
+/// inner node holds the hash of its children. The root hash commits to every leaf, and any single
+/// leaf can be shown to belong to the tree with a proof made up of one hash per level.
+///
+/// The tree never changes once built, so each level is stored as a flat list of hashes, with the
+/// leaves at level 0. A node without a sibling at the end of a level is promoted to the next level
+/// as is, rather than being paired with a copy of itself.
+pub struct MerkleTree<H: Digest> {
+    levels: Vec<Vec<Output<H>>>
+}
+
+/// The sibling hashes along the path from a leaf up to the root of a MerkleTree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MerkleProof<H: Digest> {
+    /// The index of the leaf being proven.
+    pub index: usize,
+
+    /// The siblings from the bottom of the tree to the top, along with the side they sit on.
+    pub siblings: Vec<(Side, Output<H>)>
+}
+
+impl<H: Digest> MerkleTree<H> {
+
+    /// Builds a tree whose leaves are the hashes of the given blocks of data.
+    pub fn new<T: AsRef<[u8]>>(leaves: impl IntoIterator<Item = T>) -> Self {
+        let mut levels = vec![leaves.into_iter().map(|leaf| hash_leaf::<H>(leaf.as_ref())).collect::<Vec<_>>()];
+
+        while levels.last().unwrap().len() > 1 {
+            let next = levels.last().unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_node::<H>(left, right),
+                    [single] => single.clone(),
+                    _ => unreachable!()
+                })
+                .collect();
+
+            levels.push(next);
+        }
+
+        Self { levels }
+    }
+
+    /// Returns the number of leaves in the tree.
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// Returns true if the tree has no leaves.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the root hash of the tree, which is the hash of no data at all for an empty tree.
+    pub fn root(&self) -> Output<H> {
+        match self.levels.last().unwrap().first() {
+            Some(root) => root.clone(),
+            None => H::digest([])
+        }
+    }
+
+    /// Returns the hash stored at the leaf with the given index.
+    pub fn leaf(&self, index: usize) -> Option<&Output<H>> {
+        self.levels[0].get(index)
+    }
+
+    /// Returns the proof that the leaf with the given index belongs to the tree.
+    pub fn proof(&self, index: usize) -> Option<MerkleProof<H>> {
+        if index >= self.len() {
+            return None;
+        }
+
+        let mut siblings = vec![];
+        let mut idx = index;
+
+        for level in &self.levels[..self.levels.len() - 1] {
+            // A node which was promoted as is has no sibling to record at this level.
+            let sibling = idx ^ 1;
+            if sibling < level.len() {
+                let side = if sibling < idx { Side::Left } else { Side::Right };
+                siblings.push((side, level[sibling].clone()));
+            }
+
+            idx /= 2;
+        }
+
+        Some(MerkleProof { index, siblings })
+    }
+}
+
+impl<H: Digest> MerkleProof<H> {
+
+    /// Returns true if this proof shows that the given block of data is a leaf of the tree with
+    /// the given root hash.
+    pub fn verify(&self, root: &Output<H>, leaf: &[u8]) -> bool {
+        let computed = self.siblings.iter().fold(hash_leaf::<H>(leaf), |hash, (side, sibling)| {
+            match side {
+                Side::Left => hash_node::<H>(sibling, &hash),
+                Side::Right => hash_node::<H>(&hash, sibling)
+            }
+        });
+
+        computed == *root
+    }
+}
+
+fn hash_leaf<H: Digest>(data: &[u8]) -> Output<H> {
+    H::new()
+        .chain_update([LEAF_PREFIX])
+        .chain_update(data)
+        .finalize()
+}
+
+fn hash_node<H: Digest>(left: &Output<H>, right: &Output<H>) -> Output<H> {
+    H::new()
+        .chain_update([NODE_PREFIX])
+        .chain_update(left)
+        .chain_update(right)
+        .finalize()
+}
@@ -0,0 +1,56 @@
+pub mod merkle;
+
+#[cfg(test)]
+mod tests {
+    use sha2::{Digest, Sha256};
+
+    use crate::hash::merkle::*;
+
+    #[test]
+    fn test_merkle_tree() {
+        let empty = MerkleTree::<Sha256>::new(Vec::<&[u8]>::new());
+        assert!(empty.is_empty());
+        assert_eq!(empty.root(), Sha256::digest([]));
+        assert!(empty.proof(0).is_none());
+
+        let single = MerkleTree::<Sha256>::new(["a"]);
+        assert_eq!(single.root(), *single.leaf(0).unwrap());
+        let proof = single.proof(0).unwrap();
+        assert!(proof.siblings.is_empty());
+        assert!(proof.verify(&single.root(), b"a"));
+        assert!(!proof.verify(&single.root(), b"b"));
+
+        // Each leaf's proof must hold against the root of the tree, and only for that leaf.
+        for n in 1..=17 {
+            let leaves: Vec<String> = (0..n).map(|i| format!("block {}", i)).collect();
+            let tree = MerkleTree::<Sha256>::new(&leaves);
+            assert_eq!(tree.len(), n);
+
+            let root = tree.root();
+            for (i, leaf) in leaves.iter().enumerate() {
+                let proof = tree.proof(i).unwrap();
+                assert_eq!(proof.index, i);
+                assert!(proof.verify(&root, leaf.as_bytes()));
+                assert!(!proof.verify(&root, b"forged"));
+                assert!(proof.siblings.len() <= (usize::BITS - n.leading_zeros()) as usize);
+            }
+            assert!(tree.proof(n).is_none());
+        }
+
+        // Changing any leaf changes the root.
+        let a = MerkleTree::<Sha256>::new(["x", "y", "z"]);
+        let b = MerkleTree::<Sha256>::new(["x", "y", "w"]);
+        assert_ne!(a.root(), b.root());
+
+        // A proof for one tree doesn't hold against another.
+        let proof = a.proof(2).unwrap();
+        assert!(!proof.verify(&b.root(), b"z"));
+
+        // An inner node can't be passed off as a leaf.
+        let four = MerkleTree::<Sha256>::new(["a", "b", "c", "d"]);
+        let two = MerkleTree::<Sha256>::new(["a", "b"]);
+        let mut forged = four.proof(0).unwrap();
+        forged.siblings.remove(0);
+        assert!(!forged.verify(&four.root(), &two.root()));
+    }
+}
@@ -0,0 +1,217 @@
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::arena::*;
+use crate::arena::prelude::*;
+use crate::heap::error::HeapError;
+
+type Id = GenerationalId;
+
+#[derive(Debug, Clone)]
+struct HeapNode<T: Send + Sync> {
+    pub id: Id,
+
+    pub item: T,
+
+    /// The position of this node in the heap order.
+    pub pos: usize
+}
+
+impl<T: Send + Sync> HasId for HeapNode<T> {
+    type Id = Id;
+
+    fn get_id(&self) -> Self::Id {
+        self.id
+    }
+}
+
+/// A stable reference to an item in a DaryHeap, which stays valid until the item leaves the heap.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct HeapHandle(Id);
+
+/// This class represents a thread-safe min-heap in which every node has up to 'D' children.
+///
+/// Items live in the arena and the heap order only holds their ids, so every item can be handed
+/// out a handle through which its key can later be decreased, as needed by e.g. Dijkstra's
+/// algorithm. Wider heaps are shallower, which makes pushing and decreasing keys cheaper at the
+/// cost of popping.
+pub struct DaryHeap<T: Ord + Send + Sync, const D: usize> {
+    arena: GenerationalArena<HeapNode<T>>,
+    order: Vec<Id>
+}
+
+impl<T: Ord + Send + Sync, const D: usize> Default for DaryHeap<T, D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord + Send + Sync, const D: usize> DaryHeap<T, D> {
+
+    /// Constructs a new empty DaryHeap
+    pub fn new() -> Self {
+        assert!(D >= 2, "nodes must have at least 2 children");
+
+        Self {
+            arena: GenerationalArena::new(),
+            order: vec![]
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    /// Returns true if the item referred to by the handle is still in the heap.
+    pub fn contains(&self, handle: &HeapHandle) -> bool {
+        self.arena.get_node(&handle.0).is_some()
+    }
+
+    /// Adds the item to the heap, returning a handle to it.
+    pub fn push(&mut self, item: T) -> HeapHandle {
+        let id = self.arena.get_new_id();
+        let pos = self.order.len();
+
+        self.arena.add_node(HeapNode { id, item, pos }).expect("could not add node!");
+        self.order.push(id);
+        self.sift_up(pos);
+
+        HeapHandle(id)
+    }
+
+    /// Removes the smallest item from the heap and returns it.
+    pub fn pop(&mut self) -> Option<T> {
+        let id = *self.order.first()?;
+        Some(self.take(&id))
+    }
+
+    /// Removes the item referred to by the handle from the heap and returns it.
+    pub fn remove(&mut self, handle: &HeapHandle) -> Result<T, HeapError> {
+        if !self.contains(handle) {
+            return Err(HeapError::InvalidHandle);
+        }
+
+        Ok(self.take(&handle.0))
+    }
+
+    /// Replaces the item referred to by the handle with a smaller (or equal) one.
+    pub fn decrease_key(&mut self, handle: &HeapHandle, item: T) -> Result<(), HeapError> {
+        let node_ref = self.arena.get_node(&handle.0).ok_or(HeapError::InvalidHandle)?;
+
+        let pos = {
+            let mut node = node_ref.write().unwrap();
+            if item > node.item {
+                return Err(HeapError::KeyIncreased);
+            }
+
+            node.item = item;
+            node.pos
+        };
+
+        self.sift_up(pos);
+        Ok(())
+    }
+
+    /// Removes the node from the heap order and the arena, handing back its item.
+    fn take(&mut self, node_id: &Id) -> T {
+        let pos = self.node(node_id).read().unwrap().pos;
+
+        // --
+        // Fill the hole with the last node, which then has to find its place.
+        let last = self.order.len() - 1;
+        self.swap(pos, last);
+        self.order.pop();
+
+        if pos < self.order.len() {
+            let pos = self.sift_up(pos);
+            self.sift_down(pos);
+        }
+
+        let node_ref = self.node(node_id);
+        self.arena.delete_node(node_id).expect("could not delete node");
+
+        match Arc::try_unwrap(node_ref) {
+            Ok(node) => node.into_inner().unwrap().item,
+            Err(_) => panic!("node is still referenced")
+        }
+    }
+
+    /// Moves the node at the given position up until its parent is no larger, returning its new
+    /// position.
+    fn sift_up(&mut self, pos: usize) -> usize {
+        let mut pos = pos;
+
+        while pos > 0 {
+            let parent = (pos - 1) / D;
+            if !self.less(pos, parent) {
+                break;
+            }
+
+            self.swap(pos, parent);
+            pos = parent;
+        }
+
+        pos
+    }
+
+    /// Moves the node at the given position down until none of its children are smaller.
+    fn sift_down(&mut self, pos: usize) {
+        let mut pos = pos;
+
+        loop {
+            let first = pos * D + 1;
+            let last = (first + D).min(self.order.len());
+
+            let smallest = (first..last).fold(pos, |smallest, child| {
+                if self.less(child, smallest) { child } else { smallest }
+            });
+
+            if smallest == pos {
+                break;
+            }
+
+            self.swap(pos, smallest);
+            pos = smallest;
+        }
+    }
+
+    /// Returns true if the item at position 'a' is smaller than the item at position 'b'.
+    fn less(&self, a: usize, b: usize) -> bool {
+        let a_ref = self.node(&self.order[a]);
+        let b_ref = self.node(&self.order[b]);
+        let less = a_ref.read().unwrap().item < b_ref.read().unwrap().item;
+        less
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.order.swap(a, b);
+        self.node(&self.order[a]).write().unwrap().pos = a;
+        self.node(&self.order[b]).write().unwrap().pos = b;
+    }
+
+    fn node(&self, node_id: &Id) -> SharedRef<HeapNode<T>> {
+        self.arena.get_node(node_id).expect("could not find node")
+    }
+}
+
+impl<T: Ord + Clone + Send + Sync, const D: usize> DaryHeap<T, D> {
+
+    /// Returns the smallest item in the heap.
+    pub fn peek(&self) -> Option<T> {
+        let id = self.order.first()?;
+        let item = self.node(id).read().unwrap().item.clone();
+        Some(item)
+    }
+
+    /// Returns the item referred to by the handle.
+    pub fn get(&self, handle: &HeapHandle) -> Option<T> {
+        let node_ref = self.arena.get_node(&handle.0)?;
+        let item = node_ref.read().unwrap().item.clone();
+        Some(item)
+    }
+}
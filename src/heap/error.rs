@@ -0,0 +1,23 @@
+use core::error::Error;
+use core::fmt;
+
+/// The ways in which an operation on a heap can fail.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HeapError {
+    /// The handle refers to an item which has already left the heap.
+    InvalidHandle,
+
+    /// The new key is larger than the key it was meant to replace.
+    KeyIncreased
+}
+
+impl fmt::Display for HeapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeapError::InvalidHandle => write!(f, "handle does not refer to an item in the heap"),
+            HeapError::KeyIncreased => write!(f, "new key is larger than the current key")
+        }
+    }
+}
+
+impl Error for HeapError {}
@@ -0,0 +1,101 @@
+pub mod dary;
+pub mod error;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BinaryHeap;
+    use std::cmp::Reverse;
+
+    use crate::heap::dary::*;
+    use crate::heap::error::*;
+
+    #[test]
+    fn test_dary_heap() {
+        let mut heap = DaryHeap::<(u32, char), 4>::new();
+        assert!(heap.is_empty());
+        assert!(heap.pop().is_none());
+
+        let a = heap.push((5, 'a'));
+        let b = heap.push((3, 'b'));
+        let c = heap.push((8, 'c'));
+        assert_eq!(heap.len(), 3);
+        assert_eq!(heap.peek(), Some((3, 'b')));
+
+        assert_eq!(heap.decrease_key(&c, (1, 'c')), Ok(()));
+        assert_eq!(heap.peek(), Some((1, 'c')));
+        assert_eq!(heap.decrease_key(&a, (9, 'a')), Err(HeapError::KeyIncreased));
+        assert_eq!(heap.get(&a), Some((5, 'a')));
+
+        assert_eq!(heap.pop(), Some((1, 'c')));
+        assert!(!heap.contains(&c));
+        assert_eq!(heap.decrease_key(&c, (0, 'c')), Err(HeapError::InvalidHandle));
+
+        // A stale handle must not refer to an item pushed later on.
+        let d = heap.push((4, 'd'));
+        assert_eq!(heap.get(&c), None);
+        assert_eq!(heap.remove(&b), Ok((3, 'b')));
+        assert_eq!(heap.remove(&b), Err(HeapError::InvalidHandle));
+
+        assert_eq!(heap.pop(), Some((4, 'd')));
+        assert!(!heap.contains(&d));
+        assert_eq!(heap.pop(), Some((5, 'a')));
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn test_dary_heap_against_binary_heap() {
+        let mut heap = DaryHeap::<(u32, usize), 3>::new();
+        let mut expected = BinaryHeap::new();
+
+        // The current key of every item, which is None once it leaves the heap.
+        let mut handles = vec![];
+        let mut keys: Vec<Option<u32>> = vec![];
+
+        let mut state: u32 = 9;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) % 1000
+        };
+
+        for _ in 0..3000 {
+            match next() % 4 {
+                0 => {
+                    // Drop the entries for keys which were decreased since being pushed.
+                    let mut popped = None;
+                    while let Some(Reverse((key, i))) = expected.pop() {
+                        if keys[i] == Some(key) {
+                            popped = Some((key, i));
+                            break;
+                        }
+                    }
+
+                    assert_eq!(heap.pop(), popped);
+                    if let Some((_, i)) = popped {
+                        keys[i] = None;
+                    }
+                }
+                1 if !handles.is_empty() => {
+                    let i = next() as usize % handles.len();
+                    match keys[i] {
+                        None => assert_eq!(heap.decrease_key(&handles[i], (0, i)), Err(HeapError::InvalidHandle)),
+                        Some(key) => {
+                            let new_key = key.saturating_sub(next() % 100);
+                            assert_eq!(heap.decrease_key(&handles[i], (new_key, i)), Ok(()));
+                            keys[i] = Some(new_key);
+                            expected.push(Reverse((new_key, i)));
+                        }
+                    }
+                }
+                _ => {
+                    let key = next();
+                    let i = handles.len();
+                    handles.push(heap.push((key, i)));
+                    keys.push(Some(key));
+                    expected.push(Reverse((key, i)));
+                }
+            }
+
+            assert_eq!(heap.len(), keys.iter().filter(|key| key.is_some()).count());
+        }
+    }
+}
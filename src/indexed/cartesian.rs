@@ -0,0 +1,180 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::{Bound, RangeBounds};
+
+/// A Cartesian tree holds a sequence of values in a binary tree which is a min-heap on the values,
+/// and whose in-order traversal is the sequence itself. The minimum of any range of the sequence is
+/// the lowest common ancestor of the ends of the range, which makes the tree a range-minimum query
+/// (RMQ) structure for sequences which don't change.
+///
+/// Nodes are the indices of their values. Equal values are ordered by index, so the leftmost of
+/// several minimums is the one that is found.
+///
+/// Walking the tree answers a query in time proportional to its depth, which is logarithmic for
+/// values in random order, but linear for sorted ones. A tree constructed with 'with_sparse_table'
+/// also keeps the minimum of every range whose length is a power of 2, which answers every query in
+/// constant time at the cost of O(n log n) memory.
+#[derive(Debug, Clone)]
+pub struct CartesianTree<T> {
+    values: Vec<T>,
+    root: Option<usize>,
+    parent: Vec<Option<usize>>,
+    left: Vec<Option<usize>>,
+    right: Vec<Option<usize>>,
+
+    // Entry 'k' holds the index of the minimum of every range of length 2^k, by its start.
+    sparse_table: Option<Vec<Vec<usize>>>
+}
+
+impl<T: Clone + PartialOrd> CartesianTree<T> {
+
+    /// Returns a new tree holding the given values, built in linear time.
+    pub fn from_slice(values: &[T]) -> Self {
+        let len = values.len();
+        let mut parent = vec![None; len];
+        let mut left = vec![None; len];
+        let mut right = vec![None; len];
+
+        // --
+        // The stack holds the right spine of the tree built so far. Every value becomes the right
+        // child of the last node on the spine which isn't larger than it, and takes the nodes it
+        // pops off the spine as its left subtree.
+        let mut spine: Vec<usize> = Vec::new();
+        for i in 0..len {
+            let mut last = None;
+            while let Some(&top) = spine.last() {
+                if values[top] <= values[i] {
+                    break;
+                }
+                last = spine.pop();
+            }
+
+            if let Some(child) = last {
+                parent[child] = Some(i);
+                left[i] = Some(child);
+            }
+            if let Some(&top) = spine.last() {
+                parent[i] = Some(top);
+                right[top] = Some(i);
+            }
+            spine.push(i);
+        }
+
+        Self {
+            values: values.to_vec(),
+            root: spine.first().copied(),
+            parent,
+            left,
+            right,
+            sparse_table: None
+        }
+    }
+
+    /// Returns a new tree holding the given values, which answers range queries in constant time.
+    pub fn with_sparse_table(values: &[T]) -> Self {
+        let mut tree = Self::from_slice(values);
+
+        let mut table = vec![(0..values.len()).collect::<Vec<usize>>()];
+        let mut width = 1;
+        while 2 * width <= values.len() {
+            let prev = table.last().unwrap();
+            let row = (0..=values.len() - 2 * width)
+                .map(|i| tree.min_of(prev[i], prev[i + width]))
+                .collect();
+
+            table.push(row);
+            width *= 2;
+        }
+
+        tree.sparse_table = Some(table);
+        tree
+    }
+
+    /// Returns the number of values in the tree.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns true if the tree holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns the value at the given index.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.values.get(index)
+    }
+
+    /// Returns the index of the minimum value, which is the root of the tree.
+    pub fn root(&self) -> Option<usize> {
+        self.root
+    }
+
+    /// Returns the parent of the given node.
+    pub fn parent(&self, index: usize) -> Option<usize> {
+        self.parent[index]
+    }
+
+    /// Returns the left child of the given node, i.e. the minimum of the values between the node and
+    /// the closest smaller value before it.
+    pub fn left(&self, index: usize) -> Option<usize> {
+        self.left[index]
+    }
+
+    /// Returns the right child of the given node, i.e. the minimum of the values between the node
+    /// and the closest value after it which is no larger.
+    pub fn right(&self, index: usize) -> Option<usize> {
+        self.right[index]
+    }
+
+    /// Returns the index of the minimum value within the given range of indices, or None if the range
+    /// is empty.
+    pub fn range_min_index<R: RangeBounds<usize>>(&self, range: R) -> Option<usize> {
+        let start = match range.start_bound() {
+            Bound::Included(start) => *start,
+            Bound::Excluded(start) => start + 1,
+            Bound::Unbounded => 0
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => end + 1,
+            Bound::Excluded(end) => *end,
+            Bound::Unbounded => self.len()
+        };
+
+        assert!(end <= self.len(), "index out of bounds");
+        if start >= end {
+            return None;
+        }
+
+        if let Some(table) = &self.sparse_table {
+            // Two (possibly overlapping) ranges whose length is a power of 2 cover the whole range.
+            let k = (end - start).ilog2() as usize;
+            return Some(self.min_of(table[k][start], table[k][end - (1 << k)]));
+        }
+
+        // The subtree of every node spans a range of indices, and the first node found inside the
+        // query range is the minimum of a subtree spanning all of it.
+        let mut node = self.root?;
+        while node < start || node >= end {
+            node = match node < start {
+                true => self.right[node]?,
+                false => self.left[node]?
+            };
+        }
+
+        Some(node)
+    }
+
+    /// Returns the minimum value within the given range of indices, or None if the range is empty.
+    pub fn range_min<R: RangeBounds<usize>>(&self, range: R) -> Option<&T> {
+        self.range_min_index(range).map(|index| &self.values[index])
+    }
+
+    /// Returns whichever of the two indices holds the smaller value, preferring 'a' on ties.
+    fn min_of(&self, a: usize, b: usize) -> usize {
+        match self.values[b] < self.values[a] {
+            true => b,
+            false => a
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::{AddAssign, Bound, RangeBounds, Sub};
+
+/// A Fenwick tree (or binary indexed tree) holds a sequence of values, and keeps running sums of
+/// them so that both updating a value and summing a prefix of the sequence take logarithmic time.
+///
+/// The tree is implicit in a flat list: entry 'i' (counting from 1) holds the sum of the values
+/// ending at 'i' whose count is the lowest set bit of 'i'.
+#[derive(Debug, Clone)]
+pub struct FenwickTree<T> {
+    tree: Vec<T>
+}
+
+impl<T: Copy + Default + AddAssign + Sub<Output = T>> FenwickTree<T> {
+
+    /// Returns a new tree holding 'len' values, all set to the default (i.e. zero).
+    pub fn new(len: usize) -> Self {
+        Self {
+            tree: vec![T::default(); len + 1]
+        }
+    }
+
+    /// Returns a new tree holding the given values.
+    pub fn from_slice(values: &[T]) -> Self {
+        let mut tree = vec![T::default()];
+        tree.extend_from_slice(values);
+
+        // Each entry passes its sum on to the next entry covering it, which builds every sum in a
+        // single pass.
+        for i in 1..tree.len() {
+            let parent = i + lowest_bit(i);
+            if parent < tree.len() {
+                let sum = tree[i];
+                tree[parent] += sum;
+            }
+        }
+
+        Self { tree }
+    }
+
+    /// Returns the number of values in the tree.
+    pub fn len(&self) -> usize {
+        self.tree.len() - 1
+    }
+
+    /// Returns true if the tree holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Adds 'delta' to the value at the given index.
+    pub fn update(&mut self, index: usize, delta: T) {
+        assert!(index < self.len(), "index out of bounds");
+
+        let mut i = index + 1;
+        while i < self.tree.len() {
+            self.tree[i] += delta;
+            i += lowest_bit(i);
+        }
+    }
+
+    /// Returns the sum of the first 'len' values.
+    pub fn prefix_sum(&self, len: usize) -> T {
+        assert!(len <= self.len(), "index out of bounds");
+
+        let mut sum = T::default();
+        let mut i = len;
+        while i > 0 {
+            sum += self.tree[i];
+            i -= lowest_bit(i);
+        }
+
+        sum
+    }
+
+    /// Returns the sum of the values within the given range of indices.
+    pub fn range_sum<R: RangeBounds<usize>>(&self, range: R) -> T {
+        let start = match range.start_bound() {
+            Bound::Included(start) => *start,
+            Bound::Excluded(start) => start + 1,
+            Bound::Unbounded => 0
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => end + 1,
+            Bound::Excluded(end) => *end,
+            Bound::Unbounded => self.len()
+        };
+
+        assert!(start <= end, "range starts after it ends");
+        self.prefix_sum(end) - self.prefix_sum(start)
+    }
+
+    /// Returns the value at the given index.
+    pub fn get(&self, index: usize) -> T {
+        self.range_sum(index..=index)
+    }
+}
+
+impl<T: Copy + Default + AddAssign + Sub<Output = T> + PartialOrd> FenwickTree<T> {
+
+    /// Returns the smallest index whose prefix sum (including the value at the index) is at least
+    /// 'target', or 'len()' if there is none. The values must not be negative.
+    ///
+    /// With values counting how often each index occurs, this finds the index with a given rank.
+    pub fn lower_bound(&self, target: T) -> usize {
+        let mut pos = 0;
+        let mut sum = T::default();
+
+        // --
+        // Descend through the implicit tree, skipping every block whose sum still falls short.
+        let mut step = (self.tree.len() - 1).checked_ilog2().map_or(0, |log| 1 << log);
+        while step > 0 {
+            if pos + step < self.tree.len() {
+                let mut next = sum;
+                next += self.tree[pos + step];
+
+                if next < target {
+                    pos += step;
+                    sum = next;
+                }
+            }
+            step /= 2;
+        }
+
+        pos
+    }
+}
+
+fn lowest_bit(i: usize) -> usize {
+    i & i.wrapping_neg()
+}
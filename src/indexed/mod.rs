@@ -0,0 +1,154 @@
+pub mod cartesian;
+pub mod fenwick;
+
+#[cfg(test)]
+mod tests {
+    use crate::indexed::cartesian::*;
+    use crate::indexed::fenwick::*;
+
+    #[test]
+    fn test_fenwick_tree() {
+        let mut tree = FenwickTree::<i64>::new(8);
+        assert_eq!(tree.len(), 8);
+        assert_eq!(tree.prefix_sum(8), 0);
+
+        tree.update(0, 5);
+        tree.update(3, 2);
+        tree.update(7, -1);
+        tree.update(3, 1);
+        assert_eq!(tree.prefix_sum(0), 0);
+        assert_eq!(tree.prefix_sum(1), 5);
+        assert_eq!(tree.prefix_sum(4), 8);
+        assert_eq!(tree.prefix_sum(8), 7);
+        assert_eq!(tree.range_sum(1..4), 3);
+        assert_eq!(tree.range_sum(3..=7), 2);
+        assert_eq!(tree.range_sum(..), 7);
+        assert_eq!(tree.get(3), 3);
+
+        // Counting occurrences turns 'lower_bound' into finding the k-th smallest element.
+        let counts = FenwickTree::from_slice(&[0u32, 2, 0, 1, 3, 0]);
+        assert_eq!(counts.lower_bound(1), 1);
+        assert_eq!(counts.lower_bound(2), 1);
+        assert_eq!(counts.lower_bound(3), 3);
+        assert_eq!(counts.lower_bound(6), 4);
+        assert_eq!(counts.lower_bound(7), 6);
+        assert_eq!(counts.lower_bound(0), 0);
+
+        let empty = FenwickTree::<f32>::new(0);
+        assert!(empty.is_empty());
+        assert_eq!(empty.lower_bound(1.0), 0);
+    }
+
+    #[test]
+    fn test_fenwick_tree_against_brute_force() {
+        let mut state: u32 = 5;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) as usize
+        };
+
+        let initial: Vec<u64> = (0..100).map(|_| (next() % 10) as u64).collect();
+        let mut values = initial.clone();
+        let mut tree = FenwickTree::from_slice(&initial);
+
+        for _ in 0..1000 {
+            let i = next() % values.len();
+            let delta = (next() % 5) as u64;
+            tree.update(i, delta);
+            values[i] += delta;
+
+            let a = next() % (values.len() + 1);
+            let b = a + next() % (values.len() - a + 1);
+            assert_eq!(tree.range_sum(a..b), values[a..b].iter().sum::<u64>());
+
+            let target = (next() % 2000) as u64;
+            let expected = (0..values.len())
+                .find(|i| values[..=*i].iter().sum::<u64>() >= target)
+                .unwrap_or(values.len());
+            assert_eq!(tree.lower_bound(target), expected);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_fenwick_tree_out_of_bounds() {
+        let mut tree = FenwickTree::<i32>::new(4);
+        tree.update(4, 1);
+    }
+
+    #[test]
+    fn test_cartesian_tree() {
+        let values = [9, 3, 7, 1, 8, 12, 10, 20, 15, 18, 5];
+        let tree = CartesianTree::from_slice(&values);
+        assert_eq!(tree.len(), 11);
+
+        // 1 is the minimum, with [9, 3, 7] to its left and [8, ..., 5] to its right.
+        assert_eq!(tree.root(), Some(3));
+        assert_eq!(tree.parent(3), None);
+        assert_eq!((tree.left(3), tree.right(3)), (Some(1), Some(10)));
+        assert_eq!((tree.left(1), tree.right(1)), (Some(0), Some(2)));
+        assert_eq!((tree.left(10), tree.right(10)), (Some(4), None));
+        assert_eq!(tree.parent(4), Some(10));
+
+        for tree in [tree, CartesianTree::with_sparse_table(&values)] {
+            assert_eq!(tree.range_min(..), Some(&1));
+            assert_eq!(tree.range_min(4..10), Some(&8));
+            assert_eq!(tree.range_min(5..=7), Some(&10));
+            assert_eq!(tree.range_min_index(7..), Some(10));
+            assert_eq!(tree.range_min(2..2), None);
+        }
+
+        // The leftmost of several minimums is the one that is found.
+        let ties = [4, 2, 6, 2, 2, 7];
+        for tree in [CartesianTree::from_slice(&ties), CartesianTree::with_sparse_table(&ties)] {
+            assert_eq!(tree.range_min_index(..), Some(1));
+            assert_eq!(tree.range_min_index(2..), Some(3));
+        }
+
+        let empty = CartesianTree::<f32>::with_sparse_table(&[]);
+        assert!(empty.is_empty());
+        assert_eq!(empty.root(), None);
+        assert_eq!(empty.range_min(..), None);
+    }
+
+    #[test]
+    fn test_cartesian_tree_against_brute_force() {
+        let mut state: u32 = 11;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) as usize
+        };
+
+        for len in [1, 2, 7, 64, 200] {
+            let values: Vec<u32> = (0..len).map(|_| (next() % 50) as u32).collect();
+            let trees = [CartesianTree::from_slice(&values), CartesianTree::with_sparse_table(&values)];
+
+            for tree in &trees {
+                // Every node is smaller than its children, and the parent links match the child links.
+                for i in 0..len {
+                    for child in [tree.left(i), tree.right(i)].into_iter().flatten() {
+                        assert!(values[i] <= values[child]);
+                        assert_eq!(tree.parent(child), Some(i));
+                    }
+                }
+            }
+
+            for _ in 0..500 {
+                let a = next() % len;
+                let b = a + 1 + next() % (len - a);
+                let expected = (a..b).min_by_key(|i| (values[*i], *i));
+
+                for tree in &trees {
+                    assert_eq!(tree.range_min_index(a..b), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_cartesian_tree_out_of_bounds() {
+        let tree = CartesianTree::from_slice(&[1, 2, 3]);
+        tree.range_min(1..5);
+    }
+}
@@ -1,3 +1,20 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 pub mod arena;
 pub mod trie;
-mod spatial;
+pub mod spatial;
+pub mod bst;
+pub mod btree;
+pub mod hash;
+pub mod heap;
+pub mod indexed;
+pub mod rope;
+pub mod skiplist;
+pub mod tree;
+pub mod random;
+pub mod metrics;
+pub mod visualize;
+pub mod sync;
+pub mod collections;
@@ -0,0 +1,69 @@
+/// Trees which can report on their own shape, e.g. to keep an eye on the health of a tree in
+/// production and notice when it degenerates into a long chain.
+///
+/// Every method walks the whole tree, so they are meant to be sampled once in a while rather than
+/// called on every operation.
+pub trait Metrics {
+    /// Returns the depth of the deepest node, where the root is at depth 0.
+    fn depth(&self) -> usize;
+
+    /// Returns the number of nodes in the tree, including the root.
+    fn node_count(&self) -> usize;
+
+    /// Returns the average number of non-empty children of the nodes which have any, or 0 if
+    /// there are no such nodes. The lower this gets, the more the tree resembles a linked list.
+    fn avg_branching_factor(&self) -> f64;
+
+    /// Returns the approximate number of bytes used by the nodes of the tree, including memory the
+    /// nodes allocate themselves (e.g. their buckets or the links to their children).
+    fn memory_bytes_estimate(&self) -> usize;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metrics::*;
+    use crate::spatial::quadtree::point_quadtree::*;
+    use crate::spatial::quadtree::prelude::*;
+    use crate::trie::grammar::*;
+    use crate::trie::trie::*;
+
+    #[test]
+    fn test_metrics() {
+        let mut trie = Trie::<usize>::new(Grammar::default());
+        assert_eq!((trie.depth(), trie.node_count(), trie.avg_branching_factor()), (0, 1, 0.0));
+
+        for (i, word) in ["car", "cart", "cat", "dog"].iter().enumerate() {
+            trie.insert(word, i).unwrap();
+        }
+
+        // The root has 2 children, "ca" has 2 and every other inner node has 1.
+        assert_eq!(trie.depth(), 4);
+        assert_eq!(trie.node_count(), 9);
+        assert_eq!(trie.avg_branching_factor(), 8.0 / 6.0);
+        assert_eq!(trie.memory_bytes_estimate(), trie.memory_stats().approx_bytes);
+
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([100.0, 100.0])
+        };
+        let config = QuadtreeConfig { bucket_capacity: 4, max_depth: 12 };
+
+        let mut spread = PointQuadtree::<usize>::with_config(&bbox, config);
+        let mut clustered = PointQuadtree::<usize>::with_config(&bbox, config);
+        for i in 0..256 {
+            let (x, y) = ((i % 16) as f32, (i / 16) as f32);
+            assert!(spread.insert(&Vec2::from([x * 6.25 + 1.0, y * 6.25 + 1.0]), i).is_ok());
+            assert!(clustered.insert(&Vec2::from([x * 0.001, y * 0.001]), i).is_ok());
+        }
+
+        // A grid fills every quad, while a cluster in a corner subdivides a single one over and over.
+        assert_eq!(spread.depth(), 3);
+        assert!(spread.avg_branching_factor() > 3.0);
+        assert!(clustered.depth() >= 10);
+        assert!(clustered.avg_branching_factor() < 2.0);
+        assert!(spread.memory_bytes_estimate() > 256 * std::mem::size_of::<Node<usize>>());
+
+        let empty = PointQuadtree::<usize>::new(&bbox);
+        assert_eq!((empty.depth(), empty.node_count(), empty.avg_branching_factor()), (0, 1, 0.0));
+    }
+}
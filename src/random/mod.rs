@@ -0,0 +1,112 @@
+#[cfg(not(feature = "std"))]
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::collections::hash_map::RandomState;
+#[cfg(feature = "std")]
+use std::hash::{BuildHasher, Hasher};
+
+/// A source of random numbers for the sampling methods of the trees, e.g. 'Trie::sample'.
+///
+/// It is implemented for every closure returning random u64s, so any generator can be plugged in
+/// (with the rand crate, `&mut || rng.gen::<u64>()`), and by the small XorShift generator below.
+pub trait Rng {
+    fn next_u64(&mut self) -> u64;
+
+    /// Returns a uniformly distributed number in 0..n, where n must not be 0.
+    fn below(&mut self, n: u64) -> u64 {
+        assert!(n > 0, "range must not be empty");
+
+        // Draws past the largest multiple of n would favor the small numbers, so they are redrawn.
+        let zone = u64::MAX - u64::MAX % n;
+        loop {
+            let x = self.next_u64();
+            if x < zone {
+                return x % n;
+            }
+        }
+    }
+}
+
+impl<F: FnMut() -> u64> Rng for F {
+    fn next_u64(&mut self) -> u64 {
+        self()
+    }
+}
+
+/// A fast xorshift generator, which is plenty for sampling but not for anything security related.
+/// It also generates the priorities of Treap and Rope nodes and the levels of SkipList nodes.
+#[derive(Debug, Copy, Clone)]
+pub struct XorShift {
+    state: u64
+}
+
+impl Default for XorShift {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl XorShift {
+    /// Constructs a new generator with an unpredictable seed.
+    #[cfg(feature = "std")]
+    pub fn new() -> Self {
+        // The seed only needs to be unpredictable, which the std's randomly keyed hasher already is.
+        Self::with_seed(RandomState::new().build_hasher().finish())
+    }
+
+    /// Constructs a new generator with a seed that differs from the generators constructed before
+    /// it. Without the 'std' feature there's no source of randomness, so the seeds are the same on
+    /// every run; use 'with_seed' to seed the generator from the target's own source instead.
+    #[cfg(not(feature = "std"))]
+    pub fn new() -> Self {
+        static GENERATORS: AtomicUsize = AtomicUsize::new(0);
+
+        // Scrambles the count with splitmix64, so that consecutive seeds share no bits.
+        let mut seed = (GENERATORS.fetch_add(1, Ordering::Relaxed) as u64).wrapping_add(1).wrapping_mul(0x9e3779b97f4a7c15);
+        seed = (seed ^ (seed >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        seed = (seed ^ (seed >> 27)).wrapping_mul(0x94d049bb133111eb);
+        Self::with_seed(seed ^ (seed >> 31))
+    }
+
+    /// Constructs a new generator from the given seed, which makes its output reproducible.
+    pub fn with_seed(seed: u64) -> Self {
+        // The generator would only ever produce 0 from a seed of 0.
+        Self { state: seed.max(1) }
+    }
+}
+
+impl Rng for XorShift {
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::random::*;
+
+    #[test]
+    fn test_rng() {
+        let (mut a, mut b) = (XorShift::with_seed(3), XorShift::with_seed(3));
+        assert!((0..10).all(|_| a.next_u64() == b.next_u64()));
+        assert_ne!(XorShift::with_seed(0).next_u64(), 0);
+
+        let mut counts = [0; 6];
+        for _ in 0..6000 {
+            counts[a.below(6) as usize] += 1;
+        }
+        assert!(counts.iter().all(|count| (800..1200).contains(count)));
+
+        // Closures are generators too.
+        let mut state = 0;
+        let mut counter = || {
+            state += 1;
+            state
+        };
+        assert_eq!(counter.below(4), 1);
+        assert_eq!(counter.below(4), 2);
+    }
+}
@@ -0,0 +1,112 @@
+#[allow(clippy::module_inception)]
+pub mod rope;
+
+#[cfg(test)]
+mod tests {
+    use crate::rope::rope::*;
+    use crate::visualize::ToDot;
+
+    #[test]
+    fn test_rope() {
+        let mut rope = Rope::new();
+        assert!(rope.is_empty());
+        assert_eq!(rope.len_lines(), 1);
+        assert_eq!(rope.char(0), None);
+
+        rope.insert(0, "hello world");
+        rope.insert(5, ",");
+        rope.insert(12, "!\nsecond line\n");
+        assert_eq!(rope.to_string(), "hello, world!\nsecond line\n");
+        assert_eq!(rope.len_chars(), 26);
+        assert_eq!(rope.len_lines(), 3);
+
+        assert_eq!(rope.slice(7..12), "world");
+        assert_eq!(rope.slice(..), rope.to_string());
+        assert_eq!(rope.char(4), Some('o'));
+        assert_eq!(rope.char(26), None);
+
+        assert_eq!(rope.line(0), Some("hello, world!\n".to_string()));
+        assert_eq!(rope.line(1), Some("second line\n".to_string()));
+        assert_eq!(rope.line(2), Some(String::new()));
+        assert_eq!(rope.line(3), None);
+        assert_eq!(rope.line_to_char(1), Some(14));
+        assert_eq!(rope.char_to_line(13), Some(0));
+        assert_eq!(rope.char_to_line(14), Some(1));
+        assert_eq!(rope.char_to_line(26), Some(2));
+        assert_eq!(rope.char_to_line(27), None);
+
+        rope.remove(5..7);
+        rope.remove(..=4);
+        assert_eq!(rope.to_string(), "world!\nsecond line\n");
+
+        // Chars are indexed as chars rather than bytes.
+        let mut rope = Rope::from("näïve ✓");
+        rope.insert(6, "→ ");
+        assert_eq!(rope.to_string(), "näïve → ✓");
+        assert_eq!(rope.char(1), Some('ä'));
+        assert_eq!(rope.slice(6..7), "→");
+
+        rope.remove(..);
+        assert!(rope.is_empty());
+        assert!(rope.to_dot().starts_with("digraph Rope {"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_rope_insert_out_of_bounds() {
+        let mut rope = Rope::from("abc");
+        rope.insert(4, "d");
+    }
+
+    #[test]
+    fn test_rope_against_string() {
+        let mut rope = Rope::new();
+        let mut text: Vec<char> = vec![];
+
+        let mut state: u32 = 3;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) as usize
+        };
+
+        let alphabet: Vec<char> = "abcdefghij\n é🌳".chars().collect();
+
+        for _ in 0..2000 {
+            let idx = next() % (text.len() + 1);
+
+            if next() % 3 == 0 && !text.is_empty() {
+                let end = (idx + next() % 300).min(text.len());
+                rope.remove(idx..end);
+                text.drain(idx..end);
+            } else {
+                // Mostly small insertions, with the odd large one spanning several chunks.
+                let len = if next() % 10 == 0 { next() % 1000 } else { next() % 5 };
+                let insert: String = (0..len).map(|_| alphabet[next() % alphabet.len()]).collect();
+                rope.insert(idx, &insert);
+                text.splice(idx..idx, insert.chars());
+            }
+
+            assert_eq!(rope.len_chars(), text.len());
+        }
+
+        let expected: String = text.iter().collect();
+        assert_eq!(rope.to_string(), expected);
+        assert_eq!(rope.chunks().collect::<String>(), expected);
+        assert!(rope.chunks().count() > 1);
+
+        let lines: Vec<&str> = expected.split_inclusive('\n').collect();
+        let line_count = expected.matches('\n').count() + 1;
+        assert_eq!(rope.len_lines(), line_count);
+        for (i, line) in lines.iter().enumerate() {
+            assert_eq!(rope.line(i).unwrap(), *line);
+        }
+
+        for _ in 0..200 {
+            let start = next() % (text.len() + 1);
+            let end = start + next() % (text.len() - start + 1);
+            assert_eq!(rope.slice(start..end), text[start..end].iter().collect::<String>());
+            assert_eq!(rope.char(start), text.get(start).copied());
+            assert_eq!(rope.char_to_line(start), Some(text[..start].iter().filter(|c| **c == '\n').count()));
+        }
+    }
+}
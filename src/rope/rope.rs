@@ -0,0 +1,569 @@
+use alloc::{format, vec};
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::{Bound, RangeBounds};
+
+use crate::arena::*;
+use crate::arena::prelude::*;
+use crate::random::{Rng, XorShift};
+use crate::visualize::{DotWriter, ToDot};
+
+type Id = GenerationalId;
+
+/// The most chars a single chunk of text will grow to through insertions.
+const MAX_CHUNK_CHARS: usize = 128;
+
+#[derive(Debug, Clone)]
+struct RopeNode {
+    pub id: Id,
+
+    pub chunk: String,
+
+    /// The number of chars and line breaks in the chunk held by this node.
+    pub chunk_chars: usize,
+    pub chunk_lines: usize,
+
+    /// The number of chars and line breaks in the subtree rooted at this node.
+    pub chars: usize,
+    pub lines: usize,
+
+    /// Nodes are heap-ordered by priority, i.e. no node has a higher priority than its parent.
+    pub priority: u64,
+
+    pub left: Option<Id>,
+    pub right: Option<Id>
+}
+
+impl HasId for RopeNode {
+    type Id = Id;
+
+    fn get_id(&self) -> Self::Id {
+        self.id
+    }
+}
+
+impl RopeNode {
+    /// Constructs a new leaf holding the given chunk
+    pub fn new(id: Id, chunk: String, priority: u64) -> Self {
+        let chunk_chars = chunk.chars().count();
+        let chunk_lines = chunk.matches('\n').count();
+
+        Self {
+            id,
+            chunk,
+            chunk_chars,
+            chunk_lines,
+            chars: chunk_chars,
+            lines: chunk_lines,
+            priority,
+            left: None,
+            right: None
+        }
+    }
+
+    /// Replaces the chunk held by this node, without updating the subtree counts.
+    pub fn set_chunk(&mut self, chunk: String) {
+        self.chunk_chars = chunk.chars().count();
+        self.chunk_lines = chunk.matches('\n').count();
+        self.chunk = chunk;
+    }
+}
+
+/// A Rope is a sequence of chars which can be edited anywhere in logarithmic time, which makes it
+/// a good fit for holding the contents of large text documents.
+///
+/// The text is broken up into chunks, which are the nodes of a treap ordered by their position in
+/// the text. Every node keeps track of the number of chars and line breaks below it, so positions
+/// can be found by descending the tree, and edits are made by splitting the tree apart and merging
+/// it back together.
+pub struct Rope {
+    arena: GenerationalArena<RopeNode>,
+    root: Option<Id>,
+
+    /// The generator for the priorities of new nodes.
+    rng: XorShift
+}
+
+impl Default for Rope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<&str> for Rope {
+    fn from(text: &str) -> Self {
+        let mut rope = Self::new();
+        rope.insert(0, text);
+        rope
+    }
+}
+
+impl fmt::Display for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for chunk in self.chunks() {
+            f.write_str(&chunk)?;
+        }
+        Ok(())
+    }
+}
+
+impl Rope {
+
+    /// Constructs a new empty Rope
+    pub fn new() -> Self {
+        Self {
+            arena: GenerationalArena::new(),
+            root: None,
+            rng: XorShift::new()
+        }
+    }
+
+    /// Returns the number of chars in the rope.
+    pub fn len_chars(&self) -> usize {
+        self.chars_of(&self.root)
+    }
+
+    /// Returns the number of lines in the rope, which is always one more than the number of line
+    /// breaks.
+    pub fn len_lines(&self) -> usize {
+        self.root.map_or(0, |id| self.node(&id).read().unwrap().lines) + 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Inserts the text so that it starts at the given char index.
+    pub fn insert(&mut self, char_idx: usize, text: &str) {
+        assert!(char_idx <= self.len_chars(), "char index out of bounds");
+
+        if text.is_empty() {
+            return;
+        }
+
+        // --
+        // Small insertions go straight into the chunk at the given index if it has room to spare,
+        // so that typing doesn't leave behind a trail of tiny chunks.
+        let text_chars = text.chars().count();
+        if let Some((path, offset)) = self.path_to(char_idx) {
+            let node_ref = self.node(path.last().unwrap());
+            let mut node = node_ref.write().unwrap();
+
+            if node.chunk_chars + text_chars <= MAX_CHUNK_CHARS {
+                let byte_idx = byte_index(&node.chunk, offset);
+                let mut chunk = core::mem::take(&mut node.chunk);
+                chunk.insert_str(byte_idx, text);
+                node.set_chunk(chunk);
+                drop(node);
+
+                for id in path.iter().rev() {
+                    self.update(id);
+                }
+                return;
+            }
+        }
+
+        let root = self.root.take();
+        let (left, right) = self._split(root, char_idx);
+        let middle = self.build(text);
+
+        let left = self._merge(left, middle);
+        self.root = self._merge(left, right);
+    }
+
+    /// Removes the chars within the given range.
+    pub fn remove<R: RangeBounds<usize>>(&mut self, range: R) {
+        let (start, end) = self.char_range(&range);
+        if start == end {
+            return;
+        }
+
+        let root = self.root.take();
+        let (left, rest) = self._split(root, start);
+        let (middle, right) = self._split(rest, end - start);
+
+        if let Some(middle) = middle {
+            self.delete_subtree(middle);
+        }
+        self.root = self._merge(left, right);
+    }
+
+    /// Returns the text within the given range of chars.
+    pub fn slice<R: RangeBounds<usize>>(&self, range: R) -> String {
+        let (start, end) = self.char_range(&range);
+
+        let mut result = String::new();
+        self._slice(&self.root, start, end, &mut result);
+        result
+    }
+
+    /// Returns the char at the given index.
+    pub fn char(&self, char_idx: usize) -> Option<char> {
+        let (path, offset) = self.path_to(char_idx)?;
+
+        let node_ref = self.node(path.last().unwrap());
+        let c = node_ref.read().unwrap().chunk.chars().nth(offset);
+        c
+    }
+
+    /// Returns the given line, including its trailing line break if it has one.
+    pub fn line(&self, line_idx: usize) -> Option<String> {
+        let start = self.line_to_char(line_idx)?;
+        let end = self.line_to_char(line_idx + 1).unwrap_or(self.len_chars());
+        Some(self.slice(start..end))
+    }
+
+    /// Returns the index of the line holding the char at the given index, where an index of
+    /// 'len_chars()' belongs to the last line.
+    pub fn char_to_line(&self, char_idx: usize) -> Option<usize> {
+        if char_idx > self.len_chars() {
+            return None;
+        }
+
+        // --
+        // Count the line breaks before the char.
+        let mut remaining = char_idx;
+        let mut line = 0;
+        let mut node_id = self.root;
+
+        while let Some(id) = node_id {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+            let left_chars = self.chars_of(&node.left);
+
+            if remaining < left_chars {
+                node_id = node.left;
+                continue;
+            }
+
+            remaining -= left_chars;
+            line += self.lines_of(&node.left);
+
+            if remaining < node.chunk_chars {
+                line += node.chunk.chars().take(remaining).filter(|c| *c == '\n').count();
+                break;
+            }
+
+            remaining -= node.chunk_chars;
+            line += node.chunk_lines;
+            node_id = node.right;
+        }
+
+        Some(line)
+    }
+
+    /// Returns the index of the first char of the given line.
+    pub fn line_to_char(&self, line_idx: usize) -> Option<usize> {
+        if line_idx >= self.len_lines() {
+            return None;
+        }
+        if line_idx == 0 {
+            return Some(0);
+        }
+
+        // --
+        // The line starts right after the 'line_idx'-th line break.
+        let mut remaining = line_idx;
+        let mut char_idx = 0;
+        let mut node_id = self.root;
+
+        while let Some(id) = node_id {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+            let left_lines = self.lines_of(&node.left);
+
+            if remaining <= left_lines {
+                node_id = node.left;
+                continue;
+            }
+
+            remaining -= left_lines;
+            char_idx += self.chars_of(&node.left);
+
+            if remaining <= node.chunk_lines {
+                let (offset, _) = node.chunk.chars()
+                    .enumerate()
+                    .filter(|(_, c)| *c == '\n')
+                    .nth(remaining - 1)
+                    .unwrap();
+                return Some(char_idx + offset + 1);
+            }
+
+            remaining -= node.chunk_lines;
+            char_idx += node.chunk_chars;
+            node_id = node.right;
+        }
+
+        unreachable!("line breaks are counted incorrectly")
+    }
+
+    /// Returns the chunks of text making up the rope, in order.
+    pub fn chunks(&self) -> impl Iterator<Item = String> {
+        let mut result = vec![];
+        self._chunks(&self.root, &mut result);
+        result.into_iter()
+    }
+
+    /// Returns the path from the root to the node holding the char at the given index, along with
+    /// the offset of the char within that node's chunk. An index of 'len_chars()' refers to the
+    /// position right after the last char.
+    fn path_to(&self, char_idx: usize) -> Option<(Vec<Id>, usize)> {
+        let mut remaining = char_idx;
+        let mut path = vec![];
+        let mut node_id = self.root;
+
+        while let Some(id) = node_id {
+            path.push(id);
+
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+            let left_chars = self.chars_of(&node.left);
+
+            if remaining < left_chars {
+                node_id = node.left;
+                continue;
+            }
+
+            remaining -= left_chars;
+            if remaining < node.chunk_chars || (remaining == node.chunk_chars && node.right.is_none()) {
+                return Some((path, remaining));
+            }
+
+            remaining -= node.chunk_chars;
+            node_id = node.right;
+        }
+
+        None
+    }
+
+    /// Resolves the range into start and end char indices, panicking if it is out of bounds.
+    fn char_range<R: RangeBounds<usize>>(&self, range: &R) -> (usize, usize) {
+        let start = match range.start_bound() {
+            Bound::Included(start) => *start,
+            Bound::Excluded(start) => start + 1,
+            Bound::Unbounded => 0
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => end + 1,
+            Bound::Excluded(end) => *end,
+            Bound::Unbounded => self.len_chars()
+        };
+
+        assert!(start <= end && end <= self.len_chars(), "char range out of bounds");
+        (start, end)
+    }
+
+    /// Builds a subtree holding the given text, returning its root.
+    fn build(&mut self, text: &str) -> Option<Id> {
+        let mut root = None;
+        let mut start = 0;
+
+        while start < text.len() {
+            let end = text[start..].char_indices()
+                .nth(MAX_CHUNK_CHARS)
+                .map_or(text.len(), |(idx, _)| start + idx);
+
+            let id = self.arena.get_new_id();
+            let priority = self.next_priority();
+            self.arena.add_node(RopeNode::new(id, text[start..end].to_string(), priority)).expect("could not add node!");
+
+            root = self._merge(root, Some(id));
+            start = end;
+        }
+
+        root
+    }
+
+    /// Splits the given subtree into its first 'char_idx' chars and the rest, returning the roots
+    /// of both parts. A chunk straddling the split is cut in two.
+    fn _split(&mut self, node_id: Option<Id>, char_idx: usize) -> (Option<Id>, Option<Id>) {
+        let id = match node_id {
+            None => return (None, None),
+            Some(id) => id
+        };
+
+        let (left, right, left_chars, chunk_chars) = {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+            (node.left, node.right, self.chars_of(&node.left), node.chunk_chars)
+        };
+
+        if char_idx <= left_chars {
+            let (smaller, rest) = self._split(left, char_idx);
+            self.node(&id).write().unwrap().left = rest;
+            self.update(&id);
+            return (smaller, Some(id));
+        }
+
+        if char_idx >= left_chars + chunk_chars {
+            let (rest, larger) = self._split(right, char_idx - left_chars - chunk_chars);
+            self.node(&id).write().unwrap().right = rest;
+            self.update(&id);
+            return (Some(id), larger);
+        }
+
+        // --
+        // Keep the head of the chunk in this node, and move the tail to a new node in front of the
+        // right subtree.
+        let tail = {
+            let node_ref = self.node(&id);
+            let mut node = node_ref.write().unwrap();
+
+            let byte_idx = byte_index(&node.chunk, char_idx - left_chars);
+            let tail = node.chunk.split_off(byte_idx);
+            let head = core::mem::take(&mut node.chunk);
+
+            node.set_chunk(head);
+            node.right = None;
+            tail
+        };
+        self.update(&id);
+
+        let tail_id = self.arena.get_new_id();
+        let priority = self.next_priority();
+        self.arena.add_node(RopeNode::new(tail_id, tail, priority)).expect("could not add node!");
+
+        (Some(id), self._merge(Some(tail_id), right))
+    }
+
+    /// Joins 2 subtrees, where the text of 'a' comes before the text of 'b', returning the root of
+    /// the joined tree.
+    fn _merge(&self, a: Option<Id>, b: Option<Id>) -> Option<Id> {
+        let (a_id, b_id) = match (a, b) {
+            (None, b) => return b,
+            (a, None) => return a,
+            (Some(a), Some(b)) => (a, b)
+        };
+
+        let a_ref = self.node(&a_id);
+        let b_ref = self.node(&b_id);
+        let a_priority = a_ref.read().unwrap().priority;
+        let b_priority = b_ref.read().unwrap().priority;
+
+        if a_priority >= b_priority {
+            let right = a_ref.read().unwrap().right;
+            let right = self._merge(right, b);
+            a_ref.write().unwrap().right = right;
+            self.update(&a_id);
+            a
+        } else {
+            let left = b_ref.read().unwrap().left;
+            let left = self._merge(a, left);
+            b_ref.write().unwrap().left = left;
+            self.update(&b_id);
+            b
+        }
+    }
+
+    fn _slice(&self, node_id: &Option<Id>, start: usize, end: usize, out: &mut String) {
+        let node_ref = match node_id {
+            None => return,
+            Some(id) => self.node(id)
+        };
+        let node = node_ref.read().unwrap();
+
+        // Subtrees which lie entirely outside of the range are skipped.
+        let left_chars = self.chars_of(&node.left);
+        if start < left_chars {
+            self._slice(&node.left, start, end.min(left_chars), out);
+        }
+
+        let chunk_start = start.saturating_sub(left_chars);
+        let chunk_end = end.saturating_sub(left_chars).min(node.chunk_chars);
+        if chunk_start < chunk_end {
+            out.extend(node.chunk.chars().skip(chunk_start).take(chunk_end - chunk_start));
+        }
+
+        let offset = left_chars + node.chunk_chars;
+        if end > offset {
+            self._slice(&node.right, start.saturating_sub(offset), end - offset, out);
+        }
+    }
+
+    fn _chunks(&self, node_id: &Option<Id>, out: &mut Vec<String>) {
+        if let Some(id) = node_id {
+            let node_ref = self.node(id);
+            let node = node_ref.read().unwrap();
+
+            self._chunks(&node.left, out);
+            out.push(node.chunk.clone());
+            self._chunks(&node.right, out);
+        }
+    }
+
+    /// Removes every node of the given subtree from the arena.
+    fn delete_subtree(&mut self, node_id: Id) {
+        let node = self.take_node(&node_id);
+
+        for child in [node.left, node.right].into_iter().flatten() {
+            self.delete_subtree(child);
+        }
+    }
+
+    fn next_priority(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    /// Recomputes the char and line counts of the node from its chunk and children.
+    fn update(&self, node_id: &Id) {
+        let node_ref = self.node(node_id);
+        let mut node = node_ref.write().unwrap();
+
+        node.chars = node.chunk_chars + self.chars_of(&node.left) + self.chars_of(&node.right);
+        node.lines = node.chunk_lines + self.lines_of(&node.left) + self.lines_of(&node.right);
+    }
+
+    fn chars_of(&self, node_id: &Option<Id>) -> usize {
+        node_id.map_or(0, |id| self.node(&id).read().unwrap().chars)
+    }
+
+    fn lines_of(&self, node_id: &Option<Id>) -> usize {
+        node_id.map_or(0, |id| self.node(&id).read().unwrap().lines)
+    }
+
+    fn node(&self, node_id: &Id) -> SharedRef<RopeNode> {
+        self.arena.get_node(node_id).expect("could not find node")
+    }
+
+    /// Removes the node from the arena, handing back ownership of it.
+    fn take_node(&mut self, node_id: &Id) -> RopeNode {
+        let node_ref = self.node(node_id);
+        self.arena.delete_node(node_id).expect("could not delete node");
+
+        match Arc::try_unwrap(node_ref) {
+            Ok(node) => node.into_inner().unwrap(),
+            Err(_) => panic!("node is still referenced")
+        }
+    }
+}
+
+/// Returns the byte index of the char with the given index, or the length of the text if the char
+/// index is just past the end.
+fn byte_index(text: &str, char_idx: usize) -> usize {
+    text.char_indices().nth(char_idx).map_or(text.len(), |(idx, _)| idx)
+}
+
+impl ToDot for Rope {
+    fn to_dot(&self) -> String {
+        let mut dot = DotWriter::new("Rope");
+        let mut stack: Vec<Id> = self.root.into_iter().collect();
+
+        while let Some(id) = stack.pop() {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+
+            dot.node(&id, &format!("{:?}\n{} chars", node.chunk, node.chars), "shape=box");
+
+            for (child, name) in [(node.left, "L"), (node.right, "R")] {
+                if let Some(child_id) = child {
+                    dot.edge(&id, &child_id, name, "");
+                    stack.push(child_id);
+                }
+            }
+        }
+
+        dot.finish()
+    }
+}
@@ -0,0 +1,90 @@
+#[allow(clippy::module_inception)]
+pub mod skiplist;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::skiplist::skiplist::*;
+    use crate::visualize::ToDot;
+
+    #[test]
+    fn test_skip_list() {
+        let mut list = SkipList::<i32, &str>::new();
+        assert!(list.is_empty());
+        assert_eq!(list.levels(), 0);
+        assert!(list.first().is_none());
+        assert!(list.last().is_none());
+        assert!(list.remove(&1).is_none());
+
+        assert!(list.insert(5, "five").is_none());
+        assert!(list.insert(2, "two").is_none());
+        assert!(list.insert(8, "eight").is_none());
+        assert_eq!(list.insert(2, "deux"), Some("two"));
+        assert_eq!(list.len(), 3);
+
+        assert_eq!(list.get(&2), Some("deux"));
+        assert!(list.contains_key(&8));
+        assert!(!list.contains_key(&3));
+        assert_eq!(list.first(), Some((2, "deux")));
+        assert_eq!(list.last(), Some((8, "eight")));
+
+        assert_eq!(list.range(3..=8).collect::<Vec<_>>(), vec![(5, "five"), (8, "eight")]);
+        assert_eq!(list.range(..5).collect::<Vec<_>>(), vec![(2, "deux")]);
+        assert_eq!(list.range(..).next_back(), Some((8, "eight")));
+
+        let dot = list.to_dot();
+        assert!(dot.starts_with("digraph SkipList {"));
+        assert_eq!(dot.matches("shape=box").count(), 3);
+
+        assert_eq!(list.remove(&5), Some("five"));
+        assert!(list.remove(&5).is_none());
+        assert_eq!(list.remove(&2), Some("deux"));
+        assert_eq!(list.remove(&8), Some("eight"));
+        assert!(list.is_empty());
+        assert_eq!(list.levels(), 0);
+    }
+
+    #[test]
+    fn test_skip_list_against_btreemap() {
+        let mut list = SkipList::<u32, u32>::with_seed(42);
+        let mut expected = BTreeMap::new();
+
+        let mut state = 2024u32;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) % 1000
+        };
+
+        for i in 0..5000 {
+            let key = next();
+            if i % 3 == 0 {
+                assert_eq!(list.remove(&key), expected.remove(&key));
+            } else {
+                assert_eq!(list.insert(key, i), expected.insert(key, i));
+            }
+            assert_eq!(list.len(), expected.len());
+        }
+
+        assert_eq!(list.iter().collect::<Vec<_>>(), expected.clone().into_iter().collect::<Vec<_>>());
+        assert_eq!(list.first(), expected.first_key_value().map(|(k, v)| (*k, *v)));
+        assert_eq!(list.last(), expected.last_key_value().map(|(k, v)| (*k, *v)));
+        assert_eq!(
+            list.range(100..200).collect::<Vec<_>>(),
+            expected.range(100..200).map(|(k, v)| (*k, *v)).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            list.range((std::ops::Bound::Excluded(500), std::ops::Bound::Unbounded)).collect::<Vec<_>>(),
+            expected.range(501..).map(|(k, v)| (*k, *v)).collect::<Vec<_>>()
+        );
+
+        // With a promotion probability of 1/2, the number of levels grows like log2(n).
+        assert!(list.levels() > 3 && list.levels() < 25);
+
+        for key in expected.keys() {
+            assert!(list.remove(key).is_some());
+        }
+        assert!(list.is_empty());
+        assert_eq!(list.levels(), 0);
+    }
+}
@@ -0,0 +1,308 @@
+use alloc::{format, vec};
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::ops::RangeBounds;
+
+use crate::arena::*;
+use crate::arena::prelude::*;
+use crate::bst::bounds::*;
+use crate::random::{Rng, XorShift};
+use crate::visualize::{DotWriter, ToDot};
+
+type Id = GenerationalId;
+
+/// The most levels a SkipList will grow to, which comfortably covers 2^32 keys.
+const MAX_LEVEL: usize = 32;
+
+#[derive(Debug, Clone)]
+struct SkipNode<K: Send + Sync, V: Send + Sync> {
+    pub id: Id,
+
+    pub key: K,
+    pub value: V,
+
+    /// The next node at every level this node takes part in, level 0 links every node.
+    pub forward: Vec<Option<Id>>
+}
+
+impl<K: Send + Sync, V: Send + Sync> HasId for SkipNode<K, V> {
+    type Id = Id;
+
+    fn get_id(&self) -> Self::Id {
+        self.id
+    }
+}
+
+impl<K: Send + Sync, V: Send + Sync> SkipNode<K, V> {
+    /// Constructs a new node taking part in the given number of levels
+    pub fn new(id: Id, key: K, value: V, levels: usize) -> Self {
+        Self {
+            id,
+            key,
+            value,
+            forward: vec![None; levels]
+        }
+    }
+}
+
+/// This class represents a thread-safe ordered map, implemented as a skip list.
+///
+/// Every key is stored in a sorted linked list, and is randomly promoted into a sparser list on
+/// top of it with a probability of 1/2, and from there into sparser lists still. Searches start in
+/// the sparsest list and drop down a level whenever they would overshoot, which takes O(log n)
+/// steps in expectation. Unlike the balanced trees, no rebalancing is ever needed.
+pub struct SkipList<K: Ord + Send + Sync, V: Send + Sync> {
+    arena: GenerationalArena<SkipNode<K, V>>,
+
+    /// The first node of every level, of which only the lowest 'levels' are in use.
+    head: [Option<Id>; MAX_LEVEL],
+    levels: usize,
+
+    size: usize,
+
+    /// The generator for the levels of new nodes.
+    rng: XorShift
+}
+
+impl<K: Ord + Send + Sync, V: Send + Sync> Default for SkipList<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Send + Sync, V: Send + Sync> SkipList<K, V> {
+
+    /// Constructs a new empty SkipList
+    pub fn new() -> Self {
+        Self::with_rng(XorShift::new())
+    }
+
+    /// Constructs a new empty SkipList whose levels are generated from the given seed, which makes
+    /// the shape of the list reproducible.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(XorShift::with_seed(seed))
+    }
+
+    fn with_rng(rng: XorShift) -> Self {
+        Self {
+            arena: GenerationalArena::new(),
+            head: [None; MAX_LEVEL],
+            levels: 0,
+            size: 0,
+            rng
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of levels in use, which is 0 for an empty list.
+    pub fn levels(&self) -> usize {
+        self.levels
+    }
+
+    /// Inserts the key, returning the previous value if it already exists.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let mut preds = self._find_predecessors(|k| *k < key);
+
+        if let Some(id) = self.next(&preds[0], 0).filter(|id| self.node(id).read().unwrap().key == key) {
+            return Some(core::mem::replace(&mut self.node(&id).write().unwrap().value, value));
+        }
+
+        // --
+        // The new node is linked in right after its predecessor at every level it takes part in,
+        // where levels above the current top start out at the head.
+        let levels = self.next_level();
+        if levels > self.levels {
+            preds.resize(levels, None);
+            self.levels = levels;
+        }
+
+        let id = self.arena.get_new_id();
+        let mut node = SkipNode::new(id, key, value, levels);
+        for (level, pred) in preds[..levels].iter().enumerate() {
+            node.forward[level] = self.next(pred, level);
+        }
+        self.arena.add_node(node).expect("could not add node!");
+
+        for (level, pred) in preds[..levels].iter().enumerate() {
+            self.set_next(pred, level, Some(id));
+        }
+
+        self.size += 1;
+        None
+    }
+
+    /// Removes the key, returning its value if it existed.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let preds = self._find_predecessors(|k| k < key);
+        let id = self.next(&preds[0], 0).filter(|id| self.node(id).read().unwrap().key == *key)?;
+
+        // The predecessor at every level of the node links straight to it, so it is unlinked by
+        // handing over its own links.
+        let forward = self.node(&id).read().unwrap().forward.clone();
+        for (level, next) in forward.into_iter().enumerate() {
+            self.set_next(&preds[level], level, next);
+        }
+
+        while self.levels > 0 && self.head[self.levels - 1].is_none() {
+            self.levels -= 1;
+        }
+
+        self.size -= 1;
+        Some(self.take_node(&id).value)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self._find_node(key).is_some()
+    }
+
+    /// Returns, for every level in use, the last node whose key satisfies 'is_before' (or None if
+    /// there is no such node, i.e. the head). 'is_before' must hold for a prefix of the keys.
+    fn _find_predecessors<F: Fn(&K) -> bool>(&self, is_before: F) -> Vec<Option<Id>> {
+        let mut preds = vec![None; self.levels.max(1)];
+        let mut at: Option<Id> = None;
+
+        for level in (0..self.levels).rev() {
+            while let Some(next) = self.next(&at, level) {
+                if !is_before(&self.node(&next).read().unwrap().key) {
+                    break;
+                }
+                at = Some(next);
+            }
+
+            preds[level] = at;
+        }
+
+        preds
+    }
+
+    fn _find_node(&self, key: &K) -> Option<Id> {
+        let preds = self._find_predecessors(|k| k < key);
+        self.next(&preds[0], 0).filter(|id| self.node(id).read().unwrap().key == *key)
+    }
+
+    /// Returns the node following the given node (or the head, if None) at the given level.
+    fn next(&self, node_id: &Option<Id>, level: usize) -> Option<Id> {
+        match node_id {
+            None => self.head[level],
+            Some(id) => self.node(id).read().unwrap().forward[level]
+        }
+    }
+
+    fn set_next(&mut self, node_id: &Option<Id>, level: usize, next: Option<Id>) {
+        match node_id {
+            None => self.head[level] = next,
+            Some(id) => self.node(id).write().unwrap().forward[level] = next
+        }
+    }
+
+    /// Returns the number of levels of a new node, which takes part in every level above the
+    /// lowest with a probability of 1/2.
+    fn next_level(&mut self) -> usize {
+        // Each bit of an xorshift output is a fair coin flip.
+        (1 + self.rng.next_u64().trailing_ones() as usize).min(MAX_LEVEL)
+    }
+
+    fn node(&self, node_id: &Id) -> SharedRef<SkipNode<K, V>> {
+        self.arena.get_node(node_id).expect("could not find node")
+    }
+
+    /// Removes the node from the arena, handing back ownership of it.
+    fn take_node(&mut self, node_id: &Id) -> SkipNode<K, V> {
+        let node_ref = self.node(node_id);
+        self.arena.delete_node(node_id).expect("could not delete node");
+
+        match Arc::try_unwrap(node_ref) {
+            Ok(node) => node.into_inner().unwrap(),
+            Err(_) => panic!("node is still referenced")
+        }
+    }
+}
+
+impl<K: Ord + Clone + Send + Sync, V: Clone + Send + Sync> SkipList<K, V> {
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let id = self._find_node(key)?;
+        let value = self.node(&id).read().unwrap().value.clone();
+        Some(value)
+    }
+
+    /// Returns the smallest key along with its value.
+    pub fn first(&self) -> Option<(K, V)> {
+        self.head[0].map(|id| self.entry(&id))
+    }
+
+    /// Returns the largest key along with its value.
+    pub fn last(&self) -> Option<(K, V)> {
+        let preds = self._find_predecessors(|_| true);
+        preds[0].map(|id| self.entry(&id))
+    }
+
+    /// Returns all keys along with their values, in ascending order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (K, V)> {
+        self.range(..)
+    }
+
+    /// Returns all keys within the given range along with their values, in ascending order.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> impl DoubleEndedIterator<Item = (K, V)> {
+        let mut result = vec![];
+
+        // --
+        // Skip ahead to the first key of the range, then walk the lowest level until its end.
+        let preds = self._find_predecessors(|k| !after_start(k, &range));
+        let mut node_id = self.next(&preds[0], 0);
+
+        while let Some(id) = node_id {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+
+            if !before_end(&node.key, &range) {
+                break;
+            }
+
+            result.push((node.key.clone(), node.value.clone()));
+            node_id = node.forward[0];
+        }
+
+        result.into_iter()
+    }
+
+    fn entry(&self, node_id: &Id) -> (K, V) {
+        let node_ref = self.node(node_id);
+        let node = node_ref.read().unwrap();
+        (node.key.clone(), node.value.clone())
+    }
+}
+
+impl<K: Ord + Debug + Send + Sync, V: Debug + Send + Sync> ToDot for SkipList<K, V> {
+    fn to_dot(&self) -> String {
+        let mut dot = DotWriter::new("SkipList");
+        let mut node_id = self.head[0];
+
+        while let Some(id) = node_id {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+
+            dot.node(&id, &format!("{:?}: {:?}", node.key, node.value), "shape=box");
+
+            // Every link is labelled with its level.
+            for (level, next) in node.forward.iter().enumerate() {
+                if let Some(next_id) = next {
+                    dot.edge(&id, next_id, &level.to_string(), "");
+                }
+            }
+
+            node_id = node.forward[0];
+        }
+
+        dot.finish()
+    }
+}
@@ -0,0 +1,344 @@
+extern crate nalgebra as na;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+use crate::arena::{GenerationalArena, GenerationalId};
+use crate::arena::prelude::{HasId, IsMemoryArena, SharedRef};
+use crate::spatial::quadtree::prelude::*;
+
+type Id = GenerationalId;
+
+/// The number of shapes a leaf holds before the builder splits it.
+const LEAF_SIZE: usize = 4;
+
+/// This is the trait bound for the shapes stored in a Bvh, which only needs to know the BBox
+/// bounding each of them.
+pub trait HasBounds<S: IsScalar = f32> {
+    fn bounds(&self) -> BBox2D<S>;
+}
+
+impl<S: IsScalar> HasBounds<S> for BBox2D<S> {
+    fn bounds(&self) -> BBox2D<S> {
+        *self
+    }
+}
+
+/// This controls where the nodes of a Bvh get split while it is built.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum SplitStrategy {
+    /// At the median of the centers of the shapes, along the axis in which they are spread the
+    /// most. This is quick to build and keeps the tree balanced.
+    Median,
+
+    /// Wherever the expected cost of a query is lowest, estimated by the surface area (in 2D, the
+    /// perimeter) of both halves weighted by the number of shapes in them. This is slower to build,
+    /// but gives tighter nodes when the shapes vary in size or are unevenly spread.
+    #[default]
+    SurfaceAreaHeuristic
+}
+
+/// Leaves hold the indices of their shapes, while internal nodes hold exactly 2 other nodes.
+#[derive(Clone, Debug)]
+enum Content {
+    Leaf(Vec<usize>),
+    Internal([Id; 2])
+}
+
+/// A node of the tree, whose bbox covers every shape beneath it.
+#[derive(Clone, Debug)]
+struct BvhNode<S: IsScalar> {
+    pub id: Id,
+
+    pub bbox: BBox2D<S>,
+
+    pub content: Content
+}
+
+/// A bounding volume hierarchy (BVH) is a binary tree used to find shapes of any kind which
+/// intersect a region or a ray. Unlike the spatial trees, it partitions the shapes rather than the
+/// space: every node covers the bboxes of the shapes beneath it, and nodes may overlap.
+///
+/// The tree is built in bulk. Shapes may move afterwards, in which case 'refit' grows and shrinks
+/// the nodes to cover them again while keeping the structure of the tree.
+pub struct Bvh<P: HasBounds<S>, S: IsScalar = f32> {
+    arena: GenerationalArena<BvhNode<S>>,
+    root: Option<Id>,
+
+    shapes: Vec<P>,
+
+    // The bbox of every shape as of the last build or refit.
+    bounds: Vec<BBox2D<S>>
+}
+
+impl<P: HasBounds<S>, S: IsScalar> Bvh<P, S> {
+
+    /// Builds a tree over the given shapes, splitting its nodes by the surface area heuristic.
+    pub fn new(shapes: Vec<P>) -> Self {
+        Self::with_strategy(shapes, SplitStrategy::default())
+    }
+
+    /// Builds a tree over the given shapes, splitting its nodes by the given strategy.
+    pub fn with_strategy(shapes: Vec<P>, strategy: SplitStrategy) -> Self {
+        let bounds: Vec<BBox2D<S>> = shapes.iter().map(|shape| shape.bounds()).collect();
+
+        let mut tree = Self {
+            arena: GenerationalArena::new(),
+            root: None,
+            shapes,
+            bounds
+        };
+
+        let mut items: Vec<(usize, BBox2D<S>)> = tree.bounds.iter().copied().enumerate().collect();
+        if !items.is_empty() {
+            tree.root = Some(tree._build(&mut items, strategy));
+        }
+
+        tree
+    }
+
+    /// Returns the number of shapes contained in this tree.
+    pub fn len(&self) -> usize {
+        self.shapes.len()
+    }
+
+    /// Returns true if this tree contains no shapes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the shape at the given index, i.e. its position in the Vec the tree was built from.
+    pub fn get(&self, idx: usize) -> Option<&P> {
+        self.shapes.get(idx)
+    }
+
+    /// Returns the shape at the given index for modification. Queries keep using its old bbox
+    /// until 'refit' is called.
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut P> {
+        self.shapes.get_mut(idx)
+    }
+
+    /// Returns all shapes in the order the tree was built from.
+    pub fn shapes(&self) -> &[P] {
+        &self.shapes
+    }
+
+    /// Updates the nodes to cover the current bboxes of the shapes, after they were moved through
+    /// 'get_mut'. This is much cheaper than rebuilding the tree, though the nodes grow looser as
+    /// the shapes drift away from where the tree was built.
+    pub fn refit(&mut self) {
+        for (bbox, shape) in self.bounds.iter_mut().zip(&self.shapes) {
+            *bbox = shape.bounds();
+        }
+
+        if let Some(root) = &self.root {
+            self._refit(root);
+        }
+    }
+
+    /// Calls 'f' with the index of every shape whose bbox intersects the given BBox, along with
+    /// the shape.
+    pub fn intersect_bbox_with<F: FnMut(usize, &P)>(&self, bbox: &BBox2D<S>, mut f: F) {
+        if let Some(root) = &self.root {
+            self._intersect_bbox(bbox, root, &mut f);
+        }
+    }
+
+    /// Returns every shape whose bbox intersects the given BBox, along with its index.
+    pub fn intersect_bbox(&self, bbox: &BBox2D<S>) -> Vec<(usize, &P)> {
+        let mut result = vec![];
+        self.intersect_bbox_with(bbox, |idx, _| result.push(idx));
+        result.into_iter().map(|idx| (idx, &self.shapes[idx])).collect()
+    }
+
+    /// Returns every shape whose bbox is hit by the ray 'origin + t * dir' (for 't >= 0') along
+    /// with its index and the 't' at which the ray enters the bbox, sorted from nearest to
+    /// furthest. Only the bboxes are tested, so exact hits against the shapes are up to the caller.
+    pub fn intersect_ray(&self, origin: &Vec2<S>, dir: &Vec2<S>) -> Vec<(usize, &P, S)> {
+        let mut result = vec![];
+        if let Some(root) = &self.root {
+            self._intersect_ray(origin, dir, root, &mut result);
+        }
+
+        result.sort_by(|a, b| cmp_scalar(&a.1, &b.1).then(a.0.cmp(&b.0)));
+        result.into_iter().map(|(idx, t)| (idx, &self.shapes[idx], t)).collect()
+    }
+
+    /// Returns the depth of the deepest node in this tree, where the root is at depth 0.
+    pub fn depth(&self) -> usize {
+        let mut max_depth = 0;
+        let mut stack: Vec<(Id, usize)> = self.root.iter().map(|id| (*id, 0)).collect();
+
+        while let Some((id, depth)) = stack.pop() {
+            max_depth = max_depth.max(depth);
+
+            if let Content::Internal(children) = &self.node(&id).read().unwrap().content {
+                stack.extend(children.iter().map(|child| (*child, depth + 1)));
+            }
+        }
+
+        max_depth
+    }
+
+    /// Builds the subtree holding the given shapes (as pairs of index and bbox), returning its
+    /// root. The shapes get reordered along the way.
+    fn _build(&mut self, items: &mut [(usize, BBox2D<S>)], strategy: SplitStrategy) -> Id {
+        let id = self.arena.get_new_id();
+        let bbox = cover(items.iter().map(|(_, bbox)| *bbox));
+
+        let content = if items.len() <= LEAF_SIZE {
+            Content::Leaf(items.iter().map(|(idx, _)| *idx).collect())
+        } else {
+            let mid = match strategy {
+                SplitStrategy::Median => median_split(items),
+                SplitStrategy::SurfaceAreaHeuristic => sah_split(items)
+            };
+
+            let (left, right) = items.split_at_mut(mid);
+            Content::Internal([self._build(left, strategy), self._build(right, strategy)])
+        };
+
+        self.arena.add_node(BvhNode { id, bbox, content }).expect("could not add node!");
+        id
+    }
+
+    /// Recomputes the bboxes of the subtree rooted at the given node, returning its new bbox.
+    fn _refit(&self, node_id: &Id) -> BBox2D<S> {
+        let node_ref = self.node(node_id);
+        let mut node = node_ref.write().unwrap();
+
+        let bbox = match &node.content {
+            Content::Leaf(items) => cover(items.iter().map(|idx| self.bounds[*idx])),
+            Content::Internal([left, right]) => self._refit(left).union(&self._refit(right))
+        };
+
+        node.bbox = bbox;
+        bbox
+    }
+
+    fn _intersect_bbox<F: FnMut(usize, &P)>(&self, bbox: &BBox2D<S>, node_id: &Id, f: &mut F) {
+        let node_ref = self.node(node_id);
+        let node = node_ref.read().unwrap();
+
+        if !node.bbox.intersects(bbox) {
+            return;
+        }
+
+        match &node.content {
+            Content::Leaf(items) => {
+                for idx in items {
+                    if self.bounds[*idx].intersects(bbox) {
+                        f(*idx, &self.shapes[*idx]);
+                    }
+                }
+            }
+            Content::Internal(children) => {
+                for child in children {
+                    self._intersect_bbox(bbox, child, f);
+                }
+            }
+        }
+    }
+
+    fn _intersect_ray(&self, origin: &Vec2<S>, dir: &Vec2<S>, node_id: &Id, out: &mut Vec<(usize, S)>) {
+        let node_ref = self.node(node_id);
+        let node = node_ref.read().unwrap();
+
+        if node.bbox.ray_entry(origin, dir).is_none() {
+            return;
+        }
+
+        match &node.content {
+            Content::Leaf(items) => {
+                out.extend(items.iter().filter_map(|idx| Some((*idx, self.bounds[*idx].ray_entry(origin, dir)?))));
+            }
+            Content::Internal(children) => {
+                for child in children {
+                    self._intersect_ray(origin, dir, child, out);
+                }
+            }
+        }
+    }
+
+    fn node(&self, node_id: &Id) -> SharedRef<BvhNode<S>> {
+        self.arena.get_node(node_id).expect("could not find node")
+    }
+}
+
+/// Reorders the items around the median of their centers along the axis in which the centers are
+/// spread the most, returning the index of the median.
+fn median_split<S: IsScalar>(items: &mut [(usize, BBox2D<S>)]) -> usize {
+    let centers = cover(items.iter().map(|(_, bbox)| BBox2D { min: bbox.mid(), max: bbox.mid() }));
+    let extent = centers.max - centers.min;
+    let axis = if extent.x >= extent.y { 0 } else { 1 };
+
+    let mid = items.len() / 2;
+    items.select_nth_unstable_by(mid, |a, b| cmp_scalar(&a.1.mid()[axis], &b.1.mid()[axis]));
+    mid
+}
+
+/// Reorders the items along the axis with the cheapest split according to the surface area
+/// heuristic, returning the index to split them at. Falls back to the median if no split is
+/// cheaper than keeping the items together, e.g. when all of them overlap.
+fn sah_split<S: IsScalar>(items: &mut [(usize, BBox2D<S>)]) -> usize {
+    let n = items.len();
+    let mut best: Option<(S, usize, usize)> = None;
+
+    for axis in 0..2 {
+        items.sort_by(|a, b| cmp_scalar(&a.1.mid()[axis], &b.1.mid()[axis]));
+
+        // --
+        // Sweep from both ends, so that the cost of every split is known in a single pass.
+        let mut suffix = vec![items[n - 1].1; n];
+        for i in (0..n - 1).rev() {
+            suffix[i] = suffix[i + 1].union(&items[i].1);
+        }
+
+        let mut prefix = items[0].1;
+        for i in 1..n {
+            let cost = half_perimeter(&prefix) * count::<S>(i) + half_perimeter(&suffix[i]) * count::<S>(n - i);
+            if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                best = Some((cost, axis, i));
+            }
+            prefix = prefix.union(&items[i].1);
+        }
+    }
+
+    let (cost, axis, mid) = best.expect("there are at least 2 items");
+    let parent = cover(items.iter().map(|(_, bbox)| *bbox));
+
+    if cost >= half_perimeter(&parent) * count::<S>(n) {
+        return median_split(items);
+    }
+
+    items.sort_by(|a, b| cmp_scalar(&a.1.mid()[axis], &b.1.mid()[axis]));
+    mid
+}
+
+/// Returns the smallest BBox covering all of the given BBoxes, of which there is at least 1.
+fn cover<S: IsScalar>(bboxes: impl Iterator<Item = BBox2D<S>>) -> BBox2D<S> {
+    bboxes.reduce(|a, b| a.union(&b)).expect("nodes are never empty")
+}
+
+/// Returns half of the perimeter of the BBox, the 2D counterpart of the surface area of a box.
+fn half_perimeter<S: IsScalar>(bbox: &BBox2D<S>) -> S {
+    let extent = bbox.max - bbox.min;
+    extent.x + extent.y
+}
+
+fn count<S: IsScalar>(n: usize) -> S {
+    na::convert::<f64, S>(n as f64)
+}
+
+/// Compares 2 coordinates, which are never NaN.
+fn cmp_scalar<S: IsScalar>(a: &S, b: &S) -> Ordering {
+    a.partial_cmp(b).unwrap_or(Ordering::Equal)
+}
+
+impl<S: IsScalar> HasId for BvhNode<S> {
+    type Id = Id;
+    fn get_id(&self) -> Self::Id {
+        self.id
+    }
+}
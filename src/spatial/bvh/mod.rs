@@ -0,0 +1,131 @@
+pub mod bvh_impl;
+
+#[allow(non_snake_case)]
+#[cfg(test)]
+mod tests {
+    use crate::spatial::bvh::bvh_impl::*;
+    use crate::spatial::quadtree::prelude::*;
+
+    fn bbox(x: f32, y: f32, w: f32, h: f32) -> BBox2D {
+        BBox2D {
+            min: Vec2::from([x, y]),
+            max: Vec2::from([x + w, y + h])
+        }
+    }
+
+    /// A circle, which only tells the tree about its bbox.
+    #[derive(Debug, Clone, PartialEq)]
+    struct Circle {
+        center: Vec2,
+        radius: f32
+    }
+
+    impl HasBounds for Circle {
+        fn bounds(&self) -> BBox2D {
+            let r = Vec2::from([self.radius, self.radius]);
+            BBox2D { min: self.center - r, max: self.center + r }
+        }
+    }
+
+    #[test]
+    fn test_Bvh() {
+        let tree = Bvh::<BBox2D>::new(vec![]);
+        assert!(tree.is_empty());
+        assert!(tree.intersect_bbox(&bbox(0.0, 0.0, 10.0, 10.0)).is_empty());
+        assert!(tree.intersect_ray(&Vec2::zeros(), &Vec2::from([1.0, 0.0])).is_empty());
+
+        // A 10x10 grid of unit squares spaced 2 apart.
+        let boxes: Vec<BBox2D> = (0..100)
+            .map(|i| bbox(2.0 * (i % 10) as f32, 2.0 * (i / 10) as f32, 1.0, 1.0))
+            .collect();
+
+        for strategy in [SplitStrategy::Median, SplitStrategy::SurfaceAreaHeuristic] {
+            let tree = Bvh::with_strategy(boxes.clone(), strategy);
+            assert_eq!(tree.len(), 100);
+            assert!(tree.depth() <= 6);
+
+            let mut items: Vec<usize> = tree.intersect_bbox(&bbox(2.5, 2.5, 2.0, 2.0))
+                .into_iter()
+                .map(|(i, _)| i)
+                .collect();
+            items.sort();
+            assert_eq!(items, vec![11, 12, 21, 22]);
+
+            // A ray along the 3rd row hits its squares from left to right.
+            let hits = tree.intersect_ray(&Vec2::from([-5.0, 4.5]), &Vec2::from([1.0, 0.0]));
+            assert_eq!(hits.iter().map(|(i, _, _)| *i).collect::<Vec<_>>(), (20..30).collect::<Vec<_>>());
+            assert_eq!(hits[0].2, 5.0);
+            assert_eq!(*hits[0].1, boxes[20]);
+
+            // Rays starting inside of a square hit it at once, and rays pointing away miss.
+            let hits = tree.intersect_ray(&Vec2::from([18.5, 18.5]), &Vec2::from([1.0, 1.0]));
+            assert_eq!(hits.iter().map(|(i, _, t)| (*i, *t)).collect::<Vec<_>>(), vec![(99, 0.0)]);
+            assert!(tree.intersect_ray(&Vec2::from([-1.0, -1.0]), &Vec2::from([-1.0, 0.5])).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_Bvh_against_brute_force() {
+        let mut state: u64 = 5;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            ((state >> 16) % 1000) as f32 / 10.0
+        };
+
+        // Shapes of very different sizes, to give the heuristic something to work with.
+        let circles: Vec<Circle> = (0..500)
+            .map(|i| Circle { center: Vec2::from([next(), next()]), radius: if i % 10 == 0 { next() / 4.0 } else { next() / 100.0 } })
+            .collect();
+
+        let queries: Vec<BBox2D> = (0..50).map(|_| bbox(next(), next(), next() / 5.0, next() / 5.0)).collect();
+        let rays: Vec<(Vec2, Vec2)> = (0..50)
+            .map(|_| (Vec2::from([next(), next()]), Vec2::from([next() - 50.0, next() - 50.0])))
+            .collect();
+
+        let check = |tree: &Bvh<Circle>| {
+            for query in &queries {
+                let mut items: Vec<usize> = tree.intersect_bbox(query).into_iter().map(|(i, _)| i).collect();
+                items.sort();
+
+                let expected: Vec<usize> = (0..tree.len())
+                    .filter(|i| tree.get(*i).unwrap().bounds().intersects(query))
+                    .collect();
+                assert_eq!(items, expected);
+            }
+
+            for (origin, dir) in &rays {
+                let hits: Vec<(usize, f32)> = tree.intersect_ray(origin, dir).into_iter().map(|(i, _, t)| (i, t)).collect();
+
+                let mut expected: Vec<(usize, f32)> = (0..tree.len())
+                    .filter_map(|i| Some((i, tree.get(i).unwrap().bounds().ray_entry(origin, dir)?)))
+                    .collect();
+                expected.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+                assert_eq!(hits, expected);
+            }
+        };
+
+        for strategy in [SplitStrategy::Median, SplitStrategy::SurfaceAreaHeuristic] {
+            let mut tree = Bvh::with_strategy(circles.clone(), strategy);
+            check(&tree);
+
+            // --
+            // Moved shapes are only found where they used to be until the tree is refit.
+            let old = tree.get(0).unwrap().bounds();
+            for i in (0..tree.len()).step_by(3) {
+                let circle = tree.get_mut(i).unwrap();
+                circle.center += Vec2::from([25.0, -10.0]);
+            }
+
+            assert!(tree.intersect_bbox(&old).iter().any(|(i, _)| *i == 0));
+
+            tree.refit();
+            check(&tree);
+            assert_eq!(tree.shapes().len(), 500);
+        }
+
+        // Shapes which all overlap can't be split by area, but the tree stays balanced.
+        let tree = Bvh::<BBox2D>::new(vec![bbox(0.0, 0.0, 1.0, 1.0); 1000]);
+        assert!(tree.depth() <= 10);
+        assert_eq!(tree.intersect_bbox(&bbox(0.5, 0.5, 1.0, 1.0)).len(), 1000);
+    }
+}
@@ -0,0 +1,230 @@
+extern crate nalgebra as na;
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::collections::HashMap;
+use crate::spatial::quadtree::prelude::*;
+
+pub use crate::spatial::quadtree::point_quadtree::{IsPayload, Node};
+
+/// The coordinates of a cell in the grid, i.e. the point's coordinates divided by the cell size.
+type Cell = (i64, i64);
+
+/// A Spatial Hash Grid divides 2D space into square cells of a fixed size, and stores each point in
+/// a bucket keyed by the cell containing it. Only the cells holding points take up memory, so the
+/// grid is unbounded.
+///
+/// Unlike a tree, inserting or removing a point only ever touches a single bucket, which makes the
+/// grid a good fit for many uniformly distributed points that move around a lot. Queries visit
+/// every cell overlapping the queried region, so the cell size should be close to the size of the
+/// typical query.
+pub struct SpatialHashGrid<P, S: IsScalar = f32> {
+    cells: HashMap<Cell, Vec<Node<P, S>>>,
+    cell_size: S,
+    size: usize
+}
+
+impl<P, S: IsScalar> SpatialHashGrid<P, S> {
+
+    /// Returns a new, empty grid whose cells have the given side length.
+    pub fn new(cell_size: S) -> Self {
+        assert!(cell_size > S::zero(), "cell size must be positive");
+
+        Self {
+            cells: HashMap::new(),
+            cell_size,
+            size: 0
+        }
+    }
+
+    /// Returns the side length of the cells.
+    pub fn cell_size(&self) -> S {
+        self.cell_size
+    }
+
+    /// Returns the number of points contained in this grid.
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Returns true if this grid contains no points.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Attempts to insert the point into the grid, returning false if the point already exists.
+    pub fn insert(&mut self, point: &Vec2<S>, payload: P) -> bool {
+        let bucket = self.cells.entry(self.cell_of(point)).or_default();
+
+        if bucket.iter().any(|node| node.0 == *point) {
+            return false;
+        }
+
+        bucket.push((*point, payload));
+        self.size += 1;
+        true
+    }
+
+    /// Removes the point from the grid, returning its payload if it existed.
+    pub fn remove(&mut self, p: &Vec2<S>) -> Option<P> {
+        let cell = self.cell_of(p);
+        let bucket = self.cells.get_mut(&cell)?;
+        let idx = bucket.iter().position(|node| node.0 == *p)?;
+        let (_, payload) = bucket.swap_remove(idx);
+
+        // Empty buckets are dropped, so moving points don't leave a trail of cells behind them.
+        if bucket.is_empty() {
+            self.cells.remove(&cell);
+        }
+
+        self.size -= 1;
+        Some(payload)
+    }
+
+    /// Calls 'f' with the point and payload for the given point, if it exists.
+    pub fn find_with<R, F: FnOnce(&Vec2<S>, &P) -> R>(&self, p: &Vec2<S>, f: F) -> Option<R> {
+        self.cells.get(&self.cell_of(p))?
+            .iter()
+            .find(|node| node.0 == *p)
+            .map(|(p, payload)| f(p, payload))
+    }
+
+    /// Calls 'f' with every point (and its payload) in the grid within the given BBox.
+    pub fn query_bbox_with<F: FnMut(&Vec2<S>, &P)>(&self, bbox: &BBox2D<S>, mut f: F) {
+        self.for_each_cell(bbox, |bucket| {
+            for (p, payload) in bucket.iter().filter(|node| bbox.contains(&node.0)) {
+                f(p, payload);
+            }
+        });
+    }
+
+    /// Calls 'f' with every point (and its payload) in the grid within 'radius' of 'center'.
+    pub fn query_radius_with<F: FnMut(&Vec2<S>, &P)>(&self, center: &Vec2<S>, radius: S, mut f: F) {
+        let extent = Vec2::new(radius, radius);
+        let bbox = BBox2D { min: center - extent, max: center + extent };
+
+        self.for_each_cell(&bbox, |bucket| {
+            for (p, payload) in bucket.iter().filter(|node| (node.0 - center).norm() <= radius) {
+                f(p, payload);
+            }
+        });
+    }
+
+    /// Calls 'f' with the point in the grid closest to the given point (and its payload).
+    pub fn nearest_with<R, F: FnOnce(&Vec2<S>, &P) -> R>(&self, p: &Vec2<S>, f: F) -> Option<R> {
+        let (cx, cy) = self.cell_of(p);
+        let mut best: Option<(&Node<P, S>, S)> = None;
+
+        // Search the rings of cells around the point's cell, from the inside out. Every point beyond
+        // ring 'r' is at least 'r' cells away, so the search can stop once the best point found so
+        // far is closer than that. Once the rings hold more cells than are occupied it's cheaper to
+        // look at every point instead.
+        let mut searched = 0;
+        let mut r: i64 = 0;
+        loop {
+            let cells = if r == 0 { 1 } else { 8 * r as usize };
+            if searched + cells > self.cells.len() {
+                self.cells.values().for_each(|bucket| Self::closest_in(bucket, p, &mut best));
+                break;
+            }
+            searched += cells;
+
+            for x in (cx - r)..=(cx + r) {
+                for y in (cy - r)..=(cy + r) {
+                    if x == cx - r || x == cx + r || y == cy - r || y == cy + r {
+                        if let Some(bucket) = self.cells.get(&(x, y)) {
+                            Self::closest_in(bucket, p, &mut best);
+                        }
+                    }
+                }
+            }
+
+            let reach: S = na::convert::<f64, S>(r as f64) * self.cell_size;
+            if matches!(&best, Some((_, best_dist)) if *best_dist <= reach) {
+                break;
+            }
+            r += 1;
+        }
+
+        best.map(|((p, payload), _)| f(p, payload))
+    }
+
+    /// Replaces 'best' with the point in the bucket closest to 'p' if it is any closer.
+    fn closest_in<'a>(bucket: &'a [Node<P, S>], p: &Vec2<S>, best: &mut Option<(&'a Node<P, S>, S)>) {
+        for node in bucket {
+            let dist = (node.0 - p).norm();
+            if !matches!(best, Some((_, best_dist)) if dist >= *best_dist) {
+                *best = Some((node, dist));
+            }
+        }
+    }
+
+    /// Returns the cell containing the given point.
+    fn cell_of(&self, p: &Vec2<S>) -> Cell {
+        (self.coordinate_of(p.x), self.coordinate_of(p.y))
+    }
+
+    fn coordinate_of(&self, x: S) -> i64 {
+        let cell: f64 = na::try_convert((x / self.cell_size).floor()).expect("coordinate is not finite");
+        cell as i64
+    }
+
+    /// Calls 'f' with the bucket of every non-empty cell overlapping the given BBox (inclusive of
+    /// its max corner).
+    fn for_each_cell<F: FnMut(&Vec<Node<P, S>>)>(&self, bbox: &BBox2D<S>, mut f: F) {
+        let (min_x, min_y) = self.cell_of(&bbox.min);
+        let (max_x, max_y) = self.cell_of(&bbox.max);
+
+        if max_x < min_x || max_y < min_y {
+            return;
+        }
+
+        // Looking up every cell of a BBox much larger than the occupied part of the grid would
+        // mostly miss, so just go through the occupied cells instead.
+        let overlapped = (max_x - min_x + 1) as u128 * (max_y - min_y + 1) as u128;
+        if overlapped > self.cells.len() as u128 {
+            for ((x, y), bucket) in &self.cells {
+                if (min_x..=max_x).contains(x) && (min_y..=max_y).contains(y) {
+                    f(bucket);
+                }
+            }
+            return;
+        }
+
+        for x in min_x..=max_x {
+            for y in min_y..=max_y {
+                if let Some(bucket) = self.cells.get(&(x, y)) {
+                    f(bucket);
+                }
+            }
+        }
+    }
+}
+
+impl<P: IsPayload, S: IsScalar> SpatialHashGrid<P, S> {
+
+    /// Returns the point and payload for the given point, if it exists.
+    pub fn find(&self, p: &Vec2<S>) -> Option<Node<P, S>> {
+        self.find_with(p, |p, payload| (*p, payload.clone()))
+    }
+
+    /// Returns the point in the grid closest to the given point.
+    pub fn nearest(&self, p: &Vec2<S>) -> Option<Node<P, S>> {
+        self.nearest_with(p, |p, payload| (*p, payload.clone()))
+    }
+
+    /// Returns all points in the grid within the given BBox.
+    pub fn query_bbox(&self, bbox: &BBox2D<S>) -> Vec<Node<P, S>> {
+        let mut result = vec![];
+        self.query_bbox_with(bbox, |p, payload| result.push((*p, payload.clone())));
+        result
+    }
+
+    /// Returns all points in the grid within 'radius' of 'center'.
+    pub fn query_radius(&self, center: &Vec2<S>, radius: S) -> Vec<Node<P, S>> {
+        let mut result = vec![];
+        self.query_radius_with(center, radius, |p, payload| result.push((*p, payload.clone())));
+        result
+    }
+}
@@ -0,0 +1,87 @@
+pub mod hash_grid;
+
+#[allow(non_snake_case)]
+#[cfg(test)]
+mod tests {
+    use crate::spatial::grid::hash_grid::*;
+    use crate::spatial::quadtree::prelude::*;
+
+    #[test]
+    fn test_SpatialHashGrid() {
+        let mut grid = SpatialHashGrid::<usize>::new(10.0);
+        assert!(grid.is_empty());
+        assert!(grid.query_bbox(&BBox2D { min: Vec2::from([-1e9, -1e9]), max: Vec2::from([1e9, 1e9]) }).is_empty());
+
+        assert!(grid.insert(&Vec2::from([1.0, 1.0]), 0));
+        assert!(grid.insert(&Vec2::from([-1.0, -1.0]), 1));
+        assert!(grid.insert(&Vec2::from([15.0, 25.0]), 2));
+        assert!(grid.insert(&Vec2::from([1e6, -1e6]), 3));
+        assert!(!grid.insert(&Vec2::from([1.0, 1.0]), 4));
+        assert_eq!(grid.len(), 4);
+
+        assert_eq!(grid.find(&Vec2::from([-1.0, -1.0])), Some((Vec2::from([-1.0, -1.0]), 1)));
+        assert_eq!(grid.find(&Vec2::from([-1.0, 1.0])), None);
+
+        let bbox = BBox2D { min: Vec2::from([-5.0, -5.0]), max: Vec2::from([20.0, 30.0]) };
+        let mut items: Vec<usize> = grid.query_bbox(&bbox).into_iter().map(|(_, i)| i).collect();
+        items.sort();
+        assert_eq!(items, vec![0, 1, 2]);
+
+        let mut items: Vec<usize> = grid.query_radius(&Vec2::from([0.0, 0.0]), 1.5).into_iter().map(|(_, i)| i).collect();
+        items.sort();
+        assert_eq!(items, vec![0, 1]);
+
+        assert_eq!(grid.remove(&Vec2::from([15.0, 25.0])), Some(2));
+        assert_eq!(grid.remove(&Vec2::from([15.0, 25.0])), None);
+        assert_eq!(grid.query_bbox(&bbox).len(), 2);
+        assert_eq!(grid.query_radius(&Vec2::from([1e6, -1e6]), 0.0), vec![(Vec2::from([1e6, -1e6]), 3)]);
+        assert_eq!(grid.len(), 3);
+    }
+
+    #[test]
+    fn test_SpatialHashGrid_against_brute_force() {
+        let mut state: u64 = 7;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            ((state >> 16) % 2000) as f64 / 10.0 - 100.0
+        };
+
+        let mut grid = SpatialHashGrid::<usize, f64>::new(7.5);
+        let mut points: Vec<(Vec2<f64>, usize)> = vec![];
+
+        for i in 0..500 {
+            let p = Vec2::new(next(), next());
+            let expected = !points.iter().any(|(q, _)| *q == p);
+            assert_eq!(grid.insert(&p, i), expected);
+            if expected {
+                points.push((p, i));
+            }
+        }
+
+        // Remove every third point.
+        let removed: Vec<_> = points.iter().step_by(3).cloned().collect();
+        for (p, i) in &removed {
+            assert_eq!(grid.remove(p), Some(*i));
+        }
+        points.retain(|node| !removed.contains(node));
+        assert_eq!(grid.len(), points.len());
+
+        for _ in 0..50 {
+            let (a, b) = (Vec2::new(next(), next()), Vec2::new(next(), next()));
+            let bbox = BBox2D { min: a.inf(&b), max: a.sup(&b) };
+
+            let mut expected: Vec<usize> = points.iter().filter(|(p, _)| bbox.contains(p)).map(|(_, i)| *i).collect();
+            let mut actual: Vec<usize> = grid.query_bbox(&bbox).into_iter().map(|(_, i)| i).collect();
+            expected.sort();
+            actual.sort();
+            assert_eq!(actual, expected);
+
+            let radius = next().abs() / 4.0;
+            let mut expected: Vec<usize> = points.iter().filter(|(p, _)| (p - a).norm() <= radius).map(|(_, i)| *i).collect();
+            let mut actual: Vec<usize> = grid.query_radius(&a, radius).into_iter().map(|(_, i)| i).collect();
+            expected.sort();
+            actual.sort();
+            assert_eq!(actual, expected);
+        }
+    }
+}
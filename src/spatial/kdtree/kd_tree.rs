@@ -0,0 +1,219 @@
+use alloc::collections::BinaryHeap;
+use alloc::{format, vec};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+
+use crate::arena::{GenerationalArena, GenerationalId};
+use crate::arena::prelude::{HasId, IsMemoryArena};
+use crate::spatial::kdtree::prelude::*;
+use crate::spatial::search::Candidate;
+use crate::visualize::{DotWriter, ToDot};
+
+pub use crate::spatial::quadtree::point_quadtree::IsPayload;
+
+type Id = GenerationalId;
+
+/// This represents the type of payload that is stored in each node of the tree.
+pub type Node<const D: usize, P> = (VecN<D>, P);
+
+/// A node of the tree stores a single point, and splits the space below it into the points whose
+/// coordinate along 'axis' is at most the point's (left) and at least the point's (right).
+#[derive(Clone, Debug)]
+struct KdNode<const D: usize, P: IsPayload> {
+    pub id: Id,
+
+    pub point: Node<D, P>,
+
+    pub axis: usize,
+
+    pub left: Option<Id>,
+    pub right: Option<Id>
+}
+
+/// A kd-tree is a binary tree used to perform efficient queries of points in D-dimensional space.
+/// The tree is built in bulk by recursively splitting the points at the median along each axis in
+/// turn, which keeps it balanced regardless of how the points are distributed.
+pub struct KdTree<const D: usize, P: IsPayload> {
+    arena: GenerationalArena<KdNode<D, P>>,
+    root: Option<Id>,
+    size: usize
+}
+
+impl<const D: usize, P: IsPayload> KdTree<D, P> {
+
+    /// Builds a balanced tree from the given points.
+    pub fn from_points(points: &[Node<D, P>]) -> Self {
+        let mut tree = Self {
+            arena: GenerationalArena::new(),
+            root: None,
+            size: points.len()
+        };
+
+        let mut points = points.to_vec();
+        tree.root = tree._build(&mut points, 0);
+
+        tree
+    }
+
+    /// Returns the number of points contained in this tree.
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Returns true if this tree contains no points.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns all points in the tree within the given BBox.
+    pub fn find_within(&self, bbox: &BBoxN<D>) -> Vec<Node<D, P>> {
+        let mut result = vec![];
+        if let Some(root) = &self.root {
+            self._find_within(bbox, root, &mut result);
+        }
+        result
+    }
+
+    /// Returns the point in the tree closest to the given point.
+    pub fn nearest(&self, p: &VecN<D>) -> Option<Node<D, P>> {
+        self.knn(p, 1).pop().map(|(node, _)| node)
+    }
+
+    /// Returns the 'k' points in the tree closest to the given point along with their distances,
+    /// sorted from closest to furthest.
+    pub fn knn(&self, p: &VecN<D>, k: usize) -> Vec<(Node<D, P>, f32)> {
+        if k == 0 {
+            return vec![];
+        }
+
+        // The furthest of the best 'k' points found so far sits at the top of this heap.
+        let mut best: BinaryHeap<Reverse<Candidate<Node<D, P>>>> = BinaryHeap::with_capacity(k + 1);
+
+        if let Some(root) = &self.root {
+            self._knn(p, k, root, &mut best);
+        }
+
+        best.into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(Candidate { dist, item })| (item, dist))
+            .collect()
+    }
+
+    fn _build(&mut self, points: &mut [Node<D, P>], depth: usize) -> Option<Id> {
+        if points.is_empty() {
+            return None;
+        }
+
+        let axis = depth % D;
+        let mid = points.len() / 2;
+        points.select_nth_unstable_by(mid, |a, b| a.0[axis].total_cmp(&b.0[axis]));
+
+        let (left, rest) = points.split_at_mut(mid);
+        let (median, right) = rest.split_first_mut().unwrap();
+
+        let id = self.arena.get_new_id();
+        let node = KdNode {
+            id,
+            point: median.clone(),
+            axis,
+            left: self._build(left, depth + 1),
+            right: self._build(right, depth + 1)
+        };
+
+        self.arena.add_node(node).expect("could not add node!");
+
+        Some(id)
+    }
+
+    fn _find_within(&self, bbox: &BBoxN<D>, node_id: &Id, out: &mut Vec<Node<D, P>>) {
+        let node_ref = self.arena.get_node(node_id).expect("could not find node");
+        let node = node_ref.read().unwrap();
+
+        if bbox.contains(&node.point.0) {
+            out.push(node.point.clone());
+        }
+
+        let split = node.point.0[node.axis];
+
+        if let Some(left) = &node.left {
+            if bbox.min[node.axis] <= split {
+                self._find_within(bbox, left, out);
+            }
+        }
+
+        if let Some(right) = &node.right {
+            if bbox.max[node.axis] >= split {
+                self._find_within(bbox, right, out);
+            }
+        }
+    }
+
+    fn _knn(
+        &self,
+        p: &VecN<D>,
+        k: usize,
+        node_id: &Id,
+        best: &mut BinaryHeap<Reverse<Candidate<Node<D, P>>>>
+    ) {
+        let node_ref = self.arena.get_node(node_id).expect("could not find node");
+        let node = node_ref.read().unwrap();
+
+        best.push(Reverse(Candidate { dist: (node.point.0 - p).norm(), item: node.point.clone() }));
+        if best.len() > k {
+            best.pop();
+        }
+
+        // --
+        // Search the side of the split containing 'p' first, since it most likely holds the
+        // closest points, and only then the other side if it could still hold a closer point.
+        let diff = p[node.axis] - node.point.0[node.axis];
+        let (near, far) = if diff < 0.0 {
+            (&node.left, &node.right)
+        } else {
+            (&node.right, &node.left)
+        };
+
+        if let Some(near) = near {
+            self._knn(p, k, near, best);
+        }
+
+        if let Some(far) = far {
+            if best.len() < k || diff.abs() <= best.peek().unwrap().0.dist {
+                self._knn(p, k, far, best);
+            }
+        }
+    }
+}
+
+impl<const D: usize, P: IsPayload> HasId for KdNode<D, P> {
+    type Id = Id;
+    fn get_id(&self) -> Self::Id {
+        self.id
+    }
+}
+
+impl<const D: usize, P: IsPayload> ToDot for KdTree<D, P> {
+    fn to_dot(&self) -> String {
+        let mut dot = DotWriter::new("KdTree");
+        let mut stack: Vec<Id> = self.root.into_iter().collect();
+
+        while let Some(id) = stack.pop() {
+            let node_ref = self.arena.get_node(&id).expect("could not find node");
+            let node = node_ref.read().unwrap();
+
+            let coords: Vec<String> = node.point.0.iter().map(|c| c.to_string()).collect();
+            let label = format!("({}): {:?}\naxis {}", coords.join(", "), node.point.1, node.axis);
+            dot.node(&id, &label, "shape=box");
+
+            for (child, name) in [(node.left, "<="), (node.right, ">=")] {
+                if let Some(child_id) = child {
+                    dot.edge(&id, &child_id, name, "");
+                    stack.push(child_id);
+                }
+            }
+        }
+
+        dot.finish()
+    }
+}
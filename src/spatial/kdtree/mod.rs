@@ -0,0 +1,40 @@
+pub mod prelude;
+pub mod kd_tree;
+
+#[allow(non_snake_case)]
+#[cfg(test)]
+mod tests {
+    use crate::spatial::kdtree::prelude::*;
+    use crate::spatial::kdtree::kd_tree::*;
+
+    #[test]
+    fn test_KdTree() {
+        let empty = KdTree::<2, i32>::from_points(&[]);
+        assert!(empty.is_empty());
+        assert!(empty.nearest(&VecN::<2>::zeros()).is_none());
+
+        let points: Vec<(VecN<3>, usize)> = (0..125)
+            .map(|i| (VecN::<3>::new((i % 5) as f32, ((i / 5) % 5) as f32, (i / 25) as f32), i))
+            .collect();
+
+        let tree = KdTree::from_points(&points);
+        assert_eq!(tree.len(), 125);
+
+        assert_eq!(tree.nearest(&VecN::<3>::new(1.1, 2.2, 3.9)).unwrap().1, 111);
+        assert_eq!(tree.nearest(&VecN::<3>::new(-5.0, -5.0, -5.0)).unwrap().1, 0);
+
+        let result = tree.knn(&VecN::<3>::new(2.0, 2.0, 2.0), 7);
+        assert_eq!(result.len(), 7);
+        assert_eq!(result[0].0.1, 62);
+        assert_eq!(result[0].1, 0.0);
+        assert!(result[1..].iter().all(|(_, d)| *d == 1.0));
+
+        let bbox = BBoxN::<3> {
+            min: VecN::<3>::new(1.0, 1.0, 1.0),
+            max: VecN::<3>::new(3.0, 3.0, 2.0)
+        };
+        let mut items: Vec<usize> = tree.find_within(&bbox).into_iter().map(|(_, i)| i).collect();
+        items.sort();
+        assert_eq!(items, vec![31, 32, 36, 37]);
+    }
+}
@@ -0,0 +1,29 @@
+extern crate nalgebra as na;
+
+/// kd-trees exist in D-dimensional space
+pub type VecN<const D: usize> = na::SVector<f32, D>;
+
+/// This is a D-dimensional axis-aligned bounding box (AABB).
+#[derive(Debug, Copy, Clone)]
+pub struct BBoxN<const D: usize> {
+    pub min: VecN<D>,
+    pub max: VecN<D>
+}
+
+impl<const D: usize> BBoxN<D> {
+    /// Returns true if the BBox contains the given point.
+    pub fn contains(&self, p: &VecN<D>) -> bool {
+        self.min <= *p && *p < self.max
+    }
+
+    /// Returns true if the BBox intersects the given BBox.
+    pub fn intersects(&self, other: &BBoxN<D>) -> bool {
+        (0..D).all(|i| self.max[i] >= other.min[i] && other.max[i] >= self.min[i])
+    }
+
+    /// Returns the distance from the BBox to the given point, which is 0 if the point is inside.
+    pub fn distance_to_point(&self, p: &VecN<D>) -> f32 {
+        let d = VecN::<D>::from_fn(|i, _| (self.min[i] - p[i]).max(0.0).max(p[i] - self.max[i]));
+        d.norm()
+    }
+}
@@ -1 +1,137 @@
+pub mod prelude;
 pub mod quadtree;
+pub mod octree;
+pub mod kdtree;
+pub mod rtree;
+pub mod bvh;
+pub mod grid;
+pub mod rangetree;
+pub mod render;
+
+mod search;
+
+#[cfg(test)]
+mod tests {
+    use crate::spatial::grid::hash_grid::*;
+    use crate::spatial::prelude::*;
+    use crate::spatial::quadtree::point_quadtree::*;
+    use crate::spatial::quadtree::prelude::*;
+    use crate::spatial::render::*;
+
+    /// Runs the same workload against any index, checking every query against brute force.
+    fn check_index<I: SpatialIndex<usize>>(mut index: I) {
+        let mut state: u64 = 11;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            ((state >> 16) % 1000) as f32 / 10.0
+        };
+
+        let mut points: Vec<(Vec2, usize)> = vec![];
+        for i in 0..300 {
+            let p = Vec2::new(next(), next());
+            let expected = !points.iter().any(|(q, _)| *q == p);
+            assert_eq!(index.insert(&p, i), expected);
+            if expected {
+                points.push((p, i));
+            }
+        }
+
+        for (p, i) in points.iter().step_by(4) {
+            assert_eq!(index.remove(p), Some(*i));
+        }
+        points = points.into_iter().enumerate().filter(|(j, _)| j % 4 != 0).map(|(_, node)| node).collect();
+        assert_eq!(index.len(), points.len());
+        assert!(!index.is_empty());
+
+        let sorted = |nodes: Vec<Node<usize>>| {
+            let mut items: Vec<usize> = nodes.into_iter().map(|(_, i)| i).collect();
+            items.sort();
+            items
+        };
+
+        for _ in 0..30 {
+            let (a, b) = (Vec2::new(next(), next()), Vec2::new(next(), next()));
+
+            let bbox = BBox2D { min: a.inf(&b), max: a.sup(&b) };
+            let expected = points.iter().filter(|(p, _)| bbox.contains(p)).cloned().collect();
+            assert_eq!(sorted(index.query_bbox(&bbox)), sorted(expected));
+
+            let radius = next() / 5.0;
+            let expected = points.iter().filter(|(p, _)| (p - a).norm() <= radius).cloned().collect();
+            assert_eq!(sorted(index.query_radius(&a, radius)), sorted(expected));
+
+            let best = points.iter().map(|(p, _)| (p - a).norm()).fold(f32::MAX, f32::min);
+            let (p, _) = index.nearest(&a).unwrap();
+            assert_eq!((p - a).norm(), best);
+        }
+    }
+
+    #[test]
+    fn test_spatial_index() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([100.0, 100.0])
+        };
+
+        check_index(PointQuadtree::<usize>::new(&bbox));
+        check_index(PointQuadtree::<usize>::with_config(&bbox, QuadtreeConfig::default()));
+        check_index(SpatialHashGrid::<usize>::new(5.0));
+        check_index(SpatialHashGrid::<usize>::new(0.5));
+        check_index(SpatialHashGrid::<usize>::new(200.0));
+    }
+
+    #[test]
+    fn test_render() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([100.0, 100.0])
+        };
+
+        let mut tree = PointQuadtree::<usize>::with_config(&bbox, QuadtreeConfig { bucket_capacity: 4, max_depth: 8 });
+        for i in 0..20 {
+            tree.insert(&Vec2::new(10.0 + i as f32, 10.0 + i as f32 / 2.0), i).unwrap();
+        }
+        tree.insert(&Vec2::new(90.5, 90.5), 20).unwrap();
+
+        let options = RenderOptions { width: 100, height: 50, point_radius: 0, ..Default::default() };
+        let image = render(&tree, &options);
+        assert_eq!((image.width(), image.height()), (100, 50));
+        assert_eq!(image.pixels().len(), 100 * 50 * 4);
+
+        // The y axis points up, and the quad boundaries are drawn over the whole tree.
+        assert_eq!(image.pixel(90, 4), options.point_color);
+        assert_eq!(image.pixel(75, 10), options.background);
+        assert_eq!(image.pixel(0, 0), options.quad_color.unwrap());
+        assert_eq!(image.pixel(99, 49), options.quad_color.unwrap());
+        assert_eq!(image.pixel(50, 25), options.quad_color.unwrap());
+
+        // The densest cells of the heatmap are the brightest.
+        let options = RenderOptions { mode: RenderMode::Heatmap { cell_size: 10 }, quad_color: None, ..options };
+        let image = render(&tree, &options);
+        assert_eq!(image.pixel(15, 45), [255, 255, 255, 255]);
+        assert_eq!(image.pixel(25, 40), [255, 255, 255, 255]);
+        let [r, g, b, _] = image.pixel(95, 5);
+        assert!(r > 0 && g == 0 && b == 0);
+        assert_eq!(image.pixel(75, 10), options.background);
+
+        let mut ppm = vec![];
+        image.write_ppm(&mut ppm).unwrap();
+        assert!(ppm.starts_with(b"P6\n100 50\n255\n"));
+        assert_eq!(ppm.len(), 14 + 100 * 50 * 3);
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn test_render_png() {
+        let image = Image::new(3, 2, [10, 20, 30, 255]);
+
+        let mut png = vec![];
+        image.write_png(&mut png).unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]));
+
+        // The header chunk holds the size, followed by 8 bits per channel of RGBA.
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..29], &[0, 0, 0, 3, 0, 0, 0, 2, 8, 6, 0, 0, 0]);
+    }
+}
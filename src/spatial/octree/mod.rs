@@ -0,0 +1,67 @@
+pub mod prelude;
+pub mod point_octree;
+
+#[allow(non_snake_case)]
+#[cfg(test)]
+mod tests {
+    use crate::spatial::octree::prelude::*;
+    use crate::spatial::octree::point_octree::*;
+
+    #[test]
+    fn test_BBox3D() {
+        let bbox = BBox3D {
+            min: Vec3::from([-10.0, -10.0, -10.0]),
+            max: Vec3::from([10.0, 10.0, 10.0])
+        };
+
+        assert!(bbox.contains(&Vec3::default()));
+        assert!(!bbox.contains(&Vec3::from([20.0, -100.0, 0.0])));
+        assert!(!bbox.contains(&Vec3::from([0.0, 0.0, 10.0])));
+
+        let octants = bbox.subdivide(&bbox.mid());
+        assert_eq!(octants.iter().filter(|o| o.contains(&Vec3::default())).count(), 1);
+        assert!(octants[7].contains(&Vec3::default()));
+        assert!(octants[1].contains(&Vec3::from([5.0, -5.0, -5.0])));
+        assert!(octants.iter().all(|o| bbox.intersects(o)));
+
+        assert_eq!(bbox.distance_to_point(&Vec3::from([0.0, 13.0, 14.0])), 5.0);
+    }
+
+    #[test]
+    fn test_PointOctree() {
+        let bbox = BBox3D {
+            min: Vec3::from([-10.0, -10.0, -10.0]),
+            max: Vec3::from([10.0, 10.0, 10.0])
+        };
+
+        let p1 = Vec3::from([0.0, 0.0, 0.0]);
+        let p2 = Vec3::from([1.0, 4.0, 2.0]);
+        let p3 = Vec3::from([-2.0, 3.0, -5.0]);
+
+        let mut tree = PointOctree::<i32>::new(&bbox);
+        assert!(tree.is_empty());
+        assert!(tree.nearest(&p1).is_none());
+
+        assert!(tree.insert(&p1, 12));
+        assert!(!tree.insert(&p1, 14));
+        assert!(tree.insert(&p2, -1));
+        assert!(tree.insert(&p3, 4));
+        assert!(!tree.insert(&Vec3::from([0.0, 0.0, 20.0]), 0));
+        assert_eq!(tree.len(), 3);
+
+        assert_eq!(tree.find(&p1).unwrap().1, 12);
+        assert_eq!(tree.find(&p3).unwrap().1, 4);
+        assert!(tree.find(&Vec3::from([1.0, 1.0, 1.0])).is_none());
+
+        let region = BBox3D {
+            min: Vec3::from([0.0, 0.0, 0.0]),
+            max: Vec3::from([10.0, 10.0, 10.0])
+        };
+        let mut items: Vec<i32> = tree.find_within(&region).into_iter().map(|(_, x)| x).collect();
+        items.sort();
+        assert_eq!(items, vec![-1, 12]);
+
+        assert_eq!(tree.nearest(&Vec3::from([-2.0, 2.0, -4.0])).unwrap().1, 4);
+        assert_eq!(tree.nearest(&Vec3::from([1.0, 3.0, 3.0])).unwrap().1, -1);
+    }
+}
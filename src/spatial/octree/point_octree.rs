@@ -0,0 +1,257 @@
+use alloc::collections::BinaryHeap;
+use alloc::{format, vec};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::arena::{GenerationalArena, GenerationalId};
+use crate::arena::prelude::{HasId, IsMemoryArena};
+use crate::spatial::octree::prelude::*;
+use crate::spatial::search::Candidate;
+use crate::visualize::{DotWriter, ToDot};
+
+pub use crate::spatial::quadtree::point_quadtree::IsPayload;
+
+type Id = GenerationalId;
+
+/// This represents the type of payload that is stored in each Octant of the tree.
+pub type Node<T> = (Vec3, T);
+
+/// An octant represents a region of 3D space, it contains a single point and optionally 8 other
+/// octants which subdivide the space further.
+#[derive(Clone, Debug)]
+struct Octant<P: IsPayload> {
+    pub id: Id,
+
+    pub bbox: BBox3D,
+
+    pub point: Option<Node<P>>,
+
+    // The ordering follows 'BBox3D::subdivide'
+    pub children: Option<[Id; 8]>
+}
+
+/// A Point Octree is the 3D counterpart of the Point Quadtree, it recursively subdivides 3D space
+/// into 8 buckets around the points it stores.
+pub struct PointOctree<P: IsPayload> {
+    arena: GenerationalArena<Octant<P>>,
+    root_id: Id,
+    size: AtomicUsize
+}
+
+impl<P: IsPayload> PointOctree<P> {
+
+    /// Returns the number of points contained in this tree.
+    pub fn len(&self) -> usize {
+        self.size.load(Ordering::SeqCst)
+    }
+
+    /// Returns true if this tree contains no points.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a new Octree bounded by the given BBox.
+    pub fn new(bbox: &BBox3D) -> Self {
+        let mut arena = GenerationalArena::new();
+
+        let root_id = arena.get_new_id();
+        arena.add_node(Octant::<P>::new(root_id, *bbox)).expect("could not add root node!");
+
+        Self {
+            arena,
+            root_id,
+            size: Default::default()
+        }
+    }
+
+    /// Attempts to insert the point into the tree, returning false if the point already exists.
+    pub fn insert(&mut self, point: &Vec3, payload: P) -> bool {
+        let root = self.root_id;
+        if self._insert(&(*point, payload), &root) {
+            self.size.fetch_add(1, Ordering::SeqCst);
+            return true;
+        }
+
+        false
+    }
+
+    /// Returns all points in the tree within the given BBox.
+    pub fn find_within(&self, bbox: &BBox3D) -> Vec<Node<P>> {
+        self._find_within(bbox, &self.root_id)
+    }
+
+    /// Searches the tree for the given point.
+    pub fn find(&self, p: &Vec3) -> Option<Node<P>> {
+        self._find(p, &self.root_id)
+    }
+
+    /// Returns the point in the tree closest to the given point.
+    pub fn nearest(&self, p: &Vec3) -> Option<Node<P>> {
+        let mut best: Option<(Node<P>, f32)> = None;
+
+        let mut queue = BinaryHeap::new();
+        queue.push(Candidate { dist: 0.0, item: self.root_id });
+
+        while let Some(Candidate { dist, item: id }) = queue.pop() {
+            // Octants are visited closest-first, so once the closest remaining octant is further
+            // away than the best point found so far, none of the remaining octants can do better.
+            if matches!(&best, Some((_, best_dist)) if dist > *best_dist) {
+                break;
+            }
+
+            let octant_ref = self.arena.get_node(&id).expect("could not find node");
+            let octant = octant_ref.read().unwrap();
+
+            if let Some(node) = &octant.point {
+                let node_dist = (node.0 - p).norm();
+                if !matches!(&best, Some((_, best_dist)) if node_dist >= *best_dist) {
+                    best = Some((node.clone(), node_dist));
+                }
+            }
+
+            if let Some(children) = &octant.children {
+                for child in children {
+                    let child_ref = self.arena.get_node(child).expect("could not find node");
+                    let dist = child_ref.read().unwrap().bbox.distance_to_point(p);
+                    queue.push(Candidate { dist, item: *child });
+                }
+            }
+        }
+
+        best.map(|(node, _)| node)
+    }
+
+    fn _find_within(&self, bbox: &BBox3D, octant_id: &Id) -> Vec<Node<P>> {
+        let octant_ref = self.arena.get_node(octant_id).expect("could not find node");
+        let octant = octant_ref.read().unwrap();
+
+        if !octant.bbox.intersects(bbox) {
+            return vec![];
+        }
+
+        let mut result = vec![];
+
+        match &octant.point {
+            Some(node) if bbox.contains(&node.0) => {
+                result.push(node.clone())
+            }
+            _ => {}
+        }
+
+        if let Some(children) = &octant.children {
+            for id in children {
+                result.append(&mut self._find_within(bbox, id))
+            }
+        }
+
+        result
+    }
+
+    fn _find(&self, p: &Vec3, octant_id: &Id) -> Option<Node<P>> {
+        let octant_ref = self.arena.get_node(octant_id).expect("could not find node");
+        let octant = octant_ref.read().unwrap();
+
+        // If the bbox itself doesn't contain the point, then neither can this octant's subtrees.
+        if !octant.bbox.contains(p) {
+            return None;
+        }
+
+        match &octant.point {
+            // If we don't have a point, the point can't be contained.
+            None => None,
+
+            Some(point) if point.0 == *p => Some(point.clone()),
+
+            // Otherwise, we'll need to look in all of this octant's subtrees.
+            Some(_) => {
+                octant.children.as_ref()?.iter().find_map(|child| self._find(p, child))
+            }
+        }
+    }
+
+    fn _insert(&mut self, elem: &Node<P>, octant_id: &Id) -> bool {
+        let octant_ref = self.arena.get_node(octant_id).expect("could not find node");
+        let mut octant = octant_ref.write().unwrap();
+
+        if !octant.bbox.contains(&elem.0) {
+            return false;
+        }
+
+        let pivot = match &octant.point {
+            None => {
+                octant.point = Some(elem.clone());
+                return true;
+            }
+            Some(point) if point.0 == elem.0 => return false,
+            Some(point) => point.0
+        };
+
+        // --
+        // Subdivide if we need to.
+        if octant.children.is_none() {
+            let boxes = octant.bbox.subdivide(&pivot);
+
+            octant.children = Some(boxes.map(|bbox| {
+                let new_id = self.arena.get_new_id();
+                self.arena.add_node(Octant::<P>::new(new_id, bbox)).expect("could not add node!");
+                new_id
+            }));
+        }
+
+        // --
+        // Then try to insert the point into any of our children.
+        octant.children.as_ref().unwrap().iter().any(|i| {
+            self._insert(elem, i)
+        })
+    }
+}
+
+impl<P: IsPayload> Octant<P> {
+    pub fn new(id: Id, bbox: BBox3D) -> Self {
+        Self {
+            id,
+            bbox,
+            point: None,
+            children: None,
+        }
+    }
+}
+
+impl<P: IsPayload> HasId for Octant<P> {
+    type Id = Id;
+    fn get_id(&self) -> Self::Id {
+        self.id
+    }
+}
+
+impl<P: IsPayload> ToDot for PointOctree<P> {
+    fn to_dot(&self) -> String {
+        let mut dot = DotWriter::new("PointOctree");
+        let mut stack = vec![self.root_id];
+
+        while let Some(id) = stack.pop() {
+            let octant_ref = self.arena.get_node(&id).expect("could not find node");
+            let octant = octant_ref.read().unwrap();
+
+            let (min, max) = (octant.bbox.min, octant.bbox.max);
+            let mut label = format!(
+                "({}, {}, {}) - ({}, {}, {})",
+                min.x, min.y, min.z, max.x, max.y, max.z
+            );
+            if let Some((p, payload)) = &octant.point {
+                label.push_str(&format!("\n({}, {}, {}): {:?}", p.x, p.y, p.z, payload));
+            }
+            dot.node(&id, &label, "shape=box");
+
+            if let Some(children) = &octant.children {
+                for child_id in children {
+                    dot.edge(&id, child_id, "", "");
+                    stack.push(*child_id);
+                }
+            }
+        }
+
+        dot.finish()
+    }
+}
@@ -0,0 +1,50 @@
+extern crate nalgebra as na;
+
+/// Octrees exist in 3-dimensional space
+pub type Vec3 = na::Vector3<f32>;
+
+/// This is a 3D axis-aligned bounding box (AABB).
+#[derive(Default, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BBox3D {
+    pub min: Vec3,
+    pub max: Vec3
+}
+
+impl BBox3D {
+    /// Returns true if the BBox contains the given point.
+    pub fn contains(&self, p: &Vec3) -> bool {
+        self.min <= *p && *p < self.max
+    }
+
+    /// Returns true if the BBox intersects the given BBox.
+    pub fn intersects(&self, other: &BBox3D) -> bool {
+        (0..3).all(|i| self.max[i] >= other.min[i] && other.max[i] >= self.min[i])
+    }
+
+    /// Returns the distance from the BBox to the given point, which is 0 if the point is inside.
+    pub fn distance_to_point(&self, p: &Vec3) -> f32 {
+        let d = Vec3::from_fn(|i, _| (self.min[i] - p[i]).max(0.0).max(p[i] - self.max[i]));
+        d.norm()
+    }
+
+    /// Returns the midpoint of the BBox
+    pub fn mid(&self) -> Vec3 {
+        (self.min + self.max) / 2.0
+    }
+
+    /// Subdivides the BBox into 8 BBoxes, using 'mid' as the midpoint.
+    ///
+    /// The octant at index 'i' lies on the upper side of the x, y and z axes when bits 0, 1 and 2
+    /// of 'i' are set, respectively.
+    pub fn subdivide(&self, mid: &Vec3) -> [BBox3D; 8] {
+        [0, 1, 2, 3, 4, 5, 6, 7].map(|octant: usize| {
+            let upper = |axis: usize| octant & (1 << axis) != 0;
+
+            Self {
+                min: Vec3::from_fn(|i, _| if upper(i) { mid[i] } else { self.min[i] }),
+                max: Vec3::from_fn(|i, _| if upper(i) { self.max[i] } else { mid[i] })
+            }
+        })
+    }
+}
@@ -0,0 +1,87 @@
+use alloc::vec::Vec;
+
+use crate::spatial::grid::hash_grid::SpatialHashGrid;
+use crate::spatial::quadtree::point_quadtree::{IsPayload, PointQuadtree};
+use crate::spatial::quadtree::prelude::*;
+
+pub use crate::spatial::quadtree::point_quadtree::Node;
+
+/// This is the common interface of the indexes of points in 2D space, which allows code to be
+/// written against any of them and the index to be picked per workload.
+pub trait SpatialIndex<P, S: IsScalar = f32> {
+    /// Returns the number of points contained in the index.
+    fn len(&self) -> usize;
+
+    /// Returns true if the index contains no points.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Attempts to insert the point into the index, returning false if it can't be inserted (e.g.
+    /// because the point already exists).
+    fn insert(&mut self, point: &Vec2<S>, payload: P) -> bool;
+
+    /// Removes the point from the index, returning its payload if it existed.
+    fn remove(&mut self, point: &Vec2<S>) -> Option<P>;
+
+    /// Returns all points in the index within the given BBox.
+    fn query_bbox(&self, bbox: &BBox2D<S>) -> Vec<Node<P, S>>;
+
+    /// Returns all points in the index within 'radius' of 'center'.
+    fn query_radius(&self, center: &Vec2<S>, radius: S) -> Vec<Node<P, S>>;
+
+    /// Returns the point in the index closest to the given point.
+    fn nearest(&self, point: &Vec2<S>) -> Option<Node<P, S>>;
+}
+
+impl<P: IsPayload, S: IsScalar> SpatialIndex<P, S> for PointQuadtree<P, S> {
+    fn len(&self) -> usize {
+        PointQuadtree::len(self)
+    }
+
+    fn insert(&mut self, point: &Vec2<S>, payload: P) -> bool {
+        PointQuadtree::insert(self, point, payload).is_ok()
+    }
+
+    fn remove(&mut self, point: &Vec2<S>) -> Option<P> {
+        PointQuadtree::remove(self, point)
+    }
+
+    fn query_bbox(&self, bbox: &BBox2D<S>) -> Vec<Node<P, S>> {
+        self.find_within(bbox)
+    }
+
+    fn query_radius(&self, center: &Vec2<S>, radius: S) -> Vec<Node<P, S>> {
+        self.find_within_radius(center, radius)
+    }
+
+    fn nearest(&self, point: &Vec2<S>) -> Option<Node<P, S>> {
+        PointQuadtree::nearest(self, point)
+    }
+}
+
+impl<P: IsPayload, S: IsScalar> SpatialIndex<P, S> for SpatialHashGrid<P, S> {
+    fn len(&self) -> usize {
+        SpatialHashGrid::len(self)
+    }
+
+    fn insert(&mut self, point: &Vec2<S>, payload: P) -> bool {
+        SpatialHashGrid::insert(self, point, payload)
+    }
+
+    fn remove(&mut self, point: &Vec2<S>) -> Option<P> {
+        SpatialHashGrid::remove(self, point)
+    }
+
+    fn query_bbox(&self, bbox: &BBox2D<S>) -> Vec<Node<P, S>> {
+        SpatialHashGrid::query_bbox(self, bbox)
+    }
+
+    fn query_radius(&self, center: &Vec2<S>, radius: S) -> Vec<Node<P, S>> {
+        SpatialHashGrid::query_radius(self, center, radius)
+    }
+
+    fn nearest(&self, point: &Vec2<S>) -> Option<Node<P, S>> {
+        SpatialHashGrid::nearest(self, point)
+    }
+}
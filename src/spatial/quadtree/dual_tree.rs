@@ -0,0 +1,178 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::spatial::quadtree::point_quadtree::cmp_scalar;
+use crate::spatial::quadtree::prelude::*;
+
+/// A read-only copy of the quads of a PointQuadtree, laid out for traversing pairs of quads at once
+/// (a dual-tree traversal). Points are stored in pre-order, so the points below every quad form a
+/// contiguous range, which lets a query keep its state per point in a plain Vec.
+///
+/// Points held by a subdivided quad are moved into an extra leaf below it, so points only ever live
+/// in leaves.
+pub(super) struct DualTree<S: IsScalar> {
+    pub points: Vec<Vec2<S>>,
+    quads: Vec<FlatQuad<S>>
+}
+
+struct FlatQuad<S: IsScalar> {
+    bbox: BBox2D<S>,
+    range: Range<usize>,
+    children: Vec<usize>
+}
+
+impl<S: IsScalar> DualTree<S> {
+
+    pub fn new() -> Self {
+        Self { points: vec![], quads: vec![] }
+    }
+
+    /// Adds a quad below 'parent' (or the root if there is none) and returns its index. Points
+    /// pushed from now on belong to it, until it is closed.
+    pub fn open(&mut self, bbox: BBox2D<S>, parent: Option<usize>) -> usize {
+        let idx = self.quads.len();
+        let start = self.points.len();
+        self.quads.push(FlatQuad { bbox, range: start..start, children: vec![] });
+
+        if let Some(parent) = parent {
+            self.quads[parent].children.push(idx);
+        }
+        idx
+    }
+
+    pub fn push(&mut self, p: Vec2<S>) {
+        self.points.push(p);
+    }
+
+    pub fn close(&mut self, idx: usize) {
+        self.quads[idx].range.end = self.points.len();
+    }
+
+    // --
+    // Closest pair
+
+    /// Returns the indices of the 2 closest points along with their distance.
+    pub fn closest_pair(&self) -> Option<(usize, usize, S)> {
+        let mut best = None;
+        if !self.quads.is_empty() {
+            self._closest_pair(0, 0, &mut best);
+        }
+        best
+    }
+
+    /// Looks for a pair closer than 'best' with one point below quad 'a' and the other below quad
+    /// 'b', where 'a' and 'b' are either the same quad or disjoint.
+    fn _closest_pair(&self, a: usize, b: usize, best: &mut Option<(usize, usize, S)>) {
+        let (qa, qb) = (&self.quads[a], &self.quads[b]);
+
+        if qa.range.is_empty() || qb.range.is_empty() {
+            return;
+        }
+        if matches!(best, Some((_, _, d)) if qa.bbox.distance_to_bbox(&qb.bbox) >= *d) {
+            return;
+        }
+
+        match (qa.children.is_empty(), qb.children.is_empty()) {
+            (true, true) => {
+                for i in qa.range.clone() {
+                    // A leaf paired with itself only compares each pair of its points once.
+                    let start = if a == b { i + 1 } else { qb.range.start };
+                    for j in start..qb.range.end {
+                        let d = (self.points[i] - self.points[j]).norm();
+                        if !matches!(best, Some((_, _, best_d)) if d >= *best_d) {
+                            *best = Some((i, j, d));
+                        }
+                    }
+                }
+            }
+            (true, false) => {
+                for c in self.by_distance(&qa.bbox, &qb.children) {
+                    self._closest_pair(a, c, best);
+                }
+            }
+            (false, true) => {
+                for c in self.by_distance(&qb.bbox, &qa.children) {
+                    self._closest_pair(c, b, best);
+                }
+            }
+            (false, false) => {
+                let mut pairs = vec![];
+                for (i, &ca) in qa.children.iter().enumerate() {
+                    let others = if a == b { &qa.children[i..] } else { &qb.children[..] };
+                    for &cb in others {
+                        let d = self.quads[ca].bbox.distance_to_bbox(&self.quads[cb].bbox);
+                        pairs.push((d, ca, cb));
+                    }
+                }
+
+                // Visiting the closest pairs of quads first finds a good bound early on.
+                pairs.sort_by(|x, y| cmp_scalar(&x.0, &y.0));
+                for (_, ca, cb) in pairs {
+                    self._closest_pair(ca, cb, best);
+                }
+            }
+        }
+    }
+
+    // --
+    // All nearest neighbors
+
+    /// Returns the index of the closest other point of every point along with its distance, or
+    /// None if there is no other point.
+    pub fn all_nearest(&self) -> Vec<Option<(usize, S)>> {
+        let mut best = vec![None; self.points.len()];
+        if !self.quads.is_empty() {
+            self._all_nearest(0, 0, &mut best);
+        }
+        best
+    }
+
+    /// Looks for closer neighbors of the points below quad 'q' among the points below quad 'r'.
+    fn _all_nearest(&self, q: usize, r: usize, best: &mut Vec<Option<(usize, S)>>) {
+        let (qq, qr) = (&self.quads[q], &self.quads[r]);
+
+        if qq.range.is_empty() || qr.range.is_empty() {
+            return;
+        }
+
+        // No point below 'q' can find a closer neighbor below 'r' than the furthest neighbor found
+        // for any of them so far, if 'r' is further away than that.
+        let bound = best[qq.range.clone()].iter().try_fold(S::zero(), |bound, b| b.map(|(_, d)| bound.max(d)));
+        if matches!(bound, Some(bound) if qq.bbox.distance_to_bbox(&qr.bbox) >= bound) {
+            return;
+        }
+
+        if !qq.children.is_empty() {
+            for &c in &qq.children {
+                self._all_nearest(c, r, best);
+            }
+            return;
+        }
+
+        if !qr.children.is_empty() {
+            for c in self.by_distance(&qq.bbox, &qr.children) {
+                self._all_nearest(q, c, best);
+            }
+            return;
+        }
+
+        for i in qq.range.clone() {
+            for j in qr.range.clone().filter(|&j| j != i) {
+                let d = (self.points[i] - self.points[j]).norm();
+                if !matches!(best[i], Some((_, best_d)) if d >= best_d) {
+                    best[i] = Some((j, d));
+                }
+            }
+        }
+    }
+
+    /// Returns the given quads sorted by their distance to 'bbox'.
+    fn by_distance(&self, bbox: &BBox2D<S>, quads: &[usize]) -> Vec<usize> {
+        let mut sorted: Vec<(S, usize)> = quads.iter()
+            .map(|&c| (bbox.distance_to_bbox(&self.quads[c].bbox), c))
+            .collect();
+        sorted.sort_by(|x, y| cmp_scalar(&x.0, &y.0));
+        sorted.into_iter().map(|(_, c)| c).collect()
+    }
+}
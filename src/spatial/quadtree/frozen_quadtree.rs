@@ -0,0 +1,255 @@
+use alloc::collections::BinaryHeap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+
+use crate::arena::{GenerationalArena, GenerationalId};
+use crate::arena::prelude::IsMemoryArena;
+use crate::spatial::quadtree::point_quadtree::{Node, Quad};
+use crate::spatial::quadtree::prelude::*;
+use crate::spatial::search::Candidate;
+
+type Id = GenerationalId;
+
+/// Marks a quad which isn't subdivided.
+const NO_CHILDREN: u32 = u32::MAX;
+
+/// This class represents a read-only PointQuadtree, compiled into a handful of flat arrays by
+/// 'PointQuadtree::freeze'.
+///
+/// The quads are numbered in level order (the root first, then its children, then their children,
+/// and so on) so that the 4 children of every quad are numbered consecutively, and so are the
+/// points held by the quads. Every quad then only needs to store its bbox, where its children start
+/// and where its points start. Compared to the PointQuadtree, there is no lock, reference count or
+/// arena lookup per quad, and the points of neighbouring quads sit next to each other in memory.
+pub struct FrozenQuadtree<P, S: IsScalar = f32> {
+    bboxes: Vec<BBox2D<S>>,
+
+    /// The children of quad 'i' are the quads in 'first_child[i]..first_child[i] + 4', in SW, SE,
+    /// NE, NW order, or NO_CHILDREN.
+    first_child: Vec<u32>,
+
+    /// The points of quad 'i' are the ones in 'first_point[i]..first_point[i + 1]'.
+    first_point: Vec<u32>,
+
+    points: Vec<Vec2<S>>,
+    payloads: Vec<P>
+}
+
+impl<P: Send + Sync, S: IsScalar> FrozenQuadtree<P, S> {
+
+    /// Compiles the quads of a PointQuadtree, moving their points out of them.
+    pub(crate) fn new(arena: GenerationalArena<Quad<P, S>>, root: Id) -> Self {
+        let mut tree = Self {
+            bboxes: vec![],
+            first_child: vec![],
+            first_point: vec![],
+            points: vec![],
+            payloads: vec![]
+        };
+
+        // --
+        // Quads are numbered as they are discovered by a breadth-first traversal, so the children
+        // of every quad get consecutive numbers.
+        let mut order = vec![root];
+        let mut next = 0;
+
+        while next < order.len() {
+            let quad_ref = arena.get_node(&order[next]).expect("could not find node");
+            let mut quad = quad_ref.write().unwrap();
+
+            tree.bboxes.push(quad.bbox);
+            match quad.children {
+                None => tree.first_child.push(NO_CHILDREN),
+                Some(children) => {
+                    tree.first_child.push(Self::to_u32(order.len()));
+                    order.extend(children);
+                }
+            }
+
+            tree.first_point.push(Self::to_u32(tree.points.len()));
+            for (p, payload) in core::mem::take(&mut quad.points) {
+                tree.points.push(p);
+                tree.payloads.push(payload);
+            }
+
+            next += 1;
+        }
+
+        tree.first_point.push(Self::to_u32(tree.points.len()));
+        tree
+    }
+
+    fn to_u32(n: usize) -> u32 {
+        u32::try_from(n).ok().filter(|n| *n != NO_CHILDREN).expect("quadtree is too large to be frozen")
+    }
+}
+
+impl<P, S: IsScalar> FrozenQuadtree<P, S> {
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the bbox of the tree.
+    pub fn bbox(&self) -> BBox2D<S> {
+        self.bboxes[0]
+    }
+
+    /// Returns the number of quads in the tree.
+    pub fn node_count(&self) -> usize {
+        self.bboxes.len()
+    }
+
+    /// Returns the approximate number of bytes used by the tree, not counting memory which the
+    /// payloads themselves allocate.
+    pub fn approx_bytes(&self) -> usize {
+        self.bboxes.capacity() * core::mem::size_of::<BBox2D<S>>()
+            + (self.first_child.capacity() + self.first_point.capacity()) * core::mem::size_of::<u32>()
+            + self.points.capacity() * core::mem::size_of::<Vec2<S>>()
+            + self.payloads.capacity() * core::mem::size_of::<P>()
+    }
+
+    /// Returns the payload of the given point, if it is stored in the tree.
+    pub fn find(&self, p: &Vec2<S>) -> Option<&P> {
+        let mut quad = 0;
+
+        loop {
+            if let Some(idx) = self.point_range(quad).find(|idx| self.points[*idx] == *p) {
+                return Some(&self.payloads[idx]);
+            }
+
+            // Otherwise, the point can only be in the child which contains it (if there is one).
+            quad = self.children(quad)?.find(|child| self.bboxes[*child].contains(p))?;
+        }
+    }
+
+    pub fn contains(&self, p: &Vec2<S>) -> bool {
+        self.find(p).is_some()
+    }
+
+    /// Calls 'f' on every point in the tree, in level order of their quads.
+    pub fn for_each<F: FnMut(&Vec2<S>, &P)>(&self, mut f: F) {
+        for (p, payload) in self.points.iter().zip(&self.payloads) {
+            f(p, payload);
+        }
+    }
+
+    /// Returns every point in the tree, in level order of their quads.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = Node<&P, S>> + ExactSizeIterator {
+        self.points.iter().copied().zip(&self.payloads)
+    }
+
+    /// Calls 'f' on every point in the tree within the given BBox.
+    pub fn find_within_with<F: FnMut(&Vec2<S>, &P)>(&self, bbox: &BBox2D<S>, mut f: F) {
+        self._find_within(bbox, 0, &mut |idx| f(&self.points[idx], &self.payloads[idx]));
+    }
+
+    /// Returns all points in the tree within the given BBox.
+    pub fn find_within(&self, bbox: &BBox2D<S>) -> Vec<Node<&P, S>> {
+        let mut result = vec![];
+        self._find_within(bbox, 0, &mut |idx| result.push((self.points[idx], &self.payloads[idx])));
+        result
+    }
+
+    /// Calls 'f' on every point in the tree within 'radius' of the given center.
+    pub fn find_within_radius_with<F: FnMut(&Vec2<S>, &P)>(&self, center: &Vec2<S>, radius: S, mut f: F) {
+        self._find_within_radius(center, radius, 0, &mut |idx| f(&self.points[idx], &self.payloads[idx]));
+    }
+
+    /// Returns all points in the tree within 'radius' of the given center.
+    pub fn find_within_radius(&self, center: &Vec2<S>, radius: S) -> Vec<Node<&P, S>> {
+        let mut result = vec![];
+        self._find_within_radius(center, radius, 0, &mut |idx| result.push((self.points[idx], &self.payloads[idx])));
+        result
+    }
+
+    /// Returns the point in the tree closest to the given point.
+    pub fn nearest(&self, p: &Vec2<S>) -> Option<Node<&P, S>> {
+        self.knn(p, 1).pop().map(|(node, _)| node)
+    }
+
+    /// Returns the 'k' points in the tree closest to the given point along with their distances,
+    /// sorted from closest to furthest.
+    pub fn knn(&self, p: &Vec2<S>, k: usize) -> Vec<(Node<&P, S>, S)> {
+        if k == 0 || self.is_empty() {
+            return vec![];
+        }
+
+        // The furthest of the best 'k' points found so far sits at the top of this heap.
+        let mut best: BinaryHeap<Reverse<Candidate<usize, S>>> = BinaryHeap::with_capacity(k + 1);
+
+        let mut queue = BinaryHeap::new();
+        queue.push(Candidate { dist: S::zero(), item: 0 });
+
+        while let Some(Candidate { dist, item: quad }) = queue.pop() {
+            // Once we have 'k' points, any quad further away than the k-th best can be pruned.
+            if best.len() == k && dist > best.peek().unwrap().0.dist {
+                break;
+            }
+
+            for idx in self.point_range(quad) {
+                best.push(Reverse(Candidate { dist: (self.points[idx] - p).norm(), item: idx }));
+                if best.len() > k {
+                    best.pop();
+                }
+            }
+
+            for child in self.children(quad).into_iter().flatten() {
+                queue.push(Candidate { dist: self.bboxes[child].distance_to_point(p), item: child });
+            }
+        }
+
+        best.into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(Candidate { dist, item })| ((self.points[item], &self.payloads[item]), dist))
+            .collect()
+    }
+
+    // --
+    // Helpers, which report points by their index.
+
+    fn _find_within<F: FnMut(usize)>(&self, bbox: &BBox2D<S>, quad: usize, f: &mut F) {
+        if !self.bboxes[quad].intersects(bbox) {
+            return;
+        }
+
+        for idx in self.point_range(quad).filter(|idx| bbox.contains(&self.points[*idx])) {
+            f(idx);
+        }
+
+        for child in self.children(quad).into_iter().flatten() {
+            self._find_within(bbox, child, f);
+        }
+    }
+
+    fn _find_within_radius<F: FnMut(usize)>(&self, center: &Vec2<S>, radius: S, quad: usize, f: &mut F) {
+        // If the circle doesn't touch this quad, it can't touch any of its subtrees either.
+        if self.bboxes[quad].distance_to_point(center) > radius {
+            return;
+        }
+
+        for idx in self.point_range(quad).filter(|idx| (self.points[*idx] - center).norm() <= radius) {
+            f(idx);
+        }
+
+        for child in self.children(quad).into_iter().flatten() {
+            self._find_within_radius(center, radius, child, f);
+        }
+    }
+
+    fn point_range(&self, quad: usize) -> core::ops::Range<usize> {
+        self.first_point[quad] as usize..self.first_point[quad + 1] as usize
+    }
+
+    fn children(&self, quad: usize) -> Option<core::ops::Range<usize>> {
+        match self.first_child[quad] {
+            NO_CHILDREN => None,
+            first => Some(first as usize..first as usize + 4)
+        }
+    }
+}
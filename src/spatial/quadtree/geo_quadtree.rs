@@ -0,0 +1,233 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::f64::consts::PI;
+
+#[cfg(not(feature = "std"))]
+use nalgebra::ComplexField;
+
+use crate::spatial::quadtree::point_quadtree::*;
+use crate::spatial::quadtree::prelude::*;
+
+/// The mean radius of the earth, in meters.
+pub const EARTH_RADIUS_METERS: f64 = 6_371_008.8;
+
+/// A position on the globe, in degrees.
+#[derive(Default, Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LatLon {
+    pub lat: f64,
+    pub lon: f64
+}
+
+impl LatLon {
+    pub fn new(lat: f64, lon: f64) -> Self {
+        Self { lat, lon }
+    }
+
+    /// Returns the great-circle distance to the given position in meters, using the haversine
+    /// formula on a spherical earth.
+    pub fn distance_meters(&self, other: &LatLon) -> f64 {
+        let (lat_a, lat_b) = (self.lat.to_radians(), other.lat.to_radians());
+        let d_lat = lat_b - lat_a;
+        let d_lon = (other.lon - self.lon).to_radians();
+
+        let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_METERS * h.sqrt().min(1.0).asin()
+    }
+}
+
+/// A box on the globe, bounded by two parallels and two meridians, in degrees. All of its bounds
+/// are inclusive.
+///
+/// The box spans eastwards from 'west' to 'east', so a box whose west bound is greater than its
+/// east bound crosses the antimeridian, e.g. `GeoBBox::new(-20.0, 170.0, -10.0, -170.0)` around
+/// Fiji.
+#[derive(Default, Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GeoBBox {
+    pub south: f64,
+    pub west: f64,
+    pub north: f64,
+    pub east: f64
+}
+
+impl GeoBBox {
+    pub fn new(south: f64, west: f64, north: f64, east: f64) -> Self {
+        Self { south, west, north, east }
+    }
+
+    /// Returns the box covering the whole globe.
+    pub fn world() -> Self {
+        Self::new(-90.0, -180.0, 90.0, 180.0)
+    }
+
+    /// Returns true if the box contains the given position.
+    pub fn contains(&self, p: &LatLon) -> bool {
+        if p.lat < self.south || self.north < p.lat {
+            return false;
+        }
+
+        let (west, east, lon) = (normalize_lon(self.west), normalize_lon(self.east), normalize_lon(p.lon));
+        if self.east - self.west >= 360.0 {
+            true
+        } else if west <= east {
+            west <= lon && lon <= east
+        } else {
+            west <= lon || lon <= east
+        }
+    }
+
+    /// Returns the planar BBoxes of longitude and latitude covering this box, which are two if it
+    /// crosses the antimeridian. The max bounds are nudged up, since BBoxes don't contain them.
+    fn planar(&self) -> Vec<BBox2D<f64>> {
+        let bbox = |west: f64, east: f64| BBox2D {
+            min: Vec2::from([west, self.south]),
+            max: Vec2::from([east.next_up(), self.north.next_up()])
+        };
+
+        let (west, east) = (normalize_lon(self.west), normalize_lon(self.east));
+        if self.east - self.west >= 360.0 {
+            vec![bbox(-180.0, 180.0)]
+        } else if west <= east {
+            vec![bbox(west, east)]
+        } else {
+            vec![bbox(west, 180.0), bbox(-180.0, east)]
+        }
+    }
+}
+
+/// A Point Quadtree over the globe, which stores positions by latitude and longitude rather than
+/// planar coordinates.
+///
+/// Longitudes are normalized into [-180, 180), so a position at 180° is stored at -180°, and boxes
+/// crossing the antimeridian are split in two. Distances are measured along great circles, so
+/// radius queries stay correct near the poles and across the antimeridian.
+pub struct GeoQuadtree<P: Send + Sync> {
+    tree: PointQuadtree<P, f64>
+}
+
+impl<P: Send + Sync> Default for GeoQuadtree<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Send + Sync> GeoQuadtree<P> {
+
+    /// Constructs a new, empty tree covering the whole globe.
+    pub fn new() -> Self {
+        Self::with_config(QuadtreeConfig::default())
+    }
+
+    /// Constructs a new, empty tree covering the whole globe, subdivided according to 'config'.
+    pub fn with_config(config: QuadtreeConfig) -> Self {
+        let bbox = BBox2D {
+            min: Vec2::from([-180.0, -90.0]),
+            max: Vec2::from([180.0, 90.0f64.next_up()])
+        };
+
+        Self { tree: PointQuadtree::with_config(&bbox, config) }
+    }
+
+    /// Returns the number of positions contained in this tree.
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// Returns true if this tree contains no positions.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Inserts a position along with its payload. Positions whose latitude lies outside of
+    /// [-90, 90] or which aren't finite are out of bounds.
+    pub fn insert(&mut self, p: &LatLon, payload: P) -> Result<(), InsertError> {
+        if !(p.lat.abs() <= 90.0 && p.lon.is_finite()) {
+            return Err(InsertError::OutOfBounds);
+        }
+
+        self.tree.insert(&to_planar(p), payload)
+    }
+
+    /// Removes the given position, returning its payload.
+    pub fn remove(&mut self, p: &LatLon) -> Option<P> {
+        self.tree.remove(&to_planar(p))
+    }
+
+    /// Calls 'f' on every position within the given box, without cloning the payloads.
+    pub fn find_within_with<F: FnMut(&LatLon, &P)>(&self, bbox: &GeoBBox, mut f: F) {
+        for planar in bbox.planar() {
+            self.tree.find_within_with(&planar, |p, payload| f(&from_planar(p), payload));
+        }
+    }
+
+    /// Calls 'f' on every position within 'radius_m' meters of the given center, along with its
+    /// distance in meters, without cloning the payloads.
+    ///
+    /// Only the box bounding the circle is searched, and the positions within it are filtered by
+    /// their great-circle distance.
+    pub fn find_within_meters_with<F: FnMut(&LatLon, &P, f64)>(&self, center: &LatLon, radius_m: f64, mut f: F) {
+        self.find_within_with(&bounding_box(center, radius_m), |p, payload| {
+            let distance = center.distance_meters(p);
+            if distance <= radius_m {
+                f(p, payload, distance);
+            }
+        });
+    }
+}
+
+impl<P: IsPayload> GeoQuadtree<P> {
+
+    /// Returns all positions within the given box.
+    pub fn find_within(&self, bbox: &GeoBBox) -> Vec<(LatLon, P)> {
+        let mut result = vec![];
+        self.find_within_with(bbox, |p, payload| result.push((*p, payload.clone())));
+        result
+    }
+
+    /// Returns all positions within 'radius_m' meters of the given center, closest first.
+    pub fn find_within_meters(&self, center: &LatLon, radius_m: f64) -> Vec<(LatLon, P)> {
+        let mut result = vec![];
+        self.find_within_meters_with(center, radius_m, |p, payload, distance| {
+            result.push((distance, *p, payload.clone()));
+        });
+
+        result.sort_by(|a, b| a.0.total_cmp(&b.0));
+        result.into_iter().map(|(_, p, payload)| (p, payload)).collect()
+    }
+}
+
+/// Returns the box bounding the circle of 'radius_m' meters around the given center.
+fn bounding_box(center: &LatLon, radius_m: f64) -> GeoBBox {
+    let angle = radius_m / EARTH_RADIUS_METERS;
+    if angle >= PI {
+        return GeoBBox::world();
+    }
+
+    let d_lat = angle.to_degrees();
+    let (south, north) = (center.lat - d_lat, center.lat + d_lat);
+
+    // --
+    // A circle around a pole spans every longitude. Otherwise, the circle is widest at the
+    // latitude where its tangent meridians touch it, which is a little closer to the pole.
+    if south <= -90.0 || north >= 90.0 {
+        return GeoBBox::new(south.max(-90.0), -180.0, north.min(90.0), 180.0);
+    }
+
+    let d_lon = (angle.sin() / center.lat.to_radians().cos()).min(1.0).asin().to_degrees();
+    GeoBBox::new(south, center.lon - d_lon, north, center.lon + d_lon)
+}
+
+/// Maps a longitude into [-180, 180).
+fn normalize_lon(lon: f64) -> f64 {
+    let lon = (lon + 180.0) % 360.0;
+    if lon < 0.0 { lon + 180.0 } else { lon - 180.0 }
+}
+
+fn to_planar(p: &LatLon) -> Vec2<f64> {
+    Vec2::from([normalize_lon(p.lon), p.lat])
+}
+
+fn from_planar(p: &Vec2<f64>) -> LatLon {
+    LatLon::new(p.y, p.x)
+}
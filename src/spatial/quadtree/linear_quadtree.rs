@@ -0,0 +1,245 @@
+use alloc::collections::BinaryHeap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::spatial::quadtree::point_quadtree::{IsPayload, Node};
+use crate::spatial::quadtree::prelude::*;
+use crate::spatial::search::Candidate;
+
+/// The number of bits used for each axis of a Morton code, i.e. the depth of the deepest quads.
+pub(super) const BITS: usize = 16;
+
+/// Quads holding at most this many points are scanned directly instead of being subdivided.
+const LEAF_SIZE: usize = 8;
+
+/// A quad in the implicit hierarchy, which holds the points whose Morton codes start with the
+/// 2 * 'depth' bits of 'prefix'. Those points make up the slice 'range' of the tree's points.
+#[derive(Debug, Clone)]
+struct Quad {
+    depth: usize,
+    prefix: u32,
+    range: Range<usize>
+}
+
+/// A Linear Quadtree stores its points in a flat Vec sorted by their Morton (Z-order) codes.
+///
+/// Interleaving the bits of the x and y cells of a point means every quad of the equivalent
+/// quadtree covers a contiguous run of codes, so the hierarchy is implicit and a quad's points are
+/// found with a binary search. There are no nodes to allocate or lock, which makes this much faster
+/// to build and query than a PointQuadtree, but the tree can't be modified once built.
+pub struct LinearQuadtree<P> {
+    bbox: BBox2D,
+    codes: Vec<u32>,
+    points: Vec<Node<P>>
+}
+
+impl<P> LinearQuadtree<P> {
+
+    /// Builds a tree bounded by the given BBox holding the given points. Points outside of the
+    /// BBox and repeats of earlier points are skipped.
+    pub fn from_points(bbox: &BBox2D, points: impl IntoIterator<Item = Node<P>>) -> Self {
+        let mut tree = Self {
+            bbox: *bbox,
+            codes: vec![],
+            points: vec![]
+        };
+
+        let mut entries: Vec<(u32, Node<P>)> = points.into_iter()
+            .filter(|node| bbox.contains(&node.0))
+            .map(|node| (tree.code(&node.0), node))
+            .collect();
+
+        // The sort is stable, so only the first of any repeated points is kept.
+        entries.sort_by(|a, b| {
+            a.0.cmp(&b.0)
+                .then(a.1.0.x.total_cmp(&b.1.0.x))
+                .then(a.1.0.y.total_cmp(&b.1.0.y))
+        });
+        entries.dedup_by(|a, b| a.1.0 == b.1.0);
+
+        (tree.codes, tree.points) = entries.into_iter().unzip();
+        tree
+    }
+
+    /// Returns the number of points contained in this tree.
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Returns true if this tree contains no points.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the Morton code of the cell containing the given point.
+    fn code(&self, p: &Vec2) -> u32 {
+        let cells = (1u32 << BITS) as f64;
+        let extent = self.bbox.max - self.bbox.min;
+
+        let cell = |v: f32, min: f32, extent: f32| {
+            (((v - min) as f64 / extent as f64) * cells).clamp(0.0, cells - 1.0) as u32
+        };
+
+        spread(cell(p.x, self.bbox.min.x, extent.x)) | (spread(cell(p.y, self.bbox.min.y, extent.y)) << 1)
+    }
+
+    /// Returns the root of the implicit hierarchy.
+    fn root(&self) -> Quad {
+        Quad { depth: 0, prefix: 0, range: 0..self.points.len() }
+    }
+
+    /// Returns the non-empty children of the given quad.
+    fn children(&self, quad: &Quad) -> Vec<Quad> {
+        let shift = 2 * (BITS - quad.depth - 1);
+        let codes = &self.codes[quad.range.clone()];
+
+        (0..4u32)
+            .map(|k| {
+                let prefix = (quad.prefix << 2) | k;
+                let start = (prefix as u64) << shift;
+                let end = (prefix as u64 + 1) << shift;
+
+                let lo = codes.partition_point(|code| (*code as u64) < start);
+                let hi = codes.partition_point(|code| (*code as u64) < end);
+
+                Quad {
+                    depth: quad.depth + 1,
+                    prefix,
+                    range: quad.range.start + lo..quad.range.start + hi
+                }
+            })
+            .filter(|child| !child.range.is_empty())
+            .collect()
+    }
+
+    /// Returns true if the points of the given quad should be scanned instead of subdividing it.
+    fn is_leaf(&self, quad: &Quad) -> bool {
+        quad.range.len() <= LEAF_SIZE || quad.depth == BITS
+    }
+
+    /// Returns the BBox of the given quad, padded by a cell on every side so that rounding while
+    /// computing codes never places a point outside of the BBox of its quad.
+    fn bbox_of(&self, quad: &Quad) -> BBox2D {
+        let size = 1u32 << (BITS - quad.depth);
+        let x = compact(quad.prefix) * size;
+        let y = compact(quad.prefix >> 1) * size;
+
+        let cells = (1u32 << BITS) as f32;
+        let extent = self.bbox.max - self.bbox.min;
+        let to_world = |x: f32, y: f32| Vec2::from([
+            self.bbox.min.x + extent.x * x / cells,
+            self.bbox.min.y + extent.y * y / cells
+        ]);
+
+        let (x, y, size) = (x as f32, y as f32, size as f32);
+
+        BBox2D {
+            min: to_world(x - 1.0, y - 1.0),
+            max: to_world(x + size + 1.0, y + size + 1.0)
+        }
+    }
+}
+
+impl<P: IsPayload> LinearQuadtree<P> {
+
+    /// Searches the tree for the given point.
+    pub fn find(&self, p: &Vec2) -> Option<Node<P>> {
+        if !self.bbox.contains(p) {
+            return None;
+        }
+
+        let code = self.code(p);
+        let lo = self.codes.partition_point(|c| *c < code);
+        let hi = self.codes.partition_point(|c| *c <= code);
+
+        self.points[lo..hi].iter().find(|node| node.0 == *p).cloned()
+    }
+
+    /// Returns all points in the tree within the given BBox, in Morton order.
+    pub fn find_within(&self, bbox: &BBox2D) -> Vec<Node<P>> {
+        let mut result = vec![];
+        if !self.is_empty() {
+            self._find_within(bbox, &self.root(), &mut result);
+        }
+        result
+    }
+
+    /// Returns the point in the tree closest to the given point.
+    pub fn nearest(&self, p: &Vec2) -> Option<Node<P>> {
+        let mut best: Option<(&Node<P>, f32)> = None;
+
+        let mut queue = BinaryHeap::new();
+        if !self.is_empty() {
+            queue.push(Candidate { dist: 0.0, item: self.root() });
+        }
+
+        while let Some(Candidate { dist, item: quad }) = queue.pop() {
+            // Quads are visited closest-first, so once the closest remaining quad is further away
+            // than the best point found so far, none of the remaining quads can do any better.
+            if matches!(&best, Some((_, best_dist)) if dist > *best_dist) {
+                break;
+            }
+
+            if self.is_leaf(&quad) {
+                for node in &self.points[quad.range] {
+                    let node_dist = (node.0 - p).norm();
+                    if !matches!(&best, Some((_, best_dist)) if node_dist >= *best_dist) {
+                        best = Some((node, node_dist));
+                    }
+                }
+                continue;
+            }
+
+            for child in self.children(&quad) {
+                let dist = self.bbox_of(&child).distance_to_point(p);
+                queue.push(Candidate { dist, item: child });
+            }
+        }
+
+        best.map(|(node, _)| node.clone())
+    }
+
+    fn _find_within(&self, bbox: &BBox2D, quad: &Quad, out: &mut Vec<Node<P>>) {
+        let quad_bbox = self.bbox_of(quad);
+
+        if !quad_bbox.intersects(bbox) {
+            return;
+        }
+
+        let points = &self.points[quad.range.clone()];
+
+        // If the quad lies entirely inside the query, every one of its points matches.
+        if bbox.contains_bbox(&quad_bbox) {
+            out.extend_from_slice(points);
+            return;
+        }
+
+        if self.is_leaf(quad) {
+            out.extend(points.iter().filter(|node| bbox.contains(&node.0)).cloned());
+            return;
+        }
+
+        for child in self.children(quad) {
+            self._find_within(bbox, &child, out);
+        }
+    }
+}
+
+/// Spreads the lower 16 bits of 'v' out into the even bits of the result.
+pub(super) fn spread(v: u32) -> u32 {
+    let mut v = v & 0x0000FFFF;
+    v = (v | (v << 8)) & 0x00FF00FF;
+    v = (v | (v << 4)) & 0x0F0F0F0F;
+    v = (v | (v << 2)) & 0x33333333;
+    (v | (v << 1)) & 0x55555555
+}
+
+/// The inverse of 'spread', gathers the even bits of 'v' into the lower 16 bits of the result.
+fn compact(v: u32) -> u32 {
+    let mut v = v & 0x55555555;
+    v = (v | (v >> 1)) & 0x33333333;
+    v = (v | (v >> 2)) & 0x0F0F0F0F;
+    v = (v | (v >> 4)) & 0x00FF00FF;
+    (v | (v >> 8)) & 0x0000FFFF
+}
@@ -0,0 +1,376 @@
+extern crate nalgebra as na;
+
+use alloc::vec;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::arena::{GenerationalArena, GenerationalId};
+use crate::arena::prelude::{HasId, IsMemoryArena, SharedRef};
+use crate::spatial::bvh::bvh_impl::HasBounds;
+use crate::spatial::quadtree::point_quadtree::{InsertError, IsPayload};
+use crate::spatial::quadtree::prelude::*;
+
+type Id = GenerationalId;
+
+/// Quads at this depth are never subdivided, so tiny objects don't subdivide the tree forever.
+const MAX_DEPTH: usize = 16;
+
+/// A quad of a LooseQuadtree. Its cell partitions the space like the quads of a PointQuadtree do,
+/// while its loose bbox is the cell grown by the looseness of the tree around its midpoint.
+#[derive(Clone, Debug)]
+struct LooseQuad<S: IsScalar> {
+    pub id: Id,
+
+    pub cell: BBox2D<S>,
+
+    pub loose: BBox2D<S>,
+
+    pub depth: usize,
+
+    pub parent: Option<Id>,
+
+    pub objects: Vec<Id>,
+
+    // The ordering goes SW, SE, NE, NW. Quads are only created once an object is placed in them.
+    pub children: [Option<Id>; 4]
+}
+
+#[derive(Clone, Debug)]
+struct LooseObject<P, S: IsScalar> {
+    pub id: Id,
+
+    pub shape: P,
+
+    // The bbox of the shape as of its last insertion or relocation.
+    pub bounds: BBox2D<S>,
+
+    pub quad: Id
+}
+
+/// A stable reference to an object in a LooseQuadtree, which stays valid until the object is
+/// removed, no matter how far it moves.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ObjectId(Id);
+
+/// A Loose Quadtree stores objects with an extent (anything with a bbox) rather than points, and
+/// is meant for objects which move around a lot, e.g. in the broad phase of collision detection.
+///
+/// In a regular quadtree, an object straddling the boundary between two quads has to be stored in
+/// their parent, so small objects near the center of the tree end up at the very top. Here, every
+/// quad accepts any object which fits into its loose bbox: its cell grown by the 'looseness' of
+/// the tree (2 by default, i.e. twice as wide and high). An object is stored in the deepest quad
+/// along the path of its center which it fits into, so it sits at a depth matching its size
+/// regardless of where it is.
+///
+/// Moving an object which still fits into the loose bbox of its quad only updates its bounds, so
+/// objects which move a little every frame are relocated in O(1) most of the time.
+pub struct LooseQuadtree<P: HasBounds<S> + Send + Sync, S: IsScalar = f32> {
+    quads: GenerationalArena<LooseQuad<S>>,
+    objects: GenerationalArena<LooseObject<P, S>>,
+    root_id: Id,
+    looseness: S
+}
+
+impl<P: HasBounds<S> + Send + Sync, S: IsScalar> LooseQuadtree<P, S> {
+
+    /// Constructs a new, empty tree over the given BBox, with quads twice the size of their cells.
+    pub fn new(bbox: &BBox2D<S>) -> Self {
+        Self::with_looseness(bbox, na::convert(2.0))
+    }
+
+    /// Constructs a new, empty tree over the given BBox, whose quads are 'looseness' times the size
+    /// of their cells. Looser quads hold objects deeper in the tree, but overlap more so queries
+    /// visit more of them. The looseness must be greater than 1.
+    pub fn with_looseness(bbox: &BBox2D<S>, looseness: S) -> Self {
+        assert!(looseness > S::one(), "looseness must be greater than 1");
+
+        let mut quads = GenerationalArena::new();
+        let root_id = quads.get_new_id();
+        quads.add_node(LooseQuad::new(root_id, *bbox, looseness, 0, None)).expect("could not add node!");
+
+        Self {
+            quads,
+            objects: GenerationalArena::new(),
+            root_id,
+            looseness
+        }
+    }
+
+    /// Returns the number of objects contained in this tree.
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Returns true if this tree contains no objects.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns true if the id refers to an object in the tree.
+    pub fn contains(&self, object: &ObjectId) -> bool {
+        self.objects.get_node(&object.0).is_some()
+    }
+
+    /// Returns the depth of the deepest quad in this tree, where the root is at depth 0.
+    pub fn depth(&self) -> usize {
+        let mut max_depth = 0;
+        let mut stack = vec![self.root_id];
+
+        while let Some(id) = stack.pop() {
+            let quad_ref = self.quad(&id);
+            let quad = quad_ref.read().unwrap();
+
+            max_depth = max_depth.max(quad.depth);
+            stack.extend(quad.children.iter().flatten());
+        }
+
+        max_depth
+    }
+
+    /// Inserts the object, returning its id. Objects whose center lies outside of the tree, or
+    /// which don't fit into the loose bbox of the root, are out of bounds.
+    pub fn insert(&mut self, shape: P) -> Result<ObjectId, InsertError> {
+        let bounds = shape.bounds();
+        let quad_id = self.place(&bounds).ok_or(InsertError::OutOfBounds)?;
+
+        let id = self.objects.get_new_id();
+        self.objects.add_node(LooseObject { id, shape, bounds, quad: quad_id }).expect("could not add node!");
+        self.quad(&quad_id).write().unwrap().objects.push(id);
+
+        Ok(ObjectId(id))
+    }
+
+    /// Removes the object from the tree, returning its shape. Quads left without any objects below
+    /// them are pruned.
+    pub fn remove(&mut self, object: &ObjectId) -> Option<P> {
+        let object_ref = self.objects.get_node(&object.0)?;
+        let quad_id = object_ref.read().unwrap().quad;
+        drop(object_ref);
+
+        self.quad(&quad_id).write().unwrap().objects.retain(|id| *id != object.0);
+        self.prune(&quad_id);
+
+        let object_ref = self.objects.get_node(&object.0).expect("could not find node");
+        self.objects.delete_node(&object.0).expect("could not delete node");
+        match Arc::try_unwrap(object_ref) {
+            Ok(node) => Some(node.into_inner().unwrap().shape),
+            Err(_) => panic!("node is still referenced")
+        }
+    }
+
+    /// Replaces the shape of the object, e.g. after it moved, returning its previous shape. The
+    /// object is left alone and an error is returned if the new shape is out of bounds, and
+    /// likewise if the object doesn't exist.
+    ///
+    /// The object only moves to another quad if it no longer fits into the loose bbox of its quad.
+    /// An object which shrinks or moves while staying within the bbox isn't pushed further down,
+    /// which is harmless since every quad may hold objects of any smaller size.
+    pub fn relocate(&mut self, object: &ObjectId, shape: P) -> Result<P, InsertError> {
+        let object_ref = self.objects.get_node(&object.0).ok_or(InsertError::OutOfBounds)?;
+        let bounds = shape.bounds();
+        let quad_id = object_ref.read().unwrap().quad;
+
+        let fits = self.quad(&quad_id).read().unwrap().loose.contains_bbox(&bounds);
+        if !fits {
+            let new_quad_id = self.place(&bounds).ok_or(InsertError::OutOfBounds)?;
+
+            self.quad(&quad_id).write().unwrap().objects.retain(|id| *id != object.0);
+            self.quad(&new_quad_id).write().unwrap().objects.push(object.0);
+            object_ref.write().unwrap().quad = new_quad_id;
+
+            // The old quad may be an ancestor of the new one, in which case it is still needed.
+            self.prune(&quad_id);
+        }
+
+        let mut node = object_ref.write().unwrap();
+        node.bounds = bounds;
+        Ok(core::mem::replace(&mut node.shape, shape))
+    }
+
+    /// Calls 'f' with the shape of the object, without cloning it.
+    pub fn get_with<R, F: FnOnce(&P) -> R>(&self, object: &ObjectId, f: F) -> Option<R> {
+        let object_ref = self.objects.get_node(&object.0)?;
+        let result = f(&object_ref.read().unwrap().shape);
+        Some(result)
+    }
+
+    /// Calls 'f' with every object whose bbox intersects the given BBox, along with its id.
+    pub fn find_intersecting_with<F: FnMut(&ObjectId, &P)>(&self, bbox: &BBox2D<S>, mut f: F) {
+        self._find_intersecting(bbox, &self.root_id, &mut f);
+    }
+
+    /// Calls 'f' with every pair of objects whose bboxes intersect, each pair once.
+    pub fn find_intersecting_pairs_with<F: FnMut(&ObjectId, &P, &ObjectId, &P)>(&self, mut f: F) {
+        let mut stack = vec![self.root_id];
+
+        while let Some(quad_id) = stack.pop() {
+            let quad_ref = self.quad(&quad_id);
+            let quad = quad_ref.read().unwrap();
+            stack.extend(quad.children.iter().flatten());
+
+            // The loose bboxes of neighbouring quads overlap, so an object may intersect objects
+            // anywhere around it. Every pair is found from both sides, and reported from one.
+            for a_id in &quad.objects {
+                let a_ref = self.object(a_id);
+                let a = a_ref.read().unwrap();
+
+                self._find_intersecting(&a.bounds, &self.root_id, &mut |b_id: &ObjectId, b: &P| {
+                    if (a_id.index, a_id.generation) < (b_id.0.index, b_id.0.generation) {
+                        f(&ObjectId(*a_id), &a.shape, b_id, b);
+                    }
+                });
+            }
+        }
+    }
+
+    /// Returns the deepest quad along the path of the center of 'bounds' whose loose bbox holds
+    /// them, creating it if needed, or None if they are out of bounds.
+    fn place(&mut self, bounds: &BBox2D<S>) -> Option<Id> {
+        let center = bounds.mid();
+        let mut quad_id = self.root_id;
+
+        {
+            let root_ref = self.quad(&quad_id);
+            let root = root_ref.read().unwrap();
+            if !root.cell.contains(&center) || !root.loose.contains_bbox(bounds) {
+                return None;
+            }
+        }
+
+        loop {
+            let quad_ref = self.quad(&quad_id);
+            let mut quad = quad_ref.write().unwrap();
+            if quad.depth == MAX_DEPTH {
+                return Some(quad_id);
+            }
+
+            let cells = quad.cell.subdivide(&quad.cell.mid());
+            let idx = cells.iter().position(|cell| cell.contains(&center)).expect("center is within the quad");
+
+            if !loosen(&cells[idx], self.looseness).contains_bbox(bounds) {
+                return Some(quad_id);
+            }
+
+            quad_id = match quad.children[idx] {
+                Some(child_id) => child_id,
+                None => {
+                    let child_id = self.quads.get_new_id();
+                    let child = LooseQuad::new(child_id, cells[idx], self.looseness, quad.depth + 1, Some(quad_id));
+                    self.quads.add_node(child).expect("could not add node!");
+                    quad.children[idx] = Some(child_id);
+                    child_id
+                }
+            };
+        }
+    }
+
+    /// Removes the quad if it holds no objects and has no children, and likewise its ancestors.
+    fn prune(&mut self, quad_id: &Id) {
+        let mut quad_id = *quad_id;
+
+        while quad_id != self.root_id {
+            let parent_id = {
+                let quad_ref = self.quad(&quad_id);
+                let quad = quad_ref.read().unwrap();
+                if !quad.objects.is_empty() || quad.children.iter().any(|child| child.is_some()) {
+                    return;
+                }
+                quad.parent.expect("only the root has no parent")
+            };
+
+            for child in self.quad(&parent_id).write().unwrap().children.iter_mut() {
+                if *child == Some(quad_id) {
+                    *child = None;
+                }
+            }
+
+            self.quads.delete_node(&quad_id).expect("could not delete node");
+            quad_id = parent_id;
+        }
+    }
+
+    fn _find_intersecting<F: FnMut(&ObjectId, &P)>(&self, bbox: &BBox2D<S>, quad_id: &Id, f: &mut F) {
+        let quad_ref = self.quad(quad_id);
+        let quad = quad_ref.read().unwrap();
+
+        if !quad.loose.intersects(bbox) {
+            return;
+        }
+
+        for object_id in &quad.objects {
+            let object_ref = self.object(object_id);
+            let object = object_ref.read().unwrap();
+            if object.bounds.intersects(bbox) {
+                f(&ObjectId(object.id), &object.shape);
+            }
+        }
+
+        for child_id in quad.children.iter().flatten() {
+            self._find_intersecting(bbox, child_id, f);
+        }
+    }
+
+    fn quad(&self, quad_id: &Id) -> SharedRef<LooseQuad<S>> {
+        self.quads.get_node(quad_id).expect("could not find node")
+    }
+
+    fn object(&self, object_id: &Id) -> SharedRef<LooseObject<P, S>> {
+        self.objects.get_node(object_id).expect("could not find node")
+    }
+}
+
+impl<P: HasBounds<S> + IsPayload, S: IsScalar> LooseQuadtree<P, S> {
+
+    /// Returns the shape of the object.
+    pub fn get(&self, object: &ObjectId) -> Option<P> {
+        self.get_with(object, |shape| shape.clone())
+    }
+
+    /// Returns every object whose bbox intersects the given BBox, along with its id.
+    pub fn find_intersecting(&self, bbox: &BBox2D<S>) -> Vec<(ObjectId, P)> {
+        let mut result = vec![];
+        self.find_intersecting_with(bbox, |id, shape| result.push((*id, shape.clone())));
+        result
+    }
+
+    /// Returns the ids of every pair of objects whose bboxes intersect, each pair once.
+    pub fn find_intersecting_pairs(&self) -> Vec<(ObjectId, ObjectId)> {
+        let mut result = vec![];
+        self.find_intersecting_pairs_with(|a, _, b, _| result.push((*a, *b)));
+        result
+    }
+}
+
+/// Grows the cell by 'looseness' around its midpoint.
+fn loosen<S: IsScalar>(cell: &BBox2D<S>, looseness: S) -> BBox2D<S> {
+    let margin = (cell.max - cell.min) * (looseness - S::one()) / na::convert::<f64, S>(2.0);
+    BBox2D { min: cell.min - margin, max: cell.max + margin }
+}
+
+impl<S: IsScalar> LooseQuad<S> {
+    pub fn new(id: Id, cell: BBox2D<S>, looseness: S, depth: usize, parent: Option<Id>) -> Self {
+        Self {
+            id,
+            cell,
+            loose: loosen(&cell, looseness),
+            depth,
+            parent,
+            objects: vec![],
+            children: [None; 4]
+        }
+    }
+}
+
+impl<S: IsScalar> HasId for LooseQuad<S> {
+    type Id = Id;
+    fn get_id(&self) -> Self::Id {
+        self.id
+    }
+}
+
+impl<P: Send + Sync, S: IsScalar> HasId for LooseObject<P, S> {
+    type Id = Id;
+    fn get_id(&self) -> Self::Id {
+        self.id
+    }
+}
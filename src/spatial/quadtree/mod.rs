@@ -1,11 +1,26 @@
 pub mod prelude;
 pub mod point_quadtree;
+pub mod frozen_quadtree;
+pub mod geo_quadtree;
+pub mod linear_quadtree;
+pub mod loose_quadtree;
+#[cfg(feature = "std")]
+pub mod packed;
+pub mod writer;
+
+mod dual_tree;
 
 #[allow(non_snake_case)]
 #[cfg(test)]
 mod tests {
+    use crate::metrics::Metrics;
+    use crate::random::*;
     use crate::spatial::quadtree::prelude::*;
     use crate::spatial::quadtree::point_quadtree::*;
+    use crate::spatial::quadtree::linear_quadtree::*;
+    use crate::spatial::quadtree::loose_quadtree::*;
+    use crate::spatial::quadtree::geo_quadtree::*;
+    use crate::spatial::quadtree::writer::*;
 
     #[test]
     fn test_BBox2D() {
@@ -28,6 +43,112 @@ mod tests {
         assert!(bbox.intersects(&se));
         assert!(bbox.intersects(&ne));
         assert!(bbox.intersects(&nw));
+
+        assert_eq!(bbox.distance_to_point(&Vec2::default()), 0.0);
+        assert_eq!(bbox.distance_to_point(&Vec2::from([13.0, 14.0])), 5.0);
+        assert_eq!(bbox.distance_to_point(&Vec2::from([0.0, -12.0])), 2.0);
+
+        assert_eq!(bbox.intersection(&ne), Some(ne));
+        assert_eq!(ne.intersection(&sw), Some(BBox2D { min: Vec2::default(), max: Vec2::default() }));
+        assert_eq!(
+            bbox.intersection(&BBox2D { min: Vec2::from([5.0, -20.0]), max: Vec2::from([30.0, 0.0]) }),
+            Some(BBox2D { min: Vec2::from([5.0, -10.0]), max: Vec2::from([10.0, 0.0]) })
+        );
+        assert!(sw.intersection(&BBox2D { min: Vec2::from([1.0, 1.0]), max: Vec2::from([2.0, 2.0]) }).is_none());
+
+        let mut grown = sw;
+        grown.expand(&Vec2::from([-20.0, 5.0]));
+        assert_eq!(grown, BBox2D { min: Vec2::from([-20.0, -10.0]), max: Vec2::from([0.0, 5.0]) });
+        grown.expand(&Vec2::from([-15.0, 0.0]));
+        assert_eq!(grown.area(), 300.0);
+        assert!(grown.contains_bbox(&sw));
+        assert_eq!(grown.union(&ne).area(), 600.0);
+
+        let points = [Vec2::from([3.0, -1.0]), Vec2::from([-2.0, 4.0]), Vec2::from([0.5, 0.5])];
+        let enclosing = BBox2D::from_points(&points).unwrap();
+        assert_eq!(enclosing.min, Vec2::from([-2.0, -1.0]));
+        assert!(enclosing.max.x > 3.0 && enclosing.max.y > 4.0);
+        assert!((enclosing.max - Vec2::from([3.0, 4.0])).norm() < 1e-5);
+        assert!(BBox2D::<f32>::from_points(&[]).is_none());
+    }
+
+    #[test]
+    fn test_BBox2D_geometry() {
+        let bbox = |min: [f32; 2], max: [f32; 2]| BBox2D { min: Vec2::from(min), max: Vec2::from(max) };
+        let a = bbox([0.0, 0.0], [4.0, 2.0]);
+
+        // --
+        // area
+        assert_eq!(a.area(), 8.0);
+        assert_eq!(bbox([1.0, 1.0], [1.0, 5.0]).area(), 0.0);
+        assert_eq!(BBox2D::<f32>::default().area(), 0.0);
+
+        // --
+        // union
+        assert_eq!(a.union(&a), a);
+        assert_eq!(a.union(&bbox([1.0, 0.5], [2.0, 1.0])), a);
+        assert_eq!(a.union(&bbox([6.0, -3.0], [7.0, -1.0])), bbox([0.0, -3.0], [7.0, 2.0]));
+        assert_eq!(bbox([6.0, -3.0], [7.0, -1.0]).union(&a), bbox([0.0, -3.0], [7.0, 2.0]));
+
+        // --
+        // intersection
+        assert_eq!(a.intersection(&a), Some(a));
+        assert_eq!(a.intersection(&bbox([2.0, 1.0], [9.0, 9.0])), Some(bbox([2.0, 1.0], [4.0, 2.0])));
+        assert_eq!(a.intersection(&bbox([1.0, 0.5], [2.0, 1.0])), Some(bbox([1.0, 0.5], [2.0, 1.0])));
+
+        // Boxes which only share an edge or a corner overlap in an empty box.
+        assert_eq!(a.intersection(&bbox([4.0, 0.0], [6.0, 2.0])), Some(bbox([4.0, 0.0], [4.0, 2.0])));
+        assert_eq!(a.intersection(&bbox([4.0, 2.0], [6.0, 3.0])), Some(bbox([4.0, 2.0], [4.0, 2.0])));
+        assert!(a.intersection(&bbox([4.5, 0.0], [6.0, 2.0])).is_none());
+        assert!(a.intersection(&bbox([0.0, -2.0], [4.0, -0.5])).is_none());
+
+        // --
+        // distance_to_bbox
+        assert_eq!(a.distance_to_bbox(&a), 0.0);
+        assert_eq!(a.distance_to_bbox(&bbox([1.0, 1.0], [9.0, 9.0])), 0.0);
+        assert_eq!(a.distance_to_bbox(&bbox([4.0, 2.0], [5.0, 5.0])), 0.0);
+        assert_eq!(a.distance_to_bbox(&bbox([6.0, 0.5], [8.0, 1.0])), 2.0);
+        assert_eq!(a.distance_to_bbox(&bbox([1.0, -5.0], [2.0, -3.0])), 3.0);
+        assert_eq!(a.distance_to_bbox(&bbox([7.0, 6.0], [8.0, 8.0])), 5.0);
+        assert_eq!(bbox([7.0, 6.0], [8.0, 8.0]).distance_to_bbox(&a), 5.0);
+
+        // --
+        // expand
+        let mut grown = a;
+        grown.expand(&Vec2::from([1.0, 1.0]));
+        assert_eq!(grown, a);
+
+        // A point grown onto the max bounds lies on them, but isn't contained.
+        grown.expand(&Vec2::from([5.0, 2.0]));
+        assert_eq!(grown, bbox([0.0, 0.0], [5.0, 2.0]));
+        assert!(!grown.contains(&Vec2::from([5.0, 2.0])));
+
+        grown.expand(&Vec2::from([-1.0, -1.0]));
+        assert_eq!(grown, bbox([-1.0, -1.0], [5.0, 2.0]));
+        assert!(grown.contains(&Vec2::from([-1.0, -1.0])));
+
+        // --
+        // from_points
+        let points = [
+            Vec2::from([0.0, 0.0]),
+            Vec2::from([-3.5, 1e6]),
+            Vec2::from([2.0, -7.0]),
+            Vec2::from([1e-9, 3.0])
+        ];
+        let enclosing = BBox2D::from_points(&points).unwrap();
+        assert!(points.iter().all(|p| enclosing.contains(p)));
+        assert_eq!(enclosing.min, Vec2::from([-3.5, -7.0]));
+
+        // A single point, even one at the origin, makes a tiny box which contains it.
+        for p in [Vec2::from([0.0, 0.0]), Vec2::from([-2.0, -2.0]), Vec2::from([f32::MAX / 2.0, 1e30])] {
+            let single = BBox2D::from_points(&[p]).unwrap();
+            assert!(single.contains(&p));
+            assert_eq!(single.min, p);
+        }
+
+        let precise = [Vec2::new(1.0, -1.0), Vec2::new(1e15, 1e-300)];
+        let enclosing = BBox2D::<f64>::from_points(&precise).unwrap();
+        assert!(precise.iter().all(|p| enclosing.contains(p)));
     }
 
     #[test]
@@ -44,18 +165,20 @@ mod tests {
         let mut tree = PointQuadtree::<i32>::new(&bbox);
         assert_eq!(tree.len(), 0);
 
-        assert!(tree.insert(&p1, 12));
+        assert!(tree.insert(&p1, 12).is_ok());
         assert_eq!(tree.len(), 1);
 
-        assert!(!tree.insert(&p1, 14));
+        assert_eq!(tree.insert(&p1, 14), Err(InsertError::DuplicatePoint));
+        assert_eq!(tree.insert(&Vec2::from([20.0, 0.0]), 14), Err(InsertError::OutOfBounds));
+        assert_eq!(tree.insert(&bbox.max, 14), Err(InsertError::OutOfBounds));
         assert_eq!(tree.len(), 1);
 
         // Now let's try to find 'p1'
         let (_, item) = tree.find(&p1).unwrap();
         assert_eq!(item, 12);
 
-        assert!(tree.insert(&p2, -1));
-        assert!(tree.insert(&p3, 4));
+        assert!(tree.insert(&p2, -1).is_ok());
+        assert!(tree.insert(&p3, 4).is_ok());
 
         // Now let's try a query points in a region
         let region = BBox2D {
@@ -67,4 +190,1518 @@ mod tests {
         assert_eq!(items[0].1, 12);
         assert_eq!(items[1].1, -1);
     }
+
+    #[test]
+    fn test_PointQuadtree_remove() {
+        let bbox = BBox2D {
+            min: Vec2::from([-10.0, -10.0]),
+            max: Vec2::from([10.0, 10.0])
+        };
+
+        let p1 = Vec2::from([0.0, 0.0]);
+        let p2 = Vec2::from([1.0, 4.0]);
+        let p3 = Vec2::from([-2.0, 3.0]);
+        let p4 = Vec2::from([2.0, 5.0]);
+
+        let mut tree = PointQuadtree::<i32>::new(&bbox);
+        assert!(tree.remove(&p1).is_none());
+
+        assert!(tree.insert(&p1, 1).is_ok());
+        assert!(tree.insert(&p2, 2).is_ok());
+        assert!(tree.insert(&p3, 3).is_ok());
+        assert!(tree.insert(&p4, 4).is_ok());
+        assert_eq!(tree.len(), 4);
+
+        // Removing the root point must leave the points in its subtrees reachable.
+        assert_eq!(tree.remove(&p1), Some(1));
+        assert_eq!(tree.len(), 3);
+        assert!(tree.find(&p1).is_none());
+        assert_eq!(tree.find(&p2).unwrap().1, 2);
+        assert_eq!(tree.find(&p3).unwrap().1, 3);
+        assert_eq!(tree.find(&p4).unwrap().1, 4);
+
+        assert!(tree.remove(&p1).is_none());
+        assert_eq!(tree.len(), 3);
+
+        assert_eq!(tree.remove(&p4), Some(4));
+        assert_eq!(tree.remove(&p3), Some(3));
+        assert_eq!(tree.remove(&p2), Some(2));
+        assert!(tree.is_empty());
+
+        // The tree should be fully usable after being emptied.
+        assert!(tree.insert(&p4, 5).is_ok());
+        assert_eq!(tree.find(&p4).unwrap().1, 5);
+    }
+
+    #[test]
+    fn test_PointQuadtree_remove_within() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([64.0, 64.0])
+        };
+
+        let points: Vec<Node<usize>> = (0..1024)
+            .map(|i| (Vec2::from([(i % 32) as f32 * 2.0, (i / 32) as f32 * 2.0]), i))
+            .collect();
+
+        for mut tree in [
+            PointQuadtree::from_points(&bbox, points.clone()),
+            PointQuadtree::with_config(&bbox, QuadtreeConfig { bucket_capacity: 1, max_depth: 8 }),
+        ] {
+            if tree.is_empty() {
+                points.iter().for_each(|(p, i)| assert!(tree.insert(p, *i).is_ok()));
+            }
+            let nodes = tree.node_count();
+
+            // A chunk aligned with the quads, and one cutting across them.
+            let chunk = BBox2D { min: Vec2::from([0.0, 0.0]), max: Vec2::from([32.0, 32.0]) };
+            let mut removed: Vec<usize> = tree.remove_within(&chunk).into_iter().map(|(_, i)| i).collect();
+            removed.sort();
+            let expected: Vec<usize> = points.iter().filter(|(p, _)| chunk.contains(p)).map(|(_, i)| *i).collect();
+            assert_eq!(removed, expected);
+            assert_eq!(tree.len(), 1024 - 256);
+            assert!(tree.node_count() < nodes);
+
+            let strip = BBox2D { min: Vec2::from([5.0, -10.0]), max: Vec2::from([9.5, 100.0]) };
+            assert_eq!(tree.remove_within(&strip).len(), 2 * 16);
+            assert!(tree.remove_within(&strip).is_empty());
+            assert_eq!(tree.len(), 1024 - 256 - 32);
+
+            let mut remaining: Vec<usize> = tree.find_within(&bbox).into_iter().map(|(_, i)| i).collect();
+            remaining.sort();
+            let expected: Vec<usize> = points.iter()
+                .filter(|(p, _)| !chunk.contains(p) && !strip.contains(p))
+                .map(|(_, i)| *i)
+                .collect();
+            assert_eq!(remaining, expected);
+
+            // Emptied quads get collapsed all the way up.
+            assert_eq!(tree.remove_within(&bbox).len(), 1024 - 256 - 32);
+            assert!(tree.is_empty());
+            assert_eq!(tree.node_count(), 1);
+            assert!(tree.insert(&Vec2::from([1.0, 1.0]), 0).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_PointQuadtree_nearest() {
+        let bbox = BBox2D {
+            min: Vec2::from([-10.0, -10.0]),
+            max: Vec2::from([10.0, 10.0])
+        };
+
+        let mut tree = PointQuadtree::<i32>::new(&bbox);
+        assert!(tree.nearest(&Vec2::default()).is_none());
+
+        assert!(tree.insert(&Vec2::from([0.0, 0.0]), 1).is_ok());
+        assert!(tree.insert(&Vec2::from([5.0, 5.0]), 2).is_ok());
+        assert!(tree.insert(&Vec2::from([-6.0, 2.0]), 3).is_ok());
+        assert!(tree.insert(&Vec2::from([4.0, -7.0]), 4).is_ok());
+        assert!(tree.insert(&Vec2::from([6.0, 4.0]), 5).is_ok());
+
+        assert_eq!(tree.nearest(&Vec2::from([0.5, 0.5])).unwrap().1, 1);
+        assert_eq!(tree.nearest(&Vec2::from([6.0, 3.5])).unwrap().1, 5);
+        assert_eq!(tree.nearest(&Vec2::from([-9.0, 9.0])).unwrap().1, 3);
+        assert_eq!(tree.nearest(&Vec2::from([3.0, -9.0])).unwrap().1, 4);
+
+        // Points outside of the tree's bbox are still answered.
+        assert_eq!(tree.nearest(&Vec2::from([50.0, 50.0])).unwrap().1, 2);
+    }
+
+    #[test]
+    fn test_PointQuadtree_knn() {
+        let bbox = BBox2D {
+            min: Vec2::from([-10.0, -10.0]),
+            max: Vec2::from([10.0, 10.0])
+        };
+
+        let mut tree = PointQuadtree::<i32>::new(&bbox);
+        assert!(tree.knn(&Vec2::default(), 3).is_empty());
+
+        assert!(tree.insert(&Vec2::from([0.0, 0.0]), 1).is_ok());
+        assert!(tree.insert(&Vec2::from([5.0, 5.0]), 2).is_ok());
+        assert!(tree.insert(&Vec2::from([-6.0, 2.0]), 3).is_ok());
+        assert!(tree.insert(&Vec2::from([4.0, -7.0]), 4).is_ok());
+        assert!(tree.insert(&Vec2::from([1.0, 1.0]), 5).is_ok());
+
+        assert!(tree.knn(&Vec2::default(), 0).is_empty());
+
+        let result = tree.knn(&Vec2::from([0.0, 0.0]), 3);
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].0.1, 1);
+        assert_eq!(result[0].1, 0.0);
+        assert_eq!(result[1].0.1, 5);
+        assert_eq!(result[2].0.1, 3);
+
+        // Asking for more points than exist returns all of them.
+        let result = tree.knn(&Vec2::from([0.0, 0.0]), 10);
+        assert_eq!(result.len(), 5);
+        assert!(result.windows(2).all(|w| w[0].1 <= w[1].1));
+    }
+
+    #[test]
+    fn test_PointQuadtree_find_within_radius() {
+        let bbox = BBox2D {
+            min: Vec2::from([-10.0, -10.0]),
+            max: Vec2::from([10.0, 10.0])
+        };
+
+        let mut tree = PointQuadtree::<i32>::new(&bbox);
+        assert!(tree.find_within_radius(&Vec2::default(), 5.0).is_empty());
+
+        assert!(tree.insert(&Vec2::from([0.0, 0.0]), 1).is_ok());
+        assert!(tree.insert(&Vec2::from([3.0, 4.0]), 2).is_ok());
+        assert!(tree.insert(&Vec2::from([4.0, 4.0]), 3).is_ok());
+        assert!(tree.insert(&Vec2::from([-8.0, -8.0]), 4).is_ok());
+
+        let mut items: Vec<i32> = tree.find_within_radius(&Vec2::default(), 5.0)
+            .into_iter()
+            .map(|(_, item)| item)
+            .collect();
+        items.sort();
+        assert_eq!(items, vec![1, 2]);
+
+        let items = tree.find_within_radius(&Vec2::from([-9.0, -9.0]), 2.0);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].1, 4);
+
+        assert!(tree.find_within_radius(&Vec2::from([-9.0, 9.0]), 1.0).is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_PointQuadtree_serde() {
+        let bbox = BBox2D {
+            min: Vec2::from([-10.0, -10.0]),
+            max: Vec2::from([10.0, 10.0])
+        };
+
+        let mut tree = PointQuadtree::<i32>::new(&bbox);
+        assert!(tree.insert(&Vec2::from([0.0, 0.0]), 1).is_ok());
+        assert!(tree.insert(&Vec2::from([1.0, 4.0]), 2).is_ok());
+        assert!(tree.insert(&Vec2::from([-2.0, 3.0]), 3).is_ok());
+
+        let json = serde_json::to_string(&tree).unwrap();
+        let mut restored: PointQuadtree<i32> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.len(), 3);
+        assert_eq!(restored.find(&Vec2::from([1.0, 4.0])).unwrap().1, 2);
+        assert_eq!(restored.find(&Vec2::from([-2.0, 3.0])).unwrap().1, 3);
+
+        // The restored tree must remain fully mutable.
+        assert!(restored.insert(&Vec2::from([5.0, 5.0]), 4).is_ok());
+        assert_eq!(restored.remove(&Vec2::from([0.0, 0.0])), Some(1));
+        assert_eq!(restored.len(), 3);
+    }
+
+    #[test]
+    fn test_PointQuadtree_with_config() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([10.0, 10.0])
+        };
+
+        let config = QuadtreeConfig { bucket_capacity: 4, max_depth: 3 };
+        let mut tree = PointQuadtree::<usize>::with_config(&bbox, config);
+
+        let points: Vec<Vec2> = (0..100)
+            .map(|i| Vec2::from([(i % 10) as f32, (i / 10) as f32]))
+            .collect();
+
+        for (i, p) in points.iter().enumerate() {
+            assert!(tree.insert(p, i).is_ok());
+        }
+        assert_eq!(tree.insert(&points[42], 0), Err(InsertError::DuplicatePoint));
+        assert_eq!(tree.len(), 100);
+
+        for (i, p) in points.iter().enumerate() {
+            assert_eq!(tree.find(p).unwrap().1, i);
+        }
+
+        let region = BBox2D {
+            min: Vec2::from([2.0, 2.0]),
+            max: Vec2::from([5.0, 4.0])
+        };
+        assert_eq!(tree.find_within(&region).len(), 6);
+        assert_eq!(tree.nearest(&Vec2::from([3.2, 6.9])).unwrap().1, 73);
+
+        for (i, p) in points.iter().enumerate() {
+            assert_eq!(tree.remove(p), Some(i));
+        }
+        assert!(tree.is_empty());
+
+        // Clustered points end up sharing a bucket once the max depth is reached.
+        let config = QuadtreeConfig { bucket_capacity: 1, max_depth: 2 };
+        let mut tree = PointQuadtree::<usize>::with_config(&bbox, config);
+        for i in 0..10 {
+            assert!(tree.insert(&Vec2::from([0.001 * i as f32, 0.0]), i).is_ok());
+        }
+        assert_eq!(tree.find_within(&bbox).len(), 10);
+        assert_eq!(tree.find(&Vec2::from([0.001 * 5.0, 0.0])).unwrap().1, 5);
+
+        // Points which are as close as floats allow stop subdividing at the max depth.
+        let config = QuadtreeConfig { bucket_capacity: 1, max_depth: 16 };
+        let mut tree = PointQuadtree::<usize>::with_config(&bbox, config);
+        let mut x = 1.0f32;
+        for i in 0..100 {
+            assert!(tree.insert(&Vec2::from([x, x]), i).is_ok());
+            x = f32::from_bits(x.to_bits() + 1);
+        }
+        assert_eq!(tree.depth(), 16);
+        assert_eq!(tree.find(&Vec2::from([1.0, 1.0])).unwrap().1, 0);
+        assert_eq!(tree.insert(&Vec2::from([1.0, 1.0]), 100), Err(InsertError::DuplicatePoint));
+    }
+
+    #[test]
+    fn test_PointQuadtree_insert_errors() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([10.0, 10.0])
+        };
+
+        // --
+        // The min bounds are part of the tree, the max bounds aren't.
+        let mut tree = PointQuadtree::<usize>::with_config(&bbox, QuadtreeConfig { bucket_capacity: 2, max_depth: 4 });
+        for p in [[-0.1, 5.0], [5.0, -0.1], [10.0, 5.0], [5.0, 10.0], [10.0, 10.0], [f32::NAN, 1.0], [20.0, -20.0]] {
+            assert_eq!(tree.insert(&Vec2::from(p), 0), Err(InsertError::OutOfBounds));
+        }
+        assert!(tree.is_empty());
+
+        assert!(tree.insert(&bbox.min, 0).is_ok());
+        assert!(tree.insert(&Vec2::from([9.99, 9.99]), 1).is_ok());
+        assert_eq!(tree.len(), 2);
+
+        // --
+        // Duplicates are rejected from buckets and from subdivided quads alike, and leave the
+        // stored payload alone.
+        assert_eq!(tree.insert(&bbox.min, 2), Err(InsertError::DuplicatePoint));
+        for i in 0..20 {
+            assert!(tree.insert(&Vec2::from([i as f32 * 0.45, 3.0]), 10 + i).is_ok());
+        }
+        assert!(tree.depth() > 0);
+        assert_eq!(tree.insert(&Vec2::from([4.5, 3.0]), 99), Err(InsertError::DuplicatePoint));
+        assert_eq!(tree.insert(&Vec2::from([9.99, 9.99]), 99), Err(InsertError::DuplicatePoint));
+        assert_eq!(tree.find(&Vec2::from([4.5, 3.0])).unwrap().1, 20);
+        assert_eq!(tree.len(), 22);
+
+        // --
+        // A quad at the max depth keeps every point in its bucket instead of subdividing.
+        let config = QuadtreeConfig { bucket_capacity: 2, max_depth: 1 };
+        let mut tree = PointQuadtree::<usize>::with_config(&bbox, config);
+        for i in 0..50 {
+            assert!(tree.insert(&Vec2::from([1.0 + i as f32 * 0.01, 1.0]), i).is_ok());
+        }
+        assert_eq!(tree.depth(), 1);
+
+        let mut quads = vec![];
+        tree.visit_quads(|bbox, depth| quads.push((*bbox, depth)));
+        assert_eq!(quads.len(), 5);
+        assert_eq!(tree.find_within(&quads[1].0).len(), 50);
+        assert_eq!(tree.insert(&Vec2::from([1.25, 1.0]), 50), Err(InsertError::DuplicatePoint));
+        assert!((0..50).all(|i| tree.find(&Vec2::from([1.0 + i as f32 * 0.01, 1.0])).unwrap().1 == i));
+
+        // --
+        // Trees built with 'new' stop subdividing at a finite depth, even when every point lands
+        // in the same corner of the previous one.
+        let mut tree = PointQuadtree::<usize>::new(&bbox);
+        let mut x = 5.0f32;
+        for i in 0..100 {
+            assert!(tree.insert(&Vec2::from([x, x]), i).is_ok());
+            x = f32::from_bits(x.to_bits() + 1);
+        }
+        assert_eq!(tree.depth(), 32);
+        assert_eq!(tree.len(), 100);
+        assert_eq!(tree.find(&Vec2::from([5.0, 5.0])).unwrap().1, 0);
+    }
+
+    #[test]
+    fn test_PointQuadtree_from_points() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([10.0, 10.0])
+        };
+
+        let mut points: Vec<(Vec2, usize)> = (0..100)
+            .map(|i| (Vec2::from([(i % 10) as f32, (i / 10) as f32]), i))
+            .collect();
+
+        // Repeated and out of bounds points are skipped.
+        points.push((Vec2::from([4.0, 2.0]), 1000));
+        points.push((Vec2::from([40.0, 2.0]), 1001));
+
+        let mut tree = PointQuadtree::from_points(&bbox, points);
+        assert_eq!(tree.len(), 100);
+        assert_eq!(tree.find(&Vec2::from([4.0, 2.0])).unwrap().1, 24);
+
+        for i in 0..100 {
+            assert_eq!(tree.find(&Vec2::from([(i % 10) as f32, (i / 10) as f32])).unwrap().1, i);
+        }
+
+        let region = BBox2D {
+            min: Vec2::from([2.0, 2.0]),
+            max: Vec2::from([5.0, 4.0])
+        };
+        assert_eq!(tree.find_within(&region).len(), 6);
+        assert_eq!(tree.nearest(&Vec2::from([3.2, 6.9])).unwrap().1, 73);
+
+        // The tree should remain fully mutable.
+        assert_eq!(tree.insert(&Vec2::from([3.0, 3.0]), 0), Err(InsertError::DuplicatePoint));
+        assert!(tree.insert(&Vec2::from([3.5, 3.5]), 100).is_ok());
+        assert_eq!(tree.remove(&Vec2::from([0.0, 0.0])), Some(0));
+        assert_eq!(tree.len(), 100);
+
+        let tree = PointQuadtree::<usize>::from_points(&bbox, vec![]);
+        assert!(tree.is_empty());
+        assert!(tree.nearest(&Vec2::default()).is_none());
+    }
+
+    #[test]
+    fn test_PointQuadtree_visitors() {
+        let bbox = BBox2D {
+            min: Vec2::from([-10.0, -10.0]),
+            max: Vec2::from([10.0, 10.0])
+        };
+
+        // Payloads which can't be cloned can still be queried.
+        let mut tree = PointQuadtree::<Box<dyn Fn() -> i32 + Send + Sync>>::new(&bbox);
+        assert!(tree.insert(&Vec2::from([0.0, 0.0]), Box::new(|| 1)).is_ok());
+        assert!(tree.insert(&Vec2::from([3.0, 4.0]), Box::new(|| 2)).is_ok());
+        assert!(tree.insert(&Vec2::from([-8.0, -8.0]), Box::new(|| 3)).is_ok());
+
+        assert_eq!(tree.find_with(&Vec2::from([3.0, 4.0]), |_, f| f()), Some(2));
+        assert!(tree.find_with(&Vec2::from([4.0, 3.0]), |_, f| f()).is_none());
+
+        let region = BBox2D {
+            min: Vec2::from([-1.0, -1.0]),
+            max: Vec2::from([10.0, 10.0])
+        };
+        let mut sum = 0;
+        tree.find_within_with(&region, |_, f| sum += f());
+        assert_eq!(sum, 3);
+
+        let mut points = vec![];
+        tree.find_within_radius_with(&Vec2::from([-9.0, -9.0]), 2.0, |p, _| points.push(*p));
+        assert_eq!(points, vec![Vec2::from([-8.0, -8.0])]);
+    }
+
+    #[test]
+    fn test_PointQuadtree_find_pairs_within() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([100.0, 100.0])
+        };
+
+        let mut points = vec![];
+        let mut state: u32 = 99;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            ((state >> 16) % 10000) as f32 / 100.0
+        };
+        for _ in 0..300 {
+            points.push(Vec2::from([next(), next()]));
+        }
+
+        // A tight cluster puts plenty of points in the buckets of subdivided quads.
+        for i in 0..20 {
+            points.push(Vec2::from([50.0 + i as f32 * 0.01, 50.0]));
+        }
+
+        let config = QuadtreeConfig {
+            bucket_capacity: 4,
+            max_depth: 6
+        };
+        let trees = [PointQuadtree::<usize>::new(&bbox), PointQuadtree::<usize>::with_config(&bbox, config)];
+
+        for mut tree in trees {
+            let mut stored = vec![];
+            for p in &points {
+                if tree.insert(p, stored.len()).is_ok() {
+                    stored.push(*p);
+                }
+            }
+
+            for d in [0.0, 0.05, 1.5, 7.0] {
+                let mut expected = vec![];
+                for i in 0..stored.len() {
+                    for j in i + 1..stored.len() {
+                        if (stored[i] - stored[j]).norm() <= d {
+                            expected.push((i, j));
+                        }
+                    }
+                }
+
+                let mut found: Vec<(usize, usize)> = tree.find_pairs_within(d).into_iter()
+                    .map(|((p, i), (q, j))| {
+                        assert_eq!((stored[i], stored[j]), (p, q));
+                        (i.min(j), i.max(j))
+                    })
+                    .collect();
+                found.sort();
+
+                assert_eq!(found, expected);
+            }
+        }
+
+        let empty = PointQuadtree::<usize>::new(&bbox);
+        assert!(empty.find_pairs_within(10.0).is_empty());
+    }
+
+    #[test]
+    fn test_PointQuadtree_query_filtered() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([100.0, 100.0])
+        };
+        let config = QuadtreeConfig {
+            bucket_capacity: 4,
+            max_depth: 8
+        };
+        let mut tree = PointQuadtree::<usize>::with_config(&bbox, config);
+
+        let mut stored = vec![];
+        let mut state: u32 = 3;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            ((state >> 16) % 10000) as f32 / 100.0
+        };
+        for _ in 0..500 {
+            let p = Vec2::from([next(), next()]);
+            if tree.insert(&p, stored.len()).is_ok() {
+                stored.push(p);
+            }
+        }
+
+        // An annulus around (30, 30) of odd payloads: quads entirely inside the hole or outside the
+        // ring can be skipped.
+        let center = Vec2::from([30.0, 30.0]);
+        let (inner, outer) = (5.0, 10.0);
+        let visited = std::cell::Cell::new(0);
+
+        let accept_bbox = |b: &BBox2D| {
+            visited.set(visited.get() + 1);
+            let furthest = (b.min - center).abs().sup(&(b.max - center).abs()).norm();
+            b.distance_to_point(&center) <= outer && furthest >= inner
+        };
+        let accept_point = |p: &Vec2<f32>, payload: &usize| {
+            let d = (p - center).norm();
+            inner <= d && d <= outer && payload % 2 == 1
+        };
+
+        let mut found: Vec<usize> = tree.query_filtered(accept_bbox, accept_point).into_iter()
+            .map(|(p, i)| {
+                assert_eq!(stored[i], p);
+                i
+            })
+            .collect();
+        found.sort();
+
+        let expected: Vec<usize> = (0..stored.len()).filter(|&i| accept_point(&stored[i], &i)).collect();
+        assert!(!expected.is_empty());
+        assert_eq!(found, expected);
+        assert!(visited.get() < tree.node_count());
+
+        // Accepting every bbox and point returns the whole tree.
+        assert_eq!(tree.query_filtered(|_| true, |_, _| true).len(), stored.len());
+        assert!(tree.query_filtered(|_| false, |_, _| true).is_empty());
+    }
+
+    #[test]
+    fn test_PointQuadtree_find_within_polygon() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([100.0, 100.0])
+        };
+        let config = QuadtreeConfig {
+            bucket_capacity: 4,
+            max_depth: 8
+        };
+        let mut tree = PointQuadtree::<usize>::with_config(&bbox, config);
+
+        let mut stored = vec![];
+        let mut state: u32 = 17;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            ((state >> 16) % 10000) as f32 / 100.0
+        };
+        for _ in 0..1000 {
+            let p = Vec2::from([next(), next()]);
+            if tree.insert(&p, stored.len()).is_ok() {
+                stored.push(p);
+            }
+        }
+
+        // An L-shaped polygon, a triangle, and a bow tie which crosses itself.
+        let polygons: Vec<Vec<Vec2>> = vec![
+            vec![[10.0, 10.0], [80.0, 10.0], [80.0, 30.0], [30.0, 30.0], [30.0, 90.0], [10.0, 90.0]],
+            vec![[50.0, 40.0], [95.0, 60.0], [60.0, 95.0]],
+            vec![[0.0, 0.0], [100.0, 100.0], [100.0, 0.0], [0.0, 100.0]],
+        ].into_iter().map(|polygon| polygon.into_iter().map(Vec2::from).collect()).collect();
+
+        for polygon in &polygons {
+            let mut found: Vec<usize> = tree.find_within_polygon(polygon).into_iter().map(|(_, i)| i).collect();
+            found.sort();
+
+            let expected: Vec<usize> = (0..stored.len()).filter(|&i| polygon_contains(polygon, &stored[i])).collect();
+            assert!(!expected.is_empty());
+            assert_eq!(found, expected);
+        }
+
+        let quad = BBox2D { min: Vec2::from([12.0, 40.0]), max: Vec2::from([20.0, 50.0]) };
+        assert!(quad.inside_polygon(&polygons[0]));
+        assert!(quad.intersects_polygon(&polygons[0]));
+        assert!(!quad.intersects_polygon(&polygons[1]));
+        assert!(!bbox.inside_polygon(&polygons[1]));
+        assert!(bbox.intersects_polygon(&polygons[1]));
+
+        // Polygons with fewer than 3 vertices contain nothing.
+        assert!(tree.find_within_polygon(&polygons[1][..2]).is_empty());
+        assert!(tree.find_within_polygon(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_FrozenQuadtree() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([100.0, 100.0])
+        };
+
+        let mut points = vec![];
+        let mut state: u32 = 21;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            ((state >> 16) % 10000) as f32 / 100.0
+        };
+        for _ in 0..500 {
+            points.push(Vec2::from([next(), next()]));
+        }
+
+        let config = QuadtreeConfig {
+            bucket_capacity: 4,
+            max_depth: 8
+        };
+
+        for mut tree in [PointQuadtree::<usize>::new(&bbox), PointQuadtree::<usize>::with_config(&bbox, config)] {
+            for (i, p) in points.iter().enumerate() {
+                let _ = tree.insert(p, i);
+            }
+
+            let queries: Vec<Vec2> = (0..20).map(|i| Vec2::from([i as f32 * 5.0, 100.0 - i as f32 * 4.0])).collect();
+            let expected_within: Vec<Vec<Node<usize>>> = queries.iter()
+                .map(|q| tree.find_within(&BBox2D { min: q - Vec2::from([10.0, 10.0]), max: q + Vec2::from([10.0, 10.0]) }))
+                .collect();
+            let expected_radius: Vec<Vec<Node<usize>>> = queries.iter().map(|q| tree.find_within_radius(q, 8.0)).collect();
+            let expected_knn: Vec<Vec<(Node<usize>, f32)>> = queries.iter().map(|q| tree.knn(q, 5)).collect();
+            let (len, quads) = (tree.len(), tree.node_count());
+
+            let frozen = tree.freeze();
+            assert_eq!((frozen.len(), frozen.node_count()), (len, quads));
+            assert_eq!(frozen.bbox(), bbox);
+            assert_eq!(frozen.iter().count(), len);
+
+            for (i, p) in points.iter().enumerate() {
+                assert!(frozen.find(p).is_some_and(|j| points[*j] == points[i]));
+            }
+            assert!(frozen.find(&Vec2::from([200.0, 50.0])).is_none());
+            assert!(!frozen.contains(&Vec2::from([100.5, 50.0])));
+
+            let sorted = |mut nodes: Vec<Node<usize>>| {
+                nodes.sort_by_key(|(_, i)| *i);
+                nodes
+            };
+            for (i, q) in queries.iter().enumerate() {
+                let within = frozen.find_within(&BBox2D { min: q - Vec2::from([10.0, 10.0]), max: q + Vec2::from([10.0, 10.0]) });
+                assert_eq!(sorted(within.into_iter().map(|(p, i)| (p, *i)).collect()), sorted(expected_within[i].clone()));
+
+                let radius = frozen.find_within_radius(q, 8.0);
+                assert_eq!(sorted(radius.into_iter().map(|(p, i)| (p, *i)).collect()), sorted(expected_radius[i].clone()));
+
+                let knn: Vec<f32> = frozen.knn(q, 5).into_iter().map(|(_, d)| d).collect();
+                assert_eq!(knn, expected_knn[i].iter().map(|(_, d)| *d).collect::<Vec<_>>());
+                assert_eq!(frozen.nearest(q).map(|(p, _)| (p - q).norm()), Some(knn[0]));
+            }
+        }
+
+        let empty = PointQuadtree::<usize>::new(&bbox).freeze();
+        assert!(empty.is_empty());
+        assert!(empty.nearest(&Vec2::default()).is_none());
+        assert!(empty.find_within(&bbox).is_empty());
+    }
+
+    #[test]
+    fn test_PointQuadtree_compact() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([100.0, 100.0])
+        };
+        let config = QuadtreeConfig {
+            bucket_capacity: 2,
+            max_depth: 8
+        };
+        let mut tree = PointQuadtree::<usize>::with_config(&bbox, config);
+
+        let points: Vec<Vec2> = (0..400)
+            .map(|i| Vec2::from([(i % 20) as f32 * 5.0 + 0.5, (i / 20) as f32 * 5.0 + 0.5]))
+            .collect();
+        for (i, p) in points.iter().enumerate() {
+            assert!(tree.insert(p, i).is_ok());
+        }
+
+        // Emptying most of the tree merges quads back into their parents, leaving their slots behind.
+        let removed = tree.remove_within(&BBox2D { min: Vec2::from([0.0, 0.0]), max: Vec2::from([100.0, 80.0]) });
+        assert_eq!(removed.len(), 320);
+        let (quads, before) = (tree.node_count(), tree.memory_bytes_estimate());
+
+        tree.compact();
+        assert_eq!(tree.node_count(), quads);
+        assert!(tree.memory_bytes_estimate() < before);
+        assert_eq!(tree.len(), 80);
+        assert_eq!(tree.find(&points[399]), Some((points[399], 399)));
+        assert_eq!(tree.find_within(&bbox).len(), 80);
+        assert_eq!(tree.nearest(&Vec2::from([0.0, 0.0])).map(|(_, i)| i), Some(320));
+
+        assert!(tree.insert(&Vec2::from([1.0, 1.0]), 1000).is_ok());
+        assert_eq!(tree.remove(&points[399]), Some(399));
+        assert_eq!(tree.len(), 80);
+    }
+
+    #[test]
+    fn test_PointQuadtree_closest_pair() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([100.0, 100.0])
+        };
+
+        let empty = PointQuadtree::<usize>::new(&bbox);
+        assert!(empty.closest_pair().is_none());
+        assert!(empty.all_nearest_neighbors().is_empty());
+
+        let mut single = PointQuadtree::<usize>::new(&bbox);
+        assert!(single.insert(&Vec2::from([1.0, 1.0]), 0).is_ok());
+        assert!(single.closest_pair().is_none());
+        assert!(single.all_nearest_neighbors().is_empty());
+
+        let mut points = vec![];
+        let mut state: u32 = 7;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            ((state >> 16) % 10000) as f32 / 100.0
+        };
+        for _ in 0..400 {
+            points.push(Vec2::from([next(), next()]));
+        }
+
+        // A tight cluster puts plenty of points in the buckets of subdivided quads.
+        for i in 0..20 {
+            points.push(Vec2::from([30.0 + i as f32 * 0.013, 70.0]));
+        }
+
+        let config = QuadtreeConfig {
+            bucket_capacity: 4,
+            max_depth: 6
+        };
+        let trees = [PointQuadtree::<usize>::new(&bbox), PointQuadtree::<usize>::with_config(&bbox, config)];
+
+        for mut tree in trees {
+            let mut stored = vec![];
+            for p in &points {
+                if tree.insert(p, stored.len()).is_ok() {
+                    stored.push(*p);
+                }
+            }
+
+            let nearest_dist = |i: usize| {
+                (0..stored.len())
+                    .filter(|&j| j != i)
+                    .map(|j| (stored[i] - stored[j]).norm())
+                    .fold(f32::INFINITY, f32::min)
+            };
+
+            let ((p, i), (q, j), d) = tree.closest_pair().unwrap();
+            assert_eq!((stored[i], stored[j]), (p, q));
+            assert_eq!((p - q).norm(), d);
+            assert_eq!(d, (0..stored.len()).map(nearest_dist).fold(f32::INFINITY, f32::min));
+
+            let neighbors = tree.all_nearest_neighbors();
+            assert_eq!(neighbors.len(), stored.len());
+
+            let mut seen = vec![false; stored.len()];
+            for ((p, i), (q, j), d) in neighbors {
+                assert_ne!(i, j);
+                assert_eq!((stored[i], stored[j]), (p, q));
+                assert_eq!(d, nearest_dist(i));
+                seen[i] = true;
+            }
+            assert!(seen.into_iter().all(|s| s));
+        }
+    }
+
+    #[test]
+    fn test_PointQuadtree_binary_format() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([100.0, 100.0])
+        };
+
+        let mut state: u32 = 5;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            ((state >> 16) % 10000) as f32 / 100.0
+        };
+        let points: Vec<Vec2> = (0..300).map(|_| Vec2::from([next(), next()])).collect();
+
+        let config = QuadtreeConfig {
+            bucket_capacity: 4,
+            max_depth: 6
+        };
+        let trees = [PointQuadtree::<String>::new(&bbox), PointQuadtree::<String>::with_config(&bbox, config)];
+
+        for mut tree in trees {
+            for (i, p) in points.iter().enumerate() {
+                let _ = tree.insert(p, format!("point {}", i));
+            }
+            tree.remove(&points[7]);
+
+            let mut bytes = vec![];
+            tree.write_to(&mut bytes).unwrap();
+            let mut restored = PointQuadtree::<String>::read_from(bytes.as_slice()).unwrap();
+
+            // The restored tree has exactly the same shape, down to the buckets of every quad.
+            let quads = |tree: &PointQuadtree<String>| {
+                let mut quads = vec![];
+                tree.visit_quads(|bbox, depth| quads.push((*bbox, depth)));
+                quads
+            };
+            assert_eq!(quads(&restored), quads(&tree));
+            assert_eq!(restored.len(), tree.len());
+            assert_eq!(restored.to_vec(), tree.to_vec());
+
+            // Every payload is only a few bytes, so the points make up most of the output.
+            assert!(bytes.len() < tree.len() * 30 + tree.node_count() * 20);
+
+            // The restored tree must remain fully mutable.
+            assert_eq!(restored.remove(&points[3]), Some("point 3".to_string()));
+            assert!(restored.insert(&points[7], "again".to_string()).is_ok());
+            assert_eq!(restored.find(&points[7]).unwrap().1, "again");
+
+            // --
+            // Input which isn't a tree of the same type is rejected.
+            let error = |bytes: &[u8]| PointQuadtree::<String>::read_from(bytes).err().unwrap().kind();
+            assert_eq!(error(&bytes[..bytes.len() - 1]), std::io::ErrorKind::UnexpectedEof);
+            assert_eq!(error(b"JSON{}"), std::io::ErrorKind::InvalidData);
+
+            let mut corrupted = bytes.clone();
+            corrupted[4] += 1;
+            assert_eq!(error(&corrupted), std::io::ErrorKind::InvalidData);
+
+            assert!(PointQuadtree::<String, f64>::read_from(bytes.as_slice()).is_err());
+            assert!(PointQuadtree::<u32>::read_from(bytes.as_slice()).is_err());
+        }
+
+        // Payloads can be anything Packed, e.g. tuples of vectors.
+        let mut tree = PointQuadtree::<(u8, Vec<i64>), f64>::with_config(&BBox2D {
+            min: Vec2::from([-1.0, -1.0]),
+            max: Vec2::from([1.0, 1.0])
+        }, QuadtreeConfig::default());
+        assert!(tree.insert(&Vec2::from([0.5, -0.5]), (1, vec![-1, 2])).is_ok());
+
+        let mut bytes = vec![];
+        tree.write_to(&mut bytes).unwrap();
+        let restored = PointQuadtree::<(u8, Vec<i64>), f64>::read_from(&bytes[..]).unwrap();
+        assert_eq!(restored.find(&Vec2::from([0.5, -0.5])).unwrap().1, (1, vec![-1, 2]));
+
+        let empty = PointQuadtree::<()>::new(&bbox);
+        let mut bytes = vec![];
+        empty.write_to(&mut bytes).unwrap();
+        assert!(PointQuadtree::<()>::read_from(bytes.as_slice()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_PointQuadtree_sample_within() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([10.0, 10.0])
+        };
+
+        let mut tree = PointQuadtree::<usize>::with_config(&bbox, QuadtreeConfig { bucket_capacity: 2, max_depth: 8 });
+        for i in 0..100 {
+            assert!(tree.insert(&Vec2::from([(i % 10) as f32, (i / 10) as f32]), i).is_ok());
+        }
+
+        let mut rng = XorShift::with_seed(5);
+        let query = BBox2D {
+            min: Vec2::from([2.0, 2.0]),
+            max: Vec2::from([4.0, 5.0])
+        };
+
+        let mut counts = vec![0; 100];
+        for _ in 0..6000 {
+            let (p, i) = tree.sample_within(&query, &mut rng).unwrap();
+            assert!(query.contains(&p));
+            counts[i] += 1;
+        }
+
+        // The 6 points within the query are picked equally often.
+        let picked: Vec<usize> = (0..100).filter(|i| counts[*i] > 0).collect();
+        assert_eq!(picked, vec![22, 23, 32, 33, 42, 43]);
+        assert!(picked.iter().all(|i| (800..1200).contains(&counts[*i])));
+
+        let outside = BBox2D {
+            min: Vec2::from([2.5, 2.5]),
+            max: Vec2::from([2.9, 2.9])
+        };
+        assert!(tree.sample_within(&outside, &mut rng).is_none());
+    }
+
+    #[test]
+    fn test_PointQuadtree_iteration() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([100.0, 100.0])
+        };
+        let config = QuadtreeConfig {
+            bucket_capacity: 2,
+            max_depth: 8
+        };
+
+        let mut tree = PointQuadtree::<usize>::with_config(&bbox, config);
+        let mut state: u32 = 3;
+        let mut points = vec![];
+        while points.len() < 50 {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            let x = (state >> 16) as f32 % 100.0;
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            let y = (state >> 16) as f32 % 100.0;
+
+            if tree.insert(&Vec2::from([x, y]), points.len()).is_ok() {
+                points.push(Vec2::from([x, y]));
+            }
+        }
+
+        let sorted = |nodes: Vec<Node<usize>>| {
+            let mut nodes = nodes;
+            nodes.sort_by_key(|node| node.1);
+            nodes
+        };
+        let expected: Vec<Node<usize>> = points.iter().enumerate().map(|(i, p)| (*p, i)).collect();
+
+        assert_eq!(tree.to_vec().len(), 50);
+        assert_eq!(sorted(tree.to_vec()), expected);
+
+        let mut count = 0;
+        tree.for_each(|p, i| {
+            assert_eq!(points[*i], *p);
+            count += 1;
+        });
+        assert_eq!(count, 50);
+
+        // Parents are visited before their children, which lie inside of them one level deeper.
+        let mut quads: Vec<(BBox2D, usize)> = vec![];
+        tree.visit_quads(|bbox, depth| quads.push((*bbox, depth)));
+        assert_eq!(quads.len(), tree.node_count());
+        assert_eq!(quads[0], (bbox, 0));
+        for (idx, (quad, depth)) in quads.iter().enumerate().skip(1) {
+            assert!(quads[..idx].iter().rev().any(|(parent, d)| *d + 1 == *depth && parent.contains_bbox(quad)));
+        }
+        assert_eq!(quads.iter().map(|(_, depth)| *depth).max(), Some(tree.depth()));
+
+        // Draining leaves an empty tree which is still usable.
+        assert_eq!(sorted(tree.drain().collect()), expected);
+        assert!(tree.is_empty());
+        assert_eq!(tree.node_count(), 1);
+        assert!(tree.to_vec().is_empty());
+        assert!(tree.insert(&points[0], 0).is_ok());
+        assert!(tree.insert(&Vec2::from([100.0, 0.0]), 0).is_err());
+
+        let tree = PointQuadtree::<usize>::from_points(&bbox, expected.clone());
+        assert_eq!(sorted(tree.into_iter().collect()), expected);
+    }
+
+    #[test]
+    fn test_PointQuadtree_rebalance() {
+        let bbox = BBox2D {
+            min: Vec2::from([-100.0, -100.0]),
+            max: Vec2::from([100.0, 100.0])
+        };
+
+        // Inserting sorted points makes each one the pivot of the next, so the tree degenerates
+        // into a chain, down to the max depth.
+        let mut tree = PointQuadtree::<usize>::new(&bbox);
+        for i in 0..64 {
+            assert!(tree.insert(&Vec2::from([i as f32, i as f32 * 0.5]), i).is_ok());
+        }
+        assert_eq!(tree.depth(), 32);
+
+        tree.rebalance();
+        assert!(tree.depth() <= 8);
+        assert_eq!(tree.len(), 64);
+
+        for i in 0..64 {
+            let p = Vec2::from([i as f32, i as f32 * 0.5]);
+            assert_eq!(tree.find(&p), Some((p, i)));
+        }
+
+        // The tree keeps working as usual after being rebuilt.
+        assert!(tree.insert(&Vec2::from([-50.0, 20.0]), 64).is_ok());
+        assert_eq!(tree.insert(&Vec2::from([10.0, 5.0]), 65), Err(InsertError::DuplicatePoint));
+        assert_eq!(tree.remove(&Vec2::from([10.0, 5.0])), Some(10));
+        assert_eq!(tree.find_within(&bbox).len(), 64);
+
+        // Points sharing coordinates can't always be split around the median.
+        let mut tree = PointQuadtree::<usize>::new(&bbox);
+        for (i, p) in [[0.0, 0.0], [0.0, 1.0], [1.0, 0.0], [0.0, 2.0], [2.0, 0.0]].iter().enumerate() {
+            assert!(tree.insert(&Vec2::from(*p), i).is_ok());
+        }
+        tree.rebalance();
+        assert_eq!(tree.find_within(&bbox).len(), 5);
+        assert_eq!(tree.find(&Vec2::from([2.0, 0.0])), Some((Vec2::from([2.0, 0.0]), 4)));
+
+        let mut empty = PointQuadtree::<usize>::new(&bbox);
+        empty.rebalance();
+        assert_eq!(empty.depth(), 0);
+        assert_eq!(empty.node_count(), 1);
+    }
+
+    #[test]
+    fn test_PointQuadtree_snapshot() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([100.0, 100.0])
+        };
+
+        let mut tree = PointQuadtree::<usize>::with_config(&bbox, QuadtreeConfig { bucket_capacity: 2, max_depth: 8 });
+        let points: Vec<Vec2> = (0..50).map(|i| Vec2::from([(i * 7 % 100) as f32, (i * 13 % 100) as f32])).collect();
+        for (i, p) in points.iter().enumerate() {
+            assert!(tree.insert(p, i).is_ok());
+        }
+
+        let snapshot = tree.snapshot();
+
+        assert_eq!(tree.remove_within(&BBox2D { min: Vec2::from([0.0, 0.0]), max: Vec2::from([50.0, 50.0]) }).len(), 15);
+        assert!(tree.relocate(&points[4], &Vec2::from([99.0, 99.0])));
+        assert!(tree.insert(&Vec2::from([1.5, 1.5]), 100).is_ok());
+        tree.rebalance();
+
+        tree.restore(&snapshot);
+        assert_eq!(tree.len(), 50);
+        let mut found = tree.find_within(&bbox);
+        found.sort_by_key(|(_, i)| *i);
+        assert_eq!(found, points.iter().copied().zip(0..).collect::<Vec<_>>());
+        assert!(tree.find(&Vec2::from([1.5, 1.5])).is_none());
+    }
+
+    #[test]
+    fn test_PointQuadtree_with_shards() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([100.0, 100.0])
+        };
+
+        let mut tree = PointQuadtree::<usize>::with_shards(&bbox, QuadtreeConfig::default(), 8);
+        for i in 0..100 {
+            assert!(tree.insert(&Vec2::from([(i % 10) as f32 * 10.0, (i / 10) as f32 * 10.0]), i).is_ok());
+        }
+        assert_eq!(tree.len(), 100);
+        assert_eq!(tree.find(&Vec2::from([30.0, 40.0])), Some((Vec2::from([30.0, 40.0]), 43)));
+        assert_eq!(tree.remove(&Vec2::from([30.0, 40.0])), Some(43));
+
+        // Inserting the points one by one gives the same quads as loading them all at once.
+        let points: Vec<(Vec2, usize)> = (0..100)
+            .map(|i| (Vec2::from([(i % 10) as f32 * 10.0, (i / 10) as f32 * 10.0]), i))
+            .filter(|(_, i)| *i != 43)
+            .collect();
+        let bulk = PointQuadtree::from_points(&bbox, points);
+
+        let (mut quads, mut bulk_quads) = (vec![], vec![]);
+        tree.visit_quads(|bbox, depth| quads.push((*bbox, depth)));
+        bulk.visit_quads(|bbox, depth| bulk_quads.push((*bbox, depth)));
+        assert_eq!(quads, bulk_quads);
+
+        // Rebuilding keeps the tree sharded.
+        tree.rebalance();
+        assert_eq!(tree.len(), 99);
+        assert_eq!(tree.find_within(&bbox).len(), 99);
+        assert_eq!(tree.find(&Vec2::from([90.0, 90.0])), Some((Vec2::from([90.0, 90.0]), 99)));
+    }
+
+    #[test]
+    fn test_PointQuadtree_find_along_segment() {
+        let bbox = BBox2D {
+            min: Vec2::from([-100.0, -100.0]),
+            max: Vec2::from([100.0, 100.0])
+        };
+
+        let mut state: u32 = 7;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            ((state >> 16) % 2000) as f32 / 10.0 - 100.0
+        };
+
+        let mut tree = PointQuadtree::<usize>::with_config(&bbox, QuadtreeConfig { bucket_capacity: 4, max_depth: 8 });
+        let mut points = vec![];
+        for i in 0..500 {
+            let p = Vec2::from([next(), next()]);
+            if tree.insert(&p, i).is_ok() {
+                points.push((p, i));
+            }
+        }
+
+        for _ in 0..50 {
+            let a = Vec2::from([next(), next()]);
+            let b = Vec2::from([next(), next()]);
+            let tolerance = next().abs() / 10.0;
+
+            let mut expected: Vec<usize> = points.iter()
+                .filter(|(p, _)| {
+                    let dir = b - a;
+                    let t = ((p - a).dot(&dir) / dir.norm_squared()).clamp(0.0, 1.0);
+                    (p - (a + dir * t)).norm() <= tolerance
+                })
+                .map(|(_, i)| *i)
+                .collect();
+            expected.sort();
+
+            let mut actual: Vec<usize> = tree.find_along_segment(&a, &b, tolerance).iter().map(|(_, i)| *i).collect();
+            actual.sort();
+            assert_eq!(actual, expected);
+        }
+
+        // Axis-aligned and degenerate segments.
+        let mut tree = PointQuadtree::<usize>::new(&bbox);
+        assert!(tree.insert(&Vec2::from([0.0, 0.0]), 0).is_ok());
+        assert!(tree.insert(&Vec2::from([5.0, 0.5]), 1).is_ok());
+        assert!(tree.insert(&Vec2::from([5.0, 3.0]), 2).is_ok());
+        assert!(tree.insert(&Vec2::from([-5.0, 0.0]), 3).is_ok());
+
+        let mut found: Vec<usize> = tree.find_along_segment(&Vec2::from([0.0, 0.0]), &Vec2::from([10.0, 0.0]), 1.0)
+            .iter().map(|(_, i)| *i).collect();
+        found.sort();
+        assert_eq!(found, vec![0, 1]);
+
+        let found = tree.find_along_segment(&Vec2::from([5.0, 3.0]), &Vec2::from([5.0, 3.0]), 0.0);
+        assert_eq!(found, vec![(Vec2::from([5.0, 3.0]), 2)]);
+
+        let mut count = 0;
+        tree.find_along_segment_with(&Vec2::from([-10.0, 0.0]), &Vec2::from([10.0, 0.0]), 0.1, |_, _| count += 1);
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn test_PointQuadtree_f64() {
+        let bbox = BBox2D::<f64> {
+            min: Vec2::new(-1e6, -1e6),
+            max: Vec2::new(1e6, 1e6)
+        };
+
+        // These points are too close together to tell apart as f32.
+        let a = Vec2::new(123456.000001, 0.5);
+        let b = Vec2::new(123456.000002, 0.5);
+        assert_eq!(a.x as f32, b.x as f32);
+
+        let mut tree = PointQuadtree::<&str, f64>::new(&bbox);
+        assert!(tree.insert(&a, "a").is_ok());
+        assert!(tree.insert(&b, "b").is_ok());
+        assert_eq!(tree.len(), 2);
+
+        assert_eq!(tree.find(&b), Some((b, "b")));
+        assert_eq!(tree.nearest(&Vec2::new(123456.0000019, 0.5)), Some((b, "b")));
+        assert_eq!(tree.find_within_radius(&a, 1.5e-6).len(), 2);
+        assert_eq!(tree.find_within_radius(&a, 0.5e-6), vec![(a, "a")]);
+
+        let knn = tree.knn(&Vec2::new(0.0, 0.0), 1);
+        assert_eq!(knn[0].0, (a, "a"));
+
+        let tree = PointQuadtree::<usize, f64>::from_points(&bbox, (0..100).map(|i| (Vec2::new(i as f64 * 1e-7, 0.0), i)));
+        assert_eq!(tree.len(), 100);
+        assert_eq!(tree.find_within(&BBox2D { min: Vec2::new(0.0, 0.0), max: Vec2::new(5e-7, 1.0) }).len(), 5);
+    }
+
+    #[test]
+    fn test_PointQuadtree_relocate() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([100.0, 100.0])
+        };
+
+        let config = QuadtreeConfig { bucket_capacity: 2, max_depth: 8 };
+        let mut tree = PointQuadtree::<usize>::with_config(&bbox, config);
+        for i in 0..50 {
+            assert!(tree.insert(&Vec2::from([(i * 7 % 100) as f32, (i * 13 % 100) as f32]), i).is_ok());
+        }
+
+        let a = Vec2::from([7.0, 13.0]);
+        let b = Vec2::from([14.0, 26.0]);
+
+        // Small moves stay within the same quad, larger ones cross over to another.
+        assert!(tree.relocate(&a, &Vec2::from([7.5, 13.5])));
+        assert!(tree.relocate(&Vec2::from([7.5, 13.5]), &Vec2::from([90.5, 90.5])));
+        assert_eq!(tree.find(&Vec2::from([90.5, 90.5])), Some((Vec2::from([90.5, 90.5]), 1)));
+        assert!(tree.find(&a).is_none());
+        assert_eq!(tree.len(), 50);
+
+        // Moves onto a taken point, outside of the tree, or of missing points are refused.
+        assert!(!tree.relocate(&b, &Vec2::from([90.5, 90.5])));
+        assert!(!tree.relocate(&b, &Vec2::from([100.0, 50.0])));
+        assert!(!tree.relocate(&a, &Vec2::from([1.0, 1.0])));
+        assert!(tree.relocate(&b, &b));
+        assert_eq!(tree.find(&b), Some((b, 2)));
+        assert_eq!(tree.find_within(&bbox).len(), 50);
+    }
+
+    #[test]
+    fn test_PointQuadtree_update_all() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([100.0, 100.0])
+        };
+
+        let mut tree = PointQuadtree::<(f32, usize)>::new(&bbox);
+        for i in 0..20 {
+            assert!(tree.insert(&Vec2::from([i as f32 * 5.0, 50.0]), (1.0, i)).is_ok());
+        }
+
+        // Every point moves right by its velocity, which pushes the last ones out of the tree and
+        // merges 2 of them into the same spot.
+        let dropped = tree.update_all(|p, (velocity, i)| {
+            p.x += *velocity * 10.0;
+            if *i == 3 {
+                p.x = 50.0;
+            }
+            *velocity = 2.0;
+        });
+
+        assert_eq!(dropped.len(), 3);
+        assert_eq!(tree.len(), 17);
+        assert_eq!(tree.find_within(&bbox).len(), 17);
+        assert!(tree.find(&Vec2::from([0.0, 50.0])).is_none());
+        assert_eq!(tree.find(&Vec2::from([10.0, 50.0])), Some((Vec2::from([10.0, 50.0]), (2.0, 0))));
+        assert!(tree.find_within(&bbox).iter().all(|(_, (velocity, _))| *velocity == 2.0));
+        assert!(dropped.iter().any(|(p, _)| p.x == 50.0));
+    }
+
+    #[test]
+    fn test_PointQuadtree_non_clone_payload() {
+        let bbox = BBox2D {
+            min: Vec2::from([-10.0, -10.0]),
+            max: Vec2::from([10.0, 10.0])
+        };
+
+        let mut tree = PointQuadtree::<Box<dyn Fn() -> i32 + Send + Sync>>::new(&bbox);
+        assert!(tree.insert(&Vec2::from([1.0, 1.0]), Box::new(|| 1)).is_ok());
+        assert!(tree.insert(&Vec2::from([2.0, 2.0]), Box::new(|| 2)).is_ok());
+        assert_eq!(tree.len(), 2);
+
+        assert_eq!(tree.remove(&Vec2::from([2.0, 2.0])).unwrap()(), 2);
+        assert_eq!(tree.remove(&Vec2::from([1.0, 1.0])).unwrap()(), 1);
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_LinearQuadtree() {
+        let bbox = BBox2D {
+            min: Vec2::from([-10.0, -10.0]),
+            max: Vec2::from([10.0, 10.0])
+        };
+
+        let tree = LinearQuadtree::<i32>::from_points(&bbox, vec![]);
+        assert!(tree.is_empty());
+        assert!(tree.nearest(&Vec2::default()).is_none());
+        assert!(tree.find_within(&bbox).is_empty());
+
+        let tree = LinearQuadtree::from_points(&bbox, vec![
+            (Vec2::from([0.0, 0.0]), 1),
+            (Vec2::from([5.0, 5.0]), 2),
+            (Vec2::from([-6.0, 2.0]), 3),
+            (Vec2::from([4.0, -7.0]), 4),
+            (Vec2::from([5.0, 5.0]), 5),
+            (Vec2::from([50.0, 5.0]), 6)
+        ]);
+        assert_eq!(tree.len(), 4);
+
+        assert_eq!(tree.find(&Vec2::from([5.0, 5.0])).unwrap().1, 2);
+        assert!(tree.find(&Vec2::from([5.0, 4.0])).is_none());
+        assert_eq!(tree.nearest(&Vec2::from([-9.0, 9.0])).unwrap().1, 3);
+        assert_eq!(tree.nearest(&Vec2::from([50.0, 50.0])).unwrap().1, 2);
+
+        let region = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([10.0, 10.0])
+        };
+        let mut items: Vec<i32> = tree.find_within(&region).into_iter().map(|(_, i)| i).collect();
+        items.sort();
+        assert_eq!(items, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_LinearQuadtree_against_brute_force() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([100.0, 100.0])
+        };
+
+        let mut state = 31u32;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 8) as f32 / (1u32 << 24) as f32 * 100.0
+        };
+
+        let points: Vec<(Vec2, usize)> = (0..2000).map(|i| (Vec2::from([next(), next()]), i)).collect();
+        let tree = LinearQuadtree::from_points(&bbox, points.clone());
+        assert_eq!(tree.len(), points.len());
+
+        for _ in 0..100 {
+            let p = Vec2::from([next() * 1.2 - 10.0, next() * 1.2 - 10.0]);
+            let expected = points.iter()
+                .map(|(q, _)| (q - p).norm())
+                .fold(f32::INFINITY, f32::min);
+            assert_eq!((tree.nearest(&p).unwrap().0 - p).norm(), expected);
+
+            let (a, b) = (next(), next());
+            let region = BBox2D {
+                min: Vec2::from([a.min(b), 20.0]),
+                max: Vec2::from([a.max(b), 60.0])
+            };
+            let mut expected: Vec<usize> = points.iter()
+                .filter(|(q, _)| region.contains(q))
+                .map(|(_, i)| *i)
+                .collect();
+            let mut found: Vec<usize> = tree.find_within(&region).into_iter().map(|(_, i)| i).collect();
+            expected.sort();
+            found.sort();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_PointQuadtree_par_find_within_many() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([100.0, 100.0])
+        };
+
+        let mut tree = PointQuadtree::<usize>::with_config(&bbox, QuadtreeConfig { bucket_capacity: 4, max_depth: 8 });
+        for i in 0..1000 {
+            assert!(tree.insert(&Vec2::from([(i % 100) as f32, (i / 10) as f32]), i).is_ok());
+        }
+
+        // Enough queries to be spread across threads.
+        let queries: Vec<BBox2D> = (0..500)
+            .map(|i| BBox2D {
+                min: Vec2::from([(i % 90) as f32, (i * 2 % 95) as f32]),
+                max: Vec2::from([(i % 90 + 10) as f32, (i * 2 % 95 + 5) as f32])
+            })
+            .collect();
+
+        let results = tree.par_find_within_many(&queries);
+        assert_eq!(results.len(), queries.len());
+        for (query, result) in queries.iter().zip(results) {
+            assert_eq!(result, tree.find_within(query));
+        }
+
+        assert!(tree.par_find_within_many(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_GeoQuadtree() {
+        let paris = LatLon::new(48.8566, 2.3522);
+        let london = LatLon::new(51.5074, -0.1278);
+        assert!((343_000.0..344_500.0).contains(&paris.distance_meters(&london)));
+        assert_eq!(paris.distance_meters(&paris), 0.0);
+
+        let mut tree = GeoQuadtree::<usize>::new();
+        assert_eq!(tree.insert(&LatLon::new(91.0, 0.0), 0), Err(InsertError::OutOfBounds));
+        assert_eq!(tree.insert(&LatLon::new(0.0, f64::NAN), 0), Err(InsertError::OutOfBounds));
+
+        // --
+        // A grid of positions over the whole globe, including both poles and the antimeridian.
+        let mut positions = vec![];
+        for lat in (-90..=90).step_by(5) {
+            for lon in (-180..180).step_by(5) {
+                positions.push(LatLon::new(lat as f64, lon as f64));
+            }
+        }
+        for (i, p) in positions.iter().enumerate() {
+            assert!(tree.insert(p, i).is_ok());
+        }
+        assert_eq!(tree.len(), positions.len());
+
+        // Longitudes wrap around, so 180° is the same meridian as -180°.
+        assert_eq!(tree.insert(&LatLon::new(0.0, 180.0), 0), Err(InsertError::DuplicatePoint));
+
+        let boxes = [
+            GeoBBox::new(-20.0, 170.0, -10.0, -170.0),
+            GeoBBox::new(-20.0, -190.0, -10.0, -170.0),
+            GeoBBox::new(40.0, -10.0, 55.0, 5.0),
+            GeoBBox::new(85.0, -180.0, 90.0, 180.0),
+            GeoBBox::world()
+        ];
+        for bbox in &boxes {
+            let mut found: Vec<usize> = tree.find_within(bbox).into_iter().map(|(_, i)| i).collect();
+            found.sort();
+            let expected: Vec<usize> = (0..positions.len()).filter(|i| bbox.contains(&positions[*i])).collect();
+            assert_eq!(found, expected);
+        }
+        assert_eq!(tree.find_within(&boxes[0]).len(), 3 * 5);
+        assert_eq!(tree.find_within(&boxes[0]), tree.find_within(&boxes[1]));
+        assert_eq!(tree.find_within(&GeoBBox::world()).len(), positions.len());
+
+        // --
+        // Radius queries match brute force, across the antimeridian and around the poles.
+        let centers = [
+            (LatLon::new(0.0, 179.0), 800_000.0),
+            (LatLon::new(-15.0, -178.0), 1_500_000.0),
+            (LatLon::new(88.0, 45.0), 600_000.0),
+            (LatLon::new(-70.0, 0.0), 3_000_000.0),
+            (LatLon::new(48.8566, 2.3522), 500_000.0),
+            (LatLon::new(10.0, 10.0), 30_000_000.0)
+        ];
+        for (center, radius) in &centers {
+            let result = tree.find_within_meters(center, *radius);
+            let distances: Vec<f64> = result.iter().map(|(p, _)| center.distance_meters(p)).collect();
+            assert!(distances.windows(2).all(|pair| pair[0] <= pair[1]));
+
+            let mut found: Vec<usize> = result.into_iter().map(|(_, i)| i).collect();
+            found.sort();
+            let expected: Vec<usize> = (0..positions.len())
+                .filter(|i| center.distance_meters(&positions[*i]) <= *radius)
+                .collect();
+            assert!(!expected.is_empty());
+            assert_eq!(found, expected);
+        }
+
+        let idx = positions.iter().position(|p| *p == LatLon::new(0.0, -180.0)).unwrap();
+        assert_eq!(tree.find_within(&GeoBBox::new(0.0, 180.0, 0.0, 180.0)), vec![(positions[idx], idx)]);
+        assert_eq!(tree.remove(&LatLon::new(0.0, 180.0)), Some(idx));
+        assert!(tree.find_within(&GeoBBox::new(0.0, 180.0, 0.0, 180.0)).is_empty());
+    }
+
+    #[test]
+    fn test_LooseQuadtree() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([100.0, 100.0])
+        };
+        let square = |x: f32, y: f32, size: f32| BBox2D {
+            min: Vec2::from([x, y]),
+            max: Vec2::from([x + size, y + size])
+        };
+
+        let mut tree = LooseQuadtree::<BBox2D>::new(&bbox);
+        assert!(tree.is_empty());
+
+        // Small objects sit deep in the tree even when they straddle the middle of it.
+        let small = tree.insert(square(49.5, 49.5, 1.0)).unwrap();
+        assert!(tree.depth() >= 5);
+        let large = tree.insert(square(10.0, 10.0, 60.0)).unwrap();
+        assert_eq!(tree.insert(square(150.0, 50.0, 1.0)), Err(InsertError::OutOfBounds));
+        assert_eq!(tree.insert(square(-90.0, 0.0, 100.0)), Err(InsertError::OutOfBounds));
+
+        let found = tree.find_intersecting(&square(49.0, 49.0, 0.8));
+        assert_eq!(found.len(), 2);
+        assert_eq!(tree.find_intersecting_pairs(), vec![(small, large)]);
+
+        // Small moves keep the object where it is, large ones move it to another quad.
+        let depth = tree.depth();
+        assert_eq!(tree.relocate(&small, square(49.7, 49.6, 1.0)), Ok(square(49.5, 49.5, 1.0)));
+        assert_eq!(tree.depth(), depth);
+        assert!(tree.relocate(&small, square(90.0, 90.0, 1.0)).is_ok());
+        assert!(tree.find_intersecting_pairs().is_empty());
+        assert_eq!(tree.relocate(&small, square(120.0, 0.0, 1.0)), Err(InsertError::OutOfBounds));
+        assert_eq!(tree.get(&small), Some(square(90.0, 90.0, 1.0)));
+
+        assert_eq!(tree.remove(&large), Some(square(10.0, 10.0, 60.0)));
+        assert!(!tree.contains(&large));
+        assert!(tree.remove(&large).is_none());
+        assert!(tree.relocate(&large, square(1.0, 1.0, 1.0)).is_err());
+        assert_eq!(tree.remove(&small), Some(square(90.0, 90.0, 1.0)));
+        assert!(tree.is_empty());
+        assert_eq!(tree.depth(), 0);
+
+        // Boxes of all sizes keep moving, and every query agrees with a brute force search.
+        let mut state: u32 = 7;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            ((state >> 16) % 10000) as f32 / 100.0
+        };
+
+        let mut boxes = vec![];
+        for i in 0..200 {
+            let size = if i % 10 == 0 { next() / 4.0 } else { next() / 50.0 };
+            let b = square(next() * 0.7, next() * 0.7, size);
+            boxes.push((tree.insert(b).unwrap(), b));
+        }
+
+        for round in 0..5 {
+            for (i, (id, b)) in boxes.iter_mut().enumerate() {
+                let step = if (i + round) % 7 == 0 { 20.0 } else { 0.5 };
+                let moved = square(
+                    (b.min.x + step * (next() / 50.0 - 1.0)).clamp(0.0, 70.0),
+                    (b.min.y + step * (next() / 50.0 - 1.0)).clamp(0.0, 70.0),
+                    b.max.x - b.min.x
+                );
+                assert_eq!(tree.relocate(id, moved), Ok(*b));
+                *b = moved;
+            }
+
+            let query = square(next(), next(), 15.0);
+            let mut found: Vec<_> = tree.find_intersecting(&query).into_iter().map(|(id, _)| id).collect();
+            let expected: Vec<_> = boxes.iter().filter(|(_, b)| b.intersects(&query)).map(|(id, _)| *id).collect();
+            found.sort_by_key(|id| boxes.iter().position(|(other, _)| other == id));
+            assert_eq!(found, expected);
+
+            let pairs = tree.find_intersecting_pairs();
+            let expected = (0..boxes.len())
+                .flat_map(|i| (i + 1..boxes.len()).map(move |j| (i, j)))
+                .filter(|(i, j)| boxes[*i].1.intersects(&boxes[*j].1))
+                .count();
+            assert_eq!(pairs.len(), expected);
+        }
+        assert_eq!(tree.len(), 200);
+    }
+
+    #[test]
+    fn test_QuadtreeWriter() {
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([100.0, 100.0])
+        };
+
+        let mut state: u32 = 3;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            ((state >> 16) % 10000) as f32 / 100.0
+        };
+        let points: Vec<Vec2> = (0..2000).map(|_| Vec2::from([next(), next()])).collect();
+
+        // Trees split around their points fall back to inserting them one by one.
+        let config = QuadtreeConfig { bucket_capacity: 4, max_depth: 10 };
+        for mut tree in [PointQuadtree::<usize>::with_config(&bbox, config), PointQuadtree::new(&bbox)] {
+            let mut expected = PointQuadtree::<usize>::with_config(&bbox, config);
+            for (i, p) in points.iter().enumerate().take(100) {
+                assert!(tree.insert(p, i).is_ok());
+                assert!(expected.insert(p, i).is_ok());
+            }
+
+            let mut writer = QuadtreeWriter::with_batch_size(&mut tree, 300);
+            for (i, p) in points.iter().enumerate() {
+                assert!(writer.insert(p, i).is_ok());
+                let _ = expected.insert(p, i);
+            }
+            assert_eq!(writer.insert(&Vec2::from([100.0, 5.0]), 0), Err(InsertError::OutOfBounds));
+            assert!(writer.insert(&points[0], 0).is_ok());
+            writer.flush();
+            assert_eq!(writer.skipped(), 101 + points.len() - expected.len());
+            drop(writer);
+
+            assert_eq!(tree.len(), expected.len());
+            let mut found = tree.to_vec();
+            let mut all = expected.to_vec();
+            found.sort_by_key(|node| node.1);
+            all.sort_by_key(|node| node.1);
+            assert_eq!(found, all);
+
+            let query = BBox2D { min: Vec2::from([20.0, 30.0]), max: Vec2::from([45.0, 70.0]) };
+            assert_eq!(tree.find_within(&query).len(), expected.find_within(&query).len());
+        }
+    }
 }
@@ -0,0 +1,115 @@
+use alloc::vec;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use std::io::{self, Read, Write};
+
+/// Values which can be written to and read back from the compact binary format of
+/// 'PointQuadtree::write_to'. Numbers are stored in little-endian byte order, and strings and
+/// vectors are prefixed with their length.
+pub trait Packed: Sized {
+    fn write_packed<W: Write>(&self, w: &mut W) -> io::Result<()>;
+
+    fn read_packed<R: Read>(r: &mut R) -> io::Result<Self>;
+}
+
+macro_rules! impl_packed {
+    ($($t:ty),*) => {
+        $(
+            impl Packed for $t {
+                fn write_packed<W: Write>(&self, w: &mut W) -> io::Result<()> {
+                    w.write_all(&self.to_le_bytes())
+                }
+
+                fn read_packed<R: Read>(r: &mut R) -> io::Result<Self> {
+                    let mut bytes = [0; core::mem::size_of::<$t>()];
+                    r.read_exact(&mut bytes)?;
+                    Ok(Self::from_le_bytes(bytes))
+                }
+            }
+        )*
+    };
+}
+
+impl_packed!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+impl Packed for usize {
+    fn write_packed<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        (*self as u64).write_packed(w)
+    }
+
+    fn read_packed<R: Read>(r: &mut R) -> io::Result<Self> {
+        usize::try_from(u64::read_packed(r)?).map_err(|_| invalid_data("length doesn't fit into usize"))
+    }
+}
+
+impl Packed for bool {
+    fn write_packed<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        (*self as u8).write_packed(w)
+    }
+
+    fn read_packed<R: Read>(r: &mut R) -> io::Result<Self> {
+        match u8::read_packed(r)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid_data("invalid bool"))
+        }
+    }
+}
+
+impl Packed for () {
+    fn write_packed<W: Write>(&self, _: &mut W) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn read_packed<R: Read>(_: &mut R) -> io::Result<Self> {
+        Ok(())
+    }
+}
+
+impl Packed for String {
+    fn write_packed<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.len().write_packed(w)?;
+        w.write_all(self.as_bytes())
+    }
+
+    fn read_packed<R: Read>(r: &mut R) -> io::Result<Self> {
+        let len = usize::read_packed(r)?;
+
+        // The length isn't trusted with an allocation up front, the bytes have to actually be there.
+        let mut bytes = vec![];
+        r.take(len as u64).read_to_end(&mut bytes)?;
+        if bytes.len() != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        String::from_utf8(bytes).map_err(|_| invalid_data("invalid utf-8"))
+    }
+}
+
+impl<T: Packed> Packed for Vec<T> {
+    fn write_packed<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.len().write_packed(w)?;
+        self.iter().try_for_each(|item| item.write_packed(w))
+    }
+
+    fn read_packed<R: Read>(r: &mut R) -> io::Result<Self> {
+        let len = usize::read_packed(r)?;
+        (0..len).map(|_| T::read_packed(r)).collect()
+    }
+}
+
+impl<A: Packed, B: Packed> Packed for (A, B) {
+    fn write_packed<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.0.write_packed(w)?;
+        self.1.write_packed(w)
+    }
+
+    fn read_packed<R: Read>(r: &mut R) -> io::Result<Self> {
+        Ok((A::read_packed(r)?, B::read_packed(r)?))
+    }
+}
+
+/// Returns the error for input which isn't in the expected format.
+pub(crate) fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
@@ -1,9 +1,33 @@
-use std::fmt::Debug;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use alloc::collections::BinaryHeap;
+use alloc::{format, vec};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+use core::error::Error;
+use core::fmt;
+use core::fmt::Debug;
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
 
-use crate::arena::{Arena, Id};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::arena::{ArenaSnapshot, GenerationalArena, GenerationalId};
 use crate::arena::prelude::{HasId, IsMemoryArena};
+use crate::collections::HashMap;
+use crate::metrics::Metrics;
+use crate::spatial::quadtree::dual_tree::DualTree;
+use crate::spatial::quadtree::frozen_quadtree::FrozenQuadtree;
+use crate::random::Rng;
+#[cfg(feature = "std")]
+use crate::spatial::quadtree::packed::{invalid_data, Packed};
 use crate::spatial::quadtree::prelude::*;
+use crate::spatial::quadtree::writer::QuadtreeWriter;
+use crate::spatial::search::Candidate;
+use crate::visualize::{DotWriter, ToDot};
+
+type Id = GenerationalId;
 
 /// This is the trait bound for the payload associated with a Point in the tree.
 pub trait IsPayload: Clone + Debug + Send + Sync {}
@@ -11,212 +35,1630 @@ pub trait IsPayload: Clone + Debug + Send + Sync {}
 impl<T: Clone + Debug + Send + Sync> IsPayload for T {}
 
 /// This represents the type of payload that is stored in each Quad of the tree.
-pub type Node<T> = (Vec2, T);
+pub type Node<T, S = f32> = (Vec2<S>, T);
 
-/// A quad represents a quadrant in 3D space, it contains a single point and optionally 4 other
+/// A pair of points along with the distance between them.
+pub type NodePair<T, S = f32> = (Node<T, S>, Node<T, S>, S);
+
+/// A quad represents a quadrant in 2D space, it contains a bucket of points and optionally 4 other
 /// quads which subdivide the space further.
 #[derive(Clone, Debug)]
-struct Quad<P: IsPayload> {
+pub(crate) struct Quad<P, S: IsScalar> {
     pub id: Id,
 
-    pub bbox: BBox2D,
+    pub bbox: BBox2D<S>,
+
+    pub depth: usize,
 
-    pub point: Option<Node<P>>,
+    // Points are only added to quads without children, so buckets of subdivided quads only shrink.
+    pub points: Vec<Node<P, S>>,
 
     // The ordering goes SW, SE, NE, NW
     pub children: Option<[Id; 4]>
 }
 
+/// This controls how the quads of a PointQuadtree get subdivided.
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QuadtreeConfig {
+    /// The number of points a quad can hold before it gets subdivided.
+    pub bucket_capacity: usize,
+
+    /// Quads at this depth are never subdivided, instead their buckets grow as needed. This keeps
+    /// clusters of nearly identical points from subdividing the tree over and over.
+    pub max_depth: usize
+}
+
+impl Default for QuadtreeConfig {
+    fn default() -> Self {
+        Self {
+            bucket_capacity: 8,
+            max_depth: 16
+        }
+    }
+}
+
+/// The reasons a point can't be inserted into a PointQuadtree.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InsertError {
+    /// The point lies outside of the BBox bounding the tree.
+    OutOfBounds,
+
+    /// The tree already holds a point at the same position.
+    DuplicatePoint
+}
+
+impl fmt::Display for InsertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InsertError::OutOfBounds => write!(f, "point is out of bounds"),
+            InsertError::DuplicatePoint => write!(f, "point already exists")
+        }
+    }
+}
+
+impl Error for InsertError {}
+
+/// This is where a full quad gets subdivided.
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum Pivot {
+    /// Around the first point stored in the quad.
+    Point,
+
+    /// Around the midpoint of the quad's bbox.
+    Midpoint
+}
+
+/// Picks the point around which a bulk loaded quad gets subdivided, given its points.
+type SplitFn<P, S> = fn(&BBox2D<S>, &[Node<P, S>]) -> Vec2<S>;
+
+/// The state of a PointQuadtree at some point in time, which it can be rolled back to.
+pub struct QuadtreeSnapshot<P, S: IsScalar = f32> {
+    arena: ArenaSnapshot<Quad<P, S>>,
+    root_id: Id,
+    size: usize
+}
+
+impl<P, S: IsScalar> Clone for QuadtreeSnapshot<P, S> {
+    fn clone(&self) -> Self {
+        Self {
+            arena: self.arena.clone(),
+            root_id: self.root_id,
+            size: self.size
+        }
+    }
+}
+
 /// A Point Quadtree is a data structure used to perform efficient queries of points / regions in
-/// 2D space. The tree works by recursively subdividing (partitioning) 3D space into buckets.
-pub struct PointQuadtree<P: IsPayload> {
-    arena: Arena<Quad<P>>,
+/// 2D space. The tree works by recursively subdividing (partitioning) 2D space into buckets.
+pub struct PointQuadtree<P: Send + Sync, S: IsScalar = f32> {
+    arena: GenerationalArena<Quad<P, S>>,
     root_id: Id,
-    size: AtomicUsize
+    size: AtomicUsize,
+    config: QuadtreeConfig,
+    pivot: Pivot
 }
 
-impl<P: IsPayload> PointQuadtree<P> {
+impl<P: Send + Sync, S: IsScalar> PointQuadtree<P, S> {
 
     /// Returns the number of points contained in this tree.
     pub fn len(&self) -> usize {
         self.size.load(Ordering::SeqCst)
     }
 
-    /// Returns a new Quadtree bounded by the given BBox.
-    pub fn new(bbox: &BBox2D) -> Self {
-        let mut arena = Arena::new();
+    /// Returns true if this tree contains no points.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 
-        let root_id = arena.get_new_id();
-        let root = Quad::<P> {
-            id: root_id,
-            bbox: *bbox,
-            point: None,
-            children: None
+    /// Returns a new Quadtree bounded by the given BBox, where each quad holds a single point and
+    /// is subdivided around it. Quads at depth 32 aren't subdivided anymore, which bounds the depth
+    /// of trees whose points arrive in sorted order.
+    pub fn new(bbox: &BBox2D<S>) -> Self {
+        let config = QuadtreeConfig {
+            bucket_capacity: 1,
+            max_depth: 32
         };
 
-        arena.add_node(root).expect("could not add root node!");
+        Self::_new(bbox, config, Pivot::Point, 1)
+    }
+
+    /// Returns a new Quadtree bounded by the given BBox, where each quad holds up to
+    /// 'config.bucket_capacity' points before being subdivided at its midpoint.
+    pub fn with_config(bbox: &BBox2D<S>, config: QuadtreeConfig) -> Self {
+        Self::with_shards(bbox, config, 1)
+    }
+
+    /// Same as 'with_config', but the quads are spread over the given number of arena shards to
+    /// reduce lock contention between threads querying the tree.
+    pub fn with_shards(bbox: &BBox2D<S>, config: QuadtreeConfig, shards: usize) -> Self {
+        assert!(config.bucket_capacity > 0, "bucket capacity must be at least 1");
+
+        Self::_new(bbox, config, Pivot::Midpoint, shards)
+    }
+
+    /// Returns a new Quadtree bounded by the given BBox holding the given points, using the default
+    /// QuadtreeConfig. Points outside of the BBox and repeats of earlier points are skipped.
+    pub fn from_points(bbox: &BBox2D<S>, points: impl IntoIterator<Item = Node<P, S>>) -> Self {
+        Self::from_points_with_config(bbox, QuadtreeConfig::default(), points)
+    }
+
+    /// Same as 'from_points', but with the given QuadtreeConfig.
+    ///
+    /// The points are partitioned top-down, so each quad is only ever added to the arena once
+    /// instead of being locked for every point that passes through it.
+    pub fn from_points_with_config(
+        bbox: &BBox2D<S>,
+        config: QuadtreeConfig,
+        points: impl IntoIterator<Item = Node<P, S>>
+    ) -> Self {
+        assert!(config.bucket_capacity > 0, "bucket capacity must be at least 1");
+
+        let mut points: Vec<Node<P, S>> = points.into_iter()
+            .filter(|node| bbox.contains(&node.0))
+            .collect();
+
+        // The sort is stable, so only the first of any repeated points is kept (just like insert).
+        points.sort_by(|a, b| cmp_scalar(&a.0.x, &b.0.x).then(cmp_scalar(&a.0.y, &b.0.y)));
+        points.dedup_by(|a, b| a.0 == b.0);
+
+        let mut arena = GenerationalArena::new();
+        let size = points.len();
+        let root_id = Self::_build(&mut arena, &config, points, *bbox, 0, |bbox, _| bbox.mid());
+
+        Self {
+            arena,
+            root_id,
+            size: AtomicUsize::new(size),
+            config,
+            pivot: Pivot::Midpoint
+        }
+    }
+
+    /// Returns the number of quads in this tree.
+    pub fn node_count(&self) -> usize {
+        self.arena.len()
+    }
+
+    /// Returns the depth of the deepest quad in this tree, where the root is at depth 0.
+    pub fn depth(&self) -> usize {
+        let mut max_depth = 0;
+        let mut stack = vec![self.root_id];
+
+        while let Some(id) = stack.pop() {
+            let quad_ref = self.arena.get_node(&id).expect("could not find node");
+            let quad = quad_ref.read().unwrap();
+
+            max_depth = max_depth.max(quad.depth);
+            stack.extend(quad.children.iter().flatten());
+        }
+
+        max_depth
+    }
+
+    /// Moves the quads of the tree into as few arena slots as possible and releases the rest, which
+    /// reclaims the memory left behind by quads that were merged back into their parents. Every
+    /// quad is visited to update the links to its children.
+    pub fn compact(&mut self) {
+        let mut remapped = HashMap::with_capacity(self.arena.len());
+        self.arena.compact(|old, new| {
+            remapped.insert(old, new);
+        });
+
+        let ids: Vec<Id> = self.arena.iter().map(|(id, _)| id).collect();
+        for id in ids {
+            let quad_ref = self.arena.get_node_mut(&id).expect("could not find node");
+            let mut quad = quad_ref.write().unwrap();
+            quad.id = id;
+            if let Some(children) = &mut quad.children {
+                *children = children.map(|child| remapped[&child]);
+            }
+        }
+
+        self.root_id = remapped[&self.root_id];
+    }
+
+    /// Rebuilds the tree from scratch, subdividing each quad around the median of its points
+    /// rather than wherever insertion order happened to put the pivot.
+    pub fn rebalance(&mut self) {
+        let points = self.take_points();
+        self.rebuild(points);
+    }
+
+    /// Returns the BBox bounding the whole tree.
+    pub(crate) fn bbox(&self) -> BBox2D<S> {
+        let root_ref = self.arena.get_node(&self.root_id).expect("could not find node");
+        let bbox = root_ref.read().unwrap().bbox;
+        bbox
+    }
+
+    /// Moves every point out of the quads, leaving their buckets empty.
+    fn take_points(&mut self) -> Vec<Node<P, S>> {
+        let ids: Vec<Id> = self.arena.iter().map(|(id, _)| id).collect();
+        ids.iter()
+            .flat_map(|id| {
+                let quad_ref = self.arena.get_node_mut(id).expect("could not find node");
+                let points = core::mem::take(&mut quad_ref.write().unwrap().points);
+                points
+            })
+            .collect()
+    }
+
+    /// Replaces every quad with a tree holding the given points, which must be unique and inside
+    /// of the tree's bbox, splitting quads around the median of their points.
+    fn rebuild(&mut self, points: Vec<Node<P, S>>) {
+        let bbox = self.bbox();
+
+        let mut arena = GenerationalArena::with_shards(self.arena.shards());
+        self.root_id = Self::_build(&mut arena, &self.config, points, bbox, 0, median_pivot);
+        self.arena = arena;
+    }
+
+    /// Builds the subtree holding the given points, subdividing full quads around the pivot
+    /// returned by 'split_at'.
+    fn _build(
+        arena: &mut GenerationalArena<Quad<P, S>>,
+        config: &QuadtreeConfig,
+        points: Vec<Node<P, S>>,
+        bbox: BBox2D<S>,
+        depth: usize,
+        split_at: SplitFn<P, S>
+    ) -> Id {
+        let id = arena.get_new_id();
+        let mut quad = Quad::<P, S>::new(id, bbox, depth);
+
+        if points.len() <= config.bucket_capacity || depth >= config.max_depth {
+            quad.points = points;
+        } else {
+            // --
+            // Hand each point to the first child which contains it, exactly as '_insert' would.
+            let boxes = bbox.subdivide(&split_at(&bbox, &points));
+            let mut buckets: [Vec<Node<P, S>>; 4] = Default::default();
+
+            for node in points {
+                if let Some(idx) = boxes.iter().position(|child| child.contains(&node.0)) {
+                    buckets[idx].push(node);
+                }
+            }
+
+            let mut buckets = buckets.into_iter();
+            quad.children = Some(boxes.map(|child| {
+                Self::_build(arena, config, buckets.next().unwrap(), child, depth + 1, split_at)
+            }));
+        }
+
+        arena.add_node(quad).expect("could not add node!");
+        id
+    }
+
+    fn _new(bbox: &BBox2D<S>, config: QuadtreeConfig, pivot: Pivot, shards: usize) -> Self {
+        let mut arena = GenerationalArena::with_shards(shards);
+
+        let root_id = arena.get_new_id();
+        arena.add_node(Quad::<P, S>::new(root_id, *bbox, 0)).expect("could not add root node!");
 
         Self {
             arena,
             root_id,
-            size: Default::default()
+            size: Default::default(),
+            config,
+            pivot
         }
     }
 
-    /// Attempts to insert 'elem' into the tree, returning false if the point already exists.
-    pub fn insert(&mut self, point: &Vec2, payload: P) -> bool {
+    /// Attempts to insert the point into the tree, returning an error if it lies outside of the
+    /// tree or already exists.
+    pub fn insert(&mut self, point: &Vec2<S>, payload: P) -> Result<(), InsertError> {
+        if !self.bbox().contains(point) {
+            return Err(InsertError::OutOfBounds);
+        }
+
         let root = self.root_id;
-        if self._insert(&(*point, payload), &root) {
-            self.size.fetch_add(1, Ordering::SeqCst);
-            return true;
+        if !self._insert((*point, payload), &root) {
+            return Err(InsertError::DuplicatePoint);
         }
 
-        false
+        self.size.fetch_add(1, Ordering::SeqCst);
+        Ok(())
     }
 
-    /// Returns all points in the tree within the given BBox.
-    pub fn find_within(&self, bbox: &BBox2D) -> Vec<Node<P>> {
-        self._find_within(bbox, &self.root_id)
+    /// Returns a writer which buffers points and inserts them in Morton ordered batches, for
+    /// loading lots of points at once, see 'QuadtreeWriter'.
+    pub fn writer(&mut self) -> QuadtreeWriter<'_, P, S> {
+        QuadtreeWriter::new(self)
     }
 
-    /// Searches the tree for the given point.
-    pub fn find(&self, p: &Vec2) -> Option<Node<P>> {
-        self._find(p, &self.root_id)
+    /// Compiles the tree into a compact, read-only FrozenQuadtree which is faster to search.
+    pub fn freeze(self) -> FrozenQuadtree<P, S> {
+        FrozenQuadtree::new(self.arena, self.root_id)
+    }
+
+    /// Attempts to remove the point from the tree, returning its payload if it existed.
+    pub fn remove(&mut self, p: &Vec2<S>) -> Option<P> {
+        let root = self.root_id;
+        let (_, payload) = self._remove(p, &root)?;
+        self.size.fetch_sub(1, Ordering::SeqCst);
+        Some(payload)
+    }
+
+    /// Removes every point within the given BBox from the tree, returning them. Quads left without
+    /// any points below them are collapsed into their parents.
+    ///
+    /// Quads lying entirely inside of the BBox are emptied in one go, without looking at their
+    /// points one by one.
+    pub fn remove_within(&mut self, bbox: &BBox2D<S>) -> Vec<Node<P, S>> {
+        let mut result = vec![];
+        let root = self.root_id;
+        self._remove_within(bbox, &root, &mut result);
+
+        self.size.fetch_sub(result.len(), Ordering::SeqCst);
+        result
+    }
+
+    /// Calls 'f' on every point in the tree within the given BBox, without cloning the payloads.
+    pub fn find_within_with<F: FnMut(&Vec2<S>, &P)>(&self, bbox: &BBox2D<S>, mut f: F) {
+        self._find_within(bbox, &self.root_id, &mut f)
+    }
+
+    /// Calls 'f' on every point in the tree within 'radius' of the given center, without cloning
+    /// the payloads.
+    pub fn find_within_radius_with<F: FnMut(&Vec2<S>, &P)>(&self, center: &Vec2<S>, radius: S, mut f: F) {
+        self._find_within_radius(center, radius, &self.root_id, &mut f)
+    }
+
+    /// Calls 'f' on every point in the tree within 'tolerance' of the line segment from 'a' to 'b',
+    /// without cloning the payloads. Only the quads which the segment passes near are visited.
+    pub fn find_along_segment_with<F: FnMut(&Vec2<S>, &P)>(&self, a: &Vec2<S>, b: &Vec2<S>, tolerance: S, mut f: F) {
+        self._find_along_segment(a, b, tolerance, &self.root_id, &mut f)
+    }
+
+    /// Calls 'f' on every point in the tree accepted by 'accept_point', without cloning the
+    /// payloads. This lets callers search shapes the tree knows nothing about (polygons, annuli, ...)
+    /// while still skipping whole subtrees: quads rejected by 'accept_bbox' are skipped along with
+    /// everything below them, so it must accept every bbox which may hold an accepted point.
+    pub fn query_filtered_with<B, A, F>(&self, accept_bbox: B, accept_point: A, mut f: F)
+        where B: Fn(&BBox2D<S>) -> bool, A: Fn(&Vec2<S>, &P) -> bool, F: FnMut(&Vec2<S>, &P)
+    {
+        self._query_filtered(&accept_bbox, &accept_point, &self.root_id, &mut f)
+    }
+
+    /// Calls 'f' on every point in the tree inside of the polygon with the given vertices, see
+    /// 'polygon_contains'. Quads outside of the polygon are skipped, and the points of quads inside
+    /// of it are reported without testing them one by one.
+    pub fn find_within_polygon_with<F: FnMut(&Vec2<S>, &P)>(&self, polygon: &[Vec2<S>], mut f: F) {
+        if polygon.len() >= 3 {
+            self._find_within_polygon(polygon, &self.root_id, false, &mut f)
+        }
+    }
+
+    /// Calls 'f' on every point in the tree, without cloning the payloads. Points are stored behind
+    /// the locks of their quads, so they can only be borrowed for the duration of the call.
+    pub fn for_each<F: FnMut(&Vec2<S>, &P)>(&self, mut f: F) {
+        let mut stack = vec![self.root_id];
+
+        while let Some(id) = stack.pop() {
+            let quad_ref = self.arena.get_node(&id).expect("could not find node");
+            let quad = quad_ref.read().unwrap();
+
+            for node in &quad.points {
+                f(&node.0, &node.1);
+            }
+
+            // Pushed in reverse, so the children are visited in SW, SE, NE, NW order.
+            stack.extend(quad.children.iter().flatten().rev());
+        }
+    }
+
+    /// Calls 'f' with the bbox and depth of every quad in the tree, parents before their children.
+    /// This is mostly useful for debugging, or for drawing the subdivision of the tree.
+    pub fn visit_quads<F: FnMut(&BBox2D<S>, usize)>(&self, mut f: F) {
+        let mut stack = vec![self.root_id];
+
+        while let Some(id) = stack.pop() {
+            let quad_ref = self.arena.get_node(&id).expect("could not find node");
+            let quad = quad_ref.read().unwrap();
+
+            f(&quad.bbox, quad.depth);
+            stack.extend(quad.children.iter().flatten().rev());
+        }
+    }
+
+    /// Removes every point from the tree, returning them. The tree keeps its bbox and config.
+    pub fn drain(&mut self) -> alloc::vec::IntoIter<Node<P, S>> {
+        let points = self.take_points();
+        self.size.store(0, Ordering::SeqCst);
+        self.rebuild(vec![]);
+        points.into_iter()
     }
 
-    fn _find_within(&self, bbox: &BBox2D, quad_id: &Id) -> Vec<Node<P>> {
+    /// Calls 'f' on every pair of points in the tree which are within 'd' of each other, without
+    /// cloning the payloads. Every pair is reported once, in no particular order.
+    ///
+    /// This is the broad phase of collision detection. Rather than searching around every point,
+    /// quads are matched up against each other (a dual-tree traversal), so pairs of quads which are
+    /// further than 'd' apart are skipped along with everything below them.
+    pub fn find_pairs_within_with<F: FnMut(&Vec2<S>, &P, &Vec2<S>, &P)>(&self, d: S, mut f: F) {
+        self._find_pairs_within(d, &self.root_id, &mut f)
+    }
+
+    /// Searches the tree for the given point, returning the result of calling 'f' on it.
+    pub fn find_with<R, F: FnOnce(&Vec2<S>, &P) -> R>(&self, p: &Vec2<S>, f: F) -> Option<R> {
+        let quad_id = self._find_quad(p, &self.root_id)?;
+
+        let quad_ref = self.arena.get_node(&quad_id).expect("could not find node");
+        let quad = quad_ref.read().unwrap();
+        let node = quad.points.iter().find(|node| node.0 == *p)?;
+
+        Some(f(&node.0, &node.1))
+    }
+
+    /// Moves the point at 'old' over to 'new', returning false if there is no point at 'old', or
+    /// if 'new' is outside of the tree or already taken by another point.
+    ///
+    /// The point is only removed and reinserted if it leaves the bbox of its quad, which makes
+    /// small moves cheap.
+    pub fn relocate(&mut self, old: &Vec2<S>, new: &Vec2<S>) -> bool {
+        if old == new {
+            return self.find_with(old, |_, _| ()).is_some();
+        }
+
+        let quad_id = match self._find_quad(old, &self.root_id) {
+            None => return false,
+            Some(id) => id
+        };
+
+        if !self.bbox().contains(new) || self._find_quad(new, &self.root_id).is_some() {
+            return false;
+        }
+
+        {
+            let quad_ref = self.arena.get_node_mut(&quad_id).expect("could not find node");
+            let mut quad = quad_ref.write().unwrap();
+
+            // Any point within the quad's bbox is found by descending to this very quad, so the
+            // point can stay in its bucket.
+            if quad.bbox.contains(new) {
+                let node = quad.points.iter_mut().find(|node| node.0 == *old).unwrap();
+                node.0 = *new;
+                return true;
+            }
+        }
+
+        let root = self.root_id;
+        let (_, payload) = self._remove(old, &root).expect("point disappeared");
+        assert!(self._insert((*new, payload), &root), "could not reinsert point");
+        true
+    }
+
+    /// Calls 'f' on every point in the tree, which may move the point and modify its payload, then
+    /// rebuilds the tree around the new positions like 'rebalance' does.
+    ///
+    /// Points which were moved outside of the tree, or onto a point which is already taken, are
+    /// dropped from the tree and returned.
+    pub fn update_all<F: FnMut(&mut Vec2<S>, &mut P)>(&mut self, mut f: F) -> Vec<Node<P, S>> {
+        let bbox = self.bbox();
+
+        let mut points = self.take_points();
+        for (p, payload) in points.iter_mut() {
+            f(p, payload);
+        }
+
+        let (mut points, mut dropped): (Vec<_>, Vec<_>) = points.into_iter()
+            .partition(|node| bbox.contains(&node.0));
+
+        // Sorting brings repeated points next to each other, where all but the first are dropped.
+        points.sort_by(|a, b| cmp_scalar(&a.0.x, &b.0.x).then(cmp_scalar(&a.0.y, &b.0.y)));
+
+        let mut unique: Vec<Node<P, S>> = Vec::with_capacity(points.len());
+        for node in points {
+            match unique.last() {
+                Some(last) if last.0 == node.0 => dropped.push(node),
+                _ => unique.push(node)
+            }
+        }
+
+        self.size.store(unique.len(), Ordering::SeqCst);
+        self.rebuild(unique);
+        dropped
+    }
+
+    fn _find_within<F: FnMut(&Vec2<S>, &P)>(&self, bbox: &BBox2D<S>, quad_id: &Id, f: &mut F) {
         let quad_ref = self.arena.get_node(quad_id).expect("could not find node");
         let quad = quad_ref.read().unwrap();
 
         if !quad.bbox.intersects(bbox) {
-            return vec![];
+            return;
         }
 
-        let mut result = vec![];
+        for node in quad.points.iter().filter(|node| bbox.contains(&node.0)) {
+            f(&node.0, &node.1);
+        }
 
-        match &quad.point {
-            None => {},
-            Some(node) => {
-                if bbox.contains(&node.0) {
-                    result.push(node.clone())
-                }
+        if let Some(children) = &quad.children {
+            for id in children {
+                self._find_within(bbox, id, f);
             }
         }
+    }
 
-        match &quad.children {
-            None => {}
-            Some(children) => {
-                for id in children {
-                    result.append(&mut self._find_within(bbox, id))
-                }
+    fn _find_within_radius<F: FnMut(&Vec2<S>, &P)>(&self, center: &Vec2<S>, radius: S, quad_id: &Id, f: &mut F) {
+        let quad_ref = self.arena.get_node(quad_id).expect("could not find node");
+        let quad = quad_ref.read().unwrap();
+
+        // If the circle doesn't touch this quad, it can't touch any of its subtrees either.
+        if quad.bbox.distance_to_point(center) > radius {
+            return;
+        }
+
+        for node in quad.points.iter().filter(|node| (node.0 - center).norm() <= radius) {
+            f(&node.0, &node.1);
+        }
+
+        if let Some(children) = &quad.children {
+            for id in children {
+                self._find_within_radius(center, radius, id, f);
             }
         }
+    }
 
-        result
+    fn _find_along_segment<F: FnMut(&Vec2<S>, &P)>(&self, a: &Vec2<S>, b: &Vec2<S>, tolerance: S, quad_id: &Id, f: &mut F) {
+        let quad_ref = self.arena.get_node(quad_id).expect("could not find node");
+        let quad = quad_ref.read().unwrap();
+
+        // Points up to 'tolerance' outside of the quad could still be near the segment, so the
+        // segment has to pass through the quad grown by that much on every side.
+        let padding = Vec2::new(tolerance, tolerance);
+        let padded = BBox2D {
+            min: quad.bbox.min - padding,
+            max: quad.bbox.max + padding
+        };
+
+        if !padded.intersects_segment(a, b) {
+            return;
+        }
+
+        for node in quad.points.iter().filter(|node| distance_to_segment(&node.0, a, b) <= tolerance) {
+            f(&node.0, &node.1);
+        }
+
+        if let Some(children) = &quad.children {
+            for id in children {
+                self._find_along_segment(a, b, tolerance, id, f);
+            }
+        }
     }
 
-    fn _find(&self, p: &Vec2, quad_id: &Id) -> Option<Node<P>> {
+    fn _query_filtered<B, A, F>(&self, accept_bbox: &B, accept_point: &A, quad_id: &Id, f: &mut F)
+        where B: Fn(&BBox2D<S>) -> bool, A: Fn(&Vec2<S>, &P) -> bool, F: FnMut(&Vec2<S>, &P)
+    {
         let quad_ref = self.arena.get_node(quad_id).expect("could not find node");
         let quad = quad_ref.read().unwrap();
 
-        // If the bbox itself doesn't contain the point, then the point could not possibly be
-        // contained in this node or any subtrees of this node.
-        if !quad.bbox.contains(p) {
-            return None;
+        if !accept_bbox(&quad.bbox) {
+            return;
         }
 
-        // Otherwise, we'll need to look at the point contained in this node or the points contained
-        // in the subtrees of this node.
-        match &quad.point {
-            // If we don't have a point, the point can't be contained.
-            None => None,
-
-            Some(point) => {
-                // Let's see if the point stored at this node matches.
-                if point.0 == *p {
-                    Some(point.clone())
-                } else {
-                    // Otherwise, we'll need to look in all of this node's subtrees.
-                    match &quad.children {
-                        // If we don't have any subtrees, the point can't be contained.
-                        None => None,
-
-                        Some(children) => {
-                            for child in children {
-                                if let Some(result) = self._find(p, child) {
-                                    return Some(result);
-                                }
-                            }
-                            None
-                        }
-                    }
-                }
+        for node in quad.points.iter().filter(|node| accept_point(&node.0, &node.1)) {
+            f(&node.0, &node.1);
+        }
+
+        if let Some(children) = &quad.children {
+            for id in children {
+                self._query_filtered(accept_bbox, accept_point, id, f);
             }
         }
     }
 
-    pub fn _insert(&mut self, elem: &Node<P>, quad_id: &Id) -> bool {
+    /// Reports the points inside of the polygon in the subtree rooted at the quad, where 'inside' is
+    /// true if the quad is already known to lie inside of the polygon.
+    fn _find_within_polygon<F: FnMut(&Vec2<S>, &P)>(&self, polygon: &[Vec2<S>], quad_id: &Id, mut inside: bool, f: &mut F) {
         let quad_ref = self.arena.get_node(quad_id).expect("could not find node");
-        let mut quad = quad_ref.write().unwrap();
+        let quad = quad_ref.read().unwrap();
 
-        if !quad.bbox.contains(&elem.0) {
-            return false;
+        if !inside {
+            if !quad.bbox.intersects_polygon(polygon) {
+                return;
+            }
+            inside = quad.bbox.inside_polygon(polygon);
         }
 
-        if quad.point.is_none() {
-            quad.point = Some(elem.clone());
-            return true;
-        } else {
-            if quad.point.as_ref().unwrap().0 == elem.0 {
-                return false;
+        for node in quad.points.iter().filter(|node| inside || polygon_contains(polygon, &node.0)) {
+            f(&node.0, &node.1);
+        }
+
+        if let Some(children) = &quad.children {
+            for id in children {
+                self._find_within_polygon(polygon, id, inside, f);
             }
+        }
+    }
 
-            // --
-            // Subdivide we need to.
-            if quad.children.is_none() {
-                let mut add_one = |bbox| {
-                    let new_id : Id = self.arena.get_new_id();
-
-                    let new_node = Quad::<P>::new(new_id.clone(), bbox);
-                    self.arena.add_node(new_node).expect("could not add node!");
-                    new_id
-                };
+    /// Reports every pair of points within 'd' of each other in the subtree rooted at the quad.
+    fn _find_pairs_within<F: FnMut(&Vec2<S>, &P, &Vec2<S>, &P)>(&self, d: S, quad_id: &Id, f: &mut F) {
+        let quad_ref = self.arena.get_node(quad_id).expect("could not find node");
+        let quad = quad_ref.read().unwrap();
+
+        for (i, a) in quad.points.iter().enumerate() {
+            for b in quad.points[i + 1..].iter().filter(|b| (b.0 - a.0).norm() <= d) {
+                f(&a.0, &a.1, &b.0, &b.1);
+            }
+        }
 
-                let boxes = quad.bbox.subdivide(&quad.point.as_ref().unwrap().0);
+        let children = match &quad.children {
+            None => return,
+            Some(children) => children
+        };
 
-                quad.children = Some(
-                    [
-                        add_one(boxes[0]),
-                        add_one(boxes[1]),
-                        add_one(boxes[2]),
-                        add_one(boxes[3]),
-                    ]
-                );
+        // --
+        // Subdivided quads may still hold points of their own, which are paired up with the points
+        // below them before the children are paired up with themselves and each other.
+        for a in &quad.points {
+            for id in children {
+                self._find_within_radius(&a.0, d, id, &mut |p, payload| f(&a.0, &a.1, p, payload));
             }
+        }
 
-            // --
-            // Then try to insert the point into any of our children.
-            quad.children.as_ref().unwrap().iter().any(|i| {
-                self._insert(&elem, i)
-            })
+        for (i, a) in children.iter().enumerate() {
+            self._find_pairs_within(d, a, f);
+
+            for b in &children[i + 1..] {
+                self._find_pairs_between(d, a, b, f);
+            }
         }
     }
-}
 
-impl<P: IsPayload> Quad<P> {
-    pub fn new(id: Id, bbox: BBox2D) -> Self {
-        Self {
-            id,
-            bbox,
-            point: None,
-            children: None,
+    /// Reports every pair of points within 'd' of each other where one point lies in the subtree
+    /// rooted at quad 'a', and the other in the (disjoint) subtree rooted at quad 'b'.
+    fn _find_pairs_between<F: FnMut(&Vec2<S>, &P, &Vec2<S>, &P)>(&self, d: S, a: &Id, b: &Id, f: &mut F) {
+        let a_ref = self.arena.get_node(a).expect("could not find node");
+        let b_ref = self.arena.get_node(b).expect("could not find node");
+        let (a, b) = (a_ref.read().unwrap(), b_ref.read().unwrap());
+
+        // No point below 'a' can be within 'd' of a point below 'b' if the quads are too far apart.
+        if a.bbox.distance_to_bbox(&b.bbox) > d {
+            return;
+        }
+
+        for p in &a.points {
+            for q in b.points.iter().filter(|q| (q.0 - p.0).norm() <= d) {
+                f(&p.0, &p.1, &q.0, &q.1);
+            }
+        }
+
+        // --
+        // Points held by either quad are searched for in the children of the other one, then the
+        // children of both are matched up.
+        for p in &a.points {
+            for id in b.children.iter().flatten() {
+                self._find_within_radius(&p.0, d, id, &mut |q, payload| f(&p.0, &p.1, q, payload));
+            }
+        }
+
+        for q in &b.points {
+            for id in a.children.iter().flatten() {
+                self._find_within_radius(&q.0, d, id, &mut |p, payload| f(p, payload, &q.0, &q.1));
+            }
+        }
+
+        for a_child in a.children.iter().flatten() {
+            for b_child in b.children.iter().flatten() {
+                self._find_pairs_between(d, a_child, b_child, f);
+            }
         }
     }
-}
 
-impl<P: IsPayload> HasId for Quad<P> {
-    type Id = Id;
-    fn get_id(&self) -> Self::Id {
-        self.id
+    /// Returns the id of the quad whose bucket holds the given point.
+    fn _find_quad(&self, p: &Vec2<S>, quad_id: &Id) -> Option<Id> {
+        let quad_ref = self.arena.get_node(quad_id).expect("could not find node");
+        let quad = quad_ref.read().unwrap();
+
+        // If the bbox itself doesn't contain the point, then the point could not possibly be
+        // contained in this node or any subtrees of this node.
+        if !quad.bbox.contains(p) {
+            return None;
+        }
+
+        // Let's see if any of the points stored at this node match.
+        if quad.points.iter().any(|node| node.0 == *p) {
+            return Some(*quad_id);
+        }
+
+        // Otherwise, we'll need to look in the subtree which contains the point (if there is one).
+        let children = quad.children.as_ref()?;
+        let child = children.iter().find(|id| {
+            let child_ref = self.arena.get_node(id).expect("could not find node");
+            let contains = child_ref.read().unwrap().bbox.contains(p);
+            contains
+        })?;
+
+        self._find_quad(p, child)
+    }
+
+    fn _remove(&mut self, p: &Vec2<S>, quad_id: &Id) -> Option<Node<P, S>> {
+        let quad_ref = self.arena.get_node_mut(quad_id).expect("could not find node");
+
+        let children = {
+            let mut quad = quad_ref.write().unwrap();
+
+            if !quad.bbox.contains(p) {
+                return None;
+            }
+
+            if let Some(idx) = quad.points.iter().position(|node| node.0 == *p) {
+                return Some(quad.points.remove(idx));
+            }
+
+            quad.children?
+        };
+
+        let removed = children.iter().find_map(|id| self._remove(p, id))?;
+
+        // --
+        // Collapse the children back into this quad if none of them hold a point anymore.
+        let all_empty = children.iter().all(|id| {
+            let child_ref = self.arena.get_node(id).expect("could not find node");
+            let child = child_ref.read().unwrap();
+            child.points.is_empty() && child.children.is_none()
+        });
+
+        if all_empty {
+            for id in &children {
+                self.arena.delete_node(id).expect("could not delete node");
+            }
+            quad_ref.write().unwrap().children = None;
+        }
+
+        Some(removed)
+    }
+
+    fn _remove_within(&mut self, bbox: &BBox2D<S>, quad_id: &Id, out: &mut Vec<Node<P, S>>) {
+        let quad_ref = self.arena.get_node_mut(quad_id).expect("could not find node");
+
+        let children = {
+            let mut quad = quad_ref.write().unwrap();
+
+            if !quad.bbox.intersects(bbox) {
+                return;
+            }
+
+            // --
+            // Every point below a quad covered by the BBox is removed, so the whole subtree goes.
+            if bbox.contains_bbox(&quad.bbox) {
+                out.append(&mut quad.points);
+
+                let mut stack: Vec<Id> = quad.children.take().into_iter().flatten().collect();
+                while let Some(id) = stack.pop() {
+                    let child_ref = self.arena.get_node_mut(&id).expect("could not find node");
+                    let mut child = child_ref.write().unwrap();
+                    out.append(&mut child.points);
+                    stack.extend(child.children.into_iter().flatten());
+
+                    drop(child);
+                    self.arena.delete_node(&id).expect("could not delete node");
+                }
+                return;
+            }
+
+            let (removed, kept): (Vec<_>, Vec<_>) = core::mem::take(&mut quad.points).into_iter()
+                .partition(|node| bbox.contains(&node.0));
+            quad.points = kept;
+            out.extend(removed);
+
+            match quad.children {
+                None => return,
+                Some(children) => children
+            }
+        };
+
+        for id in &children {
+            self._remove_within(bbox, id, out);
+        }
+
+        // --
+        // Collapse the children back into this quad if none of them hold a point anymore.
+        let all_empty = children.iter().all(|id| {
+            let child_ref = self.arena.get_node(id).expect("could not find node");
+            let child = child_ref.read().unwrap();
+            child.points.is_empty() && child.children.is_none()
+        });
+
+        if all_empty {
+            for id in &children {
+                self.arena.delete_node(id).expect("could not delete node");
+            }
+            quad_ref.write().unwrap().children = None;
+        }
+    }
+
+    /// Inserts the given points, which must lie inside of the tree and be unique among themselves,
+    /// returning the number inserted. Points which are already taken are skipped. See
+    /// 'QuadtreeWriter'.
+    pub(crate) fn insert_batch(&mut self, points: Vec<Node<P, S>>) -> usize {
+        let root = self.root_id;
+        let inserted = self._insert_batch(points, &root);
+        self.size.fetch_add(inserted, Ordering::SeqCst);
+        inserted
+    }
+
+    /// Hands the points down to the quads containing them, locking every quad on the way once for
+    /// the whole batch. Full quads are subdivided in one go, just like 'from_points' builds them.
+    fn _insert_batch(&mut self, mut points: Vec<Node<P, S>>, quad_id: &Id) -> usize {
+        let quad_ref = self.arena.get_node_mut(quad_id).expect("could not find node");
+        let mut quad = quad_ref.write().unwrap();
+
+        points.retain(|node| !quad.points.iter().any(|other| other.0 == node.0));
+
+        let children = match quad.children {
+            Some(children) => children,
+            None => {
+                let inserted = points.len();
+                quad.points.append(&mut points);
+
+                if quad.points.len() > self.config.bucket_capacity && quad.depth < self.config.max_depth {
+                    let split_at: SplitFn<P, S> = match self.pivot {
+                        Pivot::Point => |_, points| points[0].0,
+                        Pivot::Midpoint => |bbox, _| bbox.mid()
+                    };
+
+                    let points = core::mem::take(&mut quad.points);
+                    let boxes = quad.bbox.subdivide(&split_at(&quad.bbox, &points));
+                    let mut buckets: [Vec<Node<P, S>>; 4] = Default::default();
+                    for node in points {
+                        if let Some(idx) = boxes.iter().position(|child| child.contains(&node.0)) {
+                            buckets[idx].push(node);
+                        }
+                    }
+
+                    let depth = quad.depth + 1;
+                    let mut buckets = buckets.into_iter();
+                    quad.children = Some(boxes.map(|child| {
+                        Self::_build(&mut self.arena, &self.config, buckets.next().unwrap(), child, depth, split_at)
+                    }));
+                }
+
+                return inserted;
+            }
+        };
+
+        // --
+        // Hand each point to the first child which contains it, exactly as '_insert' would.
+        let boxes = children.map(|id| self.arena.get_node(&id).expect("could not find node").read().unwrap().bbox);
+        let mut buckets: [Vec<Node<P, S>>; 4] = Default::default();
+        for node in points {
+            if let Some(idx) = boxes.iter().position(|child| child.contains(&node.0)) {
+                buckets[idx].push(node);
+            }
+        }
+
+        let mut inserted = 0;
+        for (child_id, bucket) in children.iter().zip(buckets) {
+            if !bucket.is_empty() {
+                inserted += self._insert_batch(bucket, child_id);
+            }
+        }
+        inserted
+    }
+
+    fn _insert(&mut self, elem: Node<P, S>, quad_id: &Id) -> bool {
+        let quad_ref = self.arena.get_node_mut(quad_id).expect("could not find node");
+        let mut quad = quad_ref.write().unwrap();
+
+        if !quad.bbox.contains(&elem.0) {
+            return false;
+        }
+
+        if quad.points.iter().any(|node| node.0 == elem.0) {
+            return false;
+        }
+
+        if quad.children.is_none() {
+            if quad.points.len() < self.config.bucket_capacity || quad.depth >= self.config.max_depth {
+                quad.points.push(elem);
+                return true;
+            }
+
+            // --
+            // Subdivide since this quad is full, moving its points down into the new children
+            // just like '_build' does, so that only leaves hold points.
+            let pivot = match self.pivot {
+                Pivot::Point => quad.points[0].0,
+                Pivot::Midpoint => quad.bbox.mid()
+            };
+
+            let depth = quad.depth + 1;
+            let boxes = quad.bbox.subdivide(&pivot);
+
+            let children = boxes.map(|bbox| {
+                let new_id: Id = self.arena.get_new_id();
+                self.arena.add_node(Quad::<P, S>::new(new_id, bbox, depth)).expect("could not add node!");
+                new_id
+            });
+            quad.children = Some(children);
+
+            for node in core::mem::take(&mut quad.points) {
+                self._insert_into_children(node, &children);
+            }
+        }
+
+        // --
+        // Then insert the point into the child which contains it.
+        let children = quad.children.unwrap();
+        self._insert_into_children(elem, &children)
+    }
+
+    /// Inserts the point into the first of the given children which contains it.
+    fn _insert_into_children(&mut self, elem: Node<P, S>, children: &[Id; 4]) -> bool {
+        let child = children.iter().find(|id| {
+            let child_ref = self.arena.get_node(id).expect("could not find node");
+            let contains = child_ref.read().unwrap().bbox.contains(&elem.0);
+            contains
+        }).copied();
+
+        match child {
+            None => false,
+            Some(child) => self._insert(elem, &child)
+        }
+    }
+}
+
+impl<P: IsPayload, S: IsScalar> PointQuadtree<P, S> {
+
+    /// Takes a snapshot of the tree, which it can be rolled back to with 'restore'. Quads are
+    /// shared with the snapshot and only copied once they are touched again.
+    pub fn snapshot(&mut self) -> QuadtreeSnapshot<P, S> {
+        QuadtreeSnapshot {
+            arena: self.arena.snapshot(),
+            root_id: self.root_id,
+            size: self.len()
+        }
+    }
+
+    /// Rolls the tree back to the given snapshot, which must have been taken from this tree. The
+    /// snapshot stays valid, so the tree can be rolled back to it any number of times.
+    pub fn restore(&mut self, snapshot: &QuadtreeSnapshot<P, S>) {
+        self.arena.restore(&snapshot.arena);
+        self.root_id = snapshot.root_id;
+        self.size.store(snapshot.size, Ordering::SeqCst);
+    }
+
+    /// Returns a copy of every point in the tree, in the order in which 'for_each' visits them.
+    /// This clones every payload, so prefer 'for_each' to just look at the points.
+    pub fn to_vec(&self) -> Vec<Node<P, S>> {
+        let mut result = Vec::with_capacity(self.len());
+        self.for_each(|p, payload| result.push((*p, payload.clone())));
+        result
+    }
+
+    /// Returns all points in the tree within the given BBox.
+    pub fn find_within(&self, bbox: &BBox2D<S>) -> Vec<Node<P, S>> {
+        let mut result = vec![];
+        self.find_within_with(bbox, |p, payload| result.push((*p, payload.clone())));
+        result
+    }
+
+    /// Returns every point in the tree accepted by the given predicates, see 'query_filtered_with'.
+    pub fn query_filtered(
+        &self,
+        accept_bbox: impl Fn(&BBox2D<S>) -> bool,
+        accept_point: impl Fn(&Vec2<S>, &P) -> bool
+    ) -> Vec<Node<P, S>> {
+        let mut result = vec![];
+        self.query_filtered_with(accept_bbox, accept_point, |p, payload| result.push((*p, payload.clone())));
+        result
+    }
+
+    /// Returns all points in the tree inside of the polygon with the given vertices, see
+    /// 'find_within_polygon_with'.
+    pub fn find_within_polygon(&self, polygon: &[Vec2<S>]) -> Vec<Node<P, S>> {
+        let mut result = vec![];
+        self.find_within_polygon_with(polygon, |p, payload| result.push((*p, payload.clone())));
+        result
+    }
+
+    /// Same as 'find_within' for every given BBox, with the queries spread across rayon's thread
+    /// pool. The results are in the order of the BBoxes. This requires the "rayon" feature.
+    #[cfg(feature = "rayon")]
+    pub fn par_find_within_many(&self, bboxes: &[BBox2D<S>]) -> Vec<Vec<Node<P, S>>> {
+        bboxes.par_iter().map(|bbox| self.find_within(bbox)).collect()
+    }
+
+    /// Returns a point within the given BBox picked uniformly at random, or None if there are no
+    /// points within it.
+    ///
+    /// Quads don't know how many points they hold, so every point within the BBox is visited once
+    /// (reservoir sampling), but only a handful of them are ever cloned and none are collected.
+    pub fn sample_within(&self, bbox: &BBox2D<S>, rng: &mut impl Rng) -> Option<Node<P, S>> {
+        let mut sample = None;
+        let mut seen = 0;
+
+        self.find_within_with(bbox, |p, payload| {
+            // The n-th point replaces the sample with a probability of 1/n.
+            seen += 1;
+            if rng.below(seen) == 0 {
+                sample = Some((*p, payload.clone()));
+            }
+        });
+
+        sample
+    }
+
+    /// Returns all points in the tree within 'radius' of the given center.
+    pub fn find_within_radius(&self, center: &Vec2<S>, radius: S) -> Vec<Node<P, S>> {
+        let mut result = vec![];
+        self.find_within_radius_with(center, radius, |p, payload| result.push((*p, payload.clone())));
+        result
+    }
+
+    /// Returns all points in the tree within 'tolerance' of the line segment from 'a' to 'b'.
+    pub fn find_along_segment(&self, a: &Vec2<S>, b: &Vec2<S>, tolerance: S) -> Vec<Node<P, S>> {
+        let mut result = vec![];
+        self.find_along_segment_with(a, b, tolerance, |p, payload| result.push((*p, payload.clone())));
+        result
+    }
+
+    /// Returns every pair of points in the tree which are within 'd' of each other, see
+    /// 'find_pairs_within_with'.
+    pub fn find_pairs_within(&self, d: S) -> Vec<(Node<P, S>, Node<P, S>)> {
+        let mut result = vec![];
+        self.find_pairs_within_with(d, |p, p_payload, q, q_payload| {
+            result.push(((*p, p_payload.clone()), (*q, q_payload.clone())));
+        });
+        result
+    }
+
+    /// Searches the tree for the given point.
+    pub fn find(&self, p: &Vec2<S>) -> Option<Node<P, S>> {
+        self.find_with(p, |p, payload| (*p, payload.clone()))
+    }
+
+    /// Returns the point in the tree closest to the given point.
+    pub fn nearest(&self, p: &Vec2<S>) -> Option<Node<P, S>> {
+        let mut best: Option<(Node<P, S>, S)> = None;
+
+        let mut queue = BinaryHeap::new();
+        queue.push(Candidate { dist: S::zero(), item: self.root_id });
+
+        while let Some(Candidate { dist, item: id }) = queue.pop() {
+            // Quads are visited closest-first, so once the closest remaining quad is further away
+            // than the best point found so far, none of the remaining quads can do any better.
+            if matches!(&best, Some((_, best_dist)) if dist > *best_dist) {
+                break;
+            }
+
+            let quad_ref = self.arena.get_node(&id).expect("could not find node");
+            let quad = quad_ref.read().unwrap();
+
+            for node in &quad.points {
+                let node_dist = (node.0 - p).norm();
+                if !matches!(&best, Some((_, best_dist)) if node_dist >= *best_dist) {
+                    best = Some((node.clone(), node_dist));
+                }
+            }
+
+            if let Some(children) = &quad.children {
+                for child in children {
+                    let child_ref = self.arena.get_node(child).expect("could not find node");
+                    let dist = child_ref.read().unwrap().bbox.distance_to_point(p);
+                    queue.push(Candidate { dist, item: *child });
+                }
+            }
+        }
+
+        best.map(|(node, _)| node)
+    }
+
+    /// Returns the 'k' points in the tree closest to the given point along with their distances,
+    /// sorted from closest to furthest.
+    pub fn knn(&self, p: &Vec2<S>, k: usize) -> Vec<(Node<P, S>, S)> {
+        if k == 0 {
+            return vec![];
+        }
+
+        // The furthest of the best 'k' points found so far sits at the top of this heap.
+        let mut best: BinaryHeap<Reverse<Candidate<Node<P, S>, S>>> = BinaryHeap::with_capacity(k + 1);
+
+        let mut queue = BinaryHeap::new();
+        queue.push(Candidate { dist: S::zero(), item: self.root_id });
+
+        while let Some(Candidate { dist, item: id }) = queue.pop() {
+            // Once we have 'k' points, any quad further away than the k-th best can be pruned.
+            if best.len() == k && dist > best.peek().unwrap().0.dist {
+                break;
+            }
+
+            let quad_ref = self.arena.get_node(&id).expect("could not find node");
+            let quad = quad_ref.read().unwrap();
+
+            for node in &quad.points {
+                best.push(Reverse(Candidate { dist: (node.0 - p).norm(), item: node.clone() }));
+                if best.len() > k {
+                    best.pop();
+                }
+            }
+
+            if let Some(children) = &quad.children {
+                for child in children {
+                    let child_ref = self.arena.get_node(child).expect("could not find node");
+                    let dist = child_ref.read().unwrap().bbox.distance_to_point(p);
+                    queue.push(Candidate { dist, item: *child });
+                }
+            }
+        }
+
+        best.into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(Candidate { dist, item })| (item, dist))
+            .collect()
+    }
+
+    /// Returns the 2 closest points in the tree along with their distance, or None if the tree
+    /// holds fewer than 2 points.
+    ///
+    /// Pairs of quads are searched at once, closest first, so that a close pair found early on
+    /// rules out most other pairs of quads, which is much faster than calling 'nearest' for every
+    /// point.
+    pub fn closest_pair(&self) -> Option<NodePair<P, S>> {
+        let mut nodes = Vec::with_capacity(self.len());
+        let tree = self.dual_tree(&mut nodes);
+
+        tree.closest_pair().map(|(i, j, d)| (nodes[i].clone(), nodes[j].clone(), d))
+    }
+
+    /// Returns every point in the tree along with the closest other point and their distance, in
+    /// the order in which 'for_each' visits them. Returns nothing if the tree holds fewer than 2
+    /// points.
+    ///
+    /// Like 'closest_pair', the neighbors of all points in a quad are searched for at once, which is
+    /// much faster than calling 'nearest' for every point.
+    pub fn all_nearest_neighbors(&self) -> Vec<NodePair<P, S>> {
+        let mut nodes = Vec::with_capacity(self.len());
+        let tree = self.dual_tree(&mut nodes);
+
+        tree.all_nearest().into_iter()
+            .enumerate()
+            .filter_map(|(i, nearest)| nearest.map(|(j, d)| (nodes[i].clone(), nodes[j].clone(), d)))
+            .collect()
+    }
+
+    /// Copies the quads of the tree into a DualTree, and their points into 'nodes' in the same
+    /// order.
+    fn dual_tree(&self, nodes: &mut Vec<Node<P, S>>) -> DualTree<S> {
+        let mut tree = DualTree::new();
+        self._dual_tree(&self.root_id, None, &mut tree, nodes);
+        tree
+    }
+
+    fn _dual_tree(&self, quad_id: &Id, parent: Option<usize>, tree: &mut DualTree<S>, nodes: &mut Vec<Node<P, S>>) {
+        let quad_ref = self.arena.get_node(quad_id).expect("could not find node");
+        let quad = quad_ref.read().unwrap();
+
+        let idx = tree.open(quad.bbox, parent);
+
+        // Points held by a subdivided quad go into a leaf of their own.
+        let leaf = match quad.children {
+            None => idx,
+            Some(_) => tree.open(quad.bbox, Some(idx))
+        };
+        for node in &quad.points {
+            tree.push(node.0);
+            nodes.push(node.clone());
+        }
+        tree.close(leaf);
+
+        for child_id in quad.children.iter().flatten() {
+            self._dual_tree(child_id, Some(idx), tree, nodes);
+        }
+        tree.close(idx);
+    }
+}
+
+/// Compares 2 coordinates, which are never NaN.
+pub(super) fn cmp_scalar<S: IsScalar>(a: &S, b: &S) -> core::cmp::Ordering {
+    a.partial_cmp(b).unwrap_or(core::cmp::Ordering::Equal)
+}
+
+/// Returns the distance from 'p' to the closest point on the line segment from 'a' to 'b'.
+fn distance_to_segment<S: IsScalar>(p: &Vec2<S>, a: &Vec2<S>, b: &Vec2<S>) -> S {
+    let dir = b - a;
+    let len_squared = dir.norm_squared();
+
+    if len_squared == S::zero() {
+        return (p - a).norm();
+    }
+
+    let t = ((p - a).dot(&dir) / len_squared).clamp(S::zero(), S::one());
+    (p - (a + dir * t)).norm()
+}
+
+/// Returns the point whose coordinates are the medians of the given points' coordinates, or the
+/// midpoint of the BBox if splitting around the median would leave every point in the same quad.
+fn median_pivot<P, S: IsScalar>(bbox: &BBox2D<S>, points: &[Node<P, S>]) -> Vec2<S> {
+    let mut xs: Vec<S> = points.iter().map(|node| node.0.x).collect();
+    let mut ys: Vec<S> = points.iter().map(|node| node.0.y).collect();
+
+    let mid = points.len() / 2;
+    let (_, x, _) = xs.select_nth_unstable_by(mid, cmp_scalar);
+    let (_, y, _) = ys.select_nth_unstable_by(mid, cmp_scalar);
+    let median = Vec2::new(*x, *y);
+
+    let boxes = bbox.subdivide(&median);
+    let quad_of = |p: &Vec2<S>| boxes.iter().position(|child| child.contains(p));
+
+    let first = quad_of(&points[0].0);
+    if points.iter().all(|node| quad_of(&node.0) == first) {
+        return bbox.mid();
+    }
+
+    median
+}
+
+impl<P: Send + Sync, S: IsScalar> IntoIterator for PointQuadtree<P, S> {
+    type Item = Node<P, S>;
+    type IntoIter = alloc::vec::IntoIter<Node<P, S>>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        self.take_points().into_iter()
+    }
+}
+
+/// The first bytes of the binary format written by 'PointQuadtree::write_to', followed by its
+/// version.
+#[cfg(feature = "std")]
+const MAGIC: &[u8; 4] = b"ARQT";
+#[cfg(feature = "std")]
+const FORMAT_VERSION: u8 = 1;
+
+#[cfg(feature = "std")]
+impl<P: Packed + Send + Sync, S: IsScalar + Packed> PointQuadtree<P, S> {
+
+    /// Writes the tree to 'w' in a compact binary format, which 'read_from' restores the exact same
+    /// tree from. Wrap 'w' in a BufWriter unless it is buffered already.
+    ///
+    /// The format starts with a header holding its version, the width of the scalar type, the
+    /// config, the bbox of the tree and its number of points. The quads follow in pre-order, each
+    /// as a flag telling whether it is subdivided, its points, and the point it is subdivided
+    /// around if so. Child bboxes follow from that point, so no bboxes or arena ids are stored.
+    pub fn write_to<W: Write>(&self, mut w: W) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        FORMAT_VERSION.write_packed(&mut w)?;
+        (core::mem::size_of::<S>() as u8).write_packed(&mut w)?;
+
+        self.config.bucket_capacity.write_packed(&mut w)?;
+        self.config.max_depth.write_packed(&mut w)?;
+        (matches!(self.pivot, Pivot::Point) as u8).write_packed(&mut w)?;
+
+        let bbox = self.bbox();
+        for scalar in [bbox.min.x, bbox.min.y, bbox.max.x, bbox.max.y] {
+            scalar.write_packed(&mut w)?;
+        }
+        self.len().write_packed(&mut w)?;
+
+        let mut stack = vec![self.root_id];
+        while let Some(id) = stack.pop() {
+            let quad_ref = self.arena.get_node(&id).expect("could not find node");
+            let quad = quad_ref.read().unwrap();
+
+            quad.children.is_some().write_packed(&mut w)?;
+            quad.points.len().write_packed(&mut w)?;
+            for (p, payload) in &quad.points {
+                p.x.write_packed(&mut w)?;
+                p.y.write_packed(&mut w)?;
+                payload.write_packed(&mut w)?;
+            }
+
+            // The SW child spans from the quad's min to the point it was subdivided around.
+            if let Some(children) = &quad.children {
+                let sw_ref = self.arena.get_node(&children[0]).expect("could not find node");
+                let mid = sw_ref.read().unwrap().bbox.max;
+                mid.x.write_packed(&mut w)?;
+                mid.y.write_packed(&mut w)?;
+
+                stack.extend(children.iter().rev());
+            }
+        }
+
+        w.flush()
+    }
+
+    /// Reads a tree written by 'write_to' from 'r', returning an error of kind InvalidData if the
+    /// input isn't in the format (or has a different version or scalar type). Wrap 'r' in a
+    /// BufReader unless it is buffered already.
+    pub fn read_from<R: Read>(mut r: R) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if magic != *MAGIC {
+            return Err(invalid_data("input is not a quadtree"));
+        }
+        if u8::read_packed(&mut r)? != FORMAT_VERSION {
+            return Err(invalid_data("unsupported format version"));
+        }
+        if u8::read_packed(&mut r)? as usize != core::mem::size_of::<S>() {
+            return Err(invalid_data("scalar type doesn't match"));
+        }
+
+        let config = QuadtreeConfig {
+            bucket_capacity: usize::read_packed(&mut r)?,
+            max_depth: usize::read_packed(&mut r)?
+        };
+        if config.bucket_capacity == 0 {
+            return Err(invalid_data("bucket capacity must be at least 1"));
+        }
+        let pivot = match u8::read_packed(&mut r)? {
+            0 => Pivot::Midpoint,
+            1 => Pivot::Point,
+            _ => return Err(invalid_data("invalid pivot"))
+        };
+
+        let min = Vec2::new(S::read_packed(&mut r)?, S::read_packed(&mut r)?);
+        let max = Vec2::new(S::read_packed(&mut r)?, S::read_packed(&mut r)?);
+        let bbox = BBox2D { min, max };
+        let size = usize::read_packed(&mut r)?;
+
+        // --
+        // Rebuild the quads in the same pre-order they were written in.
+        let mut arena = GenerationalArena::new();
+        let root_id = arena.get_new_id();
+        let mut stack = vec![(root_id, bbox, 0)];
+        let mut points_read = 0;
+
+        while let Some((id, bbox, depth)) = stack.pop() {
+            let mut quad = Quad::<P, S>::new(id, bbox, depth);
+            let subdivided = bool::read_packed(&mut r)?;
+
+            let len = usize::read_packed(&mut r)?;
+            for _ in 0..len {
+                let p = Vec2::new(S::read_packed(&mut r)?, S::read_packed(&mut r)?);
+                if !bbox.contains(&p) {
+                    return Err(invalid_data("point lies outside of its quad"));
+                }
+                quad.points.push((p, P::read_packed(&mut r)?));
+            }
+            points_read += len;
+
+            if subdivided {
+                let mid = Vec2::new(S::read_packed(&mut r)?, S::read_packed(&mut r)?);
+                if !(bbox.min <= mid && mid <= bbox.max) {
+                    return Err(invalid_data("quad is subdivided outside of its bbox"));
+                }
+
+                let boxes = bbox.subdivide(&mid);
+                let children = boxes.map(|_| arena.get_new_id());
+                for (child_id, child_bbox) in children.iter().zip(boxes).rev() {
+                    stack.push((*child_id, child_bbox, depth + 1));
+                }
+                quad.children = Some(children);
+            }
+
+            arena.add_node(quad).expect("could not add node!");
+        }
+
+        if points_read != size {
+            return Err(invalid_data("number of points doesn't match"));
+        }
+
+        Ok(Self {
+            arena,
+            root_id,
+            size: AtomicUsize::new(size),
+            config,
+            pivot
+        })
+    }
+}
+
+impl<P, S: IsScalar> Quad<P, S> {
+    pub fn new(id: Id, bbox: BBox2D<S>, depth: usize) -> Self {
+        Self {
+            id,
+            bbox,
+            depth,
+            points: vec![],
+            children: None,
+        }
+    }
+}
+
+impl<P: Send + Sync, S: IsScalar> HasId for Quad<P, S> {
+    type Id = Id;
+    fn get_id(&self) -> Self::Id {
+        self.id
+    }
+}
+
+impl<P: Debug + Send + Sync, S: IsScalar> ToDot for PointQuadtree<P, S> {
+    fn to_dot(&self) -> String {
+        let mut dot = DotWriter::new("PointQuadtree");
+        let mut stack = vec![self.root_id];
+
+        while let Some(id) = stack.pop() {
+            let quad_ref = self.arena.get_node(&id).expect("could not find node");
+            let quad = quad_ref.read().unwrap();
+
+            let mut label = format!(
+                "({}, {}) - ({}, {})",
+                quad.bbox.min.x, quad.bbox.min.y, quad.bbox.max.x, quad.bbox.max.y
+            );
+            for (p, payload) in &quad.points {
+                label.push_str(&format!("\n({}, {}): {:?}", p.x, p.y, payload));
+            }
+            dot.node(&id, &label, "shape=box");
+
+            // The ordering goes SW, SE, NE, NW
+            if let Some(children) = &quad.children {
+                for (child_id, name) in children.iter().zip(["SW", "SE", "NE", "NW"]) {
+                    dot.edge(&id, child_id, name, "");
+                    stack.push(*child_id);
+                }
+            }
+        }
+
+        dot.finish()
+    }
+}
+
+impl<P: Send + Sync, S: IsScalar> PointQuadtree<P, S> {
+    /// Returns the number of points below the given quad, counting the quads which are subdivided
+    /// in 'parents' and their children holding any points in 'branches'.
+    fn _count_branches(&self, id: &Id, parents: &mut usize, branches: &mut usize) -> usize {
+        let quad_ref = self.arena.get_node(id).expect("could not find node");
+        let quad = quad_ref.read().unwrap();
+
+        let mut count = quad.points.len();
+        if let Some(children) = &quad.children {
+            *parents += 1;
+            for child_id in children {
+                let below = self._count_branches(child_id, parents, branches);
+                if below > 0 {
+                    *branches += 1;
+                }
+                count += below;
+            }
+        }
+
+        count
+    }
+}
+
+impl<P: Send + Sync, S: IsScalar> Metrics for PointQuadtree<P, S> {
+    fn depth(&self) -> usize {
+        PointQuadtree::depth(self)
+    }
+
+    fn node_count(&self) -> usize {
+        PointQuadtree::node_count(self)
+    }
+
+    // Every subdivided quad has 4 children, so only the ones holding points are counted.
+    fn avg_branching_factor(&self) -> f64 {
+        let (mut parents, mut branches) = (0, 0);
+        self._count_branches(&self.root_id, &mut parents, &mut branches);
+
+        match parents {
+            0 => 0.0,
+            _ => branches as f64 / parents as f64
+        }
+    }
+
+    fn memory_bytes_estimate(&self) -> usize {
+        let mut bytes = self.arena.stats().approx_bytes;
+        let mut stack = vec![self.root_id];
+
+        while let Some(id) = stack.pop() {
+            let quad_ref = self.arena.get_node(&id).expect("could not find node");
+            let quad = quad_ref.read().unwrap();
+
+            bytes += quad.points.capacity() * core::mem::size_of::<Node<P, S>>();
+            stack.extend(quad.children.iter().flatten());
+        }
+
+        bytes
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde::de::Error;
+
+    use super::*;
+
+    /// The serialized form of a quad, where children are given as indices into the list of quads.
+    #[derive(Serialize, Deserialize)]
+    struct QuadRepr<P, S: IsScalar> {
+        bbox: BBox2D<S>,
+        points: Vec<Node<P, S>>,
+        children: Option<[usize; 4]>
+    }
+
+    /// The serialized form of a PointQuadtree, whose quads are stored in pre-order starting at the
+    /// root.
+    #[derive(Serialize, Deserialize)]
+    struct QuadtreeRepr<P, S: IsScalar> {
+        config: QuadtreeConfig,
+        pivot: Pivot,
+        quads: Vec<QuadRepr<P, S>>
+    }
+
+    impl<P: IsPayload, S: IsScalar> PointQuadtree<P, S> {
+        /// Appends the subtree rooted at the given quad to 'out' in pre-order, returning the index
+        /// of the quad.
+        fn flatten(&self, quad_id: &Id, out: &mut Vec<QuadRepr<P, S>>) -> usize {
+            let quad_ref = self.arena.get_node(quad_id).expect("could not find node");
+            let quad = quad_ref.read().unwrap();
+
+            let idx = out.len();
+            out.push(QuadRepr { bbox: quad.bbox, points: quad.points.clone(), children: None });
+
+            if let Some(children) = &quad.children {
+                out[idx].children = Some(children.map(|id| self.flatten(&id, out)));
+            }
+
+            idx
+        }
+    }
+
+    impl<P: IsPayload + Serialize, S: IsScalar + Serialize> Serialize for PointQuadtree<P, S> {
+        fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+            let mut quads = vec![];
+            self.flatten(&self.root_id, &mut quads);
+
+            QuadtreeRepr { config: self.config, pivot: self.pivot, quads }.serialize(serializer)
+        }
+    }
+
+    impl<'de, P, S> Deserialize<'de> for PointQuadtree<P, S>
+        where P: IsPayload + Deserialize<'de>, S: IsScalar + Deserialize<'de>
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let repr = QuadtreeRepr::<P, S>::deserialize(deserializer)?;
+            if repr.quads.is_empty() {
+                return Err(D::Error::custom("quadtree is missing its root quad"));
+            }
+
+            let mut arena = GenerationalArena::<Quad<P, S>>::new();
+            let ids: Vec<Id> = repr.quads.iter().map(|_| arena.get_new_id()).collect();
+
+            // Quads are stored in pre-order, so every child must come after its parent and have
+            // exactly one parent; this rules out cycles and shared subtrees.
+            let mut has_parent = vec![false; ids.len()];
+            let mut depths = vec![0; ids.len()];
+            let mut size = 0;
+
+            for (idx, quad) in repr.quads.into_iter().enumerate() {
+                size += quad.points.len();
+
+                let children = match quad.children {
+                    None => None,
+                    Some(children) => {
+                        for child in children {
+                            if child <= idx || child >= ids.len() || has_parent[child] {
+                                return Err(D::Error::custom("quadtree contains an invalid child link"));
+                            }
+                            has_parent[child] = true;
+                            depths[child] = depths[idx] + 1;
+                        }
+                        Some(children.map(|child| ids[child]))
+                    }
+                };
+
+                let node = Quad::<P, S> {
+                    id: ids[idx],
+                    bbox: quad.bbox,
+                    depth: depths[idx],
+                    points: quad.points,
+                    children
+                };
+                arena.add_node(node).map_err(D::Error::custom)?;
+            }
+
+            Ok(Self {
+                arena,
+                root_id: ids[0],
+                size: AtomicUsize::new(size),
+                config: repr.config,
+                pivot: repr.pivot
+            })
+        }
     }
 }
@@ -1,44 +1,193 @@
 extern crate nalgebra as na;
 
+/// This is the trait bound for the scalar type of coordinates, e.g. f32 or f64. Quads get split in
+/// half, so the scalar has to be a real number rather than an integer.
+pub trait IsScalar: na::RealField + Copy {}
+
+impl<S: na::RealField + Copy> IsScalar for S {}
+
 /// Quadtrees exist in 2-dimensional space
-pub type Vec2 = na::Vector2<f32>;
+pub type Vec2<S = f32> = na::Vector2<S>;
 
 /// This is a 2D axis-aligned bounding box (AABB).
-#[derive(Default, Debug, Copy, Clone)]
-pub struct BBox2D {
-    pub min: Vec2,
-    pub max: Vec2
+#[derive(Default, Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BBox2D<S: IsScalar = f32> {
+    pub min: Vec2<S>,
+    pub max: Vec2<S>
 }
 
 /// This defines a range of values.
-struct Range(pub (f32, f32));
+struct Range<S>(pub (S, S));
 
-impl Range {
+impl<S: IsScalar> Range<S> {
     /// Returns true if this Range intersects the given range.
-    pub fn intersects(&self, other: &Range) -> bool {
+    pub fn intersects(&self, other: &Range<S>) -> bool {
         self.0.1 >= other.0.0 && other.0.1 >= self.0.0
     }
 }
 
-impl BBox2D {
+impl<S: IsScalar> BBox2D<S> {
     /// Returns true if the BBox contains the given point.
-    pub fn contains(&self, p: &Vec2) -> bool {
+    pub fn contains(&self, p: &Vec2<S>) -> bool {
         self.min <= *p && *p < self.max
     }
 
+    /// Returns true if the given BBox lies entirely inside of this BBox.
+    pub fn contains_bbox(&self, other: &BBox2D<S>) -> bool {
+        self.min <= other.min && other.max <= self.max
+    }
+
     /// Returns true if the BBox intersects the given BBox.
-    pub fn intersects(&self, other: &BBox2D) -> bool {
+    pub fn intersects(&self, other: &BBox2D<S>) -> bool {
         self.xrange().intersects(&other.xrange()) &&
         self.yrange().intersects(&other.yrange())
     }
 
+    /// Returns true if the line segment from 'a' to 'b' passes through the BBox.
+    pub fn intersects_segment(&self, a: &Vec2<S>, b: &Vec2<S>) -> bool {
+        // Clip the segment against the slab between the BBox's bounds along each axis in turn,
+        // keeping track of the part of the segment which lies within all of them so far.
+        let (mut t_min, mut t_max) = (S::zero(), S::one());
+        let dir = b - a;
+
+        for axis in 0..2 {
+            if dir[axis] == S::zero() {
+                if a[axis] < self.min[axis] || a[axis] > self.max[axis] {
+                    return false;
+                }
+                continue;
+            }
+
+            let t0 = (self.min[axis] - a[axis]) / dir[axis];
+            let t1 = (self.max[axis] - a[axis]) / dir[axis];
+
+            t_min = t_min.max(t0.min(t1));
+            t_max = t_max.min(t0.max(t1));
+
+            if t_min > t_max {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Returns true if the polygon with the given vertices overlaps the BBox, see 'polygon_contains'.
+    pub fn intersects_polygon(&self, polygon: &[Vec2<S>]) -> bool {
+        // Either an edge of the polygon passes through the BBox, or the BBox lies entirely inside
+        // of the polygon.
+        polygon_edges(polygon).any(|(a, b)| self.intersects_segment(a, b)) || polygon_contains(polygon, &self.min)
+    }
+
+    /// Returns true if the BBox lies entirely inside of the polygon with the given vertices. An edge
+    /// touching the BBox counts as leaving it, so this may be false for BBoxes just inside.
+    pub fn inside_polygon(&self, polygon: &[Vec2<S>]) -> bool {
+        !polygon_edges(polygon).any(|(a, b)| self.intersects_segment(a, b)) && polygon_contains(polygon, &self.min)
+    }
+
+    /// Returns the smallest 't >= 0' at which the ray 'origin + t * dir' touches the BBox, which is
+    /// 0 if 'origin' lies inside of it, or None if the ray misses the BBox.
+    pub fn ray_entry(&self, origin: &Vec2<S>, dir: &Vec2<S>) -> Option<S> {
+        // The same slab clipping as in 'intersects_segment', except that the ray has no far end.
+        let mut t_min = S::zero();
+        let mut t_max: Option<S> = None;
+
+        for axis in 0..2 {
+            if dir[axis] == S::zero() {
+                if origin[axis] < self.min[axis] || origin[axis] > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+
+            let t0 = (self.min[axis] - origin[axis]) / dir[axis];
+            let t1 = (self.max[axis] - origin[axis]) / dir[axis];
+
+            t_min = t_min.max(t0.min(t1));
+            let t_far = t_max.map_or(t0.max(t1), |t| t.min(t0.max(t1)));
+            t_max = Some(t_far);
+
+            if t_min > t_far {
+                return None;
+            }
+        }
+
+        Some(t_min)
+    }
+
+    /// Returns the distance from the BBox to the given point, which is 0 if the point is inside.
+    pub fn distance_to_point(&self, p: &Vec2<S>) -> S {
+        let dx = (self.min.x - p.x).max(S::zero()).max(p.x - self.max.x);
+        let dy = (self.min.y - p.y).max(S::zero()).max(p.y - self.max.y);
+        (dx * dx + dy * dy).sqrt()
+    }
+
+    /// Returns the distance between the closest points of the BBox and the given BBox, which is 0
+    /// if they intersect.
+    pub fn distance_to_bbox(&self, other: &BBox2D<S>) -> S {
+        let dx = (other.min.x - self.max.x).max(S::zero()).max(self.min.x - other.max.x);
+        let dy = (other.min.y - self.max.y).max(S::zero()).max(self.min.y - other.max.y);
+        (dx * dx + dy * dy).sqrt()
+    }
+
+    /// Returns the area of the BBox.
+    pub fn area(&self) -> S {
+        let extent = self.max - self.min;
+        extent.x * extent.y
+    }
+
+    /// Returns the smallest BBox containing both this BBox and the given BBox.
+    pub fn union(&self, other: &BBox2D<S>) -> BBox2D<S> {
+        Self {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max)
+        }
+    }
+
+    /// Returns the overlap of the BBox and the given BBox, or None if they don't intersect.
+    pub fn intersection(&self, other: &BBox2D<S>) -> Option<BBox2D<S>> {
+        if !self.intersects(other) {
+            return None;
+        }
+
+        Some(Self {
+            min: self.min.sup(&other.min),
+            max: self.max.inf(&other.max)
+        })
+    }
+
+    /// Grows the BBox just enough for the given point to lie on or within its bounds. Note that
+    /// 'contains' excludes the max bounds, so a point grown onto them isn't contained.
+    pub fn expand(&mut self, p: &Vec2<S>) {
+        self.min = self.min.inf(p);
+        self.max = self.max.sup(p);
+    }
+
+    /// Returns a BBox which contains all of the given points, or None if there are none. Since
+    /// 'contains' excludes the max bounds, these are nudged just past the largest coordinates, so
+    /// the BBox is slightly larger than the one 'expand' would grow to.
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a Vec2<S>>) -> Option<BBox2D<S>> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+
+        let mut bbox = Self { min: *first, max: *first };
+        points.for_each(|p| bbox.expand(p));
+
+        // The margin is at least the spacing between floats around the max bounds, so adding it
+        // always moves them.
+        let margin = |v: S| v.abs().max(S::one()) * S::default_epsilon();
+        bbox.max = Vec2::new(bbox.max.x + margin(bbox.max.x), bbox.max.y + margin(bbox.max.y));
+        Some(bbox)
+    }
+
     /// Returns the midpoint of the BBox
-    pub fn mid(&self) -> Vec2 {
-        (self.min + self.max) / 2.0
+    pub fn mid(&self) -> Vec2<S> {
+        (self.min + self.max) / (S::one() + S::one())
     }
 
     /// Subdivides the BBox into 4 BBoxes, using 'mid' as the midpoint.
-    pub fn subdivide(&self, mid: &Vec2) -> [BBox2D; 4] {
+    pub fn subdivide(&self, mid: &Vec2<S>) -> [BBox2D<S>; 4] {
         [
             // Lower left
             Self {
@@ -47,8 +196,8 @@ impl BBox2D {
             },
             // Lower right
             Self {
-                min: Vec2::from([mid.x, self.min.y]),
-                max: Vec2::from([self.max.x, mid.y])
+                min: Vec2::new(mid.x, self.min.y),
+                max: Vec2::new(self.max.x, mid.y)
             },
             // Upper right
             Self {
@@ -57,19 +206,35 @@ impl BBox2D {
             },
             // Upper left
             Self {
-                min: Vec2::from([self.min.x, mid.y]),
-                max: Vec2::from([mid.x, self.max.y]),
+                min: Vec2::new(self.min.x, mid.y),
+                max: Vec2::new(mid.x, self.max.y),
             }
         ]
     }
 
     /// Returns the range of x-values of the BBox.
-    fn xrange(&self) -> Range {
+    fn xrange(&self) -> Range<S> {
         Range((self.min.x, self.max.x))
     }
 
     /// Returns the range of y-values of the BBox.
-    fn yrange(&self) -> Range {
+    fn yrange(&self) -> Range<S> {
         Range((self.min.y, self.max.y))
     }
 }
+
+/// Returns true if the point lies inside of the polygon with the given vertices, by the even-odd
+/// rule. The polygon is closed by an edge from its last vertex back to its first, and may be
+/// concave or even cross itself. Points on an edge may fall either way.
+pub fn polygon_contains<S: IsScalar>(polygon: &[Vec2<S>], p: &Vec2<S>) -> bool {
+    // Count the edges crossed by a ray from 'p' towards +x.
+    polygon_edges(polygon)
+        .filter(|(a, b)| (a.y > p.y) != (b.y > p.y))
+        .filter(|(a, b)| p.x < a.x + (b.x - a.x) * (p.y - a.y) / (b.y - a.y))
+        .count() % 2 == 1
+}
+
+/// Returns the edges of the polygon with the given vertices, including the closing edge.
+fn polygon_edges<S: IsScalar>(polygon: &[Vec2<S>]) -> impl Iterator<Item = (&Vec2<S>, &Vec2<S>)> {
+    polygon.iter().zip(polygon.iter().cycle().skip(1))
+}
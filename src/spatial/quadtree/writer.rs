@@ -0,0 +1,116 @@
+extern crate nalgebra as na;
+
+use alloc::vec::Vec;
+
+use crate::spatial::quadtree::linear_quadtree::{spread, BITS};
+use crate::spatial::quadtree::point_quadtree::*;
+use crate::spatial::quadtree::prelude::*;
+
+/// The number of points a QuadtreeWriter buffers before it writes them to the tree.
+pub const DEFAULT_BATCH_SIZE: usize = 65536;
+
+/// Buffers points on their way into a PointQuadtree and writes them in batches, which is much
+/// faster than inserting them one by one when loading lots of points into a tree that is already
+/// in use.
+///
+/// Every batch is sorted in Morton (Z-order) order and handed down the tree as a whole, so every
+/// quad on the way is locked once per batch instead of once per point, and full quads are
+/// subdivided in one go. Points only show up in the tree once their batch is written, which happens
+/// when the buffer is full, on 'flush', and when the writer is dropped.
+///
+/// Like 'PointQuadtree::from_points', repeats of points which are already stored, or which were
+/// written earlier, are skipped.
+pub struct QuadtreeWriter<'a, P: Send + Sync, S: IsScalar = f32> {
+    tree: &'a mut PointQuadtree<P, S>,
+    bbox: BBox2D<S>,
+    batch: Vec<Node<P, S>>,
+    batch_size: usize,
+    skipped: usize
+}
+
+impl<'a, P: Send + Sync, S: IsScalar> QuadtreeWriter<'a, P, S> {
+
+    /// Constructs a new writer into the given tree, which buffers DEFAULT_BATCH_SIZE points.
+    pub fn new(tree: &'a mut PointQuadtree<P, S>) -> Self {
+        Self::with_batch_size(tree, DEFAULT_BATCH_SIZE)
+    }
+
+    /// Constructs a new writer into the given tree, which buffers up to 'batch_size' points.
+    pub fn with_batch_size(tree: &'a mut PointQuadtree<P, S>, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch size must be at least 1");
+
+        let bbox = tree.bbox();
+        Self {
+            tree,
+            bbox,
+            batch: Vec::with_capacity(batch_size),
+            batch_size,
+            skipped: 0
+        }
+    }
+
+    /// Returns the number of points waiting to be written.
+    pub fn pending(&self) -> usize {
+        self.batch.len()
+    }
+
+    /// Returns the number of repeated points which were skipped so far.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Buffers the point along with its payload, writing the batch once it is full. Returns an
+    /// error right away if the point lies outside of the tree.
+    pub fn insert(&mut self, point: &Vec2<S>, payload: P) -> Result<(), InsertError> {
+        if !self.bbox.contains(point) {
+            return Err(InsertError::OutOfBounds);
+        }
+
+        self.batch.push((*point, payload));
+
+        if self.batch.len() >= self.batch_size {
+            self.flush();
+        }
+        Ok(())
+    }
+
+    /// Writes every buffered point to the tree.
+    pub fn flush(&mut self) {
+        let batch = core::mem::take(&mut self.batch);
+        let written = batch.len();
+        let mut entries: Vec<(u32, Node<P, S>)> = batch.into_iter()
+            .map(|node| (self.code(&node.0), node))
+            .collect();
+
+        // The sort is stable, so only the first of any repeated points is kept.
+        entries.sort_by(|a, b| {
+            a.0.cmp(&b.0)
+                .then(cmp_scalar(&a.1.0.x, &b.1.0.x))
+                .then(cmp_scalar(&a.1.0.y, &b.1.0.y))
+        });
+        entries.dedup_by(|a, b| a.1.0 == b.1.0);
+
+        let points: Vec<Node<P, S>> = entries.into_iter().map(|(_, node)| node).collect();
+        self.skipped += written - self.tree.insert_batch(points);
+        self.batch.reserve(self.batch_size);
+    }
+
+    /// Returns the Morton code of the cell containing the given point, see 'LinearQuadtree'.
+    fn code(&self, p: &Vec2<S>) -> u32 {
+        let cells = (1u32 << BITS) as f64;
+        let extent = self.bbox.max - self.bbox.min;
+
+        let cell = |v: S, min: S, extent: S| {
+            let t: f64 = na::try_convert((v - min) / extent).unwrap_or(0.0);
+            (t * cells).clamp(0.0, cells - 1.0) as u32
+        };
+
+        spread(cell(p.x, self.bbox.min.x, extent.x)) | (spread(cell(p.y, self.bbox.min.y, extent.y)) << 1)
+    }
+}
+
+impl<P: Send + Sync, S: IsScalar> Drop for QuadtreeWriter<'_, P, S> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
@@ -0,0 +1,104 @@
+pub mod priority_search_tree;
+pub mod range_tree;
+
+#[allow(non_snake_case)]
+#[cfg(test)]
+mod tests {
+    use crate::spatial::quadtree::prelude::*;
+    use crate::spatial::rangetree::priority_search_tree::*;
+    use crate::spatial::rangetree::range_tree::*;
+
+    #[test]
+    fn test_RangeTree2D() {
+        let empty = RangeTree2D::<usize>::from_points(vec![]);
+        assert!(empty.is_empty());
+        let everything = BBox2D { min: Vec2::from([-1000.0, -1000.0]), max: Vec2::from([1000.0, 1000.0]) };
+        assert_eq!(empty.count_within(&everything), 0);
+
+        // Plenty of repeated coordinates along both axes, and even repeated points.
+        let mut state: u32 = 21;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            ((state >> 16) % 50) as f32
+        };
+        let points: Vec<(Vec2, usize)> = (0..1000).map(|i| (Vec2::from([next(), next()]), i)).collect();
+
+        let tree = RangeTree2D::from_points(points.clone());
+        assert_eq!(tree.len(), 1000);
+        assert_eq!(tree.count_within(&everything), 1000);
+
+        for _ in 0..100 {
+            let (a, b) = (Vec2::from([next(), next()]), Vec2::from([next(), next()]));
+            let bbox = BBox2D { min: a.inf(&b), max: a.sup(&b) };
+
+            let mut expected: Vec<usize> = points.iter().filter(|(p, _)| bbox.contains(p)).map(|(_, i)| *i).collect();
+            assert_eq!(tree.count_within(&bbox), expected.len());
+
+            let mut found: Vec<usize> = tree.find_within(&bbox).into_iter()
+                .map(|(p, i)| {
+                    assert_eq!(p, points[i].0);
+                    i
+                })
+                .collect();
+            found.sort();
+            expected.sort();
+            assert_eq!(found, expected);
+        }
+
+        // BBoxes contain their min bounds, but not their max bounds.
+        let tree = RangeTree2D::from_points((0..3).map(|i| (Vec2::from([i as f32, 0.0]), i)));
+        assert_eq!(tree.count_within(&BBox2D { min: Vec2::from([0.0, 0.0]), max: Vec2::from([2.0, 1.0]) }), 2);
+        assert_eq!(tree.count_within(&BBox2D { min: Vec2::from([0.0, 0.0]), max: Vec2::from([3.0, 0.0]) }), 0);
+
+        let mut seen = vec![];
+        tree.find_within_with(&BBox2D { min: Vec2::from([1.0, -1.0]), max: Vec2::from([5.0, 1.0]) }, |_, i| seen.push(*i));
+        seen.sort();
+        assert_eq!(seen, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_PrioritySearchTree() {
+        let empty = PrioritySearchTree::<usize>::from_points(vec![]);
+        assert!(empty.is_empty());
+        assert!(empty.find_three_sided(-1000.0, 1000.0, 1000.0).is_empty());
+
+        let mut state: u32 = 8;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            ((state >> 16) % 50) as f32
+        };
+        let points: Vec<(Vec2, usize)> = (0..1000).map(|i| (Vec2::from([next(), next()]), i)).collect();
+
+        let tree = PrioritySearchTree::from_points(points.clone());
+        assert_eq!(tree.len(), 1000);
+        assert_eq!(tree.find_three_sided(0.0, 49.0, 49.0).len(), 1000);
+
+        for _ in 0..100 {
+            let (a, b, max_y) = (next(), next(), next());
+            let (min_x, max_x) = (a.min(b), a.max(b));
+
+            let mut expected: Vec<usize> = points.iter()
+                .filter(|(p, _)| min_x <= p.x && p.x <= max_x && p.y <= max_y)
+                .map(|(_, i)| *i)
+                .collect();
+
+            let mut found: Vec<usize> = tree.find_three_sided(min_x, max_x, max_y).into_iter()
+                .map(|(p, i)| {
+                    assert_eq!(p, points[i].0);
+                    i
+                })
+                .collect();
+            found.sort();
+            expected.sort();
+            assert_eq!(found, expected);
+        }
+
+        // All bounds are inclusive.
+        let tree = PrioritySearchTree::from_points((0..4).map(|i| (Vec2::from([i as f32, i as f32]), i)));
+        let mut seen = vec![];
+        tree.find_three_sided_with(1.0, 3.0, 2.0, |_, i| seen.push(*i));
+        seen.sort();
+        assert_eq!(seen, vec![1, 2]);
+        assert!(tree.find_three_sided(2.5, 2.9, 10.0).is_empty());
+    }
+}
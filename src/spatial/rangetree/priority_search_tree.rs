@@ -0,0 +1,115 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::spatial::quadtree::prelude::*;
+use crate::spatial::rangetree::range_tree::cmp_scalar;
+
+pub use crate::spatial::quadtree::point_quadtree::{IsPayload, Node};
+
+/// A node of the tree, holding the point with the lowest y-value of its subtree.
+struct PstNode<S> {
+    /// The index of the point held by this node.
+    point: usize,
+
+    /// The largest x-value of the left subtree, which is the smallest x-value of the right one.
+    split: S,
+
+    left: Option<usize>,
+    right: Option<usize>
+}
+
+/// A Priority Search Tree is a static index of points which reports the points within a 3-sided
+/// range, i.e. all points with x in [min_x, max_x] and y <= max_y, in O(log n + k) for k points
+/// reported.
+///
+/// It is a heap on y and a search tree on x at once: every node holds the point with the lowest
+/// y-value in its subtree, and the rest of the points are split between its children by x. Unlike
+/// BBoxes, the bounds of the range are inclusive.
+pub struct PrioritySearchTree<P, S: IsScalar = f32> {
+    points: Vec<Node<P, S>>,
+    nodes: Vec<PstNode<S>>,
+    root: Option<usize>
+}
+
+impl<P, S: IsScalar> PrioritySearchTree<P, S> {
+
+    /// Builds a tree holding the given points, in O(n log n).
+    pub fn from_points(points: impl IntoIterator<Item = Node<P, S>>) -> Self {
+        let mut points: Vec<Node<P, S>> = points.into_iter().collect();
+        points.sort_by(|a, b| cmp_scalar(&a.0.x, &b.0.x));
+
+        let mut tree = Self { points, nodes: vec![], root: None };
+        tree.root = tree._build((0..tree.points.len()).collect());
+        tree
+    }
+
+    /// Builds the subtree holding the given points, whose indices are sorted by x.
+    fn _build(&mut self, mut indices: Vec<usize>) -> Option<usize> {
+        let top = (0..indices.len())
+            .min_by(|a, b| cmp_scalar(&self.points[indices[*a]].0.y, &self.points[indices[*b]].0.y))?;
+        let point = indices.remove(top);
+
+        // --
+        // The rest of the points are split in half by x, which keeps the tree balanced.
+        let right_indices = indices.split_off(indices.len().div_ceil(2));
+        let split = indices.last()
+            .map_or(self.points[point].0.x, |i| self.points[*i].0.x);
+
+        let left = self._build(indices);
+        let right = self._build(right_indices);
+
+        self.nodes.push(PstNode { point, split, left, right });
+        Some(self.nodes.len() - 1)
+    }
+
+    /// Returns the number of points contained in this tree.
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Returns true if this tree contains no points.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Calls 'f' on every point with x in [min_x, max_x] and y <= max_y, without cloning the
+    /// payloads.
+    pub fn find_three_sided_with<F: FnMut(&Vec2<S>, &P)>(&self, min_x: S, max_x: S, max_y: S, mut f: F) {
+        if let Some(root) = self.root {
+            self._find_three_sided(root, min_x, max_x, max_y, &mut f);
+        }
+    }
+
+    fn _find_three_sided<F: FnMut(&Vec2<S>, &P)>(&self, node: usize, min_x: S, max_x: S, max_y: S, f: &mut F) {
+        let node = &self.nodes[node];
+        let (p, payload) = &self.points[node.point];
+
+        // No point below this node has a lower y-value.
+        if p.y > max_y {
+            return;
+        }
+
+        if min_x <= p.x && p.x <= max_x {
+            f(p, payload);
+        }
+
+        // Points with the same x-value as the split may end up on either side of it.
+        if let Some(left) = node.left.filter(|_| min_x <= node.split) {
+            self._find_three_sided(left, min_x, max_x, max_y, f);
+        }
+        if let Some(right) = node.right.filter(|_| node.split <= max_x) {
+            self._find_three_sided(right, min_x, max_x, max_y, f);
+        }
+    }
+}
+
+impl<P: IsPayload, S: IsScalar> PrioritySearchTree<P, S> {
+
+    /// Returns all points with x in [min_x, max_x] and y <= max_y, in O(log n + k) for k points
+    /// returned.
+    pub fn find_three_sided(&self, min_x: S, max_x: S, max_y: S) -> Vec<Node<P, S>> {
+        let mut result = vec![];
+        self.find_three_sided_with(min_x, max_x, max_y, |p, payload| result.push((*p, payload.clone())));
+        result
+    }
+}
@@ -0,0 +1,161 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+use crate::spatial::quadtree::prelude::*;
+
+pub use crate::spatial::quadtree::point_quadtree::{IsPayload, Node};
+
+/// A 2D Range Tree is a static index of points which counts or reports the points within a BBox in
+/// O(log² n), plus the number of points reported. Unlike the trees which report points, counting
+/// them never visits the points themselves.
+///
+/// The points are sorted by x, and every aligned block of 1, 2, 4, ... consecutive points is kept
+/// sorted by y as well, which takes O(n log n) memory. Any range of x-values splits into O(log n)
+/// such blocks, and the points of a block within the y-range of the query are found with binary
+/// searches.
+pub struct RangeTree2D<P, S: IsScalar = f32> {
+    /// The points, sorted by x.
+    points: Vec<Node<P, S>>,
+
+    /// For every level k, the indices of the points with each aligned block of 2^k of them sorted
+    /// by y, along with their y-values.
+    levels: Vec<Vec<(S, usize)>>
+}
+
+impl<P, S: IsScalar> RangeTree2D<P, S> {
+
+    /// Builds a tree holding the given points.
+    pub fn from_points(points: impl IntoIterator<Item = Node<P, S>>) -> Self {
+        let mut points: Vec<Node<P, S>> = points.into_iter().collect();
+        points.sort_by(|a, b| cmp_scalar(&a.0.x, &b.0.x));
+
+        // --
+        // Every level merges the sorted blocks of the level below it in pairs, just like a bottom-up
+        // merge sort.
+        let mut levels = vec![points.iter().enumerate().map(|(i, (p, _))| (p.y, i)).collect::<Vec<_>>()];
+        let mut block = 1;
+
+        while block < points.len() {
+            let below = levels.last().unwrap();
+            let mut level = Vec::with_capacity(points.len());
+
+            for pair in below.chunks(2 * block) {
+                let (a, b) = pair.split_at(block.min(pair.len()));
+                merge_by_y(a, b, &mut level);
+            }
+
+            levels.push(level);
+            block *= 2;
+        }
+
+        Self { points, levels }
+    }
+
+    /// Returns the number of points contained in this tree.
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Returns true if this tree contains no points.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of points within the given BBox, in O(log² n).
+    pub fn count_within(&self, bbox: &BBox2D<S>) -> usize {
+        let mut count = 0;
+        self.visit_blocks(bbox, |block| count += block.len());
+        count
+    }
+
+    /// Calls 'f' on every point within the given BBox, without cloning the payloads.
+    pub fn find_within_with<F: FnMut(&Vec2<S>, &P)>(&self, bbox: &BBox2D<S>, mut f: F) {
+        self.visit_blocks(bbox, |block| {
+            for (_, i) in block {
+                let (p, payload) = &self.points[*i];
+                f(p, payload);
+            }
+        });
+    }
+
+    /// Calls 'f' on the entries within the y-range of the BBox of every block which makes up its
+    /// x-range.
+    fn visit_blocks<F: FnMut(&[(S, usize)])>(&self, bbox: &BBox2D<S>, mut f: F) {
+        if self.is_empty() {
+            return;
+        }
+
+        // BBoxes contain their min bounds, but not their max bounds.
+        let lo = self.points.partition_point(|(p, _)| p.x < bbox.min.x);
+        let hi = self.points.partition_point(|(p, _)| p.x < bbox.max.x);
+
+        self._visit_blocks(bbox, lo, hi, self.levels.len() - 1, 0, &mut f);
+    }
+
+    /// Visits the parts of the block of the given level starting at 'start' which lie within the
+    /// range of indices from 'lo' to 'hi'.
+    fn _visit_blocks<F: FnMut(&[(S, usize)])>(
+        &self,
+        bbox: &BBox2D<S>,
+        lo: usize,
+        hi: usize,
+        level: usize,
+        start: usize,
+        f: &mut F
+    ) {
+        let end = (start + (1 << level)).min(self.len());
+        if hi <= start || end <= lo {
+            return;
+        }
+
+        if lo <= start && end <= hi {
+            let block = &self.levels[level][start..end];
+            let first = block.partition_point(|(y, _)| *y < bbox.min.y);
+            let last = block.partition_point(|(y, _)| *y < bbox.max.y);
+
+            if first < last {
+                f(&block[first..last]);
+            }
+            return;
+        }
+
+        // Blocks of single points are either inside of the range or outside of it, so this is
+        // never reached at level 0.
+        let half = 1 << (level - 1);
+        self._visit_blocks(bbox, lo, hi, level - 1, start, f);
+        self._visit_blocks(bbox, lo, hi, level - 1, start + half, f);
+    }
+}
+
+impl<P: IsPayload, S: IsScalar> RangeTree2D<P, S> {
+
+    /// Returns all points within the given BBox, in O(log² n) plus the number of points returned.
+    pub fn find_within(&self, bbox: &BBox2D<S>) -> Vec<Node<P, S>> {
+        let mut result = vec![];
+        self.find_within_with(bbox, |p, payload| result.push((*p, payload.clone())));
+        result
+    }
+}
+
+/// Appends the entries of 'a' and 'b', which are both sorted by y, to 'out' sorted by y.
+fn merge_by_y<S: IsScalar>(a: &[(S, usize)], b: &[(S, usize)], out: &mut Vec<(S, usize)>) {
+    let (mut i, mut j) = (0, 0);
+
+    while i < a.len() && j < b.len() {
+        if cmp_scalar(&b[j].0, &a[i].0) == Ordering::Less {
+            out.push(b[j]);
+            j += 1;
+        } else {
+            out.push(a[i]);
+            i += 1;
+        }
+    }
+
+    out.extend_from_slice(&a[i..]);
+    out.extend_from_slice(&b[j..]);
+}
+
+pub(super) fn cmp_scalar<S: IsScalar>(a: &S, b: &S) -> Ordering {
+    a.partial_cmp(b).unwrap_or(Ordering::Equal)
+}
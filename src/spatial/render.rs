@@ -0,0 +1,303 @@
+extern crate nalgebra as na;
+
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{self, Write};
+
+#[cfg(not(feature = "std"))]
+use nalgebra::ComplexField;
+
+use crate::spatial::quadtree::point_quadtree::PointQuadtree;
+use crate::spatial::quadtree::prelude::*;
+
+pub type Rgba = [u8; 4];
+
+/// What a PointQuadtree is rendered as.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RenderMode {
+    /// Every point, drawn as a square of 'point_radius' pixels around it.
+    Points,
+
+    /// The number of points in every square of 'cell_size' pixels, from dark (few) to bright (many).
+    /// Counts are scaled logarithmically, so that sparse areas stay visible next to dense ones.
+    Heatmap { cell_size: u32 }
+}
+
+/// Describes how a PointQuadtree is rendered by 'render'.
+#[derive(Debug, Copy, Clone)]
+pub struct RenderOptions {
+    pub width: u32,
+    pub height: u32,
+    pub mode: RenderMode,
+    pub background: Rgba,
+
+    /// The color of the boundaries of the quads, which are drawn on top of the points, or None to
+    /// leave them out.
+    pub quad_color: Option<Rgba>,
+
+    pub point_color: Rgba,
+    pub point_radius: u32
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            width: 512,
+            height: 512,
+            mode: RenderMode::Points,
+            background: [255, 255, 255, 255],
+            quad_color: Some([160, 160, 160, 255]),
+            point_color: [200, 30, 30, 255],
+            point_radius: 1
+        }
+    }
+}
+
+/// An image stored as rows of RGBA pixels, top row first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>
+}
+
+impl Image {
+
+    /// Constructs a new image filled with the given color.
+    pub fn new(width: u32, height: u32, color: Rgba) -> Self {
+        Self {
+            width,
+            height,
+            pixels: color.repeat(width as usize * height as usize)
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the pixels of the image, 4 bytes per pixel.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Rgba {
+        let i = self.offset(x, y);
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3]]
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: Rgba) {
+        let i = self.offset(x, y);
+        self.pixels[i..i + 4].copy_from_slice(&color);
+    }
+
+    /// Writes the image as a binary PPM (P6), which drops the alpha channel. PPM is as simple as
+    /// image formats get, and most image viewers open it.
+    #[cfg(feature = "std")]
+    pub fn write_ppm<W: Write>(&self, mut w: W) -> io::Result<()> {
+        write!(w, "P6\n{} {}\n255\n", self.width, self.height)?;
+
+        let rgb: Vec<u8> = self.pixels.chunks_exact(4).flat_map(|px| [px[0], px[1], px[2]]).collect();
+        w.write_all(&rgb)
+    }
+
+    /// Writes the image as a PNG. The pixel data isn't compressed, so the file takes up about as
+    /// much space as the pixels themselves. This requires the "png" feature.
+    #[cfg(feature = "png")]
+    pub fn write_png<W: Write>(&self, mut w: W) -> io::Result<()> {
+        w.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+        let mut header = vec![];
+        header.extend(self.width.to_be_bytes());
+        header.extend(self.height.to_be_bytes());
+
+        // 8 bits per channel, RGBA, no interlacing.
+        header.extend([8, 6, 0, 0, 0]);
+        write_png_chunk(&mut w, b"IHDR", &header)?;
+
+        // --
+        // Every row starts with its filter type (0 for none), and the rows are wrapped in a zlib
+        // stream made up of stored (uncompressed) deflate blocks of up to 65535 bytes.
+        let row_len = self.width as usize * 4;
+        let mut raw = Vec::with_capacity((row_len + 1) * self.height as usize);
+        for row in self.pixels.chunks_exact(row_len.max(1)).take(self.height as usize) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+
+        let mut data = vec![0x78, 0x01];
+        let mut blocks = raw.chunks(65535).peekable();
+        if blocks.peek().is_none() {
+            data.extend([1, 0, 0, 0xff, 0xff]);
+        }
+        while let Some(block) = blocks.next() {
+            let len = block.len() as u16;
+            data.push(blocks.peek().is_none() as u8);
+            data.extend(len.to_le_bytes());
+            data.extend((!len).to_le_bytes());
+            data.extend_from_slice(block);
+        }
+        data.extend(adler32(&raw).to_be_bytes());
+
+        write_png_chunk(&mut w, b"IDAT", &data)?;
+        write_png_chunk(&mut w, b"IEND", &[])
+    }
+
+    fn offset(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.width && y < self.height, "pixel is out of bounds");
+        (y as usize * self.width as usize + x as usize) * 4
+    }
+
+    /// Draws the outline of the rectangle between the given corners, inclusive.
+    fn draw_rect(&mut self, (x0, y0): (u32, u32), (x1, y1): (u32, u32), color: Rgba) {
+        for x in x0..=x1 {
+            self.set_pixel(x, y0, color);
+            self.set_pixel(x, y1, color);
+        }
+        for y in y0..=y1 {
+            self.set_pixel(x0, y, color);
+            self.set_pixel(x1, y, color);
+        }
+    }
+
+    /// Fills the rectangle between the given corners, inclusive.
+    fn fill_rect(&mut self, (x0, y0): (u32, u32), (x1, y1): (u32, u32), color: Rgba) {
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                self.set_pixel(x, y, color);
+            }
+        }
+    }
+}
+
+/// Rasterizes the given tree into an image, which is mostly useful for seeing how the points are
+/// distributed and how the tree subdivides them.
+///
+/// The bbox of the tree is stretched over the whole image, with the y axis pointing up.
+pub fn render<P: Send + Sync, S: IsScalar>(tree: &PointQuadtree<P, S>, options: &RenderOptions) -> Image {
+    let mut image = Image::new(options.width, options.height, options.background);
+    if options.width == 0 || options.height == 0 {
+        return image;
+    }
+
+    let mut quads = vec![];
+    tree.visit_quads(|bbox, _| quads.push(*bbox));
+    let canvas = Canvas { bbox: quads[0], width: options.width, height: options.height };
+
+    match options.mode {
+        RenderMode::Points => {
+            let r = options.point_radius;
+            tree.for_each(|p, _| {
+                let (x, y) = canvas.to_pixel(p);
+                let max = (options.width - 1, options.height - 1);
+                image.fill_rect(
+                    (x.saturating_sub(r), y.saturating_sub(r)),
+                    ((x + r).min(max.0), (y + r).min(max.1)),
+                    options.point_color
+                );
+            });
+        }
+        RenderMode::Heatmap { cell_size } => {
+            assert!(cell_size > 0, "cell size must be at least 1");
+
+            let cols = options.width.div_ceil(cell_size);
+            let rows = options.height.div_ceil(cell_size);
+            let mut counts = vec![0usize; cols as usize * rows as usize];
+            tree.for_each(|p, _| {
+                let (x, y) = canvas.to_pixel(p);
+                counts[(y / cell_size * cols + x / cell_size) as usize] += 1;
+            });
+
+            let max = counts.iter().copied().max().unwrap_or(0);
+            for (i, count) in counts.iter().enumerate().filter(|(_, count)| **count > 0) {
+                let (col, row) = (i as u32 % cols, i as u32 / cols);
+                let t = (*count as f64).ln_1p() / (max as f64).ln_1p();
+                image.fill_rect(
+                    (col * cell_size, row * cell_size),
+                    (((col + 1) * cell_size).min(options.width) - 1, ((row + 1) * cell_size).min(options.height) - 1),
+                    heat(t)
+                );
+            }
+        }
+    }
+
+    if let Some(color) = options.quad_color {
+        for bbox in &quads {
+            // The max corner of a quad lies just outside of it, which puts it on the pixel its
+            // neighbours share.
+            let (x0, y1) = canvas.to_pixel(&bbox.min);
+            let (x1, y0) = canvas.to_pixel(&bbox.max);
+            image.draw_rect((x0, y0), (x1, y1), color);
+        }
+    }
+
+    image
+}
+
+/// Maps the bbox of a tree onto the pixels of an image.
+struct Canvas<S: IsScalar> {
+    bbox: BBox2D<S>,
+    width: u32,
+    height: u32
+}
+
+impl<S: IsScalar> Canvas<S> {
+
+    /// Returns the pixel containing the given point, clamped to the image.
+    fn to_pixel(&self, p: &Vec2<S>) -> (u32, u32) {
+        let extent = self.bbox.max - self.bbox.min;
+
+        let cell = |v: S, min: S, extent: S, cells: u32| {
+            let t: f64 = na::try_convert((v - min) / extent).unwrap_or(0.0);
+            (t * cells as f64).clamp(0.0, (cells - 1) as f64) as u32
+        };
+
+        let x = cell(p.x, self.bbox.min.x, extent.x, self.width);
+        let y = cell(p.y, self.bbox.min.y, extent.y, self.height);
+        (x, self.height - 1 - y)
+    }
+}
+
+/// Returns the color of the given heat between 0 and 1, going from black through red and yellow to
+/// white.
+fn heat(t: f64) -> Rgba {
+    let channel = |offset: f64| ((t * 3.0 - offset).clamp(0.0, 1.0) * 255.0) as u8;
+    [channel(0.0), channel(1.0), channel(2.0), 255]
+}
+
+#[cfg(feature = "png")]
+fn write_png_chunk<W: Write>(w: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    w.write_all(&(data.len() as u32).to_be_bytes())?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+
+    let crc = crc32(kind.iter().chain(data));
+    w.write_all(&crc.to_be_bytes())
+}
+
+#[cfg(feature = "png")]
+fn crc32<'a>(bytes: impl Iterator<Item = &'a u8>) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb88320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[cfg(feature = "png")]
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in bytes {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
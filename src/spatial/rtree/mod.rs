@@ -0,0 +1,64 @@
+pub mod rtree_impl;
+
+#[allow(non_snake_case)]
+#[cfg(test)]
+mod tests {
+    use crate::spatial::quadtree::prelude::*;
+    use crate::spatial::rtree::rtree_impl::*;
+
+    fn bbox(x: f32, y: f32, w: f32, h: f32) -> BBox2D {
+        BBox2D {
+            min: Vec2::from([x, y]),
+            max: Vec2::from([x + w, y + h])
+        }
+    }
+
+    #[test]
+    fn test_RTree() {
+        let mut tree = RTree::<usize>::with_max_entries(4);
+        assert!(tree.is_empty());
+        assert!(tree.find_intersecting(&bbox(0.0, 0.0, 100.0, 100.0)).is_empty());
+
+        // A 10x10 grid of unit squares spaced 2 apart.
+        let boxes: Vec<BBox2D> = (0..100)
+            .map(|i| bbox(2.0 * (i % 10) as f32, 2.0 * (i / 10) as f32, 1.0, 1.0))
+            .collect();
+
+        for (i, b) in boxes.iter().enumerate() {
+            tree.insert(b, i);
+        }
+        assert_eq!(tree.len(), 100);
+
+        let mut items: Vec<usize> = tree.find_intersecting(&bbox(2.5, 2.5, 2.0, 2.0))
+            .into_iter()
+            .map(|(_, i)| i)
+            .collect();
+        items.sort();
+        assert_eq!(items, vec![11, 12, 21, 22]);
+
+        let mut items: Vec<usize> = tree.find_within(&bbox(1.5, 1.5, 4.0, 2.0))
+            .into_iter()
+            .map(|(_, i)| i)
+            .collect();
+        items.sort();
+        assert_eq!(items, vec![11, 12]);
+
+        let items = tree.find_containing(&bbox(4.25, 6.25, 0.5, 0.5));
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].1, 32);
+
+        assert_eq!(tree.remove(&boxes[32]), Some(32));
+        assert!(tree.remove(&boxes[32]).is_none());
+        assert!(tree.find_containing(&bbox(4.25, 6.25, 0.5, 0.5)).is_empty());
+        assert_eq!(tree.len(), 99);
+
+        for (i, b) in boxes.iter().enumerate().filter(|(i, _)| *i != 32) {
+            assert_eq!(tree.remove(b), Some(i));
+            assert_eq!(tree.find_intersecting(&bbox(-1.0, -1.0, 30.0, 30.0)).len(), tree.len());
+        }
+        assert!(tree.is_empty());
+
+        tree.insert(&boxes[0], 0);
+        assert_eq!(tree.find_intersecting(&boxes[0]).len(), 1);
+    }
+}
@@ -0,0 +1,458 @@
+use alloc::{format, vec};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::arena::{GenerationalArena, GenerationalId};
+use crate::arena::prelude::{HasId, IsMemoryArena};
+use crate::spatial::quadtree::prelude::*;
+use crate::visualize::{DotWriter, ToDot};
+
+pub use crate::spatial::quadtree::point_quadtree::IsPayload;
+
+type Id = GenerationalId;
+
+/// This represents the type of payload that is stored in the leaves of the tree.
+pub type Entry<P> = (BBox2D, P);
+
+/// Leaves hold the entries of the tree, while internal nodes hold other nodes.
+#[derive(Clone, Debug)]
+enum Content<P: IsPayload> {
+    Leaf(Vec<Entry<P>>),
+    Internal(Vec<Id>)
+}
+
+/// A node of the tree, whose bbox covers everything stored beneath it.
+#[derive(Clone, Debug)]
+struct RNode<P: IsPayload> {
+    pub id: Id,
+
+    pub bbox: BBox2D,
+
+    pub content: Content<P>
+}
+
+/// An R-tree is a balanced tree used to index bounding boxes. Each node covers the bboxes of its
+/// children, which may overlap, and is split (using Guttman's quadratic split) once it overflows.
+pub struct RTree<P: IsPayload> {
+    arena: GenerationalArena<RNode<P>>,
+    root_id: Id,
+    max_entries: usize,
+    min_entries: usize,
+    size: AtomicUsize
+}
+
+impl<P: IsPayload> RTree<P> {
+
+    /// Returns a new, empty RTree whose nodes hold up to 8 children.
+    pub fn new() -> Self {
+        Self::with_max_entries(8)
+    }
+
+    /// Returns a new, empty RTree whose nodes hold up to 'max_entries' children.
+    pub fn with_max_entries(max_entries: usize) -> Self {
+        assert!(max_entries >= 2, "nodes must be able to hold at least 2 children");
+
+        let mut arena = GenerationalArena::new();
+        let root_id = arena.get_new_id();
+        arena.add_node(RNode::<P>::new(root_id, Content::Leaf(vec![]))).expect("could not add root node!");
+
+        Self {
+            arena,
+            root_id,
+            max_entries,
+            min_entries: (max_entries * 2 / 5).max(1),
+            size: Default::default()
+        }
+    }
+
+    /// Returns the number of entries contained in this tree.
+    pub fn len(&self) -> usize {
+        self.size.load(Ordering::SeqCst)
+    }
+
+    /// Returns true if this tree contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Inserts the bbox and its payload into the tree.
+    pub fn insert(&mut self, bbox: &BBox2D, payload: P) {
+        self._insert_entry((*bbox, payload));
+        self.size.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Removes an entry with the given bbox from the tree, returning its payload if it existed.
+    pub fn remove(&mut self, bbox: &BBox2D) -> Option<P> {
+        let root = self.root_id;
+        let mut orphans = vec![];
+
+        let removed = self._remove(bbox, &root, &mut orphans)?;
+        self.size.fetch_sub(1, Ordering::SeqCst);
+
+        // --
+        // If the root has been left with a single child, that child becomes the new root.
+        loop {
+            let root_ref = self.arena.get_node(&self.root_id).expect("could not find node");
+            let mut root = root_ref.write().unwrap();
+
+            let only_child = match &root.content {
+                Content::Internal(children) if children.len() == 1 => children[0],
+                Content::Internal(children) if children.is_empty() => {
+                    root.content = Content::Leaf(vec![]);
+                    break;
+                }
+                _ => break
+            };
+
+            drop(root);
+            self.arena.delete_node(&self.root_id).expect("could not delete node");
+            self.root_id = only_child;
+        }
+
+        // Entries of nodes which underflowed get re-inserted from the top.
+        for entry in orphans {
+            self._insert_entry(entry);
+        }
+
+        Some(removed)
+    }
+
+    /// Returns all entries whose bbox intersects the given BBox.
+    pub fn find_intersecting(&self, bbox: &BBox2D) -> Vec<Entry<P>> {
+        let mut result = vec![];
+        self._search(&self.root_id, &|b| b.intersects(bbox), &|b| b.intersects(bbox), &mut result);
+        result
+    }
+
+    /// Returns all entries whose bbox lies entirely inside of the given BBox.
+    pub fn find_within(&self, bbox: &BBox2D) -> Vec<Entry<P>> {
+        let mut result = vec![];
+        self._search(&self.root_id, &|b| b.intersects(bbox), &|b| bbox.contains_bbox(b), &mut result);
+        result
+    }
+
+    /// Returns all entries whose bbox entirely contains the given BBox.
+    pub fn find_containing(&self, bbox: &BBox2D) -> Vec<Entry<P>> {
+        let mut result = vec![];
+        self._search(&self.root_id, &|b| b.contains_bbox(bbox), &|b| b.contains_bbox(bbox), &mut result);
+        result
+    }
+
+    /// Collects every entry accepted by 'accept_entry' in the subtree rooted at the given node,
+    /// only descending into nodes accepted by 'accept_node'.
+    fn _search(
+        &self,
+        node_id: &Id,
+        accept_node: &dyn Fn(&BBox2D) -> bool,
+        accept_entry: &dyn Fn(&BBox2D) -> bool,
+        out: &mut Vec<Entry<P>>
+    ) {
+        let node_ref = self.arena.get_node(node_id).expect("could not find node");
+        let node = node_ref.read().unwrap();
+
+        match &node.content {
+            Content::Leaf(entries) => {
+                out.extend(entries.iter().filter(|(b, _)| accept_entry(b)).cloned());
+            }
+            Content::Internal(children) => {
+                for child in children {
+                    let child_bbox = self.bbox_of(child);
+                    if accept_node(&child_bbox) {
+                        self._search(child, accept_node, accept_entry, out);
+                    }
+                }
+            }
+        }
+    }
+
+    fn _insert_entry(&mut self, entry: Entry<P>) {
+        let root = self.root_id;
+
+        // --
+        // If the root was split, the tree grows by one level.
+        if let Some(sibling) = self._insert(entry, &root) {
+            let new_root = self.arena.get_new_id();
+            let mut node = RNode::<P>::new(new_root, Content::Internal(vec![root, sibling]));
+            node.bbox = self.bbox_of(&root).union(&self.bbox_of(&sibling));
+
+            self.arena.add_node(node).expect("could not add node!");
+            self.root_id = new_root;
+        }
+    }
+
+    /// Inserts the entry into the subtree rooted at the given node, returning the id of a new
+    /// sibling node if the node had to be split.
+    fn _insert(&mut self, entry: Entry<P>, node_id: &Id) -> Option<Id> {
+        let node_ref = self.arena.get_node(node_id).expect("could not find node");
+
+        let child = match &node_ref.read().unwrap().content {
+            Content::Leaf(_) => None,
+            Content::Internal(children) => Some(self.choose_subtree(children, &entry.0))
+        };
+
+        let mut node = node_ref.write().unwrap();
+
+        match (&mut node.content, child) {
+            (Content::Leaf(entries), _) => {
+                entries.push(entry);
+            }
+            (Content::Internal(children), Some(child)) => {
+                if let Some(sibling) = self._insert(entry, &child) {
+                    children.push(sibling);
+                }
+            }
+            (Content::Internal(_), None) => unreachable!()
+        }
+
+        // --
+        // Split the node if it overflowed, handing half of its children to a new sibling.
+        let overflowed = match &node.content {
+            Content::Leaf(entries) => entries.len() > self.max_entries,
+            Content::Internal(children) => children.len() > self.max_entries
+        };
+
+        let sibling = if overflowed {
+            let sibling_content = match &mut node.content {
+                Content::Leaf(entries) => {
+                    let (a, b) = quadratic_split(core::mem::take(entries), |e| e.0, self.min_entries);
+                    *entries = a;
+                    Content::Leaf(b)
+                }
+                Content::Internal(children) => {
+                    let (a, b) = quadratic_split(core::mem::take(children), |id| self.bbox_of(id), self.min_entries);
+                    *children = a;
+                    Content::Internal(b)
+                }
+            };
+
+            let sibling_id = self.arena.get_new_id();
+            let mut sibling = RNode::<P>::new(sibling_id, sibling_content);
+            sibling.bbox = self.cover(&sibling.content);
+
+            self.arena.add_node(sibling).expect("could not add node!");
+            Some(sibling_id)
+        } else {
+            None
+        };
+
+        node.bbox = self.cover(&node.content);
+
+        sibling
+    }
+
+    /// Removes an entry with the given bbox from the subtree rooted at the given node. Entries of
+    /// nodes which underflow as a result are moved into 'orphans'.
+    fn _remove(&mut self, bbox: &BBox2D, node_id: &Id, orphans: &mut Vec<Entry<P>>) -> Option<P> {
+        let node_ref = self.arena.get_node(node_id).expect("could not find node");
+        let mut node = node_ref.write().unwrap();
+
+        if !node.bbox.contains_bbox(bbox) {
+            return None;
+        }
+
+        let removed = match &mut node.content {
+            Content::Leaf(entries) => {
+                let idx = entries.iter().position(|(b, _)| b == bbox)?;
+                entries.remove(idx).1
+            }
+            Content::Internal(children) => {
+                let (idx, removed) = children.iter()
+                    .enumerate()
+                    .find_map(|(idx, child)| Some((idx, self._remove(bbox, child, orphans)?)))?;
+
+                if self.child_count(&children[idx]) < self.min_entries {
+                    let child = children.remove(idx);
+                    self._drain(&child, orphans);
+                }
+
+                removed
+            }
+        };
+
+        node.bbox = self.cover(&node.content);
+
+        Some(removed)
+    }
+
+    /// Removes the node and all of its descendants from the arena, moving their entries to 'out'.
+    fn _drain(&mut self, node_id: &Id, out: &mut Vec<Entry<P>>) {
+        let node_ref = self.arena.get_node(node_id).expect("could not find node");
+        let content = core::mem::replace(&mut node_ref.write().unwrap().content, Content::Leaf(vec![]));
+
+        self.arena.delete_node(node_id).expect("could not delete node");
+
+        match content {
+            Content::Leaf(mut entries) => out.append(&mut entries),
+            Content::Internal(children) => {
+                for child in &children {
+                    self._drain(child, out);
+                }
+            }
+        }
+    }
+
+    /// Returns the child whose bbox needs the least enlargement to include 'bbox', breaking ties by
+    /// the smallest area.
+    fn choose_subtree(&self, children: &[Id], bbox: &BBox2D) -> Id {
+        children.iter()
+            .map(|id| {
+                let child_bbox = self.bbox_of(id);
+                let area = child_bbox.area();
+                (child_bbox.union(bbox).area() - area, area, *id)
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)))
+            .map(|(_, _, id)| id)
+            .expect("internal nodes always have children")
+    }
+
+    /// Returns the smallest BBox covering everything in the given content.
+    fn cover(&self, content: &Content<P>) -> BBox2D {
+        let bboxes: Vec<BBox2D> = match content {
+            Content::Leaf(entries) => entries.iter().map(|(b, _)| *b).collect(),
+            Content::Internal(children) => children.iter().map(|id| self.bbox_of(id)).collect()
+        };
+
+        bboxes.iter()
+            .copied()
+            .reduce(|a, b| a.union(&b))
+            .unwrap_or_default()
+    }
+
+    fn bbox_of(&self, node_id: &Id) -> BBox2D {
+        let node_ref = self.arena.get_node(node_id).expect("could not find node");
+        let bbox = node_ref.read().unwrap().bbox;
+        bbox
+    }
+
+    fn child_count(&self, node_id: &Id) -> usize {
+        let node_ref = self.arena.get_node(node_id).expect("could not find node");
+        let count = match &node_ref.read().unwrap().content {
+            Content::Leaf(entries) => entries.len(),
+            Content::Internal(children) => children.len()
+        };
+        count
+    }
+}
+
+impl<P: IsPayload> Default for RTree<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Splits 'items' into 2 groups of at least 'min' items each, trying to minimize the area covered
+/// by each group (Guttman's quadratic split).
+fn quadratic_split<T>(items: Vec<T>, bbox_of: impl Fn(&T) -> BBox2D, min: usize) -> (Vec<T>, Vec<T>) {
+    let bboxes: Vec<BBox2D> = items.iter().map(&bbox_of).collect();
+
+    // --
+    // Seed each group with the pair of items which would waste the most area if grouped together.
+    let mut seeds = (0, 1);
+    let mut worst = f32::NEG_INFINITY;
+    for i in 0..bboxes.len() {
+        for j in (i + 1)..bboxes.len() {
+            let waste = bboxes[i].union(&bboxes[j]).area() - bboxes[i].area() - bboxes[j].area();
+            if waste > worst {
+                worst = waste;
+                seeds = (i, j);
+            }
+        }
+    }
+
+    let mut remaining: Vec<Option<T>> = items.into_iter().map(Some).collect();
+    let mut groups = (vec![remaining[seeds.0].take().unwrap()], vec![remaining[seeds.1].take().unwrap()]);
+    let mut covers = (bboxes[seeds.0], bboxes[seeds.1]);
+    let mut left = bboxes.len() - 2;
+
+    while left > 0 {
+        // If a group needs all of the remaining items to reach the minimum, it gets them.
+        if groups.0.len() + left == min || groups.1.len() + left == min {
+            let group = if groups.0.len() + left == min { &mut groups.0 } else { &mut groups.1 };
+            group.extend(remaining.iter_mut().filter_map(Option::take));
+            break;
+        }
+
+        // --
+        // Otherwise, assign the item with the strongest preference for one group over the other.
+        let (idx, d0, d1) = remaining.iter()
+            .enumerate()
+            .filter(|(_, item)| item.is_some())
+            .map(|(idx, _)| {
+                let d0 = covers.0.union(&bboxes[idx]).area() - covers.0.area();
+                let d1 = covers.1.union(&bboxes[idx]).area() - covers.1.area();
+                (idx, d0, d1)
+            })
+            .max_by(|a, b| (a.1 - a.2).abs().total_cmp(&(b.1 - b.2).abs()))
+            .unwrap();
+
+        let prefer_first = d0.total_cmp(&d1)
+            .then(covers.0.area().total_cmp(&covers.1.area()))
+            .then(groups.0.len().cmp(&groups.1.len()))
+            .is_le();
+
+        let item = remaining[idx].take().unwrap();
+        if prefer_first {
+            groups.0.push(item);
+            covers.0 = covers.0.union(&bboxes[idx]);
+        } else {
+            groups.1.push(item);
+            covers.1 = covers.1.union(&bboxes[idx]);
+        }
+
+        left -= 1;
+    }
+
+    groups
+}
+
+impl<P: IsPayload> RNode<P> {
+    pub fn new(id: Id, content: Content<P>) -> Self {
+        Self {
+            id,
+            bbox: BBox2D::default(),
+            content
+        }
+    }
+}
+
+impl<P: IsPayload> HasId for RNode<P> {
+    type Id = Id;
+    fn get_id(&self) -> Self::Id {
+        self.id
+    }
+}
+
+impl<P: IsPayload> ToDot for RTree<P> {
+    fn to_dot(&self) -> String {
+        let format_bbox = |bbox: &BBox2D| {
+            format!("({}, {}) - ({}, {})", bbox.min.x, bbox.min.y, bbox.max.x, bbox.max.y)
+        };
+
+        let mut dot = DotWriter::new("RTree");
+        let mut stack = vec![self.root_id];
+
+        while let Some(id) = stack.pop() {
+            let node_ref = self.arena.get_node(&id).expect("could not find node");
+            let node = node_ref.read().unwrap();
+
+            let mut label = format_bbox(&node.bbox);
+            match &node.content {
+                Content::Leaf(entries) => {
+                    for (bbox, payload) in entries {
+                        label.push_str(&format!("\n{}: {:?}", format_bbox(bbox), payload));
+                    }
+                }
+                Content::Internal(children) => {
+                    for child_id in children {
+                        dot.edge(&id, child_id, "", "");
+                        stack.push(*child_id);
+                    }
+                }
+            }
+            dot.node(&id, &label, "shape=box");
+        }
+
+        dot.finish()
+    }
+}
@@ -0,0 +1,29 @@
+use core::cmp::Ordering;
+
+/// An item (node or point) encountered during a best-first search, ordered so that the closest
+/// item is at the top of a max-heap.
+pub(crate) struct Candidate<T, S = f32> {
+    pub dist: S,
+    pub item: T
+}
+
+impl<T, S: PartialOrd> PartialEq for Candidate<T, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T, S: PartialOrd> Eq for Candidate<T, S> {}
+
+impl<T, S: PartialOrd> PartialOrd for Candidate<T, S> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T, S: PartialOrd> Ord for Candidate<T, S> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Distances are never NaN, so they can be compared as if totally ordered.
+        other.dist.partial_cmp(&self.dist).unwrap_or(Ordering::Equal)
+    }
+}
@@ -0,0 +1,130 @@
+//! The locks guarding the nodes of the arenas.
+//!
+//! With the 'std' feature these are std's locks. Without it there's no OS to park threads with, so
+//! they are spin locks with the same interface instead. They never get poisoned, but still hand out
+//! their guards wrapped in a Result so that callers don't need to tell them apart.
+
+#[cfg(feature = "std")]
+pub use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+#[cfg(not(feature = "std"))]
+pub use spin::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+#[cfg(not(feature = "std"))]
+mod spin {
+    use core::cell::UnsafeCell;
+    use core::convert::Infallible;
+    use core::fmt;
+    use core::hint::spin_loop;
+    use core::ops::{Deref, DerefMut};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    /// The state of a lock which is held by a writer, rather than by some number of readers.
+    const WRITER: usize = usize::MAX;
+
+    pub type LockResult<T> = Result<T, Infallible>;
+
+    /// A reader-writer lock which spins until it can be taken. The state counts the readers
+    /// holding the lock, or is 'WRITER' while a writer holds it.
+    pub struct RwLock<T: ?Sized> {
+        state: AtomicUsize,
+        value: UnsafeCell<T>
+    }
+
+    // The lock hands out shared references to readers on different threads, and exclusive
+    // references to one writer at a time, just like std's RwLock.
+    unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+    unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+    impl<T> RwLock<T> {
+        pub const fn new(value: T) -> Self {
+            Self { state: AtomicUsize::new(0), value: UnsafeCell::new(value) }
+        }
+
+        pub fn into_inner(self) -> LockResult<T> {
+            Ok(self.value.into_inner())
+        }
+    }
+
+    impl<T: ?Sized> RwLock<T> {
+        pub fn read(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+            loop {
+                let state = self.state.load(Ordering::Relaxed);
+                if state < WRITER - 1
+                    && self.state.compare_exchange_weak(state, state + 1, Ordering::Acquire, Ordering::Relaxed).is_ok()
+                {
+                    return Ok(RwLockReadGuard { lock: self });
+                }
+                spin_loop();
+            }
+        }
+
+        pub fn write(&self) -> LockResult<RwLockWriteGuard<'_, T>> {
+            while self.state.compare_exchange_weak(0, WRITER, Ordering::Acquire, Ordering::Relaxed).is_err() {
+                spin_loop();
+            }
+            Ok(RwLockWriteGuard { lock: self })
+        }
+
+        pub fn get_mut(&mut self) -> LockResult<&mut T> {
+            Ok(self.value.get_mut())
+        }
+    }
+
+    impl<T: Default> Default for RwLock<T> {
+        fn default() -> Self {
+            Self::new(T::default())
+        }
+    }
+
+    impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_struct("RwLock").field("data", &&*self.read().unwrap()).finish()
+        }
+    }
+
+    pub struct RwLockReadGuard<'a, T: ?Sized> {
+        lock: &'a RwLock<T>
+    }
+
+    impl<T: ?Sized> Deref for RwLockReadGuard<'_, T> {
+        type Target = T;
+
+        fn deref(&self) -> &T {
+            // Readers only ever share the lock with other readers.
+            unsafe { &*self.lock.value.get() }
+        }
+    }
+
+    impl<T: ?Sized> Drop for RwLockReadGuard<'_, T> {
+        fn drop(&mut self) {
+            self.lock.state.fetch_sub(1, Ordering::Release);
+        }
+    }
+
+    pub struct RwLockWriteGuard<'a, T: ?Sized> {
+        lock: &'a RwLock<T>
+    }
+
+    impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T> {
+        type Target = T;
+
+        fn deref(&self) -> &T {
+            // The writer holds the lock on its own.
+            unsafe { &*self.lock.value.get() }
+        }
+    }
+
+    impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T> {
+        fn deref_mut(&mut self) -> &mut T {
+            // The writer holds the lock on its own.
+            unsafe { &mut *self.lock.value.get() }
+        }
+    }
+
+    impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T> {
+        fn drop(&mut self) {
+            self.lock.state.store(0, Ordering::Release);
+        }
+    }
+}
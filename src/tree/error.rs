@@ -0,0 +1,28 @@
+use core::error::Error;
+use core::fmt;
+
+/// The ways in which an operation on a tree can fail.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TreeError {
+    /// The id doesn't refer to a node in the tree, e.g. because the node was removed.
+    InvalidNode,
+
+    /// The node would become its own ancestor.
+    WouldCreateCycle,
+
+    /// The LCRS encoding of a tree links a node twice or never, or links to a node which doesn't
+    /// exist, see 'LcrsTree::is_valid'.
+    InvalidLcrs
+}
+
+impl fmt::Display for TreeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TreeError::InvalidNode => write!(f, "id does not refer to a node in the tree"),
+            TreeError::WouldCreateCycle => write!(f, "node would become its own ancestor"),
+            TreeError::InvalidLcrs => write!(f, "lcrs tree does not link every node exactly once")
+        }
+    }
+}
+
+impl Error for TreeError {}
@@ -0,0 +1,86 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// A node of an LcrsTree, which links to its first child and to its next sibling.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LcrsNode<T> {
+    pub value: T,
+    pub first_child: Option<usize>,
+    pub next_sibling: Option<usize>
+}
+
+/// A forest in left-child right-sibling (LCRS) form, where every node links to its first child and
+/// its next sibling instead of to all of its children. This takes 2 links per node no matter how
+/// many children it has, and is a common layout for exchanging trees with other systems.
+///
+/// The nodes are stored in a flat list which links refer to by position. The first root is node 0,
+/// and the other roots are its siblings. Trees exported by this crate list their nodes in pre-order,
+/// but any order is accepted as long as every node is linked exactly once, see 'is_valid'. The links
+/// of an invalid tree may run in circles, so its roots and children shouldn't be walked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LcrsTree<T> {
+    pub nodes: Vec<LcrsNode<T>>
+}
+
+impl<T> Default for LcrsTree<T> {
+    fn default() -> Self {
+        Self { nodes: vec![] }
+    }
+}
+
+impl<T> LcrsTree<T> {
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns the roots, in order.
+    pub fn roots(&self) -> impl Iterator<Item = usize> + '_ {
+        self.siblings((!self.is_empty()).then_some(0))
+    }
+
+    /// Returns the children of the given node, in order.
+    pub fn children(&self, node: usize) -> impl Iterator<Item = usize> + '_ {
+        self.siblings(self.nodes[node].first_child)
+    }
+
+    /// Returns true if every node is linked exactly once, and every link refers to a node.
+    pub fn is_valid(&self) -> bool {
+        self.pre_order().is_some()
+    }
+
+    /// Returns every node along with its parent, parents before their children and siblings in
+    /// order, or None if the tree isn't valid.
+    pub(crate) fn pre_order(&self) -> Option<Vec<(usize, Option<usize>)>> {
+        let mut visited = vec![false; self.len()];
+        let mut order = Vec::with_capacity(self.len());
+
+        // Every entry on the stack is the next node to visit in a list of siblings.
+        let mut stack = vec![((!self.is_empty()).then_some(0), None)];
+        while let Some((node, parent)) = stack.pop() {
+            let Some(node) = node else {
+                continue;
+            };
+
+            if core::mem::replace(visited.get_mut(node)?, true) {
+                return None;
+            }
+            order.push((node, parent));
+
+            stack.push((self.nodes[node].next_sibling, parent));
+            stack.push((self.nodes[node].first_child, Some(node)));
+        }
+
+        (order.len() == self.len()).then_some(order)
+    }
+
+    fn siblings(&self, first: Option<usize>) -> impl Iterator<Item = usize> + '_ {
+        core::iter::successors(first, |node| self.nodes[*node].next_sibling)
+    }
+}
@@ -0,0 +1,135 @@
+pub mod error;
+pub mod lcrs;
+#[allow(clippy::module_inception)]
+pub mod tree;
+
+#[cfg(test)]
+mod tests {
+    use crate::tree::error::*;
+    use crate::tree::lcrs::*;
+    use crate::tree::tree::*;
+
+    #[test]
+    fn test_tree() {
+        let mut tree = Tree::<&str>::new();
+        assert!(tree.is_empty());
+
+        //        root
+        //      /   |   \
+        //     a    b    c
+        //    / \        |
+        //   d   e       f
+        let root = tree.add_root("root");
+        let a = tree.add_child(&root, "a").unwrap();
+        let b = tree.add_child(&root, "b").unwrap();
+        let c = tree.add_child(&root, "c").unwrap();
+        let d = tree.add_child(&a, "d").unwrap();
+        let e = tree.add_child(&a, "e").unwrap();
+        let f = tree.add_child(&c, "f").unwrap();
+        assert_eq!(tree.len(), 7);
+
+        let values = |tree: &Tree<&'static str>, ids: Vec<NodeId>| {
+            ids.iter().map(|id| tree.get(id).unwrap()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(tree.parent(&d), Some(a));
+        assert_eq!(tree.parent(&root), None);
+        assert_eq!(tree.children(&a), vec![d, e]);
+        assert_eq!(tree.depth(&f), Some(2));
+        assert_eq!(tree.ancestors(&e).collect::<Vec<_>>(), vec![a, root]);
+        assert_eq!(tree.siblings(&b).collect::<Vec<_>>(), vec![a, c]);
+        assert_eq!(tree.descendants(&a).collect::<Vec<_>>(), vec![d, e]);
+
+        assert_eq!(values(&tree, tree.pre_order(&root).collect()), vec!["root", "a", "d", "e", "b", "c", "f"]);
+        assert_eq!(values(&tree, tree.post_order(&root).collect()), vec!["d", "e", "a", "b", "f", "c", "root"]);
+        assert_eq!(values(&tree, tree.level_order(&root).collect()), vec!["root", "a", "b", "c", "d", "e", "f"]);
+
+        // --
+        // Moving subtrees around.
+        assert_eq!(tree.reparent(&a, &d), Err(TreeError::WouldCreateCycle));
+        assert_eq!(tree.reparent(&a, &a), Err(TreeError::WouldCreateCycle));
+        assert_eq!(tree.reparent(&a, &f), Ok(()));
+        assert_eq!(tree.ancestors(&e).collect::<Vec<_>>(), vec![a, f, c, root]);
+        assert_eq!(values(&tree, tree.pre_order(&root).collect()), vec!["root", "b", "c", "f", "a", "d", "e"]);
+
+        assert_eq!(tree.detach(&f), Ok(()));
+        assert_eq!(tree.roots(), vec![root, f]);
+        assert_eq!(tree.siblings(&f).collect::<Vec<_>>(), vec![root]);
+        assert_eq!(tree.depth(&d), Some(2));
+        assert_eq!(tree.pre_order(&root).count(), 3);
+        assert_eq!(tree.reparent(&f, &b), Ok(()));
+        assert_eq!(tree.roots(), vec![root]);
+        assert_eq!(tree.depth(&d), Some(4));
+
+        tree.modify(&d, |value| *value = "dd").unwrap();
+        assert_eq!(tree.get_with(&d, |value| value.len()), Some(2));
+
+        // --
+        // Removing a node takes its subtree with it, and its ids go stale.
+        assert_eq!(tree.remove(&a), Ok("a"));
+        assert_eq!(tree.len(), 4);
+        assert!(!tree.contains(&d));
+        assert_eq!(tree.get(&e), None);
+        assert_eq!(tree.add_child(&a, "g"), Err(TreeError::InvalidNode));
+        assert_eq!(tree.remove(&a), Err(TreeError::InvalidNode));
+        assert_eq!(tree.detach(&d), Err(TreeError::InvalidNode));
+        assert_eq!(tree.pre_order(&d).count(), 0);
+        assert_eq!(tree.children(&f), vec![]);
+
+        assert_eq!(tree.remove(&root), Ok("root"));
+        assert!(tree.is_empty());
+        assert!(tree.roots().is_empty());
+    }
+
+    #[test]
+    fn test_tree_lcrs() {
+        let mut tree = Tree::<&str>::new();
+        assert_eq!(tree.to_lcrs(), LcrsTree::default());
+
+        //        root      x
+        //      /   |   \
+        //     a    b    c
+        //    / \
+        //   d   e
+        let root = tree.add_root("root");
+        let a = tree.add_child(&root, "a").unwrap();
+        tree.add_child(&root, "b").unwrap();
+        tree.add_child(&root, "c").unwrap();
+        tree.add_child(&a, "d").unwrap();
+        tree.add_child(&a, "e").unwrap();
+        tree.add_root("x");
+
+        let lcrs = tree.to_lcrs();
+        assert!(lcrs.is_valid());
+        let values: Vec<&str> = lcrs.nodes.iter().map(|node| node.value).collect();
+        assert_eq!(values, vec!["root", "a", "d", "e", "b", "c", "x"]);
+        assert_eq!(lcrs.roots().collect::<Vec<_>>(), vec![0, 6]);
+        assert_eq!(lcrs.children(0).collect::<Vec<_>>(), vec![1, 4, 5]);
+        assert_eq!(lcrs.nodes[1], LcrsNode { value: "a", first_child: Some(2), next_sibling: Some(4) });
+
+        // Converting back gives the same tree, whatever order the nodes are listed in.
+        let copy = Tree::from_lcrs(lcrs.clone()).unwrap();
+        assert_eq!(copy.len(), 7);
+        assert_eq!(copy.to_lcrs(), lcrs);
+
+        let mut reversed = lcrs.clone();
+        reversed.nodes[1..].reverse();
+        let flip = |link: Option<usize>| link.map(|i| if i == 0 { 0 } else { 7 - i });
+        for node in &mut reversed.nodes {
+            node.first_child = flip(node.first_child);
+            node.next_sibling = flip(node.next_sibling);
+        }
+        assert_eq!(Tree::from_lcrs(reversed).unwrap().to_lcrs(), lcrs);
+
+        // Nodes linked twice, never, or not at all are refused.
+        let mut invalid = lcrs.clone();
+        invalid.nodes[5].first_child = Some(1);
+        assert_eq!(Tree::from_lcrs(invalid).err(), Some(TreeError::InvalidLcrs));
+        let mut invalid = lcrs.clone();
+        invalid.nodes[0].next_sibling = None;
+        assert_eq!(Tree::from_lcrs(invalid).err(), Some(TreeError::InvalidLcrs));
+        let mut invalid = lcrs.clone();
+        invalid.nodes[6].first_child = Some(7);
+        assert!(!invalid.is_valid());
+    }
+}
@@ -0,0 +1,360 @@
+use alloc::collections::VecDeque;
+use alloc::{format, vec};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+use crate::arena::*;
+use crate::arena::prelude::*;
+use crate::tree::error::TreeError;
+use crate::tree::lcrs::{LcrsNode, LcrsTree};
+use crate::visualize::{DotWriter, ToDot};
+
+type Id = GenerationalId;
+
+#[derive(Debug, Clone)]
+struct TreeNode<T: Send + Sync> {
+    pub id: Id,
+
+    pub value: T,
+
+    pub parent: Option<Id>,
+
+    /// The children of this node, in the order in which they were added.
+    pub children: Vec<Id>
+}
+
+impl<T: Send + Sync> HasId for TreeNode<T> {
+    type Id = Id;
+
+    fn get_id(&self) -> Self::Id {
+        self.id
+    }
+}
+
+/// A stable reference to a node of a Tree, which stays valid until the node is removed, no matter
+/// where it is moved to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct NodeId(Id);
+
+/// This class represents a thread-safe, general purpose tree in which every node holds a value and
+/// any number of children, e.g. a file system or a scene graph.
+///
+/// The tree is really a forest: it may hold several roots, and detaching a node turns it into a
+/// root of its own until it is reparented or removed. Every node knows its parent, so walking up
+/// the tree is just as cheap as walking down.
+pub struct Tree<T: Send + Sync> {
+    arena: GenerationalArena<TreeNode<T>>,
+
+    /// The nodes without a parent, in the order in which they became roots.
+    roots: Vec<Id>
+}
+
+impl<T: Send + Sync> Default for Tree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Send + Sync> Tree<T> {
+
+    /// Constructs a new empty Tree
+    pub fn new() -> Self {
+        Self {
+            arena: GenerationalArena::new(),
+            roots: vec![]
+        }
+    }
+
+    /// Returns the number of nodes in the tree, including the ones below every root.
+    pub fn len(&self) -> usize {
+        self.arena.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns true if the id refers to a node in the tree.
+    pub fn contains(&self, node: &NodeId) -> bool {
+        self.arena.get_node(&node.0).is_some()
+    }
+
+    /// Constructs a new Tree from its left-child right-sibling form, see 'LcrsTree'. Returns an
+    /// error if the LCRS tree isn't valid.
+    pub fn from_lcrs(lcrs: LcrsTree<T>) -> Result<Self, TreeError> {
+        let order = lcrs.pre_order().ok_or(TreeError::InvalidLcrs)?;
+
+        let mut values: Vec<Option<T>> = lcrs.nodes.into_iter().map(|node| Some(node.value)).collect();
+        let mut ids = vec![None; values.len()];
+        let mut tree = Self::new();
+
+        for (node, parent) in order {
+            let value = values[node].take().expect("node is visited twice");
+            ids[node] = Some(match parent {
+                None => tree.add_root(value),
+                Some(parent) => tree.add_child(&ids[parent].expect("parent is not visited yet"), value)?
+            });
+        }
+
+        Ok(tree)
+    }
+
+    /// Adds a new root holding the given value.
+    pub fn add_root(&mut self, value: T) -> NodeId {
+        let id = self.arena.get_new_id();
+        self.arena.add_node(TreeNode { id, value, parent: None, children: vec![] }).expect("could not add node!");
+        self.roots.push(id);
+        NodeId(id)
+    }
+
+    /// Adds a node holding the given value as the last child of 'parent'.
+    pub fn add_child(&mut self, parent: &NodeId, value: T) -> Result<NodeId, TreeError> {
+        let parent_ref = self.arena.get_node(&parent.0).ok_or(TreeError::InvalidNode)?;
+
+        let id = self.arena.get_new_id();
+        self.arena.add_node(TreeNode { id, value, parent: Some(parent.0), children: vec![] }).expect("could not add node!");
+        parent_ref.write().unwrap().children.push(id);
+
+        Ok(NodeId(id))
+    }
+
+    /// Returns the roots of the tree, in the order in which they became roots.
+    pub fn roots(&self) -> Vec<NodeId> {
+        self.roots.iter().map(|id| NodeId(*id)).collect()
+    }
+
+    /// Returns the parent of the node, or None if it is a root or doesn't exist.
+    pub fn parent(&self, node: &NodeId) -> Option<NodeId> {
+        let parent = self.arena.get_node(&node.0)?.read().unwrap().parent;
+        parent.map(NodeId)
+    }
+
+    /// Returns the children of the node in order, which are none if it doesn't exist.
+    pub fn children(&self, node: &NodeId) -> Vec<NodeId> {
+        self.arena.get_node(&node.0)
+            .map_or(vec![], |node_ref| node_ref.read().unwrap().children.iter().map(|id| NodeId(*id)).collect())
+    }
+
+    /// Returns the number of ancestors of the node, which is 0 for a root.
+    pub fn depth(&self, node: &NodeId) -> Option<usize> {
+        self.contains(node).then(|| self.ancestors(node).count())
+    }
+
+    /// Calls 'f' with the value of the node, without cloning it.
+    pub fn get_with<R, F: FnOnce(&T) -> R>(&self, node: &NodeId, f: F) -> Option<R> {
+        let node_ref = self.arena.get_node(&node.0)?;
+        let result = f(&node_ref.read().unwrap().value);
+        Some(result)
+    }
+
+    /// Calls 'f' with the value of the node, which it can change in place.
+    pub fn modify<F: FnOnce(&mut T)>(&self, node: &NodeId, f: F) -> Result<(), TreeError> {
+        let node_ref = self.arena.get_node(&node.0).ok_or(TreeError::InvalidNode)?;
+        f(&mut node_ref.write().unwrap().value);
+        Ok(())
+    }
+
+    /// Unlinks the node (along with its subtree) from its parent, turning it into a root.
+    pub fn detach(&mut self, node: &NodeId) -> Result<(), TreeError> {
+        let node_ref = self.arena.get_node(&node.0).ok_or(TreeError::InvalidNode)?;
+
+        if node_ref.read().unwrap().parent.is_some() {
+            self.unlink(&node.0);
+            self.roots.push(node.0);
+        }
+
+        Ok(())
+    }
+
+    /// Moves the node (along with its subtree) to become the last child of 'new_parent'. A node
+    /// can't be moved below itself.
+    pub fn reparent(&mut self, node: &NodeId, new_parent: &NodeId) -> Result<(), TreeError> {
+        let parent_ref = self.arena.get_node(&new_parent.0).ok_or(TreeError::InvalidNode)?;
+        if !self.contains(node) {
+            return Err(TreeError::InvalidNode);
+        }
+
+        if node == new_parent || self.ancestors(new_parent).any(|ancestor| ancestor == *node) {
+            return Err(TreeError::WouldCreateCycle);
+        }
+
+        self.unlink(&node.0);
+        parent_ref.write().unwrap().children.push(node.0);
+        self.node(&node.0).write().unwrap().parent = Some(new_parent.0);
+
+        Ok(())
+    }
+
+    /// Removes the node along with its whole subtree, returning the value of the node.
+    pub fn remove(&mut self, node: &NodeId) -> Result<T, TreeError> {
+        if !self.contains(node) {
+            return Err(TreeError::InvalidNode);
+        }
+
+        self.unlink(&node.0);
+
+        let descendants: Vec<NodeId> = self.descendants(node).collect();
+        for descendant in descendants {
+            self.arena.delete_node(&descendant.0).expect("could not delete node");
+        }
+
+        Ok(self.take_node(&node.0).value)
+    }
+
+    /// Returns the ancestors of the node from its parent up to its root.
+    pub fn ancestors(&self, node: &NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        core::iter::successors(self.parent(node), move |node| self.parent(node))
+    }
+
+    /// Returns the other children of the node's parent in order, or the other roots if it is a
+    /// root.
+    pub fn siblings(&self, node: &NodeId) -> impl Iterator<Item = NodeId> {
+        let siblings = match self.parent(node) {
+            Some(parent) => self.children(&parent),
+            None if self.contains(node) => self.roots(),
+            None => vec![]
+        };
+
+        let node = *node;
+        siblings.into_iter().filter(move |sibling| *sibling != node)
+    }
+
+    /// Returns the nodes below the node in pre-order, not including the node itself.
+    pub fn descendants(&self, node: &NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        self.pre_order(node).skip(1)
+    }
+
+    /// Returns the node and the nodes below it in pre-order, i.e. every node comes before its
+    /// children.
+    pub fn pre_order(&self, node: &NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let mut stack: Vec<NodeId> = self.contains(node).then_some(*node).into_iter().collect();
+
+        core::iter::from_fn(move || {
+            let node = stack.pop()?;
+            stack.extend(self.children(&node).into_iter().rev());
+            Some(node)
+        })
+    }
+
+    /// Returns the node and the nodes below it in post-order, i.e. every node comes after its
+    /// children.
+    pub fn post_order(&self, node: &NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        // Every node is pushed once to expand it, and again to emit it once its children are done.
+        let mut stack: Vec<(NodeId, bool)> = self.contains(node).then_some((*node, false)).into_iter().collect();
+
+        core::iter::from_fn(move || {
+            while let Some((node, expanded)) = stack.pop() {
+                if expanded {
+                    return Some(node);
+                }
+
+                stack.push((node, true));
+                stack.extend(self.children(&node).into_iter().rev().map(|child| (child, false)));
+            }
+            None
+        })
+    }
+
+    /// Returns the node and the nodes below it in level-order, i.e. by depth and in order within
+    /// each level.
+    pub fn level_order(&self, node: &NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let mut queue: VecDeque<NodeId> = self.contains(node).then_some(*node).into_iter().collect();
+
+        core::iter::from_fn(move || {
+            let node = queue.pop_front()?;
+            queue.extend(self.children(&node));
+            Some(node)
+        })
+    }
+
+    /// Removes the node from the children of its parent (or from the roots), leaving its own
+    /// parent link for the caller to fix up.
+    fn unlink(&mut self, node_id: &Id) {
+        let parent = self.node(node_id).read().unwrap().parent;
+
+        match parent {
+            None => self.roots.retain(|root| root != node_id),
+            Some(parent) => {
+                self.node(&parent).write().unwrap().children.retain(|child| child != node_id);
+                self.node(node_id).write().unwrap().parent = None;
+            }
+        }
+    }
+
+    fn node(&self, node_id: &Id) -> SharedRef<TreeNode<T>> {
+        self.arena.get_node(node_id).expect("could not find node")
+    }
+
+    /// Removes the node from the arena, handing back ownership of it.
+    fn take_node(&mut self, node_id: &Id) -> TreeNode<T> {
+        let node_ref = self.node(node_id);
+        self.arena.delete_node(node_id).expect("could not delete node");
+
+        match Arc::try_unwrap(node_ref) {
+            Ok(node) => node.into_inner().unwrap(),
+            Err(_) => panic!("node is still referenced")
+        }
+    }
+}
+
+impl<T: Clone + Send + Sync> Tree<T> {
+
+    /// Returns the value of the node.
+    pub fn get(&self, node: &NodeId) -> Option<T> {
+        self.get_with(node, |value| value.clone())
+    }
+
+    /// Returns the tree in left-child right-sibling form, with the nodes in pre-order and the roots
+    /// in the order in which they became roots.
+    pub fn to_lcrs(&self) -> LcrsTree<T> {
+        let mut lcrs = LcrsTree::default();
+        self._to_lcrs(&self.roots, &mut lcrs);
+        lcrs
+    }
+
+    /// Adds the given list of siblings along with their subtrees to 'lcrs', returning the first.
+    fn _to_lcrs(&self, siblings: &[Id], lcrs: &mut LcrsTree<T>) -> Option<usize> {
+        let mut prev: Option<usize> = None;
+        let mut first = None;
+
+        for id in siblings {
+            let node_ref = self.node(id);
+            let node = node_ref.read().unwrap();
+
+            let idx = lcrs.nodes.len();
+            lcrs.nodes.push(LcrsNode { value: node.value.clone(), first_child: None, next_sibling: None });
+            lcrs.nodes[idx].first_child = self._to_lcrs(&node.children, lcrs);
+
+            match prev {
+                None => first = Some(idx),
+                Some(prev) => lcrs.nodes[prev].next_sibling = Some(idx)
+            }
+            prev = Some(idx);
+        }
+
+        first
+    }
+}
+
+impl<T: Debug + Send + Sync> ToDot for Tree<T> {
+    fn to_dot(&self) -> String {
+        let mut dot = DotWriter::new("Tree");
+        let mut stack = self.roots.clone();
+
+        while let Some(id) = stack.pop() {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+
+            dot.node(&id, &format!("{:?}", node.value), "");
+
+            for child_id in &node.children {
+                dot.edge(&id, child_id, "", "");
+                stack.push(*child_id);
+            }
+        }
+
+        dot.finish()
+    }
+}
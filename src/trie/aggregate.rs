@@ -0,0 +1,51 @@
+/// Summarizes the payloads stored in every subtree of a Trie, which lets ranked searches such as
+/// 'Trie::top_k_by_prefix' skip subtrees that can't hold a better key than the ones found so far.
+///
+/// Every payload is given a score, and the scores within a subtree are folded together with
+/// 'combine', which must be associative and have 'identity' as its neutral element. Pruning is only
+/// valid if the aggregate of a subtree is never lower than the score of a payload in it; this holds
+/// for 'Max', and for 'Sum' as long as no score is negative.
+pub trait Aggregate<T>: Send + Sync {
+    fn score(&self, payload: &T) -> f64;
+
+    fn identity(&self) -> f64;
+
+    fn combine(&self, a: f64, b: f64) -> f64;
+}
+
+/// Aggregates the highest score in every subtree, e.g. the best rating of any completion.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Max<F>(pub F);
+
+impl<T, F: Fn(&T) -> f64 + Send + Sync> Aggregate<T> for Max<F> {
+    fn score(&self, payload: &T) -> f64 {
+        (self.0)(payload)
+    }
+
+    fn identity(&self) -> f64 {
+        f64::NEG_INFINITY
+    }
+
+    fn combine(&self, a: f64, b: f64) -> f64 {
+        a.max(b)
+    }
+}
+
+/// Aggregates the total score of every subtree, e.g. how often any key starting with a prefix was
+/// looked up. Scores must not be negative for the total to bound them.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Sum<F>(pub F);
+
+impl<T, F: Fn(&T) -> f64 + Send + Sync> Aggregate<T> for Sum<F> {
+    fn score(&self, payload: &T) -> f64 {
+        (self.0)(payload)
+    }
+
+    fn identity(&self) -> f64 {
+        0.0
+    }
+
+    fn combine(&self, a: f64, b: f64) -> f64 {
+        a + b
+    }
+}
@@ -0,0 +1,171 @@
+use alloc::collections::VecDeque;
+use core::str::Chars;
+
+use crate::arena::*;
+use crate::arena::prelude::*;
+use crate::collections::HashMap;
+use crate::trie::grammar::*;
+use crate::trie::trie::TrieNode;
+
+type Id = GenerationalId;
+
+/// The links added to each node of the trie to turn it into an automaton.
+#[derive(Debug, Copy, Clone)]
+struct Links {
+    /// The node for the longest proper suffix of this node's path which is also in the trie.
+    fail: Id,
+
+    /// The closest node along the chain of fail links which holds a payload.
+    output: Option<Id>,
+
+    /// The length of this node's path.
+    depth: usize
+}
+
+/// A match of one of the keys of an AhoCorasick automaton, where 'start' and 'end' are the char
+/// positions of the match in the haystack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match<T> {
+    pub start: usize,
+    pub end: usize,
+    pub payload: T
+}
+
+/// This class represents an Aho-Corasick automaton, which finds every occurrence of every key of
+/// a Trie in a single pass over a haystack.
+///
+/// The automaton is the trie itself, where every node also knows where to resume matching when
+/// the next char of the haystack doesn't continue its path. The empty key never matches.
+pub struct AhoCorasick<T: Send + Sync> {
+    arena: GenerationalArena<TrieNode<T>>,
+    grammar: Grammar,
+    root: Id,
+    links: HashMap<Id, Links>
+}
+
+impl<T: Send + Sync> AhoCorasick<T> {
+
+    /// Constructs the automaton from the nodes of a Trie.
+    pub(crate) fn new(arena: GenerationalArena<TrieNode<T>>, grammar: Grammar, root: Id) -> Self {
+        let mut automaton = Self {
+            arena,
+            grammar,
+            root,
+            links: HashMap::new()
+        };
+
+        automaton.links.insert(root, Links { fail: root, output: None, depth: 0 });
+
+        // --
+        // Nodes are visited in breadth-first order, so the fail link of a node is always computed
+        // before the ones of its children.
+        let mut queue = VecDeque::from([root]);
+
+        while let Some(id) = queue.pop_front() {
+            let links = automaton.links[&id];
+            let children = automaton.node(&id).read().unwrap().children.clone();
+
+            for (c, child) in children.iter() {
+                let fail = if id == root {
+                    root
+                } else {
+                    automaton.step(links.fail, c)
+                };
+
+                let output = if fail != root && automaton.node(&fail).read().unwrap().is_terminal() {
+                    Some(fail)
+                } else {
+                    automaton.links[&fail].output
+                };
+
+                automaton.links.insert(child, Links { fail, output, depth: links.depth + 1 });
+                queue.push_back(child);
+            }
+        }
+
+        automaton
+    }
+
+    /// Returns the node reached by reading the char at grammar index 'c' from the given node,
+    /// following fail links until the char can be read.
+    fn step(&self, node_id: Id, c: usize) -> Id {
+        let mut node_id = node_id;
+
+        loop {
+            if let Some(child) = self.node(&node_id).read().unwrap().child(c) {
+                return child;
+            }
+
+            if node_id == self.root {
+                return self.root;
+            }
+
+            node_id = self.links[&node_id].fail;
+        }
+    }
+
+    fn node(&self, node_id: &Id) -> SharedRef<TrieNode<T>> {
+        self.arena.get_node(node_id).expect("node doesnt exist!")
+    }
+}
+
+impl<T: Clone + Send + Sync> AhoCorasick<T> {
+
+    /// Returns every (possibly overlapping) match in 'haystack', ordered by where the matches end
+    /// and then from longest to shortest.
+    pub fn find_iter<'a>(&'a self, haystack: &'a str) -> FindIter<'a, T> {
+        FindIter {
+            automaton: self,
+            chars: haystack.chars(),
+            node_id: self.root,
+            pos: 0,
+            pending: None
+        }
+    }
+}
+
+/// An iterator over the matches of an AhoCorasick automaton in a haystack.
+pub struct FindIter<'a, T: Send + Sync> {
+    automaton: &'a AhoCorasick<T>,
+    chars: Chars<'a>,
+
+    /// The state of the automaton after reading the first 'pos' chars.
+    node_id: Id,
+    pos: usize,
+
+    /// The next node holding a match which ends at 'pos'.
+    pending: Option<Id>
+}
+
+impl<T: Clone + Send + Sync> Iterator for FindIter<'_, T> {
+    type Item = Match<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(id) = self.pending {
+                let links = self.automaton.links[&id];
+                self.pending = links.output;
+
+                let payload = self.automaton.node(&id).read().unwrap().payload.clone()?;
+                return Some(Match { start: self.pos - links.depth, end: self.pos, payload });
+            }
+
+            let c = self.chars.next()?;
+            self.pos += 1;
+
+            // Chars outside of the grammar can't be part of any key, so matching starts over.
+            self.node_id = match self.automaton.grammar.idx(c) {
+                None => self.automaton.root,
+                Some(c) => self.automaton.step(self.node_id, c)
+            };
+
+            self.pending = if self.node_id != self.automaton.root
+                && self.automaton.node(&self.node_id).read().unwrap().is_terminal()
+            {
+                Some(self.node_id)
+            } else {
+                self.automaton.links[&self.node_id].output
+            };
+        }
+    }
+}
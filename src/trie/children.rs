@@ -0,0 +1,175 @@
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem::size_of;
+
+use crate::arena::GenerationalId;
+
+type Id = GenerationalId;
+
+/// Nodes with up to this many children keep them in a sorted Vec.
+const SPARSE_LIMIT: usize = 16;
+
+/// Nodes with more children switch to a slot per char of the grammar once that takes at most this
+/// many slots per child, and to a map otherwise.
+const DENSE_SLOTS_PER_CHILD: usize = 4;
+
+/// The links from a TrieNode to its children, keyed by the grammar index of the char leading to
+/// them.
+///
+/// A slot per char of the grammar is fast, but takes far too much memory for large grammars (a
+/// grammar of every Unicode letter has well over 100k chars), where most nodes only have a handful
+/// of children. So just like an adaptive radix tree, every node picks the representation which
+/// suits its number of children: most nodes have a few children and keep them in a small sorted
+/// Vec, nodes with many children of a small grammar get a slot per char, and the rest use a map.
+/// The map is ordered, since the trie visits children in grammar order.
+#[derive(Debug, Clone)]
+pub(crate) enum Children {
+    /// Up to SPARSE_LIMIT children, sorted by grammar index.
+    Sparse(Vec<(usize, Id)>),
+
+    /// A slot per char of the grammar.
+    Dense(Vec<Option<Id>>),
+
+    /// Many children of a large grammar.
+    Map(BTreeMap<usize, Id>)
+}
+
+impl Default for Children {
+    fn default() -> Self {
+        Children::Sparse(vec![])
+    }
+}
+
+impl Children {
+
+    /// Returns the child for the given grammar index.
+    pub fn get(&self, idx: usize) -> Option<Id> {
+        match self {
+            Children::Sparse(entries) => entries.binary_search_by_key(&idx, |(i, _)| *i).ok().map(|pos| entries[pos].1),
+            Children::Dense(slots) => slots.get(idx).copied().flatten(),
+            Children::Map(map) => map.get(&idx).copied()
+        }
+    }
+
+    /// Sets the child for the given grammar index, in a grammar with 'arity' chars.
+    pub fn insert(&mut self, idx: usize, id: Id, arity: usize) {
+        match self {
+            Children::Sparse(entries) => match entries.binary_search_by_key(&idx, |(i, _)| *i) {
+                Ok(pos) => entries[pos].1 = id,
+                Err(pos) if entries.len() < SPARSE_LIMIT => entries.insert(pos, (idx, id)),
+                Err(_) => {
+                    // The node has outgrown the Vec, so it moves to one of the larger layouts.
+                    let len = entries.len() + 1;
+                    *self = if arity <= len * DENSE_SLOTS_PER_CHILD {
+                        let mut slots = vec![None; arity];
+                        entries.iter().for_each(|(i, child)| slots[*i] = Some(*child));
+                        Children::Dense(slots)
+                    } else {
+                        Children::Map(entries.iter().copied().collect())
+                    };
+                    self.insert(idx, id, arity);
+                }
+            },
+            Children::Dense(slots) => {
+                // The grammar may have grown since the slots were allocated.
+                if idx >= slots.len() {
+                    slots.resize(arity.max(idx + 1), None);
+                }
+                slots[idx] = Some(id);
+            }
+            Children::Map(map) => {
+                map.insert(idx, id);
+            }
+        }
+    }
+
+    /// Removes the child for the given grammar index, returning it if there was one.
+    pub fn remove(&mut self, idx: usize) -> Option<Id> {
+        let removed = match self {
+            Children::Sparse(entries) => {
+                let pos = entries.binary_search_by_key(&idx, |(i, _)| *i).ok()?;
+                return Some(entries.remove(pos).1);
+            }
+            Children::Dense(slots) => slots.get_mut(idx)?.take(),
+            Children::Map(map) => map.remove(&idx)
+        };
+
+        // Nodes which lost most of their children move back into a Vec. This leaves some slack
+        // below the limit, so a node doesn't flip back and forth between layouts.
+        if removed.is_some() && self.len() <= SPARSE_LIMIT / 2 {
+            *self = Children::Sparse(self.iter().collect());
+        }
+
+        removed
+    }
+
+    /// Returns the number of children.
+    pub fn len(&self) -> usize {
+        match self {
+            Children::Sparse(entries) => entries.len(),
+            Children::Dense(slots) => slots.iter().flatten().count(),
+            Children::Map(map) => map.len()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Children::Sparse(entries) => entries.is_empty(),
+            Children::Dense(slots) => slots.iter().all(|slot| slot.is_none()),
+            Children::Map(map) => map.is_empty()
+        }
+    }
+
+    /// Returns the children along with their grammar indices, in grammar order.
+    pub fn iter(&self) -> ChildIter<'_> {
+        match self {
+            Children::Sparse(entries) => ChildIter::Sparse(entries.iter()),
+            Children::Dense(slots) => ChildIter::Dense(slots.iter().enumerate()),
+            Children::Map(map) => ChildIter::Map(map.iter())
+        }
+    }
+
+    /// Returns the children, in grammar order.
+    pub fn ids(&self) -> impl Iterator<Item = Id> + '_ {
+        self.iter().map(|(_, id)| id)
+    }
+
+    /// Replaces the Id of every child with the one returned by 'f'.
+    pub fn remap<F: FnMut(Id) -> Id>(&mut self, mut f: F) {
+        match self {
+            Children::Sparse(entries) => entries.iter_mut().for_each(|(_, id)| *id = f(*id)),
+            Children::Dense(slots) => slots.iter_mut().flatten().for_each(|id| *id = f(*id)),
+            Children::Map(map) => map.values_mut().for_each(|id| *id = f(*id))
+        }
+    }
+
+    /// Returns the approximate number of bytes allocated for the children.
+    pub fn heap_bytes(&self) -> usize {
+        match self {
+            Children::Sparse(entries) => entries.capacity() * size_of::<(usize, Id)>(),
+            Children::Dense(slots) => slots.capacity() * size_of::<Option<Id>>(),
+            // Every entry of a BTreeMap carries about half an entry of slack and bookkeeping.
+            Children::Map(map) => map.len() * size_of::<(usize, Id)>() * 3 / 2
+        }
+    }
+}
+
+/// An iterator over the children of a node along with their grammar indices, in grammar order.
+pub(crate) enum ChildIter<'a> {
+    Sparse(core::slice::Iter<'a, (usize, Id)>),
+    Dense(core::iter::Enumerate<core::slice::Iter<'a, Option<Id>>>),
+    Map(alloc::collections::btree_map::Iter<'a, usize, Id>)
+}
+
+impl Iterator for ChildIter<'_> {
+    type Item = (usize, Id);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            ChildIter::Sparse(iter) => iter.next().copied(),
+            ChildIter::Dense(iter) => iter.find_map(|(idx, slot)| slot.map(|id| (idx, id))),
+            ChildIter::Map(iter) => iter.next().map(|(idx, id)| (*idx, *id))
+        }
+    }
+}
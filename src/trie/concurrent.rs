@@ -0,0 +1,163 @@
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::trie::error::TrieError;
+use crate::trie::grammar::*;
+use crate::trie::trie::*;
+
+/// Takes a snapshot of the trie for 'ConcurrentTrie::read_snapshot'.
+type Publish<T> = fn(&Trie<T>) -> TrieSnapshot<T>;
+
+/// A Trie which can be shared between threads and modified through a shared reference, e.g. to
+/// build it from several threads at once.
+///
+/// Writers take turns holding the whole trie, while any number of readers can search it at the
+/// same time. Readers which shouldn't wait on writers at all can search a 'read_snapshot' instead.
+pub struct ConcurrentTrie<T: Send + Sync> {
+    inner: RwLock<Trie<T>>,
+
+    /// The latest snapshot handed out by 'read_snapshot', which every write swaps for a new one
+    /// once the first snapshot was asked for. It is only ever locked after 'inner'.
+    published: RwLock<Option<Arc<TrieSnapshot<T>>>>,
+
+    /// Takes the snapshots to publish. Writes don't require the payloads to be Clone, so this is
+    /// set by the first 'read_snapshot', which does.
+    publish: OnceLock<Publish<T>>
+}
+
+impl<T: Send + Sync> From<Trie<T>> for ConcurrentTrie<T> {
+    fn from(trie: Trie<T>) -> Self {
+        Self { inner: RwLock::new(trie), published: RwLock::new(None), publish: OnceLock::new() }
+    }
+}
+
+impl<T: Send + Sync> ConcurrentTrie<T> {
+
+    /// Constructs a new ConcurrentTrie with the given Grammar
+    pub fn new(grammar: Grammar) -> Self {
+        Self::from(Trie::new(grammar))
+    }
+
+    /// Constructs a new ConcurrentTrie with the given Grammar, whose nodes are spread over the given
+    /// number of arena shards.
+    pub fn with_shards(grammar: Grammar, shards: usize) -> Self {
+        Self::from(Trie::with_shards(grammar, shards))
+    }
+
+    /// Returns the underlying Trie.
+    pub fn into_inner(self) -> Trie<T> {
+        self.inner.into_inner().unwrap()
+    }
+
+    /// Attempts to insert 'seq', returning an error if it already exists.
+    pub fn insert(&self, seq: &str, t: T) -> Result<(), TrieError> {
+        self.write(|trie| trie.insert(seq, t))
+    }
+
+    /// Inserts 'seq', returning the previous value if it already exists.
+    pub fn insert_or_apply<F>(&self, seq: &str, t: T, f: F) -> Result<Option<T>, TrieError>
+        where F: Fn(&T) -> T
+    {
+        self.write(|trie| trie.insert_or_apply(seq, t, f))
+    }
+
+    pub fn delete(&self, seq: &str) -> Result<Option<T>, TrieError> {
+        self.write(|trie| trie.delete(seq))
+    }
+
+    /// Removes every key starting with 'prefix', returning the number of keys removed.
+    pub fn delete_prefix(&self, prefix: &str) -> usize {
+        self.write(|trie| trie.delete_prefix(prefix))
+    }
+
+    pub fn contains(&self, seq: &str) -> bool {
+        self.inner.read().unwrap().contains(seq)
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.read().unwrap().is_empty()
+    }
+
+    /// Returns the number of keys starting with 'prefix'.
+    pub fn count_prefix(&self, prefix: &str) -> usize {
+        self.inner.read().unwrap().count_prefix(prefix)
+    }
+
+    /// Returns the shortest prefix of 'seq' which no other key starts with.
+    pub fn shortest_unique_prefix(&self, seq: &str) -> Option<String> {
+        self.inner.read().unwrap().shortest_unique_prefix(seq)
+    }
+
+    /// Returns the Aggregate of the payloads of all keys starting with 'prefix'.
+    pub fn aggregate_prefix(&self, prefix: &str) -> Option<f64> {
+        self.inner.read().unwrap().aggregate_prefix(prefix)
+    }
+
+    /// Changes the trie under the write lock, then publishes a new snapshot for readers if any
+    /// were handed out. The lock is still held, so snapshots are published in the order of writes.
+    fn write<R>(&self, f: impl FnOnce(&mut Trie<T>) -> R) -> R {
+        let mut trie = self.inner.write().unwrap();
+        let result = f(&mut trie);
+
+        if let Some(publish) = self.publish.get() {
+            *self.published.write().unwrap() = Some(Arc::new(publish(&trie)));
+        }
+
+        result
+    }
+}
+
+impl<T: Clone + Send + Sync> ConcurrentTrie<T> {
+
+    /// Inserts 'seq', returning the previous value if it already exists.
+    pub fn insert_or_update(&self, seq: &str, t: T) -> Result<Option<T>, TrieError> {
+        self.write(|trie| trie.insert_or_update(seq, t))
+    }
+
+    /// Returns a snapshot of the trie, which readers can search without ever waiting on writers or
+    /// seeing a change they make halfway, see 'TrieSnapshot'.
+    ///
+    /// Every write publishes a new snapshot once the first one was asked for, so this only clones
+    /// the latest one. Writes pay for that by copying the nodes they change which are still shared
+    /// with a snapshot.
+    pub fn read_snapshot(&self) -> Arc<TrieSnapshot<T>> {
+        if let Some(snapshot) = self.published.read().unwrap().as_ref() {
+            return Arc::clone(snapshot);
+        }
+
+        // The trie is read locked before publishing the first snapshot, so no write can slip in
+        // between taking it and publishing it.
+        let trie = self.inner.read().unwrap();
+        self.publish.get_or_init(|| Trie::read_snapshot);
+
+        let mut published = self.published.write().unwrap();
+        Arc::clone(published.get_or_insert_with(|| Arc::new(trie.read_snapshot())))
+    }
+
+    pub fn find(&self, seq: &str) -> Option<T> {
+        self.inner.read().unwrap().find(seq)
+    }
+
+    /// Same as 'find', but returns an error if 'seq' contains a char outside of the grammar.
+    pub fn try_find(&self, seq: &str) -> Result<Option<T>, TrieError> {
+        self.inner.read().unwrap().try_find(seq)
+    }
+
+    /// Returns all keys starting with 'prefix' along with their payloads, in grammar order.
+    pub fn iter_prefix(&self, prefix: &str) -> Iter<T> {
+        self.inner.read().unwrap().iter_prefix(prefix)
+    }
+
+    /// Returns the longest key which is a prefix of 'seq', along with its payload.
+    pub fn longest_prefix(&self, seq: &str) -> Option<(String, T)> {
+        self.inner.read().unwrap().longest_prefix(seq)
+    }
+
+    /// Returns the 'k' best scoring keys starting with 'prefix' along with their payloads.
+    pub fn top_k_by_prefix(&self, prefix: &str, k: usize) -> Vec<(String, T)> {
+        self.inner.read().unwrap().top_k_by_prefix(prefix, k)
+    }
+}
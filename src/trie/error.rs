@@ -0,0 +1,41 @@
+use core::error::Error;
+use core::fmt;
+
+/// The ways in which an operation on a trie can fail.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TrieError {
+    /// The key is already stored in the trie.
+    KeyExists,
+
+    /// The key is not stored in the trie.
+    KeyNotFound,
+
+    /// The key contains a char which is not part of the trie's grammar.
+    CharNotInGrammar { ch: char },
+
+    /// The keys given to 'Trie::from_sorted_iter' aren't strictly ascending in grammar order, which
+    /// includes a key given twice.
+    UnsortedInput,
+
+    /// The LCRS encoding of a trie isn't a valid tree, or a node other than the root has no char,
+    /// see 'Trie::from_lcrs'.
+    InvalidLcrs,
+
+    /// Two tries which are combined, see 'Trie::merge', don't share the same grammar.
+    GrammarMismatch
+}
+
+impl fmt::Display for TrieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TrieError::KeyExists => write!(f, "key already exists"),
+            TrieError::KeyNotFound => write!(f, "key not found"),
+            TrieError::CharNotInGrammar { ch } => write!(f, "char '{}' is not part of grammar", ch),
+            TrieError::UnsortedInput => write!(f, "keys are not strictly ascending"),
+            TrieError::InvalidLcrs => write!(f, "lcrs tree is not a valid trie"),
+            TrieError::GrammarMismatch => write!(f, "tries do not share the same grammar")
+        }
+    }
+}
+
+impl Error for TrieError {}
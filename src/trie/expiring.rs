@@ -0,0 +1,213 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use crate::trie::error::TrieError;
+use crate::trie::grammar::*;
+use crate::trie::trie::*;
+
+/// A payload along with what the ExpiringTrie needs to know to evict it.
+#[derive(Debug, Clone)]
+struct Stamped<T> {
+    payload: T,
+
+    /// When the key was last used, which orders the keys for LRU eviction.
+    used: u64,
+
+    /// When the key expires, along with when it was written to tell apart keys expiring at once.
+    expiry: Option<(Instant, u64)>
+}
+
+/// A Trie for use as a prefix-keyed cache, whose keys expire once they have lived for their time
+/// to live (TTL), or are evicted once the trie holds more keys than its capacity, least recently
+/// used (LRU) first.
+///
+/// Expired keys are purged lazily: every call which changes the trie first purges the keys which
+/// have expired since, and lookups never return expired keys. 'purge_expired' can be called to
+/// release their memory without waiting for the next change. Purging a key prunes the path to it
+/// from the trie, just like deleting it.
+pub struct ExpiringTrie<T: Send + Sync> {
+    trie: Trie<Stamped<T>>,
+    ttl: Option<Duration>,
+    capacity: Option<usize>,
+
+    /// The keys by when they were last used, least recently used first.
+    by_use: BTreeMap<u64, String>,
+
+    /// The keys which expire, soonest first.
+    by_expiry: BTreeMap<(Instant, u64), String>,
+
+    /// Counts up with every use of a key, so it can be used to order them.
+    tick: u64
+}
+
+impl<T: Send + Sync> ExpiringTrie<T> {
+
+    /// Constructs a new ExpiringTrie with the given Grammar, whose keys expire after 'ttl' unless
+    /// given a TTL of their own, and which holds up to 'capacity' keys. Either can be left out.
+    pub fn new(grammar: Grammar, ttl: Option<Duration>, capacity: Option<usize>) -> Self {
+        assert!(capacity != Some(0), "capacity must be at least 1");
+
+        Self {
+            trie: Trie::new(grammar),
+            ttl,
+            capacity,
+            by_use: BTreeMap::new(),
+            by_expiry: BTreeMap::new(),
+            tick: 0
+        }
+    }
+
+    /// Returns the number of keys stored, which includes expired keys which haven't been purged
+    /// yet.
+    pub fn len(&self) -> usize {
+        self.trie.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Inserts 'seq' with the trie's TTL, returning the previous value if it is stored and hasn't
+    /// expired. The key counts as used, and the least recently used keys are evicted if the trie
+    /// holds too many keys.
+    pub fn insert(&mut self, seq: &str, t: T) -> Result<Option<T>, TrieError> {
+        self._insert(seq, t, self.ttl)
+    }
+
+    /// Same as 'insert', but the key expires after the given TTL instead of the trie's.
+    pub fn insert_with_ttl(&mut self, seq: &str, t: T, ttl: Duration) -> Result<Option<T>, TrieError> {
+        self._insert(seq, t, Some(ttl))
+    }
+
+    /// Removes 'seq', returning its value if it is stored and hasn't expired.
+    pub fn remove(&mut self, seq: &str) -> Option<T> {
+        self.purge_expired();
+        self.remove_entry(seq).ok().flatten().map(|stamped| stamped.payload)
+    }
+
+    /// Removes every key which has expired, returning the number of keys removed.
+    pub fn purge_expired(&mut self) -> usize {
+        let now = Instant::now();
+        let mut purged = 0;
+
+        while let Some(entry) = self.by_expiry.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+
+            let seq = entry.remove();
+            self.remove_entry(&seq).expect("could not purge key");
+            purged += 1;
+        }
+
+        purged
+    }
+
+    fn _insert(&mut self, seq: &str, t: T, ttl: Option<Duration>) -> Result<Option<T>, TrieError> {
+        self.purge_expired();
+
+        // The key is written anew, so that it is stamped from scratch.
+        let prev = self.remove_entry(seq)?.map(|stamped| stamped.payload);
+
+        let used = self.next_tick();
+        let expiry = ttl.map(|ttl| (Instant::now() + ttl, used));
+
+        self.trie.insert(seq, Stamped { payload: t, used, expiry })?;
+        self.by_use.insert(used, seq.to_string());
+        if let Some(expiry) = expiry {
+            self.by_expiry.insert(expiry, seq.to_string());
+        }
+
+        // --
+        // Evict the least recently used keys, which never includes the key just inserted.
+        while self.capacity.is_some_and(|capacity| self.len() > capacity) {
+            let (_, lru) = self.by_use.pop_first().expect("trie is empty");
+            self.remove_entry(&lru)?;
+        }
+
+        Ok(prev)
+    }
+
+    /// Removes 'seq' from the trie along with its records, returning its entry if it is stored.
+    fn remove_entry(&mut self, seq: &str) -> Result<Option<Stamped<T>>, TrieError> {
+        let stamped = match self.trie.delete(seq) {
+            Ok(stamped) => stamped.expect("key has no payload"),
+            Err(TrieError::KeyNotFound) => return Ok(None),
+            Err(err) => return Err(err)
+        };
+
+        self.by_use.remove(&stamped.used);
+        if let Some(expiry) = &stamped.expiry {
+            self.by_expiry.remove(expiry);
+        }
+
+        Ok(Some(stamped))
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+impl<T: Clone + Send + Sync> ExpiringTrie<T> {
+
+    /// Returns the value of 'seq' if it is stored and hasn't expired, marking it as used.
+    pub fn get(&mut self, seq: &str) -> Option<T> {
+        self.purge_expired();
+
+        let used = self.next_tick();
+        let entry = match self.trie.entry(seq).ok()? {
+            Entry::Vacant(_) => return None,
+            Entry::Occupied(entry) => entry
+        };
+
+        let mut last_used = used;
+        entry.modify(|stamped| std::mem::swap(&mut stamped.used, &mut last_used));
+
+        let key = self.by_use.remove(&last_used).expect("key is not tracked");
+        self.by_use.insert(used, key);
+        Some(entry.get().payload)
+    }
+
+    /// Returns the value of 'seq' if it is stored and hasn't expired, without marking it as used.
+    pub fn peek(&self, seq: &str) -> Option<T> {
+        self.trie.find(seq)
+            .filter(|stamped| !is_expired(stamped, Instant::now()))
+            .map(|stamped| stamped.payload)
+    }
+
+    /// Returns true if 'seq' is stored and hasn't expired.
+    pub fn contains(&self, seq: &str) -> bool {
+        self.peek(seq).is_some()
+    }
+
+    /// Returns all keys starting with 'prefix' which haven't expired along with their values, in
+    /// grammar order. The keys aren't marked as used.
+    pub fn iter_prefix(&self, prefix: &str) -> impl DoubleEndedIterator<Item = (String, T)> {
+        let now = Instant::now();
+        self.trie.iter_prefix(prefix)
+            .filter(move |(_, stamped)| !is_expired(stamped, now))
+            .map(|(key, stamped)| (key, stamped.payload))
+    }
+
+    /// Removes every key starting with 'prefix' (including 'prefix' itself), returning the number
+    /// of keys removed which hadn't expired.
+    pub fn delete_prefix(&mut self, prefix: &str) -> usize {
+        self.purge_expired();
+
+        let stamps: Vec<Stamped<T>> = self.trie.iter_prefix(prefix).map(|(_, stamped)| stamped).collect();
+        for stamped in &stamps {
+            self.by_use.remove(&stamped.used);
+            if let Some(expiry) = &stamped.expiry {
+                self.by_expiry.remove(expiry);
+            }
+        }
+
+        self.trie.delete_prefix(prefix)
+    }
+}
+
+fn is_expired<T>(stamped: &Stamped<T>, now: Instant) -> bool {
+    stamped.expiry.is_some_and(|(expires, _)| expires <= now)
+}
@@ -0,0 +1,205 @@
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::arena::*;
+use crate::arena::prelude::*;
+use crate::trie::grammar::*;
+use crate::trie::normalize::Normalizer;
+use crate::trie::trie::TrieNode;
+
+type Id = GenerationalId;
+
+/// Marks a node which doesn't hold a payload.
+const NO_VALUE: u32 = u32::MAX;
+
+/// This class represents a read-only Trie, compiled into a handful of flat arrays by
+/// 'Trie::freeze'.
+///
+/// The nodes are numbered in level order (the root first, then its children, then their children,
+/// and so on) so that the children of every node are numbered consecutively. Every node then only
+/// needs to store the grammar index of the char leading into it, where its children start, the
+/// number of keys below it, and where its payload is stored; a lookup binary searches the chars of
+/// the children at every level. Compared to the Trie, there is no lock, reference count or link
+/// for every char of the grammar per node.
+#[derive(Clone)]
+pub struct FrozenTrie<T> {
+    grammar: Grammar,
+    normalizer: Option<Arc<dyn Normalizer>>,
+
+    /// The grammar index of the char leading into each node (0 for the root).
+    labels: Vec<u32>,
+
+    /// The children of node 'i' are the nodes in 'first_child[i]..first_child[i + 1]'.
+    first_child: Vec<u32>,
+
+    /// The number of keys stored in the subtree rooted at each node, including its own.
+    counts: Vec<u32>,
+
+    /// The index of each node's payload in 'values', or NO_VALUE.
+    value_idx: Vec<u32>,
+
+    values: Vec<T>
+}
+
+impl<T: Send + Sync> FrozenTrie<T> {
+
+    /// Compiles the nodes of a Trie, moving their payloads out of them.
+    pub(crate) fn new(
+        arena: GenerationalArena<TrieNode<T>>,
+        grammar: Grammar,
+        normalizer: Option<Arc<dyn Normalizer>>,
+        root: Id
+    ) -> Self {
+        let mut trie = Self {
+            grammar,
+            normalizer,
+            labels: vec![0],
+            first_child: vec![],
+            counts: vec![],
+            value_idx: vec![],
+            values: vec![]
+        };
+
+        // --
+        // Nodes are numbered as they are discovered by a breadth-first traversal, so the children
+        // of every node get consecutive numbers in grammar order.
+        let mut order = vec![root];
+        let mut next = 0;
+
+        while next < order.len() {
+            let node_ref = arena.get_node(&order[next]).expect("node doesnt exist!");
+            let mut node = node_ref.write().unwrap();
+
+            trie.first_child.push(Self::to_u32(order.len()));
+            for (idx, child_id) in node.children.iter() {
+                order.push(child_id);
+                trie.labels.push(Self::to_u32(idx));
+            }
+
+            trie.counts.push(Self::to_u32(node.count));
+            match node.payload.take() {
+                None => trie.value_idx.push(NO_VALUE),
+                Some(payload) => {
+                    trie.value_idx.push(Self::to_u32(trie.values.len()));
+                    trie.values.push(payload);
+                }
+            }
+
+            next += 1;
+        }
+
+        trie.first_child.push(Self::to_u32(order.len()));
+        trie
+    }
+
+    fn to_u32(n: usize) -> u32 {
+        u32::try_from(n).ok().filter(|n| *n != NO_VALUE).expect("trie is too large to be frozen")
+    }
+}
+
+impl<T> FrozenTrie<T> {
+
+    pub fn find(&self, seq: &str) -> Option<&T> {
+        let node = self._find_node(seq)?;
+        self.value(node)
+    }
+
+    pub fn contains(&self, seq: &str) -> bool {
+        self.find(seq).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of nodes in the trie.
+    pub fn node_count(&self) -> usize {
+        self.labels.len()
+    }
+
+    /// Returns the approximate number of bytes used by the trie, not counting memory which the
+    /// payloads themselves allocate.
+    pub fn approx_bytes(&self) -> usize {
+        let arrays = self.labels.capacity() + self.first_child.capacity() + self.counts.capacity() + self.value_idx.capacity();
+        arrays * core::mem::size_of::<u32>() + self.values.capacity() * core::mem::size_of::<T>()
+    }
+
+    /// Returns the number of keys starting with 'prefix', in O(prefix length * log arity).
+    pub fn count_prefix(&self, prefix: &str) -> usize {
+        self._find_node(prefix).map_or(0, |node| self.counts[node] as usize)
+    }
+
+    /// Returns all keys along with their payloads, in grammar order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (String, &T)> {
+        self.iter_prefix("")
+    }
+
+    /// Returns all keys starting with 'prefix' along with their payloads, in grammar order.
+    pub fn iter_prefix(&self, prefix: &str) -> impl DoubleEndedIterator<Item = (String, &T)> {
+        let mut result = vec![];
+
+        if let Some(node) = self._find_node(prefix) {
+            let chars = self.grammar.seq();
+            let mut key: String = self.grammar.to_indices(&self.normalize(prefix))
+                .expect("prefix was found")
+                .iter()
+                .map(|idx| chars[*idx])
+                .collect();
+
+            self._collect(node, &chars, &mut key, &mut result);
+        }
+
+        result.into_iter()
+    }
+
+    /// Returns the node reached by following 'seq' from the root, if any. Chars outside of the
+    /// grammar can't be part of any key, so they aren't found rather than causing a panic.
+    fn _find_node(&self, seq: &str) -> Option<usize> {
+        let seq = self.grammar.to_indices(&self.normalize(seq)).ok()?;
+        let mut node = 0;
+
+        for idx in seq {
+            let children = self.first_child[node] as usize..self.first_child[node + 1] as usize;
+            let offset = self.labels[children.clone()].binary_search(&(idx as u32)).ok()?;
+            node = children.start + offset;
+        }
+
+        Some(node)
+    }
+
+    /// Passes 'seq' through the normalizer, if there is one.
+    fn normalize<'a>(&self, seq: &'a str) -> Cow<'a, str> {
+        match &self.normalizer {
+            None => Cow::Borrowed(seq),
+            Some(normalizer) => Cow::Owned(normalizer.normalize(seq))
+        }
+    }
+
+    fn value(&self, node: usize) -> Option<&T> {
+        match self.value_idx[node] {
+            NO_VALUE => None,
+            idx => Some(&self.values[idx as usize])
+        }
+    }
+
+    /// Appends every key in the subtree rooted at the given node to 'out', where 'key' is the
+    /// sequence of chars leading to the node.
+    fn _collect<'a>(&'a self, node: usize, chars: &[char], key: &mut String, out: &mut Vec<(String, &'a T)>) {
+        if let Some(payload) = self.value(node) {
+            out.push((key.clone(), payload));
+        }
+
+        for child in self.first_child[node] as usize..self.first_child[node + 1] as usize {
+            key.push(chars[self.labels[child] as usize]);
+            self._collect(child, chars, key, out);
+            key.pop();
+        }
+    }
+}
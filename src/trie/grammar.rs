@@ -1,28 +1,36 @@
-use std::collections::HashMap;
-use std::fmt;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::RangeInclusive;
+
+use crate::collections::HashMap;
+use crate::trie::error::TrieError;
 
 /// You know what this means...
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Case {
     Sensitive,
     Insensitive
 }
 
 /// This is the set of possible chars in the trie data structure.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Grammar {
     mapping: HashMap<char, usize>,
     sense: Case
 }
 
 impl Grammar {
-    pub fn to_indices(&self, s: &str) -> Result<Vec<usize>, String> {
+    pub fn to_indices(&self, s: &str) -> Result<Vec<usize>, TrieError> {
         let mut out = vec![];
 
         for raw_char in s.chars() {
             match self.idx(raw_char) {
                 None => {
-                    return Err(format!("char '{}' is not part of grammar: {}", raw_char, self));
+                    return Err(TrieError::CharNotInGrammar { ch: raw_char });
                 },
                 Some(i) => {
                     out.push(i);
@@ -33,23 +41,24 @@ impl Grammar {
         Ok(out)
     }
 
+    /// Constructs a Grammar from the chars in 's_slice'. Chars are indexed in ascending order, which
+    /// is the order in which a Trie visits its keys.
     pub fn from(s_slice: &str, sense: Case) -> Self {
-        let mut chars: Vec<char> = s_slice.chars().collect();
-        chars.sort_by(|a, b| b.cmp(a));
-
-        let mut mapping = HashMap::new();
+        GrammarBuilder::new(sense).add_chars(s_slice).build()
+    }
 
-        chars.iter().for_each(
-            |c| {
-                let k = preprocess_char(c, &sense);
-                if !mapping.contains_key(&k) {
-                    let idx = mapping.len();
-                    mapping.insert(k, idx);
-                }
-            }
-        );
+    /// Returns a GrammarBuilder for assembling a Grammar out of ranges and presets.
+    pub fn builder(sense: Case) -> GrammarBuilder {
+        GrammarBuilder::new(sense)
+    }
 
-        Grammar { mapping, sense }
+    /// Returns a Grammar holding the chars of this one along with those in 's_slice', with the same
+    /// case sensitivity. Chars are reindexed in ascending order.
+    pub fn with_chars(&self, s_slice: &str) -> Self {
+        GrammarBuilder::new(self.sense)
+            .add_chars(&self.seq().into_iter().collect::<String>())
+            .add_chars(s_slice)
+            .build()
     }
 
     pub fn idx(&self, c: char) -> Option<usize> {
@@ -60,16 +69,73 @@ impl Grammar {
         let mut seq = vec!['$'; self.mapping.len()];
         self.mapping.iter().for_each(
             |(k, v)| {
-                seq[*v] = k.clone();
+                seq[*v] = *k;
             }
         );
         seq
     }
 }
 
+/// Assembles a Grammar piece by piece, e.g.
+/// `Grammar::builder(Case::Insensitive).add_range('a'..='z').add_chars("éàü").build()`.
+#[derive(Debug, Clone)]
+pub struct GrammarBuilder {
+    chars: Vec<char>,
+    sense: Case
+}
+
+impl GrammarBuilder {
+    pub fn new(sense: Case) -> Self {
+        Self { chars: vec![], sense }
+    }
+
+    /// Adds every char in the given range.
+    pub fn add_range(mut self, range: RangeInclusive<char>) -> Self {
+        self.chars.extend(range);
+        self
+    }
+
+    /// Adds every char in 's'.
+    pub fn add_chars(mut self, s: &str) -> Self {
+        self.chars.extend(s.chars());
+        self
+    }
+
+    /// Adds the ASCII digits and letters.
+    pub fn ascii_alphanumeric(self) -> Self {
+        self.add_range('0'..='9').add_range('A'..='Z').add_range('a'..='z')
+    }
+
+    /// Adds every alphabetic char in Unicode. Note that this is well over 100k chars, which makes
+    /// 'Grammar::seq' expensive, though Trie nodes only pay for the children they actually have.
+    pub fn unicode_letters(mut self) -> Self {
+        self.chars.extend((char::MIN..=char::MAX).filter(|c| c.is_alphabetic()));
+        self
+    }
+
+    /// Constructs the Grammar. Chars are indexed in ascending order, which is the order in which a
+    /// Trie visits its keys.
+    pub fn build(self) -> Grammar {
+        let sense = self.sense;
+
+        let mut chars: Vec<char> = self.chars.iter()
+            .map(|c| preprocess_char(c, &sense))
+            .collect();
+        chars.sort();
+        chars.dedup();
+
+        let mapping = chars.into_iter()
+            .enumerate()
+            .map(|(idx, c)| (c, idx))
+            .collect();
+
+        Grammar { mapping, sense }
+    }
+}
+
 impl Default for Grammar {
     fn default() -> Self {
-        Grammar::from(&"abcdefghijklmnopqrstuvwxyz", Case::Insensitive)
+        Grammar::from("abcdefghijklmnopqrstuvwxyz", Case::Insensitive)
     }
 }
 
@@ -82,13 +148,15 @@ impl fmt::Display for Grammar {
 fn preprocess_char(c: &char, sense: &Case) -> char {
     match sense {
         Case::Sensitive => {
-            c.clone()
+            *c
         }
         Case::Insensitive => {
-            if c.is_ascii_uppercase() {
-                c.to_ascii_lowercase()
-            } else {
-                c.clone()
+            // Chars whose lowercase form spans several chars (e.g. 'İ') are left untouched, since
+            // a char can only map onto a single index.
+            let mut lower = c.to_lowercase();
+            match (lower.next(), lower.next()) {
+                (Some(l), None) => l,
+                _ => *c
             }
         }
     }
@@ -0,0 +1,112 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::ops::Bound;
+
+use crate::sync::RwLock;
+
+/// The score of a payload, ordered by 'f64::total_cmp' so that it can key a BTreeSet.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Score(pub f64);
+
+impl PartialEq for Score {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// A position within a ScoreIndex, which the iterators walking it resume from.
+pub(crate) type Cursor = Bound<(Score, String)>;
+
+#[derive(Debug, Default)]
+struct Entries {
+    by_score: BTreeSet<(Score, String)>,
+    by_key: BTreeMap<String, Score>
+}
+
+/// Keeps the keys of a Trie ordered by a score derived from their payloads, see
+/// 'Trie::set_score_index'. Keys with the same score are ordered by key.
+///
+/// The trie updates the index whenever it changes a payload, which may happen through a shared
+/// reference (e.g. 'OccupiedEntry::modify'), so the entries sit behind a lock.
+pub(crate) struct ScoreIndex<T> {
+    score: Arc<dyn Fn(&T) -> f64 + Send + Sync>,
+    entries: RwLock<Entries>
+}
+
+impl<T> ScoreIndex<T> {
+    pub fn new(score: Arc<dyn Fn(&T) -> f64 + Send + Sync>) -> Self {
+        Self { score, entries: RwLock::new(Entries::default()) }
+    }
+
+    /// Returns an empty index which scores payloads in the same way.
+    pub fn empty_like(&self) -> Self {
+        Self::new(self.score.clone())
+    }
+
+    /// Records the payload of 'key', or forgets the key if it has none.
+    pub fn update(&self, key: String, payload: Option<&T>) {
+        let mut entries = self.entries.write().unwrap();
+
+        if let Some(prev) = entries.by_key.remove(&key) {
+            entries.by_score.remove(&(prev, key.clone()));
+        }
+
+        if let Some(payload) = payload {
+            let score = Score((self.score)(payload));
+            entries.by_score.insert((score, key.clone()));
+            entries.by_key.insert(key, score);
+        }
+    }
+
+    /// Forgets every key starting with 'prefix'.
+    pub fn remove_prefix(&self, prefix: &str) {
+        let mut entries = self.entries.write().unwrap();
+
+        let keys: Vec<(String, Score)> = entries.by_key.range(prefix.to_string()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, score)| (key.clone(), *score))
+            .collect();
+
+        for (key, score) in keys {
+            entries.by_key.remove(&key);
+            entries.by_score.remove(&(score, key));
+        }
+    }
+
+    pub fn clear(&self) {
+        *self.entries.write().unwrap() = Entries::default();
+    }
+
+    /// Returns the first (or last, if 'forward' is false) entry strictly between the cursors.
+    pub fn step(&self, front: &Cursor, back: &Cursor, forward: bool) -> Option<(Score, String)> {
+        // The cursors meet once every entry has been stepped over, which BTreeSet::range rejects.
+        if let (Bound::Excluded(front), Bound::Excluded(back)) = (front, back) {
+            if front >= back {
+                return None;
+            }
+        }
+
+        let entries = self.entries.read().unwrap();
+        let mut range = entries.by_score.range((front.clone(), back.clone()));
+
+        let entry = if forward { range.next() } else { range.next_back() };
+        entry.cloned()
+    }
+}
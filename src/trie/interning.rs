@@ -0,0 +1,141 @@
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::hash::Hash;
+
+use crate::collections::HashMap;
+use crate::trie::error::TrieError;
+use crate::trie::grammar::*;
+use crate::trie::normalize::Normalizer;
+use crate::trie::trie::*;
+
+/// A Trie which stores every distinct payload once, and hands out shared references to it.
+///
+/// Every key points to an Arc of its payload, and payloads which are equal share the same Arc, so
+/// a trie mapping millions of keys to a few dozen distinct values only stores those few dozen
+/// values. The trie counts how many keys use every value, and drops a value once no key uses it
+/// anymore.
+pub struct InterningTrie<T: Eq + Hash + Send + Sync> {
+    trie: Trie<Arc<T>>,
+
+    /// Every distinct payload, along with the number of keys which use it.
+    pool: HashMap<Arc<T>, usize>
+}
+
+impl<T: Eq + Hash + Send + Sync> InterningTrie<T> {
+
+    /// Constructs a new, empty InterningTrie with the given Grammar.
+    pub fn new(grammar: Grammar) -> Self {
+        Self { trie: Trie::new(grammar), pool: HashMap::new() }
+    }
+
+    /// Constructs a new, empty InterningTrie which normalizes every key before it is inserted or
+    /// looked up, see 'Trie::with_normalizer'.
+    pub fn with_normalizer(grammar: Grammar, normalizer: impl Normalizer + 'static) -> Self {
+        Self { trie: Trie::with_normalizer(grammar, normalizer), pool: HashMap::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.trie.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of distinct payloads stored.
+    pub fn distinct_len(&self) -> usize {
+        self.pool.len()
+    }
+
+    /// Returns every distinct payload along with the number of keys which use it, in no particular
+    /// order.
+    pub fn distinct(&self) -> impl Iterator<Item = (&T, usize)> {
+        self.pool.iter().map(|(t, count)| (t.as_ref(), *count))
+    }
+
+    /// Returns the underlying trie, for the lookups this trie doesn't wrap.
+    pub fn as_trie(&self) -> &Trie<Arc<T>> {
+        &self.trie
+    }
+
+    /// Inserts 'seq', returning the previous value if it already exists. The payload is replaced by
+    /// the stored copy of an equal value if there is one.
+    pub fn insert(&mut self, seq: &str, t: T) -> Result<Option<Arc<T>>, TrieError> {
+        let t = self.acquire(t);
+
+        match self.trie.insert_or_update(seq, t.clone()) {
+            Ok(prev) => {
+                if let Some(prev) = &prev {
+                    self.release(prev);
+                }
+                Ok(prev)
+            }
+            Err(err) => {
+                self.release(&t);
+                Err(err)
+            }
+        }
+    }
+
+    /// Returns the payload of 'seq', if it exists.
+    pub fn find(&self, seq: &str) -> Option<Arc<T>> {
+        self.trie.find(seq)
+    }
+
+    pub fn contains(&self, seq: &str) -> bool {
+        self.trie.contains(seq)
+    }
+
+    /// Removes 'seq', returning its value if it was stored.
+    pub fn remove(&mut self, seq: &str) -> Option<Arc<T>> {
+        let t = self.trie.delete(seq).ok()?.expect("key has no payload");
+        self.release(&t);
+        Some(t)
+    }
+
+    /// Removes every key starting with 'prefix' (including 'prefix' itself), returning the number
+    /// of keys removed.
+    pub fn delete_prefix(&mut self, prefix: &str) -> usize {
+        let payloads: Vec<Arc<T>> = self.trie.iter_prefix(prefix).map(|(_, t)| t).collect();
+        for t in &payloads {
+            self.release(t);
+        }
+
+        self.trie.delete_prefix(prefix)
+    }
+
+    /// Returns all keys along with their values, in grammar order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (String, Arc<T>)> {
+        self.trie.iter()
+    }
+
+    /// Returns all keys starting with 'prefix' along with their values, in grammar order.
+    pub fn iter_prefix(&self, prefix: &str) -> impl DoubleEndedIterator<Item = (String, Arc<T>)> {
+        self.trie.iter_prefix(prefix)
+    }
+
+    /// Returns the stored copy of 't', storing it first if there is none, and counts one more key
+    /// using it.
+    fn acquire(&mut self, t: T) -> Arc<T> {
+        if let Some((shared, count)) = self.pool.get_key_value(&t) {
+            let shared = shared.clone();
+            self.pool.insert(shared.clone(), count + 1);
+            return shared;
+        }
+
+        let shared = Arc::new(t);
+        self.pool.insert(shared.clone(), 1);
+        shared
+    }
+
+    /// Counts one less key using 't', dropping it once no key uses it.
+    fn release(&mut self, t: &T) {
+        let count = self.pool.get_mut(t).expect("payload is not interned");
+        *count -= 1;
+
+        if *count == 0 {
+            self.pool.remove(t);
+        }
+    }
+}
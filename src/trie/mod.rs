@@ -1,21 +1,96 @@
+pub mod aggregate;
+pub mod aho_corasick;
+mod children;
+#[cfg(feature = "std")]
+pub mod concurrent;
+pub mod error;
+#[cfg(feature = "std")]
+pub mod expiring;
+pub mod frozen;
 pub mod grammar;
+mod index;
+pub mod interning;
+pub mod normalize;
+pub mod persistent;
+pub mod radix;
+pub mod seq;
+pub mod set;
+pub mod suffix;
+pub mod suggest;
+pub mod ternary;
+#[allow(clippy::module_inception)]
 pub mod trie;
+pub mod writer;
+pub mod xfast;
+pub mod yfast;
 
 #[cfg(test)]
 mod tests {
+    use crate::random::*;
+    use crate::trie::aggregate::*;
+    use crate::trie::aho_corasick::*;
+    use crate::trie::children::*;
+    use crate::trie::concurrent::*;
+    use crate::trie::error::*;
+    use crate::trie::expiring::*;
     use crate::trie::grammar::*;
+    use crate::trie::interning::*;
+    use crate::trie::normalize::*;
+    use crate::trie::set::*;
     use crate::trie::trie::*;
+    use crate::trie::persistent::*;
+    use crate::trie::radix::*;
+    use crate::trie::seq::*;
+    use crate::trie::suffix::*;
+    use crate::trie::suggest::*;
+    use crate::trie::ternary::*;
+    use crate::trie::writer::*;
+    use crate::trie::xfast::*;
+    use crate::tree::lcrs::*;
+    use crate::trie::yfast::*;
 
     #[test]
     fn test_grammar() {
         let g = Grammar::default();
         assert_eq!(g.seq().len(), 26);
 
-        let g = Grammar::from(&"Aabcdefghijklmnopqrstuvwxyz", Case::Insensitive);
+        let g = Grammar::from("Aabcdefghijklmnopqrstuvwxyz", Case::Insensitive);
         assert_eq!(g.seq().len(), 26);
 
-        let g = Grammar::from(&"Aabcdefghijklmnopqrstuvwxyz", Case::Sensitive);
+        let g = Grammar::from("Aabcdefghijklmnopqrstuvwxyz", Case::Sensitive);
         assert_eq!(g.seq().len(), 27);
+
+        let g = Grammar::from("cZab", Case::Insensitive);
+        assert_eq!(g.seq(), vec!['a', 'b', 'c', 'z']);
+
+        assert_eq!(g.to_indices("Zab"), Ok(vec![3, 0, 1]));
+        assert_eq!(g.to_indices("abd"), Err(TrieError::CharNotInGrammar { ch: 'd' }));
+    }
+
+    #[test]
+    fn test_grammar_builder() {
+        let g = Grammar::builder(Case::Insensitive)
+            .add_range('a'..='c')
+            .add_chars("éÀü")
+            .build();
+        assert_eq!(g.seq(), vec!['a', 'b', 'c', 'à', 'é', 'ü']);
+        assert_eq!(g.to_indices("ÉàC"), Ok(vec![4, 3, 2]));
+        assert_eq!(g.to_indices("d"), Err(TrieError::CharNotInGrammar { ch: 'd' }));
+
+        let g = Grammar::builder(Case::Sensitive).ascii_alphanumeric().build();
+        assert_eq!(g.seq().len(), 62);
+        assert!(g.idx('Q').is_some());
+        assert!(g.idx('-').is_none());
+
+        let g = Grammar::builder(Case::Insensitive).unicode_letters().build();
+        assert!(g.idx('Ж').is_some());
+        assert_eq!(g.idx('Ж'), g.idx('ж'));
+        assert!(g.idx('7').is_none());
+
+        let mut trie = Trie::<i32>::new(Grammar::builder(Case::Insensitive).add_range('a'..='z').add_chars("éü").build());
+        assert!(trie.insert("Über", 1).is_ok());
+        assert_eq!(trie.find("über"), Some(1));
+        assert_eq!(trie.keys().collect::<Vec<_>>(), vec![String::from("über")]);
     }
 
     #[test]
@@ -31,13 +106,1887 @@ mod tests {
         assert!(trie.find("hello").is_some());
         assert_eq!(trie.len(), 1);
 
-        assert!(trie.insert("hello", ()).is_err());
+        assert_eq!(trie.insert("hello", ()), Err(TrieError::KeyExists));
         assert_eq!(trie.len(), 1);
 
         assert!(trie.delete("hello").is_ok());
         assert_eq!(trie.len(), 0);
 
-        assert!(trie.delete("hello").is_err());
+        assert_eq!(trie.delete("hello"), Err(TrieError::KeyNotFound));
         assert_eq!(trie.len(), 0);
     }
+
+    #[test]
+    fn test_trie_iter_prefix() {
+        let mut trie = Trie::<i32>::new(Grammar::default());
+        assert_eq!(trie.iter_prefix("he").count(), 0);
+
+        assert!(trie.insert("he", 1).is_ok());
+        assert!(trie.insert("hello", 2).is_ok());
+        assert!(trie.insert("help", 3).is_ok());
+        assert!(trie.insert("world", 4).is_ok());
+
+        let mut items: Vec<(String, i32)> = trie.iter_prefix("HEL").collect();
+        items.sort();
+        assert_eq!(items, vec![(String::from("hello"), 2), (String::from("help"), 3)]);
+
+        assert_eq!(trie.iter_prefix("he").count(), 3);
+        assert_eq!(trie.iter_prefix("").count(), 4);
+        assert_eq!(trie.iter_prefix("x").count(), 0);
+
+        assert!(trie.delete("help").is_ok());
+        let items: Vec<(String, i32)> = trie.iter_prefix("hel").collect();
+        assert_eq!(items, vec![(String::from("hello"), 2)]);
+    }
+
+    #[test]
+    fn test_trie_longest_prefix() {
+        let mut trie = Trie::<i32>::new(Grammar::default());
+        assert!(trie.longest_prefix("hello").is_none());
+
+        assert!(trie.insert("a", 1).is_ok());
+        assert!(trie.insert("abc", 2).is_ok());
+        assert!(trie.insert("abcdef", 3).is_ok());
+
+        assert_eq!(trie.longest_prefix("abcde"), Some((String::from("abc"), 2)));
+        assert_eq!(trie.longest_prefix("ABCDEFG"), Some((String::from("abcdef"), 3)));
+        assert_eq!(trie.longest_prefix("ab"), Some((String::from("a"), 1)));
+        assert_eq!(trie.longest_prefix("a"), Some((String::from("a"), 1)));
+        assert!(trie.longest_prefix("b").is_none());
+        assert!(trie.longest_prefix("").is_none());
+
+        assert!(trie.insert("", 0).is_ok());
+        assert_eq!(trie.longest_prefix("b"), Some((String::new(), 0)));
+    }
+
+    #[test]
+    fn test_trie_entry() {
+        let mut trie = Trie::<Vec<i32>>::new(Grammar::default());
+
+        let entry = trie.entry("Hello").unwrap().or_insert_with(|| vec![0]);
+        assert_eq!(entry.key(), "hello");
+        entry.modify(|v| v.push(1));
+
+        // The default is only computed for missing keys.
+        trie.entry("hello").unwrap().or_insert_with(|| unreachable!()).modify(|v| v.push(2));
+        assert_eq!(trie.find("hello"), Some(vec![0, 1, 2]));
+
+        trie.entry("hello").unwrap().and_modify(|v| v.clear()).or_default();
+        trie.entry("help").unwrap().and_modify(|v| v.push(3)).or_default();
+        assert_eq!(trie.find("hello"), Some(Vec::new()));
+        assert_eq!(trie.find("help"), Some(Vec::new()));
+        assert_eq!(trie.len(), 2);
+        assert_eq!(trie.count_prefix("hel"), 2);
+
+        match trie.entry("hel").unwrap() {
+            Entry::Occupied(_) => panic!("key should be vacant"),
+            Entry::Vacant(entry) => assert_eq!(entry.insert(vec![4]).get(), vec![4])
+        }
+
+        match trie.entry("help").unwrap() {
+            Entry::Vacant(_) => panic!("key should be occupied"),
+            Entry::Occupied(entry) => {
+                assert!(entry.insert(vec![5]).is_empty());
+                assert_eq!(entry.remove(), vec![5]);
+            }
+        }
+
+        assert!(!trie.contains("help"));
+        assert_eq!(trie.len(), 2);
+        assert_eq!(trie.count_prefix("hel"), 2);
+    }
+
+    #[test]
+    fn test_trie_count_prefix() {
+        let mut trie = Trie::<i32>::new(Grammar::default());
+        assert_eq!(trie.count_prefix(""), 0);
+
+        assert!(trie.insert("he", 1).is_ok());
+        assert!(trie.insert("hello", 2).is_ok());
+        assert!(trie.insert("help", 3).is_ok());
+        assert!(trie.insert("world", 4).is_ok());
+
+        assert_eq!(trie.count_prefix(""), 4);
+        assert_eq!(trie.count_prefix("he"), 3);
+        assert_eq!(trie.count_prefix("hel"), 2);
+        assert_eq!(trie.count_prefix("HELL"), 1);
+        assert_eq!(trie.count_prefix("x"), 0);
+
+        // Failed inserts and updates must leave the counts untouched.
+        assert!(trie.insert("help", 5).is_err());
+        assert!(trie.insert_or_update("help", 5).is_ok());
+        assert_eq!(trie.count_prefix("he"), 3);
+
+        assert!(trie.delete("hel").is_err());
+        assert_eq!(trie.delete("hello"), Ok(Some(2)));
+        assert_eq!(trie.count_prefix("hel"), 1);
+        assert_eq!(trie.count_prefix("he"), 2);
+        assert_eq!(trie.count_prefix(""), 3);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_trie_serde() {
+        let mut trie = Trie::<i32>::new(Grammar::from("abc", Case::Sensitive));
+        assert!(trie.insert("ab", 1).is_ok());
+        assert!(trie.insert("abc", 2).is_ok());
+        assert!(trie.insert("ca", 3).is_ok());
+
+        let json = serde_json::to_string(&trie).unwrap();
+        let restored: Trie<i32> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.len(), 3);
+        assert_eq!(restored.find("ab"), Some(1));
+        assert_eq!(restored.find("abc"), Some(2));
+        assert_eq!(restored.find("ca"), Some(3));
+        assert!(restored.find("a").is_none());
+        assert_eq!(restored.count_prefix("ab"), 2);
+        assert_eq!(restored.count_prefix(""), 3);
+
+        assert!(serde_json::from_str::<Trie<i32>>(r#"{"grammar":{"mapping":{},"sense":"Sensitive"},"nodes":[]}"#).is_err());
+    }
+
+    #[test]
+    fn test_radix_trie() {
+        let mut trie = RadixTrie::<i32>::new(Grammar::default());
+
+        assert!(trie.find("hello").is_none());
+        assert!(trie.delete("hello").is_err());
+
+        assert!(trie.insert("hello", 1).is_ok());
+        assert!(trie.insert("help", 2).is_ok());
+        assert!(trie.insert("he", 3).is_ok());
+        assert!(trie.insert("world", 4).is_ok());
+        assert_eq!(trie.insert("Hello", 5), Err(TrieError::KeyExists));
+        assert_eq!(trie.len(), 4);
+
+        assert_eq!(trie.find("hello"), Some(1));
+        assert_eq!(trie.find("HELP"), Some(2));
+        assert_eq!(trie.find("he"), Some(3));
+        assert!(trie.find("h").is_none());
+        assert!(trie.find("hel").is_none());
+        assert!(trie.find("helping").is_none());
+        assert!(trie.contains("world"));
+
+        let mut items: Vec<(String, i32)> = trie.iter_prefix("hel").collect();
+        items.sort();
+        assert_eq!(items, vec![(String::from("hello"), 1), (String::from("help"), 2)]);
+        assert_eq!(trie.iter_prefix("h").count(), 3);
+        assert_eq!(trie.iter_prefix("wo").count(), 1);
+        assert_eq!(trie.iter_prefix("wx").count(), 0);
+
+        assert_eq!(trie.delete("he"), Ok(Some(3)));
+        assert!(trie.delete("he").is_err());
+        assert_eq!(trie.delete("hel"), Err(TrieError::KeyNotFound));
+        assert_eq!(trie.delete("help"), Ok(Some(2)));
+        assert_eq!(trie.find("hello"), Some(1));
+        assert_eq!(trie.len(), 2);
+
+        // Splitting an edge which was previously merged back together.
+        assert!(trie.insert("helium", 6).is_ok());
+        assert_eq!(trie.find("hello"), Some(1));
+        assert_eq!(trie.find("helium"), Some(6));
+
+        assert_eq!(trie.delete("hello"), Ok(Some(1)));
+        assert_eq!(trie.delete("helium"), Ok(Some(6)));
+        assert_eq!(trie.delete("world"), Ok(Some(4)));
+        assert!(trie.is_empty());
+        assert_eq!(trie.iter_prefix("").count(), 0);
+    }
+
+    #[test]
+    fn test_ternary_search_tree() {
+        let mut tst = TernarySearchTree::<i32>::new(Grammar::default());
+
+        assert!(tst.find("hello").is_none());
+        assert_eq!(tst.delete("hello"), Err(TrieError::KeyNotFound));
+
+        assert!(tst.insert("hello", 1).is_ok());
+        assert!(tst.insert("help", 2).is_ok());
+        assert!(tst.insert("he", 3).is_ok());
+        assert!(tst.insert("world", 4).is_ok());
+        assert!(tst.insert("", 5).is_ok());
+        assert_eq!(tst.insert("Hello", 6), Err(TrieError::KeyExists));
+        assert_eq!(tst.len(), 5);
+
+        assert_eq!(tst.find("HELP"), Some(2));
+        assert_eq!(tst.find(""), Some(5));
+        assert!(tst.find("hel").is_none());
+        assert!(tst.find("helping").is_none());
+        assert!(tst.contains("world"));
+        assert!(!tst.contains("w"));
+
+        assert_eq!(tst.count_prefix("he"), 3);
+        assert_eq!(tst.count_prefix("hel"), 2);
+        assert_eq!(tst.count_prefix(""), 5);
+        assert_eq!(tst.count_prefix("x"), 0);
+
+        let items: Vec<(String, i32)> = tst.iter_prefix("hel").collect();
+        assert_eq!(items, vec![(String::from("hello"), 1), (String::from("help"), 2)]);
+        assert_eq!(tst.iter().map(|(key, _)| key).collect::<Vec<_>>(), vec!["", "he", "hello", "help", "world"]);
+
+        assert_eq!(tst.insert_or_update("he", 7), Ok(Some(3)));
+        assert_eq!(tst.insert_or_update("hex", 8), Ok(None));
+        assert_eq!(tst.len(), 6);
+
+        assert_eq!(tst.delete("hel"), Err(TrieError::KeyNotFound));
+        assert_eq!(tst.delete("he"), Ok(Some(7)));
+        assert_eq!(tst.delete("hello"), Ok(Some(1)));
+        assert_eq!(tst.find("help"), Some(2));
+        assert_eq!(tst.find("hex"), Some(8));
+        assert_eq!(tst.count_prefix("he"), 2);
+
+        for key in ["", "help", "hex", "world"] {
+            assert!(tst.delete(key).is_ok());
+        }
+        assert!(tst.is_empty());
+        assert_eq!(tst.memory_stats().nodes, 0);
+    }
+
+    #[test]
+    fn test_ternary_search_tree_against_trie() {
+        let grammar = || Grammar::from("abcdeαβγ", Case::Sensitive);
+        let mut tst = TernarySearchTree::<usize>::new(grammar());
+        let mut trie = Trie::<usize>::new(grammar());
+
+        let mut state: u64 = 3;
+        let mut next = |n: u64| {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            ((state >> 16) % n) as usize
+        };
+
+        let letters: Vec<char> = "abcdeαβγ".chars().collect();
+        for i in 0..2000 {
+            let key: String = (0..1 + next(5)).map(|_| letters[next(letters.len() as u64)]).collect();
+
+            if next(3) == 0 {
+                assert_eq!(tst.delete(&key), trie.delete(&key));
+            } else {
+                assert_eq!(tst.insert_or_update(&key, i), trie.insert_or_update(&key, i));
+            }
+        }
+
+        assert_eq!(tst.len(), trie.len());
+        assert_eq!(tst.iter().collect::<Vec<_>>(), trie.iter().collect::<Vec<_>>());
+        for prefix in ["a", "ab", "β", "cγ", "eee"] {
+            assert_eq!(tst.count_prefix(prefix), trie.count_prefix(prefix));
+            assert_eq!(tst.iter_prefix(prefix).collect::<Vec<_>>(), trie.iter_prefix(prefix).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_trie_normalizer() {
+        assert_eq!(CaseFold.normalize("Straße ΣΟΦΟΣ"), "strasse σοφοσ");
+        assert_eq!(StripDiacritics.normalize("Crème Brûlée, Łódź"), "Creme Brulee, Lodz");
+        assert_eq!(StripDiacritics.normalize("Cre\u{300}me"), "Creme");
+        assert_eq!((StripDiacritics, CaseFold).normalize("ÉCOLE"), "ecole");
+
+        let grammar = Grammar::from("abcdefghijklmnopqrstuvwxyz ", Case::Sensitive);
+        let mut trie = Trie::<usize>::with_normalizer(grammar, (StripDiacritics, CaseFold));
+
+        assert!(trie.insert("Café", 0).is_ok());
+        assert!(trie.insert("Straße", 1).is_ok());
+        assert_eq!(trie.insert("CAFE", 2), Err(TrieError::KeyExists));
+        assert!(trie.insert("naïve", 3).is_ok());
+
+        assert_eq!(trie.find("cafe"), Some(0));
+        assert_eq!(trie.find("STRASSE"), Some(1));
+        assert!(trie.contains("NAI\u{308}VE"));
+        assert_eq!(trie.count_prefix("Ca"), 1);
+        assert_eq!(trie.keys().collect::<Vec<_>>(), vec!["cafe", "naive", "strasse"]);
+        assert_eq!(trie.iter_prefix("STR").collect::<Vec<_>>(), vec![(String::from("strasse"), 1)]);
+        assert_eq!(trie.find_pattern("C?F*").len(), 1);
+        assert_eq!(trie.delete("Strasse"), Ok(Some(1)));
+
+        // Any function can be used as a normalizer.
+        let mut trie = Trie::<usize>::with_normalizer(Grammar::default(), |seq: &str| seq.replace('-', ""));
+        assert!(trie.insert("e-mail", 0).is_ok());
+        assert_eq!(trie.find("email"), Some(0));
+
+        let frozen = trie.freeze();
+        assert_eq!(frozen.find("E-Mail"), Some(&0));
+        assert_eq!(frozen.count_prefix("e-m"), 1);
+    }
+
+    #[test]
+    fn test_trie_iter() {
+        let mut trie = Trie::<i32>::new(Grammar::default());
+        assert_eq!(trie.iter().count(), 0);
+
+        assert!(trie.insert("banana", 1).is_ok());
+        assert!(trie.insert("apple", 2).is_ok());
+        assert!(trie.insert("app", 3).is_ok());
+        assert!(trie.insert("cherry", 4).is_ok());
+
+        let items: Vec<(String, i32)> = trie.iter().collect();
+        assert_eq!(items, vec![
+            (String::from("app"), 3),
+            (String::from("apple"), 2),
+            (String::from("banana"), 1),
+            (String::from("cherry"), 4),
+        ]);
+
+        assert_eq!(trie.keys().collect::<Vec<_>>(), vec!["app", "apple", "banana", "cherry"]);
+        assert_eq!(trie.values().collect::<Vec<_>>(), vec![3, 2, 1, 4]);
+        assert_eq!(trie.iter().len(), 4);
+
+        // Iterating from the back gives the last keys in grammar order, e.g. for paging backwards.
+        assert_eq!(trie.keys().rev().take(2).collect::<Vec<_>>(), vec!["cherry", "banana"]);
+        let mut iter = trie.iter_prefix("app");
+        assert_eq!(iter.next_back(), Some((String::from("apple"), 2)));
+        assert_eq!(iter.next(), Some((String::from("app"), 3)));
+        assert_eq!(iter.next_back(), None);
+
+        let mut count = 0;
+        for (key, _) in &trie {
+            assert!(trie.contains(&key));
+            count += 1;
+        }
+        assert_eq!(count, 4);
+
+        assert_eq!(trie.into_iter().map(|(_, x)| x).sum::<i32>(), 10);
+    }
+
+    #[test]
+    fn test_trie_iter_matching() {
+        let mut trie = Trie::<usize>::new(Grammar::default());
+        assert_eq!(trie.iter_matching(|_| true).count(), 0);
+
+        let counts = [("the", 50), ("then", 3), ("there", 12), ("to", 40), ("tree", 1), ("apple", 7), ("ant", 2)];
+        for (word, count) in counts {
+            assert!(trie.insert(word, count).is_ok());
+        }
+
+        let frequent: Vec<String> = trie.iter_matching(|count| *count > 5).map(|(key, _)| key).collect();
+        assert_eq!(frequent, vec!["apple", "the", "there", "to"]);
+        assert_eq!(trie.iter_matching(|count| *count > 100).count(), 0);
+        assert_eq!(trie.iter_matching(|_| true).collect::<Vec<_>>(), trie.iter().collect::<Vec<_>>());
+
+        // Subtrees holding fewer than 3 keys are skipped without looking at their payloads, so only
+        // "the" (whose subtree holds "then" and "there" too) is reached.
+        let visited = std::sync::Mutex::new(vec![]);
+        let items: Vec<(String, usize)> = trie.iter_matching_with(
+            |count| *count > 5,
+            |key, keys| {
+                visited.lock().unwrap().push(key.to_string());
+                keys >= 3
+            }
+        ).collect();
+        assert_eq!(items, vec![(String::from("the"), 50)]);
+
+        let visited = visited.into_inner().unwrap();
+        assert!(visited.contains(&String::from("a")));
+        assert!(!visited.contains(&String::from("an")));
+        assert!(!visited.contains(&String::from("there")));
+
+        // Pruning on the key works too.
+        let items: Vec<String> = trie.iter_matching_with(|_| true, |key, _| !key.starts_with("th"))
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(items, vec!["ant", "apple", "to", "tree"]);
+    }
+
+    #[test]
+    fn test_trie_shortest_unique_prefix() {
+        let mut trie = Trie::<i32>::new(Grammar::default());
+        assert_eq!(trie.shortest_unique_prefix("commit"), None);
+
+        for (i, key) in ["commit", "config", "clone", "co", "branch"].iter().enumerate() {
+            trie.insert(key, i as i32).unwrap();
+        }
+
+        assert_eq!(trie.shortest_unique_prefix("commit").as_deref(), Some("com"));
+        assert_eq!(trie.shortest_unique_prefix("Config").as_deref(), Some("con"));
+        assert_eq!(trie.shortest_unique_prefix("clone").as_deref(), Some("cl"));
+        assert_eq!(trie.shortest_unique_prefix("branch").as_deref(), Some("b"));
+
+        // A key which is a prefix of other keys can only be identified by itself.
+        assert_eq!(trie.shortest_unique_prefix("co").as_deref(), Some("co"));
+
+        assert_eq!(trie.shortest_unique_prefix("com"), None);
+        assert_eq!(trie.shortest_unique_prefix("commits"), None);
+        assert_eq!(trie.shortest_unique_prefix("cl!"), None);
+
+        // Every unique prefix should count exactly one key, while none of its own prefixes do.
+        for key in trie.keys().collect::<Vec<_>>() {
+            let prefix = trie.shortest_unique_prefix(&key).unwrap();
+            assert!(key.starts_with(&prefix));
+            assert_eq!(trie.iter_prefix(&prefix).next().unwrap().0, key);
+            if prefix != key {
+                assert_eq!(trie.count_prefix(&prefix), 1);
+            }
+            if let Some((i, _)) = prefix.char_indices().last() {
+                assert!(trie.count_prefix(&prefix[..i]) > 1);
+            }
+        }
+
+        assert!(trie.delete("branch").is_ok());
+        assert!(trie.delete("clone").is_ok());
+        assert_eq!(trie.shortest_unique_prefix("commit").as_deref(), Some("com"));
+
+        let mut trie = Trie::<i32>::new(Grammar::default());
+        trie.insert("only", 1).unwrap();
+        assert_eq!(trie.shortest_unique_prefix("only").as_deref(), Some(""));
+    }
+
+    #[test]
+    fn test_trie_from_sorted_iter() {
+        let trie = Trie::<usize>::from_sorted_iter(Grammar::default(), Vec::<(&str, usize)>::new()).unwrap();
+        assert!(trie.is_empty());
+        assert!(trie.to_sorted_vec().is_empty());
+
+        let mut words = vec![];
+        let mut state: u32 = 11;
+        for _ in 0..500 {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            let len = (state >> 16) as usize % 7;
+
+            let mut word = String::new();
+            for _ in 0..len {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                word.push((b'a' + ((state >> 16) % 5) as u8) as char);
+            }
+            words.push(word);
+        }
+        words.sort();
+        words.dedup();
+
+        let mut expected = Trie::<usize>::new(Grammar::default());
+        for (i, word) in words.iter().enumerate() {
+            expected.insert(word, i).unwrap();
+        }
+
+        let trie = Trie::from_sorted_iter(Grammar::default(), words.iter().enumerate().map(|(i, w)| (w, i))).unwrap();
+        assert_eq!(trie.len(), words.len());
+        assert_eq!(trie.to_sorted_vec(), expected.to_sorted_vec());
+        assert_eq!(trie.memory_stats().nodes, expected.memory_stats().nodes);
+        for prefix in ["", "a", "bc", "eee", "x"] {
+            assert_eq!(trie.count_prefix(prefix), expected.count_prefix(prefix));
+        }
+
+        // The trie can be changed like any other, and rebuilt from its own entries.
+        let mut trie = Trie::from_sorted_iter(Grammar::default(), trie.to_sorted_vec()).unwrap();
+        assert_eq!(trie.to_sorted_vec(), expected.to_sorted_vec());
+        assert!(trie.insert(&words[3], 0).is_err());
+        assert_eq!(trie.delete(&words[3]), Ok(Some(3)));
+        assert!(trie.insert("zebra", 0).is_ok());
+        assert_eq!(trie.count_prefix(""), words.len());
+
+        assert_eq!(
+            Trie::from_sorted_iter(Grammar::default(), vec![("ok", 1), ("no!", 2)]).err(),
+            Some(TrieError::CharNotInGrammar { ch: '!' })
+        );
+    }
+
+    #[test]
+    fn test_trie_from_unsorted_iter() {
+        assert_eq!(
+            Trie::from_sorted_iter(Grammar::default(), vec![("b", 1), ("a", 2)]).err(),
+            Some(TrieError::UnsortedInput)
+        );
+        assert_eq!(
+            Trie::from_sorted_iter(Grammar::default(), vec![("a", 1), ("ab", 2), ("ab", 3)]).err(),
+            Some(TrieError::UnsortedInput)
+        );
+
+        // A prefix sorts before the keys it starts.
+        assert_eq!(
+            Trie::from_sorted_iter(Grammar::default(), vec![("ab", 1), ("a", 2)]).err(),
+            Some(TrieError::UnsortedInput)
+        );
+    }
+
+    #[test]
+    fn test_trie_top_k_by_prefix() {
+        let mut trie = Trie::<u32>::with_aggregate(Grammar::default(), Max(|freq: &u32| *freq as f64));
+
+        for (key, freq) in [("car", 40), ("cart", 5), ("care", 25), ("cat", 60), ("dog", 90), ("do", 10)] {
+            trie.insert(key, freq).unwrap();
+        }
+
+        assert_eq!(trie.aggregate_prefix(""), Some(90.0));
+        assert_eq!(trie.aggregate_prefix("car"), Some(40.0));
+        assert_eq!(trie.aggregate_prefix("cow"), None);
+
+        let top = trie.top_k_by_prefix("ca", 3);
+        assert_eq!(top, vec![("cat".to_string(), 60), ("car".to_string(), 40), ("care".to_string(), 25)]);
+        assert_eq!(trie.top_k_by_prefix("", 1), vec![("dog".to_string(), 90)]);
+        assert_eq!(trie.top_k_by_prefix("car", 10).len(), 3);
+        assert!(trie.top_k_by_prefix("x", 3).is_empty());
+        assert!(trie.top_k_by_prefix("ca", 0).is_empty());
+
+        // Aggregates follow every kind of update.
+        trie.delete("cat").unwrap();
+        assert_eq!(trie.aggregate_prefix("ca"), Some(40.0));
+        trie.entry("cart").unwrap().and_modify(|freq| *freq = 70);
+        assert_eq!(trie.top_k_by_prefix("ca", 1), vec![("cart".to_string(), 70)]);
+        trie.insert_or_apply("care", 0, |freq| freq + 100).unwrap();
+        assert_eq!(trie.aggregate_prefix(""), Some(125.0));
+        assert_eq!(trie.delete_prefix("car"), 3);
+        assert_eq!(trie.aggregate_prefix(""), Some(90.0));
+        assert_eq!(trie.aggregate_prefix("c"), None);
+
+        // Compare against sorting every completion, with plenty of ties.
+        let mut trie = Trie::<u32>::new(Grammar::from("abc", Case::Sensitive));
+        let mut state: u32 = 7;
+        for _ in 0..300 {
+            let mut key = String::new();
+            for _ in 0..1 + state % 6 {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                key.push(['a', 'b', 'c'][(state >> 16) as usize % 3]);
+            }
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            trie.insert_or_update(&key, (state >> 16) % 20).unwrap();
+        }
+
+        // The aggregate can be set after the keys were inserted.
+        trie.set_aggregate(Sum(|freq: &u32| *freq as f64));
+        let total: u32 = trie.values().sum();
+        assert_eq!(trie.aggregate_prefix(""), Some(total as f64));
+
+        for prefix in ["", "a", "bc", "cab"] {
+            let mut expected: Vec<(String, u32)> = trie.iter_prefix(prefix).collect();
+            expected.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+            for k in [1, 5, 20, 1000] {
+                let top = trie.top_k_by_prefix(prefix, k);
+                assert_eq!(top, expected[..k.min(expected.len())].to_vec());
+            }
+        }
+    }
+
+    #[test]
+    fn test_expiring_trie() {
+        use std::time::Duration;
+
+        let hour = Duration::from_secs(3600);
+
+        // --
+        // Least recently used keys are evicted once the capacity is exceeded.
+        let mut cache = ExpiringTrie::<usize>::new(Grammar::default(), None, Some(3));
+        assert_eq!(cache.insert("apple", 1), Ok(None));
+        assert_eq!(cache.insert("apricot", 2), Ok(None));
+        assert_eq!(cache.insert("banana", 3), Ok(None));
+        assert_eq!(cache.get("apple"), Some(1));
+        assert_eq!(cache.insert("cherry", 4), Ok(None));
+
+        assert_eq!(cache.len(), 3);
+        assert!(!cache.contains("apricot"));
+        assert_eq!(cache.peek("banana"), Some(3));
+        assert_eq!(cache.insert("date", 5), Ok(None));
+        assert!(!cache.contains("banana"));
+
+        assert_eq!(cache.insert("apple", 10), Ok(Some(1)));
+        assert_eq!(cache.iter_prefix("").collect::<Vec<_>>(), vec![
+            ("apple".to_string(), 10), ("cherry".to_string(), 4), ("date".to_string(), 5)
+        ]);
+        assert_eq!(cache.insert("no!", 0), Err(TrieError::CharNotInGrammar { ch: '!' }));
+        assert_eq!(cache.remove("cherry"), Some(4));
+        assert_eq!(cache.remove("cherry"), None);
+        assert_eq!(cache.len(), 2);
+
+        // --
+        // Keys expire after their TTL, and are purged lazily.
+        let mut cache = ExpiringTrie::<usize>::new(Grammar::default(), Some(hour), None);
+        cache.insert("session", 1).unwrap();
+        cache.insert_with_ttl("sessionshort", 2, Duration::ZERO).unwrap();
+        cache.insert_with_ttl("sessions", 3, Duration::ZERO).unwrap();
+
+        // Inserting "sessions" already purged "sessionshort".
+        assert_eq!(cache.len(), 2);
+        assert!(cache.contains("session"));
+        assert_eq!(cache.peek("sessions"), None);
+        assert_eq!(cache.iter_prefix("sess").count(), 1);
+        assert_eq!(cache.get("sessionshort"), None);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.purge_expired(), 0);
+
+        // Writing a key again restarts its TTL, and expiring keys take their paths with them.
+        cache.insert_with_ttl("session", 4, Duration::ZERO).unwrap();
+        cache.insert("other", 5).unwrap();
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.iter_prefix("s").count(), 0);
+        assert_eq!(cache.delete_prefix("o"), 1);
+        assert!(cache.is_empty());
+
+        // Both kinds of eviction at once.
+        let mut cache = ExpiringTrie::<usize>::new(Grammar::default(), Some(hour), Some(2));
+        cache.insert_with_ttl("a", 1, Duration::ZERO).unwrap();
+        cache.insert("b", 2).unwrap();
+        cache.insert("c", 3).unwrap();
+        cache.insert("d", 4).unwrap();
+        assert_eq!(cache.iter_prefix("").map(|(key, _)| key).collect::<Vec<_>>(), vec!["c", "d"]);
+    }
+
+    #[test]
+    fn test_trie_sample() {
+        let mut rng = XorShift::with_seed(17);
+
+        let mut trie = Trie::<usize>::new(Grammar::default());
+        assert_eq!(trie.sample(&mut rng), None);
+
+        // Nested keys, so that some keys sit above others along the same path.
+        let words = ["a", "ab", "abc", "abd", "b", "bcd", "c", "ca", "cab", "zzzzzz"];
+        for (i, word) in words.iter().enumerate() {
+            trie.insert(word, i).unwrap();
+        }
+
+        let mut counts = [0; 10];
+        for _ in 0..10000 {
+            let (key, i) = trie.sample(&mut rng).unwrap();
+            assert_eq!(key, words[i]);
+            counts[i] += 1;
+        }
+        assert!(counts.iter().all(|count| (850..1150).contains(count)));
+
+        trie.delete_prefix("a");
+        trie.delete_prefix("c");
+        trie.delete("zzzzzz").unwrap();
+        let mut counter = 0;
+        let mut next = || {
+            counter += 1;
+            counter
+        };
+        for _ in 0..10 {
+            assert!(["b", "bcd"].contains(&trie.sample(&mut next).unwrap().0.as_str()));
+        }
+    }
+
+    #[test]
+    fn test_trie_set_operations() {
+        use std::collections::BTreeMap;
+
+        let grammar = Grammar::from("abc", Case::Sensitive);
+        let random_trie = |seed: u32| {
+            let mut trie = Trie::<u32>::new(grammar.clone());
+            let mut state = seed;
+            for _ in 0..200 {
+                let mut key = String::new();
+                for _ in 0..state % 6 {
+                    state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                    key.push(['a', 'b', 'c'][(state >> 16) as usize % 3]);
+                }
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                trie.insert_or_update(&key, (state >> 16) % 100).unwrap();
+            }
+            trie
+        };
+
+        let (a, b) = (random_trie(3), random_trie(8));
+        let (a_map, b_map): (BTreeMap<_, _>, BTreeMap<_, _>) = (a.iter().collect(), b.iter().collect());
+
+        // --
+        // Compare every operation against the same operation on maps.
+        let mut union = a_map.clone();
+        for (key, theirs) in &b_map {
+            *union.entry(key.clone()).or_insert(0) += theirs;
+        }
+        let intersection: Vec<_> = a_map.iter()
+            .filter_map(|(key, ours)| b_map.get(key).map(|theirs| (key.clone(), ours + theirs)))
+            .collect();
+        let difference: Vec<_> = a_map.iter()
+            .filter(|(key, _)| !b_map.contains_key(*key))
+            .map(|(key, ours)| (key.clone(), *ours))
+            .collect();
+        assert!(!intersection.is_empty() && !difference.is_empty());
+
+        let check = |trie: &Trie<u32>, expected: Vec<(String, u32)>| {
+            assert_eq!(trie.len(), expected.len());
+            assert_eq!(trie.count_prefix(""), expected.len());
+            assert_eq!(trie.count_prefix("ab"), expected.iter().filter(|(key, _)| key.starts_with("ab")).count());
+            assert_eq!(trie.to_sorted_vec(), expected);
+        };
+
+        check(&a.union(&b, |ours, theirs| ours + theirs).unwrap(), union.clone().into_iter().collect());
+        check(&a.intersection(&b, |ours, theirs| ours + theirs).unwrap(), intersection);
+        check(&a.difference(&b).unwrap(), difference);
+        assert!(a.difference(&a).unwrap().is_empty());
+        assert_eq!(a.intersection(&a, |ours, _| ours).unwrap().memory_stats().nodes, a.memory_stats().nodes);
+
+        let mut merged = random_trie(3);
+        merged.merge(random_trie(8), |ours, theirs| ours + theirs).unwrap();
+        check(&merged, union.into_iter().collect());
+
+        // The merged trie holds no more nodes than building it from scratch would, and can be
+        // changed like any other.
+        let rebuilt = Trie::from_sorted_iter(grammar.clone(), merged.to_sorted_vec()).unwrap();
+        assert_eq!(merged.memory_stats().nodes, rebuilt.memory_stats().nodes);
+        let below_a = merged.count_prefix("a");
+        assert_eq!(merged.delete_prefix("a"), below_a);
+        assert!(merged.insert("aaaaaaa", 1).is_ok());
+        assert_eq!(merged.len(), merged.count_prefix(""));
+
+        // Aggregates are kept up to date as well.
+        let mut scored = Trie::<u32>::with_aggregate(grammar.clone(), Max(|score: &u32| *score as f64));
+        scored.insert("ab", 5).unwrap();
+        let mut other = Trie::<u32>::new(grammar);
+        other.insert("abc", 7).unwrap();
+        other.insert("ab", 1).unwrap();
+        assert_eq!(scored.union(&other, |ours, _| ours).unwrap().aggregate_prefix("a"), Some(7.0));
+        assert_eq!(scored.intersection(&other, |ours, _| ours).unwrap().aggregate_prefix("a"), Some(5.0));
+        scored.merge(other, |ours, theirs| ours.max(theirs) * 10).unwrap();
+        assert_eq!(scored.aggregate_prefix(""), Some(50.0));
+        assert_eq!(scored.aggregate_prefix("abc"), Some(7.0));
+
+        // Tries with different grammars can't be combined, and are left untouched.
+        let mut other = Trie::<u32>::new(Grammar::from("xyz", Case::Sensitive));
+        other.insert("xy", 1).unwrap();
+        assert_eq!(scored.union(&other, |ours, _| ours).err(), Some(TrieError::GrammarMismatch));
+        assert_eq!(scored.intersection(&other, |ours, _| ours).err(), Some(TrieError::GrammarMismatch));
+        assert_eq!(scored.difference(&other).err(), Some(TrieError::GrammarMismatch));
+        assert_eq!(scored.merge(other, |ours, _| ours), Err(TrieError::GrammarMismatch));
+        assert_eq!(scored.len(), 2);
+        assert_eq!(scored.aggregate_prefix(""), Some(50.0));
+    }
+
+    #[test]
+    fn test_trie_snapshot() {
+        let mut trie = Trie::<Vec<i32>>::new(Grammar::default());
+        trie.insert("hello", vec![1]).unwrap();
+        trie.insert("help", vec![2]).unwrap();
+
+        let snapshot = trie.snapshot();
+
+        trie.entry("hello").unwrap().and_modify(|v| v.push(10));
+        trie.insert("world", vec![3]).unwrap();
+        assert!(trie.delete("help").is_ok());
+        assert_eq!(trie.delete_prefix(""), 2);
+        assert!(trie.is_empty());
+
+        trie.restore(&snapshot);
+        assert_eq!(trie.len(), 2);
+        assert_eq!(trie.iter().collect::<Vec<_>>(), vec![("hello".to_string(), vec![1]), ("help".to_string(), vec![2])]);
+        assert_eq!(trie.count_prefix("hel"), 2);
+
+        // Restoring again undoes the changes made since the last restore as well.
+        trie.insert("he", vec![4]).unwrap();
+        let checkpoint = trie.snapshot();
+        trie.insert("hex", vec![5]).unwrap();
+
+        trie.restore(&snapshot);
+        assert_eq!(trie.keys().collect::<Vec<_>>(), vec!["hello", "help"]);
+        trie.restore(&checkpoint);
+        assert_eq!(trie.keys().collect::<Vec<_>>(), vec!["he", "hello", "help"]);
+        assert_eq!(trie.find("hex"), None);
+
+        // The normalizer is rolled back along with the keys it was used for.
+        let mut trie = Trie::<i32>::with_normalizer(Grammar::default(), |seq: &str| seq.replace('-', ""));
+        trie.insert("e-mail", 0).unwrap();
+
+        let snapshot = trie.snapshot();
+        trie.set_normalizer(|seq: &str| seq.replace('-', "x"));
+        assert_eq!(trie.find("e-mail"), None);
+
+        trie.restore(&snapshot);
+        assert_eq!(trie.find("e-mail"), Some(0));
+    }
+
+    #[test]
+    fn test_trie_char_not_in_grammar() {
+        let err = TrieError::CharNotInGrammar { ch: '!' };
+
+        let mut trie = Trie::<i32>::new(Grammar::default());
+        trie.insert("hello", 1).unwrap();
+        trie.insert("help", 2).unwrap();
+
+        assert_eq!(trie.insert("hi!", 3), Err(err));
+        assert_eq!(trie.insert_or_update("hi!", 3), Err(err));
+        assert_eq!(trie.delete("hello!"), Err(err));
+        assert_eq!(trie.try_find("hello!"), Err(err));
+        assert!(trie.entry("!").is_err());
+        assert_eq!(trie.len(), 2);
+
+        assert_eq!(trie.find("hello!"), None);
+        assert_eq!(trie.try_find("hello"), Ok(Some(1)));
+        assert!(!trie.contains("!"));
+        assert_eq!(trie.count_prefix("he!"), 0);
+        assert_eq!(trie.iter_prefix("!").count(), 0);
+        assert_eq!(trie.longest_prefix("hello!"), Some(("hello".to_string(), 1)));
+        assert_eq!(trie.find_fuzzy("hel!o", 1), vec![("hello".to_string(), 1, 1)]);
+        assert_eq!(trie.delete_prefix("he!"), 0);
+        assert_eq!(trie.len(), 2);
+
+        let mut radix = RadixTrie::<i32>::new(Grammar::default());
+        radix.insert("hello", 1).unwrap();
+        assert_eq!(radix.insert("hi!", 2), Err(err));
+        assert_eq!(radix.delete("hello!"), Err(err));
+        assert_eq!(radix.find("hello!"), None);
+        assert_eq!(radix.iter_prefix("!").count(), 0);
+
+        let mut tst = TernarySearchTree::<i32>::new(Grammar::default());
+        tst.insert("hello", 1).unwrap();
+        assert_eq!(tst.insert("hi!", 2), Err(err));
+        assert_eq!(tst.delete("hello!"), Err(err));
+        assert_eq!(tst.find("hello!"), None);
+        assert!(!tst.contains("!"));
+        assert_eq!(tst.count_prefix("!"), 0);
+        assert_eq!(tst.iter_prefix("!").count(), 0);
+    }
+
+    #[test]
+    fn test_trie_delete_prefix() {
+        let mut trie = Trie::<usize>::new(Grammar::default());
+        let words = ["car", "card", "care", "cared", "cart", "cat", "dog", "do"];
+        for (i, word) in words.iter().enumerate() {
+            assert!(trie.insert(word, i).is_ok());
+        }
+        let nodes = trie.memory_stats().nodes;
+
+        assert_eq!(trie.delete_prefix("cars"), 0);
+        assert_eq!(trie.delete_prefix("x"), 0);
+        assert_eq!(trie.delete_prefix("care"), 2);
+        assert_eq!(trie.len(), 6);
+        assert_eq!(trie.count_prefix("car"), 3);
+        assert!(!trie.contains("care") && !trie.contains("cared"));
+        assert!(trie.contains("card"));
+        assert_eq!(trie.memory_stats().nodes, nodes - 2);
+
+        // Ancestors which only led to the removed keys are freed too.
+        assert!(trie.delete("card").is_ok());
+        assert!(trie.delete("cart").is_ok());
+        assert_eq!(trie.delete_prefix("ca"), 2);
+        assert_eq!(trie.count_prefix("c"), 0);
+        assert_eq!(trie.keys().collect::<Vec<_>>(), vec!["do", "dog"]);
+        assert_eq!(trie.memory_stats().nodes, 4);
+
+        assert!(trie.insert("cab", 8).is_ok());
+        assert_eq!(trie.find("cab"), Some(8));
+
+        assert_eq!(trie.delete_prefix(""), 3);
+        assert!(trie.is_empty());
+        assert_eq!(trie.memory_stats().nodes, 1);
+        assert!(trie.insert("", 9).is_ok());
+        assert_eq!(trie.iter().collect::<Vec<_>>(), vec![(String::new(), 9)]);
+    }
+
+    #[test]
+    fn test_frozen_trie() {
+        let mut trie = Trie::<usize>::new(Grammar::default());
+        let words = ["", "a", "an", "and", "ant", "anthem", "bee", "zebra", "Zed"];
+        for (i, word) in words.iter().enumerate() {
+            assert!(trie.insert(word, i).is_ok());
+        }
+        assert!(trie.delete("a").is_ok());
+
+        let items: Vec<(String, usize)> = trie.iter().collect();
+        let frozen = trie.freeze();
+
+        assert_eq!(frozen.len(), 8);
+        assert_eq!(frozen.find(""), Some(&0));
+        assert_eq!(frozen.find("ANT"), Some(&4));
+        assert_eq!(frozen.find("zed"), Some(&8));
+        assert_eq!(frozen.find("a"), None);
+        assert_eq!(frozen.find("anth"), None);
+        assert_eq!(frozen.find("antics"), None);
+        assert!(!frozen.contains("a-ha"));
+        assert!(frozen.contains("bee"));
+
+        assert_eq!(frozen.count_prefix(""), 8);
+        assert_eq!(frozen.count_prefix("an"), 4);
+        assert_eq!(frozen.count_prefix("z"), 2);
+        assert_eq!(frozen.count_prefix("q"), 0);
+
+        assert_eq!(frozen.iter().map(|(key, x)| (key, *x)).collect::<Vec<_>>(), items);
+        assert_eq!(frozen.iter_prefix("ANT").map(|(key, _)| key).collect::<Vec<_>>(), vec!["ant", "anthem"]);
+        assert_eq!(frozen.iter_prefix("c").count(), 0);
+        assert_eq!(frozen.node_count(), 17);
+
+        let empty = Trie::<usize>::new(Grammar::default()).freeze();
+        assert!(empty.is_empty());
+        assert_eq!(empty.find(""), None);
+        assert_eq!(empty.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_frozen_trie_against_trie() {
+        let mut trie = Trie::<usize>::new(Grammar::from("abcdef", Case::Sensitive));
+
+        let mut state: u64 = 17;
+        let mut next = |n: u64| {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            ((state >> 16) % n) as usize
+        };
+
+        for i in 0..3000 {
+            let key: String = (0..next(8)).map(|_| ['a', 'b', 'c', 'd', 'e', 'f'][next(6)]).collect();
+            assert!(trie.insert_or_update(&key, i).is_ok());
+        }
+
+        let keys: Vec<String> = (0..500)
+            .map(|_| (0..next(8)).map(|_| ['a', 'b', 'c', 'd', 'e', 'f'][next(6)]).collect())
+            .collect();
+        let expected: Vec<_> = keys.iter().map(|key| (trie.find(key), trie.count_prefix(key))).collect();
+        let items: Vec<_> = trie.iter().collect();
+        let stats = trie.memory_stats();
+
+        let frozen = trie.freeze();
+        assert_eq!(frozen.node_count(), stats.nodes);
+        assert!(frozen.approx_bytes() < stats.approx_bytes);
+        assert_eq!(frozen.iter().map(|(key, x)| (key, *x)).collect::<Vec<_>>(), items);
+
+        for (key, (payload, count)) in keys.iter().zip(expected) {
+            assert_eq!(frozen.find(key).cloned(), payload);
+            assert_eq!(frozen.count_prefix(key), count);
+        }
+    }
+
+    #[test]
+    fn test_trie_memory_stats() {
+        let mut trie = Trie::<usize>::new(Grammar::from("abcd", Case::Sensitive));
+        assert_eq!(trie.memory_stats().nodes, 1);
+
+        let keys: Vec<String> = (0..256)
+            .map(|i| (0..4).map(|j| ['a', 'b', 'c', 'd'][(i >> (2 * j)) & 3]).collect())
+            .collect();
+        for (i, key) in keys.iter().enumerate() {
+            assert!(trie.insert(key, i).is_ok());
+        }
+
+        let full = trie.memory_stats();
+        assert_eq!(full.nodes, 1 + 4 + 16 + 64 + 256);
+
+        for key in &keys[1..] {
+            assert!(trie.delete(key).is_ok());
+        }
+        assert_eq!(trie.memory_stats().nodes, 5);
+        assert_eq!(trie.memory_stats().high_water_mark, full.nodes);
+
+        trie.shrink_to_fit();
+        assert!(trie.memory_stats().approx_bytes < full.approx_bytes);
+        assert_eq!(trie.find(&keys[0]), Some(0));
+
+        assert!(trie.insert("dddd", 7).is_ok());
+        assert_eq!(trie.iter().count(), 2);
+    }
+
+    #[test]
+    fn test_trie_compact() {
+        let mut trie = Trie::<usize>::new(Grammar::from("abcd", Case::Sensitive));
+
+        let keys: Vec<String> = (0..256)
+            .map(|i| (0..4).map(|j| ['a', 'b', 'c', 'd'][(i >> (2 * j)) & 3]).collect())
+            .collect();
+        for (i, key) in keys.iter().enumerate() {
+            assert!(trie.insert(key, i).is_ok());
+        }
+        let snapshot = trie.snapshot();
+
+        // Keep the keys which were inserted last, whose nodes are spread all over the arena.
+        for key in &keys[..240] {
+            assert!(trie.delete(key).is_ok());
+        }
+        trie.shrink_to_fit();
+        let before = trie.memory_stats();
+
+        trie.compact();
+        assert!(trie.memory_stats().approx_bytes < before.approx_bytes);
+        assert_eq!(trie.memory_stats().nodes, before.nodes);
+        assert!(keys[240..].iter().enumerate().all(|(i, key)| trie.find(key) == Some(240 + i)));
+        assert_eq!(trie.iter().count(), 16);
+
+        assert!(trie.insert("abba", 1000).is_ok());
+        assert!(trie.delete(&keys[255]).is_ok());
+        assert_eq!(trie.find("abba"), Some(1000));
+        assert_eq!(trie.len(), 16);
+
+        trie.restore(&snapshot);
+        assert_eq!(trie.len(), 256);
+        assert_eq!(trie.find(&keys[3]), Some(3));
+    }
+
+    #[test]
+    fn test_trie_debug_structure() {
+        let mut trie = Trie::<usize>::new(Grammar::default());
+
+        let empty = trie.debug_structure();
+        assert_eq!(empty.nodes_per_depth, vec![1]);
+        assert_eq!(empty.chained_nodes(), 0);
+
+        for (i, key) in ["car", "cart", "cat", "dog", ""].iter().enumerate() {
+            assert!(trie.insert(key, i).is_ok());
+        }
+
+        // "c" and "do" are the only runs of nodes without a payload which have a single child.
+        let structure = trie.debug_structure();
+        assert_eq!(structure.nodes_per_depth, vec![1, 2, 2, 3, 1]);
+        assert_eq!(structure.chain_lengths, vec![0, 1, 1]);
+        assert_eq!(structure.chained_nodes(), 3);
+        assert_eq!(structure.node_count(), 9);
+        assert_eq!(structure.memory, trie.memory_stats());
+
+        let mut out = vec![];
+        assert!(trie.dump(&mut out).is_ok());
+        assert_eq!(String::from_utf8(out).unwrap(), [
+            "(root) = 4",
+            "  c",
+            "    a",
+            "      r = 0",
+            "        t = 1",
+            "      t = 2",
+            "  d",
+            "    o",
+            "      g = 3",
+            ""
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_trie_find_fuzzy() {
+        let mut trie = Trie::<i32>::new(Grammar::default());
+        assert!(trie.find_fuzzy("hello", 2).is_empty());
+
+        assert!(trie.insert("hello", 1).is_ok());
+        assert!(trie.insert("help", 2).is_ok());
+        assert!(trie.insert("yellow", 3).is_ok());
+        assert!(trie.insert("world", 4).is_ok());
+
+        assert_eq!(trie.find_fuzzy("hello", 0), vec![(String::from("hello"), 1, 0)]);
+        assert_eq!(trie.find_fuzzy("helo", 1), vec![(String::from("hello"), 1, 1), (String::from("help"), 2, 1)]);
+
+        let result = trie.find_fuzzy("hellp", 3);
+        assert_eq!(result, vec![
+            (String::from("hello"), 1, 1),
+            (String::from("help"), 2, 1),
+            (String::from("yellow"), 3, 3),
+        ]);
+
+        assert_eq!(trie.find_fuzzy("", 4), vec![(String::from("help"), 2, 4)]);
+        assert!(trie.find_fuzzy("xyz", 2).is_empty());
+    }
+
+    #[test]
+    fn test_trie_suggest() {
+        let keyboard = Keyboard::qwerty();
+        assert!(keyboard.is_adjacent('s', 'd'));
+        assert!(keyboard.is_adjacent('S', 'w'));
+        assert!(!keyboard.is_adjacent('s', 'g'));
+        assert!(!keyboard.is_adjacent('s', 's'));
+        assert_eq!(keyboard.substitution_cost('a', 'a'), 0.0);
+        assert_eq!(Keyboard::none().substitution_cost('s', 'd'), 1.0);
+
+        // The payloads are how often the words are used.
+        let mut trie = Trie::<u32>::with_aggregate(Grammar::default(), Sum(|n: &u32| *n as f64));
+        assert!(trie.suggest("the", 5).is_empty());
+
+        for (word, n) in [("the", 5000), ("then", 800), ("they", 900), ("tho", 20), ("cat", 300), ("car", 400), ("cart", 50)] {
+            trie.insert(word, n).unwrap();
+        }
+
+        let words = |suggestions: Vec<(String, u32)>| suggestions.into_iter().map(|(key, _)| key).collect::<Vec<_>>();
+
+        // Swapping 2 chars is a single edit, and a correct word comes first.
+        assert_eq!(words(trie.suggest("teh", 1)), vec!["the"]);
+        assert_eq!(words(trie.suggest("the", 2)), vec!["the", "they"]);
+
+        // "cat" and "car" are both 1 substitution away from "caf", but 'r' and 'f' are neighbours.
+        assert_eq!(words(trie.suggest("caf", 2)), vec!["car", "cat"]);
+
+        // Without a keyboard the more frequent word wins, and without frequencies the smaller one.
+        let config = SuggestConfig { keyboard: Keyboard::none(), ..Default::default() };
+        assert_eq!(words(trie.suggest_with("thez", 3, &config)), vec!["the", "they", "then"]);
+        let config = SuggestConfig { keyboard: Keyboard::none(), frequency_weight: 0.0, ..Default::default() };
+        assert_eq!(words(trie.suggest_with("thez", 3, &config)), vec!["the", "then", "they"]);
+
+        // Frequency can outweigh a cheaper edit, but never a word beyond the max distance.
+        assert_eq!(words(trie.suggest("thw", 2)), vec!["the", "tho"]);
+        let config = SuggestConfig { max_distance: Some(0.5), ..Default::default() };
+        assert_eq!(words(trie.suggest_with("thw", 5, &config)), vec!["the"]);
+        assert!(trie.suggest("zzzz", 5).is_empty());
+    }
+
+    #[test]
+    fn test_trie_non_clone_payload() {
+        let mut trie = Trie::<Box<dyn Fn() -> i32 + Send + Sync>>::new(Grammar::default());
+
+        assert!(trie.insert("answer", Box::new(|| 42)).is_ok());
+        assert!(trie.insert_or_apply("answer", Box::new(|| 0), |_| Box::new(|| 43)).is_ok());
+        assert!(trie.contains("answer"));
+        assert!(!trie.contains("answe"));
+
+        let f = trie.delete("answer").unwrap().unwrap();
+        assert_eq!(f(), 43);
+        assert!(trie.is_empty());
+    }
+
+    #[test]
+    fn test_suffix_tree() {
+        let tree = SuffixTree::new(Grammar::default(), "Banana").unwrap();
+        assert_eq!(tree.len(), 6);
+
+        assert!(tree.contains_substring("nan"));
+        assert!(tree.contains_substring("BAN"));
+        assert!(tree.contains_substring(""));
+        assert!(!tree.contains_substring("nab"));
+        assert!(!tree.contains_substring("bananas"));
+        assert!(!tree.contains_substring("an-"));
+
+        assert_eq!(tree.count_occurrences("ana"), 2);
+        assert_eq!(tree.count_occurrences("a"), 3);
+        assert_eq!(tree.count_occurrences("x"), 0);
+        assert_eq!(tree.find_all_occurrences("ana"), vec![1, 3]);
+        assert_eq!(tree.find_all_occurrences("banana"), vec![0]);
+        assert_eq!(tree.find_all_occurrences(""), (0..=6).collect::<Vec<_>>());
+
+        assert_eq!(SuffixTree::new(Grammar::default(), "ab1").err(), Some(TrieError::CharNotInGrammar { ch: '1' }));
+        assert!(SuffixTree::new(Grammar::default(), "").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_suffix_tree_against_brute_force() {
+        let grammar = Grammar::from("abc", Case::Sensitive);
+
+        let mut state = 7u32;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) as usize
+        };
+
+        let text: Vec<char> = (0..300).map(|_| ['a', 'b', 'c'][next() % 3]).collect();
+        let tree = SuffixTree::new(grammar, &text.iter().collect::<String>()).unwrap();
+
+        for _ in 0..200 {
+            let len = 1 + next() % 6;
+            let pattern: Vec<char> = (0..len).map(|_| ['a', 'b', 'c'][next() % 3]).collect();
+
+            let expected: Vec<usize> = (0..text.len())
+                .filter(|i| text[*i..].starts_with(&pattern))
+                .collect();
+
+            let pattern: String = pattern.into_iter().collect();
+            assert_eq!(tree.find_all_occurrences(&pattern), expected);
+            assert_eq!(tree.count_occurrences(&pattern), expected.len());
+        }
+    }
+
+    #[test]
+    fn test_aho_corasick() {
+        let mut trie = Trie::<i32>::new(Grammar::default());
+        assert!(trie.insert("he", 1).is_ok());
+        assert!(trie.insert("she", 2).is_ok());
+        assert!(trie.insert("his", 3).is_ok());
+        assert!(trie.insert("hers", 4).is_ok());
+        assert!(trie.insert("", 5).is_ok());
+
+        let matcher = trie.into_matcher();
+        let matches: Vec<Match<i32>> = matcher.find_iter("uShers, his!").collect();
+
+        assert_eq!(matches, vec![
+            Match { start: 1, end: 4, payload: 2 },
+            Match { start: 2, end: 4, payload: 1 },
+            Match { start: 2, end: 6, payload: 4 },
+            Match { start: 8, end: 11, payload: 3 },
+        ]);
+
+        assert_eq!(matcher.find_iter("").count(), 0);
+        assert_eq!(matcher.find_iter("h-e").count(), 0);
+    }
+
+    #[test]
+    fn test_aho_corasick_against_brute_force() {
+        let keys = ["a", "ab", "bab", "bc", "bca", "c", "caa"];
+
+        let mut trie = Trie::<usize>::new(Grammar::from("abc", Case::Sensitive));
+        for (i, key) in keys.iter().enumerate() {
+            assert!(trie.insert(key, i).is_ok());
+        }
+        let matcher = trie.into_matcher();
+
+        let haystack = "abccabbabcaacbcab";
+        let mut expected = vec![];
+        for end in 1..=haystack.len() {
+            for start in 0..end {
+                if let Some(i) = keys.iter().position(|key| *key == &haystack[start..end]) {
+                    expected.push(Match { start, end, payload: i });
+                }
+            }
+        }
+
+        assert_eq!(matcher.find_iter(haystack).collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn test_concurrent_trie() {
+        let trie = ConcurrentTrie::<usize>::new(Grammar::from("abcd", Case::Sensitive));
+        let letters = ['a', 'b', 'c', 'd'];
+
+        // Every thread inserts every 3 letter key starting with its own letter.
+        std::thread::scope(|scope| {
+            for first in letters {
+                let trie = &trie;
+                scope.spawn(move || {
+                    for (i, second) in letters.iter().enumerate() {
+                        for (j, third) in letters.iter().enumerate() {
+                            let key: String = [first, *second, *third].iter().collect();
+                            assert!(trie.insert(&key, 4 * i + j).is_ok());
+                        }
+                    }
+                    assert!(trie.delete(&format!("{}dd", first)).is_ok());
+                });
+            }
+        });
+
+        assert_eq!(trie.len(), 60);
+        assert_eq!(trie.count_prefix("b"), 15);
+        assert_eq!(trie.find("cab"), Some(1));
+        assert!(!trie.contains("add"));
+
+        let trie = trie.into_inner();
+        assert_eq!(trie.iter().count(), 60);
+    }
+
+    #[test]
+    fn test_trie_read_snapshot() {
+        let mut trie = Trie::<usize>::with_normalizer(Grammar::default(), CaseFold);
+        trie.insert("car", 1).unwrap();
+        trie.insert("cart", 2).unwrap();
+
+        let snapshot = trie.snapshot();
+        trie.insert("cat", 3).unwrap();
+        trie.insert_or_update("car", 10).unwrap();
+        assert!(trie.delete("cart").is_ok());
+
+        // The snapshot sees none of the changes made since, and normalizes keys like the trie.
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot.find("CAR"), Some(1));
+        assert!(snapshot.contains("cart"));
+        assert!(!snapshot.contains("cat"));
+        assert!(!snapshot.contains("ca"));
+        assert!(!snapshot.contains("ca!"));
+        assert_eq!(snapshot.count_prefix("car"), 2);
+        assert_eq!(snapshot.iter_prefix("Car").collect::<Vec<_>>(), vec![("car".to_string(), 1), ("cart".to_string(), 2)]);
+        assert_eq!(trie.iter().collect::<Vec<_>>(), vec![("car".to_string(), 10), ("cat".to_string(), 3)]);
+
+        // Readers can take snapshots through a shared reference too.
+        let reader = trie.read_snapshot();
+        trie.insert("ca", 4).unwrap();
+        assert_eq!(reader.len(), 2);
+        assert!(!reader.contains("ca"));
+        assert_eq!(reader.find("car"), Some(10));
+
+        // A snapshot stays the same while the writer keeps going.
+        let trie = ConcurrentTrie::<usize>::new(Grammar::from("abcd", Case::Sensitive));
+        let letters = ['a', 'b', 'c', 'd'];
+        std::thread::scope(|scope| {
+            let trie = &trie;
+            scope.spawn(move || {
+                for (i, first) in letters.iter().enumerate() {
+                    for second in letters {
+                        for third in letters {
+                            let key: String = [*first, second, third].iter().collect();
+                            trie.insert(&key, i).unwrap();
+                            trie.insert_or_update(&first.to_string(), key.len()).unwrap();
+                        }
+                    }
+                }
+            });
+
+            for _ in 0..4 {
+                scope.spawn(move || {
+                    let mut len = 0;
+                    for _ in 0..20 {
+                        // Snapshots are published in the order of writes, which only ever add keys.
+                        let snapshot = trie.read_snapshot();
+                        assert!(snapshot.len() >= len);
+                        len = snapshot.len();
+
+                        let entries: Vec<(String, usize)> = snapshot.iter().collect();
+                        assert_eq!(entries.len(), snapshot.len());
+                        assert_eq!(snapshot.count_prefix(""), snapshot.len());
+
+                        std::thread::yield_now();
+                        assert_eq!(snapshot.iter().collect::<Vec<_>>(), entries);
+                    }
+                });
+            }
+        });
+        assert_eq!(trie.len(), 68);
+
+        // Without writes in between, readers share the latest snapshot.
+        let snapshot = trie.read_snapshot();
+        assert_eq!(snapshot.len(), 68);
+        assert!(std::sync::Arc::ptr_eq(&snapshot, &trie.read_snapshot()));
+        trie.delete("abc").unwrap();
+        assert_eq!(trie.read_snapshot().len(), 67);
+        assert_eq!(snapshot.len(), 68);
+    }
+
+    #[test]
+    fn test_trie_lcrs() {
+        let mut trie = Trie::<usize>::new(Grammar::default());
+        trie.insert("", 0).unwrap();
+        trie.insert("car", 1).unwrap();
+        trie.insert("cart", 2).unwrap();
+        trie.insert("cat", 3).unwrap();
+        trie.insert("dog", 4).unwrap();
+
+        let lcrs = trie.to_lcrs();
+        assert!(lcrs.is_valid());
+        assert_eq!(lcrs.len(), 9);
+        assert_eq!(lcrs.nodes[0].value, (None, Some(0)));
+
+        let labels: Vec<char> = lcrs.nodes[1..].iter().map(|node| node.value.0.unwrap()).collect();
+        assert_eq!(labels, vec!['c', 'a', 'r', 't', 't', 'd', 'o', 'g']);
+        assert_eq!(lcrs.children(2).map(|i| lcrs.nodes[i].value).collect::<Vec<_>>(), vec![
+            (Some('r'), Some(1)),
+            (Some('t'), Some(3))
+        ]);
+
+        let copy = Trie::from_lcrs(Grammar::default(), lcrs.clone()).unwrap();
+        assert_eq!(copy.len(), 5);
+        assert_eq!(copy.to_sorted_vec(), trie.to_sorted_vec());
+        assert_eq!(copy.count_prefix("ca"), 3);
+        assert_eq!(copy.to_lcrs(), lcrs);
+
+        // Siblings out of order are fine, but siblings sharing a char aren't.
+        let mut swapped = lcrs.clone();
+        swapped.nodes[2].first_child = Some(5);
+        swapped.nodes[5].next_sibling = Some(3);
+        swapped.nodes[3].next_sibling = None;
+        assert_eq!(Trie::from_lcrs(Grammar::default(), swapped.clone()).unwrap().to_lcrs(), lcrs);
+
+        swapped.nodes[5].value.0 = Some('r');
+        swapped.nodes[5].value.1 = Some(3);
+        swapped.nodes[5].first_child = Some(4);
+        swapped.nodes[3].first_child = None;
+        assert_eq!(Trie::from_lcrs(Grammar::default(), swapped).err(), Some(TrieError::InvalidLcrs));
+
+        let mut invalid = lcrs.clone();
+        invalid.nodes[6].value.0 = Some('!');
+        assert_eq!(Trie::from_lcrs(Grammar::default(), invalid).err(), Some(TrieError::CharNotInGrammar { ch: '!' }));
+        let mut invalid = lcrs.clone();
+        invalid.nodes[6].value.0 = None;
+        assert_eq!(Trie::from_lcrs(Grammar::default(), invalid).err(), Some(TrieError::InvalidLcrs));
+        let mut invalid = lcrs.clone();
+        invalid.nodes[0].value.0 = Some('a');
+        assert_eq!(Trie::from_lcrs(Grammar::default(), invalid).err(), Some(TrieError::InvalidLcrs));
+
+        assert!(Trie::<usize>::from_lcrs(Grammar::default(), LcrsTree::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_trie_with_shards() {
+        let mut trie = Trie::<usize>::with_shards(Grammar::from("abcd", Case::Sensitive), 4);
+        let words = ["a", "ab", "abc", "abcd", "b", "bad", "cab", "dab"];
+        for (i, word) in words.iter().enumerate() {
+            assert!(trie.insert(word, i).is_ok());
+        }
+        assert!(trie.delete("abc").is_ok());
+
+        // Readers on every thread look up nodes spread across the shards.
+        let trie = &trie;
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(move || {
+                    assert_eq!(trie.find("abcd"), Some(3));
+                    assert_eq!(trie.find("dab"), Some(7));
+                    assert!(!trie.contains("abc"));
+                    assert_eq!(trie.count_prefix("ab"), 2);
+                });
+            }
+        });
+        assert_eq!(trie.len(), 7);
+    }
+
+    #[test]
+    fn test_seq_trie() {
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        enum Segment {
+            Literal(&'static str),
+            Param
+        }
+        use Segment::*;
+
+        let mut trie = SeqTrie::<Segment, &str>::new();
+        assert!(trie.is_empty());
+
+        assert!(trie.insert(&[Literal("users")], "list_users").is_ok());
+        assert!(trie.insert(&[Literal("users"), Param], "get_user").is_ok());
+        assert!(trie.insert(&[Literal("users"), Param, Literal("posts")], "list_posts").is_ok());
+        assert!(trie.insert(&[], "index").is_ok());
+        assert_eq!(trie.insert(&[Literal("users")], "other"), Err(TrieError::KeyExists));
+        assert_eq!(trie.len(), 4);
+
+        assert_eq!(trie.find(&[Literal("users"), Param]), Some("get_user"));
+        assert!(trie.find(&[Literal("posts")]).is_none());
+        assert!(!trie.contains(&[Literal("users"), Literal("posts")]));
+        assert_eq!(trie.count_prefix(&[Literal("users")]), 3);
+
+        let route = [Literal("users"), Param, Literal("comments")];
+        assert_eq!(trie.longest_prefix(&route), Some((vec![Literal("users"), Param], "get_user")));
+
+        let mut matches: Vec<&str> = trie.iter_prefix(&[Literal("users"), Param]).map(|(_, v)| v).collect();
+        matches.sort();
+        assert_eq!(matches, vec!["get_user", "list_posts"]);
+
+        assert_eq!(trie.insert_or_update(&[Literal("users")], "all_users"), Ok(Some("list_users")));
+        assert_eq!(trie.delete(&[Literal("users"), Param, Literal("posts")]), Ok(Some("list_posts")));
+        assert_eq!(trie.delete(&[Literal("users"), Param, Literal("posts")]), Err(TrieError::KeyNotFound));
+        assert_eq!(trie.count_prefix(&[Literal("users")]), 2);
+        assert_eq!(trie.count_prefix(&[]), 3);
+        assert_eq!(trie.len(), 3);
+    }
+
+    #[test]
+    fn test_trie_find_pattern() {
+        let mut trie = Trie::<usize>::new(Grammar::default());
+        for (i, word) in ["cat", "cot", "cart", "coat", "dog", "dot", "do", "a"].iter().enumerate() {
+            assert!(trie.insert(word, i).is_ok());
+        }
+
+        let keys = |pattern: &str| -> Vec<String> {
+            trie.find_pattern(pattern).into_iter().map(|(key, _)| key).collect()
+        };
+
+        assert_eq!(keys("c?t"), vec!["cat", "cot"]);
+        assert_eq!(keys("do?"), vec!["dog", "dot"]);
+        assert_eq!(keys("do"), vec!["do"]);
+        assert_eq!(keys("co*"), vec!["coat", "cot"]);
+        assert_eq!(keys("c*t"), vec!["cart", "cat", "coat", "cot"]);
+        assert_eq!(keys("*t"), vec!["cart", "cat", "coat", "cot", "dot"]);
+        assert_eq!(keys("*a*"), vec!["a", "cart", "cat", "coat"]);
+        assert_eq!(keys("**o**"), vec!["coat", "cot", "do", "dog", "dot"]);
+        assert_eq!(keys("?"), vec!["a"]);
+        assert_eq!(keys("*").len(), trie.len());
+        assert!(keys("????t").is_empty());
+        assert!(keys("c1t").is_empty());
+
+        assert_eq!(trie.find_pattern("d?g"), vec![("dog".to_string(), 4)]);
+    }
+
+    #[test]
+    fn test_persistent_trie() {
+        let empty = PersistentTrie::<i32>::new(Grammar::default());
+        assert!(empty.is_empty());
+
+        let v1 = empty.insert("car", 1).unwrap();
+        let v2 = v1.insert("cart", 2).unwrap();
+        let v3 = v2.insert_or_update("car", 3).unwrap();
+        let v4 = v3.delete("car").unwrap();
+
+        // Every version keeps the keys it was created with.
+        assert!(empty.find("car").is_none());
+        assert_eq!(v1.find("car"), Some(&1));
+        assert!(v1.find("cart").is_none());
+        assert_eq!(v2.find("car"), Some(&1));
+        assert_eq!(v2.find("cart"), Some(&2));
+        assert_eq!(v3.find("car"), Some(&3));
+        assert!(!v4.contains("car"));
+        assert!(v4.contains("cart"));
+
+        assert_eq!([empty.len(), v1.len(), v2.len(), v3.len(), v4.len()], [0, 1, 2, 2, 1]);
+        assert_eq!(v3.count_prefix("ca"), 2);
+        assert_eq!(v4.count_prefix("car"), 1);
+
+        assert_eq!(v2.insert("cart", 5).err(), Some(TrieError::KeyExists));
+        assert_eq!(v4.delete("car").err(), Some(TrieError::KeyNotFound));
+        assert_eq!(v4.delete("ca").err(), Some(TrieError::KeyNotFound));
+        assert_eq!(v4.insert("c4r", 5).err(), Some(TrieError::CharNotInGrammar { ch: '4' }));
+        assert!(v4.find("c4r").is_none());
+
+        assert_eq!(v3.iter().collect::<Vec<_>>(), vec![("car".to_string(), &3), ("cart".to_string(), &2)]);
+        assert_eq!(v3.iter_prefix("cart").count(), 1);
+
+        // Deleting the last key leaves an empty trie behind.
+        let v5 = v4.delete("cart").unwrap();
+        assert!(v5.is_empty());
+        assert_eq!(v5.iter().count(), 0);
+        assert!(v5.insert("a", 1).unwrap().contains("a"));
+
+        let snapshot = v3.clone();
+        assert!(snapshot.ptr_eq(&v3));
+        assert!(!snapshot.ptr_eq(&v2));
+    }
+
+    #[test]
+    fn test_persistent_trie_against_btreemap() {
+        use std::collections::BTreeMap;
+
+        let mut trie = PersistentTrie::<u32>::new(Grammar::from("abc", Case::Sensitive));
+        let mut map = BTreeMap::new();
+        let mut versions = vec![];
+
+        let mut state: u32 = 13;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            state >> 16
+        };
+
+        for i in 0..500 {
+            let key: String = (0..next() % 5).map(|_| ['a', 'b', 'c'][next() as usize % 3]).collect();
+
+            if next() % 3 == 0 {
+                match map.remove(&key) {
+                    None => assert!(trie.delete(&key).is_err()),
+                    Some(_) => trie = trie.delete(&key).unwrap()
+                }
+            } else {
+                trie = trie.insert_or_update(&key, i).unwrap();
+                map.insert(key, i);
+            }
+
+            versions.push((trie.clone(), map.clone()));
+        }
+
+        // Later modifications must not have leaked into earlier versions.
+        for (trie, map) in &versions {
+            assert_eq!(trie.len(), map.len());
+            assert!(trie.iter().map(|(k, v)| (k, *v)).eq(map.iter().map(|(k, v)| (k.clone(), *v))));
+        }
+    }
+
+    #[test]
+    fn test_xfast_trie() {
+        let mut trie = XFastTrie::new();
+        assert!(trie.is_empty());
+        assert_eq!(trie.successor(5), None);
+        assert_eq!(trie.predecessor(5), None);
+        assert_eq!(trie.min(), None);
+
+        for key in [10, 3, u64::MAX, 0, 42] {
+            assert!(trie.insert(key));
+        }
+        assert!(!trie.insert(42));
+        assert_eq!(trie.len(), 5);
+
+        assert_eq!(trie.iter().collect::<Vec<_>>(), vec![0, 3, 10, 42, u64::MAX]);
+        assert_eq!((trie.min(), trie.max()), (Some(0), Some(u64::MAX)));
+        assert_eq!(trie.successor(3), Some(10));
+        assert_eq!(trie.successor(11), Some(42));
+        assert_eq!(trie.successor(43), Some(u64::MAX));
+        assert_eq!(trie.successor(u64::MAX), None);
+        assert_eq!(trie.predecessor(3), Some(0));
+        assert_eq!(trie.predecessor(9), Some(3));
+        assert_eq!(trie.predecessor(0), None);
+
+        assert!(trie.delete(3));
+        assert!(!trie.delete(3));
+        assert_eq!(trie.successor(0), Some(10));
+        assert_eq!(trie.predecessor(10), Some(0));
+
+        for key in [0, 10, 42, u64::MAX] {
+            assert!(trie.delete(key));
+        }
+        assert!(trie.is_empty());
+        assert_eq!(trie.min(), None);
+    }
+
+    #[test]
+    fn test_yfast_trie_against_btreeset() {
+        use std::collections::BTreeSet;
+
+        let mut xfast = XFastTrie::new();
+        let mut yfast = YFastTrie::new();
+        let mut expected = BTreeSet::new();
+
+        // Keys are drawn from a few clusters, which fills buckets up and empties them again.
+        let mut state: u64 = 17;
+        let mut next = || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let cluster = [0, 1 << 20, u64::MAX - 5000, 1 << 63][(state >> 62) as usize];
+            cluster.wrapping_add((state >> 20) % 4000)
+        };
+
+        for i in 0..20000 {
+            let key = next();
+            if i % 5 < 2 {
+                let removed = expected.remove(&key);
+                assert_eq!(yfast.delete(key), removed);
+                assert_eq!(xfast.delete(key), removed);
+            } else {
+                let inserted = expected.insert(key);
+                assert_eq!(yfast.insert(key), inserted);
+                assert_eq!(xfast.insert(key), inserted);
+            }
+            assert_eq!(yfast.len(), expected.len());
+
+            let probe = next();
+            let successor = expected.range(probe.saturating_add(1)..).next().copied().filter(|_| probe < u64::MAX);
+            let predecessor = expected.range(..probe).next_back().copied();
+            assert_eq!(yfast.successor(probe), successor);
+            assert_eq!(yfast.predecessor(probe), predecessor);
+            assert_eq!(yfast.contains(probe), expected.contains(&probe));
+        }
+
+        assert_eq!(yfast.iter().collect::<Vec<_>>(), expected.iter().copied().collect::<Vec<_>>());
+        assert_eq!(xfast.iter().collect::<Vec<_>>(), expected.iter().copied().collect::<Vec<_>>());
+        assert_eq!((yfast.min(), yfast.max()), (expected.first().copied(), expected.last().copied()));
+
+        for key in expected.iter() {
+            assert_eq!(xfast.successor(*key), expected.range(key + 1..).next().copied());
+            assert!(yfast.delete(*key));
+        }
+        assert!(yfast.is_empty());
+        assert_eq!(yfast.min(), None);
+    }
+
+    #[test]
+    fn test_trie_set() {
+        let mut set = TrieSet::new(Grammar::default());
+        assert!(set.is_empty());
+
+        assert_eq!(set.insert("car"), Ok(true));
+        assert_eq!(set.insert("cart"), Ok(true));
+        assert_eq!(set.insert("cat"), Ok(true));
+        assert_eq!(set.insert("dog"), Ok(true));
+        assert_eq!(set.insert("car"), Ok(false));
+        assert_eq!(set.insert("car!"), Err(TrieError::CharNotInGrammar { ch: '!' }));
+        assert_eq!(set.len(), 4);
+
+        assert!(set.contains("cart"));
+        assert!(!set.contains("ca"));
+        assert_eq!(set.count_prefix("ca"), 3);
+        assert_eq!(set.iter_prefix("car").collect::<Vec<_>>(), vec!["car", "cart"]);
+        assert_eq!(set.longest_prefix("cartwheel"), Some("cart".to_string()));
+
+        assert!(set.remove("car"));
+        assert!(!set.remove("car"));
+        assert!(!set.remove("car!"));
+        assert!(set.contains("cart"));
+
+        assert_eq!(set.remove_prefix("ca"), 2);
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec!["dog"]);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_trie_par_find_many() {
+        let mut trie = Trie::<usize>::new(Grammar::default());
+        let words: Vec<String> = (0..500)
+            .map(|i: usize| [i % 26, i / 26].iter().map(|c| (b'a' + *c as u8) as char).collect())
+            .collect();
+        for (i, word) in words.iter().enumerate().filter(|(i, _)| i % 2 == 0) {
+            trie.insert(word, i).unwrap();
+        }
+
+        // Every odd word is missing, and so is anything outside of the grammar.
+        let mut seqs: Vec<&str> = words.iter().map(|word| word.as_str()).collect();
+        seqs.push("word!");
+
+        let found = trie.par_find_many(&seqs);
+        assert_eq!(found.len(), seqs.len());
+        for (i, payload) in found.iter().enumerate() {
+            assert_eq!(*payload, (i % 2 == 0 && i < words.len()).then_some(i));
+        }
+    }
+
+    #[test]
+    fn test_trie_extend_grammar() {
+        let mut trie = Trie::<usize>::new(Grammar::from("bdf", Case::Sensitive));
+        trie.insert("bd", 1).unwrap();
+        trie.insert("fb", 2).unwrap();
+        assert_eq!(trie.insert("ab", 3), Err(TrieError::CharNotInGrammar { ch: 'a' }));
+
+        let snapshot = trie.snapshot();
+
+        // New chars are slotted in between the existing ones, keeping grammar order.
+        trie.extend_grammar("eabd");
+        assert_eq!(trie.insert("ab", 3), Ok(()));
+        trie.insert("be", 4).unwrap();
+        trie.insert("bda", 5).unwrap();
+
+        assert_eq!(trie.find("bd"), Some(1));
+        assert_eq!(trie.find("fb"), Some(2));
+        assert_eq!(trie.keys().collect::<Vec<_>>(), vec!["ab", "bd", "bda", "be", "fb"]);
+        assert_eq!(trie.count_prefix("b"), 3);
+
+        // Rolling back also rolls back the grammar.
+        trie.restore(&snapshot);
+        assert_eq!(trie.to_sorted_vec(), vec![("bd".to_string(), 1), ("fb".to_string(), 2)]);
+        assert_eq!(trie.insert("ab", 3), Err(TrieError::CharNotInGrammar { ch: 'a' }));
+    }
+
+    #[test]
+    fn test_trie_children_layouts() {
+        use crate::arena::GenerationalId;
+
+        let id = |index| GenerationalId { index, generation: 0 };
+
+        // --
+        // Nodes move from a sorted Vec to slots in small grammars, or to a map in large ones, and
+        // back once they lose most of their children.
+        for (arity, stride) in [(26, 1), (100_000, 997)] {
+            let mut children = Children::default();
+            for i in (0..20).rev() {
+                children.insert(i * stride, id(i), arity);
+            }
+
+            match (&children, arity) {
+                (Children::Dense(_), 26) | (Children::Map(_), 100_000) => {}
+                (layout, _) => panic!("unexpected layout {:?}", layout)
+            }
+            assert_eq!(children.len(), 20);
+            assert_eq!(children.get(3 * stride), Some(id(3)));
+            assert_eq!(children.get(1), (stride == 1).then(|| id(1)));
+            assert!(children.iter().map(|(idx, _)| idx).eq((0..20).map(|i| i * stride)));
+
+            for i in 0..12 {
+                assert_eq!(children.remove(i * stride), Some(id(i)));
+            }
+            assert!(matches!(children, Children::Sparse(_)));
+            assert!(children.ids().eq((12..20).map(id)));
+            assert_eq!(children.remove(0), None);
+        }
+
+        // --
+        // A trie over a large grammar only pays for the children its nodes actually have.
+        let grammar = Grammar::builder(Case::Sensitive).unicode_letters().build();
+        let mut trie = Trie::<usize>::new(grammar.clone());
+        let words = ["straße", "stadt", "über", "ünder", "δέντρο", "δάσος", "дерево", "木"];
+        for (i, word) in words.iter().enumerate() {
+            trie.insert(word, i).unwrap();
+        }
+
+        let stats = trie.memory_stats();
+        assert!(stats.approx_bytes < 64 * 1024);
+
+        let mut sorted = words.to_vec();
+        sorted.sort_by_key(|word| grammar.to_indices(word).unwrap());
+        assert!(trie.keys().eq(sorted.iter().map(|word| word.to_string())));
+        assert_eq!(trie.find("δάσος"), Some(5));
+        assert_eq!(trie.delete("über"), Ok(Some(2)));
+        assert_eq!(trie.count_prefix("ü"), 1);
+
+        // Every child of the root, in a small grammar.
+        let mut trie = Trie::<usize>::new(Grammar::default());
+        for (i, c) in ('a'..='z').enumerate() {
+            trie.insert(&c.to_string(), i).unwrap();
+        }
+        assert!(trie.values().eq(0..26));
+        for c in 'a'..='x' {
+            trie.delete(&c.to_string()).unwrap();
+        }
+        assert_eq!(trie.to_sorted_vec(), vec![("y".to_string(), 24), ("z".to_string(), 25)]);
+    }
+
+    #[test]
+    fn test_trie_score_index() {
+        let mut trie = Trie::<u32>::with_score_index(Grammar::default(), |freq: &u32| *freq as f64);
+        for (word, freq) in [("car", 40), ("cat", 10), ("dog", 90), ("door", 10), ("cart", 25)] {
+            trie.insert(word, freq).unwrap();
+        }
+
+        let keys = |trie: &Trie<u32>| trie.iter_by_score().map(|(key, _)| key).collect::<Vec<_>>();
+        assert_eq!(keys(&trie), vec!["cat", "door", "cart", "car", "dog"]);
+        assert_eq!(trie.iter_by_score().next_back(), Some(("dog".to_string(), 90)));
+
+        // Both ends can be consumed without stepping over each other.
+        let mut iter = trie.iter_by_score();
+        assert_eq!(iter.next().unwrap().0, "cat");
+        assert_eq!(iter.next_back().unwrap().0, "dog");
+        assert_eq!(iter.rev().map(|(key, _)| key).collect::<Vec<_>>(), vec!["car", "cart", "door"]);
+
+        // Every kind of change moves the keys.
+        trie.insert_or_update("cat", 95).unwrap();
+        trie.entry("door").unwrap().and_modify(|freq| *freq = 30);
+        trie.delete("car").unwrap();
+        assert_eq!(keys(&trie), vec!["cart", "door", "dog", "cat"]);
+
+        let snapshot = trie.snapshot();
+        assert_eq!(trie.delete_prefix("ca"), 2);
+        assert_eq!(keys(&trie), vec!["door", "dog"]);
+        trie.restore(&snapshot);
+        assert_eq!(keys(&trie), vec!["cart", "door", "dog", "cat"]);
+
+        let mut other = Trie::<u32>::new(Grammar::default());
+        other.insert("cow", 50).unwrap();
+        other.insert("dog", 1).unwrap();
+        trie.merge(other, |ours, theirs| ours + theirs).unwrap();
+        assert_eq!(keys(&trie), vec!["cart", "door", "cow", "dog", "cat"]);
+
+        // The index can be set after the keys were inserted, and set operations keep it.
+        let mut plain = Trie::<u32>::new(Grammar::default());
+        plain.insert("cow", 3).unwrap();
+        plain.insert("cat", 2).unwrap();
+        plain.set_score_index(|freq: &u32| -(*freq as f64));
+        assert_eq!(keys(&plain), vec!["cow", "cat"]);
+        assert_eq!(keys(&plain.intersection(&trie, |_, theirs| theirs).unwrap()), vec!["cat", "cow"]);
+    }
+
+    #[test]
+    fn test_trie_writer() {
+        let mut trie = Trie::<usize>::with_aggregate(Grammar::default(), Sum(|n: &usize| *n as f64));
+        trie.set_score_index(|n: &usize| *n as f64);
+        trie.insert("cab", 1).unwrap();
+
+        let words: Vec<String> = (0..1000usize)
+            .map(|i| [i % 26, i / 26 % 26, i % 7].iter().map(|c| (b'a' + *c as u8) as char).collect())
+            .collect();
+
+        let mut expected = Trie::<usize>::new(Grammar::default());
+        expected.insert("cab", 1).unwrap();
+        {
+            let mut writer = TrieWriter::with_batch_size(&mut trie, 64);
+            for (i, word) in words.iter().enumerate() {
+                writer.insert(word, i).unwrap();
+                expected.insert_or_update(word, i).unwrap();
+            }
+
+            // Keys outside of the grammar are refused right away, and later writes win.
+            assert_eq!(writer.insert("c4b", 0), Err(TrieError::CharNotInGrammar { ch: '4' }));
+            writer.insert("CAB", 2000).unwrap();
+            writer.insert("cab", 2001).unwrap();
+            expected.insert_or_update("cab", 2001).unwrap();
+            assert!(writer.pending() > 0);
+        }
+
+        assert_eq!(trie.len(), expected.len());
+        assert_eq!(trie.to_sorted_vec(), expected.to_sorted_vec());
+        assert_eq!(trie.count_prefix("ca"), expected.count_prefix("ca"));
+
+        // The aggregates and the score index are kept up to date along the way.
+        let total: usize = expected.values().sum();
+        assert_eq!(trie.aggregate_prefix(""), Some(total as f64));
+        let prefix_total: usize = expected.iter_prefix("b").map(|(_, n)| n).sum();
+        assert_eq!(trie.aggregate_prefix("b"), Some(prefix_total as f64));
+        assert_eq!(trie.iter_by_score().next_back(), Some(("cab".to_string(), 2001)));
+
+        let mut writer = trie.writer();
+        writer.insert("zzz", 5).unwrap();
+        writer.flush();
+        assert_eq!(writer.pending(), 0);
+        drop(writer);
+        assert_eq!(trie.find("zzz"), Some(5));
+    }
+
+    #[test]
+    fn test_interning_trie() {
+        use std::sync::Arc;
+
+        let mut trie = InterningTrie::new(Grammar::default());
+        assert!(trie.is_empty());
+
+        let categories = ["noun", "verb", "adjective"];
+        let words: Vec<String> = (0..300usize)
+            .map(|i| [i % 26, i / 26 % 26, i % 5].iter().map(|c| (b'a' + *c as u8) as char).collect())
+            .collect();
+        for (i, word) in words.iter().enumerate() {
+            assert_eq!(trie.insert(word, categories[i % 3].to_string()), Ok(None));
+        }
+
+        // Every key shares one of the 3 stored payloads.
+        assert_eq!(trie.len(), 300);
+        assert_eq!(trie.distinct_len(), 3);
+        assert!(trie.distinct().all(|(_, count)| count == 100));
+
+        let a = trie.find(&words[0]).unwrap();
+        let b = trie.find(&words[3]).unwrap();
+        assert_eq!(*a, "noun");
+        assert!(Arc::ptr_eq(&a, &b));
+
+        // Payloads are dropped once no key uses them.
+        assert_eq!(trie.insert("x!", "pronoun".to_string()), Err(TrieError::CharNotInGrammar { ch: '!' }));
+        assert_eq!(trie.distinct_len(), 3);
+
+        let prev = trie.insert(&words[0], "pronoun".to_string()).unwrap();
+        assert_eq!(prev.as_deref().map(String::as_str), Some("noun"));
+        assert_eq!(trie.distinct_len(), 4);
+        assert_eq!(trie.remove(&words[0]).as_deref().map(String::as_str), Some("pronoun"));
+        assert_eq!(trie.remove(&words[0]), None);
+        assert_eq!(trie.distinct_len(), 3);
+
+        let removed = trie.delete_prefix("");
+        assert_eq!(removed, 299);
+        assert!(trie.is_empty());
+        assert_eq!(trie.distinct_len(), 0);
+    }
 }
@@ -0,0 +1,94 @@
+use alloc::string::String;
+
+/// Rewrites keys into a canonical form before a Trie maps them onto its Grammar, so that keys which
+/// only differ in e.g. case or accents are treated as the same key.
+///
+/// Normalizers should leave '?' and '*' alone, since patterns passed to 'Trie::find_pattern' are
+/// normalized as well. Any 'Fn(&str) -> String' is a Normalizer, which makes it easy to plug in
+/// e.g. Unicode NFC from an external crate, and a pair of Normalizers applies the first, then the
+/// second.
+pub trait Normalizer: Send + Sync {
+    fn normalize(&self, seq: &str) -> String;
+}
+
+impl<F: Fn(&str) -> String + Send + Sync> Normalizer for F {
+    fn normalize(&self, seq: &str) -> String {
+        self(seq)
+    }
+}
+
+impl<A: Normalizer, B: Normalizer> Normalizer for (A, B) {
+    fn normalize(&self, seq: &str) -> String {
+        self.1.normalize(&self.0.normalize(seq))
+    }
+}
+
+/// Folds case so that keys match regardless of it. Unlike 'Case::Insensitive', chars may fold into
+/// several chars (e.g. 'ß' into "ss"), which approximates Unicode full case folding.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CaseFold;
+
+impl Normalizer for CaseFold {
+    fn normalize(&self, seq: &str) -> String {
+        let mut out = String::with_capacity(seq.len());
+
+        for c in seq.chars() {
+            match c {
+                'ß' | 'ẞ' => out.push_str("ss"),
+                'ς' => out.push('σ'),
+                c => out.extend(c.to_lowercase())
+            }
+        }
+
+        out
+    }
+}
+
+/// Removes accents and other diacritics from Latin letters, e.g. "Crème Brûlée" becomes
+/// "Creme Brulee".
+///
+/// Combining marks (U+0300 to U+036F) are dropped, which handles decomposed text, and the
+/// precomposed letters of the Latin-1 Supplement and Latin Extended-A blocks are replaced by their
+/// base letters. Other scripts are left untouched.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct StripDiacritics;
+
+/// The precomposed letters which get replaced by each base letter.
+const DIACRITICS: [(char, &str); 38] = [
+    ('A', "ÀÁÂÃÄÅĀĂĄ"), ('a', "àáâãäåāăą"),
+    ('C', "ÇĆĈĊČ"), ('c', "çćĉċč"),
+    ('D', "ĎĐ"), ('d', "ďđ"),
+    ('E', "ÈÉÊËĒĔĖĘĚ"), ('e', "èéêëēĕėęě"),
+    ('G', "ĜĞĠĢ"), ('g', "ĝğġģ"),
+    ('H', "ĤĦ"), ('h', "ĥħ"),
+    ('I', "ÌÍÎÏĨĪĬĮİ"), ('i', "ìíîïĩīĭįı"),
+    ('J', "Ĵ"), ('j', "ĵ"),
+    ('K', "Ķ"), ('k', "ķ"),
+    ('L', "ĹĻĽĿŁ"), ('l', "ĺļľŀł"),
+    ('N', "ÑŃŅŇ"), ('n', "ñńņň"),
+    ('O', "ÒÓÔÕÖØŌŎŐ"), ('o', "òóôõöøōŏő"),
+    ('R', "ŔŖŘ"), ('r', "ŕŗř"),
+    ('S', "ŚŜŞŠ"), ('s', "śŝşš"),
+    ('T', "ŢŤŦ"), ('t', "ţťŧ"),
+    ('U', "ÙÚÛÜŨŪŬŮŰŲ"), ('u', "ùúûüũūŭůűų"),
+    ('W', "Ŵ"), ('w', "ŵ"),
+    ('Y', "ÝŶŸ"), ('y', "ýÿŷ"),
+    ('Z', "ŹŻŽ"), ('z', "źżž")
+];
+
+impl Normalizer for StripDiacritics {
+    fn normalize(&self, seq: &str) -> String {
+        seq.chars()
+            .filter(|c| !('\u{300}'..='\u{36f}').contains(c))
+            .map(|c| {
+                if c.is_ascii() {
+                    return c;
+                }
+
+                DIACRITICS.iter()
+                    .find(|(_, accented)| accented.contains(c))
+                    .map_or(c, |(base, _)| *base)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,241 @@
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::trie::error::TrieError;
+use crate::trie::grammar::*;
+
+/// A node of a PersistentTrie, which never changes once created so that it can be shared by any
+/// number of versions of the trie.
+struct PersistentNode<T> {
+    payload: Option<Arc<T>>,
+
+    /// The number of keys stored in the subtree rooted at this node, including its own.
+    count: usize,
+
+    children: Vec<Option<Arc<PersistentNode<T>>>>
+}
+
+impl<T> Clone for PersistentNode<T> {
+    fn clone(&self) -> Self {
+        Self {
+            payload: self.payload.clone(),
+            count: self.count,
+            children: self.children.clone()
+        }
+    }
+}
+
+impl<T> PersistentNode<T> {
+    /// Constructs a new node without a payload or children
+    fn new(arity: usize) -> Self {
+        Self {
+            payload: None,
+            count: 0,
+            children: vec![None; arity]
+        }
+    }
+}
+
+/// This class represents an immutable Trie, where every modification returns a new version of the
+/// trie and leaves the original untouched.
+///
+/// Versions share every node which a modification didn't touch, so a new version only costs the
+/// nodes along the path to the modified key. Unlike the other trees, nodes live behind plain Arcs
+/// rather than in an arena, since they belong to every version referring to them and are freed
+/// once the last of those is dropped.
+pub struct PersistentTrie<T> {
+    grammar: Grammar,
+    root: Arc<PersistentNode<T>>
+}
+
+impl<T> Clone for PersistentTrie<T> {
+    fn clone(&self) -> Self {
+        Self {
+            grammar: self.grammar.clone(),
+            root: Arc::clone(&self.root)
+        }
+    }
+}
+
+impl<T> PersistentTrie<T> {
+
+    /// Constructs a new empty PersistentTrie with the given Grammar
+    pub fn new(grammar: Grammar) -> Self {
+        let root = Arc::new(PersistentNode::new(grammar.seq().len()));
+        Self { grammar, root }
+    }
+
+    pub fn len(&self) -> usize {
+        self.root.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a new version of the trie which also holds 'seq', or an error if it already exists.
+    pub fn insert(&self, seq: &str, t: T) -> Result<Self, TrieError> {
+        let seq = self.grammar.to_indices(seq)?;
+        let root = self._insert(Some(&self.root), &seq, Arc::new(t), false)?;
+        Ok(self.with_root(root))
+    }
+
+    /// Returns a new version of the trie which holds 'seq', replacing its payload if it already
+    /// exists.
+    pub fn insert_or_update(&self, seq: &str, t: T) -> Result<Self, TrieError> {
+        let seq = self.grammar.to_indices(seq)?;
+        let root = self._insert(Some(&self.root), &seq, Arc::new(t), true)?;
+        Ok(self.with_root(root))
+    }
+
+    /// Returns a new version of the trie without 'seq', or an error if it doesn't exist.
+    pub fn delete(&self, seq: &str) -> Result<Self, TrieError> {
+        let seq = self.grammar.to_indices(seq)?;
+
+        let root = self._delete(&self.root, &seq)?
+            .unwrap_or_else(|| Arc::new(PersistentNode::new(self.grammar.seq().len())));
+
+        Ok(self.with_root(root))
+    }
+
+    pub fn find(&self, seq: &str) -> Option<&T> {
+        self._find_node(seq)?.payload.as_deref()
+    }
+
+    pub fn contains(&self, seq: &str) -> bool {
+        self.find(seq).is_some()
+    }
+
+    /// Returns the number of keys starting with 'prefix'.
+    pub fn count_prefix(&self, prefix: &str) -> usize {
+        self._find_node(prefix).map_or(0, |node| node.count)
+    }
+
+    /// Returns all keys along with their payloads, in grammar order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (String, &T)> {
+        self.iter_prefix("")
+    }
+
+    /// Returns all keys starting with 'prefix' along with their payloads, in grammar order.
+    pub fn iter_prefix(&self, prefix: &str) -> impl DoubleEndedIterator<Item = (String, &T)> {
+        let mut result = vec![];
+
+        if let (Some(node), Ok(indices)) = (self._find_node(prefix), self.grammar.to_indices(prefix)) {
+            let chars = self.grammar.seq();
+            let mut key: String = indices.iter().map(|idx| chars[*idx]).collect();
+            Self::_collect(node, &chars, &mut key, &mut result);
+        }
+
+        result.into_iter()
+    }
+
+    /// Returns true if both versions share the same root, and therefore hold the same keys.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.root, &other.root)
+    }
+
+    fn with_root(&self, root: Arc<PersistentNode<T>>) -> Self {
+        Self {
+            grammar: self.grammar.clone(),
+            root
+        }
+    }
+
+    /// Returns a copy of the given subtree (which may not exist yet) holding the payload at 'seq',
+    /// sharing every node off of the path to it.
+    fn _insert(
+        &self,
+        node: Option<&Arc<PersistentNode<T>>>,
+        seq: &[usize],
+        payload: Arc<T>,
+        update: bool
+    ) -> Result<Arc<PersistentNode<T>>, TrieError> {
+        let mut copy = match node {
+            None => PersistentNode::new(self.grammar.seq().len()),
+            Some(node) => PersistentNode::clone(node)
+        };
+
+        match seq.split_first() {
+            None => {
+                if copy.payload.is_some() {
+                    if !update {
+                        return Err(TrieError::KeyExists);
+                    }
+                } else {
+                    copy.count += 1;
+                }
+                copy.payload = Some(payload);
+            }
+            Some((idx, rest)) => {
+                let child = self._insert(copy.children[*idx].as_ref(), rest, payload, update)?;
+
+                copy.count += child.count - copy.children[*idx].as_ref().map_or(0, |c| c.count);
+                copy.children[*idx] = Some(child);
+            }
+        }
+
+        Ok(Arc::new(copy))
+    }
+
+    /// Returns a copy of the given subtree without the payload at 'seq', or None if nothing would be
+    /// left of it.
+    fn _delete(
+        &self,
+        node: &Arc<PersistentNode<T>>,
+        seq: &[usize]
+    ) -> Result<Option<Arc<PersistentNode<T>>>, TrieError> {
+        let mut copy = PersistentNode::clone(node);
+
+        match seq.split_first() {
+            None => {
+                if copy.payload.take().is_none() {
+                    return Err(TrieError::KeyNotFound);
+                }
+            }
+            Some((idx, rest)) => {
+                let child = copy.children[*idx].as_ref().ok_or(TrieError::KeyNotFound)?;
+                copy.children[*idx] = self._delete(child, rest)?;
+            }
+        }
+
+        copy.count -= 1;
+
+        if copy.count == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Arc::new(copy)))
+    }
+
+    fn _find_node(&self, seq: &str) -> Option<&PersistentNode<T>> {
+        let mut node = self.root.as_ref();
+
+        for idx in self.grammar.to_indices(seq).ok()? {
+            node = node.children[idx].as_deref()?;
+        }
+
+        Some(node)
+    }
+
+    /// Appends every key in the subtree rooted at the given node to 'out', where 'key' is the
+    /// sequence of chars leading to the node.
+    fn _collect<'a>(
+        node: &'a PersistentNode<T>,
+        chars: &[char],
+        key: &mut String,
+        out: &mut Vec<(String, &'a T)>
+    ) {
+        if let Some(payload) = &node.payload {
+            out.push((key.clone(), payload));
+        }
+
+        for (idx, child) in node.children.iter().enumerate() {
+            if let Some(child) = child {
+                key.push(chars[idx]);
+                Self::_collect(child, chars, key, out);
+                key.pop();
+            }
+        }
+    }
+}
@@ -0,0 +1,384 @@
+use alloc::{format, vec};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::arena::*;
+use crate::arena::prelude::*;
+use crate::trie::error::TrieError;
+use crate::trie::grammar::*;
+use crate::visualize::{DotWriter, ToDot};
+
+type Id = GenerationalId;
+
+#[derive(Debug, Clone)]
+struct RadixNode<T: Debug + Clone + Send + Sync> {
+    pub id: Id,
+
+    /// The label of the edge leading into this node, this is empty only for the root.
+    pub label: String,
+
+    pub payload: Option<T>,
+
+    /// Children are indexed by the first char of their label.
+    pub children: Vec<Option<Id>>,
+}
+
+impl<T: Debug + Clone + Send + Sync> HasId for RadixNode<T> {
+    type Id = Id;
+
+    fn get_id(&self) -> Self::Id {
+        self.id
+    }
+}
+
+impl<T: Debug + Clone + Send + Sync> RadixNode<T> {
+    /// Constructs a new RadixNode from the given arguments
+    pub fn new(id: Id, label: String, payload: Option<T>, arity: usize) -> Self {
+        Self {
+            id,
+            label,
+            payload,
+            children: vec![None; arity]
+        }
+    }
+}
+
+/// This class represents a thread-safe compressed (radix / Patricia) trie.
+///
+/// Unlike the Trie, which allocates a node per char, chains of nodes with a single child are
+/// collapsed into a single edge whose label holds all of their chars. This saves a lot of memory
+/// for long keys which share little structure.
+pub struct RadixTrie<T: Debug + Clone + Send + Sync> {
+    arena: GenerationalArena<RadixNode<T>>,
+    grammar: Grammar,
+    chars: Vec<char>,
+    root: Id,
+    size: AtomicUsize
+}
+
+impl<T: Debug + Clone + Send + Sync> RadixTrie<T> {
+
+    /// Constructs a new RadixTrie with the given Grammar
+    pub fn new(grammar: Grammar) -> Self {
+        let mut arena = GenerationalArena::<RadixNode<T>>::new();
+        let chars = grammar.seq();
+
+        let root: Id = arena.get_new_id();
+        arena.add_node(RadixNode::new(root, String::new(), None, chars.len()))
+            .expect("failed to add root to tree!");
+
+        Self {
+            arena,
+            grammar,
+            chars,
+            root,
+            size: AtomicUsize::new(0)
+        }
+    }
+
+    /// Attempts to insert 'seq', returning an error if it already exists.
+    pub fn insert(&mut self, seq: &str, t: T) -> Result<(), TrieError> {
+        let seq = self.preprocess_seq(seq)?;
+
+        let mut node_id = self.root;
+        let mut rest = &seq[..];
+
+        loop {
+            let node_ref = self.arena.get_node(&node_id).expect("node doesnt exist!");
+
+            let first = match rest.chars().next() {
+                None => {
+                    let mut node = node_ref.write().unwrap();
+
+                    if node.payload.is_some() {
+                        return Err(TrieError::KeyExists);
+                    }
+
+                    node.payload = Some(t);
+                    self.size.fetch_add(1, Ordering::SeqCst);
+                    return Ok(());
+                }
+                Some(c) => c
+            };
+
+            let idx = self.idx(first);
+            let child_id = node_ref.read().unwrap().children[idx];
+
+            let child_id = match child_id {
+                // --
+                // Nothing shares a prefix with 'rest', so it gets an edge of its own.
+                None => {
+                    let new_id = self.arena.get_new_id();
+                    let child = RadixNode::new(new_id, rest.to_string(), Some(t), self.chars.len());
+                    self.arena.add_node(child).expect("could not add node!");
+
+                    node_ref.write().unwrap().children[idx] = Some(new_id);
+                    self.size.fetch_add(1, Ordering::SeqCst);
+                    return Ok(());
+                }
+                Some(id) => id
+            };
+
+            let child_ref = self.arena.get_node(&child_id).expect("node doesnt exist!");
+            let n = common_prefix_len(&child_ref.read().unwrap().label, rest);
+
+            // --
+            // If 'rest' diverges from the child's label part way through, the edge needs to be
+            // split so the shared part of the label gets a node of its own.
+            if n < child_ref.read().unwrap().label.len() {
+                let mid_id = self.arena.get_new_id();
+
+                let mut child = child_ref.write().unwrap();
+                let suffix = child.label.split_off(n);
+
+                let mut mid = RadixNode::new(mid_id, child.label.clone(), None, self.chars.len());
+                mid.children[self.idx(suffix.chars().next().unwrap())] = Some(child_id);
+                child.label = suffix;
+
+                self.arena.add_node(mid).expect("could not add node!");
+                node_ref.write().unwrap().children[idx] = Some(mid_id);
+
+                node_id = mid_id;
+            } else {
+                node_id = child_id;
+            }
+
+            rest = &rest[n..];
+        }
+    }
+
+    pub fn find(&self, seq: &str) -> Option<T> {
+        let seq = self.preprocess_seq(seq).ok()?;
+        let node_id = self._find_node(&seq)?;
+
+        let node_ref = self.arena.get_node(&node_id)?;
+        let payload = node_ref.read().unwrap().payload.clone();
+        payload
+    }
+
+    pub fn contains(&self, seq: &str) -> bool {
+        self.find(seq).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.size.load(Ordering::SeqCst)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn delete(&mut self, seq: &str) -> Result<Option<T>, TrieError> {
+        if self.is_empty() {
+            return Err(TrieError::KeyNotFound);
+        }
+
+        let seq = self.preprocess_seq(seq)?;
+        let root = self.root;
+        let payload = self._delete(&seq, &root)?;
+
+        self.size.fetch_sub(1, Ordering::SeqCst);
+        Ok(Some(payload))
+    }
+
+    /// Returns all keys starting with 'prefix' along with their payloads, in grammar order.
+    pub fn iter_prefix(&self, prefix: &str) -> impl DoubleEndedIterator<Item = (String, T)> {
+        let mut result = vec![];
+
+        // No key can start with a char outside of the grammar.
+        let prefix = match self.preprocess_seq(prefix) {
+            Ok(prefix) => prefix,
+            Err(_) => return result.into_iter()
+        };
+
+        let mut node_id = self.root;
+        let mut key = String::new();
+        let mut rest = &prefix[..];
+
+        // --
+        // Walk down the tree until 'rest' is used up, which may happen part way along an edge.
+        while let Some(first) = rest.chars().next() {
+            let node_ref = self.arena.get_node(&node_id).expect("node doesnt exist!");
+            let child_id = node_ref.read().unwrap().children[self.idx(first)];
+
+            let child_id = match child_id {
+                None => return result.into_iter(),
+                Some(id) => id
+            };
+
+            let child_ref = self.arena.get_node(&child_id).expect("node doesnt exist!");
+            let label = child_ref.read().unwrap().label.clone();
+
+            if rest.starts_with(&label) {
+                rest = &rest[label.len()..];
+            } else if label.starts_with(rest) {
+                rest = "";
+            } else {
+                return result.into_iter();
+            }
+
+            key.push_str(&label);
+            node_id = child_id;
+        }
+
+        self._collect(&node_id, &mut key, &mut result);
+
+        result.into_iter()
+    }
+
+    /// Returns the id of the node whose path spells out exactly 'seq', if any.
+    fn _find_node(&self, seq: &str) -> Option<Id> {
+        let mut node_id = self.root;
+        let mut rest = seq;
+
+        while let Some(first) = rest.chars().next() {
+            let node_ref = self.arena.get_node(&node_id)?;
+            let child_id = node_ref.read().unwrap().children[self.idx(first)]?;
+
+            let child_ref = self.arena.get_node(&child_id)?;
+            let child = child_ref.read().unwrap();
+
+            rest = rest.strip_prefix(child.label.as_str())?;
+            node_id = child_id;
+        }
+
+        Some(node_id)
+    }
+
+    fn _delete(&mut self, seq: &str, node_id: &Id) -> Result<T, TrieError> {
+        let node_ref = self.arena.get_node(node_id).expect("node doesnt exist!");
+
+        let first = match seq.chars().next() {
+            None => {
+                return node_ref.write().unwrap().payload.take()
+                    .ok_or(TrieError::KeyNotFound);
+            }
+            Some(c) => c
+        };
+
+        let idx = self.idx(first);
+        let child_id = node_ref.read().unwrap().children[idx]
+            .ok_or(TrieError::KeyNotFound)?;
+
+        let remainder = {
+            let child_ref = self.arena.get_node(&child_id).expect("node doesnt exist!");
+            let child = child_ref.read().unwrap();
+
+            match seq.strip_prefix(child.label.as_str()) {
+                None => return Err(TrieError::KeyNotFound),
+                Some(remainder) => remainder.to_string()
+            }
+        };
+
+        let payload = self._delete(&remainder, &child_id)?;
+        self.compact_child(node_id, idx);
+
+        Ok(payload)
+    }
+
+    /// Restores the invariants of the child at 'idx' after a deletion: non-terminal nodes must
+    /// have at least 2 children, otherwise they get removed or merged with their only child.
+    fn compact_child(&mut self, node_id: &Id, idx: usize) {
+        let node_ref = self.arena.get_node(node_id).expect("node doesnt exist!");
+        let child_id = match node_ref.read().unwrap().children[idx] {
+            None => return,
+            Some(id) => id
+        };
+
+        let child_ref = self.arena.get_node(&child_id).expect("node doesnt exist!");
+        let mut child = child_ref.write().unwrap();
+
+        if child.payload.is_some() {
+            return;
+        }
+
+        let grandchildren: Vec<Id> = child.children.iter().flatten().cloned().collect();
+
+        match grandchildren[..] {
+            [] => {
+                self.arena.delete_node(&child_id).expect("could not delete node");
+                node_ref.write().unwrap().children[idx] = None;
+            }
+            [grandchild_id] => {
+                let grandchild_ref = self.arena.get_node(&grandchild_id).expect("node doesnt exist!");
+                let mut grandchild = grandchild_ref.write().unwrap();
+
+                child.label.push_str(&grandchild.label);
+                child.payload = grandchild.payload.take();
+                child.children = core::mem::take(&mut grandchild.children);
+
+                self.arena.delete_node(&grandchild_id).expect("could not delete node");
+            }
+            _ => {}
+        }
+    }
+
+    /// Appends every key in the subtree rooted at the given node to 'out', where 'key' is the
+    /// sequence of chars leading to the node.
+    fn _collect(&self, node_id: &Id, key: &mut String, out: &mut Vec<(String, T)>) {
+        let node_ref = self.arena.get_node(node_id).expect("node doesnt exist!");
+        let node = node_ref.read().unwrap();
+
+        if let Some(payload) = &node.payload {
+            out.push((key.clone(), payload.clone()));
+        }
+
+        for child_id in node.children.iter().flatten() {
+            let label = {
+                let child_ref = self.arena.get_node(child_id).expect("node doesnt exist!");
+                let child = child_ref.read().unwrap();
+                child.label.clone()
+            };
+
+            key.push_str(&label);
+            self._collect(child_id, key, out);
+            key.truncate(key.len() - label.len());
+        }
+    }
+
+    /// Returns the index of the given (already preprocessed) char in the grammar.
+    fn idx(&self, c: char) -> usize {
+        self.grammar.idx(c).expect("char is not part of grammar")
+    }
+
+    /// Maps every char of 'seq' to its canonical form in the grammar.
+    fn preprocess_seq(&self, seq: &str) -> Result<String, TrieError> {
+        self.grammar.to_indices(seq).map(|indices| indices.iter().map(|i| self.chars[*i]).collect())
+    }
+}
+
+/// Returns the length in bytes of the longest common prefix of 'a' and 'b'.
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    a.chars()
+        .zip(b.chars())
+        .take_while(|(x, y)| x == y)
+        .map(|(x, _)| x.len_utf8())
+        .sum()
+}
+
+impl<T: Debug + Clone + Send + Sync> ToDot for RadixTrie<T> {
+    fn to_dot(&self) -> String {
+        let mut dot = DotWriter::new("RadixTrie");
+        let mut stack = vec![self.root];
+
+        while let Some(id) = stack.pop() {
+            let node_ref = self.arena.get_node(&id).expect("node doesnt exist!");
+            let node = node_ref.read().unwrap();
+
+            match &node.payload {
+                None => dot.node(&id, "", "shape=circle"),
+                Some(payload) => dot.node(&id, &format!("{:?}", payload), "shape=doublecircle")
+            }
+
+            for child_id in node.children.iter().flatten() {
+                let child_ref = self.arena.get_node(child_id).expect("node doesnt exist!");
+                dot.edge(&id, child_id, &child_ref.read().unwrap().label, "");
+                stack.push(*child_id);
+            }
+        }
+
+        dot.finish()
+    }
+}
@@ -0,0 +1,253 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::hash::Hash;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::arena::*;
+use crate::arena::prelude::*;
+use crate::collections::HashMap;
+use crate::trie::error::TrieError;
+
+type Id = GenerationalId;
+
+#[derive(Debug, Clone)]
+struct SeqNode<K: Send + Sync, V: Send + Sync> {
+    pub id: Id,
+
+    pub payload: Option<V>,
+
+    /// The number of keys stored in the subtree rooted at this node, including its own.
+    pub count: usize,
+
+    pub children: HashMap<K, Id>
+}
+
+impl<K: Send + Sync, V: Send + Sync> HasId for SeqNode<K, V> {
+    type Id = Id;
+
+    fn get_id(&self) -> Self::Id {
+        self.id
+    }
+}
+
+impl<K: Send + Sync, V: Send + Sync> SeqNode<K, V> {
+    /// Constructs a new SeqNode without a payload or children
+    pub fn new(id: Id) -> Self {
+        Self {
+            id,
+            payload: None,
+            count: 0,
+            children: HashMap::new()
+        }
+    }
+}
+
+/// This class represents a thread-safe Trie whose keys are sequences of arbitrary tokens, e.g. the
+/// segments of a URL route, rather than strings over a Grammar.
+///
+/// Since tokens don't have to be ordered, keys are visited in no particular order.
+pub struct SeqTrie<K: Eq + Hash + Clone + Send + Sync, V: Send + Sync> {
+    arena: GenerationalArena<SeqNode<K, V>>,
+    root: Id,
+    size: AtomicUsize
+}
+
+impl<K: Eq + Hash + Clone + Send + Sync, V: Send + Sync> Default for SeqTrie<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Eq + Hash + Clone + Send + Sync, V: Send + Sync> SeqTrie<K, V> {
+
+    /// Constructs a new empty SeqTrie
+    pub fn new() -> Self {
+        let mut arena = GenerationalArena::new();
+
+        let root = arena.get_new_id();
+        arena.add_node(SeqNode::new(root)).expect("failed to add root to tree!");
+
+        Self {
+            arena,
+            root,
+            size: AtomicUsize::new(0)
+        }
+    }
+
+    /// Attempts to insert 'seq', returning an error if it already exists.
+    pub fn insert(&mut self, seq: &[K], v: V) -> Result<(), TrieError> {
+        let mut path = vec![self.root];
+
+        for token in seq {
+            let node_ref = self.node(path.last().unwrap());
+            let child = node_ref.read().unwrap().children.get(token).copied();
+
+            let child = match child {
+                Some(child) => child,
+                None => {
+                    let child = self.arena.get_new_id();
+                    self.arena.add_node(SeqNode::new(child)).expect("could not add node!");
+                    node_ref.write().unwrap().children.insert(token.clone(), child);
+                    child
+                }
+            };
+
+            path.push(child);
+        }
+
+        {
+            let node_ref = self.node(path.last().unwrap());
+            let mut node = node_ref.write().unwrap();
+
+            if node.payload.is_some() {
+                return Err(TrieError::KeyExists);
+            }
+            node.payload = Some(v);
+        }
+
+        // Every node along the path now has one more key below it.
+        for id in &path {
+            self.node(id).write().unwrap().count += 1;
+        }
+
+        self.size.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub fn contains(&self, seq: &[K]) -> bool {
+        self._find_node(seq)
+            .is_some_and(|id| self.node(&id).read().unwrap().payload.is_some())
+    }
+
+    pub fn len(&self) -> usize {
+        self.size.load(Ordering::SeqCst)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of keys starting with 'prefix'.
+    pub fn count_prefix(&self, prefix: &[K]) -> usize {
+        self._find_node(prefix)
+            .map_or(0, |id| self.node(&id).read().unwrap().count)
+    }
+
+    pub fn delete(&mut self, seq: &[K]) -> Result<Option<V>, TrieError> {
+        let mut path = vec![self.root];
+        for token in seq {
+            let child = self.node(path.last().unwrap()).read().unwrap().children.get(token).copied();
+            path.push(child.ok_or(TrieError::KeyNotFound)?);
+        }
+
+        let payload = self.node(path.last().unwrap()).write().unwrap().payload.take()
+            .ok_or(TrieError::KeyNotFound)?;
+
+        for id in &path {
+            self.node(id).write().unwrap().count -= 1;
+        }
+
+        // --
+        // Prune the nodes which no longer lead to any key, from the bottom up.
+        for (depth, token) in seq.iter().enumerate().rev() {
+            let child = path[depth + 1];
+            if self.node(&child).read().unwrap().count > 0 {
+                break;
+            }
+
+            self.arena.delete_node(&child).expect("could not delete node");
+            self.node(&path[depth]).write().unwrap().children.remove(token);
+        }
+
+        self.size.fetch_sub(1, Ordering::SeqCst);
+        Ok(Some(payload))
+    }
+
+    /// Returns the id of the node reached by following 'seq' from the root, if any.
+    fn _find_node(&self, seq: &[K]) -> Option<Id> {
+        let mut node_id = self.root;
+
+        for token in seq {
+            node_id = *self.node(&node_id).read().unwrap().children.get(token)?;
+        }
+
+        Some(node_id)
+    }
+
+    fn node(&self, node_id: &Id) -> SharedRef<SeqNode<K, V>> {
+        self.arena.get_node(node_id).expect("node doesnt exist!")
+    }
+}
+
+impl<K: Eq + Hash + Clone + Send + Sync, V: Clone + Send + Sync> SeqTrie<K, V> {
+
+    /// Inserts 'seq', returning the previous value if it already exists.
+    pub fn insert_or_update(&mut self, seq: &[K], v: V) -> Result<Option<V>, TrieError> {
+        if let Some(id) = self._find_node(seq) {
+            let node_ref = self.node(&id);
+            let mut node = node_ref.write().unwrap();
+
+            if node.payload.is_some() {
+                return Ok(node.payload.replace(v));
+            }
+        }
+
+        self.insert(seq, v).map(|_| None)
+    }
+
+    pub fn find(&self, seq: &[K]) -> Option<V> {
+        let id = self._find_node(seq)?;
+        let payload = self.node(&id).read().unwrap().payload.clone();
+        payload
+    }
+
+    /// Returns all keys starting with 'prefix' along with their payloads.
+    pub fn iter_prefix(&self, prefix: &[K]) -> impl DoubleEndedIterator<Item = (Vec<K>, V)> {
+        let mut result = vec![];
+
+        if let Some(id) = self._find_node(prefix) {
+            self._collect(&id, &mut prefix.to_vec(), &mut result);
+        }
+
+        result.into_iter()
+    }
+
+    /// Returns the longest key which is a prefix of 'seq', along with its payload.
+    pub fn longest_prefix(&self, seq: &[K]) -> Option<(Vec<K>, V)> {
+        let mut best = None;
+        let mut node_id = self.root;
+
+        for depth in 0..=seq.len() {
+            let node_ref = self.node(&node_id);
+            let node = node_ref.read().unwrap();
+
+            if let Some(payload) = &node.payload {
+                best = Some((depth, payload.clone()));
+            }
+
+            node_id = match seq.get(depth).and_then(|token| node.children.get(token)) {
+                None => break,
+                Some(id) => *id
+            };
+        }
+
+        best.map(|(depth, payload)| (seq[..depth].to_vec(), payload))
+    }
+
+    /// Appends every key in the subtree rooted at the given node to 'out', where 'key' is the
+    /// sequence of tokens leading to the node.
+    fn _collect(&self, node_id: &Id, key: &mut Vec<K>, out: &mut Vec<(Vec<K>, V)>) {
+        let node_ref = self.node(node_id);
+        let node = node_ref.read().unwrap();
+
+        if let Some(payload) = &node.payload {
+            out.push((key.clone(), payload.clone()));
+        }
+
+        for (token, child_id) in &node.children {
+            key.push(token.clone());
+            self._collect(child_id, key, out);
+            key.pop();
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use alloc::string::String;
+
+use crate::trie::error::TrieError;
+use crate::trie::grammar::*;
+use crate::trie::normalize::Normalizer;
+use crate::trie::trie::*;
+
+/// A Trie which stores keys without payloads, for the common case of only needing to know which
+/// keys exist.
+///
+/// This is only a convenience wrapper around a Trie<()> whose methods deal in keys only. Its nodes
+/// are the same as the Trie's, so they still hold a payload slot each, it's just always empty.
+pub struct TrieSet {
+    trie: Trie<()>
+}
+
+impl TrieSet {
+
+    /// Constructs a new, empty TrieSet with the given Grammar.
+    pub fn new(grammar: Grammar) -> Self {
+        Self { trie: Trie::new(grammar) }
+    }
+
+    /// Constructs a new, empty TrieSet which normalizes every key before it is inserted or looked
+    /// up, see 'Trie::with_normalizer'.
+    pub fn with_normalizer(grammar: Grammar, normalizer: impl Normalizer + 'static) -> Self {
+        Self { trie: Trie::with_normalizer(grammar, normalizer) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.trie.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Inserts 'seq', returning true if it wasn't stored yet, or an error if 'seq' contains a char
+    /// outside of the grammar.
+    pub fn insert(&mut self, seq: &str) -> Result<bool, TrieError> {
+        match self.trie.insert(seq, ()) {
+            Ok(()) => Ok(true),
+            Err(TrieError::KeyExists) => Ok(false),
+            Err(err) => Err(err)
+        }
+    }
+
+    pub fn contains(&self, seq: &str) -> bool {
+        self.trie.contains(seq)
+    }
+
+    /// Removes 'seq', returning true if it was stored.
+    pub fn remove(&mut self, seq: &str) -> bool {
+        self.trie.delete(seq).is_ok()
+    }
+
+    /// Removes every key starting with 'prefix' (including 'prefix' itself), returning the number
+    /// of keys removed.
+    pub fn remove_prefix(&mut self, prefix: &str) -> usize {
+        self.trie.delete_prefix(prefix)
+    }
+
+    /// Returns the number of keys starting with 'prefix', in O(prefix length).
+    pub fn count_prefix(&self, prefix: &str) -> usize {
+        self.trie.count_prefix(prefix)
+    }
+
+    /// Returns all keys, in grammar order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = String> {
+        self.trie.keys()
+    }
+
+    /// Returns all keys starting with 'prefix', in grammar order.
+    pub fn iter_prefix(&self, prefix: &str) -> impl DoubleEndedIterator<Item = String> {
+        self.trie.iter_prefix(prefix).map(|(key, _)| key)
+    }
+
+    /// Returns the longest key which is a prefix of 'seq'.
+    pub fn longest_prefix(&self, seq: &str) -> Option<String> {
+        self.trie.longest_prefix(seq).map(|(key, _)| key)
+    }
+}
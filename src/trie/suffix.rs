@@ -0,0 +1,314 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::arena::*;
+use crate::arena::prelude::*;
+use crate::trie::error::TrieError;
+use crate::trie::grammar::*;
+
+type Id = GenerationalId;
+
+#[derive(Debug, Clone)]
+struct SuffixNode {
+    pub id: Id,
+
+    /// The label of the edge leading into this node is 'text[start..end]', leaves have no end of
+    /// their own since they always extend to the end of the text.
+    pub start: usize,
+    pub end: Option<usize>,
+
+    /// Only used during construction, this points to the node for the path with its first char
+    /// removed.
+    pub link: Option<Id>,
+
+    /// Children are indexed by the first char of their label, the last slot is for the terminal.
+    pub children: Vec<Option<Id>>,
+
+    /// For leaves, the position in the text at which the suffix spelled out by the path starts.
+    pub suffix: Option<usize>,
+
+    /// The number of leaves in the subtree rooted at this node.
+    pub leaves: usize
+}
+
+impl HasId for SuffixNode {
+    type Id = Id;
+
+    fn get_id(&self) -> Self::Id {
+        self.id
+    }
+}
+
+impl SuffixNode {
+    /// Constructs a new SuffixNode from the given arguments
+    pub fn new(id: Id, start: usize, end: Option<usize>, arity: usize) -> Self {
+        Self {
+            id,
+            start,
+            end,
+            link: None,
+            children: vec![None; arity],
+            suffix: None,
+            leaves: 0
+        }
+    }
+}
+
+/// This class represents a suffix tree, a compressed trie of every suffix of a text, which allows
+/// for finding all occurrences of a pattern in time proportional to the length of the pattern and
+/// the number of occurrences.
+///
+/// The tree is built in linear time using Ukkonen's algorithm. Positions are given in chars.
+pub struct SuffixTree {
+    arena: GenerationalArena<SuffixNode>,
+    grammar: Grammar,
+
+    /// The grammar indices of the chars of the text, followed by a unique terminal.
+    text: Vec<usize>,
+
+    root: Id
+}
+
+impl SuffixTree {
+
+    /// Constructs the SuffixTree of 'text', returning an error if it contains a char which is not
+    /// part of the grammar.
+    pub fn new(grammar: Grammar, text: &str) -> Result<Self, TrieError> {
+        let terminal = grammar.seq().len();
+
+        let mut indices = grammar.to_indices(text)?;
+        indices.push(terminal);
+
+        let mut arena = GenerationalArena::new();
+        let root = arena.get_new_id();
+        arena.add_node(SuffixNode::new(root, 0, Some(0), terminal + 1))
+            .expect("failed to add root to tree!");
+
+        let mut tree = Self {
+            arena,
+            grammar,
+            text: indices,
+            root
+        };
+
+        tree.build();
+        tree.annotate();
+
+        Ok(tree)
+    }
+
+    /// Returns the number of chars in the text.
+    pub fn len(&self) -> usize {
+        self.text.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns true if 'pattern' occurs anywhere in the text.
+    pub fn contains_substring(&self, pattern: &str) -> bool {
+        self._find_node(pattern).is_some()
+    }
+
+    /// Returns the number of (possibly overlapping) occurrences of 'pattern' in the text.
+    pub fn count_occurrences(&self, pattern: &str) -> usize {
+        self._find_node(pattern)
+            .map_or(0, |id| self.node(&id).read().unwrap().leaves)
+    }
+
+    /// Returns the positions of every (possibly overlapping) occurrence of 'pattern' in the text,
+    /// in ascending order.
+    pub fn find_all_occurrences(&self, pattern: &str) -> Vec<usize> {
+        let mut result = vec![];
+
+        if let Some(id) = self._find_node(pattern) {
+            let mut stack = vec![id];
+
+            while let Some(id) = stack.pop() {
+                let node_ref = self.node(&id);
+                let node = node_ref.read().unwrap();
+
+                result.extend(node.suffix);
+                stack.extend(node.children.iter().flatten());
+            }
+        }
+
+        result.sort_unstable();
+        result
+    }
+
+    /// Returns the id of the highest node whose path starts with 'pattern', if any.
+    fn _find_node(&self, pattern: &str) -> Option<Id> {
+        let pattern = self.grammar.to_indices(pattern).ok()?;
+
+        let mut node_id = self.root;
+        let mut rest = &pattern[..];
+
+        while let Some(first) = rest.first() {
+            let child_id = self.node(&node_id).read().unwrap().children[*first]?;
+            let label = self.label(&child_id);
+
+            let n = label.len().min(rest.len());
+            if label[..n] != rest[..n] {
+                return None;
+            }
+
+            rest = &rest[n..];
+            node_id = child_id;
+        }
+
+        Some(node_id)
+    }
+
+    /// Runs Ukkonen's algorithm, extending the tree by one char of the text at a time.
+    fn build(&mut self) {
+        let arity = self.grammar.seq().len() + 1;
+
+        let mut active_node = self.root;
+        let mut active_edge = 0;
+        let mut active_length = 0;
+
+        // The number of suffixes which still need to be added explicitly.
+        let mut remainder = 0;
+
+        for i in 0..self.text.len() {
+            let c = self.text[i];
+            let mut last_internal: Option<Id> = None;
+            remainder += 1;
+
+            while remainder > 0 {
+                if active_length == 0 {
+                    active_edge = i;
+                }
+
+                let edge_char = self.text[active_edge];
+                let child_id = self.node(&active_node).read().unwrap().children[edge_char];
+
+                match child_id {
+                    // --
+                    // Nothing starts with the active char yet, so the suffix gets a new leaf.
+                    None => {
+                        let leaf = self.arena.get_new_id();
+                        self.arena.add_node(SuffixNode::new(leaf, i, None, arity))
+                            .expect("could not add node!");
+                        self.node(&active_node).write().unwrap().children[edge_char] = Some(leaf);
+
+                        if let Some(id) = last_internal.take() {
+                            self.node(&id).write().unwrap().link = Some(active_node);
+                        }
+                    }
+
+                    Some(child_id) => {
+                        let (start, end) = {
+                            let child_ref = self.node(&child_id);
+                            let child = child_ref.read().unwrap();
+                            (child.start, child.end.unwrap_or(i + 1))
+                        };
+
+                        // Walk down if the active point lies beyond the end of this edge.
+                        if active_length >= end - start {
+                            active_edge += end - start;
+                            active_length -= end - start;
+                            active_node = child_id;
+                            continue;
+                        }
+
+                        // --
+                        // The suffix is already in the tree implicitly, which means all of the
+                        // shorter ones are too, so this phase is over.
+                        if self.text[start + active_length] == c {
+                            if let Some(id) = last_internal.take() {
+                                if active_node != self.root {
+                                    self.node(&id).write().unwrap().link = Some(active_node);
+                                }
+                            }
+
+                            active_length += 1;
+                            break;
+                        }
+
+                        // --
+                        // Otherwise, the edge is split where the suffix diverges from it.
+                        let split = self.arena.get_new_id();
+                        let mut split_node = SuffixNode::new(split, start, Some(start + active_length), arity);
+
+                        let leaf = self.arena.get_new_id();
+                        self.arena.add_node(SuffixNode::new(leaf, i, None, arity))
+                            .expect("could not add node!");
+                        split_node.children[c] = Some(leaf);
+
+                        self.node(&child_id).write().unwrap().start = start + active_length;
+                        split_node.children[self.text[start + active_length]] = Some(child_id);
+
+                        self.arena.add_node(split_node).expect("could not add node!");
+                        self.node(&active_node).write().unwrap().children[edge_char] = Some(split);
+
+                        if let Some(id) = last_internal.replace(split) {
+                            self.node(&id).write().unwrap().link = Some(split);
+                        }
+                    }
+                }
+
+                remainder -= 1;
+
+                if active_node == self.root && active_length > 0 {
+                    active_length -= 1;
+                    active_edge = i + 1 - remainder;
+                } else if active_node != self.root {
+                    let link = self.node(&active_node).read().unwrap().link;
+                    active_node = link.unwrap_or(self.root);
+                }
+            }
+        }
+    }
+
+    /// Records the suffix of every leaf and the number of leaves below every node.
+    fn annotate(&mut self) {
+        let n = self.text.len();
+
+        // --
+        // Visit the nodes in pre-order (along with the length of their path), so the reverse order
+        // visits every node after all of its descendants.
+        let mut order = vec![];
+        let mut stack = vec![(self.root, 0)];
+
+        while let Some((id, depth)) = stack.pop() {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+
+            let depth = depth + node.end.unwrap_or(n) - node.start;
+            for child in node.children.iter().flatten() {
+                stack.push((*child, depth));
+            }
+
+            order.push((id, depth));
+        }
+
+        for (id, depth) in order.into_iter().rev() {
+            let node_ref = self.node(&id);
+            let mut node = node_ref.write().unwrap();
+
+            if node.end.is_none() {
+                node.suffix = Some(n - depth);
+                node.leaves = 1;
+            } else {
+                node.leaves = node.children.iter()
+                    .flatten()
+                    .map(|child| self.node(child).read().unwrap().leaves)
+                    .sum();
+            }
+        }
+    }
+
+    /// Returns the grammar indices making up the label of the edge leading into the given node.
+    fn label(&self, node_id: &Id) -> Vec<usize> {
+        let node_ref = self.node(node_id);
+        let node = node_ref.read().unwrap();
+        self.text[node.start..node.end.unwrap_or(self.text.len())].to_vec()
+    }
+
+    fn node(&self, node_id: &Id) -> SharedRef<SuffixNode> {
+        self.arena.get_node(node_id).expect("could not find node")
+    }
+}
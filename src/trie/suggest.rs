@@ -0,0 +1,109 @@
+#[cfg(not(feature = "std"))]
+use nalgebra::ComplexField;
+
+use crate::collections::HashMap;
+
+/// The rows of a QWERTY keyboard, along with how far each row is shifted to the right of the one
+/// above it, in keys.
+const QWERTY: [(&str, f64); 3] = [
+    ("qwertyuiop", 0.0),
+    ("asdfghjkl", 0.25),
+    ("zxcvbnm", 0.75)
+];
+
+/// The layout of a keyboard, which makes typos hitting a key next to the intended one cheaper than
+/// other substitutions in 'Trie::suggest'.
+///
+/// Keys are compared case-insensitively, and chars which aren't on the keyboard are never next to
+/// any other char.
+#[derive(Debug, Clone)]
+pub struct Keyboard {
+    positions: HashMap<char, (f64, f64)>,
+
+    /// The cost of substituting a char for one on a neighbouring key.
+    pub adjacent_cost: f64
+}
+
+impl Keyboard {
+
+    /// Constructs a new keyboard from its rows of keys, top row first, each along with how far it
+    /// is shifted to the right, in keys.
+    pub fn from_rows(rows: &[(&str, f64)], adjacent_cost: f64) -> Self {
+        let positions = rows.iter()
+            .enumerate()
+            .flat_map(|(y, (keys, shift))| {
+                keys.chars().enumerate().map(move |(x, c)| (c.to_ascii_lowercase(), (x as f64 + shift, y as f64)))
+            })
+            .collect();
+
+        Self { positions, adjacent_cost }
+    }
+
+    /// Returns the US QWERTY keyboard.
+    pub fn qwerty() -> Self {
+        Self::from_rows(&QWERTY, 0.5)
+    }
+
+    /// Returns a keyboard without keys, which makes every substitution cost the same.
+    pub fn none() -> Self {
+        Self::from_rows(&[], 1.0)
+    }
+
+    /// Returns true if the given chars sit on neighbouring keys, including diagonally.
+    pub fn is_adjacent(&self, a: char, b: char) -> bool {
+        let position = |c: char| self.positions.get(&c.to_ascii_lowercase());
+
+        match (position(a), position(b)) {
+            (Some(pa), Some(pb)) if pa != pb => (pa.0 - pb.0).hypot(pa.1 - pb.1) < 1.5,
+            _ => false
+        }
+    }
+
+    /// Returns the cost of typing 'b' in place of 'a'.
+    pub fn substitution_cost(&self, a: char, b: char) -> f64 {
+        if a == b {
+            0.0
+        } else if self.is_adjacent(a, b) {
+            self.adjacent_cost
+        } else {
+            1.0
+        }
+    }
+}
+
+impl Default for Keyboard {
+    fn default() -> Self {
+        Self::qwerty()
+    }
+}
+
+/// This controls how 'Trie::suggest_with' finds and ranks its suggestions.
+#[derive(Debug, Clone)]
+pub struct SuggestConfig {
+    /// The most edits a suggestion can be away from the word, where inserting, deleting or swapping
+    /// 2 neighbouring chars costs 1 and substituting a char costs what the keyboard says. If None,
+    /// words of up to 4 chars allow 1 edit and longer words allow 2.
+    pub max_distance: Option<f64>,
+
+    pub keyboard: Keyboard,
+
+    /// How many edits a suggestion 10 times as frequent as another is worth, see 'Trie::suggest'.
+    pub frequency_weight: f64
+}
+
+impl SuggestConfig {
+    /// Returns the max distance for the given number of chars.
+    pub(crate) fn max_distance(&self, len: usize) -> f64 {
+        self.max_distance.unwrap_or(if len <= 4 { 1.0 } else { 2.0 })
+    }
+}
+
+impl Default for SuggestConfig {
+    fn default() -> Self {
+        Self {
+            max_distance: None,
+            keyboard: Keyboard::qwerty(),
+            frequency_weight: 0.5
+        }
+    }
+}
@@ -0,0 +1,450 @@
+use alloc::{format, vec};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::arena::*;
+use crate::arena::prelude::*;
+use crate::trie::error::TrieError;
+use crate::trie::grammar::*;
+use crate::visualize::{DotWriter, ToDot};
+
+type Id = GenerationalId;
+
+#[derive(Debug, Clone)]
+struct TernaryNode<T: Send + Sync> {
+    pub id: Id,
+
+    /// The grammar index of the char held by this node.
+    pub split: usize,
+
+    pub payload: Option<T>,
+
+    /// The number of keys whose path ends at or passes through this node's 'eq' link.
+    pub count: usize,
+
+    /// Nodes holding a char before / after 'split' at the same position of the key.
+    pub lo: Option<Id>,
+    pub hi: Option<Id>,
+
+    /// The subtree holding the next char of keys which contain 'split' at this position.
+    pub eq: Option<Id>
+}
+
+impl<T: Send + Sync> HasId for TernaryNode<T> {
+    type Id = Id;
+
+    fn get_id(&self) -> Self::Id {
+        self.id
+    }
+}
+
+impl<T: Send + Sync> TernaryNode<T> {
+    /// Constructs a new TernaryNode holding the given char.
+    pub fn new(id: Id, split: usize) -> Self {
+        Self {
+            id,
+            split,
+            payload: None,
+            count: 0,
+            lo: None,
+            hi: None,
+            eq: None
+        }
+    }
+}
+
+/// The link of a node which leads to one of its children.
+#[derive(Debug, Copy, Clone)]
+enum Branch {
+    Lo,
+    Eq,
+    Hi
+}
+
+/// This class represents a thread-safe ternary search tree, an alternative to the Trie whose nodes
+/// only hold 3 links (to the chars before / after their own, and to the next char of the key).
+///
+/// Every node of a Trie allocates a link for every char of the grammar, which is wasteful when
+/// nodes only have a few children out of a large grammar (e.g. Unicode letters). A ternary search
+/// tree uses a fixed amount of memory per node instead, at the cost of visiting up to O(log arity)
+/// nodes per char of the key rather than a single one.
+pub struct TernarySearchTree<T: Send + Sync> {
+    arena: GenerationalArena<TernaryNode<T>>,
+    grammar: Grammar,
+    root: Option<Id>,
+
+    /// The payload of the empty key, which has no node to be stored in.
+    empty: Option<T>,
+
+    size: AtomicUsize
+}
+
+impl<T: Send + Sync> TernarySearchTree<T> {
+
+    /// Constructs a new TernarySearchTree with the given Grammar
+    pub fn new(grammar: Grammar) -> Self {
+        Self {
+            arena: GenerationalArena::new(),
+            grammar,
+            root: None,
+            empty: None,
+            size: AtomicUsize::new(0)
+        }
+    }
+
+    /// Attempts to insert 'seq', returning an error if it already exists.
+    pub fn insert(&mut self, seq: &str, t: T) -> Result<(), TrieError> {
+        let seq = self.preprocess_seq(seq)?;
+
+        if seq.is_empty() {
+            if self.empty.is_some() {
+                return Err(TrieError::KeyExists);
+            }
+
+            self.empty = Some(t);
+            self.size.fetch_add(1, Ordering::SeqCst);
+            return Ok(());
+        }
+
+        // The counts along the path are bumped on the way down, so the key must be new.
+        if self._find_node(&seq).is_some_and(|id| self.node(&id).read().unwrap().payload.is_some()) {
+            return Err(TrieError::KeyExists);
+        }
+
+        let mut depth = 0;
+        let mut parent: Option<(Id, Branch)> = None;
+        let mut current = self.root;
+
+        loop {
+            let node_id = match current {
+                Some(id) => id,
+                None => {
+                    let id = self.arena.get_new_id();
+                    self.arena.add_node(TernaryNode::new(id, seq[depth])).expect("could not add node!");
+                    self.set_link(parent, Some(id));
+                    id
+                }
+            };
+
+            let node_ref = self.node(&node_id);
+            let mut node = node_ref.write().unwrap();
+            let c = seq[depth];
+
+            if c < node.split {
+                parent = Some((node_id, Branch::Lo));
+                current = node.lo;
+            } else if c > node.split {
+                parent = Some((node_id, Branch::Hi));
+                current = node.hi;
+            } else {
+                node.count += 1;
+
+                if depth + 1 == seq.len() {
+                    node.payload = Some(t);
+                    break;
+                }
+
+                depth += 1;
+                parent = Some((node_id, Branch::Eq));
+                current = node.eq;
+            }
+        }
+
+        self.size.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub fn contains(&self, seq: &str) -> bool {
+        let seq = match self.preprocess_seq(seq) {
+            Ok(seq) => seq,
+            Err(_) => return false
+        };
+
+        if seq.is_empty() {
+            return self.empty.is_some();
+        }
+
+        self._find_node(&seq).is_some_and(|id| self.node(&id).read().unwrap().payload.is_some())
+    }
+
+    pub fn len(&self) -> usize {
+        self.size.load(Ordering::SeqCst)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of keys starting with 'prefix', in O(prefix length * log arity).
+    pub fn count_prefix(&self, prefix: &str) -> usize {
+        let prefix = match self.preprocess_seq(prefix) {
+            Ok(prefix) => prefix,
+            Err(_) => return 0
+        };
+
+        if prefix.is_empty() {
+            return self.len();
+        }
+
+        self._find_node(&prefix).map_or(0, |id| self.node(&id).read().unwrap().count)
+    }
+
+    /// Returns the number of nodes in the tree along with the memory they use.
+    pub fn memory_stats(&self) -> ArenaStats {
+        self.arena.stats()
+    }
+
+    /// Removes 'seq', returning its payload. Nodes which no longer lead to any key are removed.
+    pub fn delete(&mut self, seq: &str) -> Result<Option<T>, TrieError> {
+        let seq = self.preprocess_seq(seq)?;
+
+        let payload = if seq.is_empty() {
+            self.empty.take().ok_or(TrieError::KeyNotFound)?
+        } else {
+            let root = self.root.ok_or(TrieError::KeyNotFound)?;
+            let (link, payload) = self._delete(root, &seq)?;
+            self.root = link;
+            payload
+        };
+
+        self.size.fetch_sub(1, Ordering::SeqCst);
+        Ok(Some(payload))
+    }
+
+    /// Removes the key from the subtree rooted at the given node, returning the node which should
+    /// take the subtree's place (as it may have been pruned) along with the key's payload.
+    fn _delete(&mut self, node_id: Id, seq: &[usize]) -> Result<(Option<Id>, T), TrieError> {
+        let node_ref = self.node(&node_id);
+        let (split, lo, eq, hi) = {
+            let node = node_ref.read().unwrap();
+            (node.split, node.lo, node.eq, node.hi)
+        };
+
+        let payload = if seq[0] < split {
+            let (link, payload) = self._delete(lo.ok_or(TrieError::KeyNotFound)?, seq)?;
+            node_ref.write().unwrap().lo = link;
+            payload
+        } else if seq[0] > split {
+            let (link, payload) = self._delete(hi.ok_or(TrieError::KeyNotFound)?, seq)?;
+            node_ref.write().unwrap().hi = link;
+            payload
+        } else if seq.len() > 1 {
+            let (link, payload) = self._delete(eq.ok_or(TrieError::KeyNotFound)?, &seq[1..])?;
+            let mut node = node_ref.write().unwrap();
+            node.eq = link;
+            node.count -= 1;
+            payload
+        } else {
+            let mut node = node_ref.write().unwrap();
+            let payload = node.payload.take().ok_or(TrieError::KeyNotFound)?;
+            node.count -= 1;
+            payload
+        };
+
+        // --
+        // A node which no longer leads to any key is replaced by its lo and hi subtrees.
+        let (lo, hi) = {
+            let node = node_ref.read().unwrap();
+            if node.payload.is_some() || node.eq.is_some() {
+                return Ok((Some(node_id), payload));
+            }
+            (node.lo, node.hi)
+        };
+
+        drop(node_ref);
+        self.arena.delete_node(&node_id).expect("could not delete node");
+
+        Ok((self.join(lo, hi), payload))
+    }
+
+    /// Joins 2 sibling subtrees, where every char in 'lo' comes before every char in 'hi', by
+    /// hanging 'hi' off of the last node of 'lo'.
+    fn join(&self, lo: Option<Id>, hi: Option<Id>) -> Option<Id> {
+        let (lo, hi) = match (lo, hi) {
+            (None, other) | (other, None) => return other,
+            (Some(lo), Some(hi)) => (lo, hi)
+        };
+
+        let mut last = lo;
+        while let Some(next) = self.node(&last).read().unwrap().hi {
+            last = next;
+        }
+        self.node(&last).write().unwrap().hi = Some(hi);
+
+        Some(lo)
+    }
+
+    /// Returns the id of the node holding the last char of 'seq', if any.
+    fn _find_node(&self, seq: &[usize]) -> Option<Id> {
+        let mut depth = 0;
+        let mut current = self.root;
+
+        while let Some(node_id) = current {
+            let node_ref = self.node(&node_id);
+            let node = node_ref.read().unwrap();
+
+            if seq[depth] < node.split {
+                current = node.lo;
+            } else if seq[depth] > node.split {
+                current = node.hi;
+            } else if depth + 1 == seq.len() {
+                return Some(node_id);
+            } else {
+                depth += 1;
+                current = node.eq;
+            }
+        }
+
+        None
+    }
+
+    /// Points the given link at 'id', where no parent stands for the root.
+    fn set_link(&mut self, parent: Option<(Id, Branch)>, id: Option<Id>) {
+        match parent {
+            None => self.root = id,
+            Some((parent_id, branch)) => {
+                let parent_ref = self.node(&parent_id);
+                let mut parent = parent_ref.write().unwrap();
+                match branch {
+                    Branch::Lo => parent.lo = id,
+                    Branch::Eq => parent.eq = id,
+                    Branch::Hi => parent.hi = id
+                }
+            }
+        }
+    }
+
+    fn node(&self, id: &Id) -> SharedRef<TernaryNode<T>> {
+        self.arena.get_node(id).expect("node doesnt exist!")
+    }
+
+    /// Converts 'seq' into grammar indices, failing if a char isn't part of the grammar.
+    fn preprocess_seq(&self, seq: &str) -> Result<Vec<usize>, TrieError> {
+        self.grammar.to_indices(seq)
+    }
+}
+
+impl<T: Clone + Send + Sync> TernarySearchTree<T> {
+
+    /// Inserts 'seq', returning the previous value if it already exists.
+    pub fn insert_or_update(&mut self, seq: &str, t: T) -> Result<Option<T>, TrieError> {
+        let indices = self.preprocess_seq(seq)?;
+
+        if indices.is_empty() {
+            let old = self.empty.replace(t);
+            if old.is_none() {
+                self.size.fetch_add(1, Ordering::SeqCst);
+            }
+            return Ok(old);
+        }
+
+        if let Some(node_id) = self._find_node(&indices) {
+            let node_ref = self.node(&node_id);
+            let mut node = node_ref.write().unwrap();
+            if node.payload.is_some() {
+                return Ok(node.payload.replace(t));
+            }
+        }
+
+        self.insert(seq, t).map(|_| None)
+    }
+
+    pub fn find(&self, seq: &str) -> Option<T> {
+        let seq = self.preprocess_seq(seq).ok()?;
+
+        if seq.is_empty() {
+            return self.empty.clone();
+        }
+
+        let node_id = self._find_node(&seq)?;
+        let payload = self.node(&node_id).read().unwrap().payload.clone();
+        payload
+    }
+
+    /// Returns all keys along with their payloads, in grammar order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (String, T)> {
+        self.iter_prefix("")
+    }
+
+    /// Returns all keys starting with 'prefix' along with their payloads, in grammar order.
+    pub fn iter_prefix(&self, prefix: &str) -> impl DoubleEndedIterator<Item = (String, T)> {
+        let mut result = vec![];
+
+        // No key can start with a char outside of the grammar.
+        let prefix = match self.preprocess_seq(prefix) {
+            Ok(prefix) => prefix,
+            Err(_) => return result.into_iter()
+        };
+        let chars = self.grammar.seq();
+        let mut key: String = prefix.iter().map(|idx| chars[*idx]).collect();
+
+        if prefix.is_empty() {
+            if let Some(payload) = &self.empty {
+                result.push((String::new(), payload.clone()));
+            }
+            self._collect(self.root, &chars, &mut key, &mut result);
+        } else if let Some(node_id) = self._find_node(&prefix) {
+            let node_ref = self.node(&node_id);
+            let node = node_ref.read().unwrap();
+
+            if let Some(payload) = &node.payload {
+                result.push((key.clone(), payload.clone()));
+            }
+            self._collect(node.eq, &chars, &mut key, &mut result);
+        }
+
+        result.into_iter()
+    }
+
+    /// Appends every key in the subtree rooted at the given node to 'out' in grammar order, where
+    /// 'key' is the sequence of chars leading to the subtree.
+    fn _collect(&self, node_id: Option<Id>, chars: &[char], key: &mut String, out: &mut Vec<(String, T)>) {
+        let node_id = match node_id {
+            None => return,
+            Some(id) => id
+        };
+
+        let node_ref = self.node(&node_id);
+        let node = node_ref.read().unwrap();
+
+        self._collect(node.lo, chars, key, out);
+
+        key.push(chars[node.split]);
+        if let Some(payload) = &node.payload {
+            out.push((key.clone(), payload.clone()));
+        }
+        self._collect(node.eq, chars, key, out);
+        key.pop();
+
+        self._collect(node.hi, chars, key, out);
+    }
+}
+
+impl<T: Debug + Send + Sync> ToDot for TernarySearchTree<T> {
+    fn to_dot(&self) -> String {
+        let mut dot = DotWriter::new("TernarySearchTree");
+        let chars = self.grammar.seq();
+        let mut stack: Vec<Id> = self.root.into_iter().collect();
+
+        while let Some(id) = stack.pop() {
+            let node_ref = self.node(&id);
+            let node = node_ref.read().unwrap();
+
+            match &node.payload {
+                None => dot.node(&id, &chars[node.split].to_string(), "shape=circle"),
+                Some(payload) => dot.node(&id, &format!("{}: {:?}", chars[node.split], payload), "shape=doublecircle")
+            }
+
+            for (child, label, style) in [(node.lo, "lo", "style=dashed"), (node.eq, "", ""), (node.hi, "hi", "style=dashed")] {
+                if let Some(child_id) = child {
+                    dot.edge(&id, &child_id, label, style);
+                    stack.push(child_id);
+                }
+            }
+        }
+
+        dot.finish()
+    }
+}
@@ -1,24 +1,60 @@
-use std::fmt::Debug;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use alloc::borrow::Cow;
+use alloc::collections::BinaryHeap;
+use alloc::{format, vec};
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::Ordering as CmpOrdering;
+use core::f64::consts::LN_10;
+use core::fmt::Debug;
+use core::ops::Bound;
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::io::{self, Write};
+
+#[cfg(not(feature = "std"))]
+use nalgebra::ComplexField;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 
 use crate::arena::*;
 use crate::arena::prelude::*;
+use crate::collections::{HashMap, HashSet};
+use crate::metrics::Metrics;
+use crate::random::Rng;
+use crate::trie::aggregate::Aggregate;
+use crate::trie::aho_corasick::AhoCorasick;
+use crate::trie::children::Children;
+use crate::trie::error::TrieError;
+use crate::trie::frozen::FrozenTrie;
 use crate::trie::grammar::*;
+use crate::trie::index::{Cursor, ScoreIndex};
+use crate::trie::normalize::Normalizer;
+use crate::trie::suggest::SuggestConfig;
+use crate::trie::writer::TrieWriter;
+use crate::tree::lcrs::{LcrsNode, LcrsTree};
+use crate::visualize::{DotWriter, ToDot};
 
-type Id = usize;
+type Id = GenerationalId;
 
 #[derive(Debug, Clone)]
-struct TrieNode<T: Debug + Clone + Send + Sync> {
+pub(crate) struct TrieNode<T: Send + Sync> {
     pub id: Id,
 
     pub payload: Option<T>,
 
+    /// The number of keys stored in the subtree rooted at this node, including its own.
+    pub count: usize,
+
+    /// The Aggregate of the payloads in the subtree rooted at this node, if the Trie has one.
+    pub aggregate: f64,
+
     /// These 2 are dependent on the Grammar of the Trie
     pub arity: usize,
-    pub children: Vec<Option<Id>>,
+    pub children: Children,
 }
 
-impl<T: Debug + Clone + Send + Sync> HasId for TrieNode<T> {
+impl<T: Send + Sync> HasId for TrieNode<T> {
     type Id = Id;
 
     fn get_id(&self) -> Self::Id {
@@ -26,22 +62,239 @@ impl<T: Debug + Clone + Send + Sync> HasId for TrieNode<T> {
     }
 }
 
+/// A single element of a pattern passed to 'Trie::find_pattern'.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum PatternToken {
+    /// Matches the char with the given grammar index.
+    Char(usize),
+
+    /// Matches any single char.
+    AnyChar,
+
+    /// Matches any sequence of chars, including the empty one.
+    AnySeq
+}
+
+/// Either a subtree or a single key, ranked by its score in 'Trie::top_k_by_prefix'.
+enum Candidate<T> {
+    Node(Id),
+    Key(T)
+}
+
+struct Ranked<T> {
+    score: f64,
+    key: String,
+    candidate: Candidate<T>
+}
+
+impl<T> PartialEq for Ranked<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl<T> Eq for Ranked<T> {}
+
+impl<T> PartialOrd for Ranked<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Ranked<T> {
+    /// Higher scores rank first, ties are broken in favor of the smaller key. A subtree ranks ahead
+    /// of a key with the same score and key, so that the key is only taken once nothing below it
+    /// could beat it.
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.score.total_cmp(&other.score)
+            .then_with(|| other.key.cmp(&self.key))
+            .then_with(|| match (&self.candidate, &other.candidate) {
+                (Candidate::Node(_), Candidate::Key(_)) => CmpOrdering::Greater,
+                (Candidate::Key(_), Candidate::Node(_)) => CmpOrdering::Less,
+                _ => CmpOrdering::Equal
+            })
+    }
+}
+
+/// A summary of the shape of a Trie, see 'Trie::debug_structure'.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrieStructure {
+    /// The number of nodes at every depth, starting with the root at depth 0.
+    pub nodes_per_depth: Vec<usize>,
+
+    /// The number of chains of every length. A chain is a run of nodes below the root which hold no
+    /// payload and have a single child, which a radix trie would merge into a single edge.
+    pub chain_lengths: Vec<usize>,
+
+    /// The memory used by the nodes, see 'Trie::memory_stats'.
+    pub memory: ArenaStats
+}
+
+impl TrieStructure {
+    /// Returns the number of nodes in the Trie.
+    pub fn node_count(&self) -> usize {
+        self.nodes_per_depth.iter().sum()
+    }
+
+    /// Returns the number of nodes which are part of a chain, i.e. the number of nodes a radix trie
+    /// would save.
+    pub fn chained_nodes(&self) -> usize {
+        self.chain_lengths.iter().enumerate().map(|(len, count)| len * count).sum()
+    }
+}
+
+/// The state of a Trie at some point in time, which it can be rolled back to, and which can be
+/// searched on its own.
+///
+/// Taking a snapshot only shares the nodes, and the trie copies a shared node the first time it
+/// changes it afterwards. So a snapshot sees all of the changes made before it was taken and none
+/// made after, no matter how many nodes they touch, and searching it never waits on the trie. This
+/// makes it a consistent view for readers on other threads while the trie keeps being written to.
+pub struct TrieSnapshot<T: Send + Sync> {
+    arena: ArenaSnapshot<TrieNode<T>>,
+
+    /// The grammar the nodes were laid out for, since it may have been extended since.
+    grammar: Grammar,
+
+    /// The normalizer the keys were stored with, since it may have been replaced since.
+    normalizer: Option<Arc<dyn Normalizer>>,
+    root: Id,
+    size: usize
+}
+
+impl<T: Send + Sync> Clone for TrieSnapshot<T> {
+    fn clone(&self) -> Self {
+        Self {
+            arena: self.arena.clone(),
+            grammar: self.grammar.clone(),
+            normalizer: self.normalizer.clone(),
+            root: self.root,
+            size: self.size
+        }
+    }
+}
+
+impl<T: Clone + Send + Sync> TrieSnapshot<T> {
+
+    /// Returns the number of keys stored when the snapshot was taken.
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the payload 'seq' had when the snapshot was taken, if it existed.
+    pub fn find(&self, seq: &str) -> Option<T> {
+        self.find_node(seq).and_then(|node_ref| node_ref.read().unwrap().payload.clone())
+    }
+
+    pub fn contains(&self, seq: &str) -> bool {
+        self.find_node(seq).is_some_and(|node_ref| node_ref.read().unwrap().is_terminal())
+    }
+
+    /// Returns the number of keys starting with 'prefix'.
+    pub fn count_prefix(&self, prefix: &str) -> usize {
+        self.find_node(prefix).map_or(0, |node_ref| node_ref.read().unwrap().count)
+    }
+
+    /// Returns all keys along with their payloads, in grammar order.
+    pub fn iter(&self) -> Iter<T> {
+        self.iter_prefix("")
+    }
+
+    /// Returns all keys starting with 'prefix' along with their payloads, in grammar order.
+    pub fn iter_prefix(&self, prefix: &str) -> Iter<T> {
+        let mut result = vec![];
+
+        let chars = self.grammar.seq();
+        if let Some((prefix, node_ref)) = self.preprocess_seq(prefix)
+            .and_then(|prefix| self._find_node(&prefix).map(|node_ref| (prefix, node_ref)))
+        {
+            let mut key = prefix.iter().map(|idx| chars[*idx]).collect();
+            self._collect(&node_ref, &chars, &mut key, &mut result);
+        }
+
+        Iter { entries: result.into_iter() }
+    }
+
+    /// Same as 'Trie::preprocess_seq', but a key outside of the grammar can't exist anyway.
+    fn preprocess_seq(&self, seq: &str) -> Option<Vec<usize>> {
+        let seq = match &self.normalizer {
+            None => Cow::Borrowed(seq),
+            Some(normalizer) => Cow::Owned(normalizer.normalize(seq))
+        };
+        self.grammar.to_indices(&seq).ok()
+    }
+
+    /// Returns the node 'seq' leads to, if there is one.
+    fn find_node(&self, seq: &str) -> Option<SharedRef<TrieNode<T>>> {
+        self._find_node(&self.preprocess_seq(seq)?)
+    }
+
+    fn _find_node(&self, seq: &[usize]) -> Option<SharedRef<TrieNode<T>>> {
+        let mut node_ref = self.arena.get_node(&self.root)?;
+        for idx in seq {
+            let child_id = node_ref.read().unwrap().child(*idx)?;
+            node_ref = self.arena.get_node(&child_id).expect("node doesnt exist!");
+        }
+
+        Some(node_ref)
+    }
+
+    fn _collect(&self, node_ref: &SharedRef<TrieNode<T>>, seq: &[char], key: &mut String, out: &mut Vec<(String, T)>) {
+        let node = node_ref.read().unwrap();
+
+        if let Some(payload) = &node.payload {
+            out.push((key.clone(), payload.clone()));
+        }
+
+        for (idx, child_id) in node.children.iter() {
+            let child_ref = self.arena.get_node(&child_id).expect("node doesnt exist!");
+            key.push(seq[idx]);
+            self._collect(&child_ref, seq, key, out);
+            key.pop();
+        }
+    }
+}
+
 /// This class represents a thread-safe Trie (prefix tree) data structure.
-pub struct Trie<T: Debug + Clone + Send + Sync> {
-    arena: Arena<TrieNode<T>>,
+pub struct Trie<T: Send + Sync> {
+    arena: GenerationalArena<TrieNode<T>>,
     grammar: Grammar,
+    normalizer: Option<Arc<dyn Normalizer>>,
+    aggregate: Option<Arc<dyn Aggregate<T>>>,
+    index: Option<ScoreIndex<T>>,
     root: Id,
     size: AtomicUsize
 }
 
-impl<T: Debug + Clone + Send + Sync> TrieNode<T> {
+impl<T: Send + Sync> TrieNode<T> {
     /// Constructs a new TriNode from the given arguments
     pub fn new(id: Id, payload: Option<T>, arity: usize) -> Self {
         Self {
             id,
             payload,
+            count: 0,
+            aggregate: 0.0,
             arity,
-            children: vec![None; arity]
+            children: Children::default()
+        }
+    }
+
+    /// Returns the child for the char with the given grammar index.
+    pub fn child(&self, idx: usize) -> Option<Id> {
+        self.children.get(idx)
+    }
+
+    /// Sets or clears the child for the char with the given grammar index.
+    pub fn set_child(&mut self, idx: usize, child: Option<Id>) {
+        match child {
+            Some(id) => self.children.insert(idx, id, self.arity),
+            None => {
+                self.children.remove(idx);
+            }
         }
     }
 
@@ -50,9 +303,9 @@ impl<T: Debug + Clone + Send + Sync> TrieNode<T> {
         self.payload.is_some()
     }
 
-    /// Returns true if all children are None.
+    /// Returns true if the node has no children.
     pub fn is_leaf(&self) -> bool {
-        self.children.iter().all(|x| x.is_none())
+        self.children.is_empty()
     }
 
     /// Returns true if the node has no children and it is not terminal.
@@ -61,16 +314,243 @@ impl<T: Debug + Clone + Send + Sync> TrieNode<T> {
     }
 }
 
+/// An iterator over the keys and payloads of a Trie, in grammar order, or in reverse grammar order
+/// when iterated from the back.
+pub struct Iter<T> {
+    entries: alloc::vec::IntoIter<(String, T)>
+}
+
+impl<T> Iterator for Iter<T> {
+    type Item = (String, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<T> DoubleEndedIterator for Iter<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.entries.next_back()
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<T> {}
+
+impl<T: Clone + Send + Sync> IntoIterator for Trie<T> {
+    type Item = (String, T);
+    type IntoIter = Iter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: Clone + Send + Sync> IntoIterator for &Trie<T> {
+    type Item = (String, T);
+    type IntoIter = Iter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the keys and payloads of a Trie, ordered by the score of their payloads, see
+/// 'Trie::iter_by_score'.
+///
+/// The iterator walks the score index lazily, so keys which are changed while iterating show up
+/// at (or disappear from) their new position.
+pub struct ScoreIter<'a, T: Send + Sync> {
+    trie: &'a Trie<T>,
+    front: Cursor,
+    back: Cursor
+}
+
+impl<T: Clone + Send + Sync> ScoreIter<'_, T> {
+    fn step(&mut self, forward: bool) -> Option<(String, T)> {
+        let index = self.trie.index.as_ref().expect("trie has no score index");
+
+        let (score, key) = index.step(&self.front, &self.back, forward)?;
+        let payload = self.trie.grammar.to_indices(&key).ok()
+            .and_then(|seq| self.trie._find(&seq, &self.trie.root))
+            .expect("could not find node");
+
+        let cursor = Bound::Excluded((score, key.clone()));
+        if forward {
+            self.front = cursor;
+        } else {
+            self.back = cursor;
+        }
+
+        Some((key, payload))
+    }
+}
+
+impl<T: Clone + Send + Sync> Iterator for ScoreIter<'_, T> {
+    type Item = (String, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.step(true)
+    }
+}
+
+impl<T: Clone + Send + Sync> DoubleEndedIterator for ScoreIter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.step(false)
+    }
+}
+
+/// A view into a single key of a Trie, which may or may not be stored yet.
+pub enum Entry<'a, T: Send + Sync> {
+    Occupied(OccupiedEntry<'a, T>),
+    Vacant(VacantEntry<'a, T>)
+}
+
+/// A key which is stored in a Trie.
+///
+/// Since payloads live behind the locks of their nodes, they can't be borrowed from the entry.
+/// Instead they are accessed through 'get' and 'modify'.
+pub struct OccupiedEntry<'a, T: Send + Sync> {
+    trie: &'a mut Trie<T>,
+    seq: Vec<usize>,
+    node_id: Id
+}
+
+/// A key which is not stored in a Trie yet.
+pub struct VacantEntry<'a, T: Send + Sync> {
+    trie: &'a mut Trie<T>,
+    seq: Vec<usize>
+}
+
+impl<'a, T: Send + Sync> Entry<'a, T> {
+    /// Returns the key of this entry.
+    pub fn key(&self) -> String {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key()
+        }
+    }
+
+    /// Applies 'f' to the payload if the key is stored.
+    pub fn and_modify<F: FnOnce(&mut T)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(entry) => {
+                entry.modify(f);
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry)
+        }
+    }
+
+    /// Inserts 't' if the key is not stored yet.
+    pub fn or_insert(self, t: T) -> OccupiedEntry<'a, T> {
+        self.or_insert_with(|| t)
+    }
+
+    /// Inserts the result of 'f' if the key is not stored yet, 'f' isn't called otherwise.
+    pub fn or_insert_with<F: FnOnce() -> T>(self, f: F) -> OccupiedEntry<'a, T> {
+        match self {
+            Entry::Occupied(entry) => entry,
+            Entry::Vacant(entry) => entry.insert(f())
+        }
+    }
+}
+
+impl<'a, T: Default + Send + Sync> Entry<'a, T> {
+    /// Inserts the default payload if the key is not stored yet.
+    pub fn or_default(self) -> OccupiedEntry<'a, T> {
+        self.or_insert_with(T::default)
+    }
+}
+
+impl<T: Send + Sync> OccupiedEntry<'_, T> {
+    /// Returns the key of this entry.
+    pub fn key(&self) -> String {
+        self.trie.to_key(&self.seq)
+    }
+
+    /// Applies 'f' to the payload.
+    pub fn modify<F: FnOnce(&mut T)>(&self, f: F) {
+        let node_ref = self.trie.arena.get_node_mut(&self.node_id).expect("node doesnt exist!");
+        f(node_ref.write().unwrap().payload.as_mut().expect("entry is not occupied"));
+        self.trie.refresh_aggregates(&self.seq);
+        self.trie.refresh_index(&self.seq);
+    }
+
+    /// Replaces the payload, returning the previous one.
+    pub fn insert(&self, t: T) -> T {
+        let node_ref = self.trie.arena.get_node_mut(&self.node_id).expect("node doesnt exist!");
+        let prev = node_ref.write().unwrap().payload.replace(t);
+        self.trie.refresh_aggregates(&self.seq);
+        self.trie.refresh_index(&self.seq);
+        prev.expect("entry is not occupied")
+    }
+
+    /// Removes the key from the trie, returning its payload.
+    pub fn remove(self) -> T {
+        let root = self.trie.root;
+        let (_, payload) = self.trie._delete(&self.seq, &root).expect("entry is not occupied");
+        self.trie.refresh_aggregates(&self.seq);
+        self.trie.refresh_index(&self.seq);
+        payload.expect("entry is not occupied")
+    }
+}
+
+impl<T: Clone + Send + Sync> OccupiedEntry<'_, T> {
+    /// Returns the payload.
+    pub fn get(&self) -> T {
+        let node_ref = self.trie.arena.get_node(&self.node_id).expect("node doesnt exist!");
+        let payload = node_ref.read().unwrap().payload.clone();
+        payload.expect("entry is not occupied")
+    }
+}
+
+impl<'a, T: Send + Sync> VacantEntry<'a, T> {
+    /// Returns the key of this entry.
+    pub fn key(&self) -> String {
+        self.trie.to_key(&self.seq)
+    }
+
+    /// Inserts the key with the given payload.
+    pub fn insert(self, t: T) -> OccupiedEntry<'a, T> {
+        let root = self.trie.root;
+        self.trie._insert_apply(&self.seq, &root, t, |_| unreachable!(), OnCollision::ReturnError)
+            .expect("entry is not vacant");
+        self.trie.refresh_aggregates(&self.seq);
+        self.trie.refresh_index(&self.seq);
+
+        let node_id = self.trie._find_node(&self.seq, &root).expect("node doesnt exist!");
+        OccupiedEntry { trie: self.trie, seq: self.seq, node_id }
+    }
+}
+
 enum OnCollision {
     ReturnError,
     ApplyFn,
 }
 
-impl<T: Default + Debug + Clone + Send + Sync> Trie<T> {
+/// The set operations which combine two tries into a new one.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum SetOp {
+    Union,
+    Intersection,
+    Difference
+}
+
+impl<T: Send + Sync> Trie<T> {
 
     /// Constructs a new Trie with the given Grammar
     pub fn new(grammar: Grammar) -> Self {
-        let mut arena = Arena::<TrieNode<T>>::new();
+        Self::with_shards(grammar, 1)
+    }
+
+    /// Constructs a new Trie with the given Grammar, whose nodes are spread over the given number of
+    /// arena shards to reduce lock contention between threads reading the Trie.
+    pub fn with_shards(grammar: Grammar, shards: usize) -> Self {
+        let mut arena = GenerationalArena::<TrieNode<T>>::with_shards(shards);
 
         let root: Id = arena.get_new_id();
 
@@ -85,22 +565,215 @@ impl<T: Default + Debug + Clone + Send + Sync> Trie<T> {
         Self {
             arena,
             grammar,
+            normalizer: None,
+            aggregate: None,
+            index: None,
             root,
             size: AtomicUsize::new(0)
         }
     }
 
-    /// Attempts to insert 'seq', returning an error if it already exists.
-    pub fn insert(&mut self, seq: &str, t: T) -> Result<(), String> {
-        let seq = self.preprocess_seq(seq);
-        let root = self.root;
-        self._insert_apply(&seq[..], &root, t, |_| T::default(), OnCollision::ReturnError)
-            .and_then(|_| Ok(()))
+    /// Constructs a Trie holding the given keys, which must be strictly ascending in grammar order
+    /// (the order in which 'iter' returns them), returning an error if a key contains a char
+    /// outside of the grammar, or if a key isn't greater than the one before it.
+    ///
+    /// Consecutive keys share the path to their common prefix, so the trie is built in a single
+    /// pass: only the nodes below the prefix are created, and each node is finished once every key
+    /// below it is known, instead of descending from the root for every key. The finished nodes are
+    /// added to the arena in a single batch.
+    pub fn from_sorted_iter<K: AsRef<str>>(
+        grammar: Grammar,
+        entries: impl IntoIterator<Item = (K, T)>
+    ) -> Result<Self, TrieError> {
+        let arity = grammar.seq().len();
+        let mut arena = GenerationalArena::<TrieNode<T>>::new();
+        let root: Id = arena.get_new_id();
+
+        // The nodes along the path to the previous key, which are still being filled in, and the
+        // nodes which are done. The latter are added to the arena in one batch at the end.
+        let mut path = vec![TrieNode::<T>::new(root, None, arity)];
+        let mut done = vec![];
+        let mut prev: Option<Vec<usize>> = None;
+        let mut size = 0;
+
+        for (key, payload) in entries {
+            let seq = grammar.to_indices(key.as_ref())?;
+            if prev.as_ref().is_some_and(|prev| *prev >= seq) {
+                return Err(TrieError::UnsortedInput);
+            }
+
+            // --
+            // Nodes below the common prefix with the previous key won't get any more keys.
+            let common = prev.as_ref().map_or(0, |prev| {
+                prev.iter().zip(&seq).take_while(|(a, b)| a == b).count()
+            });
+
+            while path.len() > common + 1 {
+                Self::close_node(&mut path, &mut done);
+            }
+
+            for idx in &seq[common..] {
+                let id = arena.get_new_id();
+                path.last_mut().unwrap().set_child(*idx, Some(id));
+                path.push(TrieNode::new(id, None, arity));
+            }
+
+            let node = path.last_mut().unwrap();
+            node.payload = Some(payload);
+            node.count += 1;
+
+            size += 1;
+            prev = Some(seq);
+        }
+
+        while path.len() > 1 {
+            Self::close_node(&mut path, &mut done);
+        }
+        done.push(path.pop().unwrap());
+        arena.add_nodes(done).expect("could not add node!");
+
+        Ok(Self {
+            arena,
+            grammar,
+            normalizer: None,
+            aggregate: None,
+            index: None,
+            root,
+            size: AtomicUsize::new(size)
+        })
     }
 
-    /// Inserts 'seq', returning the previous value if it already exists.
-    pub fn insert_or_update(&mut self, seq: &str, t: T) -> Result<Option<T>, String> {
-        self.insert_or_apply(seq, t.clone(), |_| t.clone())
+    /// Constructs a Trie with the given Grammar from its left-child right-sibling form, see
+    /// 'to_lcrs'. The root is the first node of the LCRS tree and has no char, while every other
+    /// node holds the char leading to it along with its payload, if any. Siblings may come in any
+    /// order, and siblings sharing a char are merged.
+    ///
+    /// Returns an error if the LCRS tree isn't valid or doesn't have a single root, if a node other
+    /// than the root has no char, or if 2 nodes holding a payload lead to the same key.
+    pub fn from_lcrs(grammar: Grammar, lcrs: LcrsTree<(Option<char>, Option<T>)>) -> Result<Self, TrieError> {
+        let order = lcrs.pre_order().ok_or(TrieError::InvalidLcrs)?;
+        if lcrs.nodes.first().is_some_and(|root| root.next_sibling.is_some() || root.value.0.is_some()) {
+            return Err(TrieError::InvalidLcrs);
+        }
+
+        // --
+        // Every node is visited after its parent, so the sequence leading to it is known by then.
+        let mut values: Vec<_> = lcrs.nodes.into_iter().map(|node| Some(node.value)).collect();
+        let mut seqs: Vec<Vec<usize>> = vec![vec![]; values.len()];
+        let mut entries = vec![];
+
+        for (node, parent) in order {
+            let (c, payload) = values[node].take().expect("node is visited twice");
+
+            if let Some(parent) = parent {
+                let c = c.ok_or(TrieError::InvalidLcrs)?;
+                let idx = grammar.idx(c).ok_or(TrieError::CharNotInGrammar { ch: c })?;
+
+                let mut seq = seqs[parent].clone();
+                seq.push(idx);
+                seqs[node] = seq;
+            }
+
+            if let Some(payload) = payload {
+                entries.push((seqs[node].clone(), payload));
+            }
+        }
+
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        if entries.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return Err(TrieError::InvalidLcrs);
+        }
+
+        let mut trie = Self::new(grammar);
+        trie.insert_sorted(entries);
+        Ok(trie)
+    }
+
+    /// Moves the last node of 'path' to 'done', counting its keys in its parent.
+    fn close_node(path: &mut Vec<TrieNode<T>>, done: &mut Vec<TrieNode<T>>) {
+        let node = path.pop().unwrap();
+        path.last_mut().unwrap().count += node.count;
+        done.push(node);
+    }
+
+    /// Constructs a new Trie with the given Grammar, which passes every key through 'normalizer'
+    /// before mapping it onto the Grammar. Keys are stored (and returned) in their normalized form.
+    ///
+    /// The normalizer isn't serialized along with the Trie, so it has to be set up again with
+    /// 'set_normalizer' after deserializing.
+    pub fn with_normalizer(grammar: Grammar, normalizer: impl Normalizer + 'static) -> Self {
+        let mut trie = Self::new(grammar);
+        trie.set_normalizer(normalizer);
+        trie
+    }
+
+    /// Replaces the normalizer which keys are passed through. Keys which are already stored aren't
+    /// normalized again.
+    pub fn set_normalizer(&mut self, normalizer: impl Normalizer + 'static) {
+        self.normalizer = Some(Arc::new(normalizer));
+    }
+
+    /// Constructs a new Trie with the given Grammar, which keeps the given Aggregate of the payloads
+    /// in every subtree up to date, see 'top_k_by_prefix'.
+    ///
+    /// Like the normalizer, the aggregate isn't serialized along with the Trie, so it has to be set
+    /// up again with 'set_aggregate' after deserializing.
+    pub fn with_aggregate(grammar: Grammar, aggregate: impl Aggregate<T> + 'static) -> Self {
+        let mut trie = Self::new(grammar);
+        trie.set_aggregate(aggregate);
+        trie
+    }
+
+    /// Replaces the Aggregate kept for every subtree, recomputing it for the whole trie.
+    pub fn set_aggregate(&mut self, aggregate: impl Aggregate<T> + 'static) {
+        self.aggregate = Some(Arc::new(aggregate));
+        self._refresh_subtree(&self.root);
+    }
+
+    /// Constructs a new Trie with the given Grammar, which keeps its keys ordered by the given
+    /// score of their payloads, see 'iter_by_score'.
+    ///
+    /// Like the aggregate, the score index isn't serialized along with the Trie, so it has to be
+    /// set up again with 'set_score_index' after deserializing.
+    pub fn with_score_index<F>(grammar: Grammar, score: F) -> Self
+        where F: Fn(&T) -> f64 + Send + Sync + 'static
+    {
+        let mut trie = Self::new(grammar);
+        trie.set_score_index(score);
+        trie
+    }
+
+    /// Replaces the score by which the keys are ordered, rebuilding the index for the whole trie.
+    /// The index is kept up to date on every change, so iterating by score never sorts the keys.
+    pub fn set_score_index<F>(&mut self, score: F)
+        where F: Fn(&T) -> f64 + Send + Sync + 'static
+    {
+        self.index = Some(ScoreIndex::new(Arc::new(score)));
+        self.rebuild_index();
+    }
+
+    /// Returns the Aggregate of the payloads of all keys starting with 'prefix', or None if the
+    /// trie has no Aggregate or no key starts with 'prefix'.
+    pub fn aggregate_prefix(&self, prefix: &str) -> Option<f64> {
+        self.aggregate.as_ref()?;
+
+        let node_ref = self.preprocess_seq(prefix).ok()
+            .and_then(|seq| self._find_node(&seq, &self.root))
+            .and_then(|id| self.arena.get_node(&id))?;
+        let node = node_ref.read().unwrap();
+
+        (node.count > 0).then_some(node.aggregate)
+    }
+
+    /// Attempts to insert 'seq', returning an error if it already exists or contains a char outside
+    /// of the grammar.
+    pub fn insert(&mut self, seq: &str, t: T) -> Result<(), TrieError> {
+        let seq = self.preprocess_seq(seq)?;
+        let root = self.root;
+        self._insert_apply(&seq[..], &root, t, |_| unreachable!(), OnCollision::ReturnError)?;
+        self.refresh_aggregates(&seq);
+        self.refresh_index(&seq);
+        Ok(())
     }
 
     /// Inserts 'seq', returning the previous value if it already exists.
@@ -109,12 +782,15 @@ impl<T: Default + Debug + Clone + Send + Sync> Trie<T> {
         seq: &str,
         t: T,
         f: F
-    ) -> Result<Option<T>, String>
+    ) -> Result<Option<T>, TrieError>
         where F: Fn(&T) -> T
     {
-        let seq = self.preprocess_seq(seq);
+        let seq = self.preprocess_seq(seq)?;
         let root = self.root;
-        self._insert_apply(&seq[..], &root, t, f, OnCollision::ApplyFn)
+        let prev = self._insert_apply(&seq[..], &root, t, f, OnCollision::ApplyFn)?;
+        self.refresh_aggregates(&seq);
+        self.refresh_index(&seq);
+        Ok(prev)
     }
 
     fn _insert_apply<F>(
@@ -124,17 +800,17 @@ impl<T: Default + Debug + Clone + Send + Sync> Trie<T> {
         t: T,
         f: F,
         on_collision: OnCollision,
-    ) -> Result<Option<T>, String>
+    ) -> Result<Option<T>, TrieError>
         where F: Fn(&T) -> T
     {
-        if seq.len() == 0 {
-            let node_ref = self.arena.get_node(node_id).expect("node doesnt exist!");
+        if seq.is_empty() {
+            let node_ref = self.arena.get_node_mut(node_id).expect("node doesnt exist!");
             let mut node = node_ref.write().unwrap();
 
             return if node.payload.is_some() {
                 match on_collision {
                     OnCollision::ReturnError => {
-                        Err(String::from("key already exists"))
+                        Err(TrieError::KeyExists)
                     }
                     OnCollision::ApplyFn => {
                         let prev = node.payload.take().unwrap();
@@ -145,6 +821,7 @@ impl<T: Default + Debug + Clone + Send + Sync> Trie<T> {
             } else {
                 self.size.fetch_add(1, Ordering::SeqCst);
                 node.payload = Some(t);
+                node.count += 1;
                 Ok(None)
             }
         }
@@ -152,23 +829,23 @@ impl<T: Default + Debug + Clone + Send + Sync> Trie<T> {
         let (idx, remaining) = seq.split_first().unwrap();
 
         let next_id: Id = {
-            let node_ref = self.arena.get_node(node_id).expect("node doesnt exist!");
+            let node_ref = self.arena.get_node_mut(node_id).expect("node doesnt exist!");
 
-            let child_id = node_ref.read().unwrap().children[*idx];
+            let child_id = node_ref.read().unwrap().child(*idx);
 
             match child_id {
                 None => {
                     let next_id = self.arena.get_new_id();
 
                     let child = TrieNode::<T>::new(
-                        next_id.clone(),
+                        next_id,
                         None,
                         node_ref.read().unwrap().arity
                     );
 
                     self.arena.add_node(child).expect("could not add node!");
 
-                    node_ref.write().unwrap().children[*idx] = Some(next_id);
+                    node_ref.write().unwrap().set_child(*idx, Some(next_id));
 
                     next_id
                 }
@@ -177,19 +854,22 @@ impl<T: Default + Debug + Clone + Send + Sync> Trie<T> {
             }
         };
 
-        self._insert_apply(&remaining[..], &next_id, t, f, on_collision)
-    }
+        let result = self._insert_apply(remaining, &next_id, t, f, on_collision);
 
-    pub fn find(&self, seq: &str) -> Option<T> {
-        if self.is_empty() {
-            None
-        } else {
-            self._find(&self.preprocess_seq(seq)[..], &self.root)
+        // A new key was added somewhere below this node, so it needs to be counted here too.
+        if let Ok(None) = result {
+            let node_ref = self.arena.get_node_mut(node_id).expect("node doesnt exist!");
+            node_ref.write().unwrap().count += 1;
         }
+
+        result
     }
 
     pub fn contains(&self, seq: &str) -> bool {
-        self.find(seq).is_some()
+        self.preprocess_seq(seq).ok()
+            .and_then(|seq| self._find_node(&seq, &self.root))
+            .and_then(|id| self.arena.get_node(&id))
+            .is_some_and(|node_ref| node_ref.read().unwrap().is_terminal())
     }
 
     pub fn len(&self) -> usize {
@@ -200,47 +880,424 @@ impl<T: Default + Debug + Clone + Send + Sync> Trie<T> {
         self.len() == 0
     }
 
-    pub fn delete(&mut self, seq: &str) -> Result<Option<T>, String> {
-        if self.is_empty() {
-            Err(String::from("sequence not found because container is empty!"))
-        } else {
-            let seq = self.preprocess_seq(seq);
-            let root = self.root;
-            self._delete(&seq[..], &root).and_then(|(_, x)| Ok(x))
+    /// Adds the chars in 'chars' to the trie's grammar, so that keys containing them can be stored
+    /// from now on. Chars which are already part of the grammar are ignored.
+    ///
+    /// The grammar stays in ascending order, so every node's children are moved over to their new
+    /// grammar indices, in O(number of nodes).
+    pub fn extend_grammar(&mut self, chars: &str) {
+        let grammar = self.grammar.with_chars(chars);
+        let arity = grammar.seq().len();
+        if arity == self.grammar.seq().len() {
+            return;
         }
-    }
 
-    fn _delete(&mut self, seq: &[usize], node_id: &Id) -> Result<(bool, Option<T>), String> {
-        let node_ref = self.arena.get_node(node_id).unwrap();
-
-        match seq.split_first() {
-            None => {
-                let mut node = node_ref.write().unwrap();
+        let new_idx: Vec<usize> = self.grammar.seq().iter()
+            .map(|c| grammar.idx(*c).expect("char went missing from grammar"))
+            .collect();
 
-                if !node.is_terminal() {
-                    Err(String::from("sequence not found!"))
-                } else {
-                    let prev_result = node.payload.take();
+        let mut stack = vec![self.root];
+        while let Some(node_id) = stack.pop() {
+            let node_ref = self.arena.get_node_mut(&node_id).expect("node doesnt exist!");
+            let mut node = node_ref.write().unwrap();
 
-                    self.size.fetch_sub(1, Ordering::SeqCst);
-                    if node.id != self.root && node.can_delete() {
-                        self.arena.delete_node(&node.id).expect("could not delete node");
-                        Ok((true, prev_result))
-                    } else {
-                        Ok((false, prev_result))
-                    }
-                }
+            let mut children = Children::default();
+            for (idx, child) in node.children.iter() {
+                children.insert(new_idx[idx], child, arity);
+                stack.push(child);
             }
 
-            // --
-            // Otherwise, we'll need to traverse deeper in the tree by recursively calling
-            // _find(...) on the correct child.
-            Some((next_idx, remainder)) => {
-                let child_id = node_ref.read().unwrap().children[*next_idx];
+            node.children = children;
+            node.arity = arity;
+        }
+
+        self.grammar = grammar;
+    }
+
+    /// Returns the number of nodes in the Trie along with the memory they use, including the links
+    /// to their children. Every node is visited to add those up.
+    pub fn memory_stats(&self) -> ArenaStats {
+        let mut stats = self.arena.stats();
+
+        let mut stack = vec![self.root];
+        while let Some(node_id) = stack.pop() {
+            let node_ref = self.arena.get_node(&node_id).expect("node doesnt exist!");
+            let node = node_ref.read().unwrap();
+
+            stats.approx_bytes += node.children.heap_bytes();
+            stack.extend(node.children.ids());
+        }
+
+        stats
+    }
+
+    /// Returns the number of nodes at every depth, the lengths of the chains of nodes with a single
+    /// child, and the memory used by the Trie. This is mostly useful to judge how much a radix trie
+    /// would save for a given set of keys.
+    pub fn debug_structure(&self) -> TrieStructure {
+        let mut nodes_per_depth = vec![];
+        let mut chain_lengths = vec![];
+
+        // Every node is visited along with its depth and the length of the chain right above it.
+        let mut stack = vec![(self.root, 0, 0)];
+        while let Some((node_id, depth, chain)) = stack.pop() {
+            let node_ref = self.arena.get_node(&node_id).expect("node doesnt exist!");
+            let node = node_ref.read().unwrap();
+
+            if nodes_per_depth.len() <= depth {
+                nodes_per_depth.push(0);
+            }
+            nodes_per_depth[depth] += 1;
+
+            let chain = match depth > 0 && !node.is_terminal() && node.children.len() == 1 {
+                true => chain + 1,
+                false => {
+                    if chain > 0 {
+                        if chain_lengths.len() <= chain {
+                            chain_lengths.resize(chain + 1, 0);
+                        }
+                        chain_lengths[chain] += 1;
+                    }
+                    0
+                }
+            };
+
+            stack.extend(node.children.ids().map(|id| (id, depth + 1, chain)));
+        }
+
+        TrieStructure {
+            nodes_per_depth,
+            chain_lengths,
+            memory: self.memory_stats()
+        }
+    }
+
+    /// Releases as much of the memory left behind by deleted keys as possible.
+    pub fn shrink_to_fit(&mut self) {
+        self.arena.shrink_to_fit();
+    }
+
+    /// Moves the nodes of the Trie into as few arena slots as possible and releases the rest.
+    /// Unlike 'shrink_to_fit', this reclaims every slot left behind by deleted keys, which is
+    /// worthwhile for long-lived tries which see lots of churn. Every node is visited to update the
+    /// links to its children.
+    pub fn compact(&mut self) {
+        let mut remapped = HashMap::with_capacity(self.arena.len());
+        self.arena.compact(|old, new| {
+            remapped.insert(old, new);
+        });
+
+        let ids: Vec<Id> = self.arena.iter().map(|(id, _)| id).collect();
+        for id in ids {
+            let node_ref = self.arena.get_node_mut(&id).expect("node doesnt exist!");
+            let mut node = node_ref.write().unwrap();
+            node.id = id;
+            node.children.remap(|id| remapped[&id]);
+        }
+
+        self.root = remapped[&self.root];
+    }
+
+    /// Returns the number of keys starting with 'prefix', in O(prefix length).
+    pub fn count_prefix(&self, prefix: &str) -> usize {
+        self.preprocess_seq(prefix).ok()
+            .and_then(|seq| self._find_node(&seq, &self.root))
+            .and_then(|id| self.arena.get_node(&id))
+            .map_or(0, |node_ref| node_ref.read().unwrap().count)
+    }
+
+    /// Returns the shortest prefix of 'seq' which no other key starts with, like an abbreviated
+    /// git hash. Returns None if 'seq' isn't a key, and 'seq' itself if it is a prefix of other
+    /// keys, since only the exact key can tell it apart from them.
+    pub fn shortest_unique_prefix(&self, seq: &str) -> Option<String> {
+        let seq = self.preprocess_seq(seq).ok()?;
+
+        // --
+        // The prefix ends at the first node along the path whose subtree holds a single key, which
+        // has to be 'seq' once it turns out to be a key.
+        let mut unique_len = None;
+        let mut node_id = self.root;
+
+        for depth in 0..=seq.len() {
+            let node_ref = self.arena.get_node(&node_id).expect("node doesnt exist!");
+            let node = node_ref.read().unwrap();
+
+            if node.count == 1 && unique_len.is_none() {
+                unique_len = Some(depth);
+            }
+
+            match seq.get(depth) {
+                None if node.is_terminal() => break,
+                None => return None,
+                Some(idx) => node_id = node.child(*idx)?
+            }
+        }
+
+        Some(self.to_key(&seq[..unique_len.unwrap_or(seq.len())]))
+    }
+
+    /// Returns a writer which buffers keys and inserts them in sorted batches, for loading lots
+    /// of keys at once, see 'TrieWriter'.
+    pub fn writer(&mut self) -> TrieWriter<'_, T> {
+        TrieWriter::new(self)
+    }
+
+    /// Returns the entry for 'seq', which allows for inserting or updating it in place, or an error
+    /// if 'seq' contains a char outside of the grammar.
+    pub fn entry(&mut self, seq: &str) -> Result<Entry<'_, T>, TrieError> {
+        let seq = self.preprocess_seq(seq)?;
+
+        let node_id = self._find_node(&seq, &self.root)
+            .filter(|id| self.arena.get_node(id).is_some_and(|node_ref| node_ref.read().unwrap().is_terminal()));
+
+        Ok(match node_id {
+            None => Entry::Vacant(VacantEntry { trie: self, seq }),
+            Some(node_id) => Entry::Occupied(OccupiedEntry { trie: self, seq, node_id })
+        })
+    }
+
+    /// Turns the trie into an automaton which finds all of its keys in a haystack at once. Haystacks
+    /// aren't normalized, since that would shift the positions of the matches.
+    pub fn into_matcher(self) -> AhoCorasick<T> {
+        AhoCorasick::new(self.arena, self.grammar, self.root)
+    }
+
+    /// Compiles the trie into a compact, read-only FrozenTrie which is faster to search. The
+    /// FrozenTrie normalizes keys just like this trie does.
+    pub fn freeze(self) -> FrozenTrie<T> {
+        FrozenTrie::new(self.arena, self.grammar, self.normalizer, self.root)
+    }
+
+    pub fn delete(&mut self, seq: &str) -> Result<Option<T>, TrieError> {
+        if self.is_empty() {
+            Err(TrieError::KeyNotFound)
+        } else {
+            let seq = self.preprocess_seq(seq)?;
+            let root = self.root;
+            let (_, payload) = self._delete(&seq[..], &root)?;
+            self.refresh_aggregates(&seq);
+            self.refresh_index(&seq);
+            Ok(payload)
+        }
+    }
+
+    /// Removes every key starting with 'prefix' (including 'prefix' itself), returning the number
+    /// of keys removed.
+    ///
+    /// The subtree holding the keys is detached and its nodes freed in one go, along with any
+    /// ancestors which no longer lead to a key.
+    pub fn delete_prefix(&mut self, prefix: &str) -> usize {
+        // No key can start with a char outside of the grammar.
+        let seq = match self.preprocess_seq(prefix) {
+            Ok(seq) => seq,
+            Err(_) => return 0
+        };
+
+        // --
+        // Find the path of nodes leading to the subtree, bailing out if it holds no keys.
+        let mut path = vec![self.root];
+        for idx in &seq {
+            let node_ref = self.arena.get_node(path.last().unwrap()).expect("node doesnt exist!");
+            let child_id = node_ref.read().unwrap().child(*idx);
+
+            match child_id {
+                None => return 0,
+                Some(id) => path.push(id)
+            }
+        }
+
+        let subtree = *path.last().unwrap();
+        let removed = self.arena.get_node(&subtree).expect("node doesnt exist!").read().unwrap().count;
+        if removed == 0 {
+            return 0;
+        }
+
+        // --
+        // Free the subtree, keeping the root (which is never deleted) if the prefix is empty.
+        let mut stack = vec![subtree];
+        while let Some(id) = stack.pop() {
+            {
+                let node_ref = self.arena.get_node(&id).expect("node doesnt exist!");
+                let node = node_ref.read().unwrap();
+                stack.extend(node.children.ids());
+            }
+
+            if id != self.root {
+                self.arena.delete_node(&id).expect("could not delete node");
+            }
+        }
+
+        if seq.is_empty() {
+            let root_ref = self.arena.get_node_mut(&self.root).expect("node doesnt exist!");
+            let mut root = root_ref.write().unwrap();
+            *root = TrieNode::new(self.root, None, root.arity);
+        } else {
+            // --
+            // Detach the subtree, then walk back up pruning ancestors which now lead nowhere.
+            let mut detached = true;
+            for (depth, id) in path[..seq.len()].iter().enumerate().rev() {
+                let node_ref = self.arena.get_node_mut(id).expect("node doesnt exist!");
+                let mut node = node_ref.write().unwrap();
+                node.count -= removed;
+
+                if detached {
+                    node.set_child(seq[depth], None);
+                }
+
+                detached = *id != self.root && node.can_delete();
+                if detached {
+                    self.arena.delete_node(id).expect("could not delete node");
+                }
+            }
+        }
+
+        self.refresh_aggregates(&seq);
+        if let Some(index) = &self.index {
+            index.remove_prefix(&self.to_key(&seq));
+        }
+        self.size.fetch_sub(removed, Ordering::SeqCst);
+        removed
+    }
+
+    /// Moves every key of 'other' into this trie, where the payloads of keys stored in both are
+    /// combined with 'resolve(ours, theirs)'. Both tries must share the same Grammar, otherwise
+    /// neither is changed and an error is returned.
+    ///
+    /// The tries are merged node by node, so the shared prefixes are only walked once and subtrees
+    /// which only 'other' holds are moved over as a whole, without looking up any of their keys.
+    pub fn merge<F: Fn(T, T) -> T>(&mut self, other: Trie<T>, resolve: F) -> Result<(), TrieError> {
+        if self.grammar != other.grammar {
+            return Err(TrieError::GrammarMismatch);
+        }
+
+        let root = self.root;
+        self._merge(&root, &other.arena, &other.root, &resolve);
+
+        let size = self.arena.get_node(&root).expect("node doesnt exist!").read().unwrap().count;
+        self.size.store(size, Ordering::SeqCst);
+        self._refresh_subtree(&root);
+        self.rebuild_index();
+        Ok(())
+    }
+
+    /// Inserts the given sequences, which must be strictly ascending, replacing the payloads of
+    /// the ones which already exist. See 'TrieWriter'.
+    ///
+    /// Consecutive keys share the nodes along their common prefix, so every node on the way is
+    /// looked up once per batch rather than once per key, and the counts and Aggregates of the
+    /// nodes are only written back once the batch has moved past them.
+    pub(crate) fn insert_sorted(&mut self, entries: impl IntoIterator<Item = (Vec<usize>, T)>) {
+        let root_ref = self.arena.get_node_mut(&self.root).expect("node doesnt exist!");
+
+        // The nodes along the path to the previous key, along with the number of keys added below
+        // each of them so far.
+        let mut path = vec![(root_ref, 0)];
+        let mut prev: Option<Vec<usize>> = None;
+        let mut added = 0;
+
+        for (seq, payload) in entries {
+            debug_assert!(prev.as_ref().is_none_or(|prev| *prev < seq), "keys must be strictly ascending");
+
+            let common = prev.as_ref().map_or(0, |prev| {
+                prev.iter().zip(&seq).take_while(|(a, b)| a == b).count()
+            });
+
+            while path.len() > common + 1 {
+                self.close_sorted(&mut path);
+            }
+
+            for idx in &seq[common..] {
+                let (node_ref, _) = path.last().unwrap();
+                let (child_id, arity) = {
+                    let node = node_ref.read().unwrap();
+                    (node.child(*idx), node.arity)
+                };
+
+                let child_id = match child_id {
+                    Some(id) => id,
+                    None => {
+                        let id = self.arena.get_new_id();
+                        self.arena.add_node(TrieNode::new(id, None, arity)).expect("could not add node!");
+                        node_ref.write().unwrap().set_child(*idx, Some(id));
+                        id
+                    }
+                };
+
+                path.push((self.arena.get_node_mut(&child_id).expect("node doesnt exist!"), 0));
+            }
+
+            let (node_ref, count) = path.last_mut().unwrap();
+            let mut node = node_ref.write().unwrap();
+            if node.payload.replace(payload).is_none() {
+                *count += 1;
+                added += 1;
+            }
+
+            if let Some(index) = &self.index {
+                index.update(self.to_key(&seq), node.payload.as_ref());
+            }
+
+            drop(node);
+            prev = Some(seq);
+        }
+
+        while !path.is_empty() {
+            self.close_sorted(&mut path);
+        }
+        self.size.fetch_add(added, Ordering::SeqCst);
+    }
+
+    /// Writes back the count and Aggregate of the last node of 'path', whose subtree is done.
+    fn close_sorted(&self, path: &mut Vec<(SharedRef<TrieNode<T>>, usize)>) {
+        let (node_ref, added) = path.pop().unwrap();
+
+        let node_id = {
+            let mut node = node_ref.write().unwrap();
+            node.count += added;
+            node.id
+        };
+
+        if let Some(aggregate) = &self.aggregate {
+            self.refresh_node(aggregate.as_ref(), &node_id);
+        }
+
+        if let Some((_, parent_added)) = path.last_mut() {
+            *parent_added += added;
+        }
+    }
+
+    fn _delete(&mut self, seq: &[usize], node_id: &Id) -> Result<(bool, Option<T>), TrieError> {
+        let node_ref = self.arena.get_node_mut(node_id).unwrap();
+
+        match seq.split_first() {
+            None => {
+                let mut node = node_ref.write().unwrap();
+
+                if !node.is_terminal() {
+                    Err(TrieError::KeyNotFound)
+                } else {
+                    let prev_result = node.payload.take();
+                    node.count -= 1;
+
+                    self.size.fetch_sub(1, Ordering::SeqCst);
+                    if node.id != self.root && node.can_delete() {
+                        self.arena.delete_node(&node.id).expect("could not delete node");
+                        Ok((true, prev_result))
+                    } else {
+                        Ok((false, prev_result))
+                    }
+                }
+            }
+
+            // --
+            // Otherwise, we'll need to traverse deeper in the tree by recursively calling
+            // _find(...) on the correct child.
+            Some((next_idx, remainder)) => {
+                let child_id = node_ref.read().unwrap().child(*next_idx);
 
                 match child_id {
                     None => {
-                        Err(String::from("sequence not found!"))
+                        Err(TrieError::KeyNotFound)
                     },
 
                     Some(id) => {
@@ -249,11 +1306,13 @@ impl<T: Default + Debug + Clone + Send + Sync> Trie<T> {
 
                             Ok((child_deleted, payload)) => {
                                 let mut node = node_ref.write().unwrap();
+                                node.count -= 1;
+
                                 if child_deleted {
-                                    node.children[id] = None;
+                                    node.set_child(*next_idx, None);
                                 }
 
-                                if node.can_delete() {
+                                if node.id != self.root && node.can_delete() {
                                     self.arena.delete_node(node_id).expect("could not delete node");
                                     Ok((true, payload))
                                 } else {
@@ -267,10 +1326,886 @@ impl<T: Default + Debug + Clone + Send + Sync> Trie<T> {
         }
     }
 
-    fn preprocess_seq(&self, seq: &str) -> Vec<usize> {
-        match self.grammar.to_indices(seq) {
-            Ok(indices) => indices,
-            Err(msg) => panic!("{}", msg)
+    /// Passes 'seq' through the normalizer, if there is one.
+    fn normalize<'a>(&self, seq: &'a str) -> Cow<'a, str> {
+        match &self.normalizer {
+            None => Cow::Borrowed(seq),
+            Some(normalizer) => Cow::Owned(normalizer.normalize(seq))
+        }
+    }
+
+    pub(crate) fn preprocess_seq(&self, seq: &str) -> Result<Vec<usize>, TrieError> {
+        self.grammar.to_indices(&self.normalize(seq))
+    }
+
+    /// Returns the key spelled out by the given grammar indices.
+    fn to_key(&self, seq: &[usize]) -> String {
+        let chars = self.grammar.seq();
+        seq.iter().map(|idx| chars[*idx]).collect()
+    }
+
+    /// Records the current payload of 'seq' in the score index, if the trie has one.
+    fn refresh_index(&self, seq: &[usize]) {
+        let index = match &self.index {
+            None => return,
+            Some(index) => index
+        };
+
+        let node_ref = self._find_node(seq, &self.root).and_then(|id| self.arena.get_node(&id));
+        match node_ref {
+            None => index.update(self.to_key(seq), None),
+            Some(node_ref) => index.update(self.to_key(seq), node_ref.read().unwrap().payload.as_ref())
+        }
+    }
+
+    /// Refills the score index from every key in the trie, if the trie has one.
+    fn rebuild_index(&self) {
+        let index = match &self.index {
+            None => return,
+            Some(index) => index
+        };
+
+        index.clear();
+
+        let chars = self.grammar.seq();
+        let mut stack = vec![(self.root, String::new())];
+        while let Some((node_id, key)) = stack.pop() {
+            let node_ref = self.arena.get_node(&node_id).expect("node doesnt exist!");
+            let node = node_ref.read().unwrap();
+
+            if let Some(payload) = &node.payload {
+                index.update(key.clone(), Some(payload));
+            }
+
+            for (idx, child_id) in node.children.iter() {
+                let mut child_key = key.clone();
+                child_key.push(chars[idx]);
+                stack.push((child_id, child_key));
+            }
+        }
+    }
+
+    /// Recomputes the Aggregate of every node along 'seq' which is still in the trie, from the
+    /// bottom up. Nodes off the path aren't affected by inserting or deleting 'seq'.
+    fn refresh_aggregates(&self, seq: &[usize]) {
+        let aggregate = match &self.aggregate {
+            None => return,
+            Some(aggregate) => aggregate
+        };
+
+        let mut path = vec![self.root];
+        for idx in seq {
+            let node_ref = self.arena.get_node(path.last().unwrap()).expect("node doesnt exist!");
+            let child_id = node_ref.read().unwrap().child(*idx);
+
+            match child_id {
+                None => break,
+                Some(id) => path.push(id)
+            }
+        }
+
+        for id in path.iter().rev() {
+            self.refresh_node(aggregate.as_ref(), id);
+        }
+    }
+
+    /// Recomputes the Aggregate of every node in the subtree rooted at the given node.
+    fn _refresh_subtree(&self, node_id: &Id) {
+        let aggregate = match &self.aggregate {
+            None => return,
+            Some(aggregate) => aggregate
+        };
+
+        let children: Vec<Id> = {
+            let node_ref = self.arena.get_node(node_id).expect("node doesnt exist!");
+            let node = node_ref.read().unwrap();
+            node.children.ids().collect()
+        };
+
+        for child_id in &children {
+            self._refresh_subtree(child_id);
+        }
+
+        self.refresh_node(aggregate.as_ref(), node_id);
+    }
+
+    /// Recomputes the Aggregate of the given node from its payload and the Aggregates of its
+    /// children, which must be up to date.
+    fn refresh_node(&self, aggregate: &dyn Aggregate<T>, node_id: &Id) {
+        let node_ref = self.arena.get_node_mut(node_id).expect("node doesnt exist!");
+        let mut node = node_ref.write().unwrap();
+
+        let mut value = node.payload.as_ref().map_or(aggregate.identity(), |payload| aggregate.score(payload));
+        for child_id in node.children.ids() {
+            let child_ref = self.arena.get_node(&child_id).expect("node doesnt exist!");
+            value = aggregate.combine(value, child_ref.read().unwrap().aggregate);
+        }
+
+        node.aggregate = value;
+    }
+
+    /// Merges the subtree of 'other' rooted at 'other_id' into the subtree rooted at the given node,
+    /// taking the payloads out of 'other' along the way.
+    fn _merge<F: Fn(T, T) -> T>(
+        &mut self,
+        node_id: &Id,
+        other: &GenerationalArena<TrieNode<T>>,
+        other_id: &Id,
+        resolve: &F
+    ) {
+        let (theirs, other_children) = {
+            let other_ref = other.get_node_mut(other_id).expect("node doesnt exist!");
+            let mut other_node = other_ref.write().unwrap();
+            (other_node.payload.take(), other_node.children.clone())
+        };
+
+        let node_ref = self.arena.get_node_mut(node_id).expect("node doesnt exist!");
+        if let Some(theirs) = theirs {
+            let mut node = node_ref.write().unwrap();
+            node.payload = Some(match node.payload.take() {
+                None => theirs,
+                Some(ours) => resolve(ours, theirs)
+            });
+        }
+
+        for (idx, other_child) in other_children.iter() {
+            let child_id = node_ref.read().unwrap().child(idx);
+            match child_id {
+                Some(child_id) => self._merge(&child_id, other, &other_child, resolve),
+                None => {
+                    let child_id = self._graft(other, &other_child);
+                    node_ref.write().unwrap().set_child(idx, Some(child_id));
+                }
+            }
+        }
+
+        // --
+        // Recount the keys, now that the children are up to date.
+        let mut node = node_ref.write().unwrap();
+        node.count = node.payload.is_some() as usize;
+        for child_id in node.children.clone().ids() {
+            node.count += self.arena.get_node(&child_id).expect("node doesnt exist!").read().unwrap().count;
+        }
+    }
+
+    /// Moves the subtree of 'other' rooted at 'other_id' into this trie's arena, returning the id of
+    /// its root here.
+    fn _graft(&mut self, other: &GenerationalArena<TrieNode<T>>, other_id: &Id) -> Id {
+        let other_ref = other.get_node_mut(other_id).expect("node doesnt exist!");
+        let mut other_node = other_ref.write().unwrap();
+
+        let id = self.arena.get_new_id();
+        let mut node = TrieNode::new(id, other_node.payload.take(), other_node.arity);
+        node.count = other_node.count;
+
+        for (idx, other_child) in other_node.children.iter() {
+            node.set_child(idx, Some(self._graft(other, &other_child)));
+        }
+
+        self.arena.add_node(node).expect("could not add node!");
+        id
+    }
+
+    /// Returns the id of the node reached by following 'seq' from the given node, if any.
+    fn _find_node(&self, seq: &[usize], node_id: &Id) -> Option<Id> {
+        match seq.split_first() {
+            None => Some(*node_id),
+
+            Some((next_idx, remainder)) => {
+                let node_ref = self.arena.get_node(node_id)?;
+                let child_id = node_ref.read().unwrap().child(*next_idx);
+                self._find_node(remainder, &child_id?)
+            }
+        }
+    }
+
+}
+
+impl<T: Clone + Send + Sync> Trie<T> {
+
+    /// Takes a snapshot of the trie, which it can be rolled back to with 'restore', and which can
+    /// be searched while the trie keeps changing.
+    ///
+    /// The snapshot shares its nodes with the trie, which only copies a node once it is touched
+    /// again. This makes checkpointing a trie, speculatively changing it and rolling it back much
+    /// cheaper than cloning it.
+    pub fn snapshot(&mut self) -> TrieSnapshot<T> {
+        self.read_snapshot()
+    }
+
+    /// Takes a snapshot of the trie for readers, see 'snapshot'. This doesn't visit any nodes, so
+    /// it can be taken after every write to hand readers an up to date view of the trie.
+    pub fn read_snapshot(&self) -> TrieSnapshot<T> {
+        TrieSnapshot {
+            arena: self.arena.snapshot(),
+            grammar: self.grammar.clone(),
+            normalizer: self.normalizer.clone(),
+            root: self.root,
+            size: self.len()
+        }
+    }
+
+    /// Returns the trie in left-child right-sibling form, with the nodes in pre-order and siblings
+    /// in grammar order. The root comes first and has no char, while every other node holds the char
+    /// leading to it along with its payload, if any. See 'from_lcrs'.
+    pub fn to_lcrs(&self) -> LcrsTree<(Option<char>, Option<T>)> {
+        let mut lcrs = LcrsTree::default();
+        self._to_lcrs(&self.root, None, &self.grammar.seq(), &mut lcrs);
+        lcrs
+    }
+
+    /// Rolls the trie back to the given snapshot, which must have been taken from this trie. The
+    /// snapshot stays valid, so the trie can be rolled back to it any number of times.
+    pub fn restore(&mut self, snapshot: &TrieSnapshot<T>) {
+        self.arena.restore(&snapshot.arena);
+        self.grammar = snapshot.grammar.clone();
+        self.normalizer = snapshot.normalizer.clone();
+        self.root = snapshot.root;
+        self.size.store(snapshot.size, Ordering::SeqCst);
+        self.rebuild_index();
+    }
+
+    /// Inserts 'seq', returning the previous value if it already exists.
+    pub fn insert_or_update(&mut self, seq: &str, t: T) -> Result<Option<T>, TrieError> {
+        self.insert_or_apply(seq, t.clone(), |_| t.clone())
+    }
+
+    /// Returns a new trie holding the keys of both tries, where the payloads of keys stored in both
+    /// are combined with 'resolve(ours, theirs)'. Both tries must share the same Grammar, otherwise
+    /// an error is returned.
+    ///
+    /// The new trie is built node by node, and takes over this trie's normalizer and Aggregate.
+    pub fn union<F: Fn(T, T) -> T>(&self, other: &Self, resolve: F) -> Result<Self, TrieError> {
+        self.combine(other, SetOp::Union, &resolve)
+    }
+
+    /// Returns a new trie holding the keys stored in both tries, with their payloads combined with
+    /// 'resolve(ours, theirs)'. Both tries must share the same Grammar.
+    ///
+    /// Subtrees which only one of the tries holds are skipped entirely.
+    pub fn intersection<F: Fn(T, T) -> T>(&self, other: &Self, resolve: F) -> Result<Self, TrieError> {
+        self.combine(other, SetOp::Intersection, &resolve)
+    }
+
+    /// Returns a new trie holding the keys of this trie which aren't stored in 'other', with their
+    /// payloads. Both tries must share the same Grammar.
+    pub fn difference(&self, other: &Self) -> Result<Self, TrieError> {
+        self.combine(other, SetOp::Difference, &|ours, _| ours)
+    }
+
+    /// Returns the payload of 'seq', if it exists. A key containing a char outside of the grammar
+    /// can't exist, so it isn't found.
+    pub fn find(&self, seq: &str) -> Option<T> {
+        self.try_find(seq).unwrap_or(None)
+    }
+
+    /// Same as 'find' for every given key, with the lookups spread across rayon's thread pool. The
+    /// results are in the order of the keys. This requires the "rayon" feature.
+    #[cfg(feature = "rayon")]
+    pub fn par_find_many(&self, seqs: &[&str]) -> Vec<Option<T>> {
+        seqs.par_iter().map(|seq| self.find(seq)).collect()
+    }
+
+    /// Same as 'find', but returns an error if 'seq' contains a char outside of the grammar.
+    pub fn try_find(&self, seq: &str) -> Result<Option<T>, TrieError> {
+        let seq = self.preprocess_seq(seq)?;
+
+        if self.is_empty() {
+            Ok(None)
+        } else {
+            Ok(self._find(&seq[..], &self.root))
+        }
+    }
+
+    /// Returns the 'k' keys starting with 'prefix' whose payloads score highest according to the
+    /// trie's Aggregate, along with their payloads, best first. Keys with the same score are
+    /// sorted by key.
+    ///
+    /// Subtrees are visited in order of their Aggregates, so those which can't hold any of the 'k'
+    /// best keys are never visited.
+    ///
+    /// Panics if the trie has no Aggregate, see 'with_aggregate'.
+    pub fn top_k_by_prefix(&self, prefix: &str, k: usize) -> Vec<(String, T)> {
+        let aggregate = self.aggregate.as_ref().expect("trie has no aggregate");
+        let mut result = vec![];
+
+        let (prefix, node_id) = match self.preprocess_seq(prefix).ok()
+            .and_then(|prefix| self._find_node(&prefix, &self.root).map(|node_id| (prefix, node_id)))
+        {
+            None => return result,
+            Some(found) => found
+        };
+
+        let chars = self.grammar.seq();
+        let mut heap = BinaryHeap::new();
+        {
+            let node_ref = self.arena.get_node(&node_id).expect("node doesnt exist!");
+            let node = node_ref.read().unwrap();
+
+            if node.count > 0 {
+                heap.push(Ranked { score: node.aggregate, key: self.to_key(&prefix), candidate: Candidate::Node(node_id) });
+            }
+        }
+
+        // --
+        // Best-first search: a key is only popped once no subtree left on the heap can hold a key
+        // ranking ahead of it.
+        while result.len() < k {
+            let Ranked { key, candidate, .. } = match heap.pop() {
+                None => break,
+                Some(ranked) => ranked
+            };
+
+            let node_id = match candidate {
+                Candidate::Key(payload) => {
+                    result.push((key, payload));
+                    continue;
+                }
+                Candidate::Node(node_id) => node_id
+            };
+
+            let node_ref = self.arena.get_node(&node_id).expect("node doesnt exist!");
+            let node = node_ref.read().unwrap();
+
+            if let Some(payload) = &node.payload {
+                heap.push(Ranked { score: aggregate.score(payload), key: key.clone(), candidate: Candidate::Key(payload.clone()) });
+            }
+
+            for (idx, child_id) in node.children.iter() {
+                let child_ref = self.arena.get_node(&child_id).expect("node doesnt exist!");
+                let score = child_ref.read().unwrap().aggregate;
+
+                let mut child_key = key.clone();
+                child_key.push(chars[idx]);
+                heap.push(Ranked { score, key: child_key, candidate: Candidate::Node(child_id) });
+            }
+        }
+
+        result
+    }
+
+    /// Returns a key picked uniformly at random along with its payload, or None if the trie is
+    /// empty.
+    ///
+    /// The key is found by descending from the root, picking each child with a probability
+    /// proportional to the number of keys below it, so nothing besides the key is visited.
+    pub fn sample(&self, rng: &mut impl Rng) -> Option<(String, T)> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let chars = self.grammar.seq();
+        let mut key = String::new();
+        let mut node_id = self.root;
+
+        loop {
+            let node_ref = self.arena.get_node(&node_id).expect("node doesnt exist!");
+            let node = node_ref.read().unwrap();
+
+            // --
+            // The key of this node comes first, followed by the keys below each child in turn.
+            let mut pick = rng.below(node.count as u64) as usize;
+            if let Some(payload) = &node.payload {
+                if pick == 0 {
+                    return Some((key, payload.clone()));
+                }
+                pick -= 1;
+            }
+
+            for (idx, child_id) in node.children.iter() {
+                let count = self.arena.get_node(&child_id).expect("node doesnt exist!").read().unwrap().count;
+                if pick < count {
+                    key.push(chars[idx]);
+                    node_id = child_id;
+                    break;
+                }
+                pick -= count;
+            }
+        }
+    }
+
+    /// Returns all keys along with their payloads, in grammar order.
+    pub fn iter(&self) -> Iter<T> {
+        self.iter_prefix("")
+    }
+
+    /// Returns all keys along with their payloads, by ascending score of the payloads. Keys with
+    /// the same score come in key order. Iterate in reverse for the highest scores first.
+    ///
+    /// Panics if the trie has no score index, see 'set_score_index'.
+    pub fn iter_by_score(&self) -> ScoreIter<'_, T> {
+        assert!(self.index.is_some(), "trie has no score index");
+        ScoreIter { trie: self, front: Bound::Unbounded, back: Bound::Unbounded }
+    }
+
+    /// Returns all keys along with their payloads, in grammar order. This is the order which
+    /// 'from_sorted_iter' expects, so the result can be used to rebuild the trie.
+    pub fn to_sorted_vec(&self) -> Vec<(String, T)> {
+        self.iter().collect()
+    }
+
+    /// Returns all keys, in grammar order.
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = String> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    /// Returns the payloads of all keys, in grammar order of their keys.
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = T> {
+        self.iter().map(|(_, payload)| payload)
+    }
+
+    /// Returns all keys starting with 'prefix' along with their payloads, in grammar order.
+    pub fn iter_prefix(&self, prefix: &str) -> Iter<T> {
+        let mut result = vec![];
+
+        if let Some((prefix, node_id)) = self.preprocess_seq(prefix).ok()
+            .and_then(|prefix| self._find_node(&prefix, &self.root).map(|node_id| (prefix, node_id)))
+        {
+            let mut key = self.to_key(&prefix);
+            self._collect(&node_id, &self.grammar.seq(), &mut key, &mut result);
+        }
+
+        Iter { entries: result.into_iter() }
+    }
+
+    /// Returns all keys whose payloads satisfy 'pred' along with their payloads, in grammar order.
+    pub fn iter_matching<F: Fn(&T) -> bool>(&self, pred: F) -> Iter<T> {
+        self.iter_matching_with(pred, |_, _| true)
+    }
+
+    /// Same as 'iter_matching', but subtrees are only visited if 'descend' returns true when called
+    /// with the key leading to the subtree and the number of keys stored in it. This allows whole
+    /// subtrees to be pruned without visiting their payloads, e.g. skipping the ones holding too
+    /// few keys to be of interest.
+    pub fn iter_matching_with<F, G>(&self, pred: F, descend: G) -> Iter<T>
+    where
+        F: Fn(&T) -> bool,
+        G: Fn(&str, usize) -> bool
+    {
+        let mut result = vec![];
+        self._collect_matching(&self.root, &self.grammar.seq(), &mut String::new(), &pred, &descend, &mut result);
+
+        Iter { entries: result.into_iter() }
+    }
+
+    /// Returns the longest key which is a prefix of 'seq', along with its payload.
+    pub fn longest_prefix(&self, seq: &str) -> Option<(String, T)> {
+        // Keys can only match up to the first char outside of the grammar.
+        let indices: Vec<usize> = self.normalize(seq).chars()
+            .map_while(|c| self.grammar.idx(c))
+            .collect();
+        let chars = self.grammar.seq();
+
+        let mut best = None;
+        let mut node_id = Some(self.root);
+
+        for depth in 0..=indices.len() {
+            let node_ref = match node_id.and_then(|id| self.arena.get_node(&id)) {
+                None => break,
+                Some(node_ref) => node_ref
+            };
+            let node = node_ref.read().unwrap();
+
+            if let Some(payload) = &node.payload {
+                best = Some((depth, payload.clone()));
+            }
+
+            node_id = indices.get(depth).and_then(|idx| node.child(*idx));
+        }
+
+        best.map(|(depth, payload)| {
+            (indices[..depth].iter().map(|idx| chars[*idx]).collect(), payload)
+        })
+    }
+
+    /// Returns all keys within 'max_distance' edits (Levenshtein distance) of 'seq', along with
+    /// their payloads and distances, in grammar order.
+    pub fn find_fuzzy(&self, seq: &str, max_distance: usize) -> Vec<(String, T, usize)> {
+        // A char outside of the grammar never matches the char of a key, so it always costs an edit.
+        let seq: Vec<usize> = self.normalize(seq).chars()
+            .map(|c| self.grammar.idx(c).unwrap_or(usize::MAX))
+            .collect();
+        let chars = self.grammar.seq();
+
+        // The first row of the DP table is the distance from the empty key to each prefix of 'seq'.
+        let row: Vec<usize> = (0..=seq.len()).collect();
+
+        let mut result = vec![];
+        self._find_fuzzy(&self.root, &seq, &row, max_distance, &chars, &mut String::new(), &mut result);
+        result
+    }
+
+    /// Returns up to 'limit' keys which the given word is most likely a misspelling of, along with
+    /// their payloads, best first, using the default SuggestConfig.
+    ///
+    /// Suggestions are the keys within a few edits of the word (see 'find_fuzzy'), where typos
+    /// hitting a neighbouring key and swapping 2 chars count as cheap edits. If the trie has an
+    /// Aggregate, the score it gives a payload is taken as the frequency of its key, and more
+    /// frequent keys rank ahead of rarer keys which are slightly closer to the word.
+    pub fn suggest(&self, word: &str, limit: usize) -> Vec<(String, T)> {
+        self.suggest_with(word, limit, &SuggestConfig::default())
+    }
+
+    /// Same as 'suggest', with the given config.
+    ///
+    /// Every suggestion is ranked by its distance to the word, less 'frequency_weight' times the log
+    /// of its frequency, so that a key 10 times as frequent as another is worth 'frequency_weight'
+    /// edits. Ties are broken in favor of the smaller key.
+    pub fn suggest_with(&self, word: &str, limit: usize, config: &SuggestConfig) -> Vec<(String, T)> {
+        let word: Vec<char> = self.normalize(word).chars().collect();
+        let max_distance = config.max_distance(word.len());
+        let chars = self.grammar.seq();
+
+        let row: Vec<f64> = (0..=word.len()).map(|i| i as f64).collect();
+
+        let mut found = vec![];
+        self._suggest(&self.root, &word, None, &row, None, max_distance, config, &chars, &mut String::new(), &mut found);
+
+        let mut ranked: Vec<(f64, String, T)> = found.into_iter()
+            .map(|(key, payload, distance)| {
+                let frequency = self.aggregate.as_ref().map_or(0.0, |aggregate| aggregate.score(&payload));
+                (distance - config.frequency_weight * frequency.max(0.0).ln_1p() / LN_10, key, payload)
+            })
+            .collect();
+
+        ranked.sort_by(|a, b| a.0.total_cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+        ranked.into_iter().take(limit).map(|(_, key, payload)| (key, payload)).collect()
+    }
+
+    /// Returns all keys matching the given pattern along with their payloads, in grammar order. In
+    /// the pattern, '?' matches any single char and '*' matches any sequence of chars (including
+    /// none), while every other char matches itself.
+    pub fn find_pattern(&self, pattern: &str) -> Vec<(String, T)> {
+        let mut tokens = vec![];
+
+        for c in self.normalize(pattern).chars() {
+            let token = match c {
+                '?' => PatternToken::AnyChar,
+                '*' => PatternToken::AnySeq,
+                c => match self.grammar.idx(c) {
+                    // A char outside of the grammar can't be part of any key.
+                    None => return vec![],
+                    Some(idx) => PatternToken::Char(idx)
+                }
+            };
+
+            // Consecutive '*' match nothing more than a single one does.
+            if !(token == PatternToken::AnySeq && tokens.last() == Some(&PatternToken::AnySeq)) {
+                tokens.push(token);
+            }
+        }
+
+        let mut result = vec![];
+        let mut visited = HashSet::new();
+        self._find_pattern(&self.root, &tokens, &self.grammar.seq(), &mut String::new(), &mut visited, &mut result);
+
+        // Branching on '*' visits keys out of order.
+        result.sort_by(|a, b| a.0.cmp(&b.0));
+        result
+    }
+
+    /// Builds a new trie from the keys of both tries, as chosen by 'op'.
+    fn combine(&self, other: &Self, op: SetOp, resolve: &dyn Fn(T, T) -> T) -> Result<Self, TrieError> {
+        if self.grammar != other.grammar {
+            return Err(TrieError::GrammarMismatch);
+        }
+
+        let mut arena = GenerationalArena::<TrieNode<T>>::new();
+        let root_node = self._combine(other, Some(&self.root), Some(&other.root), op, resolve, &mut arena)
+            .unwrap_or_else(|| TrieNode::new(arena.get_new_id(), None, self.grammar.seq().len()));
+
+        let root = root_node.id;
+        let size = root_node.count;
+        arena.add_node(root_node).expect("failed to add root to tree!");
+
+        let trie = Self {
+            arena,
+            grammar: self.grammar.clone(),
+            normalizer: self.normalizer.clone(),
+            aggregate: self.aggregate.clone(),
+            index: self.index.as_ref().map(|index| index.empty_like()),
+            root,
+            size: AtomicUsize::new(size)
+        };
+        trie._refresh_subtree(&root);
+        trie.rebuild_index();
+        Ok(trie)
+    }
+
+    /// Builds the node of a new trie which combines the given nodes of this trie and 'other',
+    /// adding the nodes below it to 'arena'. Returns None if the node would hold no keys.
+    fn _combine(
+        &self,
+        other: &Self,
+        ours: Option<&Id>,
+        theirs: Option<&Id>,
+        op: SetOp,
+        resolve: &dyn Fn(T, T) -> T,
+        arena: &mut GenerationalArena<TrieNode<T>>
+    ) -> Option<TrieNode<T>> {
+        // Skip subtrees which can't hold any key of the result.
+        match (op, ours, theirs) {
+            (_, None, None) => return None,
+            (SetOp::Intersection, None, _) | (SetOp::Intersection, _, None) => return None,
+            (SetOp::Difference, None, _) => return None,
+            _ => {}
+        }
+
+        // The payloads and children are copied out, so that no lock is held while descending.
+        let parts = |trie: &Self, node_id: Option<&Id>| match node_id {
+            None => (None, Children::default(), 0),
+            Some(id) => {
+                let node_ref = trie.arena.get_node(id).expect("node doesnt exist!");
+                let node = node_ref.read().unwrap();
+                (node.payload.clone(), node.children.clone(), node.arity)
+            }
+        };
+        let (our_payload, our_children, our_arity) = parts(self, ours);
+        let (their_payload, their_children, their_arity) = parts(other, theirs);
+
+        let payload = match (op, our_payload, their_payload) {
+            (SetOp::Difference, _, Some(_)) => None,
+            (SetOp::Intersection, None, _) | (SetOp::Intersection, _, None) => None,
+            (_, Some(a), Some(b)) => Some(resolve(a, b)),
+            (_, a, b) => a.or(b)
+        };
+
+        let mut node = TrieNode::new(Id::default(), payload, our_arity.max(their_arity));
+        node.count = node.payload.is_some() as usize;
+
+        let mut indices: Vec<usize> = our_children.iter().chain(their_children.iter()).map(|(idx, _)| idx).collect();
+        indices.sort_unstable();
+        indices.dedup();
+
+        for idx in indices {
+            let our_child = our_children.get(idx);
+            let their_child = their_children.get(idx);
+
+            if let Some(child) = self._combine(other, our_child.as_ref(), their_child.as_ref(), op, resolve, arena) {
+                node.count += child.count;
+                node.set_child(idx, Some(child.id));
+                arena.add_node(child).expect("could not add node!");
+            }
+        }
+
+        if node.count == 0 {
+            return None;
+        }
+
+        // The id is only issued once the node is known to be kept, so no slot is left reserved.
+        node.id = arena.get_new_id();
+        Some(node)
+    }
+
+    /// Appends every key in the subtree rooted at the given node to 'out', where 'key' is the
+    /// sequence of chars leading to the node.
+    fn _collect(&self, node_id: &Id, seq: &[char], key: &mut String, out: &mut Vec<(String, T)>) {
+        let node_ref = self.arena.get_node(node_id).expect("node doesnt exist!");
+        let node = node_ref.read().unwrap();
+
+        if let Some(payload) = &node.payload {
+            out.push((key.clone(), payload.clone()));
+        }
+
+        for (idx, child_id) in node.children.iter() {
+            key.push(seq[idx]);
+            self._collect(&child_id, seq, key, out);
+            key.pop();
+        }
+    }
+
+    /// Adds the node along with its subtree to 'lcrs', where 'c' is the char leading to it, and
+    /// returns its index.
+    fn _to_lcrs(&self, node_id: &Id, c: Option<char>, chars: &[char], lcrs: &mut LcrsTree<(Option<char>, Option<T>)>) -> usize {
+        let node_ref = self.arena.get_node(node_id).expect("node doesnt exist!");
+        let node = node_ref.read().unwrap();
+
+        let idx = lcrs.nodes.len();
+        lcrs.nodes.push(LcrsNode { value: (c, node.payload.clone()), first_child: None, next_sibling: None });
+
+        let mut prev: Option<usize> = None;
+        for (child_idx, child_id) in node.children.iter() {
+            let child = self._to_lcrs(&child_id, Some(chars[child_idx]), chars, lcrs);
+            match prev {
+                None => lcrs.nodes[idx].first_child = Some(child),
+                Some(prev) => lcrs.nodes[prev].next_sibling = Some(child)
+            }
+            prev = Some(child);
+        }
+
+        idx
+    }
+
+    /// Same as '_collect', but only keys whose payloads satisfy 'pred' are appended, and subtrees
+    /// are skipped unless 'descend' returns true for them.
+    fn _collect_matching<F, G>(
+        &self,
+        node_id: &Id,
+        seq: &[char],
+        key: &mut String,
+        pred: &F,
+        descend: &G,
+        out: &mut Vec<(String, T)>
+    )
+    where
+        F: Fn(&T) -> bool,
+        G: Fn(&str, usize) -> bool
+    {
+        let node_ref = self.arena.get_node(node_id).expect("node doesnt exist!");
+        let node = node_ref.read().unwrap();
+
+        if node.count == 0 || !descend(key, node.count) {
+            return;
+        }
+
+        if let Some(payload) = node.payload.as_ref().filter(|payload| pred(payload)) {
+            out.push((key.clone(), payload.clone()));
+        }
+
+        for (idx, child_id) in node.children.iter() {
+            key.push(seq[idx]);
+            self._collect_matching(&child_id, seq, key, pred, descend, out);
+            key.pop();
+        }
+    }
+
+    /// Visits the subtree rooted at the given node, which is reached by 'key', matching it against
+    /// the remaining tokens of the pattern.
+    fn _find_pattern(
+        &self,
+        node_id: &Id,
+        tokens: &[PatternToken],
+        chars: &[char],
+        key: &mut String,
+        visited: &mut HashSet<(Id, usize)>,
+        out: &mut Vec<(String, T)>
+    ) {
+        // A '*' can reach the same node with the same tokens left in several ways (e.g. "*a*"
+        // against "aa"), but every such visit would find the same keys.
+        if !visited.insert((*node_id, tokens.len())) {
+            return;
+        }
+
+        let node_ref = self.arena.get_node(node_id).expect("node doesnt exist!");
+        let node = node_ref.read().unwrap();
+
+        let (token, rest) = match tokens.split_first() {
+            None => {
+                if let Some(payload) = &node.payload {
+                    out.push((key.clone(), payload.clone()));
+                }
+                return;
+            }
+            Some(split) => split
+        };
+
+        // A '*' can match nothing at all, which leaves us at this node.
+        if *token == PatternToken::AnySeq {
+            self._find_pattern(node_id, rest, chars, key, visited, out);
+        }
+
+        for (idx, child_id) in node.children.iter() {
+            // After consuming a char, a '*' stays in place to possibly consume more.
+            let next = match token {
+                PatternToken::Char(c) if *c != idx => continue,
+                PatternToken::Char(_) | PatternToken::AnyChar => rest,
+                PatternToken::AnySeq => tokens
+            };
+
+            key.push(chars[idx]);
+            self._find_pattern(&child_id, next, chars, key, visited, out);
+            key.pop();
+        }
+    }
+
+    /// Visits the subtree rooted at the given node, where 'row' holds the edit distances between
+    /// 'key' (the path to the node) and each prefix of 'seq'.
+    #[allow(clippy::too_many_arguments)]
+    fn _find_fuzzy(
+        &self,
+        node_id: &Id,
+        seq: &[usize],
+        row: &[usize],
+        max_distance: usize,
+        chars: &[char],
+        key: &mut String,
+        out: &mut Vec<(String, T, usize)>
+    ) {
+        let node_ref = self.arena.get_node(node_id).expect("node doesnt exist!");
+        let node = node_ref.read().unwrap();
+
+        let distance = row[seq.len()];
+        if let Some(payload) = &node.payload {
+            if distance <= max_distance {
+                out.push((key.clone(), payload.clone(), distance));
+            }
+        }
+
+        for (idx, child_id) in node.children.iter() {
+            let mut next_row = vec![row[0] + 1; row.len()];
+            for i in 1..row.len() {
+                let substitution = row[i - 1] + usize::from(seq[i - 1] != idx);
+                next_row[i] = substitution.min(row[i] + 1).min(next_row[i - 1] + 1);
+            }
+
+            // Distances never shrink further down the tree, so if every entry of the row is
+            // already too large, nothing in this subtree can match.
+            if next_row.iter().min().is_some_and(|d| *d <= max_distance) {
+                key.push(chars[idx]);
+                self._find_fuzzy(&child_id, seq, &next_row, max_distance, chars, key, out);
+                key.pop();
+            }
+        }
+    }
+
+    /// Same as '_find_fuzzy', but the edit distance is weighted by 'config', and swapping 2
+    /// neighbouring chars counts as a single edit. Computing a row takes the one before it as well,
+    /// along with the char leading to the node.
+    #[allow(clippy::too_many_arguments)]
+    fn _suggest(
+        &self,
+        node_id: &Id,
+        word: &[char],
+        prev_row: Option<&[f64]>,
+        row: &[f64],
+        last: Option<char>,
+        max_distance: f64,
+        config: &SuggestConfig,
+        chars: &[char],
+        key: &mut String,
+        out: &mut Vec<(String, T, f64)>
+    ) {
+        let node_ref = self.arena.get_node(node_id).expect("node doesnt exist!");
+        let node = node_ref.read().unwrap();
+
+        let distance = row[word.len()];
+        if let Some(payload) = &node.payload {
+            if distance <= max_distance {
+                out.push((key.clone(), payload.clone(), distance));
+            }
+        }
+
+        for (idx, child_id) in node.children.iter() {
+            let c = chars[idx];
+
+            let mut next_row = vec![row[0] + 1.0; row.len()];
+            for i in 1..row.len() {
+                let substitution = row[i - 1] + config.keyboard.substitution_cost(word[i - 1], c);
+                next_row[i] = substitution.min(row[i] + 1.0).min(next_row[i - 1] + 1.0);
+
+                if let (Some(prev_row), Some(last)) = (prev_row, last) {
+                    if i > 1 && word[i - 1] == last && word[i - 2] == c && last != c {
+                        next_row[i] = next_row[i].min(prev_row[i - 2] + 1.0);
+                    }
+                }
+            }
+
+            // Like in '_find_fuzzy', distances never shrink further down the tree, swaps included.
+            if next_row.iter().any(|d| *d <= max_distance) {
+                key.push(c);
+                self._suggest(&child_id, word, Some(row), &next_row, Some(c), max_distance, config, chars, key, out);
+                key.pop();
+            }
         }
     }
 
@@ -294,10 +2229,10 @@ impl<T: Default + Debug + Clone + Send + Sync> Trie<T> {
                     // Otherwise, we'll need to traverse deeper in the tree by recursively calling
                     // _find(...) on the correct child.
                     Some((next_idx, remainder)) => {
-                        match node_ref.read().unwrap().children[*next_idx] {
+                        match node_ref.read().unwrap().child(*next_idx) {
                             None => { None }
                             Some(id) => {
-                                self._find(&remainder[..], &id)
+                                self._find(remainder, &id)
                             }
                         }
                     }
@@ -306,3 +2241,227 @@ impl<T: Default + Debug + Clone + Send + Sync> Trie<T> {
         }
     }
 }
+
+impl<T: Debug + Send + Sync> ToDot for Trie<T> {
+    fn to_dot(&self) -> String {
+        let chars = self.grammar.seq();
+        let mut dot = DotWriter::new("Trie");
+        let mut stack = vec![self.root];
+
+        while let Some(id) = stack.pop() {
+            let node_ref = self.arena.get_node(&id).expect("node doesnt exist!");
+            let node = node_ref.read().unwrap();
+
+            // Terminal nodes are drawn with a double border, along with their payload.
+            match &node.payload {
+                None => dot.node(&id, "", "shape=circle"),
+                Some(payload) => dot.node(&id, &format!("{:?}", payload), "shape=doublecircle")
+            }
+
+            for (idx, child_id) in node.children.iter() {
+                dot.edge(&id, &child_id, &chars[idx].to_string(), "");
+                stack.push(child_id);
+            }
+        }
+
+        dot.finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Debug + Send + Sync> Trie<T> {
+
+    /// Writes the Trie to 'w' as an indented tree, with a line per node holding the char leading to
+    /// it along with its payload, if any. Children are written in grammar order.
+    pub fn dump(&self, mut w: impl Write) -> io::Result<()> {
+        let chars = self.grammar.seq();
+
+        // The root holds the payload of the empty key, if it is stored.
+        let root_ref = self.arena.get_node(&self.root).expect("node doesnt exist!");
+        match &root_ref.read().unwrap().payload {
+            None => writeln!(w, "(root)")?,
+            Some(payload) => writeln!(w, "(root) = {:?}", payload)?
+        }
+
+        self._dump(&self.root, 1, &chars, &mut w)
+    }
+
+    fn _dump(&self, node_id: &Id, depth: usize, chars: &[char], w: &mut impl Write) -> io::Result<()> {
+        let node_ref = self.arena.get_node(node_id).expect("node doesnt exist!");
+        let node = node_ref.read().unwrap();
+
+        for (idx, child_id) in node.children.iter() {
+            let child_ref = self.arena.get_node(&child_id).expect("node doesnt exist!");
+            match &child_ref.read().unwrap().payload {
+                None => writeln!(w, "{:indent$}{}", "", chars[idx], indent = 2 * depth)?,
+                Some(payload) => writeln!(w, "{:indent$}{} = {:?}", "", chars[idx], payload, indent = 2 * depth)?
+            }
+
+            self._dump(&child_id, depth + 1, chars, w)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: Send + Sync> Metrics for Trie<T> {
+    fn depth(&self) -> usize {
+        let mut max_depth = 0;
+        let mut stack = vec![(self.root, 0)];
+
+        while let Some((node_id, depth)) = stack.pop() {
+            let node_ref = self.arena.get_node(&node_id).expect("node doesnt exist!");
+            let node = node_ref.read().unwrap();
+
+            max_depth = max_depth.max(depth);
+            stack.extend(node.children.ids().map(|id| (id, depth + 1)));
+        }
+
+        max_depth
+    }
+
+    fn node_count(&self) -> usize {
+        self.arena.len()
+    }
+
+    fn avg_branching_factor(&self) -> f64 {
+        let (mut parents, mut branches) = (0, 0);
+        let mut stack = vec![self.root];
+
+        while let Some(node_id) = stack.pop() {
+            let node_ref = self.arena.get_node(&node_id).expect("node doesnt exist!");
+            let node = node_ref.read().unwrap();
+
+            if !node.children.is_empty() {
+                parents += 1;
+                branches += node.children.len();
+            }
+            stack.extend(node.children.ids());
+        }
+
+        match parents {
+            0 => 0.0,
+            _ => branches as f64 / parents as f64
+        }
+    }
+
+    fn memory_bytes_estimate(&self) -> usize {
+        self.memory_stats().approx_bytes
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde::de::Error;
+
+    use super::*;
+
+    /// The serialized form of a node, where children are given as (grammar index, node index)
+    /// pairs into the list of nodes.
+    #[derive(Serialize, Deserialize)]
+    struct NodeRepr<T> {
+        payload: Option<T>,
+        children: Vec<(usize, usize)>
+    }
+
+    /// The serialized form of a Trie, whose nodes are stored in pre-order starting at the root.
+    #[derive(Serialize, Deserialize)]
+    struct TrieRepr<T> {
+        grammar: Grammar,
+        nodes: Vec<NodeRepr<T>>
+    }
+
+    impl<T: Clone + Send + Sync> Trie<T> {
+        /// Appends the subtree rooted at the given node to 'out' in pre-order, returning the index
+        /// of the node.
+        fn flatten(&self, node_id: &Id, out: &mut Vec<NodeRepr<T>>) -> usize {
+            let node_ref = self.arena.get_node(node_id).expect("node doesnt exist!");
+            let node = node_ref.read().unwrap();
+
+            let idx = out.len();
+            out.push(NodeRepr { payload: node.payload.clone(), children: vec![] });
+
+            for (c, child_id) in node.children.iter() {
+                let child_idx = self.flatten(&child_id, out);
+                out[idx].children.push((c, child_idx));
+            }
+
+            idx
+        }
+    }
+
+    impl<T> Serialize for Trie<T>
+        where T: Serialize + Clone + Send + Sync
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut nodes = vec![];
+            self.flatten(&self.root, &mut nodes);
+
+            TrieRepr { grammar: self.grammar.clone(), nodes }.serialize(serializer)
+        }
+    }
+
+    impl<'de, T> Deserialize<'de> for Trie<T>
+        where T: Deserialize<'de> + Send + Sync
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let repr = TrieRepr::<T>::deserialize(deserializer)?;
+            if repr.nodes.is_empty() {
+                return Err(D::Error::custom("trie is missing its root node"));
+            }
+
+            let arity = repr.grammar.seq().len();
+
+            let mut arena = GenerationalArena::<TrieNode<T>>::new();
+            let ids: Vec<Id> = repr.nodes.iter().map(|_| arena.get_new_id()).collect();
+
+            // Nodes are stored in pre-order, so every child must come after its parent and have
+            // exactly one parent; this rules out cycles and shared subtrees.
+            let mut has_parent = vec![false; ids.len()];
+            let mut nodes = Vec::with_capacity(ids.len());
+
+            for (idx, node) in repr.nodes.into_iter().enumerate() {
+                let mut trie_node = TrieNode::<T>::new(ids[idx], node.payload, arity);
+                let mut children = vec![];
+
+                for (c, child) in node.children {
+                    if c >= arity || child <= idx || child >= ids.len() || has_parent[child] {
+                        return Err(D::Error::custom("trie contains an invalid child link"));
+                    }
+
+                    has_parent[child] = true;
+                    trie_node.set_child(c, Some(ids[child]));
+                    children.push(child);
+                }
+
+                nodes.push((trie_node, children));
+            }
+
+            // --
+            // Children always come after their parents, so walking backwards tallies every
+            // subtree before the node which owns it.
+            let mut counts = vec![0; nodes.len()];
+            for (idx, (node, children)) in nodes.iter().enumerate().rev() {
+                counts[idx] = usize::from(node.is_terminal())
+                    + children.iter().map(|child| counts[*child]).sum::<usize>();
+            }
+
+            let size = counts[0];
+            for ((mut node, _), count) in nodes.into_iter().zip(counts) {
+                node.count = count;
+                arena.add_node(node).map_err(D::Error::custom)?;
+            }
+
+            Ok(Self {
+                arena,
+                grammar: repr.grammar,
+                normalizer: None,
+                aggregate: None,
+                index: None,
+                root: ids[0],
+                size: AtomicUsize::new(size)
+            })
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use alloc::vec::Vec;
+
+use crate::trie::error::TrieError;
+use crate::trie::trie::*;
+
+/// The number of keys a TrieWriter buffers before it writes them to the trie.
+pub const DEFAULT_BATCH_SIZE: usize = 65536;
+
+/// Buffers keys on their way into a Trie and writes them in batches, which is faster than
+/// inserting them one by one when loading lots of keys into a trie that is already in use.
+///
+/// Every batch is sorted before it is written, so keys sharing a prefix are written together and
+/// the nodes along the prefix are only looked up once. Keys which mostly consist of new nodes gain
+/// the least, since allocating the nodes costs the same either way, see 'benches/bulk_ingest.rs'.
+/// Keys only show up in the trie once their batch is written, which happens when the buffer is
+/// full, on 'flush', and when the writer is dropped.
+///
+/// Like 'Trie::insert_or_update', a key which is already stored gets its payload replaced, and so
+/// does a key written twice, where the later payload wins.
+pub struct TrieWriter<'a, T: Send + Sync> {
+    trie: &'a mut Trie<T>,
+    batch: Vec<(Vec<usize>, T)>,
+    batch_size: usize
+}
+
+impl<'a, T: Send + Sync> TrieWriter<'a, T> {
+
+    /// Constructs a new writer into the given trie, which buffers DEFAULT_BATCH_SIZE keys.
+    pub fn new(trie: &'a mut Trie<T>) -> Self {
+        Self::with_batch_size(trie, DEFAULT_BATCH_SIZE)
+    }
+
+    /// Constructs a new writer into the given trie, which buffers up to 'batch_size' keys.
+    pub fn with_batch_size(trie: &'a mut Trie<T>, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch size must be at least 1");
+
+        Self {
+            trie,
+            batch: Vec::with_capacity(batch_size),
+            batch_size
+        }
+    }
+
+    /// Returns the number of keys waiting to be written.
+    pub fn pending(&self) -> usize {
+        self.batch.len()
+    }
+
+    /// Buffers 'seq' along with its payload, writing the batch once it is full. Returns an error
+    /// right away if 'seq' contains a char outside of the grammar.
+    pub fn insert(&mut self, seq: &str, t: T) -> Result<(), TrieError> {
+        let seq = self.trie.preprocess_seq(seq)?;
+        self.batch.push((seq, t));
+
+        if self.batch.len() >= self.batch_size {
+            self.flush();
+        }
+        Ok(())
+    }
+
+    /// Writes every buffered key to the trie.
+    pub fn flush(&mut self) {
+        let mut batch = core::mem::take(&mut self.batch);
+
+        // The sort is stable, so the last of any repeated keys is the one which is kept.
+        batch.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut entries: Vec<(Vec<usize>, T)> = Vec::with_capacity(batch.len());
+        for entry in batch {
+            match entries.last_mut() {
+                Some(last) if last.0 == entry.0 => *last = entry,
+                _ => entries.push(entry)
+            }
+        }
+
+        self.trie.insert_sorted(entries);
+        self.batch.reserve(self.batch_size);
+    }
+}
+
+impl<T: Send + Sync> Drop for TrieWriter<'_, T> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
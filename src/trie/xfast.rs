@@ -0,0 +1,200 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::collections::HashMap;
+
+/// The number of bits in a key, i.e. the depth of the trie.
+const BITS: usize = 64;
+
+/// The neighbors of a key in ascending order.
+#[derive(Debug, Copy, Clone, Default)]
+struct Link {
+    prev: Option<u64>,
+    next: Option<u64>
+}
+
+/// This class represents an x-fast trie, a set of u64 keys which finds the successor or
+/// predecessor of any u64 in O(log log U), where U = 2^64.
+///
+/// The trie is a binary trie over the bits of the keys, where the nodes of every level are kept in
+/// a hash map keyed by their prefix. Since the prefixes of a key which are in the trie are exactly
+/// its shortest ones, the longest one can be found with a binary search over the 64 levels. Every
+/// node knows the smallest and largest key below it, and the keys are linked up in ascending order,
+/// which is all that is needed to go from that prefix to the neighbors of the key.
+///
+/// Inserting and deleting keys touches every level, which takes O(log U), and every key takes up a
+/// node on every level. 'YFastTrie' brings both down by only storing some of the keys in here.
+#[derive(Debug, Clone)]
+pub struct XFastTrie {
+    /// The smallest and largest key below every prefix, for each prefix length below 64.
+    levels: Vec<HashMap<u64, (u64, u64)>>,
+
+    leaves: HashMap<u64, Link>
+}
+
+impl Default for XFastTrie {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl XFastTrie {
+
+    /// Constructs a new empty XFastTrie
+    pub fn new() -> Self {
+        Self {
+            levels: vec![HashMap::new(); BITS],
+            leaves: HashMap::new()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, key: u64) -> bool {
+        self.leaves.contains_key(&key)
+    }
+
+    /// Returns the smallest key, if any.
+    pub fn min(&self) -> Option<u64> {
+        self.levels[0].get(&0).map(|(min, _)| *min)
+    }
+
+    /// Returns the largest key, if any.
+    pub fn max(&self) -> Option<u64> {
+        self.levels[0].get(&0).map(|(_, max)| *max)
+    }
+
+    /// Returns the smallest key which is larger than 'key', in O(log log U).
+    pub fn successor(&self, key: u64) -> Option<u64> {
+        match self.leaves.get(&key) {
+            Some(link) => link.next,
+            None => self.neighbors(key).1
+        }
+    }
+
+    /// Returns the largest key which is smaller than 'key', in O(log log U).
+    pub fn predecessor(&self, key: u64) -> Option<u64> {
+        match self.leaves.get(&key) {
+            Some(link) => link.prev,
+            None => self.neighbors(key).0
+        }
+    }
+
+    /// Returns all keys, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        core::iter::successors(self.min(), |key| self.leaves[key].next)
+    }
+
+    /// Inserts the key, returning false if it already exists.
+    pub fn insert(&mut self, key: u64) -> bool {
+        if self.contains(key) {
+            return false;
+        }
+
+        // --
+        // Link the key in between its neighbors.
+        let (prev, next) = self.neighbors(key);
+        if let Some(prev) = prev {
+            self.leaves.get_mut(&prev).unwrap().next = Some(key);
+        }
+        if let Some(next) = next {
+            self.leaves.get_mut(&next).unwrap().prev = Some(key);
+        }
+        self.leaves.insert(key, Link { prev, next });
+
+        // --
+        // Then add (or widen) its prefix on every level.
+        for (len, level) in self.levels.iter_mut().enumerate() {
+            level.entry(prefix(key, len))
+                .and_modify(|(min, max)| {
+                    *min = (*min).min(key);
+                    *max = (*max).max(key);
+                })
+                .or_insert((key, key));
+        }
+
+        true
+    }
+
+    /// Removes the key, returning false if it didn't exist.
+    pub fn delete(&mut self, key: u64) -> bool {
+        let link = match self.leaves.remove(&key) {
+            None => return false,
+            Some(link) => link
+        };
+
+        if let Some(prev) = link.prev {
+            self.leaves.get_mut(&prev).unwrap().next = link.next;
+        }
+        if let Some(next) = link.next {
+            self.leaves.get_mut(&next).unwrap().prev = link.prev;
+        }
+
+        // --
+        // Recompute the prefixes from the bottom up, each from the (at most 2) below it.
+        for len in (0..BITS).rev() {
+            let p = prefix(key, len);
+            let bounds = [p << 1, p << 1 | 1].iter()
+                .filter_map(|child| self.bounds(*child, len + 1))
+                .reduce(|(min, max), (child_min, child_max)| (min.min(child_min), max.max(child_max)));
+
+            match bounds {
+                None => self.levels[len].remove(&p),
+                Some(bounds) => self.levels[len].insert(p, bounds)
+            };
+        }
+
+        true
+    }
+
+    /// Returns the smallest and largest key below the given prefix of the given length.
+    fn bounds(&self, prefix: u64, len: usize) -> Option<(u64, u64)> {
+        if len == BITS {
+            self.leaves.contains_key(&prefix).then_some((prefix, prefix))
+        } else {
+            self.levels[len].get(&prefix).copied()
+        }
+    }
+
+    /// Returns the largest key smaller than 'key' and the smallest key larger than it, where 'key'
+    /// itself must not be in the trie.
+    fn neighbors(&self, key: u64) -> (Option<u64>, Option<u64>) {
+        if self.is_empty() {
+            return (None, None);
+        }
+
+        // --
+        // Binary search for the longest prefix of the key which is in the trie. The root (the
+        // empty prefix) always is, and the key itself isn't.
+        let (mut lo, mut hi) = (0, BITS - 1);
+        while lo < hi {
+            let mid = (lo + hi).div_ceil(2);
+            if self.levels[mid].contains_key(&prefix(key, mid)) {
+                lo = mid;
+            } else {
+                hi = mid - 1;
+            }
+        }
+
+        // --
+        // The subtree where the key would go is empty, so everything below the prefix lies on the
+        // other side of the key.
+        let (min, max) = self.levels[lo][&prefix(key, lo)];
+        if (key >> (BITS - 1 - lo)) & 1 == 1 {
+            (Some(max), self.leaves[&max].next)
+        } else {
+            (self.leaves[&min].prev, Some(min))
+        }
+    }
+}
+
+/// Returns the first 'len' bits of the key.
+fn prefix(key: u64, len: usize) -> u64 {
+    key.checked_shr((BITS - len) as u32).unwrap_or(0)
+}
@@ -0,0 +1,186 @@
+use alloc::collections::BTreeSet;
+use core::ops::Bound;
+
+use crate::collections::HashMap;
+use crate::trie::xfast::XFastTrie;
+
+/// Buckets are split once they hold more than twice this many keys, and merged with a neighbor
+/// once they hold fewer than half of it. This is log U for u64 keys.
+const BUCKET_SIZE: usize = 64;
+
+/// This class represents a y-fast trie, a set of u64 keys which finds the successor or predecessor
+/// of any u64 in O(log log U), where U = 2^64, and inserts and deletes keys in O(log log U)
+/// amortized.
+///
+/// The keys are split up into buckets of around log U consecutive keys, each kept in a small
+/// balanced tree. Only a representative of every bucket is stored in an XFastTrie, which finds the
+/// bucket a key belongs to, so the XFastTrie only takes up O(n) space in total and is only changed
+/// when buckets are split or merged.
+#[derive(Debug, Clone, Default)]
+pub struct YFastTrie {
+    /// The representatives of the buckets, where every bucket holds the keys from its
+    /// representative up to the next one. A representative doesn't have to be a key itself.
+    reps: XFastTrie,
+    buckets: HashMap<u64, BTreeSet<u64>>,
+
+    size: usize
+}
+
+impl YFastTrie {
+
+    /// Constructs a new empty YFastTrie
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, key: u64) -> bool {
+        self.bucket_of(key).is_some_and(|rep| self.buckets[&rep].contains(&key))
+    }
+
+    /// Returns the smallest key, if any.
+    pub fn min(&self) -> Option<u64> {
+        self.reps.min().and_then(|rep| self.buckets[&rep].first().copied())
+    }
+
+    /// Returns the largest key, if any.
+    pub fn max(&self) -> Option<u64> {
+        self.reps.max().and_then(|rep| self.buckets[&rep].last().copied())
+    }
+
+    /// Returns the smallest key which is larger than 'key'.
+    pub fn successor(&self, key: u64) -> Option<u64> {
+        let rep = self.bucket_of(key);
+
+        if let Some(rep) = rep {
+            let mut larger = self.buckets[&rep].range((Bound::Excluded(key), Bound::Unbounded));
+            if let Some(next) = larger.next() {
+                return Some(*next);
+            }
+        }
+
+        // Buckets are never empty, so otherwise it is the first key of the next bucket.
+        let next_rep = match rep {
+            None => self.reps.min(),
+            Some(rep) => self.reps.successor(rep)
+        }?;
+        self.buckets[&next_rep].first().copied()
+    }
+
+    /// Returns the largest key which is smaller than 'key'.
+    pub fn predecessor(&self, key: u64) -> Option<u64> {
+        let rep = self.bucket_of(key)?;
+
+        if let Some(prev) = self.buckets[&rep].range(..key).next_back() {
+            return Some(*prev);
+        }
+
+        let prev_rep = self.reps.predecessor(rep)?;
+        self.buckets[&prev_rep].last().copied()
+    }
+
+    /// Returns all keys, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        self.reps.iter().flat_map(|rep| self.buckets[&rep].iter().copied())
+    }
+
+    /// Inserts the key, returning false if it already exists.
+    pub fn insert(&mut self, key: u64) -> bool {
+        let rep = match (self.bucket_of(key), self.reps.min()) {
+            (Some(rep), _) => rep,
+            // The key is smaller than every representative, so the first bucket is extended down
+            // to it.
+            (None, Some(first)) => {
+                let bucket = self.take_bucket(first);
+                self.put_bucket(key, bucket);
+                key
+            }
+            (None, None) => {
+                self.put_bucket(key, BTreeSet::new());
+                key
+            }
+        };
+
+        if !self.buckets.get_mut(&rep).unwrap().insert(key) {
+            return false;
+        }
+
+        self.size += 1;
+        self.split(rep);
+        true
+    }
+
+    /// Removes the key, returning false if it didn't exist.
+    pub fn delete(&mut self, key: u64) -> bool {
+        let rep = match self.bucket_of(key) {
+            None => return false,
+            Some(rep) => rep
+        };
+
+        let bucket = self.buckets.get_mut(&rep).unwrap();
+        if !bucket.remove(&key) {
+            return false;
+        }
+
+        self.size -= 1;
+        if bucket.len() < BUCKET_SIZE / 2 {
+            self.merge(rep);
+        }
+        true
+    }
+
+    /// Returns the representative of the bucket which 'key' belongs in, or None if 'key' is
+    /// smaller than every representative.
+    fn bucket_of(&self, key: u64) -> Option<u64> {
+        if self.reps.contains(key) {
+            Some(key)
+        } else {
+            self.reps.predecessor(key)
+        }
+    }
+
+    /// Splits the given bucket in half if it has grown too large.
+    fn split(&mut self, rep: u64) {
+        let bucket = self.buckets.get_mut(&rep).unwrap();
+        if bucket.len() <= 2 * BUCKET_SIZE {
+            return;
+        }
+
+        let mid = *bucket.iter().nth(bucket.len() / 2).unwrap();
+        let upper = bucket.split_off(&mid);
+        self.put_bucket(mid, upper);
+    }
+
+    /// Merges the given bucket, which has shrunk too small, into one of its neighbors. The merged
+    /// bucket is split again if it ends up too large.
+    fn merge(&mut self, rep: u64) {
+        if let Some(next) = self.reps.successor(rep) {
+            let mut upper = self.take_bucket(next);
+            self.buckets.get_mut(&rep).unwrap().append(&mut upper);
+            self.split(rep);
+        } else if let Some(prev) = self.reps.predecessor(rep) {
+            let mut upper = self.take_bucket(rep);
+            self.buckets.get_mut(&prev).unwrap().append(&mut upper);
+            self.split(prev);
+        } else if self.buckets[&rep].is_empty() {
+            self.take_bucket(rep);
+        }
+    }
+
+    fn take_bucket(&mut self, rep: u64) -> BTreeSet<u64> {
+        self.reps.delete(rep);
+        self.buckets.remove(&rep).expect("bucket doesnt exist!")
+    }
+
+    fn put_bucket(&mut self, rep: u64, bucket: BTreeSet<u64>) {
+        self.reps.insert(rep);
+        self.buckets.insert(rep, bucket);
+    }
+}
@@ -0,0 +1,112 @@
+use alloc::{format, vec};
+use alloc::string::{String, ToString};
+
+use crate::arena::GenerationalId;
+
+/// Trees which can be rendered as a Graphviz graph in the DOT language, e.g. with
+/// `dot -Tsvg tree.dot -o tree.svg`.
+pub trait ToDot {
+    fn to_dot(&self) -> String;
+}
+
+/// Builds up a directed graph in the DOT language, using the arena Ids of the nodes as names.
+pub struct DotWriter {
+    out: String
+}
+
+impl DotWriter {
+    pub fn new(name: &str) -> Self {
+        Self {
+            out: format!("digraph {} {{\n    node [fontname=\"monospace\"];\n", name)
+        }
+    }
+
+    /// Adds a node with the given label, along with any extra attributes (e.g. "shape=box").
+    pub fn node(&mut self, id: &GenerationalId, label: &str, attrs: &str) {
+        let attrs = if attrs.is_empty() { String::new() } else { format!(", {}", attrs) };
+        self.out.push_str(&format!("    {} [label=\"{}\"{}];\n", node_name(id), escape(label), attrs));
+    }
+
+    /// Adds an edge between 2 nodes, along with any extra attributes.
+    pub fn edge(&mut self, from: &GenerationalId, to: &GenerationalId, label: &str, attrs: &str) {
+        let mut all = vec![];
+        if !label.is_empty() {
+            all.push(format!("label=\"{}\"", escape(label)));
+        }
+        if !attrs.is_empty() {
+            all.push(attrs.to_string());
+        }
+
+        let attrs = if all.is_empty() { String::new() } else { format!(" [{}]", all.join(", ")) };
+        self.out.push_str(&format!("    {} -> {}{};\n", node_name(from), node_name(to), attrs));
+    }
+
+    pub fn finish(mut self) -> String {
+        self.out.push_str("}\n");
+        self.out
+    }
+}
+
+fn node_name(id: &GenerationalId) -> String {
+    format!("n{}_{}", id.index, id.generation)
+}
+
+/// Escapes the chars which can't appear as is within a quoted DOT string.
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bst::rbtree::*;
+    use crate::spatial::quadtree::point_quadtree::*;
+    use crate::spatial::quadtree::prelude::*;
+    use crate::trie::grammar::*;
+    use crate::trie::trie::*;
+    use crate::tree::tree::*;
+    use crate::visualize::*;
+
+    #[test]
+    fn test_to_dot() {
+        let mut trie = Trie::<i32>::new(Grammar::default());
+        assert!(trie.insert("ab", 1).is_ok());
+
+        let dot = trie.to_dot();
+        assert!(dot.starts_with("digraph Trie {"));
+        assert!(dot.ends_with("}\n"));
+        assert_eq!(dot.matches(" -> ").count(), 2);
+        assert_eq!(dot.matches("doublecircle").count(), 1);
+        assert!(dot.contains("[label=\"a\"]"));
+
+        let bbox = BBox2D {
+            min: Vec2::from([0.0, 0.0]),
+            max: Vec2::from([10.0, 10.0])
+        };
+        let mut tree = PointQuadtree::<&str>::new(&bbox);
+        assert!(tree.insert(&Vec2::from([5.0, 5.0]), "center").is_ok());
+        assert!(tree.insert(&Vec2::from([1.0, 2.0]), "quote\"d").is_ok());
+
+        let dot = tree.to_dot();
+        assert!(dot.contains("(0, 0) - (10, 10)"));
+        assert!(dot.contains(r#"(1, 2): \"quote\\\"d\""#));
+        assert_eq!(dot.matches(" -> ").count(), 4);
+
+        let mut tree = RbTree::<i32, ()>::new();
+        for i in 0..3 {
+            tree.insert(i, ());
+        }
+        assert_eq!(tree.to_dot().matches(" -> ").count(), 2);
+
+        let mut tree = Tree::<&str>::new();
+        let root = tree.add_root("root");
+        for child in ["a", "b", "c"] {
+            tree.add_child(&root, child).unwrap();
+        }
+        tree.add_root("other");
+
+        let dot = tree.to_dot();
+        assert!(dot.starts_with("digraph Tree {"));
+        assert_eq!(dot.matches(" -> ").count(), 3);
+        assert!(dot.contains(r#"\"other\""#));
+    }
+}